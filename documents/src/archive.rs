@@ -0,0 +1,307 @@
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use crate::email;
+use crate::table::is_table;
+
+/// The character that ends the path to an archive in the path to one of its
+/// members, like `docs.zip!/guide/ch1.txt`.
+pub const MEMBER_SEPARATOR: char = '!';
+
+/// The endings of the names of the archives whose members are documents,
+/// including mailboxes, whose members are their messages.
+const ARCHIVE_SUFFIXES: [&str; 5] = [".zip", ".tar", ".tar.gz", ".tgz", ".mbox"];
+
+/// A reader of the contents of a document, whether it is a file or a member
+/// of an archive.
+pub(crate) trait Source: Read + Seek {}
+
+impl<T: Read + Seek> Source for T {}
+
+/// Checks if a file is an archive whose members can be documents, from the
+/// ending of its name: `.zip`, `.tar`, `.tar.gz`, `.tgz`, or `.mbox` for a
+/// mailbox.
+pub fn is_archive(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            let name = name.to_ascii_lowercase();
+            ARCHIVE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        })
+}
+
+/// Checks if the members of an archive can be read. Mailboxes can always be
+/// read, while zip and tar archives need the `archives` feature.
+pub fn is_supported(path: impl AsRef<Path>) -> bool {
+    is_archive(&path) && (is_mbox(path.as_ref()) || cfg!(feature = "archives"))
+}
+
+/// Returns the path to a member of an archive, which is the path to the
+/// archive, followed by [`MEMBER_SEPARATOR`] and the path to the member in
+/// the archive.
+pub fn member_path(archive: impl AsRef<Path>, member: impl AsRef<Path>) -> PathBuf {
+    let mut path = archive.as_ref().as_os_str().to_owned();
+    path.push(MEMBER_SEPARATOR.to_string());
+    PathBuf::from(path).join(member)
+}
+
+/// Splits the path to a member of an archive into the path to the archive
+/// and the path to the member in the archive, or returns `None` if the path
+/// is not to a member of an archive. The rows of a table, like
+/// `sales.csv!/12`, are members of it too.
+pub fn split_member_path(path: impl AsRef<Path>) -> Option<(PathBuf, PathBuf)> {
+    let mut archive = PathBuf::new();
+    let mut components = path.as_ref().components();
+    while let Some(component) = components.next() {
+        let name = match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        };
+        if let Some(name) = name.and_then(|name| name.strip_suffix(MEMBER_SEPARATOR)) {
+            if is_archive(name) || is_table(name) {
+                archive.push(name);
+                return Some((archive, components.as_path().to_path_buf()));
+            }
+        }
+        archive.push(component);
+    }
+    None
+}
+
+/// Returns the path to the file that a document is read from, which is the
+/// archive of a member of an archive, and the document itself otherwise.
+/// The members of an archive are modified and removed along with it.
+pub fn source_path(path: impl AsRef<Path>) -> PathBuf {
+    match split_member_path(&path) {
+        Some((archive, _)) => archive,
+        None => path.as_ref().to_path_buf(),
+    }
+}
+
+/// Returns the canonical path to a document. The path to an archive is
+/// resolved for a member of an archive, as the member is not a file.
+///
+/// # Errors
+///
+/// Returns an error if the file, or the archive, does not exist.
+pub fn canonicalize(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    match split_member_path(&path) {
+        Some((archive, member)) => Ok(member_path(archive.canonicalize()?, member)),
+        None => path.as_ref().canonicalize(),
+    }
+}
+
+/// Opens a document for reading, from its file, or from its archive if it
+/// is a member of an archive, in which case it is read as a whole.
+pub(crate) fn open(path: impl AsRef<Path>) -> io::Result<Box<dyn Source>> {
+    match split_member_path(&path) {
+        Some((archive, member)) => Ok(Box::new(Cursor::new(read_member(&archive, &member)?))),
+        None => Ok(Box::new(File::open(path)?)),
+    }
+}
+
+/// Lists the files of an archive, with the paths of their documents, which
+/// are made with [`member_path`], and their sizes in bytes once they are
+/// extracted. Directories, links, and members whose paths leave the archive,
+/// like `../notes.txt`, are skipped. The messages of a mailbox are members
+/// named by their numbers, from one, like `inbox.mbox!/1.eml`.
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be read, or if it is a zip or tar
+/// archive without the `archives` feature.
+pub fn list_members(archive: impl AsRef<Path>) -> io::Result<Vec<(PathBuf, u64)>> {
+    let archive = archive.as_ref();
+    if is_mbox(archive) {
+        let contents = fs::read(archive)?;
+        let messages = email::split_mbox(&contents);
+        return Ok((1..)
+            .zip(messages)
+            .map(|(number, message)| {
+                let member = format!("{number}.eml");
+                (member_path(archive, member), message.len() as u64)
+            })
+            .collect());
+    }
+    list_archive_members(archive)
+}
+
+/// Lists the files of a zip or tar archive.
+#[cfg(feature = "archives")]
+fn list_archive_members(archive: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut members = vec![];
+    if is_zip(archive) {
+        let mut zip = zip_archive(archive)?;
+        for i in 0..zip.len() {
+            let file = zip.by_index_raw(i).map_err(invalid_data)?;
+            if let Some(member) = file.enclosed_name().filter(|_| file.is_file()) {
+                members.push((member_path(archive, member), file.size()));
+            }
+        }
+    } else {
+        let mut tar = tar_archive(archive)?;
+        for entry in tar.entries()? {
+            let entry = entry?;
+            let member = entry.path()?;
+            if entry.header().entry_type().is_file() && is_enclosed(&member) {
+                members.push((member_path(archive, member), entry.size()));
+            }
+        }
+    }
+    Ok(members)
+}
+
+/// Reads the contents of a member of an archive, or of a message of a
+/// mailbox, with its escaped `From ` lines unescaped.
+fn read_member(archive: &Path, member: &Path) -> io::Result<Vec<u8>> {
+    if !is_mbox(archive) {
+        return read_archive_member(archive, member);
+    }
+    let contents = fs::read(archive)?;
+    let message = member
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|number| number.parse::<usize>().ok())
+        .and_then(|number| {
+            email::split_mbox(&contents)
+                .get(number.checked_sub(1)?)
+                .copied()
+        });
+    match message {
+        Some(message) => Ok(email::unescape_mbox(message)),
+        None => Err(not_found(archive, member)),
+    }
+}
+
+/// Reads the contents of a member of a zip or tar archive. The entries of a
+/// tar archive are read in order up to the member, as they cannot be looked
+/// up.
+#[cfg(feature = "archives")]
+fn read_archive_member(archive: &Path, member: &Path) -> io::Result<Vec<u8>> {
+    let mut contents = vec![];
+    if is_zip(archive) {
+        let mut zip = zip_archive(archive)?;
+        let name = member.to_string_lossy().replace('\\', "/");
+        zip.by_name(&name)
+            .map_err(invalid_data)?
+            .read_to_end(&mut contents)?;
+        return Ok(contents);
+    }
+    let mut tar = tar_archive(archive)?;
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() && entry.path()? == member {
+            entry.read_to_end(&mut contents)?;
+            return Ok(contents);
+        }
+    }
+    Err(not_found(archive, member))
+}
+
+/// Lists the files of a zip or tar archive, which is not supported without
+/// the `archives` feature.
+#[cfg(not(feature = "archives"))]
+fn list_archive_members(archive: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    Err(unsupported(archive))
+}
+
+/// Reads the contents of a member of a zip or tar archive, which is not
+/// supported without the `archives` feature.
+#[cfg(not(feature = "archives"))]
+fn read_archive_member(archive: &Path, _member: &Path) -> io::Result<Vec<u8>> {
+    Err(unsupported(archive))
+}
+
+#[cfg(not(feature = "archives"))]
+fn unsupported(archive: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "Cannot read {}, as archives are not supported without the archives feature",
+            archive.display()
+        ),
+    )
+}
+
+fn not_found(archive: &Path, member: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not in {}", member.display(), archive.display()),
+    )
+}
+
+/// Checks if an archive is a mailbox.
+fn is_mbox(archive: &Path) -> bool {
+    archive
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mbox"))
+}
+
+/// Checks if an archive is a zip archive, rather than a tar archive.
+#[cfg(feature = "archives")]
+fn is_zip(archive: &Path) -> bool {
+    archive
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// Opens a zip archive.
+#[cfg(feature = "archives")]
+fn zip_archive(archive: &Path) -> io::Result<zip::ZipArchive<io::BufReader<File>>> {
+    zip::ZipArchive::new(io::BufReader::new(File::open(archive)?)).map_err(invalid_data)
+}
+
+/// Opens a tar archive, which is decompressed as it is read if it is
+/// compressed with gzip.
+#[cfg(feature = "archives")]
+fn tar_archive(archive: &Path) -> io::Result<tar::Archive<Box<dyn Read>>> {
+    let file = io::BufReader::new(File::open(archive)?);
+    let reader: Box<dyn Read> = match archive.extension() {
+        Some(extension) if extension == "gz" || extension == "tgz" => {
+            Box::new(flate2::read::GzDecoder::new(file))
+        }
+        _ => Box::new(file),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+/// Checks if the path to a member of an archive stays in the archive, that
+/// is, if it is relative and it has no `..` components.
+#[cfg(feature = "archives")]
+fn is_enclosed(member: &Path) -> bool {
+    member
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(feature = "archives")]
+fn invalid_data(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_path() {
+        let path = member_path("/corpus/docs.zip", "guide/ch1.txt");
+        assert_eq!(path, Path::new("/corpus/docs.zip!/guide/ch1.txt"));
+        let (archive, member) = split_member_path(&path).unwrap();
+        assert_eq!(archive, Path::new("/corpus/docs.zip"));
+        assert_eq!(member, Path::new("guide/ch1.txt"));
+        assert_eq!(source_path(&path), Path::new("/corpus/docs.zip"));
+
+        assert_eq!(split_member_path("/corpus/notes!/ch1.txt"), None);
+        assert_eq!(
+            source_path("/corpus/notes.txt"),
+            Path::new("/corpus/notes.txt")
+        );
+        assert!(is_archive("logs.tar.gz"));
+        assert!(is_archive("Docs.ZIP"));
+        assert!(!is_archive("notes.gz"));
+        assert!(is_supported("inbox.mbox"));
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::html::{unescape_html, Html};
+
+/// Structure that represents a web bookmark, exported from a browser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    title: String,
+    url: String,
+}
+
+impl Bookmark {
+    /// Creates a new bookmark with specified title and URL.
+    pub fn new(title: String, url: String) -> Self {
+        Self { title, url }
+    }
+
+    /// Returns the title of the bookmark.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the URL of the bookmark.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// Reads the bookmarks from a browser bookmarks export.
+///
+/// Two kinds of exports are supported:
+///
+/// * HTML exports in the Netscape bookmark file format, which both Firefox
+///   and Chrome produce when exporting bookmarks.
+/// * JSON files, namely Firefox bookmark backups and the Chrome `Bookmarks`
+///   file from the browser profile.
+///
+/// Bookmarks that do not point to a web page, like Firefox smart folders and
+/// bookmarklets, are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, or if it is a JSON file that
+/// cannot be parsed.
+pub fn read_bookmarks(path: impl AsRef<Path>) -> std::io::Result<Vec<Bookmark>> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)?;
+    let bookmarks = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") | Some("htm") => parse_netscape_bookmarks(&contents),
+        _ => {
+            let value: Value = serde_json::from_str(&contents)?;
+            let mut bookmarks = vec![];
+            collect_json_bookmarks(&value, &mut bookmarks);
+            bookmarks
+        }
+    };
+    Ok(bookmarks
+        .into_iter()
+        .filter(|bookmark| is_web_url(bookmark.url()))
+        .collect())
+}
+
+/// Parses the bookmarks of an export in the Netscape bookmark file format.
+///
+/// Each bookmark is an anchor, `<A HREF="...">Title</A>`, and the tags are
+/// matched case-insensitively.
+fn parse_netscape_bookmarks(html: &str) -> Vec<Bookmark> {
+    let html = Html::new(html);
+    let mut bookmarks = vec![];
+    let mut pos = 0;
+    while let Some(tag) = html.find_tag(pos, Some("a")) {
+        let Some(close) = html.find_closing_tag(tag.end, "a") else {
+            break;
+        };
+        if let Some(url) = html.attribute(&tag, "href").map(unescape_html) {
+            let title = unescape_html(html.as_str()[tag.end..close.start].trim());
+            let title = if title.is_empty() { url.clone() } else { title };
+            bookmarks.push(Bookmark::new(title, url));
+        }
+        pos = close.end;
+    }
+    bookmarks
+}
+
+/// Collects the bookmarks of a JSON export recursively.
+///
+/// Chrome bookmarks have a `url` and a `name`, while Firefox bookmarks have a
+/// `uri` and a `title`. Folders in both keep their bookmarks in `children`.
+fn collect_json_bookmarks(value: &Value, bookmarks: &mut Vec<Bookmark>) {
+    match value {
+        Value::Object(map) => {
+            let url = map.get("url").or_else(|| map.get("uri"));
+            if let Some(url) = url.and_then(Value::as_str) {
+                let title = map
+                    .get("name")
+                    .or_else(|| map.get("title"))
+                    .and_then(Value::as_str)
+                    .filter(|title| !title.is_empty())
+                    .unwrap_or(url);
+                bookmarks.push(Bookmark::new(title.to_string(), url.to_string()));
+            }
+            for value in map.values() {
+                collect_json_bookmarks(value, bookmarks);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_json_bookmarks(value, bookmarks);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Checks if a URL points to a web page, or a local file.
+fn is_web_url(url: &str) -> bool {
+    ["http://", "https://", "ftp://", "file://"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_netscape_bookmarks() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><H3>Rust</H3>
+    <DL><p>
+        <DT><A HREF="https://doc.rust-lang.org/book/" ADD_DATE="1700000000">The Rust Book</A>
+        <DT><a href="https://crates.io/search?q=a&amp;b">Crates &amp; more</a>
+        <DT><A HREF="place:sort=8">Recent Tags</A>
+    </DL><p>
+</DL>"#;
+        let bookmarks = parse_netscape_bookmarks(html);
+        assert_eq!(bookmarks.len(), 3);
+        assert_eq!(bookmarks[0].title(), "The Rust Book");
+        assert_eq!(bookmarks[0].url(), "https://doc.rust-lang.org/book/");
+        assert_eq!(bookmarks[1].title(), "Crates & more");
+        assert_eq!(bookmarks[1].url(), "https://crates.io/search?q=a&b");
+        assert!(!is_web_url(bookmarks[2].url()));
+    }
+}
@@ -0,0 +1,171 @@
+//! Parsing of the content lines of iCalendar (`.ics`) and vCard (`.vcf`)
+//! files, which share the same line format:
+//!
+//! ```text
+//! NAME;PARAM=VALUE:value
+//! ```
+//!
+//! Long lines are folded by starting the continuation lines with a space or
+//! a horizontal tab.
+
+/// Properties of calendar events that contain text worth indexing.
+pub(crate) const CALENDAR_PROPERTIES: &[&str] = &[
+    "SUMMARY",
+    "DESCRIPTION",
+    "LOCATION",
+    "CATEGORIES",
+    "COMMENT",
+];
+
+/// Property of calendar events that contains their date.
+pub(crate) const CALENDAR_DATE_PROPERTY: &str = "DTSTART";
+
+/// Properties of contacts that contain text worth indexing.
+pub(crate) const CONTACT_PROPERTIES: &[&str] = &[
+    "FN", "N", "NICKNAME", "ORG", "TITLE", "ROLE", "EMAIL", "TEL", "ADR", "URL", "NOTE",
+];
+
+/// Property of contacts that contains their date.
+pub(crate) const CONTACT_DATE_PROPERTY: &str = "BDAY";
+
+/// Extracts the text of the specified properties from the contents of an
+/// iCalendar or vCard file, one property value per line. The dates of the
+/// date property are appended as `YYYY-MM-DD`, so they can be searched too.
+pub(crate) fn extract_text(contents: &str, properties: &[&str], date_property: &str) -> String {
+    let mut text = String::new();
+    for (name, value) in parse(contents) {
+        if properties.contains(&name.as_str()) {
+            text.push_str(&unescape(&value).replace(';', " "));
+            text.push('\n');
+        } else if name == date_property {
+            if let Some(date) = format_date(&value) {
+                text.push_str(&date);
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+/// Returns the first date of the specified date property, formatted as
+/// `YYYY-MM-DD`. If there is no such property, or its value is not a date,
+/// `None` is returned.
+pub(crate) fn extract_date(contents: &str, date_property: &str) -> Option<String> {
+    parse(contents)
+        .into_iter()
+        .filter(|(name, _)| name == date_property)
+        .find_map(|(_, value)| format_date(&value))
+}
+
+/// Parses the content lines into pairs of uppercase property names and raw
+/// values. Parameters and group prefixes of the names are dropped.
+fn parse(contents: &str) -> Vec<(String, String)> {
+    unfold(contents)
+        .iter()
+        .filter_map(|line| {
+            let colon = find_value_separator(line)?;
+            let name = line[..colon].split(';').next()?;
+            let name = name.rsplit('.').next()?.to_uppercase();
+            Some((name, line[colon + 1..].to_string()))
+        })
+        .collect()
+}
+
+/// Unfolds the folded content lines.
+fn unfold(contents: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in contents.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Finds the colon that separates the name and parameters of a content line
+/// from its value. Colons in quoted parameter values are skipped.
+fn find_value_separator(line: &str) -> Option<usize> {
+    let mut in_quotes = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ':' if !in_quotes => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Unescapes a text value.
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => text.push('\n'),
+                Some(escaped) => text.push(escaped),
+                None => {}
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
+
+/// Formats a date value, like `20240115`, `20240115T100000Z` or `2024-01-15`,
+/// as `YYYY-MM-DD`.
+fn format_date(value: &str) -> Option<String> {
+    let digits = value
+        .chars()
+        .take_while(|c| *c != 'T')
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>();
+    if digits.len() != 8 {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_text() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;TZID=Europe/Athens:20240115T100000\r\n\
+                   SUMMARY:Budget review\r\nDESCRIPTION:Go over the quarterly\\, and yearly\r\n  budget.\r\n\
+                   LOCATION;ALTREP=\"http://example.com:80/room\":Room 4\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let text = extract_text(ics, CALENDAR_PROPERTIES, CALENDAR_DATE_PROPERTY);
+        assert_eq!(
+            text,
+            "2024-01-15\nBudget review\nGo over the quarterly, and yearly budget.\nRoom 4\n"
+        );
+        assert_eq!(
+            extract_date(ics, CALENDAR_DATE_PROPERTY),
+            Some("2024-01-15".to_string())
+        );
+    }
+
+    #[test]
+    fn test_contact_text() {
+        let vcf = "BEGIN:VCARD\nVERSION:4.0\nFN:Jane Doe\nN:Doe;Jane;;;\nitem1.EMAIL;TYPE=work:jane@example.com\n\
+                   BDAY:1985-04-12\nEND:VCARD\n";
+        let text = extract_text(vcf, CONTACT_PROPERTIES, CONTACT_DATE_PROPERTY);
+        assert_eq!(
+            text,
+            "Jane Doe\nDoe Jane   \njane@example.com\n1985-04-12\n"
+        );
+        assert_eq!(
+            extract_date(vcf, CONTACT_DATE_PROPERTY),
+            Some("1985-04-12".to_string())
+        );
+    }
+}
@@ -2,9 +2,12 @@ use std::path::Path;
 
 use anyhow::Context;
 
+use crate::core::dtype::DocumentType;
 use crate::core::metadata::{DocumentMetadata, DocumentMetadataBuilder, DocumentSource};
-use crate::read_to_string;
+use crate::extract::{extract, Extraction};
+use crate::table::TableReader;
 use crate::DocumentId;
+use crate::{redact_secrets, Link};
 
 /// Structure that represents a document. Document in this
 /// case is any part of ++++++
@@ -12,6 +15,7 @@ pub struct Document {
     doc_id: DocumentId,
     page_content: String,
     metadata: DocumentMetadata,
+    links: Vec<Link>,
 }
 
 impl Document {
@@ -28,14 +32,52 @@ impl Document {
             doc_id,
             page_content,
             metadata,
+            links: vec![],
         }
     }
 
-    /// Loads a document from file.
+    /// Loads a document from file, with its links to other files, if it is
+    /// a Markdown or HTML document. The document is extracted by the
+    /// extractor of the registry that sniffs it.
     pub fn from_file(doc_id: DocumentId, path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let path = path.as_ref().to_path_buf();
-        let content =
-            read_to_string(&path).context(format!("Failed to read file {}", path.display()))?;
+        let extraction =
+            extract(&path).context(format!("Failed to read file {}", path.display()))?;
+        Ok(Self::from_extraction(doc_id, path, extraction))
+    }
+
+    /// Creates a document from the text and the metadata that were extracted
+    /// from a file, like by an external program.
+    pub fn from_extraction(
+        doc_id: DocumentId,
+        path: impl AsRef<Path>,
+        extraction: Extraction,
+    ) -> Self {
+        let source = DocumentSource::File(path.as_ref().to_path_buf());
+        let mut builder = DocumentMetadataBuilder::new().with_source(source);
+        if let Some(date) = extraction.date {
+            builder = builder.with_date(date);
+        }
+        let metadata = builder.build();
+        let mut document = Document::new(doc_id, extraction.text, metadata);
+        document.links = extraction.links;
+        document
+    }
+
+    /// Loads a row of a CSV or TSV file as a document, from the path to the
+    /// row, like `sales.csv!/12`, with the values of the named columns, or
+    /// of all of them if no columns are named. The table is read by a reader
+    /// that keeps it, so that its rows are loaded without reading it again.
+    pub fn from_row(
+        doc_id: DocumentId,
+        path: impl AsRef<Path>,
+        columns: &[String],
+        reader: &mut TableReader,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let content = reader
+            .read_row(&path, columns)
+            .context(format!("Failed to read row {}", path.display()))?;
         let metadata = DocumentMetadataBuilder::new()
             .with_source(DocumentSource::File(path))
             .build();
@@ -51,4 +93,34 @@ impl Document {
     pub fn page_content(&self) -> &str {
         &self.page_content
     }
+
+    /// Redacts the likely secrets in the contents of the document, like API
+    /// keys and private keys, and returns the number of secrets that were
+    /// redacted.
+    pub fn redact_secrets(&mut self) -> usize {
+        let (page_content, n_secrets) = redact_secrets(&self.page_content);
+        if n_secrets > 0 {
+            self.page_content = page_content;
+        }
+        n_secrets
+    }
+
+    /// Returns the links of the document to other files, in the order they
+    /// appear in it.
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Returns the metadata of the document.
+    pub fn metadata(&self) -> &DocumentMetadata {
+        &self.metadata
+    }
+
+    /// Returns the type of the document, based on its source. If the source
+    /// is unknown or its type is not supported, `None` is returned.
+    pub fn document_type(&self) -> Option<DocumentType> {
+        match self.metadata.source()? {
+            DocumentSource::File(path) => DocumentType::from_path(path),
+        }
+    }
 }
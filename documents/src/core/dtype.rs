@@ -1,11 +1,17 @@
 use std::ffi::OsStr;
 use std::path::Path;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DocumentType {
     Xml,
     Text,
+    Code,
+    Calendar,
+    Contact,
+    Email,
     Pdf,
+    Docx,
+    Odt,
 }
 
 impl DocumentType {
@@ -24,8 +30,19 @@ impl DocumentType {
     pub fn from_extension(ext: &OsStr) -> Option<Self> {
         match ext.to_str().unwrap() {
             "xhtml" | "html" | "xml" => Some(Self::Xml),
-            "txt" | "md" => Some(Self::Text),
+            "txt" | "md" | "csv" | "tsv" => Some(Self::Text),
+            "rs" | "ts" | "tsx" | "js" | "jsx" | "mjs" | "py" | "go" | "java" | "kt" | "scala"
+            | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "rb" | "php" | "swift" | "sh" => {
+                Some(Self::Code)
+            }
+            "ics" => Some(Self::Calendar),
+            "vcf" => Some(Self::Contact),
+            "eml" => Some(Self::Email),
             "pdf" => Some(Self::Pdf),
+            #[cfg(feature = "office")]
+            "docx" => Some(Self::Docx),
+            #[cfg(feature = "office")]
+            "odt" => Some(Self::Odt),
             _ => None,
         }
     }
@@ -69,6 +86,30 @@ mod tests {
             DocumentType::from_extension(OsStr::new("md")),
             Some(DocumentType::Text)
         );
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("csv")),
+            Some(DocumentType::Text)
+        );
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("rs")),
+            Some(DocumentType::Code)
+        );
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("ts")),
+            Some(DocumentType::Code)
+        );
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("ics")),
+            Some(DocumentType::Calendar)
+        );
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("vcf")),
+            Some(DocumentType::Contact)
+        );
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("eml")),
+            Some(DocumentType::Email)
+        );
         assert_eq!(
             DocumentType::from_extension(OsStr::new("pdf")),
             Some(DocumentType::Pdf)
@@ -76,4 +117,17 @@ mod tests {
         assert_eq!(DocumentType::from_extension(OsStr::new("")), None);
         assert_eq!(DocumentType::from_extension(OsStr::new("jpg")), None);
     }
+
+    #[cfg(feature = "office")]
+    #[test]
+    fn test_office_file_type_from_extension() {
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("docx")),
+            Some(DocumentType::Docx)
+        );
+        assert_eq!(
+            DocumentType::from_extension(OsStr::new("odt")),
+            Some(DocumentType::Odt)
+        );
+    }
 }
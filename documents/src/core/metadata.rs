@@ -6,6 +6,20 @@ use std::path::PathBuf;
 #[derive(Default, Debug, Clone)]
 pub struct DocumentMetadata {
     source: Option<DocumentSource>,
+    date: Option<String>,
+}
+
+impl DocumentMetadata {
+    /// Returns the source of the document, if it is known.
+    pub fn source(&self) -> Option<&DocumentSource> {
+        self.source.as_ref()
+    }
+
+    /// Returns the date of the document as `YYYY-MM-DD`, if it has one, like
+    /// the start date of a calendar event or the birthday of a contact.
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
 }
 
 #[derive(Default, Debug)]
@@ -29,6 +43,16 @@ impl DocumentMetadataBuilder {
         }
     }
 
+    /// Sets the date of the document, formatted as `YYYY-MM-DD`.
+    pub fn with_date(self, date: String) -> Self {
+        DocumentMetadataBuilder {
+            meta: DocumentMetadata {
+                date: Some(date),
+                ..self.meta
+            },
+        }
+    }
+
     /// Consumes the document metadata builder and returns the metadata.
     pub fn build(self) -> DocumentMetadata {
         self.meta
@@ -0,0 +1,466 @@
+//! Parsing of email messages (RFC 822), from `.eml` files and from the
+//! messages of mbox files.
+//!
+//! A message is a block of headers, with folded lines, followed by an empty
+//! line and its body. The body may be encoded with base64 or quoted-printable,
+//! and a multipart body is split into parts by a boundary line, each part
+//! being a message of its own.
+
+use crate::html::{unescape_html, Html};
+
+/// The headers of messages that are indexed along with their bodies.
+const INDEXED_HEADERS: [&str; 2] = ["subject", "from"];
+
+/// The headers of a message or of a part, with lowercase names and unfolded
+/// values.
+type Headers = Vec<(String, String)>;
+
+/// Extracts the text of a message: its subject and its sender, one per
+/// line, followed by the decoded text of its body. The plain text of a
+/// multipart message is preferred over its HTML, which is stripped of its
+/// tags, and attachments that are not text are skipped.
+pub(crate) fn extract_text(message: &[u8]) -> String {
+    let (headers, body) = parse_part(message);
+    let mut text = String::new();
+    for name in INDEXED_HEADERS {
+        if let Some(value) = header(&headers, name) {
+            text.push_str(&decode_words(value));
+            text.push('\n');
+        }
+    }
+    text.push('\n');
+    push_body_text(&headers, body, &mut text);
+    text
+}
+
+/// Extracts the decoded subject of a message from its headers, or `None`
+/// if it has no subject.
+pub(crate) fn extract_subject(message: &[u8]) -> Option<String> {
+    let (headers, _) = parse_part(message);
+    let subject = decode_words(header(&headers, "subject")?);
+    let subject = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!subject.is_empty()).then_some(subject)
+}
+
+/// Splits the contents of an mbox file into its messages, without the
+/// `From ` lines that start them. A `From ` line starts a message at the
+/// start of the file or after an empty line.
+pub(crate) fn split_mbox(contents: &[u8]) -> Vec<&[u8]> {
+    let mut messages = vec![];
+    let mut message_start = None;
+    let mut after_blank = true;
+    for (start, line) in lines(contents) {
+        if after_blank && line.starts_with(b"From ") {
+            if let Some(message_start) = message_start {
+                messages.push(&contents[message_start..start]);
+            }
+            message_start = Some(start + line.len());
+        }
+        after_blank = is_blank(line);
+    }
+    if let Some(message_start) = message_start {
+        messages.push(&contents[message_start..]);
+    }
+    messages
+}
+
+/// Unescapes the lines of a message of an mbox file that were escaped as
+/// `>From `, so that they are not read as the start of another message.
+pub(crate) fn unescape_mbox(message: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(message.len());
+    for (_, line) in lines(message) {
+        let quotes = line.iter().take_while(|&&byte| byte == b'>').count();
+        match quotes > 0 && line[quotes..].starts_with(b"From ") {
+            true => unescaped.extend_from_slice(&line[1..]),
+            false => unescaped.extend_from_slice(line),
+        }
+    }
+    unescaped
+}
+
+/// Splits a message, or a part of a multipart message, into its headers and
+/// its body, which starts after the first empty line.
+fn parse_part(message: &[u8]) -> (Headers, &[u8]) {
+    let (head, body) = match lines(message).find(|(_, line)| is_blank(line)) {
+        Some((start, line)) => (&message[..start], &message[start + line.len()..]),
+        None => (message, &[][..]),
+    };
+    let mut headers: Headers = vec![];
+    for line in String::from_utf8_lossy(head).lines() {
+        match (line.starts_with([' ', '\t']), headers.last_mut()) {
+            (true, Some((_, value))) => {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            _ => {
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+                }
+            }
+        }
+    }
+    (headers, body)
+}
+
+/// Returns the value of the first header with a lowercase name.
+fn header<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header_name, _)| header_name == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Returns the lowercase media type of a part, like `text/plain`, which is
+/// the default.
+fn media_type(headers: &Headers) -> String {
+    header(headers, "content-type")
+        .and_then(|value| value.split(';').next())
+        .map_or_else(
+            || "text/plain".to_string(),
+            |value| value.trim().to_ascii_lowercase(),
+        )
+}
+
+/// Returns the value of a parameter of a header, like the `boundary` of
+/// `multipart/mixed; boundary="abc"`, without its quotes.
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|parameter| {
+        let (key, value) = parameter.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Appends the text of the body of a part. Multipart bodies are walked into,
+/// with only the plain text of alternative parts, and messages that are
+/// forwarded as attachments are extracted too.
+fn push_body_text(headers: &Headers, body: &[u8], text: &mut String) {
+    let content_type = media_type(headers);
+    if content_type.starts_with("multipart/") {
+        let boundary =
+            header(headers, "content-type").and_then(|value| parameter(value, "boundary"));
+        let Some(boundary) = boundary else {
+            return;
+        };
+        let parts = split_multipart(body, &boundary)
+            .into_iter()
+            .map(parse_part)
+            .collect::<Vec<_>>();
+        if content_type == "multipart/alternative" {
+            let part = parts
+                .iter()
+                .find(|(headers, _)| media_type(headers) == "text/plain")
+                .or(parts.first());
+            if let Some((headers, body)) = part {
+                push_body_text(headers, body, text);
+            }
+            return;
+        }
+        for (headers, body) in &parts {
+            push_body_text(headers, body, text);
+        }
+        return;
+    }
+    match content_type.as_str() {
+        "text/plain" => text.push_str(&decode_body(headers, body)),
+        "text/html" => text.push_str(&strip_html(&decode_body(headers, body))),
+        "message/rfc822" => text.push_str(&extract_text(decode_body(headers, body).as_bytes())),
+        _ => return,
+    }
+    text.push('\n');
+}
+
+/// Splits a multipart body into its parts, which are separated by lines of
+/// `--` and the boundary, and end with a line of `--`, the boundary and
+/// `--`. The text before the first part is skipped.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = vec![];
+    let mut part_start = None;
+    for (start, line) in lines(body) {
+        let end = line
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        let Some(rest) = line[..end].strip_prefix(delimiter.as_bytes()) else {
+            continue;
+        };
+        if !rest.is_empty() && rest != b"--" {
+            continue;
+        }
+        if let Some(part_start) = part_start {
+            parts.push(&body[part_start..start]);
+        }
+        if rest == b"--" {
+            return parts;
+        }
+        part_start = Some(start + line.len());
+    }
+    // A body that is cut off before its last boundary keeps its last part.
+    if let Some(part_start) = part_start {
+        parts.push(&body[part_start..]);
+    }
+    parts
+}
+
+/// Decodes the body of a part with its transfer encoding and its charset.
+fn decode_body(headers: &Headers, body: &[u8]) -> String {
+    let encoding = header(headers, "content-transfer-encoding").map(str::to_ascii_lowercase);
+    let bytes = match encoding.as_deref() {
+        Some("base64") => decode_base64(body),
+        Some("quoted-printable") => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    };
+    let charset = header(headers, "content-type").and_then(|value| parameter(value, "charset"));
+    decode_charset(&bytes, charset.as_deref())
+}
+
+/// Decodes the encoded words of a header, like `=?UTF-8?Q?Caf=C3=A9?=`. The
+/// whitespace between two encoded words is dropped, as they are one text.
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let Some((word, len)) = decode_word(&rest[start..]) else {
+            decoded.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_word = false;
+            continue;
+        };
+        let between = &rest[..start];
+        if !after_word || !between.trim().is_empty() {
+            decoded.push_str(between);
+        }
+        decoded.push_str(&word);
+        rest = &rest[start + len..];
+        after_word = true;
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Decodes the encoded word at the start of a text, returning its text and
+/// its length in the text, or `None` if the text does not start with one.
+fn decode_word(text: &str) -> Option<(String, usize)> {
+    let (charset, rest) = text.strip_prefix("=?")?.split_once('?')?;
+    let (encoding, rest) = rest.split_once('?')?;
+    let encoded = &rest[..rest.find("?=")?];
+    if encoded.contains(char::is_whitespace) {
+        return None;
+    }
+    let bytes = match encoding {
+        "B" | "b" => decode_base64(encoded.as_bytes()),
+        "Q" | "q" => decode_quoted_printable(encoded.as_bytes(), true),
+        _ => return None,
+    };
+    // The charset may be followed by a language, like `UTF-8*en`.
+    let charset = charset.split('*').next();
+    let len = text.len() - rest.len() + encoded.len() + 2;
+    Some((decode_charset(&bytes, charset), len))
+}
+
+/// Decodes base64, skipping the line breaks and other characters that are
+/// not part of the alphabet, up to the padding.
+fn decode_base64(text: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in text {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => continue,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    bytes
+}
+
+/// Decodes quoted-printable, where `=` is followed by the hex code of a byte,
+/// or ends a line that continues on the next one. In the encoded words of
+/// headers, underscores are spaces.
+fn decode_quoted_printable(text: &[u8], underscores: bool) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            b'=' => {
+                let rest = &text[i + 1..];
+                let soft_break = [&b"\r\n"[..], b"\n"]
+                    .into_iter()
+                    .find(|line_break| rest.starts_with(line_break));
+                if let Some(line_break) = soft_break {
+                    i += 1 + line_break.len();
+                    continue;
+                }
+                let hex = rest
+                    .get(..2)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        bytes.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => bytes.push(b'='),
+                }
+            }
+            b'_' if underscores => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+        i += 1;
+    }
+    bytes
+}
+
+/// Decodes text in a charset. Latin-1 and Windows-1252, which is read as
+/// Latin-1, are decoded by their code points, and other charsets as UTF-8,
+/// with invalid bytes replaced.
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    let charset = charset.map(str::to_ascii_lowercase);
+    match charset.as_deref() {
+        Some("iso-8859-1" | "latin1" | "windows-1252") => {
+            bytes.iter().map(|&byte| byte as char).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Strips the tags of an HTML body, and the contents of its scripts and
+/// styles, and unescapes its entities.
+pub(crate) fn strip_html(html: &str) -> String {
+    let html = Html::new(html);
+    let source = html.as_str();
+    let mut text = String::new();
+    let mut i = 0;
+    while let Some(tag) = html.find_tag(i, None) {
+        text.push_str(&source[i..tag.start]);
+        text.push(' ');
+        i = match ["script", "style"]
+            .into_iter()
+            .find(|name| html.is_element(&tag, name))
+        {
+            Some(name) => html
+                .find_closing_tag(tag.end, name)
+                .map_or(source.len(), |closing| closing.end),
+            None => tag.end,
+        };
+    }
+    text.push_str(&source[i..]);
+    unescape_html(&text)
+}
+
+/// Returns the lines of bytes, with their line breaks, along with the
+/// offsets they start at.
+fn lines(bytes: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        if start >= bytes.len() {
+            return None;
+        }
+        let end = bytes[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |i| start + i + 1);
+        let line = (start, &bytes[start..end]);
+        start = end;
+        Some(line)
+    })
+}
+
+/// Checks if a line is empty, apart from its line break and whitespace.
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(u8::is_ascii_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_text() {
+        let message = b"From: =?UTF-8?Q?Ren=C3=A9e?= <renee@example.com>\r\n\
+            Subject: =?UTF-8?B?UXVhcnRlcmx5?=\r\n =?UTF-8?Q?_budget?=\r\n\
+            Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+            \r\n\
+            Preamble\r\n\
+            --outer\r\n\
+            Content-Type: multipart/alternative; boundary=inner\r\n\
+            \r\n\
+            --inner\r\n\
+            Content-Type: text/html\r\n\
+            \r\n\
+            <p>Ignored &amp; duplicated</p>\r\n\
+            --inner\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            The caf=C3=A9 budget is =\r\nready.\r\n\
+            --inner--\r\n\
+            --outer\r\n\
+            Content-Type: text/plain\r\n\
+            Content-Transfer-Encoding: base64\r\n\
+            \r\n\
+            U2VlIHRoZSBub3Rlcy4=\r\n\
+            --outer\r\n\
+            Content-Type: image/png\r\n\
+            \r\n\
+            binary\r\n\
+            --outer--\r\n";
+        let text = extract_text(message);
+        assert!(text.starts_with("Quarterly budget\nRen\u{e9}e <renee@example.com>\n"));
+        assert!(text.contains("The caf\u{e9} budget is ready."));
+        assert!(text.contains("See the notes."));
+        assert!(!text.contains("Preamble"));
+        assert!(!text.contains("Ignored"));
+        assert!(!text.contains("binary"));
+        assert_eq!(
+            extract_subject(message).as_deref(),
+            Some("Quarterly budget")
+        );
+    }
+
+    #[test]
+    fn test_split_mbox() {
+        let mbox = b"From alice@example.com Mon Jan  1 00:00:00 2024\n\
+            Subject: First\n\
+            \n\
+            >From the start.\n\
+            \n\
+            From bob@example.com Tue Jan  2 00:00:00 2024\n\
+            Subject: Second\n\
+            \n\
+            Text\n";
+        let messages = split_mbox(mbox);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with(b"Subject: First"));
+        assert_eq!(
+            unescape_mbox(messages[0]),
+            b"Subject: First\n\nFrom the start.\n\n"
+        );
+        assert_eq!(extract_subject(messages[1]).as_deref(), Some("Second"));
+        assert!(split_mbox(b"").is_empty());
+    }
+
+    #[test]
+    fn test_strip_html() {
+        let html = "<style>p { color: red; }</style><p>Tea &amp; cake<br>menu</p>";
+        assert_eq!(
+            strip_html(html).split_whitespace().collect::<Vec<_>>(),
+            ["Tea", "&", "cake", "menu"]
+        );
+    }
+}
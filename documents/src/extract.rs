@@ -0,0 +1,238 @@
+//! Extraction of the text and the metadata of documents, by extractors that
+//! are looked up in a registry.
+//!
+//! The built-in formats are read by the [`BuiltinExtractor`], and other
+//! formats can be supported by implementing [`DocumentExtractor`] and
+//! registering it with [`register_extractor`], before the documents are
+//! walked and loaded:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use documents::extract::{register_extractor, DocumentExtractor, Extraction};
+//!
+//! struct LogExtractor;
+//!
+//! impl DocumentExtractor for LogExtractor {
+//!     fn sniff(&self, path: &Path, _head: &[u8]) -> bool {
+//!         path.extension().is_some_and(|extension| extension == "log")
+//!     }
+//!
+//!     fn extract(&self, path: &Path) -> anyhow::Result<Extraction> {
+//!         let text = std::fs::read_to_string(path)?;
+//!         Ok(Extraction::new(text))
+//!     }
+//! }
+//!
+//! register_extractor(LogExtractor);
+//! ```
+
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::archive;
+use crate::core::dtype::DocumentType;
+use crate::{read_date, read_links, read_to_string, Link};
+
+/// The number of bytes at the start of a file that extractors sniff.
+pub const SNIFF_BYTES: usize = 512;
+
+/// The text that was extracted from a document, with its metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extraction {
+    /// The text of the document.
+    pub text: String,
+    /// The date of the document, formatted as `YYYY-MM-DD`, if it has one.
+    pub date: Option<String>,
+    /// The links of the document to other files, in the order they appear in
+    /// it.
+    pub links: Vec<Link>,
+}
+
+impl Extraction {
+    /// Creates an extraction of a text without metadata.
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            ..Self::default()
+        }
+    }
+}
+
+/// An extractor of the text and the metadata of a format of documents.
+pub trait DocumentExtractor: Send + Sync {
+    /// Checks if a file is a document that the extractor reads, from its
+    /// path and its first bytes, which are at most [`SNIFF_BYTES`] and are
+    /// empty if the file could not be read. The members of archives are
+    /// sniffed from their paths only, with no bytes.
+    fn sniff(&self, path: &Path, head: &[u8]) -> bool;
+
+    /// Extracts the text and the metadata of a document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document cannot be read or parsed.
+    fn extract(&self, path: &Path) -> anyhow::Result<Extraction>;
+}
+
+/// The extractor of the formats that are supported out of the box, which are
+/// told apart by their extensions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinExtractor;
+
+impl DocumentExtractor for BuiltinExtractor {
+    fn sniff(&self, path: &Path, _head: &[u8]) -> bool {
+        DocumentType::from_path(path).is_some()
+    }
+
+    fn extract(&self, path: &Path) -> anyhow::Result<Extraction> {
+        Ok(Extraction {
+            text: read_to_string(path)?,
+            date: read_date(path),
+            links: read_links(path)?,
+        })
+    }
+}
+
+/// A registry of document extractors. A document is extracted by the last
+/// registered extractor that sniffs it, so extractors that are registered
+/// later take precedence over the built-in one.
+#[derive(Clone)]
+pub struct ExtractorRegistry {
+    extractors: Vec<Arc<dyn DocumentExtractor>>,
+}
+
+impl ExtractorRegistry {
+    /// Creates a registry without extractors.
+    pub fn new() -> Self {
+        Self { extractors: vec![] }
+    }
+
+    /// Registers an extractor, ahead of the ones that are already registered.
+    pub fn register(&mut self, extractor: impl DocumentExtractor + 'static) {
+        self.extractors.push(Arc::new(extractor));
+    }
+
+    /// Returns the extractor of a document, from its path and its first
+    /// bytes, or `None` if its format is not supported.
+    pub fn find(&self, path: &Path, head: &[u8]) -> Option<Arc<dyn DocumentExtractor>> {
+        self.extractors
+            .iter()
+            .rev()
+            .find(|extractor| extractor.sniff(path, head))
+            .cloned()
+    }
+
+    /// Extracts the text and the metadata of a document, with the extractor
+    /// that sniffs it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no extractor sniffs the document, or if its
+    /// extractor fails.
+    pub fn extract(&self, path: &Path) -> anyhow::Result<Extraction> {
+        match self.find(path, &read_head(path)) {
+            Some(extractor) => extractor.extract(path),
+            None => anyhow::bail!("Unsupported file type"),
+        }
+    }
+}
+
+impl Default for ExtractorRegistry {
+    /// Creates a registry with the built-in extractor.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(BuiltinExtractor);
+        registry
+    }
+}
+
+/// Reads the first bytes of a file that extractors sniff. A file that cannot
+/// be read has no bytes.
+pub fn read_head(path: impl AsRef<Path>) -> Vec<u8> {
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    if let Ok(file) = archive::open(path) {
+        let _ = file.take(SNIFF_BYTES as u64).read_to_end(&mut head);
+    }
+    head
+}
+
+/// Returns the registry that documents are extracted with, which starts with
+/// the built-in extractor.
+fn registry() -> &'static RwLock<ExtractorRegistry> {
+    static REGISTRY: OnceLock<RwLock<ExtractorRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(ExtractorRegistry::default()))
+}
+
+/// Registers an extractor in the registry that documents are walked and
+/// loaded with, ahead of the built-in one and of the ones registered before.
+pub fn register_extractor(extractor: impl DocumentExtractor + 'static) {
+    registry()
+        .write()
+        .unwrap_or_else(|err| err.into_inner())
+        .register(extractor);
+}
+
+/// Returns the extractor of a document from the registry, from its path and
+/// its first bytes, or `None` if its format is not supported.
+pub fn find_extractor(path: impl AsRef<Path>, head: &[u8]) -> Option<Arc<dyn DocumentExtractor>> {
+    registry()
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .find(path.as_ref(), head)
+}
+
+/// Extracts the text and the metadata of a document, with the extractor of
+/// the registry that sniffs it.
+///
+/// # Errors
+///
+/// Returns an error if the format of the document is not supported, or if
+/// its extractor fails.
+pub fn extract(path: impl AsRef<Path>) -> anyhow::Result<Extraction> {
+    let path = path.as_ref();
+    let head = read_head(path);
+    match find_extractor(path, &head) {
+        Some(extractor) => extractor.extract(path),
+        None => anyhow::bail!("Unsupported file type"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MagicExtractor;
+
+    impl DocumentExtractor for MagicExtractor {
+        fn sniff(&self, _path: &Path, head: &[u8]) -> bool {
+            head.starts_with(b"MAGIC")
+        }
+
+        fn extract(&self, path: &Path) -> anyhow::Result<Extraction> {
+            let contents = std::fs::read_to_string(path)?;
+            Ok(Extraction::new(
+                contents["MAGIC".len()..].trim().to_string(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let magic = dir.join("notes.md");
+        std::fs::write(&magic, "MAGIC hidden text").unwrap();
+        let plain = dir.join("plain.md");
+        std::fs::write(&plain, "plain text").unwrap();
+
+        let mut registry = ExtractorRegistry::default();
+        assert_eq!(registry.extract(&magic).unwrap().text, "MAGIC hidden text");
+        registry.register(MagicExtractor);
+        assert_eq!(registry.extract(&magic).unwrap().text, "hidden text");
+        assert_eq!(registry.extract(&plain).unwrap().text, "plain text");
+        assert!(registry.find(Path::new("image.jpg"), b"").is_none());
+        assert!(ExtractorRegistry::new().extract(&plain).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Scanning of the tags of HTML documents, without parsing them into a tree.
+//!
+//! The tags and their attributes are matched case-insensitively, in an ASCII
+//! lowercase copy of the document. ASCII lowercasing preserves the byte
+//! offsets, so the offsets found in the copy are used to slice the original.
+
+use std::ops::Range;
+
+/// A tag of an HTML document, by its offsets from its `<` up to and
+/// including its `>`, or up to the end of the document if it is not closed.
+pub(crate) type Tag = Range<usize>;
+
+/// An HTML document, with the lowercase copy that its tags are found in.
+pub(crate) struct Html<'a> {
+    html: &'a str,
+    lower: String,
+}
+
+impl<'a> Html<'a> {
+    /// Creates the lowercase copy of an HTML document to scan its tags.
+    pub(crate) fn new(html: &'a str) -> Self {
+        Self {
+            html,
+            lower: html.to_ascii_lowercase(),
+        }
+    }
+
+    /// Returns the original text of the document.
+    pub(crate) fn as_str(&self) -> &'a str {
+        self.html
+    }
+
+    /// Finds the first opening tag of an element from an offset, or the
+    /// first tag of any element, or closing tag, if no name is given.
+    pub(crate) fn find_tag(&self, from: usize, name: Option<&str>) -> Option<Tag> {
+        let mut pos = from;
+        loop {
+            let start = pos + self.lower[pos..].find('<')?;
+            pos = start + 1;
+            if name.is_none_or(|name| self.is_named(pos, name)) {
+                return Some(start..self.tag_end(start));
+            }
+        }
+    }
+
+    /// Finds the first closing tag of an element from an offset.
+    pub(crate) fn find_closing_tag(&self, from: usize, name: &str) -> Option<Tag> {
+        let mut pos = from;
+        loop {
+            let start = pos + self.lower[pos..].find("</")?;
+            pos = start + 2;
+            if self.is_named(pos, name) {
+                return Some(start..self.tag_end(start));
+            }
+        }
+    }
+
+    /// Returns an iterator of the opening tags of an element.
+    pub(crate) fn tags<'b>(&'b self, name: &'b str) -> impl Iterator<Item = Tag> + 'b {
+        let mut pos = 0;
+        std::iter::from_fn(move || {
+            let tag = self.find_tag(pos, Some(name))?;
+            pos = tag.end;
+            Some(tag)
+        })
+    }
+
+    /// Checks if a tag is the opening tag of an element.
+    pub(crate) fn is_element(&self, tag: &Tag, name: &str) -> bool {
+        self.is_named(tag.start + 1, name)
+    }
+
+    /// Returns the value of an attribute of a tag, as it is written, in
+    /// double or in single quotes.
+    pub(crate) fn attribute(&self, tag: &Tag, name: &str) -> Option<&'a str> {
+        let lower_tag = &self.lower[tag.clone()];
+        for quote in ['"', '\''] {
+            let key = format!("{name}={quote}");
+            let Some(start) = lower_tag.find(&key).map(|i| tag.start + i + key.len()) else {
+                continue;
+            };
+            let end = start + self.html[start..tag.end].find(quote)?;
+            return Some(&self.html[start..end]);
+        }
+        None
+    }
+
+    /// Checks if the name of a tag at an offset, after its `<` or `</`, is
+    /// the name of an element.
+    fn is_named(&self, pos: usize, name: &str) -> bool {
+        self.lower[pos..].strip_prefix(name).is_some_and(|rest| {
+            rest.is_empty()
+                || rest.starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        })
+    }
+
+    /// Returns the offset after the `>` of a tag, or the end of the document
+    /// if the tag is not closed.
+    fn tag_end(&self, start: usize) -> usize {
+        self.lower[start..]
+            .find('>')
+            .map_or(self.lower.len(), |i| start + i + 1)
+    }
+}
+
+/// Replaces the HTML entities that appear in bookmark exports and titles.
+pub(crate) fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_tags() {
+        let html =
+            Html::new("<P>Text <A class=x HREF='a.html'>A</a> <abbr>B</ABBR> <a href=\"b\">");
+        let tags = html.tags("a").collect::<Vec<_>>();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(html.attribute(&tags[0], "href"), Some("a.html"));
+        assert_eq!(html.attribute(&tags[1], "href"), Some("b"));
+        assert_eq!(html.attribute(&tags[0], "id"), None);
+        let closing = html.find_closing_tag(tags[0].end, "a").unwrap();
+        assert_eq!(&html.as_str()[tags[0].end..closing.start], "A");
+        assert_eq!(&html.as_str()[closing], "</a>");
+        let abbr = html.find_tag(0, Some("abbr")).unwrap();
+        assert!(html.is_element(&abbr, "abbr") && !html.is_element(&abbr, "a"));
+        assert_eq!(html.find_tag(0, None), Some(0..3));
+        assert_eq!(html.find_tag(0, Some("title")), None);
+    }
+}
@@ -1,10 +1,25 @@
+pub use crate::bookmarks::{read_bookmarks, Bookmark};
 pub use crate::core::document::Document;
 pub use crate::core::dtype::DocumentType;
 pub use crate::core::metadata::{DocumentMetadata, DocumentSource};
-pub use crate::read::read_to_string;
+pub use crate::extract::{register_extractor, DocumentExtractor, Extraction, ExtractorRegistry};
+pub use crate::links::{read_links, Link};
+pub use crate::read::{read_date, read_source_url, read_to_string};
+pub use crate::redact::redact_secrets;
+pub use crate::title::read_title;
 
 pub type DocumentId = u32;
 
+pub mod archive;
+pub mod bookmarks;
+mod contentline;
 pub mod core;
+mod email;
+pub mod extract;
+mod html;
+pub mod links;
 pub mod read;
+pub mod redact;
 pub mod split;
+pub mod table;
+pub mod title;
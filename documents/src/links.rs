@@ -0,0 +1,232 @@
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+use crate::archive;
+use crate::html::Html;
+
+/// A link of a document to another document of the same collection.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Link {
+    /// A link to a relative or absolute path, like `[text](../notes.md)` in
+    /// Markdown, or `<a href="notes.html">` in HTML. The fragment and the
+    /// query of the link are dropped.
+    Path(String),
+    /// A wiki link to a document by its name, like `[[Meeting Notes]]`,
+    /// which points to the document whose file is named after it, wherever
+    /// it is.
+    Name(String),
+}
+
+impl fmt::Display for Link {
+    /// Formats a link as its target, with wiki links in double brackets, as
+    /// they are written in Markdown. [`Link::parse`] reads it back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{path}"),
+            Self::Name(name) => write!(f, "[[{name}]]"),
+        }
+    }
+}
+
+impl Link {
+    /// Parses a link from the way it is formatted.
+    pub fn parse(text: &str) -> Self {
+        match text
+            .strip_prefix("[[")
+            .and_then(|name| name.strip_suffix("]]"))
+        {
+            Some(name) => Self::Name(name.to_string()),
+            None => Self::Path(text.to_string()),
+        }
+    }
+}
+
+/// Reads the links of a Markdown or HTML file to other local files.
+///
+/// Links with a scheme, like `https://` or `mailto:`, point outside of the
+/// collection and are skipped, as are links to a fragment of the same file.
+/// Other files have no links.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+pub fn read_links(path: impl AsRef<Path>) -> std::io::Result<Vec<Link>> {
+    let path = path.as_ref();
+    let parse = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("md") => parse_markdown_links,
+        Some("html") | Some("xhtml") => parse_html_links,
+        _ => return Ok(vec![]),
+    };
+    let mut contents = String::new();
+    archive::open(path)?.read_to_string(&mut contents)?;
+    Ok(parse(&contents))
+}
+
+/// Parses the links of a Markdown document: its inline links, `[text](url)`,
+/// its reference definitions, `[label]: url`, and its wiki links,
+/// `[[name]]` or `[[name|text]]`. Images are not links.
+fn parse_markdown_links(markdown: &str) -> Vec<Link> {
+    let mut links = vec![];
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if let Some(rest) = trimmed.strip_prefix('[') {
+            if let Some((_, target)) = rest.split_once("]:") {
+                let target = target.split_whitespace().next().unwrap_or_default();
+                links.extend(path_link(unbracket(target)));
+                continue;
+            }
+        }
+        let mut pos = 0;
+        while let Some(start) = line[pos..].find('[').map(|i| pos + i) {
+            if let Some(rest) = line[start..].strip_prefix("[[") {
+                let Some(end) = rest.find("]]") else {
+                    break;
+                };
+                let name = rest[..end].split(['|', '#']).next().unwrap_or_default();
+                if !name.trim().is_empty() {
+                    links.push(Link::Name(name.trim().to_string()));
+                }
+                pos = start + 2 + end + 2;
+                continue;
+            }
+            let Some(close) = line[start..].find("](").map(|i| start + i) else {
+                break;
+            };
+            let target_start = close + 2;
+            let Some(target_end) = line[target_start..].find(')').map(|i| target_start + i) else {
+                break;
+            };
+            let is_image = line[..start].ends_with('!');
+            let target = line[target_start..target_end]
+                .split_whitespace()
+                .next()
+                .unwrap_or_default();
+            if !is_image {
+                links.extend(path_link(unbracket(target)));
+            }
+            pos = target_end + 1;
+        }
+    }
+    links
+}
+
+/// Removes the angle brackets that the target of a Markdown link can be
+/// written in.
+fn unbracket(target: &str) -> &str {
+    target
+        .strip_prefix('<')
+        .and_then(|target| target.strip_suffix('>'))
+        .unwrap_or(target)
+}
+
+/// Parses the links of an HTML document, the `href` attributes of its
+/// anchors. The tags are matched case-insensitively.
+fn parse_html_links(html: &str) -> Vec<Link> {
+    parse_hrefs(html)
+        .iter()
+        .filter_map(|target| path_link(target))
+        .collect()
+}
+
+/// Parses the targets of the anchors of an HTML document, the `href`
+/// attributes of its `<a>` tags, as they are written, with their `&amp;`
+/// unescaped. Unlike the links of [`read_links`], they include the targets
+/// with a scheme, like `https://`. The tags are matched case-insensitively.
+pub fn parse_hrefs(html: &str) -> Vec<String> {
+    let html = Html::new(html);
+    html.tags("a")
+        .filter_map(|tag| html.attribute(&tag, "href"))
+        .map(|target| target.replace("&amp;", "&"))
+        .collect()
+}
+
+/// Turns the target of a link into a link to a local file, dropping its
+/// fragment and its query, and decoding its percent-encoded bytes, or
+/// returns `None` if it points outside of the collection.
+fn path_link(target: &str) -> Option<Link> {
+    let scheme = target.split_once(':').map(|(scheme, _)| scheme);
+    if scheme.is_some_and(|scheme| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '+')
+    }) {
+        return None;
+    }
+    let path = target.split(['#', '?']).next().unwrap_or_default();
+    match path.is_empty() {
+        true => None,
+        false => Some(Link::Path(percent_decode(path))),
+    }
+}
+
+/// Decodes the percent-encoded bytes of a path, like `%20` for a space.
+/// Invalid escapes are kept as they are.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markdown_links() {
+        let markdown = "# Notes\n\
+See [the budget](budget.md#2024) and [[Meeting Notes|the meeting]].\n\
+![chart](chart.png) [site](https://example.com) [top](#notes)\n\
+[report]: <../reports/Q1%20report.md> \"Q1\"\n";
+        assert_eq!(
+            parse_markdown_links(markdown),
+            [
+                Link::Path("budget.md".to_string()),
+                Link::Name("Meeting Notes".to_string()),
+                Link::Path("../reports/Q1 report.md".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_html_links() {
+        let html = r#"<p><A HREF="guide/index.html?lang=en">Guide</A>
+<a class="x" href='../faq.html#top'>FAQ</a> <a href="mailto:team@example.com">Mail</a>
+<link href="style.css"></p>"#;
+        assert_eq!(
+            parse_html_links(html),
+            [
+                Link::Path("guide/index.html".to_string()),
+                Link::Path("../faq.html".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_links() {
+        for link in [
+            Link::Path("notes/budget.md".to_string()),
+            Link::Name("Meeting Notes".to_string()),
+        ] {
+            assert_eq!(Link::parse(&link.to_string()), link);
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 
 use xml::reader::XmlEvent;
 use xml::EventReader;
 
+use crate::archive;
+use crate::contentline::*;
 use crate::core::dtype::DocumentType;
+use crate::email;
 
 /// Reads the contents of a file and returns it as a string.
 ///
@@ -27,8 +29,30 @@ use crate::core::dtype::DocumentType;
 /// is an I/O error.
 pub fn read_to_string(path: impl AsRef<Path>) -> std::io::Result<String> {
     match DocumentType::from_path(&path) {
+        Some(DocumentType::Xml) if is_html(&path) => read_html_file(path),
         Some(DocumentType::Xml) => read_xml_file(path),
-        Some(DocumentType::Text) => read_text_file(path),
+        Some(DocumentType::Text) | Some(DocumentType::Code) => read_text_file(path),
+        Some(DocumentType::Calendar) => {
+            let contents = read_text_file(path)?;
+            Ok(extract_text(
+                &contents,
+                CALENDAR_PROPERTIES,
+                CALENDAR_DATE_PROPERTY,
+            ))
+        }
+        Some(DocumentType::Contact) => {
+            let contents = read_text_file(path)?;
+            Ok(extract_text(
+                &contents,
+                CONTACT_PROPERTIES,
+                CONTACT_DATE_PROPERTY,
+            ))
+        }
+        Some(DocumentType::Email) => read_email_file(path),
+        #[cfg(feature = "office")]
+        Some(DocumentType::Docx) => read_office_file(path, "word/document.xml"),
+        #[cfg(feature = "office")]
+        Some(DocumentType::Odt) => read_office_file(path, "content.xml"),
         _ => Err(std::io::Error::new(
             std::io::ErrorKind::InvalidData,
             "Unsupported file type",
@@ -36,9 +60,31 @@ pub fn read_to_string(path: impl AsRef<Path>) -> std::io::Result<String> {
     }
 }
 
+/// Reads the date of a calendar event or a contact, formatted as `YYYY-MM-DD`.
+///
+/// The date of an event is its start date, and the date of a contact is its
+/// birthday. For other file types, or files without a date, `None` is returned.
+pub fn read_date(path: impl AsRef<Path>) -> Option<String> {
+    let date_property = match DocumentType::from_path(&path)? {
+        DocumentType::Calendar => CALENDAR_DATE_PROPERTY,
+        DocumentType::Contact => CONTACT_DATE_PROPERTY,
+        _ => return None,
+    };
+    let contents = read_text_file(path).ok()?;
+    extract_date(&contents, date_property)
+}
+
+/// Reads the URL that a document was fetched from, which is kept in a file
+/// next to it, with the same name and the `url` extension. For documents
+/// that were not fetched, `None` is returned.
+pub fn read_source_url(path: impl AsRef<Path>) -> Option<String> {
+    let contents = std::fs::read_to_string(path.as_ref().with_extension("url")).ok()?;
+    Some(contents.trim().to_string()).filter(|url| !url.is_empty())
+}
+
 /// Reads an XML file and returns its contents (characters) as a string.
 fn read_xml_file(path: impl AsRef<Path>) -> std::io::Result<String> {
-    let file = File::open(path)?;
+    let file = archive::open(path)?;
     let reader = BufReader::new(file);
     let er = EventReader::new(reader);
     let mut contents = String::new();
@@ -51,10 +97,75 @@ fn read_xml_file(path: impl AsRef<Path>) -> std::io::Result<String> {
     Ok(contents)
 }
 
+/// Checks if a file is an HTML page, rather than an XML document, from its
+/// extension.
+fn is_html(path: impl AsRef<Path>) -> bool {
+    matches!(
+        path.as_ref().extension().and_then(|ext| ext.to_str()),
+        Some("html") | Some("xhtml")
+    )
+}
+
+/// Reads an HTML file and returns its text, without its tags, and the
+/// contents of its scripts and styles. HTML is not parsed as XML, as pages
+/// are rarely well-formed, with elements like `<meta>` and `<br>` that are
+/// never closed.
+fn read_html_file(path: impl AsRef<Path>) -> std::io::Result<String> {
+    let contents = read_text_file(path)?;
+    Ok(email::strip_html(&contents))
+}
+
+/// Reads an office document (Office Open XML or OpenDocument) and returns its
+/// text as a string.
+///
+/// Office documents are zip archives, with the text of the document stored in
+/// an XML part of the archive. The characters of the part are collected, and
+/// paragraphs, headings, tabs and line breaks are separated by whitespace, as
+/// words are often split over multiple runs of characters.
+///
+/// # Arguments
+///
+/// * `path` - A path to the office document.
+/// * `part` - The name of the XML part in the archive that contains the text.
+#[cfg(feature = "office")]
+fn read_office_file(path: impl AsRef<Path>, part: &str) -> std::io::Result<String> {
+    let file = archive::open(path)?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    let part = archive
+        .by_name(part)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    let er = EventReader::new(BufReader::new(part));
+    let mut contents = String::new();
+    for event in er.into_iter().flatten() {
+        match event {
+            XmlEvent::Characters(string) | XmlEvent::Whitespace(string) => {
+                contents.push_str(&string);
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "p" | "h" => contents.push('\n'),
+                "tab" | "br" | "s" => contents.push(' '),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    Ok(contents)
+}
+
+/// Reads an email message, from an `.eml` file or a message of a mailbox,
+/// and returns its subject, its sender and the decoded text of its body.
+fn read_email_file(path: impl AsRef<Path>) -> std::io::Result<String> {
+    let mut message = vec![];
+    archive::open(path)?.read_to_end(&mut message)?;
+    Ok(email::extract_text(&message))
+}
+
 /// Reads a text file and returns its contents as a string.
 fn read_text_file(path: impl AsRef<Path>) -> std::io::Result<String> {
-    let file = File::open(path)?;
-    let size = file.metadata().map(|m| m.len() as usize).ok();
+    let mut file = archive::open(path)?;
+    let size = file.seek(SeekFrom::End(0)).map(|len| len as usize).ok();
+    file.rewind()?;
     let mut string = String::new();
     string.try_reserve_exact(size.unwrap_or(0))?;
     let mut reader = BufReader::new(file);
@@ -0,0 +1,195 @@
+//! Reading of the rows of CSV and TSV files, which can be documents of their
+//! own, at paths like `sales.csv!/12`, with the number of the row after the
+//! header, from one.
+//!
+//! Fields may be quoted with `"`, with quotes in them doubled, and quoted
+//! fields may span lines.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::archive::{member_path, split_member_path};
+
+/// Checks if a file is a table whose rows can be documents, from its
+/// extension: `.csv` or `.tsv`.
+pub fn is_table(path: impl AsRef<Path>) -> bool {
+    delimiter(path.as_ref()).is_some()
+}
+
+/// Lists the paths to the rows of a table, skipping its header and its empty
+/// rows.
+///
+/// # Errors
+///
+/// Returns an error if the table cannot be read.
+pub fn list_rows(path: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    let records = read_records(path)?;
+    Ok(records
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, record)| record.iter().any(|value| !value.trim().is_empty()))
+        .map(|(number, _)| member_path(path, number.to_string()))
+        .collect())
+}
+
+/// Reads the rows of tables, keeping the records of the last table that was
+/// read, so that the rows of a table are read one after the other without
+/// parsing it again for each.
+#[derive(Debug, Default)]
+pub struct TableReader {
+    table: Option<(PathBuf, Vec<Vec<String>>)>,
+}
+
+impl TableReader {
+    /// Creates a reader that has not read a table yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the values of a row of a table, one per line, from the path to
+    /// the row. Only the values of the named columns are read, in their
+    /// order, or all of them if no columns are named. Columns that are not
+    /// in the header are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is not to a row of a table, if the table
+    /// cannot be read, or if it has no such row.
+    pub fn read_row(&mut self, path: impl AsRef<Path>, columns: &[String]) -> io::Result<String> {
+        let path = path.as_ref();
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a row of a table", path.display()),
+            )
+        };
+        let (table, row) = split_member_path(path)
+            .filter(|(table, _)| is_table(table))
+            .ok_or_else(not_found)?;
+        let number = row
+            .to_str()
+            .and_then(|row| row.parse::<usize>().ok())
+            .filter(|&number| number > 0)
+            .ok_or_else(not_found)?;
+        if !matches!(&self.table, Some((cached, _)) if *cached == table) {
+            let records = read_records(&table)?;
+            self.table = Some((table, records));
+        }
+        let records = self
+            .table
+            .as_ref()
+            .map_or(&[][..], |(_, records)| records.as_slice());
+        let (Some(header), Some(record)) = (records.first(), records.get(number)) else {
+            return Err(not_found());
+        };
+        let values = match columns.is_empty() {
+            true => record.iter().collect::<Vec<_>>(),
+            false => columns
+                .iter()
+                .filter_map(|column| header.iter().position(|name| name.trim() == column))
+                .filter_map(|i| record.get(i))
+                .collect(),
+        };
+        Ok(values
+            .into_iter()
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+/// Returns the delimiter of the fields of a table, from its extension.
+fn delimiter(path: &Path) -> Option<char> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    }
+}
+
+/// Reads the records of a table, the header first. Bytes that are not valid
+/// UTF-8 are replaced.
+fn read_records(path: &Path) -> io::Result<Vec<Vec<String>>> {
+    let delimiter = delimiter(path).unwrap_or(',');
+    let contents = fs::read(path)?;
+    Ok(parse_records(
+        &String::from_utf8_lossy(&contents),
+        delimiter,
+    ))
+}
+
+/// Parses the records of a table, with their fields unquoted. An empty line
+/// is a record with an empty field, so that the records keep their numbers.
+fn parse_records(contents: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if in_quotes => match chars.peek() {
+                Some('"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                _ => in_quotes = false,
+            },
+            '"' if field.is_empty() => in_quotes = true,
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            '\r' if !in_quotes => {}
+            char if char == delimiter && !in_quotes => record.push(std::mem::take(&mut field)),
+            char => field.push(char),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let csv = "name,notes\r\nAlice,\"Likes \"\"tea\"\",\nand cake\"\r\n\r\nBob,coffee";
+        let records = parse_records(csv, ',');
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1], ["Alice", "Likes \"tea\",\nand cake"]);
+        assert_eq!(records[2], [""]);
+        assert_eq!(records[3], ["Bob", "coffee"]);
+        assert_eq!(parse_records("a\tb\n", '\t'), [["a", "b"]]);
+    }
+
+    #[test]
+    fn test_read_row() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("sales.csv");
+        fs::write(
+            &path,
+            "id,region,notes\n1,North,Late delivery\n\n2,South,\n",
+        )
+        .unwrap();
+
+        let rows = list_rows(&path).unwrap();
+        assert_eq!(rows, [member_path(&path, "1"), member_path(&path, "3")]);
+        let mut reader = TableReader::new();
+        let columns = ["notes", "missing", "region"].map(String::from);
+        let text = reader.read_row(&rows[0], &columns).unwrap();
+        assert_eq!(text, "Late delivery\nNorth");
+        assert_eq!(reader.read_row(&rows[1], &[]).unwrap(), "2\nSouth");
+        assert!(reader.read_row(member_path(&path, "9"), &[]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::archive::{self, Source};
+use crate::email;
+use crate::html::{unescape_html, Html};
+
+/// The number of bytes of the head of an HTML file that are searched for
+/// its title, so that the whole file is not read for it.
+const MAX_HTML_HEAD_LEN: usize = 64 * 1024;
+
+/// A parser of the title of a file, from the start of the file.
+type TitleParser = fn(BufReader<Box<dyn Source>>) -> std::io::Result<Option<String>>;
+
+/// Reads the title of a Markdown, HTML or email file, which is the first
+/// heading of a Markdown file, the `<title>` element of an HTML file, or the
+/// subject of an email, with its whitespace collapsed.
+///
+/// Only the start of the file is read, up to the title. Other files, and
+/// files without a title, have none, and are shown by their file names.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+pub fn read_title(path: impl AsRef<Path>) -> std::io::Result<Option<String>> {
+    let path = path.as_ref();
+    let parse: TitleParser = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("md") => parse_markdown_title,
+        Some("html") | Some("xhtml") => parse_html_title,
+        Some("eml") => parse_email_title,
+        _ => return Ok(None),
+    };
+    let reader = BufReader::new(archive::open(path)?);
+    parse(reader)
+}
+
+/// Finds the first ATX heading of a Markdown document, like `# Title`.
+/// Headings in fenced code blocks and in the front matter are skipped, as
+/// lines that start with `#` are comments there.
+fn parse_markdown_title(reader: impl BufRead) -> std::io::Result<Option<String>> {
+    let mut fence = None;
+    let mut in_front_matter = false;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+        if i == 0 && trimmed == "---" {
+            in_front_matter = true;
+            continue;
+        }
+        if in_front_matter {
+            in_front_matter = trimmed != "---";
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = match fence {
+                Some(open) if open == marker => None,
+                Some(open) => Some(open),
+                None => Some(marker),
+            };
+            continue;
+        }
+        if fence.is_some() {
+            continue;
+        }
+        let Some(heading) = heading_text(&line) else {
+            continue;
+        };
+        if !heading.is_empty() {
+            return Ok(Some(collapse_whitespace(heading)));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the text of a line that is an ATX heading, without the opening
+/// and the closing `#`, or `None` if the line is not a heading.
+fn heading_text(line: &str) -> Option<&str> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let line = &line[indent..];
+    let level = line.len() - line.trim_start_matches('#').len();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let text = &line[level..];
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    let text = text.trim();
+    let text = match text.trim_end_matches('#') {
+        closed if closed.is_empty() || closed.ends_with([' ', '\t']) => closed.trim_end(),
+        _ => text,
+    };
+    Some(text)
+}
+
+/// Finds the `<title>` element of an HTML document, in its head. The
+/// entities of the title are unescaped.
+fn parse_html_title(mut reader: impl BufRead) -> std::io::Result<Option<String>> {
+    let mut head = Vec::new();
+    while head.len() < MAX_HTML_HEAD_LEN {
+        let read = reader.read_until(b'\n', &mut head)?;
+        let line = String::from_utf8_lossy(&head[head.len() - read..]).to_ascii_lowercase();
+        if read == 0 || line.contains("</title") || line.contains("<body") {
+            break;
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let html = Html::new(&head);
+    let Some(tag) = html.find_tag(0, Some("title")) else {
+        return Ok(None);
+    };
+    let text_end = html
+        .find_closing_tag(tag.end, "title")
+        .map_or(head.len(), |closing| closing.start);
+    let title = collapse_whitespace(&unescape_html(&head[tag.end..text_end]));
+    Ok((!title.is_empty()).then_some(title))
+}
+
+/// Finds the subject of an email, in its headers, which end at the first
+/// empty line.
+fn parse_email_title(mut reader: impl BufRead) -> std::io::Result<Option<String>> {
+    let mut head = Vec::new();
+    loop {
+        let read = reader.read_until(b'\n', &mut head)?;
+        let line = &head[head.len() - read..];
+        if read == 0 || line.iter().all(u8::is_ascii_whitespace) {
+            break;
+        }
+    }
+    Ok(email::extract_subject(&head))
+}
+
+/// Joins the words of a text with single spaces.
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_title() {
+        let markdown = "---\n# not a title\n---\n\n```sh\n# a comment\n```\n\
+                        Some text\n## Quarterly  Budget ##\n# Second\n";
+        let title = parse_markdown_title(markdown.as_bytes()).unwrap();
+        assert_eq!(title.as_deref(), Some("Quarterly Budget"));
+        assert_eq!(
+            parse_markdown_title("#hashtag\ntext".as_bytes()).unwrap(),
+            None
+        );
+        assert_eq!(heading_text("# C#"), Some("C#"));
+        assert_eq!(heading_text("    # code"), None);
+    }
+
+    #[test]
+    fn test_html_title() {
+        let html = "<html><head>\n<TITLE>\n  Notes &amp; Plans\n</TITLE>\n</head><body></body>";
+        let title = parse_html_title(html.as_bytes()).unwrap();
+        assert_eq!(title.as_deref(), Some("Notes & Plans"));
+        let html = "<html><body><h1>No title</h1></body></html>";
+        assert_eq!(parse_html_title(html.as_bytes()).unwrap(), None);
+    }
+}
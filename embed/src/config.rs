@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::{Embedder, HttpEmbedder};
+
+/// The configuration of the embedding provider, read from the `[embedder]`
+/// section of the config of a repository.
+///
+/// ```toml
+/// [embedder]
+/// provider = "http"
+/// url = "http://localhost:11434/v1/embeddings"
+/// model = "nomic-embed-text"
+/// api_key_env = "OPENAI_API_KEY"
+/// ```
+///
+/// ```toml
+/// [embedder]
+/// provider = "onnx"
+/// model_dir = "/path/to/all-MiniLM-L6-v2"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum EmbedderConfig {
+    /// An HTTP embedding API, compatible with the OpenAI embeddings API.
+    Http {
+        /// The URL of the embeddings endpoint.
+        url: String,
+        /// The name of the model.
+        model: String,
+        /// The environment variable that holds the API key, if the API
+        /// requires one.
+        #[serde(default)]
+        api_key_env: Option<String>,
+        /// The maximum number of texts per request.
+        #[serde(default = "default_batch_size")]
+        batch_size: usize,
+    },
+    /// A local ONNX model, with its `model.onnx` and `tokenizer.json` files
+    /// in a directory.
+    Onnx {
+        /// The directory of the model.
+        model_dir: PathBuf,
+        /// The maximum number of texts per batch.
+        #[serde(default = "default_batch_size")]
+        batch_size: usize,
+    },
+}
+
+fn default_batch_size() -> usize {
+    32
+}
+
+impl EmbedderConfig {
+    /// Creates the embedder that is configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API key is not set in its environment
+    /// variable, if the ONNX model cannot be loaded, or if an ONNX model is
+    /// configured, but searchine was built without the `onnx` feature.
+    pub fn build(&self) -> anyhow::Result<Box<dyn Embedder>> {
+        match self {
+            EmbedderConfig::Http {
+                url,
+                model,
+                api_key_env,
+                batch_size,
+            } => {
+                let api_key = match api_key_env {
+                    Some(var) => Some(std::env::var(var).map_err(|_| {
+                        anyhow::anyhow!("Environment variable {var} of the API key is not set.")
+                    })?),
+                    None => None,
+                };
+                let embedder = HttpEmbedder::new(url, model, api_key, *batch_size);
+                Ok(Box::new(embedder))
+            }
+            #[cfg(feature = "onnx")]
+            EmbedderConfig::Onnx {
+                model_dir,
+                batch_size,
+            } => Ok(Box::new(crate::OnnxEmbedder::open(model_dir, *batch_size)?)),
+            #[cfg(not(feature = "onnx"))]
+            EmbedderConfig::Onnx { .. } => anyhow::bail!(
+                "searchine was built without ONNX support. Rebuild it with `--features onnx`."
+            ),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::Embedder;
+
+/// An embedder that calls an HTTP embedding API, compatible with the OpenAI
+/// embeddings API, which is also served by local model servers.
+pub struct HttpEmbedder {
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    batch_size: usize,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl HttpEmbedder {
+    /// Creates an embedder for an embeddings endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `url`        - The URL of the embeddings endpoint.
+    /// * `model`      - The name of the model.
+    /// * `api_key`    - The API key, sent as a bearer token, if any.
+    /// * `batch_size` - The maximum number of texts per request.
+    pub fn new(url: &str, model: &str, api_key: Option<String>, batch_size: usize) -> Self {
+        Self {
+            url: url.to_string(),
+            model: model.to_string(),
+            api_key,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Sends a single request for a batch of texts.
+    fn request(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut request = ureq::post(&self.url);
+        if let Some(api_key) = &self.api_key {
+            request = request.set("Authorization", &format!("Bearer {api_key}"));
+        }
+        let body = EmbeddingRequest {
+            model: &self.model,
+            input: texts,
+        };
+        let response: EmbeddingResponse = request
+            .send_json(body)
+            .context(format!("Failed to request embeddings from: {}", self.url))?
+            .into_json()
+            .context("Failed to read the embeddings from the response.")?;
+
+        let mut data = response.data;
+        if data.len() != texts.len() {
+            anyhow::bail!(
+                "Expected {} embeddings, but the API returned {}.",
+                texts.len(),
+                data.len()
+            );
+        }
+        data.sort_by_key(|data| data.index);
+        Ok(data.into_iter().map(|data| data.embedding).collect())
+    }
+}
+
+impl Embedder for HttpEmbedder {
+    fn model_id(&self) -> String {
+        format!("http:{}", self.model)
+    }
+
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            embeddings.extend(self.request(batch)?);
+        }
+        Ok(embeddings)
+    }
+}
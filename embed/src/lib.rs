@@ -0,0 +1,37 @@
+//! Providers of text embeddings for the semantic index.
+//!
+//! An embedder maps texts to vectors, so that texts with similar meanings
+//! have similar vectors. The provider is chosen in the config of the
+//! repository, either an HTTP embedding API, or a local ONNX model if
+//! searchine is built with the `onnx` feature.
+
+use anyhow::Context;
+
+pub use config::EmbedderConfig;
+pub use http::HttpEmbedder;
+#[cfg(feature = "onnx")]
+pub use onnx::OnnxEmbedder;
+
+mod config;
+mod http;
+#[cfg(feature = "onnx")]
+mod onnx;
+
+/// A provider of text embeddings.
+pub trait Embedder {
+    /// Returns an identifier of the model of the embedder. Vectors of
+    /// different models cannot be compared, so the identifier is stored
+    /// with the vectors, to detect when the model has changed.
+    fn model_id(&self) -> String;
+
+    /// Embeds a batch of texts, returning one vector for each text, in the
+    /// same order.
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>>;
+
+    /// Embeds a single text.
+    fn embed_one(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        self.embed(&[text])?
+            .pop()
+            .context("Embedder returned no embedding.")
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use ort::session::{Session, SessionInputValue};
+use ort::value::Tensor;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+use crate::Embedder;
+
+/// The maximum number of tokens of a text. Longer texts are truncated, as
+/// sentence embedding models are trained on short texts.
+const MAX_TOKENS: usize = 512;
+
+/// An embedder that runs a local sentence embedding model with ONNX Runtime.
+///
+/// The model directory has the model in `model.onnx` and its tokenizer in
+/// `tokenizer.json`, as they are exported for Hugging Face models. The
+/// embedding of a text is the mean of the embeddings of its tokens.
+pub struct OnnxEmbedder {
+    session: Session,
+    tokenizer: Tokenizer,
+    model_dir: PathBuf,
+    batch_size: usize,
+    has_token_type_ids: bool,
+}
+
+impl OnnxEmbedder {
+    /// Loads the model and the tokenizer from a model directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model or the tokenizer cannot be loaded.
+    pub fn open(model_dir: impl AsRef<Path>, batch_size: usize) -> anyhow::Result<Self> {
+        let model_dir = model_dir.as_ref();
+        let model_path = model_dir.join("model.onnx");
+        let session = Session::builder()?
+            .commit_from_file(&model_path)
+            .context(format!("Failed to load model: {}", model_path.display()))?;
+
+        let tokenizer_path = model_dir.join("tokenizer.json");
+        let mut tokenizer = Tokenizer::from_file(&tokenizer_path).map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to load tokenizer: {} because: {err}",
+                tokenizer_path.display()
+            )
+        })?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..TruncationParams::default()
+            }))
+            .map_err(|err| anyhow::anyhow!("Failed to set up tokenizer because: {err}"))?;
+
+        let has_token_type_ids = session
+            .inputs
+            .iter()
+            .any(|input| input.name == "token_type_ids");
+        Ok(Self {
+            session,
+            tokenizer,
+            model_dir: model_dir.to_path_buf(),
+            batch_size: batch_size.max(1),
+            has_token_type_ids,
+        })
+    }
+
+    /// Runs the model on a single batch of texts.
+    fn run(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|err| anyhow::anyhow!("Failed to tokenize texts because: {err}"))?;
+        let n_texts = encodings.len();
+        let n_tokens = encodings.first().map_or(0, |encoding| encoding.len());
+        let shape = [n_texts, n_tokens];
+        let flatten = |values: fn(&tokenizers::Encoding) -> &[u32]| {
+            encodings
+                .iter()
+                .flat_map(|encoding| values(encoding).iter().map(|&value| value as i64))
+                .collect::<Vec<_>>()
+        };
+        let attention_mask = flatten(|encoding| encoding.get_attention_mask());
+
+        let mut inputs: Vec<(&str, SessionInputValue)> = vec![
+            (
+                "input_ids",
+                Tensor::from_array((shape, flatten(|encoding| encoding.get_ids())))?.into(),
+            ),
+            (
+                "attention_mask",
+                Tensor::from_array((shape, attention_mask.clone()))?.into(),
+            ),
+        ];
+        if self.has_token_type_ids {
+            let token_type_ids = flatten(|encoding| encoding.get_type_ids());
+            inputs.push((
+                "token_type_ids",
+                Tensor::from_array((shape, token_type_ids))?.into(),
+            ));
+        }
+        let outputs = self.session.run(inputs)?;
+        let (output_shape, hidden) = outputs[0].try_extract_raw_tensor::<f32>()?;
+        let dimension = *output_shape
+            .last()
+            .context("Model output has no dimensions.")? as usize;
+
+        // Mean pooling of the embeddings of the tokens that are not padding.
+        let mut embeddings = vec![vec![0.0; dimension]; n_texts];
+        for (i, embedding) in embeddings.iter_mut().enumerate() {
+            let mut n_counted = 0.0;
+            for j in 0..n_tokens {
+                if attention_mask[i * n_tokens + j] == 0 {
+                    continue;
+                }
+                let start = (i * n_tokens + j) * dimension;
+                for (value, token_value) in embedding.iter_mut().zip(&hidden[start..]) {
+                    *value += token_value;
+                }
+                n_counted += 1.0;
+            }
+            if n_counted > 0.0 {
+                embedding.iter_mut().for_each(|value| *value /= n_counted);
+            }
+        }
+        Ok(embeddings)
+    }
+}
+
+impl Embedder for OnnxEmbedder {
+    fn model_id(&self) -> String {
+        format!("onnx:{}", self.model_dir.display())
+    }
+
+    fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            embeddings.extend(self.run(batch)?);
+        }
+        Ok(embeddings)
+    }
+}
@@ -0,0 +1,197 @@
+//! Helpers for reading and writing the binary on-disk format of the index.
+//!
+//! Every binary file starts with a header, made of a signature that
+//! identifies searchine files and a version byte. Fixed-size integers are
+//! written in big-endian byte order, and strings are prefixed with their
+//! length. Integers that are usually small, like the gaps between sorted
+//! document IDs, are written with variable-byte encoding.
+
+use std::io::{self, Read, Write};
+
+use crate::error::FormatError;
+
+/// Writes the header of a binary file.
+pub(crate) fn write_header(
+    writer: &mut impl Write,
+    signature: &[u8],
+    version: u8,
+) -> io::Result<()> {
+    writer.write_all(signature)?;
+    writer.write_all(&[version])
+}
+
+/// Reads the header of a binary file, and checks its version.
+///
+/// # Errors
+///
+/// Returns an error if the file does not start with the signature, or if it
+/// has another version.
+pub(crate) fn read_header(reader: &mut impl Read, signature: &[u8], version: u8) -> io::Result<()> {
+    let mut buffer = vec![0; signature.len()];
+    reader.read_exact(&mut buffer)?;
+    if buffer != signature {
+        return Err(FormatError::NotAnIndex.into());
+    }
+    let mut found = [0; 1];
+    reader.read_exact(&mut found)?;
+    check_version(found[0], version)
+}
+
+/// Checks that the version of a binary file is the expected one.
+pub(crate) fn check_version(found: u8, expected: u8) -> io::Result<()> {
+    if found != expected {
+        return Err(FormatError::VersionMismatch { expected, found }.into());
+    }
+    Ok(())
+}
+
+/// Writes an unsigned 32-bit integer.
+pub(crate) fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+/// Reads an unsigned 32-bit integer.
+pub(crate) fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_be_bytes(buffer))
+}
+
+/// Writes a 32-bit floating point number.
+pub(crate) fn write_f32(writer: &mut impl Write, value: f32) -> io::Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+/// Reads a 32-bit floating point number.
+pub(crate) fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(f32::from_be_bytes(buffer))
+}
+
+/// Writes an unsigned 64-bit integer.
+pub(crate) fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+/// Writes an unsigned 32-bit integer with variable-byte encoding.
+///
+/// The integer is split in groups of 7 bits, which are written from the
+/// least significant one, one group per byte. The most significant bit of
+/// each byte is set if more bytes follow, so small integers take a single
+/// byte.
+pub(crate) fn write_varint(writer: &mut impl Write, mut value: u32) -> io::Result<()> {
+    let mut buffer = [0; 5];
+    let mut len = 0;
+    while value >= 0x80 {
+        buffer[len] = (value as u8 & 0x7F) | 0x80;
+        value >>= 7;
+        len += 1;
+    }
+    buffer[len] = value as u8;
+    writer.write_all(&buffer[..=len])
+}
+
+/// Reads an unsigned 32-bit integer written with variable-byte encoding.
+///
+/// # Errors
+///
+/// Returns an error if the encoded integer does not fit in 32 bits.
+pub(crate) fn read_varint(reader: &mut impl Read) -> io::Result<u32> {
+    let mut value = 0u32;
+    let mut byte = [0; 1];
+    for shift in (0..35).step_by(7) {
+        reader.read_exact(&mut byte)?;
+        let group = (byte[0] & 0x7F) as u32;
+        if shift == 28 && group > 0x0F {
+            break;
+        }
+        value |= group << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Variable-byte integer does not fit in 32 bits.",
+    ))
+}
+
+/// Reads the gap of an integer from the previous one in a sorted sequence,
+/// written with variable-byte encoding, and returns the integer.
+///
+/// # Errors
+///
+/// Returns an error if the integer does not fit in 32 bits.
+pub(crate) fn read_delta(reader: &mut impl Read, prev: u32) -> io::Result<u32> {
+    prev.checked_add(read_varint(reader)?).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Delta-encoded integer overflows.",
+        )
+    })
+}
+
+/// Writes a string, prefixed with its length in bytes.
+pub(crate) fn write_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(writer, value.len() as u32)?;
+    writer.write_all(value.as_bytes())
+}
+
+/// Reads a string that is prefixed with its length in bytes.
+///
+/// # Errors
+///
+/// Returns an error if the string is not valid UTF-8.
+pub(crate) fn read_str(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(reader)? as usize;
+    let mut buffer = vec![0; len];
+    reader.read_exact(&mut buffer)?;
+    String::from_utf8(buffer).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_codec() {
+        let mut buffer = vec![];
+        write_header(&mut buffer, b"SEARHINE", 3).unwrap();
+        write_u32(&mut buffer, 42).unwrap();
+        write_u64(&mut buffer, 1 << 40).unwrap();
+        write_str(&mut buffer, "καλη").unwrap();
+
+        assert_eq!(&buffer[..9], b"SEARHINE\x03");
+        assert_eq!(&buffer[9..13], &[0, 0, 0, 42]);
+        assert_eq!(&buffer[13..21], &[0, 0, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(&buffer[21..25], &[0, 0, 0, 8]);
+        assert_eq!(std::str::from_utf8(&buffer[25..]).unwrap(), "καλη");
+
+        assert!(read_header(&mut Cursor::new(&buffer), b"SEARHINE", 4).is_err());
+        let mut reader = Cursor::new(buffer);
+        read_header(&mut reader, b"SEARHINE", 3).unwrap();
+        assert_eq!(read_u32(&mut reader).unwrap(), 42);
+        reader.set_position(21);
+        assert_eq!(read_str(&mut reader).unwrap(), "καλη");
+    }
+
+    #[test]
+    fn test_varint() {
+        let values = [0, 1, 127, 128, 300, 16_383, 16_384, u32::MAX];
+        let mut buffer = vec![];
+        for value in values {
+            write_varint(&mut buffer, value).unwrap();
+        }
+        assert_eq!(buffer.len(), 1 + 1 + 1 + 2 + 2 + 2 + 3 + 5);
+
+        let mut reader = Cursor::new(buffer);
+        for value in values {
+            assert_eq!(read_varint(&mut reader).unwrap(), value);
+        }
+        let overflow = [0xFF, 0xFF, 0xFF, 0xFF, 0x7F];
+        assert!(read_varint(&mut Cursor::new(overflow)).is_err());
+    }
+}
@@ -1,29 +1,72 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufReader, BufWriter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
 
-use documents::DocumentId;
+use documents::archive::source_path;
+use documents::{read_date, read_source_url, read_title, DocumentId};
 
 use crate::collection::CollectionEntry;
+use crate::compress::{decompress, Compression};
+use crate::error::{Error, Result};
+
+/// The version of the format of the collection file. Collection files that
+/// were written before versions were kept have version 0, and are read with
+/// the defaults of the fields they lack.
+const VERSION: u8 = 1;
 
 /// A struct representing a corpus index, which also serves as cache.
 ///
 /// This struct is used to build an in-memory index for multiple documents.
 /// Each document is assigned a unique document ID, and the last time the
 /// document was indexed.
-#[derive(Default, Serialize, Deserialize)]
+///
+/// Document IDs are never reused. The IDs of removed documents may still be
+/// in the postings of the inverted index, so a new document always gets an
+/// ID that was never assigned before.
+///
+/// The documents are kept by their absolute paths, but they are written to
+/// disk relative to the root directory of the corpus that they are in, so a
+/// corpus can span several directories, and a root can be moved by changing
+/// its path alone.
+#[derive(Default, Clone)]
 pub struct Collection {
-    root_dir: PathBuf,
+    /// The root directories of the corpus.
+    roots: Vec<PathBuf>,
     index: HashMap<PathBuf, CollectionEntry>,
+    /// The ID of the next document that is added.
+    next_id: DocumentId,
+    /// The compression that the collection is written with.
+    compression: Compression,
+}
+
+/// A collection, as it is written to disk.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CollectionFile {
+    version: u8,
+    /// The root directories of the corpus, with their documents by their
+    /// paths relative to them.
+    roots: Vec<RootFile>,
+    /// The documents that are in none of the roots, by their absolute paths,
+    /// which is how every document was kept before the collection had roots.
+    index: HashMap<PathBuf, CollectionEntry>,
+    next_id: DocumentId,
+}
+
+/// A root directory of a collection, as it is written to disk.
+#[derive(Serialize, Deserialize)]
+struct RootFile {
+    path: PathBuf,
+    documents: HashMap<PathBuf, CollectionEntry>,
 }
 
 impl Collection {
     /// Creates a new `CorpusIndex` from an iterator of paths.
-    pub fn from_paths(iter: impl IntoIterator<Item = PathBuf>) -> io::Result<Self> {
+    pub fn from_paths(iter: impl IntoIterator<Item = PathBuf>) -> Result<Self> {
         let mut index = Self::default();
         for path in iter {
             index.insert(path)?;
@@ -31,23 +74,75 @@ impl Collection {
         Ok(index)
     }
 
+    /// Sets the root directories of the corpus, that the paths of the
+    /// documents are written relative to. Documents that are in none of the
+    /// roots are written by their absolute paths.
+    pub fn set_roots(&mut self, roots: Vec<PathBuf>) {
+        self.roots = roots;
+    }
+
+    /// Returns the root directories of the corpus.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Sets the compression that the collection is written with. A loaded
+    /// collection keeps the compression of its file.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
     /// Adds a document to the index, and assigns it a unique ID.
-    pub fn insert(&mut self, document_path: PathBuf) -> io::Result<()> {
+    pub fn insert(&mut self, document_path: PathBuf) -> Result<()> {
         if !self.index.contains_key(&document_path) {
-            let modified = document_path.metadata()?.modified()?;
-            let next_id = self.index.len() as u32;
-            let entry = CollectionEntry::new(next_id, modified);
+            let entry = entry(self.next_id, &document_path)?;
             self.index.insert(document_path, entry);
+            self.next_id += 1;
+        }
+        Ok(())
+    }
+
+    /// Updates the last modified time, and the size, of a document in the
+    /// index to its current ones, keeping its document ID and its tags. If
+    /// the document is not in the index, nothing is done.
+    pub fn update_modified(&mut self, document_path: &PathBuf) -> Result<()> {
+        if let Some(current) = self.index.get_mut(document_path) {
+            let tags = current.tags().clone();
+            *current = entry(current.document_id(), document_path)?;
+            current.set_tags(tags);
         }
         Ok(())
     }
 
+    /// Copies the tags of the documents of another collection to the same
+    /// documents of this one, so that the tags of a corpus are kept when its
+    /// collection is built again.
+    pub fn keep_tags(&mut self, previous: &Collection) {
+        for (path, entry) in &mut self.index {
+            if let Some(previous_entry) = previous.index.get(path) {
+                entry.set_tags(previous_entry.tags().clone());
+            }
+        }
+    }
+
     /// Returns true if the index contains a document with the specified path.
     /// Otherwise, it returns false.
     pub fn contains_path(&self, document_path: &PathBuf) -> bool {
         self.index.contains_key(document_path)
     }
 
+    /// Returns the entry of the document with a given path. If the path is
+    /// not found in the index, `None` is returned.
+    pub fn get(&self, document_path: &PathBuf) -> Option<&CollectionEntry> {
+        self.index.get(document_path)
+    }
+
+    /// Returns the mutable entry of the document with a given path, to change
+    /// its tags. If the path is not found in the index, `None` is returned.
+    pub fn get_mut(&mut self, document_path: &PathBuf) -> Option<&mut CollectionEntry> {
+        self.index.get_mut(document_path)
+    }
+
     /// Returns the document id for a given path. If the path is not found
     /// in the index, `None` is returned.
     ///
@@ -87,23 +182,127 @@ impl Collection {
         self.index.remove(document_path)
     }
 
-    /// Write the document index to a disk.
-    pub fn into_file(self, path: impl AsRef<Path>) -> io::Result<()> {
+    /// Write the document index to a disk, in its compression.
+    ///
+    /// The collection is written to a temporary file next to it first, which
+    /// then replaces the collection file, so a write that is interrupted
+    /// leaves the previous collection intact.
+    pub fn into_file(self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
-        let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, &self)?;
-        Ok(())
+        let tmp_path = path.with_extension("tmp");
+        let compression = self.compression;
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_vec(&self.into_collection_file())
+            .map_err(io::Error::from)
+            .and_then(|bytes| compression.compress(bytes))
+            .and_then(|bytes| writer.write_all(&bytes))
+            .and_then(|()| writer.flush())
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
     }
 
-    /// Load the document index from a disk.
-    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+    /// Load the document index from a disk. A compressed collection file is
+    /// decompressed. A collection file that was written by a newer version of
+    /// the format is not read, so that its fields are not lost.
+    ///
+    /// The paths of the documents are resolved against the roots they were
+    /// written relative to. Collections that were written before the next
+    /// document ID was kept continue after their largest document ID.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let index = serde_json::from_reader(reader)?;
+        let bytes = fs::read(path).map_err(Error::at(path))?;
+        let compression = Compression::detect(&bytes);
+        let bytes = decompress(bytes).map_err(Error::at(path))?;
+        let file: CollectionFile =
+            serde_json::from_slice(&bytes).map_err(|err| Error::from_io(path, err.into()))?;
+        if file.version > VERSION {
+            return Err(Error::VersionMismatch {
+                path: path.to_path_buf(),
+                expected: VERSION,
+                found: file.version,
+            });
+        }
+        let mut index = Self::from_collection_file(file);
+        index.compression = compression;
+        let max_id = index.index.values().map(|entry| entry.document_id()).max();
+        if let Some(max_id) = max_id {
+            index.next_id = index.next_id.max(max_id + 1);
+        }
         Ok(index)
     }
+
+    /// Splits the documents by the root they are in, with their paths
+    /// relative to it. A document in nested roots goes to the deepest one.
+    fn into_collection_file(self) -> CollectionFile {
+        let mut roots = self
+            .roots
+            .iter()
+            .map(|root| RootFile {
+                path: root.clone(),
+                documents: HashMap::new(),
+            })
+            .collect::<Vec<_>>();
+        let mut index = HashMap::new();
+        for (path, entry) in self.index {
+            let root = roots
+                .iter_mut()
+                .filter(|root| path.starts_with(&root.path))
+                .max_by_key(|root| root.path.components().count());
+            match root {
+                Some(root) => {
+                    let relative = path.strip_prefix(&root.path).unwrap_or(&path);
+                    root.documents.insert(relative.to_path_buf(), entry);
+                }
+                None => {
+                    index.insert(path, entry);
+                }
+            }
+        }
+        CollectionFile {
+            version: VERSION,
+            roots,
+            index,
+            next_id: self.next_id,
+        }
+    }
+
+    /// Resolves the paths of the documents of a collection file against
+    /// their roots.
+    fn from_collection_file(file: CollectionFile) -> Self {
+        let mut index = file.index;
+        let mut roots = vec![];
+        for root in file.roots {
+            for (relative, entry) in root.documents {
+                index.insert(root.path.join(relative), entry);
+            }
+            roots.push(root.path);
+        }
+        Self {
+            roots,
+            index,
+            next_id: file.next_id,
+            compression: Compression::None,
+        }
+    }
+}
+
+/// Creates the entry of a file, with a document ID, from the metadata of the
+/// file, its title, its date and the URL it was fetched from. Files whose
+/// title cannot be read have none. A member of an archive has the metadata
+/// of the archive, so it is modified with it.
+fn entry(document_id: DocumentId, path: &Path) -> Result<CollectionEntry> {
+    let metadata = source_path(path).metadata().map_err(Error::at(path))?;
+    let modified = metadata.modified().map_err(Error::at(path))?;
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned());
+    let title = read_title(path).ok().flatten();
+    let entry = CollectionEntry::new(document_id, modified, metadata.len(), extension);
+    Ok(entry
+        .with_title(title)
+        .with_date(read_date(path))
+        .with_url(read_source_url(path)))
 }
 
 impl IntoIterator for Collection {
@@ -123,3 +322,119 @@ impl<'a> IntoIterator for &'a Collection {
         self.index.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+    }
+
+    #[test]
+    fn test_remove_then_insert() {
+        let mut collection = Collection::from_paths([path("Cargo.toml"), path("src")]).unwrap();
+        assert_eq!(collection.get_document_id(&path("Cargo.toml")), Some(0));
+        assert_eq!(collection.get_document_id(&path("src")), Some(1));
+
+        collection.remove(&path("Cargo.toml"));
+        collection.insert(path("src/lib.rs")).unwrap();
+        assert_eq!(collection.get_document_id(&path("src/lib.rs")), Some(2));
+
+        // A removed document that is added again gets a new ID.
+        collection.insert(path("Cargo.toml")).unwrap();
+        assert_eq!(collection.get_document_id(&path("Cargo.toml")), Some(3));
+        assert_eq!(collection.get_document_id(&path("src")), Some(1));
+
+        let entry = collection.get(&path("src/lib.rs")).unwrap();
+        assert_eq!(entry.extension(), Some("rs"));
+        assert!(entry.size().is_some_and(|size| size > 0));
+        assert_eq!(collection.get(&path("src")).unwrap().extension(), None);
+    }
+
+    #[test]
+    fn test_ids_after_reload() {
+        let mut collection = Collection::from_paths([path("Cargo.toml"), path("src")]).unwrap();
+        collection.remove(&path("src"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("collection.json");
+        collection.into_file(&file).unwrap();
+        let mut collection = Collection::from_file(&file).unwrap();
+
+        collection.insert(path("src/lib.rs")).unwrap();
+        assert_eq!(collection.get_document_id(&path("src/lib.rs")), Some(2));
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut collection = Collection::from_paths([path("Cargo.toml"), path("src")]).unwrap();
+        let entry = collection.get_mut(&path("Cargo.toml")).unwrap();
+        assert!(entry.add_tag("project:alpha"));
+        assert!(!entry.add_tag("project:alpha"));
+
+        // The tags are kept when the document is modified.
+        collection.update_modified(&path("Cargo.toml")).unwrap();
+        assert!(collection.get(&path("Cargo.toml")).unwrap().has_tag("project:alpha"));
+
+        // The tags are kept when the collection is built again.
+        let mut rebuilt = Collection::from_paths([path("src"), path("Cargo.toml")]).unwrap();
+        rebuilt.keep_tags(&collection);
+        let entry = rebuilt.get(&path("Cargo.toml")).unwrap();
+        assert_eq!(entry.tags().iter().collect::<Vec<_>>(), ["project:alpha"]);
+        assert!(rebuilt.get(&path("src")).unwrap().tags().is_empty());
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("collection.new");
+        let contents = format!(r#"{{"version":{},"index":{{}},"next_id":0}}"#, VERSION + 1);
+        fs::write(&file, contents).unwrap();
+        let err = Collection::from_file(&file).err().unwrap();
+        assert!(matches!(err, Error::VersionMismatch { expected: VERSION, .. }));
+    }
+
+    #[test]
+    fn test_compressed_file() {
+        let mut collection = Collection::from_paths([path("Cargo.toml")]).unwrap();
+        collection.set_compression(Compression::Zstd);
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("collection.zst");
+        collection.into_file(&file).unwrap();
+        let bytes = fs::read(&file).unwrap();
+        assert_eq!(Compression::detect(&bytes), Compression::Zstd);
+        let collection = Collection::from_file(&file).unwrap();
+
+        assert_eq!(collection.compression, Compression::Zstd);
+        assert_eq!(collection.get_document_id(&path("Cargo.toml")), Some(0));
+    }
+
+    #[test]
+    fn test_roots() {
+        let mut collection =
+            Collection::from_paths([path("Cargo.toml"), path("src/lib.rs")]).unwrap();
+        collection.set_roots(vec![path(""), path("src")]);
+        let file = collection.clone().into_collection_file();
+        let documents = |root: &RootFile| {
+            let mut documents = root.documents.keys().cloned().collect::<Vec<_>>();
+            documents.sort();
+            documents
+        };
+        assert_eq!(documents(&file.roots[0]), [PathBuf::from("Cargo.toml")]);
+        assert_eq!(documents(&file.roots[1]), [PathBuf::from("lib.rs")]);
+        assert!(file.index.is_empty());
+
+        // The documents of a moved root are resolved against its new path.
+        let mut file = file;
+        file.roots[1].path = PathBuf::from("/moved");
+        let collection = Collection::from_collection_file(file);
+        assert_eq!(collection.roots(), [path(""), PathBuf::from("/moved")]);
+        assert_eq!(
+            collection.get_document_id(&PathBuf::from("/moved/lib.rs")),
+            Some(1)
+        );
+        assert_eq!(collection.get_document_id(&path("Cargo.toml")), Some(0));
+    }
+}
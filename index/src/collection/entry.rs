@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::collections::BTreeSet;
 use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
@@ -6,24 +7,58 @@ use serde::{Deserialize, Serialize};
 use documents::DocumentId;
 
 /// A struct representing an entry in the corpus index.
-/// It contains the document ID and the last time the document was modified.
+/// It contains the document ID, the last time the document was modified,
+/// the size and the extension of its file, and the title, the date, the URL
+/// and the tags of the document.
 ///
 /// The document ID is a unique identifier for each document in the corpus.
 /// The last modified time is used to determine if the document has been
-/// modified since the last indexing.
+/// modified since the last indexing. The size and the extension are used to
+/// filter the documents of a search without reading their files. Entries that
+/// were written before they were kept have neither. The title is read from
+/// the document when it is indexed, and is shown in the results of a search.
+/// The date is the one of a calendar event or a contact, which the date
+/// filters of a search match instead of the last modified time. The URL is
+/// the one of a fetched web page, which is shown in the results of a search
+/// instead of the path of its file. The tags are set by the user, and are
+/// kept as long as the document is in the corpus.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CollectionEntry {
     document_id: DocumentId,
     modified: SystemTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extension: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
 }
 
 impl CollectionEntry {
-    /// Creates a new `CollectionEntry` with specified document ID,
-    /// and the last time the document was modified.
-    pub fn new(document_id: DocumentId, modified: SystemTime) -> Self {
+    /// Creates a new `CollectionEntry` with specified document ID, the last
+    /// time the document was modified, and the size and the extension of its
+    /// file.
+    pub fn new(
+        document_id: DocumentId,
+        modified: SystemTime,
+        size: u64,
+        extension: Option<String>,
+    ) -> Self {
         Self {
             document_id,
             modified,
+            size: Some(size),
+            extension,
+            title: None,
+            date: None,
+            url: None,
+            tags: BTreeSet::new(),
         }
     }
 
@@ -37,6 +72,78 @@ impl CollectionEntry {
     pub fn document_id(&self) -> DocumentId {
         self.document_id
     }
+
+    /// Returns the size of the file of the document in bytes, at the time
+    /// that it was indexed, if it was kept.
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Returns the extension of the file of the document, without the dot,
+    /// if it has one and it was kept.
+    pub fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    /// Sets the title of the document, as it was read from its file.
+    pub fn with_title(self, title: Option<String>) -> Self {
+        Self { title, ..self }
+    }
+
+    /// Returns the title of the document, if it has one and it was kept.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Sets the date of the document, formatted as `YYYY-MM-DD`, as it was
+    /// read from its file.
+    pub fn with_date(self, date: Option<String>) -> Self {
+        Self { date, ..self }
+    }
+
+    /// Returns the date of the document, formatted as `YYYY-MM-DD`, if it
+    /// has one, like the start date of a calendar event or the birthday of a
+    /// contact.
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    /// Sets the URL that the document was fetched from.
+    pub fn with_url(self, url: Option<String>) -> Self {
+        Self { url, ..self }
+    }
+
+    /// Returns the URL that the document was fetched from, if it is a web
+    /// page.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Returns the tags of the document, in order.
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    /// Checks if the document has a tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// Adds a tag to the document. Returns `false` if it already had it.
+    pub fn add_tag(&mut self, tag: impl Into<String>) -> bool {
+        self.tags.insert(tag.into())
+    }
+
+    /// Removes a tag from the document. Returns `false` if it did not have
+    /// it.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        self.tags.remove(tag)
+    }
+
+    /// Replaces the tags of the document.
+    pub fn set_tags(&mut self, tags: BTreeSet<String>) {
+        self.tags = tags;
+    }
 }
 
 impl Ord for CollectionEntry {
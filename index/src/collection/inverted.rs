@@ -1,32 +1,66 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use anyhow::Context;
-
 use documents::DocumentId;
 
 use crate::collection::Collection;
+use crate::error::Result;
 
-/// A structure that maps document IDs to their path.
+/// A structure that maps document IDs to their path, and to their title and
+/// the URL they were fetched from if they have them.
 pub struct InvertedCollection {
     inner: HashMap<DocumentId, PathBuf>,
+    titles: HashMap<DocumentId, String>,
+    urls: HashMap<DocumentId, String>,
 }
 
 impl InvertedCollection {
     /// Creates an `InvertedCollection` from a `Collection` file.
-    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let collection =
-            Collection::from_file(path).context("Failed to load collection from file.")?;
-        let inv = collection
-            .into_iter()
-            .map(|(path, entry)| (entry.document_id(), path.clone()))
-            .collect::<HashMap<DocumentId, PathBuf>>();
-
-        Ok(InvertedCollection { inner: inv })
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let collection = Collection::from_file(path)?;
+        let mut inv = HashMap::new();
+        let mut titles = HashMap::new();
+        let mut urls = HashMap::new();
+        for (path, entry) in collection {
+            if let Some(title) = entry.title() {
+                titles.insert(entry.document_id(), title.to_string());
+            }
+            if let Some(url) = entry.url() {
+                urls.insert(entry.document_id(), url.to_string());
+            }
+            inv.insert(entry.document_id(), path);
+        }
+
+        Ok(InvertedCollection {
+            inner: inv,
+            titles,
+            urls,
+        })
     }
 
     /// Returns the path of the document with specified document ID.
     pub fn get_path(&self, doc_id: DocumentId) -> Option<&PathBuf> {
         self.inner.get(&doc_id)
     }
+
+    /// Returns the title of the document with specified document ID, which is
+    /// the name of its file without the extension if it has no title.
+    pub fn get_title(&self, doc_id: DocumentId) -> Option<&str> {
+        if let Some(title) = self.titles.get(&doc_id) {
+            return Some(title);
+        }
+        let path = self.inner.get(&doc_id)?;
+        Some(path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default())
+    }
+
+    /// Returns the URL that the document with specified document ID was
+    /// fetched from, if it is a web page.
+    pub fn get_url(&self, doc_id: DocumentId) -> Option<&str> {
+        self.urls.get(&doc_id).map(String::as_str)
+    }
+
+    /// Returns an iterator over the document IDs and their paths.
+    pub fn iter(&self) -> impl Iterator<Item = (DocumentId, &PathBuf)> {
+        self.inner.iter().map(|(doc_id, path)| (*doc_id, path))
+    }
 }
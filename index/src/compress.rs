@@ -0,0 +1,128 @@
+//! Compression of the files of the index.
+//!
+//! Compressed files are recognized by the magic number of their format, so
+//! they are decompressed when they are read, whatever the compression that
+//! the index is configured with, and files of both kinds can be mixed.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// The magic number that zstd frames start with.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// The compression level of zstd. Level 3 is the default of zstd, which is
+/// fast to compress and still much smaller than the JSON files.
+const ZSTD_LEVEL: i32 = 3;
+
+/// The compression of the files of the index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// The files are written as they are.
+    #[default]
+    None,
+    /// The files are compressed with zstd.
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the extension that is added to the names of files with this
+    /// compression, if any.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    /// Compresses the contents of a file.
+    pub fn compress(self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes),
+            Compression::Zstd => zstd::encode_all(bytes.as_slice(), ZSTD_LEVEL),
+        }
+    }
+
+    /// Wraps a writer, so that what is written to it is compressed as it is
+    /// written, without holding the whole file in memory.
+    pub fn writer<W: Write>(self, writer: W) -> io::Result<CompressedWriter<W>> {
+        match self {
+            Compression::None => Ok(CompressedWriter::None(writer)),
+            Compression::Zstd => zstd::Encoder::new(writer, ZSTD_LEVEL).map(CompressedWriter::Zstd),
+        }
+    }
+
+    /// Detects the compression of the contents of a file.
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.starts_with(&ZSTD_MAGIC) {
+            true => Compression::Zstd,
+            false => Compression::None,
+        }
+    }
+}
+
+/// A writer that compresses what is written to it, before it is written to
+/// the writer it wraps. It has to be finished once everything is written.
+pub enum CompressedWriter<W: Write> {
+    None(W),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Writes the end of the compressed data, and returns the writer it
+    /// wraps.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            CompressedWriter::None(writer) => Ok(writer),
+            CompressedWriter::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::None(writer) => writer.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::None(writer) => writer.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Decompresses the contents of a file, in the compression that they are
+/// detected to have. Contents that are not compressed are returned as they
+/// are.
+pub fn decompress(bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    match Compression::detect(&bytes) {
+        Compression::None => Ok(bytes),
+        Compression::Zstd => zstd::decode_all(bytes.as_slice()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let bytes = br#"{"budget": [1, 2, 3], "meeting": [2, 3]}"#.repeat(100);
+        let compressed = Compression::Zstd.compress(bytes.clone()).unwrap();
+        assert!(compressed.len() < bytes.len());
+        assert_eq!(Compression::detect(&compressed), Compression::Zstd);
+        assert_eq!(decompress(compressed).unwrap(), bytes);
+        assert_eq!(Compression::detect(&bytes), Compression::None);
+        assert_eq!(decompress(bytes.clone()).unwrap(), bytes);
+
+        let mut writer = Compression::Zstd.writer(vec![]).unwrap();
+        writer.write_all(&bytes).unwrap();
+        let compressed = writer.finish().unwrap();
+        assert_eq!(decompress(compressed).unwrap(), bytes);
+    }
+}
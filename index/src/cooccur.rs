@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use documents::DocumentId;
+use tokenize::Token;
+
+use crate::codec::{
+    read_delta, read_header, read_str, read_u32, read_varint, write_header, write_str, write_u32,
+    write_varint,
+};
+use crate::error::{Error, Result};
+
+const SIGNATURE: &[u8] = b"SEARCOOC";
+const VERSION: u8 = 1;
+
+/// A compact sketch of the documents that each term is in, from which the
+/// terms that co-occur with a term are estimated.
+///
+/// The sketch of a term is a bottom-k MinHash of its documents, that is, the
+/// `size` smallest hashes of their IDs. Two sketches estimate the Jaccard
+/// similarity of the documents of their terms, the share of the documents
+/// with either term that have both. The sketches of terms that are in at
+/// most `size` documents are exact, so the sketch of a collection takes at
+/// most `size` integers per term, however large the collection is.
+#[derive(Debug, Default)]
+pub struct CooccurrenceSketch {
+    size: usize,
+    terms: BTreeMap<Token, TermSketch>,
+}
+
+/// The sketch of the documents of a term.
+#[derive(Debug, Clone, PartialEq)]
+struct TermSketch {
+    /// The number of documents that the term is in.
+    n_docs: u32,
+    /// The smallest hashes of the IDs of the documents, in ascending order.
+    hashes: Vec<u32>,
+}
+
+/// A term that co-occurs with another term, with estimates of how often.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelatedTerm {
+    pub term: Token,
+    /// The estimated Jaccard similarity of the documents of the terms.
+    pub similarity: f64,
+    /// The estimated number of documents that have both terms.
+    pub n_docs: u32,
+}
+
+impl CooccurrenceSketch {
+    /// Creates a new, empty sketch, that keeps `size` hashes per term.
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of terms in the sketch.
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// Checks if the sketch has no terms.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Checks if a term is in the sketch.
+    pub fn contains(&self, term: &str) -> bool {
+        self.terms.contains_key(term)
+    }
+
+    /// Adds a term with the IDs of the documents it is in, replacing its
+    /// previous sketch.
+    pub fn insert(&mut self, term: Token, doc_ids: &[DocumentId]) {
+        let mut hashes = doc_ids
+            .iter()
+            .map(|doc_id| hash(*doc_id))
+            .collect::<Vec<_>>();
+        hashes.sort_unstable();
+        hashes.dedup();
+        let n_docs = hashes.len() as u32;
+        hashes.truncate(self.size);
+        self.terms.insert(term, TermSketch { n_docs, hashes });
+    }
+
+    /// Finds the `top_k` terms whose documents are the most similar to the
+    /// documents of a term.
+    ///
+    /// # Returns
+    ///
+    /// The related terms, sorted by descending similarity, and then by the
+    /// number of documents they share with the term. Terms that share no
+    /// documents with it are left out, as is the term itself.
+    pub fn related(&self, term: &str, top_k: usize) -> Vec<RelatedTerm> {
+        let Some(sketch) = self.terms.get(term) else {
+            return vec![];
+        };
+        let mut related = self
+            .terms
+            .iter()
+            .filter(|(other, _)| other.as_str() != term)
+            .filter_map(|(other, other_sketch)| {
+                let similarity = sketch.similarity(other_sketch, self.size);
+                // |A ∪ B| = (|A| + |B|) / (1 + J), and |A ∩ B| = J |A ∪ B|.
+                let union = f64::from(sketch.n_docs + other_sketch.n_docs) / (1.0 + similarity);
+                (similarity > 0.0).then(|| RelatedTerm {
+                    term: other.clone(),
+                    similarity,
+                    n_docs: (similarity * union).round() as u32,
+                })
+            })
+            .collect::<Vec<_>>();
+        related.sort_by(|a, b| {
+            b.similarity
+                .total_cmp(&a.similarity)
+                .then_with(|| b.n_docs.cmp(&a.n_docs))
+                .then_with(|| a.term.cmp(&b.term))
+        });
+        related.truncate(top_k);
+        related
+    }
+
+    /// Writes the sketch to file, in a binary format. The header is followed
+    /// by the size of the sketches, the number of terms, and each term with
+    /// the number of its documents and its hashes. The hashes are sorted, so
+    /// they are written as gaps with variable-byte encoding.
+    pub fn into_file(self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        self.write_to(&mut BufWriter::new(file))
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Loads the sketch from file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(Error::at(path))?;
+        Self::read_from(&mut BufReader::new(file)).map_err(Error::at(path))
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_header(writer, SIGNATURE, VERSION)?;
+        write_u32(writer, self.size as u32)?;
+        write_u32(writer, self.terms.len() as u32)?;
+        for (term, sketch) in &self.terms {
+            write_str(writer, term)?;
+            write_varint(writer, sketch.n_docs)?;
+            write_varint(writer, sketch.hashes.len() as u32)?;
+            let mut prev = 0;
+            for hash in &sketch.hashes {
+                write_varint(writer, hash - prev)?;
+                prev = *hash;
+            }
+        }
+        writer.flush()
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        read_header(reader, SIGNATURE, VERSION)?;
+        let size = read_u32(reader)? as usize;
+        let n_terms = read_u32(reader)?;
+        let mut terms = BTreeMap::new();
+        for _ in 0..n_terms {
+            let term = read_str(reader)?;
+            let n_docs = read_varint(reader)?;
+            let n_hashes = read_varint(reader)?;
+            let mut hashes = Vec::with_capacity(n_hashes as usize);
+            let mut prev = 0;
+            for _ in 0..n_hashes {
+                prev = read_delta(reader, prev)?;
+                hashes.push(prev);
+            }
+            terms.insert(term, TermSketch { n_docs, hashes });
+        }
+        Ok(Self { size, terms })
+    }
+}
+
+impl TermSketch {
+    /// Estimates the Jaccard similarity of the documents of two terms, by
+    /// the share of the `size` smallest hashes of both sketches together
+    /// that are in both.
+    ///
+    /// A sketch that was truncated has `size` hashes, so the smallest hashes
+    /// of both are never past its last hash, and whether they are in both
+    /// sketches is known.
+    fn similarity(&self, other: &Self, size: usize) -> f64 {
+        let (mut i, mut j) = (0, 0);
+        let (mut union, mut shared) = (0, 0);
+        while union < size {
+            match (self.hashes.get(i), other.hashes.get(j)) {
+                (Some(a), Some(b)) if a == b => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+                (Some(a), Some(b)) if a < b => i += 1,
+                (Some(_), Some(_)) => j += 1,
+                (Some(_), None) => i += 1,
+                (None, Some(_)) => j += 1,
+                (None, None) => break,
+            }
+            union += 1;
+        }
+        match union {
+            0 => 0.0,
+            _ => shared as f64 / union as f64,
+        }
+    }
+}
+
+/// Hashes a document ID with the finalizer of MurmurHash3, which mixes its
+/// bits, and maps different IDs to different hashes.
+fn hash(doc_id: DocumentId) -> u32 {
+    let mut hash = doc_id;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_related_terms() {
+        let mut sketch = CooccurrenceSketch::new(8);
+        sketch.insert("budget".to_string(), &[0, 1, 2, 3]);
+        sketch.insert("quarterly".to_string(), &[0, 1, 2]);
+        sketch.insert("meeting".to_string(), &[3, 4]);
+        sketch.insert("recipe".to_string(), &[5]);
+
+        let related = sketch.related("budget", 10);
+        assert_eq!(related.len(), 2);
+        assert_eq!(related[0].term, "quarterly");
+        assert_eq!(related[0].similarity, 0.75);
+        assert_eq!(related[0].n_docs, 3);
+        assert_eq!(related[1].term, "meeting");
+        assert_eq!(related[1].n_docs, 1);
+        assert_eq!(sketch.related("budget", 1).len(), 1);
+        assert!(sketch.related("missing", 10).is_empty());
+
+        // Sketches of terms in more documents than their size are estimates.
+        let mut sketch = CooccurrenceSketch::new(16);
+        let evens = (0..1000).step_by(2).collect::<Vec<_>>();
+        sketch.insert("all".to_string(), &(0..1000).collect::<Vec<_>>());
+        sketch.insert("even".to_string(), &evens);
+        let related = sketch.related("all", 1);
+        assert!((0.2..=0.8).contains(&related[0].similarity));
+    }
+
+    #[test]
+    fn test_binary_format() {
+        let mut sketch = CooccurrenceSketch::new(2);
+        sketch.insert("budget".to_string(), &[7, 3, 1, 7]);
+        let mut buffer = vec![];
+        sketch.write_to(&mut buffer).unwrap();
+        let loaded = CooccurrenceSketch::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(loaded.size, 2);
+        assert_eq!(loaded.terms, sketch.terms);
+        assert_eq!(loaded.terms["budget"].n_docs, 3);
+    }
+}
@@ -46,6 +46,16 @@ impl DocumentFrequencyIndex {
         }
     }
 
+    /// Indexes an iterator of tokens of another field of the document, like
+    /// its title, or its terms in their original case. The tokens are not
+    /// counted in the total number of terms of the document, so the length
+    /// of the document does not change.
+    pub fn index_field_tokens(&mut self, tokens: impl IntoIterator<Item = Token>) {
+        for token in tokens {
+            *self.index.entry(token).or_insert(0) += 1;
+        }
+    }
+
     /// Returns the ID of the document that the document index is
     /// referring to.
     pub fn doc_id(&self) -> DocumentId {
@@ -57,6 +67,11 @@ impl DocumentFrequencyIndex {
         self.n_terms
     }
 
+    /// Returns the distinct terms of the document, in no particular order.
+    pub fn terms(&self) -> impl Iterator<Item = &Token> + '_ {
+        self.index.keys()
+    }
+
     /// Returns the number of occurrences of a term in the document.
     pub fn term_count(&self, term: Token) -> u32 {
         *self.index.get(&term).unwrap_or(&0)
@@ -1,15 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
 
 use serde::{Deserialize, Serialize};
 
 use documents::DocumentId;
 
+use crate::codec::{read_delta, read_varint, write_varint};
+
 /// Stores the number of terms for each document, specified
 /// by their document ID.
 #[derive(Default, Debug, Deserialize, Serialize)]
 pub(crate) struct DocumentTermsCounter {
     inner: HashMap<u32, u32>,
-    total: u32,
+    total: u64,
 }
 
 impl DocumentTermsCounter {
@@ -17,7 +20,28 @@ impl DocumentTermsCounter {
     /// document ID.
     pub(crate) fn insert_doc_terms(&mut self, doc_id: DocumentId, n_terms: u32) {
         self.inner.insert(doc_id, n_terms);
-        self.total += n_terms;
+        self.total += n_terms as u64;
+    }
+
+    /// Removes the number of terms for a document with specified
+    /// document ID. If the document is not present, nothing is done.
+    pub(crate) fn remove_doc_terms(&mut self, doc_id: DocumentId) {
+        if let Some(n_terms) = self.inner.remove(&doc_id) {
+            self.total -= n_terms as u64;
+        }
+    }
+
+    /// Merges the counts of another counter into this one.
+    pub(crate) fn merge(&mut self, other: Self) {
+        for (doc_id, n_terms) in other.inner {
+            self.remove_doc_terms(doc_id);
+            self.insert_doc_terms(doc_id, n_terms);
+        }
+    }
+
+    /// Checks if the counter has the number of terms of a document.
+    pub(crate) fn contains(&self, doc_id: DocumentId) -> bool {
+        self.inner.contains_key(&doc_id)
     }
 
     /// Returns the total number of terms in a document with a
@@ -27,15 +51,48 @@ impl DocumentTermsCounter {
         *self.inner.get(&doc_id).unwrap_or(&0)
     }
 
+    /// Returns the IDs of the documents that the counter has.
+    pub(crate) fn doc_ids(&self) -> impl Iterator<Item = DocumentId> + '_ {
+        self.inner.keys().copied()
+    }
+
     /// Returns the number of documents in the collection.
     pub(crate) fn n_docs(&self) -> u32 {
         self.inner.len() as u32
     }
 
-    /// Returns the total number of terms in all the documents.
-    pub(crate) fn n_terms_total(&self) -> u32 {
+    /// Returns the total number of terms in all the documents, which can
+    /// outgrow the number of terms of any one document.
+    pub(crate) fn n_terms_total(&self) -> u64 {
         self.total
     }
+
+    /// Writes the counter in the binary format. The number of documents is
+    /// followed by the pairs of document ID and number of terms, sorted by
+    /// document ID. The document IDs are written as gaps from the previous
+    /// one, and all numbers with variable-byte encoding.
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_varint(writer, self.inner.len() as u32)?;
+        let mut prev_doc_id = 0;
+        for (doc_id, n_terms) in self.inner.iter().collect::<BTreeMap<_, _>>() {
+            write_varint(writer, doc_id - prev_doc_id)?;
+            write_varint(writer, *n_terms)?;
+            prev_doc_id = *doc_id;
+        }
+        Ok(())
+    }
+
+    /// Reads a counter that was written in the binary format.
+    pub(crate) fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut counter = Self::default();
+        let mut doc_id = 0u32;
+        for _ in 0..read_varint(reader)? {
+            doc_id = read_delta(reader, doc_id)?;
+            let n_terms = read_varint(reader)?;
+            counter.insert_doc_terms(doc_id, n_terms);
+        }
+        Ok(counter)
+    }
 }
 
 #[cfg(test)]
@@ -50,4 +107,27 @@ mod tests {
         assert_eq!(counter.n_terms(0), 10);
         assert_eq!(counter.n_terms(1), 20);
     }
+
+    #[test]
+    fn test_remove_doc_terms() {
+        let mut counter = DocumentTermsCounter::default();
+        counter.insert_doc_terms(0, 10);
+        counter.insert_doc_terms(1, 20);
+        counter.remove_doc_terms(0);
+        counter.remove_doc_terms(2);
+        assert_eq!(counter.n_docs(), 1);
+        assert_eq!(counter.n_terms(0), 0);
+        assert_eq!(counter.n_terms_total(), 20);
+    }
+
+    #[test]
+    fn test_n_terms_total() {
+        let mut counter = DocumentTermsCounter::default();
+        for doc_id in 0..3 {
+            counter.insert_doc_terms(doc_id, u32::MAX);
+        }
+        assert_eq!(counter.n_terms_total(), 3 * u32::MAX as u64);
+        counter.remove_doc_terms(1);
+        assert_eq!(counter.n_terms_total(), 2 * u32::MAX as u64);
+    }
 }
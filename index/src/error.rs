@@ -0,0 +1,217 @@
+//! The errors of the index, which applications can match on to react to
+//! them, like by indexing a repository again when its files were written in
+//! another version of the format.
+//!
+//! The functions that read or write the files of the index return an
+//! [`Error`] with the path of the file. The functions that only read from a
+//! reader, or write to a writer, return an [`io::Error`], like the readers
+//! and writers of the standard library.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use documents::DocumentId;
+
+/// A specialized `Result` type for the operations of the index.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error of the index.
+#[derive(Debug)]
+pub enum Error {
+    /// The file is not a file of the index.
+    NotAnIndex { path: PathBuf },
+    /// The file was written in another version of the format of the index.
+    VersionMismatch {
+        path: PathBuf,
+        expected: u8,
+        found: u8,
+    },
+    /// The file is damaged or cut short, so it cannot be read.
+    Corrupt { path: PathBuf, reason: String },
+    /// The repository is locked by another running process, with its ID if
+    /// it could be read.
+    Locked { path: PathBuf, pid: Option<u32> },
+    /// The text of a document could not be extracted from its file.
+    ExtractionFailed {
+        path: PathBuf,
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    /// The vector of a document has a different dimension from the vectors
+    /// of its index.
+    DimensionMismatch {
+        doc_id: DocumentId,
+        expected: usize,
+        found: usize,
+    },
+    /// A file could not be read or written.
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl Error {
+    /// Returns a function that converts an I/O error that occurred on a file
+    /// into an error of the index, to be used with `map_err`.
+    pub(crate) fn at(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |err| Self::from_io(path, err)
+    }
+
+    /// Converts an I/O error that occurred on a file into an error of the
+    /// index. Invalid or missing data means that the file is corrupt, unless
+    /// its header was found to be wrong.
+    pub(crate) fn from_io(path: &Path, err: io::Error) -> Self {
+        let path = path.to_path_buf();
+        match err.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                match err
+                    .get_ref()
+                    .and_then(|err| err.downcast_ref::<FormatError>())
+                {
+                    Some(FormatError::NotAnIndex) => Self::NotAnIndex { path },
+                    Some(&FormatError::VersionMismatch { expected, found }) => {
+                        Self::VersionMismatch {
+                            path,
+                            expected,
+                            found,
+                        }
+                    }
+                    None => Self::Corrupt {
+                        path,
+                        reason: err.to_string(),
+                    },
+                }
+            }
+            _ => Self::Io { path, source: err },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnIndex { path } => {
+                write!(f, "File is not a searchine index file: {}", path.display())
+            }
+            Self::VersionMismatch {
+                path,
+                expected,
+                found,
+            } if found < expected => write!(
+                f,
+                "File was written in version {found} of the index format, but version \
+                 {expected} is supported: {}. Run `searchine migrate` to upgrade the index, \
+                 or `searchine index --full` to build it again.",
+                path.display()
+            ),
+            Self::VersionMismatch {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "File was written in version {found} of the index format, which is newer \
+                 than version {expected}: {}. Upgrade searchine, or run \
+                 `searchine index --full` to build the index again.",
+                path.display()
+            ),
+            Self::Corrupt { path, reason } => {
+                write!(f, "File is corrupt: {}. {reason}", path.display())
+            }
+            Self::Locked { path, pid } => {
+                write!(f, "Repository is locked by another searchine process")?;
+                if let Some(pid) = pid {
+                    write!(f, " (PID {pid})")?;
+                }
+                write!(f, ": {}. Try again once it finishes.", path.display())
+            }
+            Self::ExtractionFailed { path, .. } => {
+                write!(f, "Failed to read file {}", path.display())
+            }
+            Self::DimensionMismatch {
+                doc_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Vector of document {doc_id} has dimension {found}, but the index has \
+                 dimension {expected}."
+            ),
+            Self::Io { path, .. } => write!(f, "Failed to access file: {}", path.display()),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::ExtractionFailed { source, .. } => Some(source.as_ref()),
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// An error in the header of a binary file, which is carried inside an I/O
+/// error by the readers, so that it can be told apart from other invalid
+/// data once the path of the file is known.
+#[derive(Debug)]
+pub(crate) enum FormatError {
+    /// The file does not start with the signature.
+    NotAnIndex,
+    /// The file has another version.
+    VersionMismatch { expected: u8, found: u8 },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnIndex => write!(f, "File is not a searchine binary file."),
+            Self::VersionMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Unsupported version: {found}. Expected version: {expected}."
+                )
+            }
+        }
+    }
+}
+
+impl StdError for FormatError {}
+
+impl From<FormatError> for io::Error {
+    fn from(err: FormatError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io() {
+        let path = Path::new("index.bin");
+        let err = Error::from_io(path, FormatError::NotAnIndex.into());
+        assert!(matches!(err, Error::NotAnIndex { .. }));
+
+        let version = FormatError::VersionMismatch {
+            expected: 3,
+            found: 2,
+        };
+        let err = Error::from_io(path, version.into());
+        assert!(matches!(
+            err,
+            Error::VersionMismatch {
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+
+        let err = Error::from_io(path, io::ErrorKind::UnexpectedEof.into());
+        assert!(matches!(err, Error::Corrupt { .. }));
+
+        let err = Error::from_io(path, io::ErrorKind::NotFound.into());
+        assert!(matches!(err, Error::Io { .. }));
+    }
+}
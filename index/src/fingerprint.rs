@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+
+use documents::DocumentId;
+
+/// The number of consecutive words that make up each shingle of a text.
+const SHINGLE_SIZE: usize = 3;
+
+/// The number of bands that a fingerprint is split into, to find the
+/// fingerprints that are close to each other without comparing them all.
+const BANDS: u32 = 4;
+
+/// A SimHash fingerprint of the text of a document, from which documents
+/// with near-identical texts are found.
+///
+/// Each shingle of the text, a run of consecutive words, is hashed, and
+/// each bit of the fingerprint is the majority of the same bit of the
+/// hashes. Texts that share most of their shingles have fingerprints that
+/// differ in few bits, so the number of bits that two fingerprints differ
+/// in, their distance, estimates how different the texts are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// Computes the fingerprint of a text, or returns `None` if the text has
+    /// no words. The words are compared in lowercase, so the fingerprint does
+    /// not depend on the case, the punctuation or the layout of the text.
+    pub fn of_text(text: &str) -> Option<Self> {
+        let words = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if words.is_empty() {
+            return None;
+        }
+        let mut weights = [0i64; 64];
+        for shingle in words.windows(SHINGLE_SIZE.min(words.len())) {
+            let hash = hash(&shingle.join(" "));
+            for (bit, weight) in weights.iter_mut().enumerate() {
+                match (hash >> bit) & 1 {
+                    1 => *weight += 1,
+                    _ => *weight -= 1,
+                }
+            }
+        }
+        let bits = weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0)
+            .fold(0, |bits, (bit, _)| bits | (1 << bit));
+        Some(Self(bits))
+    }
+
+    /// Creates a fingerprint from its bits.
+    pub fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the bits of the fingerprint.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the number of bits that the fingerprint differs in from
+    /// another fingerprint.
+    pub fn distance(&self, other: &Self) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+/// Groups the documents whose fingerprints are at most `max_distance` bits
+/// apart into clusters of near-duplicates. A document is in the cluster of
+/// any document that it is close to, so the documents of a cluster may be
+/// further apart than `max_distance` from each other.
+///
+/// The fingerprints are split into bands, and only the fingerprints that
+/// have a band in common are compared. Two fingerprints that are at most
+/// `BANDS - 1` bits apart have at least one band in common, so for larger
+/// distances some near-duplicates may be missed.
+///
+/// # Returns
+///
+/// The clusters with more than one document, each sorted by document ID, in
+/// the order of their first document.
+pub fn cluster(
+    fingerprints: &[(DocumentId, Fingerprint)],
+    max_distance: u32,
+) -> Vec<Vec<DocumentId>> {
+    let band_bits = 64 / BANDS;
+    let mut buckets = HashMap::<(u32, u64), Vec<usize>>::new();
+    for (i, (_, fingerprint)) in fingerprints.iter().enumerate() {
+        for band in 0..BANDS {
+            let value = (fingerprint.bits() >> (band * band_bits)) & ((1 << band_bits) - 1);
+            buckets.entry((band, value)).or_default().push(i);
+        }
+    }
+
+    let mut parents = (0..fingerprints.len()).collect::<Vec<_>>();
+    for bucket in buckets.values() {
+        for (n, &i) in bucket.iter().enumerate() {
+            for &j in &bucket[n + 1..] {
+                if fingerprints[i].1.distance(&fingerprints[j].1) <= max_distance {
+                    let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+                    parents[root_i.max(root_j)] = root_i.min(root_j);
+                }
+            }
+        }
+    }
+
+    let mut clusters = BTreeMap::<DocumentId, Vec<DocumentId>>::new();
+    let mut roots = HashMap::<usize, Vec<DocumentId>>::new();
+    for (i, (doc_id, _)) in fingerprints.iter().enumerate() {
+        let root = find(&mut parents, i);
+        roots.entry(root).or_default().push(*doc_id);
+    }
+    for mut doc_ids in roots.into_values().filter(|doc_ids| doc_ids.len() > 1) {
+        doc_ids.sort_unstable();
+        clusters.insert(doc_ids[0], doc_ids);
+    }
+    clusters.into_values().collect()
+}
+
+/// Finds the root of the set of an element, compressing the path to it.
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Hashes a shingle with 64-bit FNV-1a, which does not change across
+/// versions of Rust, unlike the hasher of the standard library, so the
+/// fingerprints that are stored stay comparable.
+fn hash(shingle: &str) -> u64 {
+    shingle.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "The budget of the project for the next quarter covers the salaries \
+        of the team, the rent of the office, the licenses of the tools, and the travel \
+        to the conference in the spring.";
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = Fingerprint::of_text(TEXT).unwrap();
+        let reformatted = TEXT.to_uppercase().replace(", ", ",\n");
+        assert_eq!(Fingerprint::of_text(&reformatted), Some(fingerprint));
+
+        let edited = TEXT.replace("spring", "autumn");
+        let distance = fingerprint.distance(&Fingerprint::of_text(&edited).unwrap());
+        assert!(distance < 16);
+
+        let other = Fingerprint::of_text("A recipe for bread with flour, water and salt.");
+        assert!(fingerprint.distance(&other.unwrap()) > distance);
+        assert_eq!(Fingerprint::of_text(" -- "), None);
+    }
+
+    #[test]
+    fn test_cluster() {
+        let fingerprints = [
+            (0, Fingerprint::from_bits(0b1111)),
+            (1, Fingerprint::from_bits(u64::MAX)),
+            (2, Fingerprint::from_bits(0b0111)),
+            (3, Fingerprint::from_bits(0b0011 | (1 << 40))),
+            (4, Fingerprint::from_bits(u64::MAX ^ 1)),
+            (5, Fingerprint::from_bits(1 << 20)),
+        ];
+        assert_eq!(cluster(&fingerprints, 1), vec![vec![0, 2], vec![1, 4]]);
+        assert_eq!(cluster(&fingerprints, 2), vec![vec![0, 2, 3], vec![1, 4]]);
+        assert_eq!(cluster(&fingerprints, 0), Vec::<Vec<DocumentId>>::new());
+    }
+}
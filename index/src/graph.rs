@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use documents::DocumentId;
+
+/// The graph of the links between the documents of a collection, from which
+/// the importance of each document is estimated, independently of queries.
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    /// The documents of the graph, with the documents that each links to.
+    links: BTreeMap<DocumentId, BTreeSet<DocumentId>>,
+}
+
+impl LinkGraph {
+    /// Creates a new, empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a document to the graph, without links.
+    pub fn add_document(&mut self, doc_id: DocumentId) {
+        self.links.entry(doc_id).or_default();
+    }
+
+    /// Adds a link between two documents, adding them to the graph if they
+    /// are not in it. Links of a document to itself, and repeated links, are
+    /// not counted.
+    pub fn add_link(&mut self, from: DocumentId, to: DocumentId) {
+        self.add_document(to);
+        let links = self.links.entry(from).or_default();
+        if from != to {
+            links.insert(to);
+        }
+    }
+
+    /// Returns the number of documents in the graph.
+    pub fn n_docs(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Returns the number of links between different documents.
+    pub fn n_links(&self) -> usize {
+        self.links.values().map(BTreeSet::len).sum()
+    }
+
+    /// Computes the PageRank of the documents: the share of the time that a
+    /// reader who follows the links, and jumps to any document with
+    /// probability `1 - damping` at each step, spends on each document.
+    /// Documents without links are read as if they linked to every document.
+    ///
+    /// # Returns
+    ///
+    /// The rank of each document, scaled by the number of documents, so that
+    /// the average rank is 1. Documents that are linked to by many important
+    /// documents rank above 1, and documents that no document links to rank
+    /// below it.
+    pub fn pagerank(&self, damping: f64, iterations: usize) -> BTreeMap<DocumentId, f64> {
+        let n = self.links.len();
+        if n == 0 {
+            return BTreeMap::new();
+        }
+        let positions = self
+            .links
+            .keys()
+            .enumerate()
+            .map(|(i, doc_id)| (*doc_id, i))
+            .collect::<BTreeMap<_, _>>();
+        let links = self
+            .links
+            .values()
+            .map(|targets| targets.iter().map(|to| positions[to]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut ranks = vec![1.0 / n as f64; n];
+        for _ in 0..iterations {
+            let dangling = links
+                .iter()
+                .zip(&ranks)
+                .filter(|(targets, _)| targets.is_empty())
+                .map(|(_, rank)| rank)
+                .sum::<f64>();
+            let base = (1.0 - damping + damping * dangling) / n as f64;
+            let mut next = vec![base; n];
+            for (targets, rank) in links.iter().zip(&ranks) {
+                let share = damping * rank / targets.len() as f64;
+                for to in targets {
+                    next[*to] += share;
+                }
+            }
+            ranks = next;
+        }
+        self.links
+            .keys()
+            .zip(ranks)
+            .map(|(doc_id, rank)| (*doc_id, rank * n as f64))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pagerank() {
+        let mut graph = LinkGraph::new();
+        // Three pages link to a hub, which links back to one of them, and a
+        // copy of the hub is linked to by none.
+        for from in [1, 2, 3] {
+            graph.add_link(from, 0);
+        }
+        graph.add_link(0, 1);
+        graph.add_link(0, 0);
+        graph.add_document(4);
+        assert_eq!(graph.n_docs(), 5);
+        assert_eq!(graph.n_links(), 4);
+
+        let ranks = graph.pagerank(0.85, 50);
+        assert!((ranks.values().sum::<f64>() - 5.0).abs() < 1e-9);
+        assert!(ranks[&0] > ranks[&1]);
+        assert!(ranks[&1] > ranks[&2]);
+        assert!((ranks[&2] - ranks[&4]).abs() < 1e-9);
+        assert!(ranks[&4] < 1.0);
+        assert!(LinkGraph::new().pagerank(0.85, 50).is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+
+use crate::compress::Compression;
+use crate::doc::freq::DocumentFrequencyIndex;
+use crate::error::{Error, Result};
+use crate::inverted::freq::FrequencyIndex;
+use crate::inverted::layout::{self, IndexFile};
+use crate::postings::PostingsOrder;
+
+/// The estimated number of bytes that a posting takes in memory, with its
+/// share of the tables of the index, besides the bytes of its term.
+const POSTING_BYTES: usize = 48;
+
+/// A builder of an index, which indexes documents in memory, within an
+/// optional budget of memory.
+///
+/// Once the postings in memory are estimated to take more than the budget,
+/// they are spilled to a run, which is an index file in a directory of
+/// runs, and indexing starts over with an empty index. The runs have the
+/// terms of their dictionaries sorted, so they are merged into the final
+/// index file one term at a time, and only the number of terms of each
+/// document and the postings lists of one term are in memory as they are
+/// merged. The runs are removed when the builder is dropped.
+#[derive(Default)]
+pub struct IndexBuilder {
+    index: FrequencyIndex,
+    /// The number of bytes that the index in memory is estimated to take.
+    used_bytes: usize,
+    /// The budget of memory in bytes, and the directory of the runs.
+    budget: Option<(usize, PathBuf)>,
+    /// The paths to the runs that were spilled, in order.
+    runs: Vec<PathBuf>,
+}
+
+impl IndexBuilder {
+    /// Creates a builder that keeps the whole index in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the budget of memory in bytes of the index, and the directory
+    /// that the runs are spilled to once it is exceeded, which is created if
+    /// it does not exist.
+    pub fn with_memory_budget(mut self, bytes: usize, runs_dir: impl Into<PathBuf>) -> Self {
+        self.budget = Some((bytes, runs_dir.into()));
+        self
+    }
+
+    /// Returns the number of runs that were spilled so far.
+    pub fn n_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Indexes a document index with frequency postings, and spills the
+    /// index to a run if it exceeds the budget of memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the run cannot be written.
+    pub fn index(&mut self, doc_index: DocumentFrequencyIndex) -> Result<()> {
+        self.used_bytes += doc_index
+            .terms()
+            .map(|term| term.len() + POSTING_BYTES)
+            .sum::<usize>();
+        self.index.index(doc_index);
+        match &self.budget {
+            Some((bytes, _)) if self.used_bytes >= *bytes => self.spill(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the index in memory to a new run, and starts over with an
+    /// empty index.
+    fn spill(&mut self) -> Result<()> {
+        let Some((_, runs_dir)) = &self.budget else {
+            return Ok(());
+        };
+        fs::create_dir_all(runs_dir).map_err(Error::at(runs_dir))?;
+        let path = runs_dir.join(format!("{:08}.run", self.runs.len()));
+        std::mem::take(&mut self.index).into_file(&path)?;
+        self.runs.push(path);
+        self.used_bytes = 0;
+        Ok(())
+    }
+
+    /// Returns the whole index in memory, with the runs merged into it. The
+    /// budget of memory does not hold for it, so it is meant for the formats
+    /// that are written from memory, like JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a run cannot be read.
+    pub fn into_index(mut self) -> Result<FrequencyIndex> {
+        let mut index = std::mem::take(&mut self.index);
+        for run in &self.runs {
+            index.merge(FrequencyIndex::from_file(run)?);
+        }
+        Ok(index)
+    }
+
+    /// Writes the index to file, in the compact binary layout, with the
+    /// postings lists in the specified order, merging the runs into it. Like
+    /// [`FrequencyIndex::into_file`], it is written to a temporary file
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a run cannot be read, or if the file cannot be
+    /// written.
+    pub fn into_file(mut self, path: impl AsRef<Path>, order: PostingsOrder) -> Result<()> {
+        let path = path.as_ref();
+        let runs = self.open_runs()?;
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        self.write_to(BufWriter::new(file), &runs, order)
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Writes the index to file, in the compact binary layout, compressed,
+    /// merging the runs into it. The index is compressed as it is merged, so
+    /// the budget of memory holds for it too. Like [`Self::into_file`], it
+    /// is written to a temporary file first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a run cannot be read, or if the file cannot be
+    /// written.
+    pub fn into_compressed_file(
+        mut self,
+        path: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let runs = self.open_runs()?;
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        compression
+            .writer(BufWriter::new(file))
+            .and_then(|mut writer| {
+                self.write_to(&mut writer, &runs, PostingsOrder::DocId)?;
+                writer.finish()?.flush()
+            })
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Spills the rest of the index, if any runs were spilled, and opens the
+    /// runs for merging.
+    fn open_runs(&mut self) -> Result<Vec<IndexFile<Mmap>>> {
+        if self.runs.is_empty() {
+            return Ok(vec![]);
+        }
+        self.spill()?;
+        self.runs.iter().map(|path| open_run(path)).collect()
+    }
+
+    /// Writes the index to a writer, in the compact binary layout, merging
+    /// its runs into it, if any were spilled.
+    fn write_to(
+        &mut self,
+        writer: impl Write,
+        runs: &[IndexFile<Mmap>],
+        order: PostingsOrder,
+    ) -> io::Result<()> {
+        match runs.is_empty() {
+            true => std::mem::take(&mut self.index).write_ordered_to(writer, order),
+            false => layout::merge(writer, runs, order),
+        }
+    }
+}
+
+impl Drop for IndexBuilder {
+    /// Removes the runs, and their directory if it is left empty.
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = fs::remove_file(run);
+        }
+        if let Some((_, runs_dir)) = &self.budget {
+            let _ = fs::remove_dir(runs_dir);
+        }
+    }
+}
+
+/// Opens a run for merging, through a memory map of its file.
+fn open_run(path: &Path) -> Result<IndexFile<Mmap>> {
+    let file = File::open(path).map_err(Error::at(path))?;
+    // SAFETY: Runs are written once by the builder that maps them, and they
+    // are not modified until they are removed.
+    let mmap = unsafe { Mmap::map(&file) }.map_err(Error::at(path))?;
+    IndexFile::new(mmap).map_err(Error::at(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inverted::Index;
+
+    use super::*;
+
+    #[test]
+    fn test_spilled_runs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let runs_dir = dir.join("runs");
+        let mut expected = FrequencyIndex::new();
+        let build = |expected: &mut FrequencyIndex| {
+            let mut builder = IndexBuilder::new().with_memory_budget(200, &runs_dir);
+            for doc_id in 0..20 {
+                let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+                let tokens = ["search", "engine", "index"][..(doc_id as usize % 3 + 1)].to_vec();
+                doc_index.index_tokens(tokens.into_iter().map(String::from));
+                doc_index.index_tokens([format!("term{doc_id}")]);
+                expected.index(doc_index.clone());
+                builder.index(doc_index).unwrap();
+            }
+            assert!(builder.n_runs() > 1);
+            builder
+        };
+
+        let path = dir.join("index.bin");
+        build(&mut expected)
+            .into_file(&path, PostingsOrder::DocId)
+            .unwrap();
+        assert!(!runs_dir.exists());
+        let merged = FrequencyIndex::from_file(&path).unwrap();
+        assert_eq!(merged.n_docs(), 20);
+        assert_eq!(merged.n_terms_total(), expected.n_terms_total());
+        let mut terms = merged.terms();
+        terms.sort();
+        let mut expected_terms = expected.terms();
+        expected_terms.sort();
+        assert_eq!(terms, expected_terms);
+        for term in &terms {
+            assert_eq!(merged.term_postings(term), expected.term_postings(term));
+        }
+
+        // The runs are merged into a compressed file as they are compressed.
+        let compressed_path = dir.join("index.bin.zst");
+        build(&mut FrequencyIndex::new())
+            .into_compressed_file(&compressed_path, Compression::Zstd)
+            .unwrap();
+        let bytes = fs::read(&compressed_path).unwrap();
+        assert_eq!(Compression::detect(&bytes), Compression::Zstd);
+        let decompressed = FrequencyIndex::from_bytes(bytes).unwrap();
+        for term in &terms {
+            assert_eq!(
+                decompressed.term_postings(term),
+                expected.term_postings(term)
+            );
+        }
+    }
+}
@@ -1,23 +1,22 @@
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Write};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
 
-use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
 use documents::DocumentId;
 use tokenize::Token;
 
+use crate::compress::{decompress, Compression};
 use crate::doc::freq::DocumentFrequencyIndex;
 use crate::doc::term::DocumentTermsCounter;
+use crate::error::{Error, Result};
+use crate::inverted::layout::{self, IndexFile, SIGNATURE};
 use crate::inverted::Index;
 use crate::postings::*;
 
-const SIGNATURE: &[u8] = b"SEARHINE";
-const VERSION: u8 = 1;
-
 /// An in-memory inverted index. The inverted index is a HashMap with
 /// the token as the key and a postings list as the value.
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -47,44 +46,42 @@ impl FrequencyInvertedIndex {
         }
     }
 
-    /// Initiates the process for writing the inverted index to a file.
-    /// It creates a new file with the specified path and writes the header in it.
-    fn pre_write_file(&self, path: impl AsRef<Path>) -> io::Result<File> {
-        let mut file = File::create(path)?;
-        file.write_all(SIGNATURE)?; // Write signature
-        file.write_all(&[VERSION])?; // Write index version
-        file.write_all(&(self.inner.len() as u32).to_be_bytes())?; // Write entry count
-        Ok(file)
-    }
-
-    /// Writes the frequency inverted index to a file.
-    fn into_file(self, path: impl AsRef<Path>) -> anyhow::Result<()> {
-        let file = File::create(path)?;
-        let file = BufWriter::new(file);
-        serde_json::to_writer(file, &self)?;
-
-        // let mut file = self.pre_write_file(path)?;
-        // let entries = self.inner
-        //     .into_iter()
-        //     .collect::<BTreeMap<_, _>>();
-        //
-        // let mut buffer = Cursor::new(Vec::<u8>::new());
-        // for (term_id, postings_list) in entries {
-        //     // Clear buffer
-        //     buffer.get_mut().clear();
-        //     buffer.set_position(0);
-        //
-        //     let gamma_encoder = GammaEncoder::new(Cursor::new(vec![]));
-        // entry_buffer.extend(&entry.ctime.to_be_bytes());
-        // entry_buffer.extend(&entry.ctime_ns.to_be_bytes());
-        // entry_buffer.extend(entry.path.to_string_lossy().as_bytes());
-        // let padding = 8 - (entry_buffer.len() % 8);
-        // entry_buffer.extend(vec![0; padding]);
-        // file.write_all(&entry_buffer)
-        //     .context("Failed to write entry in index")?;
-        // }
-
-        Ok(())
+    /// Merges another in-memory frequency inverted index into this one,
+    /// adding the postings of each of its tokens.
+    fn merge(&mut self, other: Self) {
+        for (token, postings_list) in other.inner {
+            match self.inner.get_mut(&token) {
+                Some(existing) => existing.merge(postings_list),
+                None => {
+                    self.inner.insert(token, postings_list);
+                }
+            }
+        }
+    }
+
+    /// Removes the postings of a document from the in-memory frequency
+    /// inverted index. Tokens that are left without postings are removed
+    /// too.
+    fn remove(&mut self, doc_id: DocumentId) {
+        for postings_list in self.inner.values_mut() {
+            postings_list.remove(doc_id);
+        }
+        self.inner
+            .retain(|_, postings_list| postings_list.len() > 0);
+    }
+
+    /// Removes terms and their postings lists from the in-memory frequency
+    /// inverted index, and returns the number of postings that were removed.
+    fn remove_terms(&mut self, terms: &HashSet<Token>) -> usize {
+        let mut n_postings = 0;
+        self.inner.retain(|token, postings_list| {
+            let is_removed = terms.contains(token);
+            if is_removed {
+                n_postings += postings_list.len();
+            }
+            !is_removed
+        });
+        n_postings
     }
 }
 
@@ -112,27 +109,189 @@ impl FrequencyIndex {
         self.inverted_index.index(doc_index);
     }
 
-    /// Writes inverted index with frequency postings to file.
-    pub fn into_file(self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    /// Removes a document from the index, so it can be re-indexed after it
+    /// has been modified, or dropped after it has been deleted.
+    pub fn remove(&mut self, doc_id: DocumentId) {
+        self.doc_terms_counter.remove_doc_terms(doc_id);
+        self.inverted_index.remove(doc_id);
+    }
+
+    /// Merges another index into this one. The indices are expected to have
+    /// different documents, as they do when they are segments of the same
+    /// collection.
+    pub fn merge(&mut self, other: FrequencyIndex) {
+        self.doc_terms_counter.merge(other.doc_terms_counter);
+        self.inverted_index.merge(other.inverted_index);
+    }
+
+    /// Removes terms from the index, like hashes and other one-off terms that
+    /// are never searched for. The numbers of terms of the documents are kept,
+    /// so the scores of the remaining terms do not change.
+    ///
+    /// # Returns
+    ///
+    /// The number of postings that were removed.
+    pub fn remove_terms(&mut self, terms: &HashSet<Token>) -> usize {
+        self.inverted_index.remove_terms(terms)
+    }
+
+    /// Returns the terms of the index, with the number of documents that
+    /// contain each of them.
+    pub fn document_frequencies(&self) -> impl Iterator<Item = (&Token, u32)> + '_ {
+        self.inverted_index
+            .inner
+            .iter()
+            .map(|(token, postings_list)| (token, postings_list.len() as u32))
+    }
+
+    /// Returns the IDs of all the documents in the index, whether they
+    /// appear in a postings list, or only in the counter of terms.
+    pub fn doc_ids(&self) -> BTreeSet<DocumentId> {
+        let mut doc_ids = self.doc_terms_counter.doc_ids().collect::<BTreeSet<_>>();
+        for postings_list in self.inverted_index.inner.values() {
+            doc_ids.extend(postings_list.doc_ids());
+        }
+        doc_ids
+    }
+
+    /// Writes inverted index with frequency postings to file, in the compact
+    /// binary layout, which can be memory-mapped.
+    ///
+    /// The index is written to a temporary file first, which then replaces
+    /// the index file, so an index file that is mapped by another process
+    /// never changes while it is mapped.
+    pub fn into_file(self, path: impl AsRef<Path>) -> Result<()> {
+        self.into_ordered_file(path, PostingsOrder::DocId)
+    }
+
+    /// Writes inverted index with frequency postings to file, in the compact
+    /// binary layout, with the postings lists in the specified order. Like
+    /// [`Self::into_file`], it is written to a temporary file first.
+    pub fn into_ordered_file(self, path: impl AsRef<Path>, order: PostingsOrder) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        self.write_ordered_to(BufWriter::new(file), order)
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Writes inverted index with frequency postings to file as JSON. The
+    /// JSON format is much larger and slower to load than the binary one,
+    /// but it can be inspected, which is useful for debugging.
+    ///
+    /// Like the binary index, it is written to a temporary file first, so a
+    /// write that is interrupted leaves the previous index file intact.
+    pub fn into_json_file(self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
-        let file = File::create(path).context(format!(
-            "Failed to create index file at: {}",
-            path.display()
-        ))?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, &self).context("Failed to write index to writer.")
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        self.write_json_to(BufWriter::new(file))
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Writes inverted index with frequency postings to file, in the compact
+    /// binary layout, compressed. A compressed index file cannot be
+    /// memory-mapped, so it is loaded as a whole, with [`Self::from_file`].
+    pub fn into_compressed_file(
+        self,
+        path: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<()> {
+        let mut bytes = vec![];
+        self.write_to(&mut bytes)
+            .and_then(|()| compression.compress(bytes))
+            .map_err(Error::at(path.as_ref()))
+            .and_then(|bytes| write_file(path.as_ref(), &bytes))
+    }
+
+    /// Writes inverted index with frequency postings to file as JSON,
+    /// compressed.
+    pub fn into_compressed_json_file(
+        self,
+        path: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<()> {
+        let mut bytes = vec![];
+        self.write_json_to(&mut bytes)
+            .and_then(|()| compression.compress(bytes))
+            .map_err(Error::at(path.as_ref()))
+            .and_then(|bytes| write_file(path.as_ref(), &bytes))
     }
 
-    /// Loads inverted index with frequency postings from file.
-    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    /// Loads inverted index with frequency postings from file. The format of
+    /// the file, binary or JSON, is detected from its signature, and a
+    /// compressed file is decompressed.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
-        let file =
-            File::open(path).context(format!("Failed to open file at: {}", path.display()))?;
-        let reader = BufReader::new(file);
-        serde_json::from_reader(reader).context(format!(
-            "Failed to read index from file: {}",
-            path.display()
-        ))
+        let bytes = fs::read(path).map_err(Error::at(path))?;
+        Self::from_bytes(bytes).map_err(Error::at(path))
+    }
+
+    /// Loads inverted index with frequency postings from the contents of an
+    /// index file, in the binary or the JSON format, compressed or not.
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        let bytes = decompress(bytes)?;
+        let index = if bytes.starts_with(SIGNATURE) {
+            IndexFile::new(bytes).and_then(|file| Self::from_index_file(&file))?
+        } else {
+            serde_json::from_slice(&bytes)?
+        };
+        Ok(index)
+    }
+
+    /// Loads inverted index with frequency postings from an index file that
+    /// was written in the current version of the format, or in an older one
+    /// that can be upgraded, so that it can be written again in the current
+    /// one. JSON index files have no version, and are loaded as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or if it was written in
+    /// a version of the format that cannot be upgraded.
+    pub fn upgrade_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(Error::at(path))?;
+        decompress(bytes)
+            .and_then(|bytes| match bytes.starts_with(SIGNATURE) {
+                true => IndexFile::upgradable(bytes).and_then(|file| Self::from_index_file(&file)),
+                false => Ok(serde_json::from_slice(&bytes)?),
+            })
+            .map_err(Error::at(path))
+    }
+
+    /// Writes inverted index with frequency postings to a writer, in the
+    /// compact binary layout.
+    pub fn write_to(self, writer: impl Write) -> io::Result<()> {
+        self.write_ordered_to(writer, PostingsOrder::DocId)
+    }
+
+    /// Writes inverted index with frequency postings to a writer, in the
+    /// compact binary layout, with the postings lists in the specified order.
+    pub fn write_ordered_to(self, writer: impl Write, order: PostingsOrder) -> io::Result<()> {
+        let postings = self.inverted_index.inner.iter().collect::<BTreeMap<_, _>>();
+        layout::write(writer, &self.doc_terms_counter, postings, order)
+    }
+
+    /// Writes inverted index with frequency postings to a writer as JSON.
+    pub fn write_json_to(self, mut writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer(&mut writer, &self)?;
+        writer.flush()
+    }
+
+    /// Reads the whole index from an index file in the binary layout, in the
+    /// version of the layout that it was written in.
+    fn from_index_file<B: AsRef<[u8]>>(file: &IndexFile<B>) -> io::Result<Self> {
+        let mut inner = HashMap::with_capacity(file.n_terms());
+        for i in 0..file.n_terms() {
+            let (term, postings) = file.entry(i)?;
+            inner.insert(term.to_string(), file.read_postings_list(postings)?);
+        }
+        Ok(Self {
+            inverted_index: FrequencyInvertedIndex { inner },
+            doc_terms_counter: file.counter()?,
+        })
     }
 }
 
@@ -141,7 +300,7 @@ impl Index for FrequencyIndex {
         self.doc_terms_counter.n_docs()
     }
 
-    fn n_terms_total(&self) -> u32 {
+    fn n_terms_total(&self) -> u64 {
         self.doc_terms_counter.n_terms_total()
     }
 
@@ -160,19 +319,197 @@ impl Index for FrequencyIndex {
             .map_or(0, |p_lst| p_lst.len() as u32)
     }
 
+    fn contains(&self, doc_id: DocumentId) -> bool {
+        self.doc_terms_counter.contains(doc_id)
+    }
+
+    fn doc_lengths(&self) -> Vec<(DocumentId, u32)> {
+        self.doc_terms_counter
+            .doc_ids()
+            .map(|doc_id| (doc_id, self.doc_terms_counter.n_terms(doc_id)))
+            .collect()
+    }
+
     fn n_terms(&self, doc_id: DocumentId) -> u32 {
         self.doc_terms_counter.n_terms(doc_id)
     }
 
     fn term_frequency(&self, doc_id: DocumentId, term: &Token) -> u32 {
-        let x = self.inverted_index.inner.get(term).unwrap();
-        let y = x.get(doc_id).unwrap();
-        y.frequency()
+        self.inverted_index
+            .inner
+            .get(term)
+            .and_then(|postings_list| postings_list.get(doc_id))
+            .map_or(0, |posting| posting.frequency())
+    }
+
+    fn term_postings(&self, term: &Token) -> Vec<TermPostings> {
+        let postings = self
+            .inverted_index
+            .inner
+            .get(term)
+            .map_or_else(Vec::new, |postings_list| postings_list.sorted());
+        vec![TermPostings::new(postings, |doc_id| {
+            self.doc_terms_counter.n_terms(doc_id)
+        })]
     }
+
+    fn terms(&self) -> Vec<Token> {
+        self.inverted_index.inner.keys().cloned().collect()
+    }
+}
+
+/// Writes the contents of an index file to a temporary file first, which
+/// then replaces the index file, like the uncompressed index files.
+pub(crate) fn write_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes).map_err(Error::at(&tmp_path))?;
+    fs::rename(&tmp_path, path).map_err(Error::at(path))
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_document() {
+        let mut doc_index_1 = DocumentFrequencyIndex::new(0);
+        doc_index_1.index_tokens(["search", "engine", "search"].map(String::from));
+        let mut doc_index_2 = DocumentFrequencyIndex::new(1);
+        doc_index_2.index_tokens(["search", "index"].map(String::from));
+
+        let mut index = FrequencyIndex::new();
+        index.index(doc_index_1);
+        index.index(doc_index_2);
+        index.remove(0);
+
+        assert_eq!(index.n_docs(), 1);
+        assert_eq!(index.n_terms_total(), 2);
+        assert_eq!(index.doc_ids_containing(&"search".to_string()), vec![1]);
+        assert_eq!(index.n_docs_containing(&"engine".to_string()), 0);
+        assert!(!index.inverted_index.inner.contains_key("engine"));
+    }
+
+    #[test]
+    fn test_remove_terms() {
+        let mut doc_index_1 = DocumentFrequencyIndex::new(0);
+        doc_index_1.index_tokens(["search", "engine", "search"].map(String::from));
+        let mut doc_index_2 = DocumentFrequencyIndex::new(1);
+        doc_index_2.index_tokens(["search", "index"].map(String::from));
+
+        let mut index = FrequencyIndex::new();
+        index.index(doc_index_1);
+        index.index(doc_index_2);
+        let terms = HashSet::from(["search".to_string(), "missing".to_string()]);
+        assert_eq!(index.remove_terms(&terms), 2);
+
+        assert_eq!(index.n_docs(), 2);
+        assert_eq!(index.n_terms_total(), 5);
+        assert_eq!(index.n_docs_containing(&"search".to_string()), 0);
+        let frequencies = index.document_frequencies().collect::<BTreeMap<_, _>>();
+        assert_eq!(frequencies.len(), 2);
+        assert_eq!(frequencies[&"engine".to_string()], 1);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut doc_index_1 = DocumentFrequencyIndex::new(0);
+        doc_index_1.index_tokens(["search", "engine", "search"].map(String::from));
+        let mut doc_index_2 = DocumentFrequencyIndex::new(1);
+        doc_index_2.index_tokens(["search", "index"].map(String::from));
+
+        let mut index = FrequencyIndex::new();
+        index.index(doc_index_1);
+        let mut other = FrequencyIndex::new();
+        other.index(doc_index_2);
+        index.merge(other);
+
+        let search = "search".to_string();
+        assert_eq!(index.n_docs(), 2);
+        assert_eq!(index.n_terms_total(), 5);
+        assert_eq!(index.n_docs_containing(&search), 2);
+        assert_eq!(index.term_frequency(1, &"index".to_string()), 1);
+        assert!(index.contains(1));
+        assert_eq!(index.doc_ids(), BTreeSet::from([0, 1]));
+    }
+
+    #[test]
+    fn test_binary_format() {
+        let mut doc_index_1 = DocumentFrequencyIndex::new(0);
+        doc_index_1.index_tokens(["search", "engine", "search"].map(String::from));
+        let mut doc_index_2 = DocumentFrequencyIndex::new(1);
+        doc_index_2.index_tokens(["search", "index"].map(String::from));
+        let mut index = FrequencyIndex::new();
+        index.index(doc_index_1);
+        index.index(doc_index_2);
+
+        let postings = index
+            .inverted_index
+            .inner
+            .iter()
+            .collect::<BTreeMap<_, _>>();
+        let mut buffer = vec![];
+        layout::write(
+            &mut buffer,
+            &index.doc_terms_counter,
+            postings,
+            PostingsOrder::DocId,
+        )
+        .unwrap();
+        let file = IndexFile::new(buffer).unwrap();
+        assert!(file.find("engine").unwrap().is_some());
+        assert!(file.find("missing").unwrap().is_none());
+        assert_eq!(file.terms_with_prefix("se").unwrap(), vec!["search"]);
+        assert_eq!(file.terms_with_prefix("").unwrap().len(), 3);
+        assert!(file.terms_with_prefix("z").unwrap().is_empty());
+        let loaded = FrequencyIndex::from_index_file(&file).unwrap();
+
+        let search = "search".to_string();
+        assert_eq!(loaded.n_docs(), 2);
+        assert_eq!(loaded.n_terms_total(), 5);
+        assert_eq!(loaded.n_docs_containing(&search), 2);
+        assert_eq!(loaded.term_frequency(0, &search), 2);
+        assert_eq!(loaded.term_frequency(1, &"index".to_string()), 1);
+    }
+
+    #[test]
+    fn test_impact_format() {
+        let mut index = FrequencyIndex::new();
+        for doc_id in 0..200 {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            let tokens = ["search"].repeat(doc_id as usize % 5 + 1);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            doc_index.index_tokens(["engine".to_string()]);
+            index.index(doc_index);
+        }
+
+        let search = "search".to_string();
+        let term_postings = index.term_postings(&search);
+        let mut buffer = vec![];
+        index
+            .write_ordered_to(&mut buffer, PostingsOrder::Impact)
+            .unwrap();
+        let file = IndexFile::new(buffer.as_slice()).unwrap();
+        assert_eq!(file.order(), PostingsOrder::Impact);
+        let loaded = FrequencyIndex::from_bytes(buffer).unwrap();
+        assert_eq!(loaded.n_docs_containing(&search), 200);
+        assert_eq!(loaded.term_frequency(4, &search), 5);
+        assert_eq!(loaded.term_postings(&search), term_postings);
+    }
+
+    #[test]
+    fn test_compressed_bytes() {
+        let mut doc_index = DocumentFrequencyIndex::new(0);
+        doc_index.index_tokens(["search", "engine", "search"].map(String::from));
+        let mut index = FrequencyIndex::new();
+        index.index(doc_index);
+
+        let mut buffer = vec![];
+        index.write_to(&mut buffer).unwrap();
+        let compressed = Compression::Zstd.compress(buffer).unwrap();
+        let loaded = FrequencyIndex::from_bytes(compressed).unwrap();
+        assert_eq!(loaded.term_frequency(0, &"search".to_string()), 2);
+    }
+
     // #[test]
     // fn test_frequency_indexing() {
     //     let tokens_1 = vec![1, 2, 3, 1, 4];
@@ -0,0 +1,455 @@
+//! The binary on-disk layout of the inverted index.
+//!
+//! The layout is designed so that the postings list of a term can be found
+//! without reading the whole file, which allows memory-mapping it:
+//!
+//! ```text
+//! header      signature, version and flags
+//! counter     number of terms of each document
+//! postings    compressed postings lists in blocks, one after the other
+//! dictionary  entries of term, postings offset and postings length
+//! table       offsets of the dictionary entries, sorted by term
+//! footer      counter offset, table offset and number of terms
+//! ```
+//!
+//! The table has fixed-size entries, so the dictionary can be searched with
+//! binary search, reading only the entries it visits.
+//!
+//! The postings lists are sorted by document ID, or by impact if the
+//! [`IMPACT_FLAG`] of the flags of the header is set.
+
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Write};
+use std::ops::{Range, RangeInclusive};
+
+use tokenize::Token;
+
+use crate::codec::{check_version, write_header, write_str, write_u32, write_u64};
+use crate::doc::term::DocumentTermsCounter;
+use crate::error::FormatError;
+use crate::postings::{FrequencyPostingsList, PostingsOrder};
+
+pub(crate) const SIGNATURE: &[u8] = b"SEARHINE";
+pub(crate) const VERSION: u8 = 5;
+/// The oldest version of the layout that can still be read, so that index
+/// files in it can be upgraded to the current one. Version 3 is the layout
+/// before the postings lists were split into blocks, and version 4 the one
+/// before the header had flags.
+pub(crate) const MIN_VERSION: u8 = 3;
+/// The first version of the layout whose header has a byte of flags after
+/// the version.
+const FLAGS_VERSION: u8 = 5;
+/// The flag of the header that is set if the postings lists are sorted by
+/// impact.
+const IMPACT_FLAG: u8 = 1;
+
+/// The size of the footer in bytes.
+const FOOTER_LEN: usize = 8 + 8 + 4;
+
+/// A writer that keeps track of the number of bytes written, so the offsets
+/// of the sections can be recorded.
+struct OffsetWriter<W> {
+    inner: W,
+    offset: u64,
+}
+
+impl<W: Write> Write for OffsetWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes an index in the binary layout.
+///
+/// # Arguments
+///
+/// * `writer`   - The writer of the index file.
+/// * `counter`  - The number of terms of each document.
+/// * `postings` - The postings lists of the terms, sorted by term.
+/// * `order`    - The order of the postings in the postings lists.
+pub(crate) fn write<'a>(
+    writer: impl Write,
+    counter: &DocumentTermsCounter,
+    postings: impl IntoIterator<Item = (&'a Token, &'a FrequencyPostingsList)>,
+    order: PostingsOrder,
+) -> io::Result<()> {
+    write_entries(writer, counter, postings.into_iter().map(Ok), order)
+}
+
+/// Merges index files in the binary layout, whose documents are disjoint,
+/// into one, in the current version of the layout. The dictionaries of the
+/// files are sorted, so they are read side by side, and only the postings
+/// lists of one term are in memory at a time.
+pub(crate) fn merge<B: AsRef<[u8]>>(
+    writer: impl Write,
+    files: &[IndexFile<B>],
+    order: PostingsOrder,
+) -> io::Result<()> {
+    let mut counter = DocumentTermsCounter::default();
+    for file in files {
+        counter.merge(file.counter()?);
+    }
+    // The next term of each file, with the file and the position of the term
+    // in its dictionary.
+    let mut heads = BinaryHeap::new();
+    for (i, file) in files.iter().enumerate() {
+        if file.n_terms() > 0 {
+            heads.push(Reverse((file.entry(0)?.0.to_string(), i, 0)));
+        }
+    }
+    let mut next_term = move || -> io::Result<Option<(String, FrequencyPostingsList)>> {
+        let Some(Reverse((term, _, _))) = heads.peek() else {
+            return Ok(None);
+        };
+        let term = term.clone();
+        let mut postings_list = FrequencyPostingsList::new();
+        while heads.peek().is_some_and(|head| head.0 .0 == term) {
+            let Some(Reverse((_, i, entry))) = heads.pop() else {
+                break;
+            };
+            let (_, postings) = files[i].entry(entry)?;
+            postings_list.merge(files[i].read_postings_list(postings)?);
+            if entry + 1 < files[i].n_terms() {
+                let next = files[i].entry(entry + 1)?.0.to_string();
+                heads.push(Reverse((next, i, entry + 1)));
+            }
+        }
+        Ok(Some((term, postings_list)))
+    };
+    let postings = std::iter::from_fn(move || next_term().transpose());
+    write_entries(writer, &counter, postings, order)
+}
+
+/// Writes an index in the binary layout, from postings lists that are read
+/// one at a time, sorted by term, which can fail to be read.
+fn write_entries<T, P>(
+    writer: impl Write,
+    counter: &DocumentTermsCounter,
+    postings: impl IntoIterator<Item = io::Result<(T, P)>>,
+    order: PostingsOrder,
+) -> io::Result<()>
+where
+    T: AsRef<str>,
+    P: Borrow<FrequencyPostingsList>,
+{
+    let mut writer = OffsetWriter {
+        inner: writer,
+        offset: 0,
+    };
+    let flags = match order {
+        PostingsOrder::DocId => 0,
+        PostingsOrder::Impact => IMPACT_FLAG,
+    };
+    write_header(&mut writer, SIGNATURE, VERSION)?;
+    writer.write_all(&[flags])?;
+    let avg_terms = counter.n_terms_total() as f64 / counter.n_docs().max(1) as f64;
+    let counter_offset = writer.offset;
+    counter.write_to(&mut writer)?;
+
+    let mut dictionary = vec![];
+    for entry in postings {
+        let (term, postings_list) = entry?;
+        let postings_list = postings_list.borrow();
+        let postings_offset = writer.offset;
+        let n_terms = |doc_id| counter.n_terms(doc_id);
+        match order {
+            PostingsOrder::DocId => postings_list.write_to(&mut writer, n_terms)?,
+            PostingsOrder::Impact => {
+                postings_list.write_impact_to(&mut writer, n_terms, avg_terms)?
+            }
+        }
+        let postings_len = (writer.offset - postings_offset) as u32;
+        dictionary.push((term, postings_offset, postings_len));
+    }
+
+    let mut table = Vec::with_capacity(dictionary.len());
+    for (term, postings_offset, postings_len) in &dictionary {
+        table.push(writer.offset);
+        write_str(&mut writer, term.as_ref())?;
+        write_u64(&mut writer, *postings_offset)?;
+        write_u32(&mut writer, *postings_len)?;
+    }
+
+    let table_offset = writer.offset;
+    for entry_offset in table {
+        write_u64(&mut writer, entry_offset)?;
+    }
+    write_u64(&mut writer, counter_offset)?;
+    write_u64(&mut writer, table_offset)?;
+    write_u32(&mut writer, dictionary.len() as u32)?;
+    writer.flush()
+}
+
+/// A view of an index file in the binary layout, backed by any bytes, like
+/// the contents of the file in memory, or a memory map of the file.
+pub(crate) struct IndexFile<B> {
+    bytes: B,
+    version: u8,
+    order: PostingsOrder,
+    counter_offset: usize,
+    table_offset: usize,
+    n_terms: usize,
+}
+
+impl<B: AsRef<[u8]>> IndexFile<B> {
+    /// Creates a view of an index file, checking its header and reading its
+    /// footer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not an index file, or if the index
+    /// was written in another version of the layout.
+    pub(crate) fn new(bytes: B) -> io::Result<Self> {
+        Self::with_versions(bytes, VERSION..=VERSION)
+    }
+
+    /// Creates a view of an index file in the current version of the layout,
+    /// or in an older one that can be upgraded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not an index file, or if the index
+    /// was written in a version of the layout that cannot be read.
+    pub(crate) fn upgradable(bytes: B) -> io::Result<Self> {
+        Self::with_versions(bytes, MIN_VERSION..=VERSION)
+    }
+
+    /// Creates a view of an index file in one of a range of versions of the
+    /// layout, checking its header and reading its footer.
+    fn with_versions(bytes: B, versions: RangeInclusive<u8>) -> io::Result<Self> {
+        let data = bytes.as_ref();
+        let header_len = SIGNATURE.len() + 2;
+        if data.len() < header_len + FOOTER_LEN || !data.starts_with(SIGNATURE) {
+            return Err(FormatError::NotAnIndex.into());
+        }
+        let version = data[SIGNATURE.len()];
+        if !versions.contains(&version) {
+            check_version(version, VERSION)?;
+        }
+        let flags = match version >= FLAGS_VERSION {
+            true => data[SIGNATURE.len() + 1],
+            false => 0,
+        };
+        if flags & !IMPACT_FLAG != 0 {
+            return Err(invalid_data("Unknown flags in the header."));
+        }
+        let order = match flags & IMPACT_FLAG {
+            0 => PostingsOrder::DocId,
+            _ => PostingsOrder::Impact,
+        };
+
+        let footer = data.len() - FOOTER_LEN;
+        let counter_offset = to_offset(read_u64_at(data, footer)?)?;
+        let table_offset = to_offset(read_u64_at(data, footer + 8)?)?;
+        let n_terms = read_u32_at(data, footer + 16)? as usize;
+        Ok(Self {
+            bytes,
+            version,
+            order,
+            counter_offset,
+            table_offset,
+            n_terms,
+        })
+    }
+
+    /// Returns the order of the postings in the postings lists of the file.
+    pub(crate) fn order(&self) -> PostingsOrder {
+        self.order
+    }
+
+    /// Returns the number of terms in the index.
+    pub(crate) fn n_terms(&self) -> usize {
+        self.n_terms
+    }
+
+    /// Reads the number of terms of each document.
+    pub(crate) fn counter(&self) -> io::Result<DocumentTermsCounter> {
+        let data = self.bytes.as_ref();
+        let mut reader = data.get(self.counter_offset..).ok_or_else(out_of_bounds)?;
+        DocumentTermsCounter::read_from(&mut reader)
+    }
+
+    /// Returns the term and the byte range of the postings list of the
+    /// dictionary entry with the specified position in the table. The
+    /// offsets are read from the file, so they are checked for overflow.
+    pub(crate) fn entry(&self, i: usize) -> io::Result<(&str, Range<usize>)> {
+        let data = self.bytes.as_ref();
+        let table_entry = i
+            .checked_mul(8)
+            .and_then(|offset| offset.checked_add(self.table_offset))
+            .ok_or_else(out_of_bounds)?;
+        let entry_offset = to_offset(read_u64_at(data, table_entry)?)?;
+        let term_len = read_u32_at(data, entry_offset)? as usize;
+        let term_start = add_offset(entry_offset, 4)?;
+        let term_end = add_offset(term_start, term_len)?;
+        let term = data.get(term_start..term_end).ok_or_else(out_of_bounds)?;
+        let term = std::str::from_utf8(term).map_err(|_| invalid_data("Invalid term."))?;
+        let postings_offset = to_offset(read_u64_at(data, term_end)?)?;
+        let postings_len = read_u32_at(data, add_offset(term_end, 8)?)? as usize;
+        let postings_end = add_offset(postings_offset, postings_len)?;
+        Ok((term, postings_offset..postings_end))
+    }
+
+    /// Finds the compressed postings list of a term with binary search on the
+    /// dictionary. If the term is not in the index, `None` is returned.
+    pub(crate) fn find(&self, term: &str) -> io::Result<Option<&[u8]>> {
+        let (mut low, mut high) = (0, self.n_terms);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (mid_term, postings) = self.entry(mid)?;
+            match mid_term.cmp(term) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return self.postings(postings).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds the terms that start with a prefix. The dictionary is sorted, so
+    /// the first of them is found with binary search, and the rest follow it.
+    pub(crate) fn terms_with_prefix(&self, prefix: &str) -> io::Result<Vec<&str>> {
+        let (mut low, mut high) = (0, self.n_terms);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.entry(mid)?.0 < prefix {
+                true => low = mid + 1,
+                false => high = mid,
+            }
+        }
+        let mut terms = vec![];
+        for i in low..self.n_terms {
+            let (term, _) = self.entry(i)?;
+            if !term.starts_with(prefix) {
+                break;
+            }
+            terms.push(term);
+        }
+        Ok(terms)
+    }
+
+    /// Returns the bytes of a compressed postings list.
+    pub(crate) fn postings(&self, range: Range<usize>) -> io::Result<&[u8]> {
+        self.bytes.as_ref().get(range).ok_or_else(out_of_bounds)
+    }
+
+    /// Decodes a postings list, in the version of the layout that the file
+    /// was written in, and in the order of its postings.
+    pub(crate) fn read_postings_list(
+        &self,
+        range: Range<usize>,
+    ) -> io::Result<FrequencyPostingsList> {
+        let mut postings = self.postings(range)?;
+        match self.order {
+            PostingsOrder::Impact => FrequencyPostingsList::read_impact_from(postings),
+            PostingsOrder::DocId if self.version > MIN_VERSION => {
+                FrequencyPostingsList::read_from(&mut postings)
+            }
+            PostingsOrder::DocId => FrequencyPostingsList::read_unblocked_from(&mut postings),
+        }
+    }
+}
+
+/// Reads a big-endian unsigned 32-bit integer at an offset.
+fn read_u32_at(data: &[u8], offset: usize) -> io::Result<u32> {
+    let end = add_offset(offset, 4)?;
+    let bytes = data.get(offset..end).ok_or_else(out_of_bounds)?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a big-endian unsigned 64-bit integer at an offset.
+fn read_u64_at(data: &[u8], offset: usize) -> io::Result<u64> {
+    let end = add_offset(offset, 8)?;
+    let bytes = data.get(offset..end).ok_or_else(out_of_bounds)?;
+    Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Adds a length to an offset that was read from a file, failing instead of
+/// overflowing if the file is corrupt.
+fn add_offset(offset: usize, len: usize) -> io::Result<usize> {
+    offset.checked_add(len).ok_or_else(out_of_bounds)
+}
+
+/// Converts an offset that was read from a file to a position in memory,
+/// failing if it cannot be one.
+fn to_offset(offset: u64) -> io::Result<usize> {
+    usize::try_from(offset).map_err(|_| out_of_bounds())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn out_of_bounds() -> io::Error {
+    invalid_data("Index file is truncated.")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::postings::{FrequencyPosting, PostingsList};
+
+    use super::*;
+
+    #[test]
+    fn test_postings_order_flag() {
+        let mut counter = DocumentTermsCounter::default();
+        counter.insert_doc_terms(0, 3);
+        counter.insert_doc_terms(1, 2);
+        let mut postings_list = FrequencyPostingsList::new();
+        postings_list.add(FrequencyPosting::new(0, 2));
+        postings_list.add(FrequencyPosting::new(1, 1));
+        let term = "search".to_string();
+
+        for order in [PostingsOrder::DocId, PostingsOrder::Impact] {
+            let mut bytes = vec![];
+            write(&mut bytes, &counter, [(&term, &postings_list)], order).unwrap();
+            assert_eq!(bytes[SIGNATURE.len()], VERSION);
+            let file = IndexFile::new(bytes.as_slice()).unwrap();
+            assert_eq!(file.order(), order);
+            let (_, postings) = file.entry(0).unwrap();
+            let read = file.read_postings_list(postings).unwrap();
+            assert_eq!(read.sorted(), postings_list.sorted());
+
+            bytes[SIGNATURE.len() + 1] |= 0b10;
+            assert!(IndexFile::new(bytes.as_slice()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_corrupt_offsets() {
+        let mut counter = DocumentTermsCounter::default();
+        counter.insert_doc_terms(0, 1);
+        let mut postings_list = FrequencyPostingsList::new();
+        postings_list.add(FrequencyPosting::new(0, 1));
+        let term = "search".to_string();
+        let mut bytes = vec![];
+        write(
+            &mut bytes,
+            &counter,
+            [(&term, &postings_list)],
+            PostingsOrder::DocId,
+        )
+        .unwrap();
+
+        // The offsets of the table and of its entry are the largest there
+        // are, so they overflow instead of pointing past the end of the file.
+        let footer = bytes.len() - FOOTER_LEN;
+        bytes[footer + 8..footer + 16].copy_from_slice(&u64::MAX.to_be_bytes());
+        let file = IndexFile::new(bytes.as_slice()).unwrap();
+        assert!(file.entry(0).is_err());
+        assert!(file.find("search").is_err());
+        let table_offset = bytes.len() - FOOTER_LEN - 8;
+        bytes[footer + 8..footer + 16].copy_from_slice(&(table_offset as u64).to_be_bytes());
+        bytes[table_offset..table_offset + 8].copy_from_slice(&(u64::MAX - 2).to_be_bytes());
+        let file = IndexFile::new(bytes.as_slice()).unwrap();
+        assert!(file.entry(0).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use memmap2::Mmap;
+
+use documents::DocumentId;
+use tokenize::Token;
+
+use crate::doc::term::DocumentTermsCounter;
+use crate::error::{Error, Result};
+use crate::inverted::layout::IndexFile;
+use crate::inverted::Index;
+use crate::postings::*;
+
+/// A read-only index that is accessed through a memory map of its file.
+///
+/// Opening the index only reads the number of terms of each document. The
+/// postings list of a term is looked up in the dictionary of the file, and
+/// decoded only when a query needs it, so the first query on a large index
+/// does not have to wait for the whole index to be loaded. Decoded postings
+/// lists are cached, since scoring a query looks them up repeatedly.
+///
+/// The postings lists of an index file that was written in impact order can
+/// also be decoded a block at a time, with [`Index::impact_postings`], so
+/// that a query reads only the blocks that can change its best documents.
+///
+/// A postings list that cannot be read, because the file is damaged, is
+/// treated as empty, and the error is kept until it is returned by
+/// [`Index::check`], so that the query fails instead of missing documents.
+pub struct MappedIndex {
+    path: PathBuf,
+    file: IndexFile<Mmap>,
+    doc_terms_counter: DocumentTermsCounter,
+    cache: Mutex<HashMap<Token, Option<Arc<FrequencyPostingsList>>>>,
+    error: Mutex<Option<Error>>,
+}
+
+impl MappedIndex {
+    /// Opens an index file that was written in the binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be mapped, or if it is not an
+    /// index file of the current version.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(Error::at(path))?;
+        // SAFETY: Index files are never modified in place. They are written
+        // to a temporary file, which then replaces the index file, so the
+        // mapped file does not change while it is mapped.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(Error::at(path))?;
+        let file = IndexFile::new(mmap).map_err(Error::at(path))?;
+        let doc_terms_counter = file.counter().map_err(Error::at(path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            doc_terms_counter,
+            cache: Mutex::new(HashMap::new()),
+            error: Mutex::new(None),
+        })
+    }
+
+    /// Returns the postings list of a term, decoding it from the file if it
+    /// is not cached. If the term is not in the index, `None` is returned.
+    /// If its postings list cannot be read, the error is kept, and `None` is
+    /// returned without caching it.
+    fn postings_list(&self, term: &Token) -> Option<Arc<FrequencyPostingsList>> {
+        if let Some(postings_list) = self.cache().get(term) {
+            return postings_list.clone();
+        }
+        let postings_list = self.file.find(term).and_then(|bytes| {
+            bytes
+                .map(|bytes| self.read_postings_list(bytes))
+                .transpose()
+        });
+        let postings_list = self.keep_error(postings_list)?.map(Arc::new);
+        self.cache().insert(term.clone(), postings_list.clone());
+        postings_list
+    }
+
+    /// Keeps the error of a read of the file, unless an earlier error is
+    /// kept, and returns the value that was read, if any.
+    fn keep_error<T>(&self, result: io::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                let mut error = self.error.lock().unwrap_or_else(PoisonError::into_inner);
+                error.get_or_insert_with(|| Error::from_io(&self.path, err));
+                None
+            }
+        }
+    }
+
+    /// Decodes a postings list, in the order of the postings of the file.
+    fn read_postings_list(&self, mut bytes: &[u8]) -> io::Result<FrequencyPostingsList> {
+        match self.file.order() {
+            PostingsOrder::DocId => FrequencyPostingsList::read_from(&mut bytes),
+            PostingsOrder::Impact => FrequencyPostingsList::read_impact_from(bytes),
+        }
+    }
+
+    /// Locks the cache of the postings lists. A cache that was poisoned by a
+    /// panic is still used, since its postings lists are only ever inserted
+    /// whole.
+    fn cache(&self) -> MutexGuard<'_, HashMap<Token, Option<Arc<FrequencyPostingsList>>>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Index for MappedIndex {
+    fn n_docs(&self) -> u32 {
+        self.doc_terms_counter.n_docs()
+    }
+
+    fn n_terms_total(&self) -> u64 {
+        self.doc_terms_counter.n_terms_total()
+    }
+
+    fn doc_ids_containing(&self, term: &Token) -> Vec<DocumentId> {
+        self.postings_list(term)
+            .map_or_else(Vec::new, |postings_list| postings_list.doc_ids())
+    }
+
+    fn n_docs_containing(&self, term: &Token) -> u32 {
+        self.postings_list(term)
+            .map_or(0, |postings_list| postings_list.len() as u32)
+    }
+
+    fn contains(&self, doc_id: DocumentId) -> bool {
+        self.doc_terms_counter.contains(doc_id)
+    }
+
+    fn doc_lengths(&self) -> Vec<(DocumentId, u32)> {
+        self.doc_terms_counter
+            .doc_ids()
+            .map(|doc_id| (doc_id, self.doc_terms_counter.n_terms(doc_id)))
+            .collect()
+    }
+
+    fn n_terms(&self, doc_id: DocumentId) -> u32 {
+        self.doc_terms_counter.n_terms(doc_id)
+    }
+
+    fn term_frequency(&self, doc_id: DocumentId, term: &Token) -> u32 {
+        self.postings_list(term)
+            .and_then(|postings_list| postings_list.get(doc_id).map(|p| p.frequency()))
+            .unwrap_or(0)
+    }
+
+    fn term_postings(&self, term: &Token) -> Vec<TermPostings> {
+        // The bounds of the blocks are read from the file, so only the
+        // postings are decoded. The blocks of postings that are ordered by
+        // impact are not in document ID order, so they are found again.
+        let term_postings = self.file.find(term).and_then(|bytes| match bytes {
+            Some(mut bytes) => match self.file.order() {
+                PostingsOrder::DocId => FrequencyPostingsList::read_term_postings(&mut bytes),
+                PostingsOrder::Impact => ImpactPostings::read(bytes)
+                    .and_then(ImpactPostings::read_all)
+                    .map(|postings| TermPostings::new(postings, |doc_id| self.n_terms(doc_id))),
+            },
+            None => Ok(TermPostings::default()),
+        });
+        vec![self.keep_error(term_postings).unwrap_or_default()]
+    }
+
+    fn impact_postings(&self, term: &Token) -> Option<Vec<ImpactPostings<'_>>> {
+        if self.file.order() != PostingsOrder::Impact {
+            return None;
+        }
+        let impact_postings = self.file.find(term).and_then(|bytes| match bytes {
+            Some(bytes) => ImpactPostings::read(bytes),
+            None => Ok(ImpactPostings::default()),
+        });
+        Some(vec![self.keep_error(impact_postings).unwrap_or_default()])
+    }
+
+    fn terms(&self) -> Vec<Token> {
+        (0..self.file.n_terms())
+            .filter_map(|i| self.keep_error(self.file.entry(i)))
+            .map(|(term, _)| term.to_string())
+            .collect()
+    }
+
+    fn terms_with_prefix(&self, prefix: &str) -> Vec<Token> {
+        let terms = self.keep_error(self.file.terms_with_prefix(prefix));
+        terms
+            .unwrap_or_default()
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn check(&self) -> Result<()> {
+        let mut error = self.error.lock().unwrap_or_else(PoisonError::into_inner);
+        error.take().map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doc::freq::DocumentFrequencyIndex;
+    use crate::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_corrupt_postings() {
+        let mut index = FrequencyIndex::new();
+        for (doc_id, tokens) in [(0, ["search", "engine"]), (1, ["search", "index"])] {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.map(String::from));
+            index.index(doc_index);
+        }
+        let mut buffer = vec![];
+        index.write_to(&mut buffer).unwrap();
+        let file = IndexFile::new(buffer.as_slice()).unwrap();
+        let postings = (0..file.n_terms())
+            .map(|i| file.entry(i).unwrap())
+            .find(|(term, _)| *term == "engine")
+            .map(|(_, postings)| postings)
+            .unwrap();
+        buffer[postings].fill(0xFF);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("searchine-mapped");
+        std::fs::write(&path, buffer).unwrap();
+
+        let index = MappedIndex::open(&path).unwrap();
+        assert!(index.check().is_ok());
+        assert_eq!(index.n_docs_containing(&"engine".to_string()), 0);
+        assert!(matches!(index.check(), Err(Error::Corrupt { .. })));
+        assert!(index.check().is_ok());
+        assert_eq!(index.n_docs_containing(&"search".to_string()), 2);
+        assert!(index.check().is_ok());
+    }
+}
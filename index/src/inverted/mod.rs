@@ -1,15 +1,29 @@
 use documents::DocumentId;
 use tokenize::Token;
 
+use crate::error::Result;
+use crate::postings::{ImpactPostings, TermPostings};
+use crate::score::TfWeighting;
+
+pub mod builder;
 pub mod freq;
+mod layout;
+pub mod mapped;
+pub mod segmented;
+
+/// The version of the format of the index files, which the manifest of the
+/// segments records, so that indexes in an older format can be upgraded.
+pub const FORMAT_VERSION: u8 = layout::VERSION;
 
-pub trait Index {
+/// An index of the terms of documents. Indexes can be shared between the
+/// threads that score a query in parallel.
+pub trait Index: Sync {
     /// Returns the number of documents in the index.
     fn n_docs(&self) -> u32;
 
     /// Returns the total number of terms in all the documents in
     /// the index.
-    fn n_terms_total(&self) -> u32;
+    fn n_terms_total(&self) -> u64;
 
     /// Returns the document IDs that contain the specified term.
     fn doc_ids_containing(&self, term: &Token) -> Vec<DocumentId>;
@@ -17,13 +31,77 @@ pub trait Index {
     /// Returns the number of documents containing the specified token.
     fn n_docs_containing(&self, term: &Token) -> u32;
 
+    /// Checks if a document is in the index.
+    fn contains(&self, doc_id: DocumentId) -> bool;
+
+    /// Returns the number of terms of each document in the index, with its
+    /// document ID, in no particular order.
+    fn doc_lengths(&self) -> Vec<(DocumentId, u32)>;
+
     /// Returns the number of terms in the document, counting the occurrence
     /// of the same term separately.
     fn n_terms(&self, doc_id: DocumentId) -> u32;
 
+    /// Returns the average number of terms of the documents in the index,
+    /// which length normalization, like that of BM25, compares the number
+    /// of terms of a document to. It is zero if the index is empty.
+    fn avg_n_terms(&self) -> f64 {
+        (self.n_terms_total() as f64) / (self.n_docs().max(1) as f64)
+    }
+
     /// Returns the frequency of a specified term in a specified document.
     fn term_frequency(&self, doc_id: DocumentId, term: &Token) -> u32;
 
+    /// Returns the postings of a term in each part of the index with its own
+    /// documents, like the segments of a segmented index, sorted by document
+    /// ID, with the bounds of their blocks. Every term has a postings of its
+    /// own in every part, in the same order, so that the parts are ranked
+    /// one after the other.
+    fn term_postings(&self, term: &Token) -> Vec<TermPostings> {
+        let mut postings = self
+            .doc_ids_containing(term)
+            .into_iter()
+            .map(|doc_id| (doc_id, self.term_frequency(doc_id, term)))
+            .collect::<Vec<_>>();
+        postings.sort_unstable();
+        vec![TermPostings::new(postings, |doc_id| self.n_terms(doc_id))]
+    }
+
+    /// Returns the postings of a term in each part of the index, like
+    /// [`Index::term_postings`], ordered by impact, so that they are decoded
+    /// a block at a time. If the postings of some part are not ordered by
+    /// impact, `None` is returned, which is the default.
+    fn impact_postings(&self, _term: &Token) -> Option<Vec<ImpactPostings<'_>>> {
+        None
+    }
+
+    /// Returns the terms of the index, that is, its vocabulary, in no
+    /// particular order.
+    fn terms(&self) -> Vec<Token>;
+
+    /// Returns the terms of the index that start with a prefix, in no
+    /// particular order.
+    fn terms_with_prefix(&self, prefix: &str) -> Vec<Token> {
+        self.terms()
+            .into_iter()
+            .filter(|term| term.starts_with(prefix))
+            .collect()
+    }
+
+    /// Returns the first error that the index ran into while it read its
+    /// files, since it was last checked. The other methods cannot fail, so a
+    /// term whose postings cannot be read is treated as if it was in no
+    /// document, and a query is only answered in full if the index is
+    /// checked after it is scored. Indexes in memory never fail, which is
+    /// the default.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that a file of the index could not be read with.
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Calculates the inverse document frequency score (IDF)
     fn calc_idf(&self, term: &Token) -> f64 {
         let n_docs_containing = self.n_docs_containing(term);
@@ -31,11 +109,18 @@ pub trait Index {
         crate::score::calc_idf(n_docs_containing, n_docs)
     }
 
-    /// Calculates the term frequency score (TF) of a specified term.
+    /// Returns the weighting of the term frequencies of the index. It is
+    /// the normalized frequency by default.
+    fn tf_weighting(&self) -> TfWeighting {
+        TfWeighting::default()
+    }
+
+    /// Calculates the term frequency score (TF) of a specified term, with
+    /// the weighting of the index.
     fn calc_tf(&self, doc_id: DocumentId, term: &Token) -> f64 {
-        let term_count = self.term_frequency(doc_id, &term);
+        let term_count = self.term_frequency(doc_id, term);
         let total_count = self.n_terms(doc_id);
-        crate::score::calc_tf(term_count, total_count)
+        self.tf_weighting().calc(term_count, total_count)
     }
 
     /// Calculates the TF-IDF score of a term for a specified document.
@@ -51,7 +136,7 @@ pub trait Index {
         let n_docs = self.n_docs();
         let n_docs_containing = self.n_docs_containing(term);
         let doc_terms = self.n_terms(doc_id);
-        let avg = (self.n_terms_total() as f64) / (self.n_docs() as f64);
+        let avg = self.avg_n_terms();
         crate::score::calc_bm25(term_freq, n_docs, n_docs_containing, doc_terms, avg, k, b)
     }
 }
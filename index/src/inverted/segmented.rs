@@ -0,0 +1,538 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use documents::DocumentId;
+use tokenize::Token;
+
+use crate::compress::Compression;
+use crate::error::{Error, Result};
+use crate::inverted::{Index, FORMAT_VERSION};
+use crate::postings::{ImpactPostings, PostingsOrder, TermPostings};
+use crate::score::TfWeighting;
+
+/// The list of the segments that make up an index.
+///
+/// Segments are immutable index files, each with its own documents. Adding
+/// documents writes a new segment, and changing the documents of a segment
+/// writes a new segment in its place, so the manifest is the only file that
+/// is replaced to change the index. The generation is increased with every
+/// segment that is named and every change of the segments, so segment names
+/// are never reused, and readers can tell if the index has changed since they
+/// opened it.
+///
+/// The manifest also keeps the weighting of the term frequencies that the
+/// index was built with, so that every reader scores the index the same way,
+/// and the compression that its segments are written with, so that updates
+/// write their segments like the rest, with the postings in the same order.
+/// Manifests that were written before they were kept have the default ones.
+///
+/// The version of the format that the segments were written in is kept
+/// too, so that an index in an older format is upgraded, and a manifest
+/// that was written by a newer version of the index is not misread.
+/// Manifests that were written before versions were kept have version 0.
+///
+/// Documents are deleted from a segment by marking them in the manifest,
+/// rather than by writing the segment again, so that they are dropped from
+/// the results at once. Their postings are left in the segment until it is
+/// compacted or merged, which writes it again without them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    version: u8,
+    generation: u64,
+    segments: Vec<String>,
+    #[serde(default)]
+    tf_weighting: TfWeighting,
+    #[serde(default)]
+    compression: Compression,
+    #[serde(default)]
+    postings_order: PostingsOrder,
+    /// The IDs of the deleted documents of each segment that has any.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    deleted: BTreeMap<String, BTreeSet<DocumentId>>,
+}
+
+impl Manifest {
+    /// Creates a new manifest without segments, in the current version of
+    /// the format.
+    pub fn new() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a new manifest without segments, whose generation continues
+    /// from the one of a manifest that it replaces.
+    pub fn starting_at(generation: u64) -> Self {
+        Self {
+            generation,
+            ..Self::new()
+        }
+    }
+
+    /// Returns the version of the format that the segments were written in.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Checks if the segments were written in an older version of the
+    /// format, so that they have to be upgraded.
+    pub fn is_outdated(&self) -> bool {
+        self.version < FORMAT_VERSION
+    }
+
+    /// Records that the segments were upgraded to the current version of the
+    /// format.
+    pub fn set_upgraded(&mut self) {
+        self.version = FORMAT_VERSION;
+    }
+
+    /// Returns the generation of the manifest.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the file names of the segments, from the oldest to the newest.
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// Returns the weighting of the term frequencies of the index.
+    pub fn tf_weighting(&self) -> TfWeighting {
+        self.tf_weighting
+    }
+
+    /// Sets the weighting of the term frequencies of the index.
+    pub fn set_tf_weighting(&mut self, tf_weighting: TfWeighting) {
+        self.tf_weighting = tf_weighting;
+    }
+
+    /// Returns the compression of the segments of the index.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Sets the compression of the segments of the index. The segments that
+    /// were already written keep their compression.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Returns the order of the postings of the uncompressed binary segments
+    /// of the index.
+    pub fn postings_order(&self) -> PostingsOrder {
+        self.postings_order
+    }
+
+    /// Sets the order of the postings of the uncompressed binary segments of
+    /// the index. The segments that were already written keep their order.
+    pub fn set_postings_order(&mut self, postings_order: PostingsOrder) {
+        self.postings_order = postings_order;
+    }
+
+    /// Returns the IDs of the documents that are deleted from a segment,
+    /// but that are still in its file.
+    pub fn deleted(&self, segment: &str) -> Option<&BTreeSet<DocumentId>> {
+        self.deleted.get(segment)
+    }
+
+    /// Returns the number of documents that are deleted from the segments,
+    /// but that are still in their files.
+    pub fn n_deleted(&self) -> usize {
+        self.deleted.values().map(BTreeSet::len).sum()
+    }
+
+    /// Marks documents of a segment as deleted. The documents have to be in
+    /// the segment, and they are dropped from its file when it is written
+    /// again.
+    pub fn delete(&mut self, segment: &str, doc_ids: impl IntoIterator<Item = DocumentId>) {
+        self.generation += 1;
+        self.deleted
+            .entry(segment.to_string())
+            .or_default()
+            .extend(doc_ids);
+    }
+
+    /// Returns a new, unique file name for a segment with the specified
+    /// extension.
+    pub fn new_segment_name(&mut self, extension: &str) -> String {
+        self.generation += 1;
+        format!("{:08}.{extension}", self.generation)
+    }
+
+    /// Replaces segments with a new one. The new segment is listed last,
+    /// as the newest. If no new segment is given, the old segments are only
+    /// removed. The deleted documents of the old segments are forgotten, as
+    /// the new segment is written without them.
+    pub fn replace(&mut self, old: &[String], new: Option<String>) {
+        self.generation += 1;
+        self.segments.retain(|segment| !old.contains(segment));
+        self.deleted.retain(|segment, _| !old.contains(segment));
+        self.segments.extend(new);
+    }
+
+    /// Writes the manifest to file.
+    ///
+    /// The manifest is written to a temporary file first, which then
+    /// replaces the manifest file, so readers always see either the old or
+    /// the new list of segments.
+    pub fn into_file(self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &self)
+            .map_err(|err| Error::from_io(&tmp_path, err.into()))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Loads the manifest from file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest cannot be read, or if it was written
+    /// in a newer version of the format than this one.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(Error::at(path))?;
+        let manifest: Self = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| Error::from_io(path, err.into()))?;
+        if manifest.version > FORMAT_VERSION {
+            return Err(Error::VersionMismatch {
+                path: path.to_path_buf(),
+                expected: FORMAT_VERSION,
+                found: manifest.version,
+            });
+        }
+        Ok(manifest)
+    }
+}
+
+/// An index made of segments, which are searched together.
+///
+/// Each document is in exactly one segment, so the statistics of the index
+/// are the sums of the statistics of its segments, and the documents that
+/// contain a term are those of all segments.
+///
+/// The documents that are deleted from a segment are skipped, as if they
+/// were not in it, and they are not counted in the statistics. A document
+/// that was modified can be deleted from an older segment and be in a newer
+/// one. The terms of the deleted documents are still in the vocabulary,
+/// until their segments are compacted.
+pub struct SegmentedIndex {
+    segments: Vec<Box<dyn Index>>,
+    /// The IDs of the deleted documents of each segment, in the order of the
+    /// segments.
+    deleted: Vec<BTreeSet<DocumentId>>,
+    tf_weighting: TfWeighting,
+}
+
+impl SegmentedIndex {
+    /// Creates an index of segments, with the default weighting of the term
+    /// frequencies.
+    pub fn new(segments: Vec<Box<dyn Index>>) -> Self {
+        Self {
+            deleted: vec![BTreeSet::new(); segments.len()],
+            segments,
+            tf_weighting: TfWeighting::default(),
+        }
+    }
+
+    /// Sets the IDs of the deleted documents of each segment, in the order
+    /// of the segments, which are the ones of its manifest.
+    pub fn with_deleted(mut self, deleted: Vec<BTreeSet<DocumentId>>) -> Self {
+        debug_assert_eq!(deleted.len(), self.segments.len());
+        self.deleted = deleted;
+        self
+    }
+
+    /// Sets the weighting of the term frequencies of the index, which is
+    /// the one of its manifest.
+    pub fn with_tf_weighting(mut self, tf_weighting: TfWeighting) -> Self {
+        self.tf_weighting = tf_weighting;
+        self
+    }
+
+    /// Returns the number of segments.
+    pub fn n_segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns the segments with the IDs of their deleted documents.
+    fn iter(&self) -> impl Iterator<Item = (&dyn Index, &BTreeSet<DocumentId>)> {
+        self.segments
+            .iter()
+            .map(|segment| segment.as_ref())
+            .zip(&self.deleted)
+    }
+
+    /// Returns the segment that contains a document, and has not deleted it.
+    fn segment_of(&self, doc_id: DocumentId) -> Option<&dyn Index> {
+        self.iter()
+            .find(|(segment, deleted)| segment.contains(doc_id) && !deleted.contains(&doc_id))
+            .map(|(segment, _)| segment)
+    }
+}
+
+impl Index for SegmentedIndex {
+    fn n_docs(&self) -> u32 {
+        self.iter()
+            .map(|(segment, deleted)| segment.n_docs().saturating_sub(deleted.len() as u32))
+            .sum()
+    }
+
+    fn n_terms_total(&self) -> u64 {
+        self.iter()
+            .map(|(segment, deleted)| {
+                let n_deleted = deleted.iter().map(|doc_id| segment.n_terms(*doc_id) as u64);
+                segment.n_terms_total().saturating_sub(n_deleted.sum())
+            })
+            .sum()
+    }
+
+    fn doc_ids_containing(&self, term: &Token) -> Vec<DocumentId> {
+        self.iter()
+            .flat_map(|(segment, deleted)| {
+                let mut doc_ids = segment.doc_ids_containing(term);
+                doc_ids.retain(|doc_id| !deleted.contains(doc_id));
+                doc_ids
+            })
+            .collect()
+    }
+
+    fn n_docs_containing(&self, term: &Token) -> u32 {
+        self.iter()
+            .map(|(segment, deleted)| {
+                let n_deleted = deleted
+                    .iter()
+                    .filter(|doc_id| segment.term_frequency(**doc_id, term) > 0)
+                    .count();
+                segment
+                    .n_docs_containing(term)
+                    .saturating_sub(n_deleted as u32)
+            })
+            .sum()
+    }
+
+    fn contains(&self, doc_id: DocumentId) -> bool {
+        self.segment_of(doc_id).is_some()
+    }
+
+    fn doc_lengths(&self) -> Vec<(DocumentId, u32)> {
+        self.iter()
+            .flat_map(|(segment, deleted)| {
+                let mut doc_lengths = segment.doc_lengths();
+                doc_lengths.retain(|(doc_id, _)| !deleted.contains(doc_id));
+                doc_lengths
+            })
+            .collect()
+    }
+
+    fn n_terms(&self, doc_id: DocumentId) -> u32 {
+        self.segment_of(doc_id)
+            .map_or(0, |segment| segment.n_terms(doc_id))
+    }
+
+    fn term_frequency(&self, doc_id: DocumentId, term: &Token) -> u32 {
+        self.segment_of(doc_id)
+            .map_or(0, |segment| segment.term_frequency(doc_id, term))
+    }
+
+    fn tf_weighting(&self) -> TfWeighting {
+        self.tf_weighting
+    }
+
+    fn check(&self) -> Result<()> {
+        // Every segment is checked, so that none keeps an error for the next
+        // query.
+        let errors = self
+            .segments
+            .iter()
+            .filter_map(|segment| segment.check().err())
+            .collect::<Vec<_>>();
+        errors.into_iter().next().map_or(Ok(()), Err)
+    }
+
+    fn term_postings(&self, term: &Token) -> Vec<TermPostings> {
+        self.iter()
+            .flat_map(|(segment, deleted)| {
+                segment
+                    .term_postings(term)
+                    .into_iter()
+                    .map(move |postings| match deleted.is_empty() {
+                        true => postings,
+                        // The bounds of the blocks are found again, as the
+                        // blocks are of the postings that are left.
+                        false => TermPostings::new(
+                            postings
+                                .postings
+                                .into_iter()
+                                .filter(|(doc_id, _)| !deleted.contains(doc_id))
+                                .collect(),
+                            |doc_id| segment.n_terms(doc_id),
+                        ),
+                    })
+            })
+            .collect()
+    }
+
+    fn impact_postings(&self, term: &Token) -> Option<Vec<ImpactPostings<'_>>> {
+        let mut parts = vec![];
+        for (segment, deleted) in self.iter() {
+            let postings = segment.impact_postings(term)?;
+            parts.extend(
+                postings
+                    .into_iter()
+                    .map(|postings| match deleted.is_empty() {
+                        true => postings,
+                        false => postings.with_deleted(deleted),
+                    }),
+            );
+        }
+        Some(parts)
+    }
+
+    fn terms(&self) -> Vec<Token> {
+        // A term can be in several segments, but it is returned once.
+        self.segments
+            .iter()
+            .flat_map(|segment| segment.terms())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn terms_with_prefix(&self, prefix: &str) -> Vec<Token> {
+        self.segments
+            .iter()
+            .flat_map(|segment| segment.terms_with_prefix(prefix))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doc::freq::DocumentFrequencyIndex;
+    use crate::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    fn segment(doc_id: DocumentId, tokens: &[&str]) -> Box<dyn Index> {
+        let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+        doc_index.index_tokens(tokens.iter().map(|token| token.to_string()));
+        let mut index = FrequencyIndex::new();
+        index.index(doc_index);
+        Box::new(index)
+    }
+
+    #[test]
+    fn test_segmented_index() {
+        let index = SegmentedIndex::new(vec![
+            segment(0, &["search", "engine", "search"]),
+            segment(1, &["search", "index"]),
+        ]);
+        let search = "search".to_string();
+        assert_eq!(index.n_docs(), 2);
+        assert_eq!(index.n_terms_total(), 5);
+        assert_eq!(index.n_docs_containing(&search), 2);
+        let mut doc_ids = index.doc_ids_containing(&search);
+        doc_ids.sort();
+        assert_eq!(doc_ids, vec![0, 1]);
+        assert_eq!(index.term_frequency(0, &search), 2);
+        assert_eq!(index.term_frequency(1, &"index".to_string()), 1);
+        assert_eq!(index.n_terms(1), 2);
+        assert_eq!(index.terms(), vec!["engine", "index", "search"]);
+        assert_eq!(index.terms_with_prefix("in"), vec!["index"]);
+
+        assert_eq!(index.calc_tf(0, &search), 2.0 / 3.0);
+        let index = index.with_tf_weighting(TfWeighting::Raw);
+        assert_eq!(index.calc_tf(0, &search), 2.0);
+    }
+
+    #[test]
+    fn test_deleted_documents() {
+        let index = SegmentedIndex::new(vec![
+            segment(0, &["search", "engine", "search"]),
+            segment(1, &["search", "index"]),
+            segment(0, &["search"]),
+        ])
+        .with_deleted(vec![BTreeSet::from([0]), BTreeSet::new(), BTreeSet::new()]);
+        let search = "search".to_string();
+        assert_eq!(index.n_docs(), 2);
+        assert_eq!(index.n_terms_total(), 3);
+        let mut doc_lengths = index.doc_lengths();
+        doc_lengths.sort();
+        assert_eq!(doc_lengths, [(0, 1), (1, 2)]);
+        assert_eq!(index.avg_n_terms(), 1.5);
+        assert_eq!(index.n_docs_containing(&search), 2);
+        assert_eq!(index.n_docs_containing(&"engine".to_string()), 0);
+        assert!(index.doc_ids_containing(&"engine".to_string()).is_empty());
+        // The document is read from the newer segment that it is not
+        // deleted from.
+        assert_eq!(index.term_frequency(0, &search), 1);
+        assert_eq!(index.n_terms(0), 1);
+        let postings = index.term_postings(&search);
+        assert!(postings[0].is_empty());
+        assert!(postings[0].blocks.is_empty());
+        assert_eq!(postings[2].postings, vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_manifest() {
+        let mut manifest = Manifest::new();
+        let first = manifest.new_segment_name("bin");
+        let second = manifest.new_segment_name("bin");
+        assert_ne!(first, second);
+        manifest.replace(&[], Some(first.clone()));
+        manifest.replace(&[], Some(second.clone()));
+        let merged = manifest.new_segment_name("bin");
+        manifest.replace(&[first, second], Some(merged.clone()));
+        assert_eq!(manifest.segments(), std::slice::from_ref(&merged));
+
+        let generation = manifest.generation();
+        manifest.delete(&merged, [3, 5]);
+        assert!(manifest.generation() > generation);
+        assert_eq!(manifest.deleted(&merged), Some(&BTreeSet::from([3, 5])));
+        assert_eq!(manifest.n_deleted(), 2);
+        let compacted = manifest.new_segment_name("bin");
+        manifest.replace(&[merged], Some(compacted.clone()));
+        assert_eq!(manifest.deleted(&compacted), None);
+        assert_eq!(manifest.n_deleted(), 0);
+        assert_eq!(manifest.segments(), &[compacted]);
+
+        let generation = manifest.generation();
+        manifest.replace(&[], None);
+        assert!(manifest.generation() > generation);
+        let mut manifest = Manifest::starting_at(manifest.generation());
+        assert!(manifest.new_segment_name("bin") > format!("{generation:08}.bin"));
+
+        assert_eq!(manifest.version(), FORMAT_VERSION);
+        assert!(!manifest.is_outdated());
+
+        let mut legacy: Manifest =
+            serde_json::from_str(r#"{"generation":1,"segments":[]}"#).unwrap();
+        assert_eq!(legacy.tf_weighting(), TfWeighting::Normalized);
+        assert_eq!(legacy.compression(), Compression::None);
+        assert_eq!(legacy.version(), 0);
+        assert!(legacy.is_outdated());
+        legacy.set_upgraded();
+        assert!(!legacy.is_outdated());
+    }
+
+    #[test]
+    fn test_newer_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        let version = FORMAT_VERSION + 1;
+        let contents = format!(r#"{{"version":{version},"generation":1,"segments":[]}}"#);
+        fs::write(&path, contents).unwrap();
+        let err = Manifest::from_file(&path).unwrap_err();
+        assert!(matches!(err, Error::VersionMismatch { found, .. } if found == version));
+    }
+}
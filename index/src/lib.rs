@@ -1,5 +1,16 @@
+mod codec;
 pub mod collection;
+pub mod compress;
+pub mod cooccur;
 pub mod doc;
+pub mod error;
+pub mod fingerprint;
+pub mod graph;
 pub mod inverted;
+pub mod positions;
 pub mod postings;
 pub mod score;
+pub mod store;
+pub mod vector;
+
+pub use error::Error;
@@ -0,0 +1,138 @@
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use crate::codec::{read_delta, read_header, read_varint, write_header, write_varint};
+
+const SIGNATURE: &[u8] = b"SEARPOSS";
+const VERSION: u8 = 1;
+
+/// A map from the positions of the terms of a document to their byte ranges
+/// and lines in the text of the document, as it was when it was indexed.
+///
+/// The position of a term is its index in the stream of terms of the
+/// document. The map is kept with the stored text of the document, so line
+/// numbers and snippets can be produced from the positions of matches
+/// without tokenizing the text again, or reading the original document,
+/// which may have changed since it was indexed.
+///
+/// The start of each term is written as the gap from the start of the
+/// previous one, and the starts of the lines as the gaps between them, with
+/// variable-byte encoding, so most of them take a single byte.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PositionMap {
+    spans: Vec<Range<u32>>,
+    line_starts: Vec<u32>,
+}
+
+impl PositionMap {
+    /// Creates the map of a text from the byte ranges of its terms, in the
+    /// order of their positions. The terms follow each other in the text, so
+    /// their starts never decrease.
+    pub fn new(text: &str, spans: impl IntoIterator<Item = Range<usize>>) -> Self {
+        let spans = spans
+            .into_iter()
+            .map(|span| span.start as u32..span.end as u32)
+            .collect();
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i as u32 + 1))
+            .collect();
+        Self { spans, line_starts }
+    }
+
+    /// Returns the number of term positions in the map.
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Returns true if the document has no terms.
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Returns the byte range of the term at a position, if there is one.
+    pub fn span(&self, position: usize) -> Option<Range<usize>> {
+        let span = self.spans.get(position)?;
+        Some(span.start as usize..span.end as usize)
+    }
+
+    /// Returns the line of the term at a position, counting from 1, if there
+    /// is a term at the position.
+    pub fn line(&self, position: usize) -> Option<usize> {
+        Some(self.line_of(self.span(position)?.start))
+    }
+
+    /// Returns the line of a byte offset in the text, counting from 1.
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.line_starts
+            .partition_point(|start| *start as usize <= offset)
+            .max(1)
+    }
+
+    /// Writes the map to a writer.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_header(writer, SIGNATURE, VERSION)?;
+        write_varint(writer, self.spans.len() as u32)?;
+        let mut prev = 0;
+        for span in &self.spans {
+            write_varint(writer, span.start - prev)?;
+            write_varint(writer, span.end - span.start)?;
+            prev = span.start;
+        }
+        write_varint(writer, self.line_starts.len() as u32)?;
+        let mut prev = 0;
+        for start in &self.line_starts {
+            write_varint(writer, start - prev)?;
+            prev = *start;
+        }
+        writer.flush()
+    }
+
+    /// Reads a map from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader does not contain a position map of
+    /// this version.
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        read_header(reader, SIGNATURE, VERSION)?;
+        let mut spans = vec![];
+        let mut prev = 0;
+        for _ in 0..read_varint(reader)? {
+            let start = read_delta(reader, prev)?;
+            let end = read_delta(reader, start)?;
+            spans.push(start..end);
+            prev = start;
+        }
+        let mut line_starts = vec![];
+        let mut prev = 0;
+        for _ in 0..read_varint(reader)? {
+            prev = read_delta(reader, prev)?;
+            line_starts.push(prev);
+        }
+        Ok(Self { spans, line_starts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_position_map() {
+        let text = "fn main() {\n    println!(\"hi\");\n}\n";
+        let map = PositionMap::new(text, [0..2, 3..7, 16..23, 26..28]);
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.span(2), Some(16..23));
+        assert_eq!(map.line(0), Some(1));
+        assert_eq!(map.line(2), Some(2));
+        assert_eq!(map.line(4), None);
+        assert_eq!(map.line_of(text.len()), 4);
+
+        let mut buffer = vec![];
+        map.write_to(&mut buffer).unwrap();
+        let read = PositionMap::read_from(&mut Cursor::new(buffer)).unwrap();
+        assert_eq!(read, map);
+    }
+}
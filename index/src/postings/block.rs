@@ -0,0 +1,88 @@
+use documents::DocumentId;
+
+/// The number of postings in each block of a postings list. The last block
+/// of a list can have fewer.
+pub const BLOCK_LEN: usize = 128;
+
+/// The bounds of the postings in a block of a postings list.
+///
+/// The score of a term in a document does not decrease as the term appears
+/// more times in it, and does not increase as the document has more terms,
+/// so a term scores at most as much in any document of a block as it would
+/// with the largest frequency of the block, in a document with the fewest
+/// terms of the block. Blocks whose bounds cannot score enough to rank are
+/// skipped without scoring their documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostingsBlock {
+    /// The largest document ID of the block.
+    pub last_doc_id: DocumentId,
+    /// The largest frequency of the term in the documents of the block.
+    pub max_frequency: u32,
+    /// The smallest number of terms of the documents of the block.
+    pub min_terms: u32,
+}
+
+impl PostingsBlock {
+    /// Finds the bounds of a block of postings, sorted by document ID, with
+    /// the number of terms of each document.
+    pub fn of(postings: &[(DocumentId, u32)], n_terms: impl Fn(DocumentId) -> u32) -> Self {
+        Self {
+            last_doc_id: postings.last().map_or(0, |(doc_id, _)| *doc_id),
+            max_frequency: postings.iter().map(|(_, frequency)| *frequency).max().unwrap_or(0),
+            min_terms: postings
+                .iter()
+                .map(|(doc_id, _)| n_terms(*doc_id))
+                .min()
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// The postings of a term, sorted by document ID, with the bounds of each
+/// of their blocks of `BLOCK_LEN` postings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TermPostings {
+    /// The IDs of the documents with the frequency of the term in them.
+    pub postings: Vec<(DocumentId, u32)>,
+    /// The bounds of the blocks of the postings, in order.
+    pub blocks: Vec<PostingsBlock>,
+}
+
+impl TermPostings {
+    /// Splits postings, sorted by document ID, into blocks, with the number
+    /// of terms of each document.
+    pub fn new(postings: Vec<(DocumentId, u32)>, n_terms: impl Fn(DocumentId) -> u32) -> Self {
+        let blocks = postings
+            .chunks(BLOCK_LEN)
+            .map(|block| PostingsBlock::of(block, &n_terms))
+            .collect();
+        Self { postings, blocks }
+    }
+
+    /// Checks if the term is in no document.
+    pub fn is_empty(&self) -> bool {
+        self.postings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks() {
+        let postings = (0..300).map(|doc_id| (doc_id, doc_id % 7 + 1)).collect();
+        let term_postings = TermPostings::new(postings, |doc_id| 1000 - doc_id);
+        assert_eq!(term_postings.blocks.len(), 3);
+        assert_eq!(
+            term_postings.blocks[0],
+            PostingsBlock {
+                last_doc_id: 127,
+                max_frequency: 7,
+                min_terms: 873,
+            }
+        );
+        assert_eq!(term_postings.blocks[2].last_doc_id, 299);
+        assert_eq!(term_postings.blocks[2].min_terms, 701);
+    }
+}
@@ -1,16 +1,21 @@
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
 
 use serde::{Deserialize, Serialize};
 
 use documents::DocumentId;
 
-use crate::postings::{Posting, PostingsList};
+use crate::codec::{read_delta, read_varint, write_varint};
+use crate::postings::impact::impact;
+use crate::postings::{
+    ImpactPostings, Posting, PostingsBlock, PostingsList, TermPostings, BLOCK_LEN,
+};
 
 /// Structure that represents a frequency-posting for a term.
 /// It contains the document ID and the frequency of the term in the document.
 #[derive(Debug, Serialize, Deserialize)]
-pub(crate) struct FrequencyPosting {
+pub struct FrequencyPosting {
     doc_id: DocumentId,
     frequency: u32,
 }
@@ -51,15 +56,172 @@ impl Hash for FrequencyPosting {
 
 /// Structure that represents a list of frequency-postings.
 #[derive(Default, Debug, Serialize, Deserialize)]
-pub(crate) struct FrequencyPostingsList {
+pub struct FrequencyPostingsList {
     inner: HashSet<FrequencyPosting>,
 }
 
 impl FrequencyPostingsList {
     /// Creates a new empty frequency-postings list.
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Self::default()
     }
+
+    /// Adds the postings of another postings list to this one.
+    pub fn merge(&mut self, other: Self) {
+        self.inner.extend(other.inner);
+    }
+
+    /// Returns the document IDs with the frequencies of the term in them,
+    /// sorted by document ID.
+    pub fn sorted(&self) -> Vec<(DocumentId, u32)> {
+        let mut postings = self
+            .inner
+            .iter()
+            .map(|posting| (posting.doc_id(), posting.frequency()))
+            .collect::<Vec<_>>();
+        postings.sort_unstable();
+        postings
+    }
+
+    /// Writes the postings list in the compressed binary format.
+    ///
+    /// The postings are sorted by document ID, and each document ID is
+    /// replaced by its gap from the previous one. Since the gaps and the
+    /// frequencies are mostly small numbers, they are written with
+    /// variable-byte encoding, after the number of postings. The postings
+    /// are split in blocks of `BLOCK_LEN`, and each block starts with its
+    /// bounds: the gap of its last document ID from the one of the block
+    /// before it, its largest frequency and the fewest terms of its
+    /// documents, which are given by `n_terms`.
+    pub fn write_to(
+        &self,
+        writer: &mut impl Write,
+        n_terms: impl Fn(DocumentId) -> u32,
+    ) -> io::Result<()> {
+        let postings = self.sorted();
+        write_varint(writer, postings.len() as u32)?;
+        let mut prev_doc_id = 0;
+        for block in postings.chunks(BLOCK_LEN) {
+            let bounds = PostingsBlock::of(block, &n_terms);
+            write_varint(writer, bounds.last_doc_id - prev_doc_id)?;
+            write_varint(writer, bounds.max_frequency)?;
+            write_varint(writer, bounds.min_terms)?;
+            for (doc_id, frequency) in block {
+                write_varint(writer, doc_id - prev_doc_id)?;
+                write_varint(writer, *frequency)?;
+                prev_doc_id = *doc_id;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the postings list in the compressed binary format, ordered by
+    /// impact (see [`crate::postings::impact`]).
+    ///
+    /// The postings are sorted by their impacts, from the largest, with the
+    /// number of terms of their documents, which are given by `n_terms`, and
+    /// `avg_terms`, the average number of terms of the documents. Ties are
+    /// broken by document ID. The bounds of all the blocks are written after
+    /// the number of postings, and the postings of each block are sorted by
+    /// document ID, with each ID written as its gap from the previous one of
+    /// the block.
+    pub fn write_impact_to(
+        &self,
+        writer: &mut impl Write,
+        n_terms: impl Fn(DocumentId) -> u32,
+        avg_terms: f64,
+    ) -> io::Result<()> {
+        let mut postings = self.sorted();
+        postings.sort_by_key(|(doc_id, frequency)| {
+            (
+                std::cmp::Reverse(impact(*frequency, n_terms(*doc_id), avg_terms)),
+                *doc_id,
+            )
+        });
+        for block in postings.chunks_mut(BLOCK_LEN) {
+            block.sort_unstable();
+        }
+        write_varint(writer, postings.len() as u32)?;
+        let blocks = postings.chunks(BLOCK_LEN).collect::<Vec<_>>();
+        for block in &blocks {
+            let bounds = PostingsBlock::of(block, &n_terms);
+            write_varint(writer, bounds.last_doc_id)?;
+            write_varint(writer, bounds.max_frequency)?;
+            write_varint(writer, bounds.min_terms)?;
+        }
+        for block in blocks {
+            let mut prev_doc_id = 0;
+            for (doc_id, frequency) in block.iter() {
+                write_varint(writer, doc_id - prev_doc_id)?;
+                write_varint(writer, *frequency)?;
+                prev_doc_id = *doc_id;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a postings list that was written in the compressed binary
+    /// format ordered by impact.
+    pub fn read_impact_from(bytes: &[u8]) -> io::Result<Self> {
+        let inner = ImpactPostings::read(bytes)?
+            .read_all()?
+            .into_iter()
+            .map(|(doc_id, frequency)| FrequencyPosting::new(doc_id, frequency))
+            .collect();
+        Ok(Self { inner })
+    }
+
+    /// Reads a postings list that was written in the compressed binary
+    /// format.
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let term_postings = Self::read_term_postings(reader)?;
+        let inner = term_postings
+            .postings
+            .into_iter()
+            .map(|(doc_id, frequency)| FrequencyPosting::new(doc_id, frequency))
+            .collect();
+        Ok(Self { inner })
+    }
+
+    /// Reads a postings list that was written in the compressed binary
+    /// format as the postings of a term, sorted, with the bounds of their
+    /// blocks.
+    pub fn read_term_postings(reader: &mut impl Read) -> io::Result<TermPostings> {
+        let n_postings = read_varint(reader)? as usize;
+        let mut postings = Vec::with_capacity(n_postings);
+        let mut blocks = Vec::with_capacity(n_postings.div_ceil(BLOCK_LEN));
+        let mut doc_id = 0u32;
+        while postings.len() < n_postings {
+            blocks.push(PostingsBlock {
+                last_doc_id: read_delta(reader, doc_id)?,
+                max_frequency: read_varint(reader)?,
+                min_terms: read_varint(reader)?,
+            });
+            for _ in 0..BLOCK_LEN.min(n_postings - postings.len()) {
+                doc_id = read_delta(reader, doc_id)?;
+                let frequency = read_varint(reader)?;
+                postings.push((doc_id, frequency));
+            }
+        }
+        Ok(TermPostings { postings, blocks })
+    }
+
+    /// Reads a postings list that was written in the compressed binary
+    /// format of version 3 of the index layout, before the postings were
+    /// split in blocks, so that old index files can be upgraded.
+    pub(crate) fn read_unblocked_from(reader: &mut impl Read) -> io::Result<Self> {
+        let n_postings = read_varint(reader)?;
+        let mut postings_list = Self {
+            inner: HashSet::with_capacity(n_postings as usize),
+        };
+        let mut doc_id = 0u32;
+        for _ in 0..n_postings {
+            doc_id = read_delta(reader, doc_id)?;
+            let frequency = read_varint(reader)?;
+            postings_list.add(FrequencyPosting::new(doc_id, frequency));
+        }
+        Ok(postings_list)
+    }
 }
 
 impl PostingsList<FrequencyPosting> for FrequencyPostingsList {
@@ -85,6 +247,8 @@ impl PostingsList<FrequencyPosting> for FrequencyPostingsList {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
     use super::*;
 
     #[test]
@@ -111,4 +275,86 @@ mod tests {
         assert_eq!(postings_list.len(), 2);
         assert!(postings_list.get(2).is_none());
     }
+
+    #[test]
+    fn test_compressed_postings_list() {
+        let mut postings_list = FrequencyPostingsList::new();
+        postings_list.add(FrequencyPosting::new(1000, 2));
+        postings_list.add(FrequencyPosting::new(3, 1));
+        postings_list.add(FrequencyPosting::new(1001, 130));
+
+        let mut buffer = vec![];
+        postings_list.write_to(&mut buffer, |_| 200).unwrap();
+        // Count, then the block with its last document 1001, its largest
+        // frequency 130 and its fewest terms 200, then gaps 3, 997, 1 and
+        // frequencies 1, 2, 130.
+        assert_eq!(buffer.len(), 1 + (2 + 2 + 2) + (1 + 1) + (2 + 1) + (1 + 2));
+
+        let loaded = FrequencyPostingsList::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.get(1000).unwrap().frequency(), 2);
+        assert_eq!(loaded.get(1001).unwrap().frequency(), 130);
+
+        let term_postings = FrequencyPostingsList::read_term_postings(&mut buffer.as_slice());
+        let term_postings = term_postings.unwrap();
+        assert_eq!(term_postings.postings, [(3, 1), (1000, 2), (1001, 130)]);
+        assert_eq!(
+            term_postings.blocks,
+            [PostingsBlock {
+                last_doc_id: 1001,
+                max_frequency: 130,
+                min_terms: 200,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_impact_postings_list() {
+        let mut postings_list = FrequencyPostingsList::new();
+        for doc_id in 0..300 {
+            postings_list.add(FrequencyPosting::new(doc_id, doc_id % 10 + 1));
+        }
+        let mut buffer = vec![];
+        let n_terms = |doc_id: DocumentId| 100 + doc_id % 7;
+        postings_list
+            .write_impact_to(&mut buffer, n_terms, 100.0)
+            .unwrap();
+
+        let mut postings = ImpactPostings::read(&buffer).unwrap();
+        assert_eq!(postings.len(), 300);
+        assert_eq!(postings.blocks.len(), 3);
+        assert_eq!(postings.blocks[0].max_frequency, 10);
+        let first = postings.next_block().unwrap().unwrap();
+        assert_eq!(first.len(), BLOCK_LEN);
+        assert!(first.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let min_first = first
+            .iter()
+            .map(|(doc_id, f)| impact(*f, n_terms(*doc_id), 100.0))
+            .min();
+        let rest = postings.read_all().unwrap();
+        let max_rest = rest
+            .iter()
+            .map(|(doc_id, f)| impact(*f, n_terms(*doc_id), 100.0))
+            .max();
+        assert!(min_first >= max_rest);
+        assert_eq!(rest.len(), 300 - BLOCK_LEN);
+
+        let loaded = FrequencyPostingsList::read_impact_from(&buffer).unwrap();
+        assert_eq!(loaded.sorted(), postings_list.sorted());
+        let deleted = BTreeSet::from([0, 1, 2]);
+        let postings = ImpactPostings::read(&buffer)
+            .unwrap()
+            .with_deleted(&deleted);
+        assert_eq!(postings.read_all().unwrap().len(), 297);
+    }
+
+    #[test]
+    fn test_unblocked_postings_list() {
+        let mut buffer = vec![];
+        for value in [2, 3, 1, 997, 2] {
+            write_varint(&mut buffer, value).unwrap();
+        }
+        let loaded = FrequencyPostingsList::read_unblocked_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(loaded.sorted(), [(3, 1), (1000, 2)]);
+    }
 }
@@ -0,0 +1,184 @@
+//! Postings lists that are ordered by impact, so that the postings of a term
+//! that score the most are read first.
+//!
+//! The impact of a posting is a quantized score of the term in the document,
+//! which is found when the list is written. The postings are sorted by their
+//! impacts, from the largest, and split into blocks of `BLOCK_LEN`. The
+//! bounds of all the blocks are written before the postings, so that the
+//! largest score of the postings that are left is known without reading
+//! them, and the blocks are decoded one at a time, only as they are needed.
+//!
+//! ```text
+//! count       number of postings
+//! bounds      last document ID, largest frequency and fewest terms of each block
+//! blocks      postings of each block, sorted by document ID, as gaps and frequencies
+//! ```
+
+use std::collections::BTreeSet;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use documents::DocumentId;
+
+use crate::codec::{read_delta, read_varint};
+use crate::postings::{PostingsBlock, BLOCK_LEN};
+
+/// The saturation of the term frequencies in the impacts, as in BM25.
+const IMPACT_K: f64 = 1.2;
+
+/// The normalization of the term frequencies by the lengths of the
+/// documents in the impacts, as in BM25.
+const IMPACT_B: f64 = 0.75;
+
+/// The order of the postings in the postings lists of an index file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PostingsOrder {
+    /// The postings are sorted by document ID, so that the lists of several
+    /// terms are read side by side.
+    #[default]
+    DocId,
+    /// The postings are sorted by their impacts, so that the best documents
+    /// of a term are read first, and a query can stop reading its lists once
+    /// the rest of them cannot change its best documents.
+    Impact,
+}
+
+/// Calculates the impact of a term that appears `frequency` times in a
+/// document of `n_terms` terms, in an index whose documents have
+/// `avg_terms` terms on average, quantized to a byte.
+///
+/// The impact is the term frequency of BM25, which grows with the frequency
+/// and shrinks with the length of the document, scaled to its largest value.
+pub fn impact(frequency: u32, n_terms: u32, avg_terms: f64) -> u8 {
+    let frequency = frequency as f64;
+    let norm = 1.0 - IMPACT_B + IMPACT_B * n_terms as f64 / avg_terms.max(1.0);
+    let tf = frequency * (IMPACT_K + 1.0) / (frequency + IMPACT_K * norm);
+    (tf / (IMPACT_K + 1.0) * u8::MAX as f64).ceil() as u8
+}
+
+/// The postings of a term in an index file, ordered by impact, which are
+/// decoded a block at a time.
+#[derive(Debug, Clone, Default)]
+pub struct ImpactPostings<'a> {
+    /// The bounds of the blocks of the postings, from the one with the
+    /// largest impacts.
+    pub blocks: Vec<PostingsBlock>,
+    /// The number of postings of the list.
+    n_postings: usize,
+    /// The number of postings that have been decoded.
+    n_read: usize,
+    /// The encoded postings of the blocks that have not been decoded.
+    bytes: &'a [u8],
+    /// The IDs of the documents whose postings are skipped.
+    deleted: Option<&'a BTreeSet<DocumentId>>,
+}
+
+impl<'a> ImpactPostings<'a> {
+    /// Reads the number of postings and the bounds of the blocks of a
+    /// postings list that was written in impact order, leaving the postings
+    /// to be decoded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not a postings list.
+    pub fn read(mut bytes: &'a [u8]) -> io::Result<Self> {
+        let n_postings = read_varint(&mut bytes)? as usize;
+        let n_blocks = n_postings.div_ceil(BLOCK_LEN);
+        let blocks = (0..n_blocks)
+            .map(|_| {
+                Ok(PostingsBlock {
+                    last_doc_id: read_varint(&mut bytes)?,
+                    max_frequency: read_varint(&mut bytes)?,
+                    min_terms: read_varint(&mut bytes)?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            blocks,
+            n_postings,
+            n_read: 0,
+            bytes,
+            deleted: None,
+        })
+    }
+
+    /// Skips the postings of deleted documents when the blocks are decoded.
+    /// The bounds of the blocks still hold for the postings that are left.
+    pub fn with_deleted(mut self, deleted: &'a BTreeSet<DocumentId>) -> Self {
+        self.deleted = Some(deleted);
+        self
+    }
+
+    /// Returns the number of postings of the list, including the ones of
+    /// deleted documents.
+    pub fn len(&self) -> usize {
+        self.n_postings
+    }
+
+    /// Checks if the list has no postings.
+    pub fn is_empty(&self) -> bool {
+        self.n_postings == 0
+    }
+
+    /// Returns the position of the next block to decode, which is the number
+    /// of blocks that have been decoded.
+    pub fn position(&self) -> usize {
+        self.n_read.div_ceil(BLOCK_LEN)
+    }
+
+    /// Decodes the next block of postings, sorted by document ID, or returns
+    /// `None` if every block has been decoded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the block is truncated or corrupted.
+    pub fn next_block(&mut self) -> io::Result<Option<Vec<(DocumentId, u32)>>> {
+        if self.n_read >= self.n_postings {
+            return Ok(None);
+        }
+        let block_len = BLOCK_LEN.min(self.n_postings - self.n_read);
+        let mut postings = Vec::with_capacity(block_len);
+        let mut doc_id = 0;
+        for _ in 0..block_len {
+            doc_id = read_delta(&mut self.bytes, doc_id)?;
+            let frequency = read_varint(&mut self.bytes)?;
+            if self
+                .deleted
+                .is_none_or(|deleted| !deleted.contains(&doc_id))
+            {
+                postings.push((doc_id, frequency));
+            }
+        }
+        self.n_read += block_len;
+        Ok(Some(postings))
+    }
+
+    /// Decodes the postings that are left, sorted by document ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a block is truncated or corrupted.
+    pub fn read_all(mut self) -> io::Result<Vec<(DocumentId, u32)>> {
+        let mut postings = Vec::with_capacity(self.n_postings - self.n_read);
+        while let Some(block) = self.next_block()? {
+            postings.extend(block);
+        }
+        postings.sort_unstable();
+        Ok(postings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_impact() {
+        assert!(impact(2, 100, 100.0) > impact(1, 100, 100.0));
+        assert!(impact(1, 50, 100.0) > impact(1, 200, 100.0));
+        assert_eq!(impact(0, 100, 100.0), 0);
+        assert_eq!(impact(1000, 1, 100.0), u8::MAX);
+    }
+}
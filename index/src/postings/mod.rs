@@ -1,17 +1,25 @@
+//! The postings of the terms of an index: the documents that each term is
+//! in, with its frequency, or its positions, in each of them.
+
 use documents::DocumentId;
-pub(crate) use freq::{FrequencyPosting, FrequencyPostingsList};
+pub use block::{PostingsBlock, TermPostings, BLOCK_LEN};
+pub use freq::{FrequencyPosting, FrequencyPostingsList};
+pub use impact::{ImpactPostings, PostingsOrder};
+pub use pos::{PositionPosting, PositionsPostingsList};
 
-pub(crate) mod freq;
-mod pos;
+pub mod block;
+pub mod freq;
+pub mod impact;
+pub mod pos;
 
-pub(crate) trait Posting {
+pub trait Posting {
     /// Returns the document id of the posting.
     fn doc_id(&self) -> DocumentId;
     /// Returns the frequency of the term in the document.
     fn frequency(&self) -> u32;
 }
 
-pub(crate) trait PostingsList<P>
+pub trait PostingsList<P>
 where
     P: Posting,
 {
@@ -23,6 +31,10 @@ where
     fn get(&self, doc_id: DocumentId) -> Option<&P>;
     /// Returns the number of postings in the list.
     fn len(&self) -> usize;
+    /// Checks if the postings list has no postings.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     /// Returns the document IDs
     fn doc_ids(&self) -> Vec<DocumentId>;
 }
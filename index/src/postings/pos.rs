@@ -7,17 +7,17 @@ use documents::DocumentId;
 
 use crate::postings::{Posting, PostingsList};
 
-/// Structure that represents a frequency-posting for a term.
-/// It contains the document ID and the frequency of the term in the document.
+/// Structure that represents a position-posting for a term.
+/// It contains the document ID and the positions of the term in the document.
 #[derive(Debug, Serialize, Deserialize)]
-pub(crate) struct PositionPosting {
+pub struct PositionPosting {
     doc_id: DocumentId,
     positions: HashSet<u32>,
 }
 
 impl PositionPosting {
-    /// Creates a new frequency-posting, by specifying the document ID
-    /// and the frequency.
+    /// Creates a new position-posting without positions, by specifying the
+    /// document ID.
     pub fn new(doc_id: DocumentId) -> Self {
         Self {
             doc_id,
@@ -25,14 +25,14 @@ impl PositionPosting {
         }
     }
 
-    /// Adds positions in the `PositionPosting`.
-    fn add_position(&mut self, pos: u32) {
+    /// Adds a position of the term in the document.
+    pub fn add_position(&mut self, pos: u32) {
         self.positions.insert(pos);
     }
 }
 
 impl Posting for PositionPosting {
-    /// Returns the document ID of the frequency-posting.
+    /// Returns the document ID of the position-posting.
     fn doc_id(&self) -> DocumentId {
         self.doc_id
     }
@@ -57,15 +57,15 @@ impl Hash for PositionPosting {
     }
 }
 
-/// Structure that represents a list of frequency-postings.
+/// Structure that represents a list of position-postings.
 #[derive(Default)]
-pub(crate) struct PositionsPostingsList {
+pub struct PositionsPostingsList {
     inner: HashSet<PositionPosting>,
 }
 
 impl PositionsPostingsList {
-    /// Creates a new empty frequency-postings list.
-    pub(crate) fn new() -> Self {
+    /// Creates a new empty position-postings list.
+    pub fn new() -> Self {
         Self::default()
     }
 }
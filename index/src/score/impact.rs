@@ -0,0 +1,311 @@
+//! Top-k evaluation of queries over postings that are ordered by impact,
+//! which stops reading the postings once they cannot change the best
+//! documents.
+//!
+//! The blocks of the postings of all the terms are read from the one that
+//! can add the largest score to a document, and the scores of the terms that
+//! were read are added up for each document. After each block, the largest
+//! score that the blocks that are left can add to a document is known from
+//! their bounds. Once the `k`th best document scores more than a document
+//! that was not seen could, and than every other document that was seen
+//! could with the terms that were not read for it, the best documents are
+//! known, and only their scores are completed, by looking up the
+//! frequencies of the terms that were not read for them.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+
+use documents::DocumentId;
+
+use crate::postings::ImpactPostings;
+
+/// The largest number of terms of a query that can be evaluated over
+/// postings that are ordered by impact, as the terms that were read for a
+/// document are kept as the bits of a mask.
+pub const MAX_IMPACT_TERMS: usize = 64;
+
+/// A cursor over the blocks of the postings of a term in a part of the
+/// index, with the largest score that the term adds to a document of the
+/// blocks from each one on.
+struct ImpactCursor<'a> {
+    term: usize,
+    postings: ImpactPostings<'a>,
+    bounds: Vec<f64>,
+}
+
+impl ImpactCursor<'_> {
+    /// Checks if every block of the cursor has been read.
+    fn is_done(&self) -> bool {
+        self.postings.position() >= self.bounds.len()
+    }
+
+    /// Returns the largest score that the term adds to a document of the
+    /// blocks that have not been read.
+    fn bound(&self) -> f64 {
+        self.bounds
+            .get(self.postings.position())
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+/// The score of a document from the terms that were read for it, with the
+/// mask of those terms.
+#[derive(Debug, Clone, Copy, Default)]
+struct Accumulator {
+    score: f64,
+    terms: u64,
+}
+
+/// Finds the `k` documents with the highest scores for the terms of a query,
+/// like [`top_k`](crate::score::topk::top_k), over postings that are ordered
+/// by impact, reading only the blocks of postings that can change the best
+/// documents.
+///
+/// # Arguments
+///
+/// * `terms`      - The postings of each term of the query, in each part of
+///   the index, as [`Index::impact_postings`](crate::inverted::Index::impact_postings)
+///   returns them.
+/// * `score`      - The score of a term, by its position in `terms`, in a
+///   document, by the frequency of the term and the number of terms of the
+///   document. It must not be negative, nor decrease as the frequency
+///   increases, nor increase as the number of terms increases.
+/// * `n_terms`    - The number of terms of a document.
+/// * `frequency`  - The frequency of a term, by its position in `terms`, in
+///   a document, like [`Index::term_frequency`](crate::inverted::Index::term_frequency).
+///   It completes the scores of the best documents.
+/// * `is_allowed` - Whether a document can be ranked.
+/// * `k`          - The number of documents to find.
+///
+/// # Errors
+///
+/// Returns an error if a block of postings cannot be decoded.
+///
+/// # Panics
+///
+/// Panics if there are more than [`MAX_IMPACT_TERMS`] terms.
+pub fn top_k_impact(
+    terms: Vec<Vec<ImpactPostings>>,
+    score: impl Fn(usize, u32, u32) -> f64,
+    n_terms: impl Fn(DocumentId) -> u32,
+    frequency: impl Fn(usize, DocumentId) -> u32,
+    is_allowed: impl Fn(DocumentId) -> bool,
+    k: usize,
+) -> io::Result<Vec<(DocumentId, f64)>> {
+    assert!(
+        terms.len() <= MAX_IMPACT_TERMS,
+        "Too many terms in the query."
+    );
+    if k == 0 {
+        return Ok(vec![]);
+    }
+    let n_query_terms = terms.len();
+    let mut cursors = terms
+        .into_iter()
+        .enumerate()
+        .flat_map(|(term, parts)| parts.into_iter().map(move |postings| (term, postings)))
+        .filter(|(_, postings)| !postings.is_empty())
+        .map(|(term, postings)| {
+            let mut bounds = postings
+                .blocks
+                .iter()
+                .map(|block| score(term, block.max_frequency, block.min_terms))
+                .collect::<Vec<_>>();
+            for i in (1..bounds.len()).rev() {
+                bounds[i - 1] = bounds[i - 1].max(bounds[i]);
+            }
+            ImpactCursor {
+                term,
+                postings,
+                bounds,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut accumulators = HashMap::<DocumentId, Accumulator>::new();
+    let mut best = 0.0_f64;
+    while let Some(cursor) = cursors
+        .iter_mut()
+        .filter(|cursor| !cursor.is_done())
+        .max_by(|a, b| a.bound().total_cmp(&b.bound()))
+    {
+        let term = cursor.term;
+        for (doc_id, frequency) in cursor.postings.next_block()?.unwrap_or_default() {
+            if !is_allowed(doc_id) {
+                continue;
+            }
+            let accumulator = accumulators.entry(doc_id).or_default();
+            accumulator.score += score(term, frequency, n_terms(doc_id));
+            accumulator.terms |= 1 << term;
+            best = best.max(accumulator.score);
+        }
+
+        // The largest score that each term adds to a document whose postings
+        // of it have not been read.
+        let mut term_bounds = vec![0.0_f64; n_query_terms];
+        for cursor in &cursors {
+            term_bounds[cursor.term] = term_bounds[cursor.term].max(cursor.bound());
+        }
+        let unseen = term_bounds.iter().sum::<f64>();
+        if unseen < best && is_settled(&accumulators, &term_bounds, unseen, k) {
+            break;
+        }
+    }
+
+    let mut ranked = accumulators.into_iter().collect::<Vec<_>>();
+    ranked.sort_by(|a, b| by_rank((a.0, a.1.score), (b.0, b.1.score)));
+    ranked.truncate(k);
+    // The best documents are known, but the blocks that were not read can
+    // still have the postings of the terms that were not read for them.
+    let mut ranked = ranked
+        .into_iter()
+        .map(|(doc_id, accumulator)| {
+            let missing = (0..n_query_terms)
+                .filter(|term| accumulator.terms & (1 << term) == 0)
+                .map(|term| (term, frequency(term, doc_id)))
+                .filter(|(_, frequency)| *frequency > 0)
+                .map(|(term, frequency)| score(term, frequency, n_terms(doc_id)))
+                .sum::<f64>();
+            (doc_id, accumulator.score + missing)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| by_rank(*a, *b));
+    Ok(ranked)
+}
+
+/// Orders documents with their scores from the best one, with documents with
+/// equal scores ordered by their IDs.
+fn by_rank(a: (DocumentId, f64), b: (DocumentId, f64)) -> Ordering {
+    b.1.total_cmp(&a.1).then(a.0.cmp(&b.0))
+}
+
+/// Checks if the best `k` documents are known: the `k`th best document
+/// scores more than a document that was not seen could, and ranks ahead of
+/// every other document that was seen, with the largest scores of the terms
+/// that were not read for it.
+fn is_settled(
+    accumulators: &HashMap<DocumentId, Accumulator>,
+    term_bounds: &[f64],
+    unseen: f64,
+    k: usize,
+) -> bool {
+    if accumulators.len() < k {
+        return false;
+    }
+    let mut ranked = accumulators
+        .iter()
+        .map(|(doc_id, accumulator)| (*doc_id, accumulator.score))
+        .collect::<Vec<_>>();
+    let (_, kth, _) = ranked.select_nth_unstable_by(k - 1, |a, b| by_rank(*a, *b));
+    let kth = *kth;
+    if unseen >= kth.1 {
+        return false;
+    }
+    accumulators.iter().all(|(doc_id, accumulator)| {
+        if by_rank((*doc_id, accumulator.score), kth) != Ordering::Greater {
+            return true;
+        }
+        let missing = term_bounds
+            .iter()
+            .enumerate()
+            .filter(|(term, _)| accumulator.terms & (1 << term) == 0)
+            .map(|(_, bound)| bound)
+            .sum::<f64>();
+        by_rank((*doc_id, accumulator.score + missing), kth) == Ordering::Greater
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::postings::{FrequencyPosting, FrequencyPostingsList, PostingsList, TermPostings};
+    use crate::score::topk::top_k;
+
+    use super::*;
+
+    #[test]
+    fn test_top_k_impact() {
+        let n_terms = |doc_id: DocumentId| 50 + doc_id % 13;
+        // A common term, a rarer one and a rare one, in two parts.
+        let postings = |step: u32, range: std::ops::Range<u32>| {
+            let mut postings_list = FrequencyPostingsList::new();
+            for doc_id in range.filter(|doc_id| doc_id % step == 0) {
+                postings_list.add(FrequencyPosting::new(doc_id, doc_id % 5 + 1));
+            }
+            postings_list
+        };
+        let lists = [1, 7, 97].map(|step| [postings(step, 0..5000), postings(step, 5000..10000)]);
+        let bytes = lists.each_ref().map(|parts| {
+            parts.each_ref().map(|postings_list| {
+                let mut buffer = vec![];
+                postings_list
+                    .write_impact_to(&mut buffer, n_terms, 56.0)
+                    .unwrap();
+                buffer
+            })
+        });
+        let impact_postings = || {
+            bytes
+                .iter()
+                .map(|parts| {
+                    parts
+                        .iter()
+                        .map(|bytes| ImpactPostings::read(bytes).unwrap())
+                        .collect()
+                })
+                .collect::<Vec<_>>()
+        };
+        let term_postings = lists
+            .iter()
+            .map(|parts| {
+                parts
+                    .iter()
+                    .map(|postings_list| TermPostings::new(postings_list.sorted(), n_terms))
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        let frequencies = lists.each_ref().map(|parts| {
+            parts
+                .iter()
+                .flat_map(|postings_list| postings_list.sorted())
+                .collect::<HashMap<_, _>>()
+        });
+        let frequency =
+            |term: usize, doc_id: DocumentId| frequencies[term].get(&doc_id).copied().unwrap_or(0);
+        let idf = [0.1, 1.0, 3.0];
+        let score = |term: usize, frequency: u32, n_terms: u32| {
+            idf[term] * frequency as f64 / n_terms as f64
+        };
+
+        for k in [1, 10, 100] {
+            let ranked =
+                top_k_impact(impact_postings(), score, n_terms, frequency, |_| true, k).unwrap();
+            let expected = top_k(&term_postings, score, n_terms, |_| true, k);
+            assert_eq!(ranked.len(), k);
+            for ((doc_id, doc_score), (expected_id, expected_score)) in ranked.iter().zip(&expected)
+            {
+                assert_eq!(doc_id, expected_id);
+                assert!((doc_score - expected_score).abs() < 1e-9);
+            }
+        }
+
+        let is_allowed = |doc_id: DocumentId| doc_id % 2 == 1;
+        let ranked =
+            top_k_impact(impact_postings(), score, n_terms, frequency, is_allowed, 10).unwrap();
+        let expected = top_k(&term_postings, score, n_terms, is_allowed, 10);
+        let doc_ids =
+            |ranked: &[(DocumentId, f64)]| ranked.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(doc_ids(&ranked), doc_ids(&expected));
+        assert!(
+            top_k_impact(impact_postings(), score, n_terms, frequency, |_| true, 0)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            top_k_impact(vec![], score, n_terms, frequency, |_| true, 10)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
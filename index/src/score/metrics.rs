@@ -36,6 +36,25 @@ pub fn calc_tf(t: u32, d: u32) -> f64 {
     }
 }
 
+/// Calculates the logarithmically scaled term frequency of a term in a
+/// document, `1 + ln(t)`, so that each further occurrence of a term adds
+/// less to its frequency than the one before.
+///
+/// # Arguments
+///
+/// * `t` - The number of times the term appears in the document.
+///
+/// # Returns
+///
+/// The scaled term frequency, or 0 if the term is not in the document.
+pub fn calc_log_tf(t: u32) -> f64 {
+    if t == 0 {
+        0.0
+    } else {
+        1.0 + (t as f64).ln()
+    }
+}
+
 /// Calculates the inverse term frequency of a term in a collection of documents.
 ///
 /// The inverse document frequency is a measure of how much information the word
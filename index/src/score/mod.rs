@@ -1,9 +1,43 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 use documents::DocumentId;
 pub use metrics::*;
 
+pub mod impact;
 pub mod metrics;
+pub mod topk;
+
+/// The way the number of times a term appears in a document is weighted
+/// into its term frequency (TF).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TfWeighting {
+    /// The number of times the term appears in the document, which favors
+    /// long documents, as they repeat terms more.
+    Raw,
+    /// The logarithm of the number of times the term appears, `1 + ln(t)`,
+    /// so that repeating a term adds less and less to its weight.
+    Log,
+    /// The number of times the term appears divided by the number of terms
+    /// in the document, so that documents of any length weigh the same.
+    #[default]
+    Normalized,
+}
+
+impl TfWeighting {
+    /// Calculates the term frequency of a term that appears `t` times in a
+    /// document of `d` terms.
+    pub fn calc(self, t: u32, d: u32) -> f64 {
+        match self {
+            TfWeighting::Raw => t as f64,
+            TfWeighting::Log => calc_log_tf(t),
+            TfWeighting::Normalized => calc_tf(t, d),
+        }
+    }
+}
+
 /// Stores the scores of each document.
 #[derive(Default, Debug)]
 pub struct DocumentsScores {
@@ -33,10 +67,26 @@ impl DocumentsScores {
         *self.inner.get(&doc_id).unwrap_or(&0.0)
     }
 
-    /// Returns a vector of the documents with the top n scores.
+    /// Keeps only the scores of the documents for which the predicate is
+    /// true, such as the documents a client is allowed to see.
+    pub fn retain(&mut self, mut predicate: impl FnMut(DocumentId) -> bool) {
+        self.inner.retain(|doc_id, _| predicate(*doc_id));
+    }
+
+    /// Multiplies the score of each document by a weight, such as a weight
+    /// of the document that does not depend on the query.
+    pub fn scale(&mut self, mut weight: impl FnMut(DocumentId) -> f64) {
+        for (doc_id, score) in self.inner.iter_mut() {
+            *score *= weight(*doc_id);
+        }
+    }
+
+    /// Returns a vector of the documents with the top n scores. Documents
+    /// with equal scores are ordered by their IDs, so the order is the same
+    /// every time.
     pub fn get_top_n(&self, top_n: u32) -> Vec<(&u32, &f64)> {
         let mut elements = self.inner.iter().collect::<Vec<_>>();
-        elements.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap().reverse());
+        elements.sort_by(|a, b| b.1.total_cmp(a.1).then(a.0.cmp(b.0)));
         elements.into_iter().take(top_n as usize).collect()
     }
 }
@@ -45,6 +95,16 @@ impl DocumentsScores {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tf_weighting() {
+        assert_eq!(TfWeighting::Raw.calc(4, 8), 4.0);
+        assert_eq!(TfWeighting::Log.calc(1, 8), 1.0);
+        assert!((TfWeighting::Log.calc(4, 8) - (1.0 + 4f64.ln())).abs() < 1e-12);
+        assert_eq!(TfWeighting::Normalized.calc(4, 8), 0.5);
+        assert_eq!(TfWeighting::Log.calc(0, 8), 0.0);
+        assert_eq!(TfWeighting::Normalized.calc(0, 0), 0.0);
+    }
+
     #[test]
     fn test_add_get_score() {
         let mut ds = DocumentsScores::new();
@@ -54,5 +114,15 @@ mod tests {
         assert_eq!(ds.get_score(0), 0.7);
         assert_eq!(ds.get_score(1), 0.3);
         assert_eq!(ds.get_score(2), 0.0);
+
+        ds.add_score(2, 0.3);
+        assert_eq!(ds.get_top_n(3), [(&0, &0.7), (&1, &0.3), (&2, &0.3)]);
+
+        ds.retain(|doc_id| doc_id != 0);
+        assert_eq!(ds.get_score(0), 0.0);
+        assert_eq!(ds.get_top_n(2), [(&1, &0.3), (&2, &0.3)]);
+
+        ds.scale(|doc_id| if doc_id == 2 { 2.0 } else { 1.0 });
+        assert_eq!(ds.get_top_n(2), [(&2, &0.6), (&1, &0.3)]);
     }
 }
@@ -0,0 +1,422 @@
+//! Top-k evaluation of queries, which finds the documents with the highest
+//! scores without scoring every document that contains a term of the query.
+//!
+//! The evaluator is a MaxScore evaluator with the bounds of the blocks of
+//! the postings. The terms are ordered by the largest score they can add to
+//! a document. Once `k` documents have been found, the terms whose scores
+//! add up to less than the score of the `k`th of them cannot rank a document
+//! alone, so only the documents of the other terms are candidates, and the
+//! postings of the former are only looked up for the candidates. A candidate
+//! is skipped, along with the rest of the blocks that it is in, if the
+//! bounds of those blocks cannot add up to the score of the `k`th document.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::ops::RangeInclusive;
+
+use rayon::prelude::*;
+
+use documents::DocumentId;
+
+use crate::postings::{TermPostings, BLOCK_LEN};
+
+/// The smallest number of postings of the longest postings list of each
+/// range of documents that is ranked in parallel, so that short lists are
+/// ranked without the cost of splitting them.
+const MIN_RANGE_POSTINGS: usize = 16 * BLOCK_LEN;
+
+/// A document with its score, ordered from the worst to the best: by its
+/// score, and then by its ID in reverse, as documents with equal scores are
+/// ranked by their IDs.
+#[derive(Debug, Clone, Copy)]
+struct Ranked {
+    score: f64,
+    doc_id: DocumentId,
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(other.doc_id.cmp(&self.doc_id))
+    }
+}
+
+/// The `k` best documents that have been found so far.
+struct TopK {
+    k: usize,
+    heap: BinaryHeap<Reverse<Ranked>>,
+}
+
+impl TopK {
+    /// Creates an empty set of the `k` best documents.
+    fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    /// Checks if a document with a score of at most `bound` can be among the
+    /// best documents. A document with the score of the worst of them can
+    /// still replace it, if it has a smaller ID.
+    fn is_competitive(&self, bound: f64) -> bool {
+        match self.heap.peek() {
+            Some(Reverse(worst)) if self.heap.len() >= self.k => bound >= worst.score,
+            _ => self.k > 0,
+        }
+    }
+
+    /// Adds a document, if it is better than the worst of the best ones.
+    fn insert(&mut self, doc_id: DocumentId, score: f64) {
+        let ranked = Ranked { score, doc_id };
+        if self.heap.len() < self.k {
+            self.heap.push(Reverse(ranked));
+        } else if self.heap.peek().is_some_and(|Reverse(worst)| ranked > *worst) {
+            self.heap.pop();
+            self.heap.push(Reverse(ranked));
+        }
+    }
+}
+
+/// A cursor over the postings of a term in a range of documents, with the
+/// largest score that the term adds to any document.
+struct Cursor<'a> {
+    term: usize,
+    postings: &'a TermPostings,
+    position: usize,
+    last: DocumentId,
+    max_score: f64,
+}
+
+impl Cursor<'_> {
+    /// Returns the ID of the document that the cursor is at, or `None` if it
+    /// is past the last posting of its range.
+    fn doc_id(&self) -> Option<DocumentId> {
+        self.postings
+            .postings
+            .get(self.position)
+            .map(|(doc_id, _)| *doc_id)
+            .filter(|doc_id| *doc_id <= self.last)
+    }
+
+    /// Finds the block of the cursor, or of a later posting, that a document
+    /// would be in, without moving the cursor.
+    fn block_of(&self, doc_id: DocumentId) -> Option<usize> {
+        let blocks = &self.postings.blocks;
+        (self.position / BLOCK_LEN..blocks.len()).find(|block| blocks[*block].last_doc_id >= doc_id)
+    }
+
+    /// Moves the cursor to the first posting of a document with an ID of at
+    /// least `doc_id`, skipping the blocks that end before it.
+    fn seek(&mut self, doc_id: DocumentId) {
+        let Some(block) = self.block_of(doc_id) else {
+            self.position = self.postings.postings.len();
+            return;
+        };
+        let start = self.position.max(block * BLOCK_LEN);
+        let end = ((block + 1) * BLOCK_LEN).min(self.postings.postings.len());
+        let postings = &self.postings.postings[start..end];
+        self.position = start + postings.partition_point(|(other, _)| *other < doc_id);
+    }
+}
+
+/// Finds the `k` documents with the highest scores for the terms of a query,
+/// ordered from the best one, with documents with equal scores ordered by
+/// their IDs. The score of a document is the sum of the scores of the terms
+/// that it contains.
+///
+/// The documents of each part are split into ranges with about as many
+/// postings, which are ranked in parallel, each with its own best documents,
+/// and the best documents of all the ranges are then merged.
+///
+/// # Arguments
+///
+/// * `terms`      - The postings of each term of the query, in each part of
+///   the index, as [`Index::term_postings`](crate::inverted::Index::term_postings)
+///   returns them.
+/// * `score`      - The score of a term, by its position in `terms`, in a
+///   document, by the frequency of the term and the number of terms of the
+///   document. It must not be negative, nor decrease as the frequency
+///   increases, nor increase as the number of terms increases.
+/// * `n_terms`    - The number of terms of a document.
+/// * `is_allowed` - Whether a document can be ranked.
+/// * `k`          - The number of documents to find.
+pub fn top_k(
+    terms: &[Vec<TermPostings>],
+    score: impl Fn(usize, u32, u32) -> f64 + Sync,
+    n_terms: impl Fn(DocumentId) -> u32 + Sync,
+    is_allowed: impl Fn(DocumentId) -> bool + Sync,
+    k: usize,
+) -> Vec<(DocumentId, f64)> {
+    let n_parts = terms.iter().map(Vec::len).max().unwrap_or(0);
+    let ranges = (0..n_parts)
+        .flat_map(|part| {
+            let postings = terms.iter().filter_map(|parts| parts.get(part));
+            split_range(postings, rayon::current_num_threads())
+                .into_iter()
+                .map(move |range| (part, range))
+        })
+        .collect::<Vec<_>>();
+    let tops = ranges
+        .into_par_iter()
+        .map(|(part, range)| {
+            let mut top = TopK::new(k);
+            let cursors = terms
+                .iter()
+                .enumerate()
+                .filter_map(|(term, parts)| Some((term, parts.get(part)?)))
+                .filter(|(_, postings)| !postings.is_empty())
+                .map(|(term, postings)| {
+                    let mut cursor = Cursor {
+                        term,
+                        postings,
+                        position: 0,
+                        last: *range.end(),
+                        max_score: postings
+                            .blocks
+                            .iter()
+                            .map(|block| score(term, block.max_frequency, block.min_terms))
+                            .fold(0.0, f64::max),
+                    };
+                    cursor.seek(*range.start());
+                    cursor
+                })
+                .collect();
+            rank_range(cursors, &mut top, &score, &n_terms, &is_allowed);
+            top
+        })
+        .collect::<Vec<_>>();
+
+    let mut top = TopK::new(k);
+    for Reverse(ranked) in tops.into_iter().flat_map(|top| top.heap) {
+        top.insert(ranked.doc_id, ranked.score);
+    }
+    let mut ranked = top
+        .heap
+        .into_iter()
+        .map(|Reverse(ranked)| ranked)
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.cmp(a));
+    ranked
+        .into_iter()
+        .map(|ranked| (ranked.doc_id, ranked.score))
+        .collect()
+}
+
+/// Splits the documents of a part of the index into at most `n_ranges`
+/// ranges, at the documents of the longest postings list of the part, so
+/// that the ranges have about as many postings. Postings lists that are too
+/// short to be worth ranking in parallel are not split.
+fn split_range<'a>(
+    postings: impl Iterator<Item = &'a TermPostings>,
+    n_ranges: usize,
+) -> Vec<RangeInclusive<DocumentId>> {
+    let longest = postings
+        .map(|postings| postings.postings.as_slice())
+        .max_by_key(|postings| postings.len())
+        .unwrap_or_default();
+    let n_ranges = n_ranges.clamp(1, longest.len() / MIN_RANGE_POSTINGS + 1);
+    let mut starts = (1..n_ranges)
+        .map(|i| longest[i * longest.len() / n_ranges].0)
+        .collect::<Vec<_>>();
+    starts.dedup();
+
+    let mut ranges = vec![];
+    let mut first = 0;
+    for start in starts.into_iter().filter(|start| *start > 0) {
+        ranges.push(first..=start - 1);
+        first = start;
+    }
+    ranges.push(first..=DocumentId::MAX);
+    ranges
+}
+
+/// Ranks the documents of a range of a part of the index, with the cursors
+/// of its terms.
+fn rank_range(
+    mut cursors: Vec<Cursor>,
+    top: &mut TopK,
+    score: &impl Fn(usize, u32, u32) -> f64,
+    n_terms: &impl Fn(DocumentId) -> u32,
+    is_allowed: &impl Fn(DocumentId) -> bool,
+) {
+    cursors.sort_by(|a, b| a.max_score.total_cmp(&b.max_score));
+    // The largest score of the terms up to each one, in order.
+    let max_scores = cursors
+        .iter()
+        .scan(0.0, |sum, cursor| {
+            *sum += cursor.max_score;
+            Some(*sum)
+        })
+        .collect::<Vec<_>>();
+    let last = cursors.first().map_or(0, |cursor| cursor.last);
+
+    loop {
+        // The terms that cannot rank a document alone are not essential.
+        let n_optional = max_scores
+            .iter()
+            .take_while(|max_score| !top.is_competitive(**max_score))
+            .count();
+        let (optional, essential) = cursors.split_at_mut(n_optional);
+        let Some(doc_id) = essential.iter().filter_map(Cursor::doc_id).min() else {
+            return;
+        };
+        if !is_allowed(doc_id) {
+            essential
+                .iter_mut()
+                .filter(|cursor| cursor.doc_id() == Some(doc_id))
+                .for_each(|cursor| cursor.position += 1);
+            continue;
+        }
+
+        // The bounds of the blocks hold for every document up to the end of
+        // the first of them to end.
+        let mut bound = 0.0;
+        let mut blocks_end = DocumentId::MAX;
+        for cursor in optional.iter().chain(essential.iter()) {
+            if let Some(block) = cursor.block_of(doc_id) {
+                let block = cursor.postings.blocks[block];
+                bound += score(cursor.term, block.max_frequency, block.min_terms);
+                blocks_end = blocks_end.min(block.last_doc_id);
+            }
+        }
+        if !top.is_competitive(bound) {
+            if blocks_end >= last {
+                return;
+            }
+            for cursor in essential.iter_mut() {
+                cursor.seek(blocks_end + 1);
+            }
+            continue;
+        }
+
+        let doc_terms = n_terms(doc_id);
+        let mut doc_score = 0.0;
+        for cursor in essential.iter_mut() {
+            if let Some((other, frequency)) = cursor.postings.postings.get(cursor.position) {
+                if *other == doc_id {
+                    doc_score += score(cursor.term, *frequency, doc_terms);
+                    cursor.position += 1;
+                }
+            }
+        }
+        // The optional terms are looked up from the one with the largest
+        // score, until the rest cannot make the document competitive.
+        for (i, cursor) in optional.iter_mut().enumerate().rev() {
+            if !top.is_competitive(doc_score + max_scores[i]) {
+                break;
+            }
+            cursor.seek(doc_id);
+            if let Some((other, frequency)) = cursor.postings.postings.get(cursor.position) {
+                if *other == doc_id {
+                    doc_score += score(cursor.term, *frequency, doc_terms);
+                }
+            }
+        }
+        // A document that is skipped for its optional terms cannot rank,
+        // so it is not added, like the documents of skipped blocks.
+        if top.is_competitive(doc_score) {
+            top.insert(doc_id, doc_score);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scores every document that contains a term, for comparison.
+    fn exhaustive(
+        terms: &[Vec<TermPostings>],
+        score: impl Fn(usize, u32, u32) -> f64,
+        n_terms: impl Fn(DocumentId) -> u32,
+        k: usize,
+    ) -> Vec<(DocumentId, f64)> {
+        let mut scores = std::collections::BTreeMap::<DocumentId, f64>::new();
+        for (term, parts) in terms.iter().enumerate() {
+            for (doc_id, frequency) in parts.iter().flat_map(|part| &part.postings) {
+                *scores.entry(*doc_id).or_default() += score(term, *frequency, n_terms(*doc_id));
+            }
+        }
+        let mut scores = scores.into_iter().collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(k);
+        scores
+    }
+
+    #[test]
+    fn test_top_k() {
+        let n_terms = |doc_id: DocumentId| 50 + doc_id % 13;
+        // A common term, a rarer one and a rare one, in two parts.
+        let postings = |step: u32, parts: [std::ops::Range<u32>; 2]| {
+            parts
+                .map(|range| {
+                    let postings = range
+                        .filter(|doc_id| doc_id % step == 0)
+                        .map(|doc_id| (doc_id, doc_id % 5 + 1))
+                        .collect();
+                    TermPostings::new(postings, n_terms)
+                })
+                .to_vec()
+        };
+        let terms = [
+            postings(1, [0..10000, 10000..20000]),
+            postings(7, [0..10000, 10000..20000]),
+            postings(97, [0..10000, 10000..20000]),
+        ];
+        let idf = [0.1, 1.0, 3.0];
+        let score = |term: usize, frequency: u32, n_terms: u32| {
+            idf[term] * frequency as f64 / n_terms as f64
+        };
+
+        for k in [1, 10, 100] {
+            let ranked = top_k(&terms, score, n_terms, |_| true, k);
+            let expected = exhaustive(&terms, score, n_terms, k);
+            assert_eq!(ranked.len(), k);
+            for ((doc_id, doc_score), (expected_id, expected_score)) in
+                ranked.iter().zip(&expected)
+            {
+                assert_eq!(doc_id, expected_id);
+                assert!((doc_score - expected_score).abs() < 1e-9);
+            }
+        }
+
+        let ranked = top_k(&terms, score, n_terms, |doc_id| doc_id % 2 == 1, 10);
+        assert!(ranked.iter().all(|(doc_id, _)| doc_id % 2 == 1));
+        assert_eq!(ranked.len(), 10);
+        assert!(top_k(&terms, score, n_terms, |_| true, 0).is_empty());
+        assert!(top_k(&[], score, n_terms, |_| true, 10).is_empty());
+    }
+
+    #[test]
+    fn test_split_range() {
+        let postings = |doc_ids: std::ops::Range<u32>| {
+            TermPostings::new(doc_ids.map(|doc_id| (doc_id, 1)).collect(), |_| 1)
+        };
+        let long = postings(100..100 + 4 * MIN_RANGE_POSTINGS as u32);
+        let short = postings(0..10);
+
+        let ranges = split_range([&short, &long].into_iter(), 4);
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(*ranges[0].start(), 0);
+        assert_eq!(*ranges[0].end() + 1, *ranges[1].start());
+        assert_eq!(*ranges[3].end(), DocumentId::MAX);
+        assert_eq!(split_range([&short].into_iter(), 4), vec![0..=DocumentId::MAX]);
+        assert_eq!(split_range([].into_iter(), 4), vec![0..=DocumentId::MAX]);
+    }
+}
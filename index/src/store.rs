@@ -0,0 +1,243 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use documents::{DocumentId, Link};
+
+use crate::error::{Error, Result};
+use crate::fingerprint::Fingerprint;
+use crate::positions::PositionMap;
+
+/// A store of the text extracted from the indexed documents.
+///
+/// The text of each document is stored in a separate file, named after the
+/// document ID, so it can be displayed or searched for matches without
+/// extracting it again from the original document, which may be slow, or
+/// may have changed since it was indexed. Along with the text, the map of
+/// the positions of its terms to their byte ranges and lines is stored, the
+/// links of the document to other files, if it has any, the fingerprint of
+/// its text, if it has words, and the hash of its text, by which identical
+/// documents are found.
+#[derive(Debug, Clone)]
+pub struct DocumentStore {
+    dir: PathBuf,
+}
+
+impl DocumentStore {
+    /// Opens the document store at the specified directory, creating the
+    /// directory if it does not exist.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(Error::at(&dir))?;
+        Ok(Self { dir })
+    }
+
+    /// Creates a new, empty document store at the specified directory. If a
+    /// store already exists there, all of its documents are removed.
+    pub fn create(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        if dir.exists() {
+            fs::remove_dir_all(dir).map_err(Error::at(dir))?;
+        }
+        Self::open(dir)
+    }
+
+    /// Moves the store to another directory, replacing the store that is
+    /// there, if any. A new store can be built next to the one that is in
+    /// use, and moved in its place once it is complete.
+    pub fn replace(self, dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        if dir.exists() {
+            fs::remove_dir_all(dir).map_err(Error::at(dir))?;
+        }
+        fs::rename(&self.dir, dir).map_err(Error::at(dir))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Stores the text of the document with the specified ID, replacing any
+    /// previously stored text.
+    ///
+    /// The text is written to a temporary file first, which then replaces
+    /// the stored text, so a query that reads it at the same time never sees
+    /// a partly written text.
+    pub fn write(&self, doc_id: DocumentId, text: &str) -> Result<()> {
+        let path = self.path(doc_id);
+        let tmp_path = path.with_extension("txt.tmp");
+        fs::write(&tmp_path, text).map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, &path).map_err(Error::at(&path))
+    }
+
+    /// Returns the stored text of the document with the specified ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text of the document is not in the store.
+    pub fn read(&self, doc_id: DocumentId) -> Result<String> {
+        let path = self.path(doc_id);
+        fs::read_to_string(&path).map_err(Error::at(&path))
+    }
+
+    /// Stores the position map of the document with the specified ID,
+    /// replacing any previously stored map.
+    pub fn write_positions(&self, doc_id: DocumentId, positions: &PositionMap) -> Result<()> {
+        let path = self.positions_path(doc_id);
+        let tmp_path = path.with_extension("pos.tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        positions
+            .write_to(&mut BufWriter::new(file))
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, &path).map_err(Error::at(&path))
+    }
+
+    /// Returns the stored position map of the document with the specified ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the map of the document is not in the store,
+    /// because it was indexed before maps were stored.
+    pub fn read_positions(&self, doc_id: DocumentId) -> Result<PositionMap> {
+        let path = self.positions_path(doc_id);
+        let file = File::open(&path).map_err(Error::at(&path))?;
+        PositionMap::read_from(&mut BufReader::new(file)).map_err(Error::at(&path))
+    }
+
+    /// Stores the links of the document with the specified ID, one per
+    /// line, replacing any previously stored links. Documents without links
+    /// have no file of links.
+    pub fn write_links(&self, doc_id: DocumentId, links: &[Link]) -> Result<()> {
+        let path = self.links_path(doc_id);
+        if links.is_empty() {
+            return remove_file(&path);
+        }
+        let tmp_path = path.with_extension("links.tmp");
+        let text = links
+            .iter()
+            .map(|link| format!("{link}\n"))
+            .collect::<String>();
+        fs::write(&tmp_path, text).map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, &path).map_err(Error::at(&path))
+    }
+
+    /// Returns the stored links of the document with the specified ID. A
+    /// document whose links are not in the store has none.
+    pub fn read_links(&self, doc_id: DocumentId) -> Result<Vec<Link>> {
+        let path = self.links_path(doc_id);
+        match fs::read_to_string(&path) {
+            Ok(text) => Ok(text.lines().map(Link::parse).collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(err) => Err(Error::from_io(&path, err)),
+        }
+    }
+
+    /// Stores the fingerprint of the text of the document with the specified
+    /// ID, replacing any previously stored fingerprint. Documents without a
+    /// fingerprint have no file of it.
+    pub fn write_fingerprint(
+        &self,
+        doc_id: DocumentId,
+        fingerprint: Option<Fingerprint>,
+    ) -> Result<()> {
+        let path = self.fingerprint_path(doc_id);
+        let Some(fingerprint) = fingerprint else {
+            return remove_file(&path);
+        };
+        let tmp_path = path.with_extension("fp.tmp");
+        fs::write(&tmp_path, format!("{:016x}", fingerprint.bits()))
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, &path).map_err(Error::at(&path))
+    }
+
+    /// Returns the stored fingerprint of the document with the specified ID,
+    /// or `None` if it is not in the store, because the document has no
+    /// words, or because it was indexed before fingerprints were stored.
+    pub fn read_fingerprint(&self, doc_id: DocumentId) -> Result<Option<Fingerprint>> {
+        let path = self.fingerprint_path(doc_id);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::from_io(&path, err)),
+        };
+        match u64::from_str_radix(text.trim(), 16) {
+            Ok(bits) => Ok(Some(Fingerprint::from_bits(bits))),
+            Err(err) => Err(Error::Corrupt {
+                path,
+                reason: err.to_string(),
+            }),
+        }
+    }
+
+    /// Stores the hash of the text of the document with the specified ID, in
+    /// hex, replacing any previously stored hash.
+    pub fn write_content_hash(&self, doc_id: DocumentId, hash: &str) -> Result<()> {
+        let path = self.content_hash_path(doc_id);
+        let tmp_path = path.with_extension("hash.tmp");
+        fs::write(&tmp_path, hash).map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, &path).map_err(Error::at(&path))
+    }
+
+    /// Returns the stored hash of the text of the document with the
+    /// specified ID, or `None` if it is not in the store, because the
+    /// document was indexed before hashes were stored.
+    pub fn read_content_hash(&self, doc_id: DocumentId) -> Result<Option<String>> {
+        let path = self.content_hash_path(doc_id);
+        match fs::read_to_string(&path) {
+            Ok(hash) => Ok(Some(hash.trim().to_string())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::from_io(&path, err)),
+        }
+    }
+
+    /// Removes the stored text, position map, links, fingerprint and hash of
+    /// the document with the specified ID. If they are not in the store, nothing
+    /// is done.
+    pub fn remove(&self, doc_id: DocumentId) -> Result<()> {
+        for path in [
+            self.path(doc_id),
+            self.positions_path(doc_id),
+            self.links_path(doc_id),
+            self.fingerprint_path(doc_id),
+            self.content_hash_path(doc_id),
+        ] {
+            remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the path to the file that stores the text of a document.
+    fn path(&self, doc_id: DocumentId) -> PathBuf {
+        self.dir.join(format!("{doc_id}.txt"))
+    }
+
+    /// Returns the path to the file that stores the position map of a
+    /// document.
+    fn positions_path(&self, doc_id: DocumentId) -> PathBuf {
+        self.dir.join(format!("{doc_id}.pos"))
+    }
+
+    /// Returns the path to the file that stores the links of a document.
+    fn links_path(&self, doc_id: DocumentId) -> PathBuf {
+        self.dir.join(format!("{doc_id}.links"))
+    }
+
+    /// Returns the path to the file that stores the fingerprint of a
+    /// document.
+    fn fingerprint_path(&self, doc_id: DocumentId) -> PathBuf {
+        self.dir.join(format!("{doc_id}.fp"))
+    }
+
+    /// Returns the path to the file that stores the hash of the text of a
+    /// document.
+    fn content_hash_path(&self, doc_id: DocumentId) -> PathBuf {
+        self.dir.join(format!("{doc_id}.hash"))
+    }
+}
+
+/// Removes a file of the store, if it exists.
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(Error::from_io(path, err)),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use documents::DocumentId;
+
+use crate::codec::{
+    read_f32, read_header, read_str, read_u32, write_f32, write_header, write_str, write_u32,
+};
+use crate::error::{Error, Result};
+
+pub use quantize::{Quantization, QuantizedIndex};
+
+mod quantize;
+
+const SIGNATURE: &[u8] = b"SEARVECS";
+const VERSION: u8 = 1;
+
+/// The semantic index of a collection, which stores an embedding vector for
+/// each document.
+///
+/// Vectors are normalized when they are inserted, so the cosine similarity
+/// of two vectors is their dot product. The identifier of the model that
+/// produced the vectors is kept with them, because vectors of different
+/// models cannot be compared.
+#[derive(Debug, Default)]
+pub struct VectorIndex {
+    model_id: String,
+    dimension: usize,
+    vectors: HashMap<DocumentId, Vec<f32>>,
+}
+
+impl VectorIndex {
+    /// Creates a new, empty vector index for the vectors of a model.
+    pub fn new(model_id: impl Into<String>) -> Self {
+        Self {
+            model_id: model_id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the identifier of the model of the vectors.
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    /// Returns the dimension of the vectors.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Returns the number of documents in the index.
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    /// Checks if the index has no documents.
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    /// Inserts the vector of a document, replacing its previous vector.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vector has a different dimension from the
+    /// vectors already in the index.
+    pub fn insert(&mut self, doc_id: DocumentId, mut vector: Vec<f32>) -> Result<()> {
+        if self.vectors.is_empty() {
+            self.dimension = vector.len();
+        } else if vector.len() != self.dimension {
+            return Err(Error::DimensionMismatch {
+                doc_id,
+                expected: self.dimension,
+                found: vector.len(),
+            });
+        }
+        normalize(&mut vector);
+        self.vectors.insert(doc_id, vector);
+        Ok(())
+    }
+
+    /// Removes the vector of a document. If the document is not in the
+    /// index, nothing is done.
+    pub fn remove(&mut self, doc_id: DocumentId) {
+        self.vectors.remove(&doc_id);
+    }
+
+    /// Finds the `top_n` documents whose vectors are the most similar to a
+    /// query vector, by cosine similarity.
+    ///
+    /// # Returns
+    ///
+    /// The document IDs and their similarities, sorted by descending
+    /// similarity.
+    pub fn search(&self, query: &[f32], top_n: usize) -> Vec<(DocumentId, f32)> {
+        let mut query = query.to_vec();
+        normalize(&mut query);
+        let mut scores = self
+            .vectors
+            .iter()
+            .filter(|(_, vector)| vector.len() == query.len())
+            .map(|(doc_id, vector)| (*doc_id, dot(&query, vector)))
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores.truncate(top_n);
+        scores
+    }
+
+    /// Returns the document IDs and their vectors, sorted by document ID,
+    /// which is the order they are written to file in.
+    pub(crate) fn sorted(&self) -> Vec<(DocumentId, &[f32])> {
+        let mut vectors = self
+            .vectors
+            .iter()
+            .map(|(doc_id, vector)| (*doc_id, vector.as_slice()))
+            .collect::<Vec<_>>();
+        vectors.sort_by_key(|(doc_id, _)| *doc_id);
+        vectors
+    }
+
+    /// Calculates the cosine similarities of the vectors of documents to a
+    /// query vector. Documents without a vector are skipped.
+    pub fn similarities(&self, query: &[f32], doc_ids: &[DocumentId]) -> Vec<(DocumentId, f32)> {
+        let mut query = query.to_vec();
+        normalize(&mut query);
+        doc_ids
+            .iter()
+            .filter_map(|doc_id| {
+                let vector = self.vectors.get(doc_id)?;
+                (vector.len() == query.len()).then(|| (*doc_id, dot(&query, vector)))
+            })
+            .collect()
+    }
+
+    /// Writes the vector index to file, in a binary format. The header is
+    /// followed by the model identifier, the dimension, the number of
+    /// vectors, and each document ID with its vector. The records of the
+    /// documents are sorted by document ID, and have a fixed size, so a
+    /// single record can be read without reading the whole file.
+    pub fn into_file(self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        self.write_to(&mut BufWriter::new(file))
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Loads the vector index from file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(Error::at(path))?;
+        Self::read_from(&mut BufReader::new(file)).map_err(Error::at(path))
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_header(writer, SIGNATURE, VERSION)?;
+        write_str(writer, &self.model_id)?;
+        write_u32(writer, self.dimension as u32)?;
+        write_u32(writer, self.vectors.len() as u32)?;
+        for (doc_id, vector) in self.sorted() {
+            write_u32(writer, doc_id)?;
+            for value in vector {
+                write_f32(writer, *value)?;
+            }
+        }
+        writer.flush()
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let (model_id, dimension, n_vectors) = read_preamble(reader)?;
+        let mut vectors = HashMap::with_capacity(n_vectors);
+        for _ in 0..n_vectors {
+            let doc_id = read_u32(reader)?;
+            let vector = (0..dimension)
+                .map(|_| read_f32(reader))
+                .collect::<io::Result<Vec<_>>>()?;
+            vectors.insert(doc_id, vector);
+        }
+        Ok(Self {
+            model_id,
+            dimension,
+            vectors,
+        })
+    }
+}
+
+/// Reads the header of a vector index file, and the fields before the
+/// records: the model identifier, the dimension and the number of vectors.
+fn read_preamble(reader: &mut impl Read) -> io::Result<(String, usize, usize)> {
+    read_header(reader, SIGNATURE, VERSION)?;
+    let model_id = read_str(reader)?;
+    let dimension = read_u32(reader)? as usize;
+    let n_vectors = read_u32(reader)? as usize;
+    Ok((model_id, dimension, n_vectors))
+}
+
+/// A reader of single records of a vector index file, which reads the exact
+/// vectors of a few documents without loading the whole index.
+pub struct VectorReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+    records_start: u64,
+    dimension: usize,
+    n_vectors: usize,
+}
+
+impl VectorReader {
+    /// Opens a vector index file for reading records.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(Error::at(path))?;
+        let mut reader = BufReader::new(file);
+        let (_, dimension, n_vectors) = read_preamble(&mut reader).map_err(Error::at(path))?;
+        let records_start = reader.stream_position().map_err(Error::at(path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader,
+            records_start,
+            dimension,
+            n_vectors,
+        })
+    }
+
+    /// Reads the record at a position in the file, which is the position of
+    /// the document in the order of document IDs.
+    ///
+    /// # Returns
+    ///
+    /// The document ID and the vector of the record, or `None` if there is
+    /// no record at the position.
+    pub fn read(&mut self, position: usize) -> Result<Option<(DocumentId, Vec<f32>)>> {
+        if position >= self.n_vectors {
+            return Ok(None);
+        }
+        self.read_record(position).map_err(Error::at(&self.path))
+    }
+
+    /// Reads the record at a position in the file, which is known to exist.
+    fn read_record(&mut self, position: usize) -> io::Result<Option<(DocumentId, Vec<f32>)>> {
+        let record_len = 4 + 4 * self.dimension as u64;
+        let offset = self.records_start + position as u64 * record_len;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let doc_id = read_u32(&mut self.reader)?;
+        let vector = (0..self.dimension)
+            .map(|_| read_f32(&mut self.reader))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Some((doc_id, vector)))
+    }
+}
+
+/// Scales a vector to unit length. The zero vector is left as it is.
+pub(crate) fn normalize(vector: &mut [f32]) {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+}
+
+/// Calculates the dot product of two vectors.
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_search() {
+        let mut index = VectorIndex::new("test");
+        index.insert(0, vec![1.0, 0.0]).unwrap();
+        index.insert(1, vec![1.0, 1.0]).unwrap();
+        index.insert(2, vec![0.0, 3.0]).unwrap();
+        assert!(index.insert(3, vec![1.0]).is_err());
+
+        let results = index.search(&[0.0, 2.0], 2);
+        assert_eq!(results[0].0, 2);
+        assert!((results[0].1 - 1.0).abs() < 1e-6);
+        assert_eq!(results[1].0, 1);
+
+        let similarities = index.similarities(&[1.0, 0.0], &[2, 5]);
+        assert_eq!(similarities, vec![(2, 0.0)]);
+    }
+
+    #[test]
+    fn test_binary_format() {
+        let mut index = VectorIndex::new("http:model");
+        index.insert(4, vec![3.0, 4.0]).unwrap();
+        let mut buffer = vec![];
+        index.write_to(&mut buffer).unwrap();
+        let loaded = VectorIndex::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(loaded.model_id(), "http:model");
+        assert_eq!(loaded.vectors[&4], vec![0.6, 0.8]);
+    }
+}
@@ -0,0 +1,481 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use documents::DocumentId;
+
+use crate::codec::{
+    read_f32, read_header, read_str, read_u32, write_f32, write_header, write_str, write_u32,
+};
+use crate::error::{Error, Result};
+use crate::vector::{dot, normalize, VectorIndex, VectorReader};
+
+const SIGNATURE: &[u8] = b"SEARQVEC";
+const VERSION: u8 = 1;
+
+/// The number of dimensions that are encoded together in a byte by product
+/// quantization.
+const SUBVECTOR_DIM: usize = 4;
+/// The maximum number of centroids of each subspace, so that a centroid is
+/// identified by a byte.
+const MAX_CENTROIDS: usize = 256;
+/// The number of iterations of k-means when the centroids are trained.
+const KMEANS_ITERATIONS: usize = 8;
+/// The maximum number of vectors that the centroids are trained on. Larger
+/// indexes are sampled, because training time grows with the number of
+/// vectors, and the centroids barely improve.
+const MAX_TRAINING_VECTORS: usize = 4096;
+
+/// The method that compresses the vectors of a quantized index.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    /// Each dimension is mapped to a byte, between the minimum and maximum
+    /// values of the dimension. Vectors are 4 times smaller.
+    Scalar,
+    /// Groups of 4 dimensions are mapped to the nearest of 256 centroids,
+    /// which are learned from the vectors. Vectors are 16 times smaller.
+    Product,
+}
+
+/// The parameters that decode the bytes of quantized vectors.
+#[derive(Debug, PartialEq)]
+enum Quantizer {
+    /// The minimum value of each dimension, and the step of each byte value.
+    Scalar { min: Vec<f32>, step: Vec<f32> },
+    /// The centroids of each subspace.
+    Product { centroids: Vec<Vec<Vec<f32>>> },
+}
+
+/// A compressed copy of a vector index, which is searched in place of the
+/// full index.
+///
+/// Similarities between quantized vectors are approximate, so a search finds
+/// more candidates than it needs, and scores them again with their exact
+/// vectors, which are read from the vector index file.
+#[derive(Debug)]
+pub struct QuantizedIndex {
+    model_id: String,
+    dimension: usize,
+    quantizer: Quantizer,
+    doc_ids: Vec<DocumentId>,
+    codes: Vec<u8>,
+}
+
+impl QuantizedIndex {
+    /// Quantizes the vectors of a vector index. The documents keep the order
+    /// of the records in the vector index file.
+    pub fn build(vectors: &VectorIndex, quantization: Quantization) -> Self {
+        let sorted = vectors.sorted();
+        let dimension = vectors.dimension();
+        let quantizer = match quantization {
+            Quantization::Scalar => train_scalar(&sorted, dimension),
+            Quantization::Product => train_product(&sorted, dimension),
+        };
+        let mut doc_ids = Vec::with_capacity(sorted.len());
+        let mut codes = Vec::with_capacity(sorted.len() * quantizer.code_len(dimension));
+        for (doc_id, vector) in sorted {
+            doc_ids.push(doc_id);
+            codes.extend(quantizer.encode(vector));
+        }
+        Self {
+            model_id: vectors.model_id().to_string(),
+            dimension,
+            quantizer,
+            doc_ids,
+            codes,
+        }
+    }
+
+    /// Returns the identifier of the model of the vectors.
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    /// Returns the method that the vectors are quantized with.
+    pub fn quantization(&self) -> Quantization {
+        match self.quantizer {
+            Quantizer::Scalar { .. } => Quantization::Scalar,
+            Quantizer::Product { .. } => Quantization::Product,
+        }
+    }
+
+    /// Finds the `top_n` documents whose vectors are the most similar to a
+    /// query vector.
+    ///
+    /// The `n_candidates` most similar documents by their quantized vectors
+    /// are scored again with their exact vectors, which are read from the
+    /// vector index file that the index was built from.
+    ///
+    /// # Returns
+    ///
+    /// The document IDs and their exact similarities, sorted by descending
+    /// similarity.
+    pub fn search(
+        &self,
+        query: &[f32],
+        top_n: usize,
+        n_candidates: usize,
+        exact: &mut VectorReader,
+    ) -> Result<Vec<(DocumentId, f32)>> {
+        if query.len() != self.dimension {
+            return Ok(vec![]);
+        }
+        let mut query = query.to_vec();
+        normalize(&mut query);
+        let scorer = self.quantizer.scorer(&query);
+        let code_len = self.quantizer.code_len(self.dimension);
+        let mut candidates = (0..self.doc_ids.len())
+            .map(|position| {
+                let code = &self.codes[position * code_len..(position + 1) * code_len];
+                (position, scorer.score(code))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.truncate(n_candidates.max(top_n));
+
+        let mut scores = vec![];
+        for (position, _) in candidates {
+            // The vector index file may have been written after the
+            // quantized index, so records of other documents are skipped.
+            match exact.read(position)? {
+                Some((doc_id, vector)) if doc_id == self.doc_ids[position] => {
+                    scores.push((doc_id, dot(&query, &vector)));
+                }
+                _ => continue,
+            }
+        }
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores.truncate(top_n);
+        Ok(scores)
+    }
+
+    /// Writes the quantized index to file, in a binary format. The header is
+    /// followed by the model identifier, the dimension, the quantizer, the
+    /// number of vectors, their document IDs and their codes.
+    pub fn into_file(self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path).map_err(Error::at(&tmp_path))?;
+        self.write_to(&mut BufWriter::new(file))
+            .map_err(Error::at(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(Error::at(path))
+    }
+
+    /// Loads the quantized index from file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(Error::at(path))?;
+        Self::read_from(&mut BufReader::new(file)).map_err(Error::at(path))
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_header(writer, SIGNATURE, VERSION)?;
+        write_str(writer, &self.model_id)?;
+        write_u32(writer, self.dimension as u32)?;
+        match &self.quantizer {
+            Quantizer::Scalar { min, step } => {
+                writer.write_all(&[0])?;
+                for (min, step) in min.iter().zip(step) {
+                    write_f32(writer, *min)?;
+                    write_f32(writer, *step)?;
+                }
+            }
+            Quantizer::Product { centroids } => {
+                writer.write_all(&[1])?;
+                for subspace in centroids {
+                    write_u32(writer, subspace.len() as u32)?;
+                    for value in subspace.iter().flatten() {
+                        write_f32(writer, *value)?;
+                    }
+                }
+            }
+        }
+        write_u32(writer, self.doc_ids.len() as u32)?;
+        for doc_id in &self.doc_ids {
+            write_u32(writer, *doc_id)?;
+        }
+        writer.write_all(&self.codes)?;
+        writer.flush()
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        read_header(reader, SIGNATURE, VERSION)?;
+        let model_id = read_str(reader)?;
+        let dimension = read_u32(reader)? as usize;
+        let mut kind = [0; 1];
+        reader.read_exact(&mut kind)?;
+        let quantizer = match kind[0] {
+            0 => {
+                let mut min = Vec::with_capacity(dimension);
+                let mut step = Vec::with_capacity(dimension);
+                for _ in 0..dimension {
+                    min.push(read_f32(reader)?);
+                    step.push(read_f32(reader)?);
+                }
+                Quantizer::Scalar { min, step }
+            }
+            1 => {
+                let mut centroids = vec![];
+                for range in subspaces(dimension) {
+                    let n_centroids = read_u32(reader)? as usize;
+                    let subspace = (0..n_centroids)
+                        .map(|_| range.clone().map(|_| read_f32(reader)).collect())
+                        .collect::<io::Result<Vec<_>>>()?;
+                    centroids.push(subspace);
+                }
+                Quantizer::Product { centroids }
+            }
+            kind => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown quantizer: {kind}."),
+                ))
+            }
+        };
+        let n_vectors = read_u32(reader)? as usize;
+        let doc_ids = (0..n_vectors)
+            .map(|_| read_u32(reader))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut codes = vec![0; n_vectors * quantizer.code_len(dimension)];
+        reader.read_exact(&mut codes)?;
+        Ok(Self {
+            model_id,
+            dimension,
+            quantizer,
+            doc_ids,
+            codes,
+        })
+    }
+}
+
+impl Quantizer {
+    /// Returns the number of bytes of a quantized vector.
+    fn code_len(&self, dimension: usize) -> usize {
+        match self {
+            Quantizer::Scalar { .. } => dimension,
+            Quantizer::Product { centroids } => centroids.len(),
+        }
+    }
+
+    /// Quantizes a vector.
+    fn encode(&self, vector: &[f32]) -> Vec<u8> {
+        match self {
+            Quantizer::Scalar { min, step } => vector
+                .iter()
+                .zip(min.iter().zip(step))
+                .map(|(value, (min, step))| match *step > 0.0 {
+                    true => ((value - min) / step).round().clamp(0.0, 255.0) as u8,
+                    false => 0,
+                })
+                .collect(),
+            Quantizer::Product { centroids } => subspaces(vector.len())
+                .zip(centroids)
+                .map(|(range, subspace)| nearest(&vector[range], subspace) as u8)
+                .collect(),
+        }
+    }
+
+    /// Prepares the scoring of quantized vectors against a query vector.
+    fn scorer(&self, query: &[f32]) -> Scorer {
+        match self {
+            Quantizer::Scalar { min, step } => Scorer::Scalar {
+                offset: dot(query, min),
+                weights: query.iter().zip(step).map(|(q, step)| q * step).collect(),
+            },
+            Quantizer::Product { centroids } => Scorer::Product {
+                tables: subspaces(query.len())
+                    .zip(centroids)
+                    .map(|(range, subspace)| {
+                        subspace
+                            .iter()
+                            .map(|centroid| dot(&query[range.clone()], centroid))
+                            .collect()
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// The precomputed terms of the similarity of quantized vectors to a query,
+/// so that a quantized vector is scored without decoding it.
+enum Scorer {
+    /// The similarity is the offset plus the weighted sum of the bytes.
+    Scalar { offset: f32, weights: Vec<f32> },
+    /// The similarity is the sum of the similarities of the centroids of each
+    /// subspace to the query, which are looked up in tables.
+    Product { tables: Vec<Vec<f32>> },
+}
+
+impl Scorer {
+    /// Calculates the approximate similarity of a quantized vector.
+    fn score(&self, code: &[u8]) -> f32 {
+        match self {
+            Scorer::Scalar { offset, weights } => {
+                offset
+                    + weights
+                        .iter()
+                        .zip(code)
+                        .map(|(weight, byte)| weight * *byte as f32)
+                        .sum::<f32>()
+            }
+            Scorer::Product { tables } => tables
+                .iter()
+                .zip(code)
+                .map(|(table, byte)| table[*byte as usize])
+                .sum(),
+        }
+    }
+}
+
+/// Finds the range of each dimension, and maps it to the 256 byte values.
+fn train_scalar(vectors: &[(DocumentId, &[f32])], dimension: usize) -> Quantizer {
+    let mut min = vec![f32::MAX; dimension];
+    let mut max = vec![f32::MIN; dimension];
+    for (_, vector) in vectors {
+        for (i, value) in vector.iter().enumerate() {
+            min[i] = min[i].min(*value);
+            max[i] = max[i].max(*value);
+        }
+    }
+    if vectors.is_empty() {
+        min = vec![0.0; dimension];
+        max = vec![0.0; dimension];
+    }
+    let step = min
+        .iter()
+        .zip(&max)
+        .map(|(min, max)| (max - min) / 255.0)
+        .collect();
+    Quantizer::Scalar { min, step }
+}
+
+/// Learns the centroids of each subspace with k-means, from a sample of the
+/// vectors.
+fn train_product(vectors: &[(DocumentId, &[f32])], dimension: usize) -> Quantizer {
+    let n_samples = vectors.len().min(MAX_TRAINING_VECTORS);
+    let samples = (0..n_samples)
+        .map(|i| vectors[i * vectors.len() / n_samples].1)
+        .collect::<Vec<_>>();
+    let centroids = subspaces(dimension)
+        .map(|range| {
+            let subvectors = samples
+                .iter()
+                .map(|vector| &vector[range.clone()])
+                .collect::<Vec<_>>();
+            kmeans(&subvectors, MAX_CENTROIDS.min(subvectors.len()))
+        })
+        .collect();
+    Quantizer::Product { centroids }
+}
+
+/// Clusters vectors into `k` clusters, and returns their centroids. The
+/// centroids start from vectors spread evenly through the input, so the
+/// result does not depend on chance.
+fn kmeans(vectors: &[&[f32]], k: usize) -> Vec<Vec<f32>> {
+    if k == 0 {
+        return vec![];
+    }
+    let mut centroids = (0..k)
+        .map(|i| vectors[i * vectors.len() / k].to_vec())
+        .collect::<Vec<_>>();
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut sums = vec![vec![0.0; centroids[0].len()]; k];
+        let mut counts = vec![0; k];
+        for vector in vectors {
+            let cluster = nearest(vector, &centroids);
+            for (sum, value) in sums[cluster].iter_mut().zip(vector.iter()) {
+                *sum += value;
+            }
+            counts[cluster] += 1;
+        }
+        // Clusters that lost all their vectors keep their centroid.
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            if count > 0 {
+                *centroid = sum.into_iter().map(|value| value / count as f32).collect();
+            }
+        }
+    }
+    centroids
+}
+
+/// Returns the index of the centroid that is nearest to a vector, by
+/// euclidean distance.
+fn nearest(vector: &[f32], centroids: &[Vec<f32>]) -> usize {
+    let distance = |centroid: &Vec<f32>| -> f32 {
+        vector
+            .iter()
+            .zip(centroid)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum()
+    };
+    centroids
+        .iter()
+        .enumerate()
+        .min_by(|a, b| distance(a.1).total_cmp(&distance(b.1)))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Returns the ranges of dimensions of the subspaces of product
+/// quantization. The last subspace is shorter if the dimension is not a
+/// multiple of the subvector dimension.
+fn subspaces(dimension: usize) -> impl Iterator<Item = Range<usize>> {
+    (0..dimension)
+        .step_by(SUBVECTOR_DIM)
+        .map(move |start| start..(start + SUBVECTOR_DIM).min(dimension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vectors() -> VectorIndex {
+        let mut vectors = VectorIndex::new("test");
+        for i in 0..20 {
+            let x = i as f32;
+            vectors
+                .insert(i, vec![x, 1.0, 20.0 - x, 0.5, x * 0.1, 2.0])
+                .unwrap();
+        }
+        vectors
+    }
+
+    #[test]
+    fn test_scalar_quantization() {
+        let vectors = vectors();
+        let quantized = QuantizedIndex::build(&vectors, Quantization::Scalar);
+        assert_eq!(quantized.codes.len(), 20 * 6);
+
+        let sorted = vectors.sorted();
+        let query = sorted[3].1;
+        let scorer = quantized.quantizer.scorer(query);
+        let approximate = scorer.score(&quantized.codes[3 * 6..4 * 6]);
+        assert!((approximate - dot(query, query)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_product_quantization() {
+        let vectors = vectors();
+        let quantized = QuantizedIndex::build(&vectors, Quantization::Product);
+        // Dimensions 0..4 and 4..6 are encoded in a byte each.
+        assert_eq!(quantized.codes.len(), 20 * 2);
+        assert_eq!(quantized.quantization(), Quantization::Product);
+    }
+
+    #[test]
+    fn test_quantized_binary_format() {
+        for quantization in [Quantization::Scalar, Quantization::Product] {
+            let quantized = QuantizedIndex::build(&vectors(), quantization);
+            let mut buffer = vec![];
+            quantized.write_to(&mut buffer).unwrap();
+            let loaded = QuantizedIndex::read_from(&mut buffer.as_slice()).unwrap();
+            assert_eq!(loaded.quantizer, quantized.quantizer);
+            assert_eq!(loaded.doc_ids, quantized.doc_ids);
+            assert_eq!(loaded.codes, quantized.codes);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+/// The operator that separates the lexical terms of a query from its
+/// semantic clause.
+const SEMANTIC_OPERATOR: &str = "~~";
+
+/// A query that combines lexical terms with a natural language description,
+/// written as `lexical terms ~~ "natural language description"`.
+///
+/// The weight of the semantic similarity can be written right after the
+/// operator, as in `budget ~~0.8 "how to spend less"`, where 0 ranks by the
+/// lexical terms only, and 1 by the description only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HybridQuery {
+    /// The lexical terms, which documents have to match.
+    pub lexical: String,
+    /// The semantic clause, if the query has one.
+    pub semantic: Option<SemanticClause>,
+    /// Whether the lexical terms only match the identifiers of source code
+    /// in their original case, instead of any terms in any case.
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// The semantic clause of a hybrid query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SemanticClause {
+    /// The natural language description, without its quotes.
+    pub text: String,
+    /// The weight of the semantic similarity, between 0 and 1, if it was
+    /// written in the query.
+    pub weight: Option<f64>,
+}
+
+impl HybridQuery {
+    /// Parses a query. A query without the semantic operator only has
+    /// lexical terms. The quotes around the description are optional, and a
+    /// missing closing quote ends the description at the end of the query.
+    pub fn parse(query: &str) -> Self {
+        let Some((lexical, clause)) = query.split_once(SEMANTIC_OPERATOR) else {
+            return Self {
+                lexical: query.trim().to_string(),
+                semantic: None,
+                case_sensitive: false,
+            };
+        };
+
+        // The weight is a number that follows the operator.
+        let end = clause
+            .find(|c: char| c.is_whitespace() || c == '"')
+            .unwrap_or(clause.len());
+        let (weight, text) = match clause[..end].parse::<f64>() {
+            Ok(weight) if !clause[..end].is_empty() => {
+                (Some(weight.clamp(0.0, 1.0)), &clause[end..])
+            }
+            _ => (None, clause),
+        };
+
+        let text = text.trim();
+        let text = match text.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+            None => text,
+        };
+        Self {
+            lexical: lexical.trim().to_string(),
+            semantic: Some(SemanticClause {
+                text: text.trim().to_string(),
+                weight,
+            }),
+            case_sensitive: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hybrid_query() {
+        let query = HybridQuery::parse("budget 2024 ~~ \"how to spend less\"");
+        assert_eq!(query.lexical, "budget 2024");
+        let clause = query.semantic.unwrap();
+        assert_eq!(clause.text, "how to spend less");
+        assert_eq!(clause.weight, None);
+
+        let query = HybridQuery::parse("budget ~~0.8 \"how to spend less");
+        let clause = query.semantic.unwrap();
+        assert_eq!(clause.text, "how to spend less");
+        assert_eq!(clause.weight, Some(0.8));
+
+        let query = HybridQuery::parse("~~ cutting costs");
+        assert_eq!(query.lexical, "");
+        assert_eq!(query.semantic.unwrap().text, "cutting costs");
+
+        let query = HybridQuery::parse(" quarterly budget ");
+        assert_eq!(query.lexical, "quarterly budget");
+        assert_eq!(query.semantic, None);
+    }
+}
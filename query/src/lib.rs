@@ -1,12 +1,26 @@
 use std::collections::HashMap;
 
-use tokenize::Token;
+use tokenize::{Token, CASED_PREFIX};
 
-/// Structure that stores the token and its count. /// /// For example, after we have tokenized a query, we can
-/// create a token counter for more efficient relevance scoring.
+pub use hybrid::{HybridQuery, SemanticClause};
+pub use phrase::Phrase;
+pub use rewrite::{CommandRewriter, QueryRewriter, RewriteConfig};
+
+mod hybrid;
+mod phrase;
+mod rewrite;
+
+/// Structure that stores the token and its count.
+///
+/// For example, after we have tokenized a query, we can create a token
+/// counter for more efficient relevance scoring.
 #[derive(Debug)]
 pub struct Query {
     inner: HashMap<Token, u32>,
+    /// The weights of the tokens that weigh less than the ones of the query
+    /// itself, like the terms that it is expanded with. The other tokens
+    /// weigh 1.
+    weights: HashMap<Token, f64>,
 }
 
 impl Query {
@@ -14,6 +28,36 @@ impl Query {
     pub fn term_count(&self, token: &Token) -> u32 {
         *self.inner.get(token).unwrap_or(&0)
     }
+
+    /// Returns the weight of a specific token in the scores of documents,
+    /// which is its count times its weight.
+    pub fn term_weight(&self, token: &Token) -> f64 {
+        let weight = self.weights.get(token).copied().unwrap_or(1.0);
+        self.term_count(token) as f64 * weight
+    }
+
+    /// Adds tokens with their weights, such as the terms of the documents
+    /// that a query is expanded with. Tokens that are already in the query
+    /// are skipped, so they keep their weights.
+    pub fn extend_weighted(&mut self, tokens: impl IntoIterator<Item = (Token, f64)>) {
+        for (token, weight) in tokens {
+            if !self.inner.contains_key(&token) {
+                self.inner.insert(token.clone(), 1);
+                self.weights.insert(token, weight);
+            }
+        }
+    }
+
+    /// Returns an iterator over the tokens and their counts.
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, Token, u32> {
+        self.inner.iter()
+    }
+
+    /// Checks if the query matches the case-sensitive field of the index,
+    /// that is, if its tokens are terms in their original case.
+    pub fn is_case_sensitive(&self) -> bool {
+        self.inner.keys().any(|token| token.starts_with(CASED_PREFIX))
+    }
 }
 
 impl<I> From<I> for Query
@@ -28,6 +72,16 @@ where
         }
         Query {
             inner: tokens_counter,
+            weights: HashMap::new(),
+        }
+    }
+}
+
+impl Extend<Token> for Query {
+    /// Adds tokens to the counter, such as the terms that a query expands to.
+    fn extend<I: IntoIterator<Item = Token>>(&mut self, tokens: I) {
+        for token in tokens {
+            *self.inner.entry(token).or_insert(0) += 1;
         }
     }
 }
@@ -58,5 +112,15 @@ mod tests {
         assert_eq!(token_counts.term_count(&"it".to_string()), 2);
         assert_eq!(token_counts.term_count(&"got".to_string()), 1);
         assert_eq!(token_counts.term_count(&"ai".to_string()), 0);
+
+        let mut token_counts = token_counts;
+        token_counts.extend(["ai".to_string(), "it".to_string()]);
+        assert_eq!(token_counts.term_count(&"ai".to_string()), 1);
+        assert_eq!(token_counts.term_count(&"it".to_string()), 3);
+
+        token_counts.extend_weighted([("it".to_string(), 0.5), ("bot".to_string(), 0.5)]);
+        assert_eq!(token_counts.term_weight(&"it".to_string()), 3.0);
+        assert_eq!(token_counts.term_weight(&"bot".to_string()), 0.5);
+        assert_eq!(token_counts.term_weight(&"cat".to_string()), 0.0);
     }
 }
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+/// The operator that follows the closing quote of a phrase to give its slop.
+const SLOP_OPERATOR: char = '~';
+
+/// A phrase of a query, written in double quotes, as in `"budget meeting"`,
+/// whose words have to follow each other in the text of the documents.
+///
+/// The slop of a phrase can be written right after its closing quote, as in
+/// `"budget meeting"~3`, to let its words be in any order, with at most that
+/// many other terms in between.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Phrase {
+    /// The words of the phrase, without their quotes.
+    pub text: String,
+    /// The number of other terms that can be in between the words of the
+    /// phrase. It is 0 if it was not written in the query.
+    pub slop: u32,
+}
+
+impl Phrase {
+    /// Splits the phrases out of the lexical terms of a query.
+    ///
+    /// The words of the phrases are kept in the terms, without their quotes
+    /// and their slops, so that they are scored like the other terms. A
+    /// missing closing quote ends the last phrase at the end of the terms.
+    ///
+    /// # Returns
+    ///
+    /// The terms without the quotes, and the phrases, in the order they were
+    /// written.
+    pub fn parse_all(lexical: &str) -> (String, Vec<Phrase>) {
+        let mut terms = String::new();
+        let mut phrases = vec![];
+        let mut rest = lexical;
+        while let Some((before, quoted)) = rest.split_once('"') {
+            terms.push_str(before);
+            let (text, after) = quoted.split_once('"').unwrap_or((quoted, ""));
+            // The slop is a number that follows the operator.
+            let (slop, after) = match after.strip_prefix(SLOP_OPERATOR) {
+                Some(after) => {
+                    let end = after
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(after.len());
+                    (after[..end].parse().unwrap_or_default(), &after[end..])
+                }
+                None => (0, after),
+            };
+            terms.push(' ');
+            terms.push_str(text);
+            terms.push(' ');
+            if !text.trim().is_empty() {
+                phrases.push(Phrase {
+                    text: text.trim().to_string(),
+                    slop,
+                });
+            }
+            rest = after;
+        }
+        terms.push_str(rest);
+        let terms = terms.split_whitespace().collect::<Vec<_>>().join(" ");
+        (terms, phrases)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_phrases() {
+        let (terms, phrases) = Phrase::parse_all("\"budget meeting\"~3 travel \"next quarter\"");
+        assert_eq!(terms, "budget meeting travel next quarter");
+        assert_eq!(
+            phrases,
+            [
+                Phrase {
+                    text: "budget meeting".to_string(),
+                    slop: 3,
+                },
+                Phrase {
+                    text: "next quarter".to_string(),
+                    slop: 0,
+                },
+            ]
+        );
+
+        let (terms, phrases) = Phrase::parse_all("travel \"budget meeting");
+        assert_eq!(terms, "travel budget meeting");
+        assert_eq!(phrases[0].text, "budget meeting");
+
+        let (terms, phrases) = Phrase::parse_all("quarterly budget \"\"");
+        assert_eq!(terms, "quarterly budget");
+        assert!(phrases.is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::HybridQuery;
+
+/// A hook that rewrites a query after it is parsed, and before it is
+/// executed, such as to expand terms with synonyms, to correct their
+/// spelling, or to strip words that are noise in a domain.
+pub trait QueryRewriter {
+    /// Rewrites a parsed query.
+    fn rewrite(&self, query: HybridQuery) -> anyhow::Result<HybridQuery>;
+}
+
+/// The configuration of the query rewriter, read from the `[rewrite]`
+/// section of the config of a repository.
+///
+/// ```toml
+/// [rewrite]
+/// command = ["python3", "/path/to/rewrite.py"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RewriteConfig {
+    /// The program of an external rewriter, followed by its arguments. If it
+    /// is empty, queries are not rewritten.
+    pub command: Vec<String>,
+}
+
+impl RewriteConfig {
+    /// Creates the query rewriter that is configured, if any.
+    pub fn build(&self) -> Option<Box<dyn QueryRewriter>> {
+        let (program, args) = self.command.split_first()?;
+        Some(Box::new(CommandRewriter::new(program, args)))
+    }
+}
+
+/// A query rewriter that runs an external command for each query.
+///
+/// The parsed query is written to the standard input of the command as a
+/// JSON object, like `{"lexical": "budget", "semantic": null}`, and the
+/// command writes the rewritten query to its standard output, in the same
+/// form.
+pub struct CommandRewriter {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandRewriter {
+    /// Creates a rewriter that runs a program with arguments.
+    pub fn new(program: &str, args: &[String]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.to_vec(),
+        }
+    }
+}
+
+impl QueryRewriter for CommandRewriter {
+    fn rewrite(&self, query: HybridQuery) -> anyhow::Result<HybridQuery> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context(format!("Failed to run query rewriter: {}", self.program))?;
+
+        let input = serde_json::to_vec(&query)?;
+        child
+            .stdin
+            .take()
+            .context("Failed to open stdin of query rewriter.")?
+            .write_all(&input)
+            .context("Failed to write query to query rewriter.")?;
+
+        let output = child
+            .wait_with_output()
+            .context("Failed to wait for query rewriter.")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Query rewriter {} failed with {}.",
+                self.program,
+                output.status
+            );
+        }
+        serde_json::from_slice(&output.stdout)
+            .context("Query rewriter did not write a valid query.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_rewriter() {
+        let config = RewriteConfig {
+            command: vec!["cat".to_string()],
+        };
+        let rewriter = config.build().unwrap();
+        let query = HybridQuery::parse("budget ~~0.2 \"spend less\"");
+        assert_eq!(rewriter.rewrite(query.clone()).unwrap(), query);
+
+        assert!(RewriteConfig::default().build().is_none());
+    }
+}
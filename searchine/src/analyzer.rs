@@ -0,0 +1,464 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use documents::{Document, DocumentSource, DocumentType};
+use query::Query;
+use tokenize::{Algorithm, Compounds, Segmentation, Token, Tokenizer};
+
+use crate::config::{ANALYZER_FILENAME, BODY_FIELD, TITLE_FIELD};
+
+/// The language of the stemmer of the analyzer, or no stemmer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stemmer {
+    None,
+    Arabic,
+    Danish,
+    Dutch,
+    #[default]
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Tamil,
+    Turkish,
+}
+
+impl Stemmer {
+    /// Returns the stemming algorithm of the language, if there is one.
+    fn algorithm(self) -> Option<Algorithm> {
+        match self {
+            Stemmer::None => None,
+            Stemmer::Arabic => Some(Algorithm::Arabic),
+            Stemmer::Danish => Some(Algorithm::Danish),
+            Stemmer::Dutch => Some(Algorithm::Dutch),
+            Stemmer::English => Some(Algorithm::English),
+            Stemmer::Finnish => Some(Algorithm::Finnish),
+            Stemmer::French => Some(Algorithm::French),
+            Stemmer::German => Some(Algorithm::German),
+            Stemmer::Greek => Some(Algorithm::Greek),
+            Stemmer::Hungarian => Some(Algorithm::Hungarian),
+            Stemmer::Italian => Some(Algorithm::Italian),
+            Stemmer::Norwegian => Some(Algorithm::Norwegian),
+            Stemmer::Portuguese => Some(Algorithm::Portuguese),
+            Stemmer::Romanian => Some(Algorithm::Romanian),
+            Stemmer::Russian => Some(Algorithm::Russian),
+            Stemmer::Spanish => Some(Algorithm::Spanish),
+            Stemmer::Swedish => Some(Algorithm::Swedish),
+            Stemmer::Tamil => Some(Algorithm::Tamil),
+            Stemmer::Turkish => Some(Algorithm::Turkish),
+        }
+    }
+}
+
+/// The way the text of documents is split into terms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenizerMode {
+    /// Source code, or the files with the code extensions of the analyzer,
+    /// is tokenized as code, and any other document as text.
+    #[default]
+    Auto,
+    /// Every document is tokenized as text.
+    Text,
+    /// Every document is tokenized as code, with its identifiers split into
+    /// their sub-terms.
+    Code,
+}
+
+/// The configuration of the analysis of the text of documents into terms,
+/// read from the `[analyzer]` section of the config of a repository.
+///
+/// The same analysis is applied when documents are indexed and when queries
+/// are searched, so the analyzer that a repository was indexed with is kept
+/// in the repository. Changing the analyzer requires indexing the repository
+/// from scratch.
+///
+/// ```toml
+/// [analyzer]
+/// stemmer = "english"
+/// stopwords = ["the", "a", "of"]
+/// tokenizer = "auto"
+/// code_extensions = ["rs", "py", "sql"]
+/// segmentation = "unicode"
+/// compounds = "both"
+/// strip_possessives = false
+/// drop_numbers = false
+/// cased_identifiers = true
+/// cased_terms = false
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyzerConfig {
+    /// The language of the stemmer, or `"none"` to keep the terms as they
+    /// are. It is English by default.
+    pub stemmer: Stemmer,
+    /// The terms that are never indexed or searched for. There are none by
+    /// default.
+    pub stopwords: Vec<String>,
+    /// The way the text of documents is split into terms.
+    pub tokenizer: TokenizerMode,
+    /// The extensions of the files, without the dot, that the `"auto"`
+    /// tokenizer tokenizes as source code, and the others as text. If they
+    /// are not set, the files of the types of source code that are supported
+    /// out of the box are.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_extensions: Option<Vec<String>>,
+    /// The way text, other than source code, is segmented into words:
+    /// `"unicode"`, the default, by the word boundaries of Unicode, or
+    /// `"simple"`, at every character that is not alphanumeric, as
+    /// repositories were indexed before the option existed.
+    pub segmentation: Segmentation,
+    /// The way compounds of words that are joined by hyphens or underscores,
+    /// like `state-of-the-art`, are indexed: `"both"`, the default, as a
+    /// whole and as their words, `"whole"`, or `"split"` into their words.
+    /// It only applies to the Unicode segmentation.
+    pub compounds: Compounds,
+    /// Whether the possessive `'s` of words is stripped, so that `parser's`
+    /// matches `parser` even without a stemmer. It is off by default, and it
+    /// only applies to the Unicode segmentation.
+    pub strip_possessives: bool,
+    /// Whether the terms that are numbers, like `42` or `3.14`, are dropped
+    /// like stopwords. It is off by default, as version numbers and error
+    /// codes are often searched for in technical text.
+    pub drop_numbers: bool,
+    /// Whether the identifiers of source code are also indexed in their
+    /// original case, in a separate field, so that they can be searched for
+    /// with `searchine search --case-sensitive`. It is off by default, as it
+    /// makes the index of source code larger.
+    pub cased_identifiers: bool,
+    /// Whether the terms of every document, and not only the identifiers of
+    /// source code, are also indexed in their original case, so that
+    /// `FooBar` and `foobar` can be told apart in any text. It is off by
+    /// default, as it makes the index larger.
+    pub cased_terms: bool,
+}
+
+impl AnalyzerConfig {
+    /// Creates the tokenizer of the analyzer.
+    pub fn tokenizer(&self) -> Tokenizer {
+        let stopwords = self
+            .stopwords
+            .iter()
+            .map(|stopword| stopword.to_lowercase())
+            .collect();
+        Tokenizer::new(self.stemmer.algorithm(), stopwords)
+            .with_segmentation(self.segmentation)
+            .with_compounds(self.compounds)
+            .with_stripped_possessives(self.strip_possessives)
+            .with_dropped_numbers(self.drop_numbers)
+    }
+
+    /// Checks if any terms are indexed in their original case, so that they
+    /// can be searched for with `searchine search --case-sensitive`.
+    pub fn is_cased(&self) -> bool {
+        self.cased_identifiers || self.cased_terms
+    }
+
+    /// Tokenizes the contents of a document into its terms, along with their
+    /// byte ranges, and the terms of its other fields: the title field, of
+    /// the name of its file, and the case-sensitive field. Source code has a
+    /// case-sensitive field if cased identifiers or cased terms are indexed,
+    /// and the other documents only if cased terms are indexed.
+    pub fn tokenize_document(
+        &self,
+        tokenizer: &mut Tokenizer,
+        document: &Document,
+    ) -> (Vec<(Token, Range<usize>)>, Vec<Token>) {
+        let text = document.page_content();
+        let is_code = match document.metadata().source() {
+            Some(DocumentSource::File(path)) => self.is_code(path),
+            None => self.tokenizer == TokenizerMode::Code,
+        };
+        let (spans, mut field_tokens) = match is_code {
+            true if self.is_cased() => (
+                tokenizer.tokenize_code_spans(text),
+                tokenizer.tokenize_code_cased(text),
+            ),
+            true => (tokenizer.tokenize_code_spans(text), vec![]),
+            false if self.cased_terms => {
+                (tokenizer.tokenize_spans(text), tokenizer.tokenize_cased(text))
+            }
+            false => (tokenizer.tokenize_spans(text), vec![]),
+        };
+        if let Some(title) = title(document) {
+            field_tokens.extend(tokenizer.tokenize_title(title));
+        }
+        (spans, field_tokens)
+    }
+
+    /// Tokenizes a document's text into terms and their byte ranges, the same
+    /// way the document was tokenized when it was indexed.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokenizer`      - The tokenizer of the analyzer.
+    /// * `text`           - The text of the document.
+    /// * `doc_path`       - The path to the document, used to determine whether
+    ///   it is tokenized as source code.
+    /// * `case_sensitive` - Whether the text is tokenized into the terms of the
+    ///   case-sensitive field, which the other documents than source code only
+    ///   have if cased terms are indexed.
+    pub fn document_spans(
+        &self,
+        tokenizer: &mut Tokenizer,
+        text: &str,
+        doc_path: impl AsRef<Path>,
+        case_sensitive: bool,
+    ) -> Vec<(Token, Range<usize>)> {
+        let is_code = self.is_code(doc_path);
+        match (is_code, case_sensitive) {
+            (true, false) => tokenizer.tokenize_code_spans(text),
+            (true, true) => tokenizer.tokenize_code_cased_spans(text),
+            (false, false) => tokenizer.tokenize_spans(text),
+            (false, true) if self.cased_terms => tokenizer.tokenize_cased_spans(text),
+            (false, true) => vec![],
+        }
+    }
+
+    /// Tokenizes the lexical terms of a query. The terms of a case-sensitive
+    /// query are tokenized like source code in its original case, so they
+    /// only match the case-sensitive field of the index.
+    ///
+    /// Otherwise, the words of the query match both the text and the title
+    /// of the documents, except for the words that are written as
+    /// `title:word`, which only match the title, and `body:word`, which only
+    /// match the text.
+    pub fn tokenize_query(&self, query: &str, case_sensitive: bool) -> Query {
+        let mut tokenizer = self.tokenizer();
+        if case_sensitive {
+            return Query::from(tokenizer.tokenize_code_cased(query));
+        }
+        let mut title = vec![];
+        let mut body = vec![];
+        for word in query.split_whitespace() {
+            if let Some(word) = word.strip_prefix(TITLE_FIELD) {
+                title.push(word);
+            } else if let Some(word) = word.strip_prefix(BODY_FIELD) {
+                body.push(word);
+            } else {
+                title.push(word);
+                body.push(word);
+            }
+        }
+        let mut tokens = tokenizer.tokenize(body.join(" "));
+        tokens.extend(tokenizer.tokenize_title(title.join(" ")));
+        Query::from(tokens)
+    }
+
+    /// Keeps the analyzer in a repository, as the one that the repository was
+    /// indexed with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the analyzer file cannot be written.
+    pub fn save(&self, repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = repo_dir.as_ref().join(ANALYZER_FILENAME);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?).context(format!(
+            "Failed to write analyzer file: {}",
+            tmp_path.display()
+        ))?;
+        fs::rename(&tmp_path, &path)
+            .context(format!("Failed to write analyzer file: {}", path.display()))
+    }
+
+    /// Checks that the analyzer is the one that a repository was indexed
+    /// with, so that documents and queries are analyzed the same way. A
+    /// repository that was indexed before the analyzer was kept is assumed
+    /// to have been indexed with it, and one whose analyzer file has no
+    /// segmentation was indexed with the simple one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the analyzer has changed since the repository was
+    /// indexed, or if the analyzer file cannot be read or parsed.
+    pub fn check(&self, repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = repo_dir.as_ref().join(ANALYZER_FILENAME);
+        if !path.exists() {
+            return Ok(());
+        }
+        let contents = fs::read_to_string(&path)
+            .context(format!("Failed to read analyzer file: {}", path.display()))?;
+        let indexed = Self::parse_indexed(&contents)
+            .context(format!("Failed to parse analyzer file: {}", path.display()))?;
+        if &indexed != self {
+            anyhow::bail!(
+                "The [analyzer] settings have changed since the repository was indexed. \
+                 Run `searchine index --full` to index it again."
+            );
+        }
+        Ok(())
+    }
+
+    /// Parses the analyzer file of a repository. Analyzer files that were
+    /// written before the segmentation was kept have the simple one.
+    fn parse_indexed(contents: &str) -> serde_json::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_str(contents)?;
+        if let Some(fields) = value.as_object_mut() {
+            fields
+                .entry("segmentation")
+                .or_insert(serde_json::to_value(Segmentation::Simple)?);
+        }
+        serde_json::from_value(value)
+    }
+
+    /// Checks if a file is tokenized as source code. With the `"auto"`
+    /// tokenizer, it is if its extension is one of the code extensions,
+    /// regardless of case, or, if they are not set, if it is of a type of
+    /// source code.
+    pub fn is_code(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        match (self.tokenizer, &self.code_extensions) {
+            (TokenizerMode::Auto, Some(extensions)) => path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    extensions
+                        .iter()
+                        .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+                }),
+            (TokenizerMode::Auto, None) => {
+                DocumentType::from_path(path) == Some(DocumentType::Code)
+            }
+            (TokenizerMode::Text, _) => false,
+            (TokenizerMode::Code, _) => true,
+        }
+    }
+}
+
+/// Returns the title of a document, which is the name of its file without
+/// the extension.
+fn title(document: &Document) -> Option<&str> {
+    match document.metadata().source()? {
+        DocumentSource::File(path) => path.file_stem()?.to_str(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyzer_config() {
+        let config: AnalyzerConfig = toml::from_str(
+            r#"
+            stemmer = "none"
+            stopwords = ["The"]
+            tokenizer = "code"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.stemmer, Stemmer::None);
+        assert_eq!(config.tokenizer, TokenizerMode::Code);
+        assert!(!config.cased_identifiers);
+
+        let query = config.tokenize_query("the running userId", false);
+        assert_eq!(query.term_count(&"the".to_string()), 0);
+        assert_eq!(query.term_count(&"running".to_string()), 1);
+        assert_eq!(query.term_count(&"^running".to_string()), 1);
+
+        let query = config.tokenize_query("title:report body:budget", false);
+        assert_eq!(query.term_count(&"report".to_string()), 0);
+        assert_eq!(query.term_count(&"^report".to_string()), 1);
+        assert_eq!(query.term_count(&"budget".to_string()), 1);
+        assert_eq!(query.term_count(&"^budget".to_string()), 0);
+    }
+
+    #[test]
+    fn test_code_extensions() {
+        let config = AnalyzerConfig::default();
+        assert!(config.is_code("src/main.rs"));
+        assert!(!config.is_code("schema.sql"));
+        assert!(!config.is_code("notes.md"));
+
+        let config: AnalyzerConfig = toml::from_str(
+            r#"
+            code_extensions = ["sql", ".MD"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.is_code("schema.SQL"));
+        assert!(config.is_code("notes.md"));
+        assert!(!config.is_code("src/main.rs"));
+        let mut tokenizer = config.tokenizer();
+        let spans = config.document_spans(&mut tokenizer, "userId", "notes.md", false);
+        assert!(spans.iter().any(|(term, _)| term == "user"));
+        let contents = serde_json::to_string(&AnalyzerConfig::default()).unwrap();
+        assert!(!contents.contains("code_extensions"));
+
+        let config = AnalyzerConfig {
+            tokenizer: TokenizerMode::Text,
+            ..config
+        };
+        assert!(!config.is_code("schema.sql"));
+    }
+
+    #[test]
+    fn test_cased_terms() {
+        let mut config = AnalyzerConfig::default();
+        let mut tokenizer = config.tokenizer();
+        let text = "The FooBar release.";
+        assert!(config.document_spans(&mut tokenizer, text, "notes.md", true).is_empty());
+
+        config.cased_terms = true;
+        assert!(config.is_cased());
+        let spans = config.document_spans(&mut tokenizer, text, "notes.md", true);
+        assert_eq!(spans[1], ("=FooBar".to_string(), 4..10));
+        let query = config.tokenize_query("FooBar", true);
+        assert_eq!(query.term_count(&"=FooBar".to_string()), 1);
+        assert_eq!(query.term_count(&"=foobar".to_string()), 0);
+    }
+
+    #[test]
+    fn test_token_filters() {
+        let config: AnalyzerConfig = toml::from_str(
+            r#"
+            stemmer = "none"
+            compounds = "whole"
+            strip_possessives = true
+            drop_numbers = true
+            "#,
+        )
+        .unwrap();
+        let mut tokenizer = config.tokenizer();
+        let text = "A state-of-the-art parser's 2nd 404";
+        let spans = config.document_spans(&mut tokenizer, text, "notes.md", false);
+        let terms = spans
+            .iter()
+            .map(|(term, _)| term.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(terms, ["a", "state-of-the-art", "parser", "2nd"]);
+
+        let query = config.tokenize_query("state-of-the-art 404", false);
+        assert_eq!(query.term_count(&"state-of-the-art".to_string()), 1);
+        assert_eq!(query.term_count(&"state".to_string()), 0);
+        assert_eq!(query.term_count(&"404".to_string()), 0);
+    }
+
+    #[test]
+    fn test_indexed_segmentation() {
+        let config = AnalyzerConfig::default();
+        let contents = serde_json::to_string(&config).unwrap();
+        assert!(contents.contains(r#""segmentation":"unicode""#));
+        assert_eq!(AnalyzerConfig::parse_indexed(&contents).unwrap(), config);
+
+        let legacy = r#"{"stemmer": "english", "stopwords": [], "tokenizer": "auto"}"#;
+        let indexed = AnalyzerConfig::parse_indexed(legacy).unwrap();
+        assert_eq!(indexed.segmentation, Segmentation::Simple);
+        assert_ne!(indexed, config);
+    }
+}
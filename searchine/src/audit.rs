@@ -0,0 +1,182 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use documents::DocumentId;
+
+use crate::config::AUDIT_FILENAME;
+use crate::settings::Settings;
+
+/// The configuration of the audit log, read from the `[audit]` section of
+/// the config of a repository.
+///
+/// ```toml
+/// [audit]
+/// enabled = true
+/// max_bytes = 10485760
+/// keep = 5
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Whether queries and changes of the index are written to the audit
+    /// log. It is off by default.
+    pub enabled: bool,
+    /// The size of the log file, in bytes, after which it is rotated.
+    pub max_bytes: u64,
+    /// The number of rotated log files that are kept.
+    pub keep: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_bytes: 10 * 1024 * 1024,
+            keep: 5,
+        }
+    }
+}
+
+/// An event that is written to the audit log.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum AuditEvent<'a> {
+    /// A query, and the documents that were returned for it.
+    Search {
+        query: &'a str,
+        results: Vec<DocumentId>,
+    },
+    /// The preview of the text of a document.
+    Preview { doc_id: DocumentId, found: bool },
+    /// A request that was denied, because its API key was missing or unknown.
+    Denied { url: &'a str },
+    /// Documents that were added to, updated in, or removed from the index.
+    Index {
+        added: Vec<&'a Path>,
+        modified: Vec<&'a Path>,
+        removed: Vec<&'a Path>,
+    },
+}
+
+/// A line of the audit log.
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    /// The time of the event, in seconds since the Unix epoch.
+    time: u64,
+    /// Who caused the event, like the user that ran a command, or the
+    /// address and the API key of a client of the server.
+    actor: &'a str,
+    #[serde(flatten)]
+    event: &'a AuditEvent<'a>,
+}
+
+/// An append-only log of the queries and the changes of the index of a
+/// repository, for repositories of shared or regulated documents.
+///
+/// Each event is a line of JSON. When the log grows past its maximum size, it
+/// is rotated: `audit.log` is renamed to `audit.log.1`, `audit.log.1` to
+/// `audit.log.2`, and so on, and the oldest log is removed.
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl AuditLog {
+    /// Opens the audit log of a repository, if it is enabled in its config.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config of the repository cannot be read.
+    pub fn open(repo_dir: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        let config = Settings::load(&repo_dir)?.audit;
+        Ok(Self::with_config(repo_dir, &config))
+    }
+
+    /// Opens the audit log of a repository with a config, if it is enabled.
+    pub fn with_config(repo_dir: impl AsRef<Path>, config: &AuditConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            path: repo_dir.as_ref().join(AUDIT_FILENAME),
+            max_bytes: config.max_bytes,
+            keep: config.keep,
+        })
+    }
+
+    /// Appends an event to the log, rotating the log first if it is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log cannot be rotated or written.
+    pub fn append(&self, actor: &str, event: &AuditEvent) -> anyhow::Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let record = AuditRecord { time, actor, event };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let len = fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+        if len > 0 && len + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context(format!("Failed to open audit log: {}", self.path.display()))?;
+        file.write_all(&line).context(format!(
+            "Failed to write audit log: {}",
+            self.path.display()
+        ))
+    }
+
+    /// Shifts the rotated logs by one, dropping the oldest, and rotates the
+    /// current log.
+    fn rotate(&self) -> anyhow::Result<()> {
+        if self.keep == 0 {
+            return fs::remove_file(&self.path).context(format!(
+                "Failed to remove audit log: {}",
+                self.path.display()
+            ));
+        }
+        for i in (1..self.keep).rev() {
+            let from = self.rotated_path(i);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(i + 1))
+                    .context(format!("Failed to rotate audit log: {}", from.display()))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1)).context(format!(
+            "Failed to rotate audit log: {}",
+            self.path.display()
+        ))
+    }
+
+    /// Returns the path to a rotated log, where 1 is the newest.
+    fn rotated_path(&self, i: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{i}"));
+        path.into()
+    }
+}
+
+/// Appends an event that was caused by the user running a command to the
+/// audit log of a repository, if it is enabled.
+pub fn record(repo_dir: impl AsRef<Path>, event: &AuditEvent) -> anyhow::Result<()> {
+    match AuditLog::open(repo_dir)? {
+        Some(log) => log.append(&local_user(), event),
+        None => Ok(()),
+    }
+}
+
+/// Returns the name of the user that runs the process.
+fn local_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use documents::DocumentId;
+
+use crate::config::{CACHE_DIRNAME, QUERY_CACHE_CAPACITY, QUERY_CACHE_FILENAME};
+
+/// The configuration of the cache of the results of queries, read from the
+/// `[cache]` section of the config of a repository.
+///
+/// ```toml
+/// [cache]
+/// enabled = true
+/// capacity = 256
+/// persist = true
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Whether the ranked results of queries are cached. It is off by
+    /// default.
+    pub enabled: bool,
+    /// The number of queries whose results are kept. The results of the
+    /// least recently used query are dropped first.
+    pub capacity: usize,
+    /// Whether `searchine search` keeps the cache in the repository, so that
+    /// it lasts across searches. `searchine serve` keeps its cache in memory
+    /// either way. It is off by default.
+    pub persist: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: QUERY_CACHE_CAPACITY,
+            persist: false,
+        }
+    }
+}
+
+/// A least recently used cache of the ranked documents of queries, at one
+/// generation of the index.
+///
+/// The keys of the queries include everything that their ranking depends on,
+/// other than the index. The results of a generation are never used for
+/// another one, so the cache is emptied when the index changes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QueryCache {
+    generation: u64,
+    /// The keys of the queries and their ranked documents, from the least to
+    /// the most recently used.
+    entries: VecDeque<(String, Vec<(DocumentId, f64)>)>,
+    #[serde(skip)]
+    capacity: usize,
+}
+
+impl QueryCache {
+    /// Creates an empty cache for a generation of the index, if the cache is
+    /// enabled in the config.
+    pub fn with_config(config: &CacheConfig, generation: u64) -> Option<Self> {
+        config.enabled.then(|| Self {
+            generation,
+            entries: VecDeque::new(),
+            capacity: config.capacity,
+        })
+    }
+
+    /// Loads the cache that is kept in a repository, if the cache is enabled
+    /// and persisted in the config. A cache of another generation of the
+    /// index, or one that cannot be read, is replaced by an empty one.
+    pub fn load(repo_dir: impl AsRef<Path>, config: &CacheConfig, generation: u64) -> Option<Self> {
+        if !config.persist {
+            return None;
+        }
+        let empty = Self::with_config(config, generation)?;
+        let path = repo_dir
+            .as_ref()
+            .join(CACHE_DIRNAME)
+            .join(QUERY_CACHE_FILENAME);
+        let cache = File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader::<_, Self>(BufReader::new(file)).ok())
+            .filter(|cache| cache.generation == generation);
+        match cache {
+            Some(mut cache) => {
+                cache.capacity = config.capacity;
+                cache.truncate();
+                Some(cache)
+            }
+            None => Some(empty),
+        }
+    }
+
+    /// Keeps the cache in a repository. The cache is written to a temporary
+    /// file first, which then replaces the cache file, so concurrent searches
+    /// never read a partial cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file cannot be written.
+    pub fn save(&self, repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let cache_dir = repo_dir.as_ref().join(CACHE_DIRNAME);
+        fs::create_dir_all(&cache_dir).context(format!(
+            "Failed to create cache directory: {}",
+            cache_dir.display()
+        ))?;
+        let path = cache_dir.join(QUERY_CACHE_FILENAME);
+        let tmp_path = path.with_extension("json.tmp");
+        let file = File::create(&tmp_path).context(format!(
+            "Failed to write cache file: {}",
+            tmp_path.display()
+        ))?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        fs::rename(&tmp_path, &path)
+            .context(format!("Failed to write cache file: {}", path.display()))
+    }
+
+    /// Removes the cache that is kept in a repository, if there is one, for
+    /// changes that the keys of the queries do not capture, like the tags of
+    /// the documents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file exists and cannot be removed.
+    pub fn clear(repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = repo_dir
+            .as_ref()
+            .join(CACHE_DIRNAME)
+            .join(QUERY_CACHE_FILENAME);
+        if path.exists() {
+            fs::remove_file(&path)
+                .context(format!("Failed to remove cache file: {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the ranked documents of a query, if they are cached, and marks
+    /// the query as the most recently used.
+    pub fn get(&mut self, key: &str) -> Option<Vec<(DocumentId, f64)>> {
+        let position = self.entries.iter().position(|(cached, _)| cached == key)?;
+        let entry = self.entries.remove(position)?;
+        let ranked = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(ranked)
+    }
+
+    /// Caches the ranked documents of a query, dropping the least recently
+    /// used queries if the cache is full.
+    pub fn insert(&mut self, key: String, ranked: Vec<(DocumentId, f64)>) {
+        self.entries.retain(|(cached, _)| *cached != key);
+        self.entries.push_back((key, ranked));
+        self.truncate();
+    }
+
+    /// Drops the least recently used queries, until the cache is not over
+    /// its capacity.
+    fn truncate(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_cache() {
+        let config = CacheConfig {
+            enabled: true,
+            capacity: 2,
+            persist: false,
+        };
+        let mut cache = QueryCache::with_config(&config, 1).unwrap();
+        cache.insert("budget".to_string(), vec![(0, 1.0)]);
+        cache.insert("report".to_string(), vec![(1, 0.5)]);
+        assert_eq!(cache.get("budget"), Some(vec![(0, 1.0)]));
+        // The least recently used query is dropped.
+        cache.insert("meeting".to_string(), vec![]);
+        assert_eq!(cache.get("report"), None);
+        assert_eq!(cache.get("budget"), Some(vec![(0, 1.0)]));
+        assert_eq!(cache.get("meeting"), Some(vec![]));
+
+        assert!(QueryCache::with_config(&CacheConfig::default(), 1).is_none());
+        assert!(QueryCache::load("/nonexistent", &config, 1).is_none());
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use index::collection::Collection;
+
+use crate::cli::index::{apply_changes, Changes};
+use crate::cli::utils::{index_format, merge_small_segments};
+use crate::config::COLLECTION_FILENAME;
+use crate::fs::Directory;
+
+/// Indexes a single document of a repository, without walking the rest of
+/// the corpus, so that the index can be kept current as files are saved.
+///
+/// A document that is not in the collection yet is given a new ID, and one
+/// that is already in it is indexed again under its ID.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `doc_path` - The path to the file of the document.
+///
+/// # Errors
+///
+/// Returns an error if the file does not exist, or if it is not a document
+/// of the corpus, because it is hidden, ignored, filtered out, of a type
+/// that is not supported, or outside the roots of the corpus, so that the
+/// next `searchine index` would remove it again.
+pub fn invoke(repo_dir: impl AsRef<Path>, doc_path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let doc_path = doc_path.as_ref();
+    let doc_path = doc_path
+        .canonicalize()
+        .with_context(|| format!("Failed to find document: {}", doc_path.display()))?;
+    if !doc_path.is_file() {
+        anyhow::bail!("Document {} is not a file.", doc_path.display());
+    }
+    if !Directory::for_repo(repo_dir)?.contains(&doc_path) {
+        anyhow::bail!(
+            "Document {} is not part of the corpus, as it is hidden, ignored, filtered out, \
+             not supported or outside its roots.",
+            doc_path.display()
+        );
+    }
+
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let is_indexed = collection.contains_path(&doc_path);
+    let changes = match is_indexed {
+        true => Changes {
+            modified: vec![doc_path.clone()],
+            ..Changes::default()
+        },
+        false => Changes {
+            new: vec![doc_path.clone()],
+            ..Changes::default()
+        },
+    };
+    let format = index_format(repo_dir);
+    apply_changes(repo_dir, collection, &changes, format, false)?;
+    merge_small_segments(repo_dir, format)?;
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
+    match is_indexed {
+        true => println_bold!("{emoji} Updated {} in the index.", doc_path.display()),
+        false => println_bold!("{emoji} Added {} to the index.", doc_path.display()),
+    }
+    Ok(())
+}
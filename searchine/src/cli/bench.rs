@@ -0,0 +1,334 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use index::collection::Collection;
+use index::inverted::builder::IndexBuilder;
+use index::inverted::Index;
+use index::store::DocumentStore;
+use query::{Phrase, Query};
+
+use crate::analyzer::AnalyzerConfig;
+use crate::cli::index::{index_paths, list_documents};
+use crate::cli::search::{rank, CanonicalPaths, DocumentBoosts, Ranking, Scorer};
+use crate::cli::utils::write_records;
+use crate::cli::OutputFormat;
+use crate::config::STORE_DIRNAME;
+use crate::pattern::{expand_patterns, split_patterns};
+use crate::progress;
+use crate::settings::Settings;
+use crate::snapshot::Snapshot;
+use crate::synonyms::Thesaurus;
+
+/// The report of a benchmark of a repository, as it is written in the JSON
+/// output. It records what was measured along with the results, so that
+/// the reports of two versions of searchine can be compared.
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    /// The number of threads that the documents are scored with.
+    threads: usize,
+    scorer: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    indexing: Option<IndexingReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queries: Option<QueryReport>,
+}
+
+/// The throughput of indexing the documents of the corpus.
+#[derive(Debug, Serialize)]
+struct IndexingReport {
+    documents: usize,
+    /// The size of the files of the documents, in bytes.
+    bytes: u64,
+    seconds: f64,
+    docs_per_sec: f64,
+    mb_per_sec: f64,
+}
+
+/// The latencies of searching for the queries of a query file, in
+/// milliseconds.
+#[derive(Debug, Serialize)]
+struct QueryReport {
+    queries: usize,
+    /// The number of times each query was searched for, after a first
+    /// search that is not measured.
+    runs: usize,
+    mean_ms: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+/// Measures how fast the documents of a repository are indexed, and how
+/// long the queries of a query file take to search for, against the current
+/// index, and prints a report of the results, so that the performance of
+/// two versions of searchine can be compared on the same corpus.
+///
+/// The documents are indexed in memory, with their texts kept in a
+/// temporary document store, so the index of the repository is left as it
+/// is. Each query is searched for once before it is measured, so that the
+/// index is warm, and then `runs` times, like `searchine search` ranks it,
+/// without snippets.
+///
+/// # Arguments
+///
+/// * `repo_dir`     - The path to the searchine repository.
+/// * `queries_path` - The path to the query file, with a query per line, if
+///   queries are measured.
+/// * `runs`         - The number of times each query is measured.
+/// * `indexing`     - `true` if indexing is measured.
+/// * `output`       - The format the report is written in.
+///
+/// # Errors
+///
+/// Returns an error if the documents cannot be indexed, if the query file
+/// cannot be read or has no queries, or if a query cannot be analyzed.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    queries_path: Option<&Path>,
+    runs: usize,
+    indexing: bool,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let settings = Settings::load(repo_dir)?;
+    let indexing = match indexing {
+        true => Some(bench_indexing(repo_dir, &settings)?),
+        false => None,
+    };
+    let queries = match queries_path {
+        Some(path) => Some(bench_queries(repo_dir, &settings, path, runs)?),
+        None => None,
+    };
+    let report = BenchReport {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        threads: rayon::current_num_threads(),
+        scorer: match settings.search.scorer {
+            Scorer::TfIdf => "tfidf",
+            Scorer::Bm25 => "bm25",
+        },
+        indexing,
+        queries,
+    };
+    match output {
+        OutputFormat::Text => display_report(&report)?,
+        _ => write_records(&[report], output)?,
+    }
+    Ok(())
+}
+
+/// Indexes the documents of the corpus of a repository in memory, through
+/// the same pipeline as `searchine index`, and measures its throughput.
+fn bench_indexing(repo_dir: &Path, settings: &Settings) -> anyhow::Result<IndexingReport> {
+    let (roots, dir) = list_documents(repo_dir, false)?;
+    let bytes = dir
+        .iter()
+        .filter_map(|path| path.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum::<u64>();
+    let documents = dir.len();
+    let store_dir = std::env::temp_dir().join(format!(
+        "searchine-bench-{}-{STORE_DIRNAME}",
+        std::process::id()
+    ));
+    let store = DocumentStore::create(&store_dir)?;
+
+    let start = Instant::now();
+    let mut collection = Collection::from_paths(dir.clone())?;
+    collection.set_roots(roots);
+    let mut builder = IndexBuilder::new();
+    let result = index_paths(
+        &mut builder,
+        dir,
+        collection,
+        store,
+        settings,
+        &progress::documents_bar(documents as u64, false),
+    );
+    let seconds = start.elapsed().as_secs_f64();
+    fs::remove_dir_all(&store_dir).context(format!(
+        "Failed to remove document store: {}",
+        store_dir.display()
+    ))?;
+    result?;
+
+    Ok(IndexingReport {
+        documents,
+        bytes,
+        seconds,
+        docs_per_sec: documents as f64 / seconds,
+        mb_per_sec: bytes as f64 / (1024.0 * 1024.0) / seconds,
+    })
+}
+
+/// Searches for each query of a query file `runs` times, after a first
+/// search that warms the index up, and measures the latencies of the
+/// searches. Empty lines and lines that start with `#` are skipped.
+fn bench_queries(
+    repo_dir: &Path,
+    settings: &Settings,
+    queries_path: &Path,
+    runs: usize,
+) -> anyhow::Result<QueryReport> {
+    let contents = fs::read_to_string(queries_path).context(format!(
+        "Failed to read query file: {}",
+        queries_path.display()
+    ))?;
+    let queries = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>();
+    if queries.is_empty() {
+        anyhow::bail!("Query file {} has no queries.", queries_path.display());
+    }
+
+    let snapshot = Snapshot::open(repo_dir)?;
+    let index = snapshot.index.as_ref();
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let analyzer = &settings.analyzer;
+    analyzer.check(repo_dir)?;
+    let thesaurus = Thesaurus::load(repo_dir, analyzer)?;
+    let search = &settings.search;
+    let boosts = DocumentBoosts::new(repo_dir, search)?;
+    let canonical = CanonicalPaths::new(repo_dir, search)?;
+    let ranking = Ranking {
+        top_n: search.top_n,
+        search,
+        boosts: boosts.as_ref(),
+        analyzer,
+        passage_config: None,
+        canonical: canonical.as_ref(),
+        collapse: None,
+    };
+
+    let mut latencies = Vec::with_capacity(queries.len() * runs);
+    for query in &queries {
+        for run in 0..=runs {
+            let start = Instant::now();
+            let terms = analyze(index, analyzer, &thesaurus, query)?;
+            let hits = rank(
+                index,
+                &snapshot.inv_collection,
+                &store,
+                &terms,
+                &ranking,
+                None,
+            )?;
+            let elapsed = start.elapsed();
+            std::hint::black_box(hits);
+            if run > 0 {
+                latencies.push(elapsed);
+            }
+        }
+    }
+    Ok(QueryReport::of(queries.len(), runs, latencies))
+}
+
+/// Analyzes a query into its terms, like `searchine search` does, with its
+/// synonyms and its patterns. The phrases are scored as terms.
+fn analyze(
+    index: &dyn Index,
+    analyzer: &AnalyzerConfig,
+    thesaurus: &Thesaurus,
+    query: &str,
+) -> anyhow::Result<Query> {
+    let (lexical, _) = Phrase::parse_all(query);
+    let (lexical, patterns) = split_patterns(&lexical);
+    let lexical = thesaurus.expand(&lexical, analyzer);
+    let mut terms = analyzer.tokenize_query(&lexical, false);
+    terms.extend(expand_patterns(index, &patterns, false)?);
+    Ok(terms)
+}
+
+impl QueryReport {
+    /// Summarizes the latencies of the searches for a number of queries.
+    fn of(queries: usize, runs: usize, mut latencies: Vec<Duration>) -> Self {
+        latencies.sort();
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let total = latencies.iter().copied().map(millis).sum::<f64>();
+        Self {
+            queries,
+            runs,
+            mean_ms: total / latencies.len().max(1) as f64,
+            p50_ms: percentile(&latencies, 50).map_or(0.0, millis),
+            p90_ms: percentile(&latencies, 90).map_or(0.0, millis),
+            p99_ms: percentile(&latencies, 99).map_or(0.0, millis),
+            max_ms: latencies.last().copied().map_or(0.0, millis),
+        }
+    }
+}
+
+/// Returns the latency at a percentile of sorted latencies, with the
+/// nearest-rank method, so that it is one of the latencies.
+fn percentile(sorted: &[Duration], percentile: usize) -> Option<Duration> {
+    let rank = (percentile * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+/// Writes the report to stdout as a table.
+fn display_report(report: &BenchReport) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    writeln!(
+        tw,
+        "searchine:\t{} ({}/{}, {} threads)",
+        report.version, report.os, report.arch, report.threads
+    )?;
+    writeln!(tw, "Scorer:\t{}", report.scorer)?;
+    if let Some(indexing) = &report.indexing {
+        writeln!(
+            tw,
+            "\nIndexing:\t{} documents, {:.1} MiB in {:.2} s",
+            indexing.documents,
+            indexing.bytes as f64 / (1024.0 * 1024.0),
+            indexing.seconds
+        )?;
+        writeln!(
+            tw,
+            "Throughput:\t{:.1} docs/s, {:.2} MiB/s",
+            indexing.docs_per_sec, indexing.mb_per_sec
+        )?;
+    }
+    if let Some(queries) = &report.queries {
+        writeln!(
+            tw,
+            "\nQueries:\t{} queries, {} runs each",
+            queries.queries, queries.runs
+        )?;
+        writeln!(tw, "Mean:\t{:.3} ms", queries.mean_ms)?;
+        writeln!(tw, "p50:\t{:.3} ms", queries.p50_ms)?;
+        writeln!(tw, "p90:\t{:.3} ms", queries.p90_ms)?;
+        writeln!(tw, "p99:\t{:.3} ms", queries.p99_ms)?;
+        writeln!(tw, "Max:\t{:.3} ms", queries.max_ms)?;
+    }
+    tw.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_report() {
+        let latencies = (1..=200).rev().map(Duration::from_millis).collect();
+        let report = QueryReport::of(40, 5, latencies);
+        assert_eq!(report.p50_ms, 100.0);
+        assert_eq!(report.p90_ms, 180.0);
+        assert_eq!(report.p99_ms, 198.0);
+        assert_eq!(report.max_ms, 200.0);
+        assert!((report.mean_ms - 100.5).abs() < 1e-9);
+        assert_eq!(percentile(&[], 50), None);
+    }
+}
@@ -1,19 +1,25 @@
 use std::collections::BTreeSet;
 use std::io::{self, Write};
 use std::path::Path;
+use std::time::UNIX_EPOCH;
 
-use anyhow::Context;
+use serde::Serialize;
 use tabwriter::TabWriter;
 
+use documents::DocumentId;
 use index::collection::*;
 
+use crate::cli::utils::{format_size, write_records};
+use crate::cli::OutputFormat;
 use crate::config::COLLECTION_FILENAME;
 use crate::fs::Directory;
+use crate::progress;
 
 /// Indexes a collection of documents.
 ///
-/// The collection is a list of documents in the directory. Each document
-/// is assigned a document ID and the last time the document was indexed.
+/// The collection is a list of documents in the directory, and in the other
+/// root directories of the corpus. Each document is assigned a document ID
+/// and the last time the document was indexed.
 ///
 /// The index is then used as a cache of the up to date indexed documents.
 /// If the last modified time of the document is later than the last indexing
@@ -22,16 +28,22 @@ use crate::fs::Directory;
 /// # Arguments
 ///
 /// * `repo_dir` - The path to the searchine repository.
-/// * `verbose`  - `true` if all information should be print in stdout.
+/// * `verbose`  - `true` if the progress of listing the files, and the files
+///   that are skipped, should be shown.
 pub fn index(repo_dir: impl AsRef<Path>, verbose: bool) -> anyhow::Result<()> {
     let repo_dir = repo_dir.as_ref();
-    let dir_path = repo_dir
-        .parent()
-        .context("Could not derive directory path.")?;
-
-    let dir = Directory::new(dir_path)?;
-    let paths = dir.iter_full_paths(verbose).collect::<BTreeSet<_>>();
-    let corpus_index = Collection::from_paths(paths)?;
+    let dir = Directory::for_repo(repo_dir)?;
+    let spinner = progress::files_spinner(verbose);
+    let paths = dir
+        .iter_full_paths(verbose)
+        .inspect(|_| spinner.inc(1))
+        .collect::<BTreeSet<_>>();
+    spinner.finish_and_clear();
+    let mut corpus_index = Collection::from_paths(paths)?;
+    corpus_index.set_roots(dir.roots());
+    if let Ok(previous) = Collection::from_file(repo_dir.join(COLLECTION_FILENAME)) {
+        corpus_index.keep_tags(&previous);
+    }
     corpus_index.into_file(repo_dir.join(COLLECTION_FILENAME))?;
 
     let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x9A]).unwrap_or_default();
@@ -40,38 +52,78 @@ pub fn index(repo_dir: impl AsRef<Path>, verbose: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// A document of the collection, as it is written in the JSON output. The
+/// last modified time is in seconds since the Unix epoch, and the size is in
+/// bytes, if it was kept. The title and the tags are left out if the
+/// document has none.
+#[derive(Serialize)]
+struct CollectionRecord<'a> {
+    path: &'a Path,
+    doc_id: DocumentId,
+    modified: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+}
+
 /// Lists the indexed documents in the corpus, which are listed in the
 /// corpus-index. The result is printed to the standard output as a table.
 /// The first column is the path to the document, the second column is the
-/// document ID, and the third column is the last modified time.
+/// document ID, the third column is the last modified time, and the fourth
+/// column is the size of the file, if it was kept. In the JSON
+/// output formats, each document is written as a record instead.
 ///
 /// # Arguments
 ///
 /// * `repo_dir` - The path to the searchine repository.
+/// * `output`   - The format of the output.
 ///
 /// # Errors
 ///
 /// Returns an error if the corpus index file cannot be read.
-pub fn list(repo_dir: impl AsRef<Path>) -> io::Result<()> {
+pub fn list(repo_dir: impl AsRef<Path>, output: OutputFormat) -> anyhow::Result<()> {
     // Load and order the paths
     let index_path = repo_dir.as_ref().join(COLLECTION_FILENAME);
     let corpus_index = Collection::from_file(index_path)?
         .into_iter()
         .collect::<BTreeSet<_>>();
 
+    if output != OutputFormat::Text {
+        let records = corpus_index
+            .iter()
+            .map(|(path, entry)| CollectionRecord {
+                path,
+                doc_id: entry.document_id(),
+                modified: entry
+                    .modified()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs()),
+                size: entry.size(),
+                title: entry.title(),
+                tags: entry.tags().iter().map(String::as_str).collect(),
+            })
+            .collect::<Vec<_>>();
+        return write_records(&records, output);
+    }
+
     // Print out the indexed documents.
     let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x9A]).unwrap_or_default();
     println!("{emoji} Documents in the corpus: {}\n", corpus_index.len());
     let mut tab_writer = TabWriter::new(io::stdout());
-    _ = writeln!(tab_writer, "\tPath\tDocument ID\tLast Modified");
+    _ = writeln!(tab_writer, "\tPath\tDocument ID\tLast Modified\tSize");
     for (path, entry) in corpus_index {
         _ = writeln!(
             tab_writer,
-            "\t{}\t{}\t{:?}",
+            "\t{}\t{}\t{:?}\t{}",
             path.display(),
             entry.document_id(),
-            entry.modified()
+            entry.modified(),
+            entry.size().map_or("-".to_string(), format_size)
         );
     }
-    tab_writer.flush()
+    tab_writer.flush()?;
+    Ok(())
 }
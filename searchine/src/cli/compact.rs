@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use crate::cli::utils::{compact_index, format_size, index_size, load_manifest};
+
+/// Compacts the index of a repository, by writing the segments that have
+/// deleted documents again without them.
+///
+/// Documents that are removed from the index, or that are modified, are
+/// only marked as deleted in the manifest, so that the index is updated
+/// without rewriting its segments, and their postings take up space until
+/// their segments are compacted or merged.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+pub fn invoke(repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    if load_manifest(repo_dir)?.n_deleted() == 0 {
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+        println_bold!("{emoji} Index has no deleted documents. Nothing to compact.");
+        return Ok(());
+    }
+
+    let size_before = index_size(repo_dir)?;
+    let n_deleted = compact_index(repo_dir)?;
+    let size_after = index_size(repo_dir)?;
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
+    println_bold!(
+        "{emoji} Dropped {n_deleted} deleted documents, saving {} ({} -> {}).",
+        format_size(size_before.saturating_sub(size_after)),
+        format_size(size_before),
+        format_size(size_after),
+    );
+    Ok(())
+}
@@ -0,0 +1,438 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use documents::DocumentId;
+use index::collection::Collection;
+use index::cooccur::CooccurrenceSketch;
+use index::vector::{QuantizedIndex, VectorReader};
+
+use crate::cli::utils::{is_indexed, load_manifest, segments_dir, write_records};
+use crate::cli::verify;
+use crate::cli::OutputFormat;
+use crate::config::{
+    COLLECTION_FILENAME, LEGACY_INDEX_FILENAMES, MANIFEST_FILENAME, QUANTIZED_VECTORS_FILENAME,
+    RELATED_FILENAME, STAGING_STORE_DIRNAME, STORE_DIRNAME, VECTORS_FILENAME,
+};
+use crate::lock::{Holder, RepoLock};
+use crate::scheduler::{MergeStatus, MERGING_EXTENSION};
+use crate::settings::Settings;
+
+/// The status of a check, from the least to the most severe.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The result of a check of a repository, as it is written in the JSON
+/// output, with the commands that fix what the check found, in order.
+#[derive(Serialize, Debug)]
+struct Check {
+    check: &'static str,
+    status: Status,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    remediation: Vec<String>,
+}
+
+impl Check {
+    fn ok(check: &'static str, message: impl Into<String>) -> Self {
+        Self::new(check, Status::Ok, message)
+    }
+
+    fn warning(check: &'static str, message: impl Into<String>) -> Self {
+        Self::new(check, Status::Warning, message)
+    }
+
+    fn error(check: &'static str, message: impl Into<String>) -> Self {
+        Self::new(check, Status::Error, message)
+    }
+
+    fn new(check: &'static str, status: Status, message: impl Into<String>) -> Self {
+        Self {
+            check,
+            status,
+            message: message.into(),
+            remediation: vec![],
+        }
+    }
+
+    /// Adds a command that fixes what the check found.
+    fn remediation(mut self, command: impl Into<String>) -> Self {
+        self.remediation.push(command.into());
+        self
+    }
+}
+
+/// Checks the health of a repository, and reports what is wrong with it,
+/// along with the commands that fix it.
+///
+/// The checks are:
+/// * `lock`: whether the repository is locked, and by which process.
+/// * `config`: whether the config file can be read.
+/// * `analyzer`: whether the analyzer of the config is the one that the
+///   repository was indexed with.
+/// * `version`: whether the files of the index are in the supported version
+///   of the format.
+/// * `segments`: whether the segments of the index can be read.
+/// * `consistency`: whether the collection, the segments and the filesystem
+///   agree, like `searchine verify`.
+/// * `orphaned_files`: whether files were left behind by commands that were
+///   interrupted, such as temporary files, segments that are not in the
+///   manifest, and stored documents that are not in the collection.
+///
+/// Nothing is changed. In the text output, each check is shown on a line,
+/// followed by its remediation commands. In the JSON output formats, each
+/// check is written as a record.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `output`   - The format to write the report in.
+pub fn invoke(repo_dir: impl AsRef<Path>, output: OutputFormat) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let holder = RepoLock::holder(repo_dir);
+    let in_use = matches!(holder, Holder::Running(_));
+
+    let mut checks = vec![check_lock(holder)];
+    checks.extend(check_settings(repo_dir));
+    checks.extend(check_index(repo_dir));
+    checks.push(check_orphaned_files(repo_dir, in_use));
+
+    if output != OutputFormat::Text {
+        return write_records(&checks, output);
+    }
+    display_checks(&checks);
+    Ok(())
+}
+
+/// Checks the lock of the repository.
+fn check_lock(holder: Holder) -> Check {
+    match holder {
+        Holder::Running(Some(pid)) => Check::warning(
+            "lock",
+            format!("Repository is locked by a running searchine process (PID {pid})."),
+        ),
+        Holder::Running(None) => Check::warning(
+            "lock",
+            "Repository is locked by a running searchine process.",
+        ),
+        Holder::None => Check::ok("lock", "Repository is not locked."),
+    }
+}
+
+/// Checks the config file, and the analyzer it configures against the one
+/// that the repository was indexed with.
+fn check_settings(repo_dir: &Path) -> Vec<Check> {
+    let settings = match Settings::load(repo_dir) {
+        Ok(settings) => settings,
+        Err(err) => return vec![Check::error("config", format!("{err:#}"))],
+    };
+    let analyzer = match settings.analyzer.check(repo_dir) {
+        Ok(()) => Check::ok(
+            "analyzer",
+            "Analyzer is the one the repository was indexed with.",
+        ),
+        Err(err) => {
+            Check::error("analyzer", format!("{err:#}")).remediation("searchine index --full")
+        }
+    };
+    vec![Check::ok("config", "Config is valid."), analyzer]
+}
+
+/// Checks the version and the readability of the files of the index, and
+/// the consistency of the collection with the segments and the filesystem.
+fn check_index(repo_dir: &Path) -> Vec<Check> {
+    if !is_indexed(repo_dir) {
+        let legacy = LEGACY_INDEX_FILENAMES
+            .iter()
+            .any(|name| repo_dir.join(name).exists());
+        let check = if legacy {
+            Check::error(
+                "version",
+                "Repository was indexed in the format of an older version of searchine.",
+            )
+            .remediation("searchine index --full")
+        } else {
+            Check::error("version", "Repository has not been indexed.")
+                .remediation("searchine index")
+        };
+        return vec![check];
+    }
+
+    let collection_path = repo_dir.join(COLLECTION_FILENAME);
+    let collection = match Collection::from_file(&collection_path) {
+        Ok(collection) => collection,
+        Err(err) => {
+            return vec![Check::error("consistency", format!("{err:#}"))
+                .remediation("searchine index --full")]
+        }
+    };
+    let report = match verify::check(repo_dir, &collection) {
+        Ok(report) => report,
+        Err(err) => {
+            return vec![
+                Check::error("version", format!("{err:#}")).remediation("searchine index --full")
+            ]
+        }
+    };
+
+    let mut mismatches = vec![];
+    let mut corrupted = vec![];
+    for (name, err) in &report.corrupted {
+        match err.downcast_ref::<index::Error>() {
+            Some(err @ index::Error::VersionMismatch { .. }) => mismatches.push(err.to_string()),
+            _ => corrupted.push(format!("{name} ({err:#})")),
+        }
+    }
+    for name in [
+        VECTORS_FILENAME,
+        QUANTIZED_VECTORS_FILENAME,
+        RELATED_FILENAME,
+    ] {
+        let path = repo_dir.join(name);
+        if !path.exists() {
+            continue;
+        }
+        let opened = match name {
+            VECTORS_FILENAME => VectorReader::open(&path).map(|_| ()),
+            QUANTIZED_VECTORS_FILENAME => QuantizedIndex::from_file(&path).map(|_| ()),
+            _ => CooccurrenceSketch::from_file(&path).map(|_| ()),
+        };
+        match opened {
+            Ok(()) => {}
+            Err(err @ index::Error::VersionMismatch { .. }) => mismatches.push(err.to_string()),
+            Err(err) => corrupted.push(format!("{name} ({err})")),
+        }
+    }
+
+    let mut checks = vec![];
+    checks.push(if mismatches.is_empty() {
+        Check::ok(
+            "version",
+            "Index is in the supported version of the format.",
+        )
+    } else {
+        Check::error("version", mismatches.join(" "))
+            .remediation("searchine migrate")
+            .remediation("searchine index --full")
+    });
+    checks.push(if corrupted.is_empty() {
+        Check::ok("segments", "Files of the index can be read.")
+    } else {
+        Check::error(
+            "segments",
+            format!(
+                "Files of the index cannot be read: {}.",
+                corrupted.join(", ")
+            ),
+        )
+        .remediation("searchine verify --fix")
+        .remediation("searchine index")
+    });
+    checks.extend(check_consistency(&report, &collection));
+    checks
+}
+
+/// Checks the report of the consistency of the collection with the segments
+/// and the filesystem.
+fn check_consistency(report: &verify::Report, collection: &Collection) -> Vec<Check> {
+    let mut checks = vec![];
+    if !report.orphans.is_empty() {
+        checks.push(
+            Check::error(
+                "consistency",
+                format!(
+                    "{} documents are in the index, but not in the collection.",
+                    report.orphans.len()
+                ),
+            )
+            .remediation("searchine verify --fix"),
+        );
+    }
+    if !report.missing.is_empty() {
+        checks.push(
+            Check::warning(
+                "consistency",
+                format!(
+                    "{} documents of the collection no longer exist on disk.",
+                    report.missing.len()
+                ),
+            )
+            .remediation("searchine prune --deleted"),
+        );
+    }
+    if !report.unindexed.is_empty() {
+        checks.push(
+            Check::warning(
+                "consistency",
+                format!(
+                    "{} documents of the collection are in no segment of the index.",
+                    report.unindexed.len()
+                ),
+            )
+            .remediation("searchine verify --fix")
+            .remediation("searchine index"),
+        );
+    }
+    if checks.is_empty() {
+        let count = collection.into_iter().count();
+        checks.push(Check::ok(
+            "consistency",
+            format!("Collection and index agree on {count} documents."),
+        ));
+    }
+    checks
+}
+
+/// Checks for files that were left behind by commands that were interrupted.
+/// While another process holds the lock, the files may be in use, so they
+/// are reported without a command to remove them.
+fn check_orphaned_files(repo_dir: &Path, in_use: bool) -> Check {
+    let paths = match orphaned_files(repo_dir) {
+        Ok(paths) => paths,
+        Err(err) => return Check::error("orphaned_files", format!("{err:#}")),
+    };
+    if paths.is_empty() {
+        return Check::ok("orphaned_files", "No orphaned files.");
+    }
+    let names = paths
+        .iter()
+        .map(|path| {
+            path.strip_prefix(repo_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    let check = Check::warning(
+        "orphaned_files",
+        format!(
+            "Found {} orphaned files: {}.",
+            paths.len(),
+            names.join(", ")
+        ),
+    );
+    if in_use {
+        return check;
+    }
+    let paths = paths.iter().map(|path| quote(path)).collect::<Vec<_>>();
+    check.remediation(format!("rm -r {}", paths.join(" ")))
+}
+
+/// Finds the files in a repository that no file of the index refers to.
+fn orphaned_files(repo_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(repo_dir)? {
+        let path = entry?.path();
+        let is_staging = path
+            .file_name()
+            .is_some_and(|name| name == STAGING_STORE_DIRNAME);
+        if is_staging || is_tmp(&path) {
+            paths.push(path);
+        }
+    }
+
+    let segments_dir = segments_dir(repo_dir);
+    if segments_dir.is_dir() {
+        let segments = match load_manifest(repo_dir) {
+            Ok(manifest) => manifest.segments().iter().cloned().collect::<BTreeSet<_>>(),
+            Err(_) => BTreeSet::new(),
+        };
+        let merging = MergeStatus::load(repo_dir).is_some();
+        for entry in fs::read_dir(&segments_dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let is_merging = path
+                .extension()
+                .is_some_and(|extension| extension == MERGING_EXTENSION);
+            if name == MANIFEST_FILENAME || segments.contains(name) || (is_merging && merging) {
+                continue;
+            }
+            paths.push(path);
+        }
+    }
+
+    let store_dir = repo_dir.join(STORE_DIRNAME);
+    if store_dir.is_dir() {
+        let doc_ids = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))
+            .map(|collection| {
+                (&collection)
+                    .into_iter()
+                    .map(|(_, entry)| entry.document_id())
+                    .collect::<BTreeSet<_>>()
+            })
+            .unwrap_or_default();
+        for entry in fs::read_dir(&store_dir)? {
+            let path = entry?.path();
+            let doc_id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<DocumentId>().ok());
+            if is_tmp(&path) || doc_id.is_some_and(|doc_id| !doc_ids.contains(&doc_id)) {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Checks if a file is a temporary file, which is renamed once it has been
+/// written.
+fn is_tmp(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "tmp")
+}
+
+/// Quotes a path for the shell.
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
+
+fn display_checks(checks: &[Check]) {
+    let ok = String::from_utf8(vec![0xE2, 0x9C, 0x94]).unwrap_or_default();
+    let warning = String::from_utf8(vec![0xE2, 0x9A, 0xA0]).unwrap_or_default();
+    let error = String::from_utf8(vec![0xE2, 0x9C, 0x96]).unwrap_or_default();
+    for check in checks {
+        match check.status {
+            Status::Ok => {
+                println_green!("  {ok} {}: {}", check.check, check.message);
+            }
+            Status::Warning => {
+                println_bold!("  {warning} {}: {}", check.check, check.message);
+            }
+            Status::Error => {
+                println_red!("  {error} {}: {}", check.check, check.message);
+            }
+        }
+        for command in &check.remediation {
+            println!("      (run \"{command}\")");
+        }
+    }
+    let worst = checks.iter().map(|check| check.status).max();
+    if worst == Some(Status::Ok) {
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+        println_bold!("{emoji} Repository is healthy.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(
+            quote(Path::new("/repo/.searchine/lock")),
+            "'/repo/.searchine/lock'"
+        );
+        assert_eq!(quote(Path::new("/it's/lock")), r"'/it'\''s/lock'");
+    }
+}
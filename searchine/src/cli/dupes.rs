@@ -0,0 +1,86 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use index::collection::InvertedCollection;
+use index::fingerprint::cluster;
+use index::store::DocumentStore;
+
+use crate::cli::utils::write_records;
+use crate::cli::OutputFormat;
+use crate::config::{COLLECTION_FILENAME, STORE_DIRNAME};
+
+/// A cluster of near-duplicate documents, as it is written in the JSON
+/// output.
+#[derive(Debug, PartialEq, Serialize)]
+struct Cluster<'a> {
+    documents: Vec<&'a PathBuf>,
+}
+
+/// Lists the clusters of near-duplicate documents of a repository, like
+/// copies of the same file with small edits, or exports of the same page.
+///
+/// The fingerprint of the text of each document is computed when it is
+/// indexed, and the documents whose fingerprints are at most `max_distance`
+/// bits apart are clustered, so the texts are not read again. Documents
+/// without words, and documents that were indexed before fingerprints were
+/// computed, are not clustered, until the repository is indexed again with
+/// `searchine index --full`.
+///
+/// # Arguments
+///
+/// * `repo_dir`     - The path to the searchine repository.
+/// * `max_distance` - The maximum distance of the fingerprints of
+///   near-duplicates.
+/// * `output`       - The format the clusters are written in.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    max_distance: u32,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let inv_collection = InvertedCollection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let mut fingerprints = vec![];
+    for (doc_id, _) in inv_collection.iter() {
+        if let Some(fingerprint) = store.read_fingerprint(doc_id)? {
+            fingerprints.push((doc_id, fingerprint));
+        }
+    }
+    fingerprints.sort_by_key(|(doc_id, _)| *doc_id);
+    let clusters = cluster(&fingerprints, max_distance)
+        .into_iter()
+        .map(|doc_ids| Cluster {
+            documents: doc_ids
+                .into_iter()
+                .filter_map(|doc_id| inv_collection.get_path(doc_id))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    match output {
+        OutputFormat::Text => display_clusters(&clusters)?,
+        _ => write_records(&clusters, output)?,
+    }
+    Ok(())
+}
+
+/// Writes the clusters to stdout, with the paths of the documents of each
+/// cluster on their own lines, and an empty line between clusters.
+fn display_clusters(clusters: &[Cluster]) -> io::Result<()> {
+    if clusters.is_empty() {
+        println_bold!("No near-duplicate documents found.");
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (i, cluster) in clusters.iter().enumerate() {
+        if i > 0 {
+            writeln!(stdout)?;
+        }
+        for path in &cluster.documents {
+            writeln!(stdout, "{}", path.display())?;
+        }
+    }
+    stdout.flush()
+}
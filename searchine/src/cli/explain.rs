@@ -0,0 +1,315 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use documents::archive;
+use documents::DocumentId;
+use index::inverted::Index;
+use index::store::DocumentStore;
+use query::{Phrase, Query};
+use tokenize::{CASED_PREFIX, TITLE_PREFIX};
+
+use crate::cli::search::{BoostFactors, DocumentBoosts, Scorer, SearchConfig};
+use crate::cli::utils::write_records;
+use crate::cli::OutputFormat;
+use crate::config::{BM25_B, BM25_K, STORE_DIRNAME};
+use crate::pattern::{expand_patterns, split_patterns};
+use crate::proximity::proximity;
+use crate::settings::Settings;
+use crate::snapshot::Snapshot;
+use crate::synonyms::Thesaurus;
+
+/// The breakdown of the score of a document against a query, as it is
+/// written in the JSON output.
+#[derive(Debug, Serialize)]
+struct Explanation {
+    path: PathBuf,
+    doc_id: DocumentId,
+    scorer: &'static str,
+    /// The number of documents in the index.
+    documents: u32,
+    /// The number of terms of the document.
+    document_length: u32,
+    /// The average number of terms of the documents.
+    avg_document_length: f64,
+    terms: Vec<TermScore>,
+    /// The sum of the weighted scores of the terms.
+    lexical_score: f64,
+    boosts: BoostFactors,
+    /// The proximity of the terms of the query in the text of the document,
+    /// if it is weighted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proximity: Option<f64>,
+    /// The factor that the score is multiplied by for the proximity.
+    proximity_factor: f64,
+    score: f64,
+}
+
+/// The contribution of a term of a query to the score of a document.
+#[derive(Debug, PartialEq, Serialize)]
+struct TermScore {
+    /// The term, without the prefix of its field.
+    term: String,
+    field: &'static str,
+    /// The number of times the term is in the query.
+    count: u32,
+    /// The number of times the term is in the document.
+    tf: u32,
+    /// The frequency of the term with the weighting of the index, which
+    /// TF-IDF multiplies by the IDF.
+    weighted_tf: f64,
+    /// The number of documents that contain the term.
+    df: u32,
+    idf: f64,
+    /// The score of the term with the scorer, before it is weighted.
+    score: f64,
+    /// The boost of the field of the term.
+    boost: f64,
+    /// The score times the count and the boost, which is added to the score
+    /// of the document.
+    weighted_score: f64,
+}
+
+/// Explains the score of a document against a query, by showing how much
+/// each term of the query adds to it, with the frequencies that it is
+/// calculated from, and the weights of the document that multiply it, so
+/// that the ranking of a document can be understood and tuned.
+///
+/// The query is analyzed like the query of a search, with its synonyms and
+/// its patterns, and the document is scored with the `[search]` section of
+/// the config, or with a ranking profile. The passages of the document are
+/// not scored.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `query`    - The query to score the document against.
+/// * `doc_path` - The path to the indexed document.
+/// * `profile`  - The ranking profile to score the document with, if any.
+/// * `output`   - The format the explanation is written in.
+///
+/// # Errors
+///
+/// Returns an error if the document is not indexed, if the profile does not
+/// exist, or if a pattern of the query is invalid.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    query: &str,
+    doc_path: impl AsRef<Path>,
+    profile: Option<&str>,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let doc_path = doc_path.as_ref();
+    let doc_path = archive::canonicalize(doc_path)
+        .context(format!("Failed to find document: {}", doc_path.display()))?;
+    let snapshot = Snapshot::open(repo_dir)?;
+    let index = snapshot.index.as_ref();
+    let doc_id = snapshot
+        .inv_collection
+        .iter()
+        .find_map(|(doc_id, path)| (*path == doc_path).then_some(doc_id))
+        .context(format!("Document {} is not indexed.", doc_path.display()))?;
+    let settings = Settings::load(repo_dir)?;
+    let analyzer = &settings.analyzer;
+    analyzer.check(repo_dir)?;
+    let search = settings.search_config(profile)?;
+
+    let (lexical, _) = Phrase::parse_all(query);
+    let (lexical, patterns) = split_patterns(&lexical);
+    let lexical = Thesaurus::load(repo_dir, analyzer)?.expand(&lexical, analyzer);
+    let mut terms = analyzer.tokenize_query(&lexical, false);
+    terms.extend(expand_patterns(index, &patterns, false)?);
+
+    let term_scores = score_terms(index, doc_id, &terms, &search);
+    let lexical_score = term_scores.iter().map(|term| term.weighted_score).sum::<f64>();
+    let boosts = DocumentBoosts::new(repo_dir, &search)?
+        .map_or_else(BoostFactors::default, |boosts| boosts.factors(&doc_path));
+    let proximity = match search.proximity_weight > 0.0 {
+        true => DocumentStore::open(repo_dir.join(STORE_DIRNAME))?
+            .read(doc_id)
+            .ok()
+            .map(|text| proximity(&text, &doc_path, &terms, analyzer)),
+        false => None,
+    };
+    let proximity_factor = proximity.map_or(1.0, |proximity| {
+        1.0 + search.proximity_weight * proximity
+    });
+    let n_docs = index.n_docs();
+    let explanation = Explanation {
+        path: doc_path,
+        doc_id,
+        scorer: match search.scorer {
+            Scorer::TfIdf => "tfidf",
+            Scorer::Bm25 => "bm25",
+        },
+        documents: n_docs,
+        document_length: index.n_terms(doc_id),
+        avg_document_length: index.avg_n_terms(),
+        terms: term_scores,
+        lexical_score,
+        boosts,
+        proximity,
+        proximity_factor,
+        score: lexical_score * boosts.product() * proximity_factor,
+    };
+    match output {
+        OutputFormat::Text => display_explanation(&explanation, &search)?,
+        _ => write_records(&[explanation], output)?,
+    }
+    Ok(())
+}
+
+/// Scores a document against each term of a query, the same way as
+/// [`score_documents`](crate::cli::search::score_documents) does, with the
+/// frequencies that the scores are calculated from. The terms are ordered
+/// by their weighted scores, from the highest, and then by the terms.
+fn score_terms(
+    index: &dyn Index,
+    doc_id: DocumentId,
+    query: &Query,
+    search: &SearchConfig,
+) -> Vec<TermScore> {
+    let mut terms = query
+        .iter()
+        .map(|(term, count)| {
+            let score = match search.scorer {
+                Scorer::TfIdf => index.calc_tf_idf(doc_id, term),
+                Scorer::Bm25 => index.calc_bm25(doc_id, term, BM25_K, BM25_B),
+            };
+            let boost = search.boost(term);
+            let (field, text) = match term.chars().next() {
+                Some(TITLE_PREFIX) => ("title", &term[TITLE_PREFIX.len_utf8()..]),
+                Some(CASED_PREFIX) => ("cased", &term[CASED_PREFIX.len_utf8()..]),
+                _ => ("body", term.as_str()),
+            };
+            TermScore {
+                term: text.to_string(),
+                field,
+                count: *count,
+                tf: index.term_frequency(doc_id, term),
+                weighted_tf: index.calc_tf(doc_id, term),
+                df: index.n_docs_containing(term),
+                idf: index.calc_idf(term),
+                score,
+                boost,
+                weighted_score: (*count as f64) * boost * score,
+            }
+        })
+        .collect::<Vec<_>>();
+    terms.sort_by(|a, b| {
+        b.weighted_score
+            .total_cmp(&a.weighted_score)
+            .then_with(|| a.term.cmp(&b.term))
+            .then_with(|| a.field.cmp(b.field))
+    });
+    terms
+}
+
+/// Writes the explanation to stdout, as a table of the scores of the terms,
+/// followed by the weights of the document and the final score.
+fn display_explanation(explanation: &Explanation, search: &SearchConfig) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    writeln!(
+        tw,
+        "Document:\t{} (ID {})",
+        explanation.path.display(),
+        explanation.doc_id
+    )?;
+    writeln!(
+        tw,
+        "Length:\t{} terms (average {:.1} of {} documents)",
+        explanation.document_length, explanation.avg_document_length, explanation.documents
+    )?;
+    writeln!(tw, "Scorer:\t{}", explanation.scorer)?;
+    writeln!(
+        tw,
+        "\nTerm\tField\tCount\tTF\tWeighted TF\tDF\tIDF\tScore\tBoost\tWeighted"
+    )?;
+    for term in &explanation.terms {
+        writeln!(
+            tw,
+            "{}\t{}\t{}\t{}\t{:.4}\t{}\t{:.4}\t{:.4}\t{:.2}\t{:.4}",
+            term.term,
+            term.field,
+            term.count,
+            term.tf,
+            term.weighted_tf,
+            term.df,
+            term.idf,
+            term.score,
+            term.boost,
+            term.weighted_score
+        )?;
+    }
+    writeln!(tw, "\nLexical score:\t{:.4}", explanation.lexical_score)?;
+    let boosts = &explanation.boosts;
+    writeln!(tw, "Recency boost:\t\u{00D7} {:.4}", boosts.recency)?;
+    writeln!(tw, "Path boost:\t\u{00D7} {:.4}", boosts.path)?;
+    writeln!(tw, "Link boost:\t\u{00D7} {:.4}", boosts.links)?;
+    if let Some(proximity) = explanation.proximity {
+        writeln!(
+            tw,
+            "Proximity boost:\t\u{00D7} {:.4} (proximity {proximity:.4}, weight {})",
+            explanation.proximity_factor, search.proximity_weight
+        )?;
+    }
+    writeln!(tw, "Score:\t{:.4}", explanation.score)?;
+    tw.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use crate::cli::search::score_documents;
+
+    use super::*;
+
+    #[test]
+    fn test_score_terms() {
+        let mut index = FrequencyIndex::new();
+        let documents = [
+            (0, vec!["budget", "report", "budget"], vec!["^budget"]),
+            (1, vec!["budget", "meeting", "agenda"], vec!["^notes"]),
+            (2, vec!["meeting", "notes"], vec!["^notes"]),
+        ];
+        for (doc_id, tokens, field_tokens) in documents {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            doc_index.index_field_tokens(field_tokens.into_iter().map(String::from));
+            index.index(doc_index);
+        }
+        let query = Query::from(["budget", "^budget", "agenda"].map(String::from));
+
+        for scorer in [Scorer::TfIdf, Scorer::Bm25] {
+            let search = SearchConfig {
+                scorer,
+                ..SearchConfig::default()
+            };
+            let terms = score_terms(&index, 0, &query, &search);
+            let total = terms.iter().map(|term| term.weighted_score).sum::<f64>();
+            let scores = score_documents(&index, &query, &search, None);
+            assert!((total - scores.get_score(0)).abs() < 1e-9);
+
+            assert_eq!(terms.len(), 3);
+            let budget = terms
+                .iter()
+                .find(|term| term.term == "budget" && term.field == "body")
+                .unwrap();
+            assert_eq!((budget.tf, budget.df), (2, 2));
+            assert_eq!(budget.boost, search.body_boost);
+            let title = terms.iter().find(|term| term.field == "title").unwrap();
+            assert_eq!(title.term, "budget");
+            assert_eq!(title.boost, search.title_boost);
+            // A term that is not in the document adds nothing, and comes last.
+            assert_eq!(terms[2].term, "agenda");
+            assert_eq!(terms[2].weighted_score, 0.0);
+        }
+    }
+}
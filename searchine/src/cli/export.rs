@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use documents::DocumentId;
+use index::inverted::Index;
+use tokenize::{CASED_PREFIX, TITLE_PREFIX};
+
+use crate::cli::ExportFormat;
+use crate::snapshot::Snapshot;
+
+/// The name of the schema of the export, so that tools can tell its records
+/// from the ones of other files.
+const SCHEMA: &str = "searchine-index";
+/// The version of the schema of the export. It changes only if a field of a
+/// record is removed or changes its meaning, as tools can ignore new fields.
+const SCHEMA_VERSION: u32 = 1;
+
+/// A record of the export of an index. Each record is written on a line, as
+/// a JSON object whose `type` names the kind of the record.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record<'a> {
+    /// The first record, which describes the export.
+    Header {
+        schema: &'static str,
+        version: u32,
+        /// The generation of the index that is exported.
+        generation: u64,
+        /// The number of the document records.
+        documents: u32,
+        /// The number of the term records.
+        terms: usize,
+    },
+    /// A document of the index, before the terms.
+    Document {
+        id: DocumentId,
+        /// The absolute path of the file of the document.
+        path: &'a Path,
+        /// The number of terms of the text of the document.
+        length: u32,
+    },
+    /// A term of the index, after the documents, with its postings.
+    Term {
+        /// The term, without the prefix of its field.
+        term: &'a str,
+        /// The field of the term: `body`, `title` or `cased`.
+        field: &'static str,
+        /// The number of documents that contain the term.
+        documents: u32,
+        /// The IDs of the documents that contain the term, in ascending
+        /// order, each with the number of times the term is in it.
+        postings: Vec<(DocumentId, u32)>,
+    },
+}
+
+/// Exports the term dictionary and the postings of the index of a
+/// repository to stdout, in a stable schema, so that other tools can read
+/// the index without knowing how its segments are written.
+///
+/// The records are written as JSON lines: a header with the version of the
+/// schema, then the documents, in the order of their IDs, and the terms,
+/// sorted by their fields and then by their bytes. The index is read from a
+/// snapshot, so the export is of a single generation of the index.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `format`   - The format the index is exported in.
+pub fn invoke(repo_dir: impl AsRef<Path>, format: ExportFormat) -> anyhow::Result<()> {
+    let snapshot = Snapshot::open(repo_dir)?;
+    let paths = snapshot
+        .inv_collection
+        .iter()
+        .filter(|(doc_id, _)| snapshot.index.contains(*doc_id))
+        .map(|(doc_id, path)| (doc_id, path.as_path()))
+        .collect::<BTreeMap<_, _>>();
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    match format {
+        ExportFormat::Jsonl => export_jsonl(
+            snapshot.index.as_ref(),
+            snapshot.generation,
+            &paths,
+            &mut writer,
+        )?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the records of the export of an index as JSON lines, with the
+/// paths of its documents. The postings of each term are read when its
+/// record is written, so the export is never held in memory.
+fn export_jsonl(
+    index: &dyn Index,
+    generation: u64,
+    paths: &BTreeMap<DocumentId, &Path>,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    let mut terms = index.terms();
+    terms.sort_unstable();
+    let header = Record::Header {
+        schema: SCHEMA,
+        version: SCHEMA_VERSION,
+        generation,
+        documents: paths.len() as u32,
+        terms: terms.len(),
+    };
+    write_record(writer, &header)?;
+    for (doc_id, path) in paths {
+        let document = Record::Document {
+            id: *doc_id,
+            path,
+            length: index.n_terms(*doc_id),
+        };
+        write_record(writer, &document)?;
+    }
+    for term in &terms {
+        let (field, name) = match term.chars().next() {
+            Some(TITLE_PREFIX) => ("title", &term[TITLE_PREFIX.len_utf8()..]),
+            Some(CASED_PREFIX) => ("cased", &term[CASED_PREFIX.len_utf8()..]),
+            _ => ("body", term.as_str()),
+        };
+        let mut doc_ids = index.doc_ids_containing(term);
+        doc_ids.sort_unstable();
+        let postings = doc_ids
+            .into_iter()
+            .map(|doc_id| (doc_id, index.term_frequency(doc_id, term)))
+            .collect::<Vec<_>>();
+        let record = Record::Term {
+            term: name,
+            field,
+            documents: postings.len() as u32,
+            postings,
+        };
+        write_record(writer, &record)?;
+    }
+    Ok(())
+}
+
+/// Writes a record on a line.
+fn write_record(writer: &mut impl Write, record: &Record) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_export_jsonl() {
+        let mut index = FrequencyIndex::new();
+        let documents = [
+            (0, vec!["budget", "report", "budget"], vec!["^notes"]),
+            (1, vec!["budget"], vec!["=Budget"]),
+        ];
+        for (doc_id, tokens, field_tokens) in documents {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            doc_index.index_field_tokens(field_tokens.into_iter().map(String::from));
+            index.index(doc_index);
+        }
+        let paths = BTreeMap::from([(0, Path::new("/notes/a.md")), (1, Path::new("/notes/b.md"))]);
+
+        let mut buffer = vec![];
+        export_jsonl(&index, 3, &paths, &mut buffer).unwrap();
+        let records = String::from_utf8(buffer).unwrap();
+        let records = records
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 7);
+        assert_eq!(records[0]["type"], "header");
+        assert_eq!(records[0]["version"], SCHEMA_VERSION);
+        assert_eq!(records[0]["generation"], 3);
+        assert_eq!(records[0]["documents"], 2);
+        assert_eq!(records[0]["terms"], 4);
+        assert_eq!(records[1]["type"], "document");
+        assert_eq!(records[1]["path"], "/notes/a.md");
+        assert_eq!(records[1]["length"], 3);
+        let terms = records[3..].iter().map(|record| {
+            (
+                record["field"].as_str().unwrap(),
+                record["term"].as_str().unwrap(),
+            )
+        });
+        assert_eq!(
+            terms.collect::<Vec<_>>(),
+            [
+                ("cased", "Budget"),
+                ("title", "notes"),
+                ("body", "budget"),
+                ("body", "report")
+            ]
+        );
+        assert_eq!(records[5]["documents"], 2);
+        assert_eq!(
+            records[5]["postings"],
+            serde_json::to_value([[0, 2], [1, 1]]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use url::Url;
+
+use documents::links::parse_hrefs;
+
+use crate::cli::index::index_new_documents;
+use crate::cli::utils::fnv1a;
+use crate::config::{FETCH_MAX_BYTES, FETCH_TIMEOUT_SECS, FETCH_USER_AGENT, WEB_DIRNAME};
+use crate::shutdown;
+
+/// The bounds of a crawl, so that fetching a list of URLs stays small and
+/// polite to the servers.
+#[derive(Debug, Clone, Copy)]
+pub struct CrawlLimits {
+    /// The number of links that are followed from the listed URLs, to pages
+    /// of the same host. With `0`, only the listed URLs are fetched.
+    pub depth: usize,
+    /// The largest number of pages that are fetched.
+    pub max_pages: usize,
+    /// The time that is waited between two requests to the same host.
+    pub delay: Duration,
+}
+
+/// A page that was fetched, with the URL it was fetched from after the
+/// redirects, and the extension of the file it is stored in, so that its
+/// text is extracted like the one of a local file.
+struct Page {
+    url: Url,
+    body: Vec<u8>,
+    extension: &'static str,
+}
+
+/// Fetches the web pages of a list of URLs into the web store of the
+/// repository, and indexes them.
+///
+/// Each page is stored as a separate document, an HTML or a text file, whose
+/// name is derived from its URL, so fetching a URL again replaces its page,
+/// and the new contents are indexed the next time the repository is indexed.
+/// The URL of the page, after the redirects, is stored next to it in a file
+/// with the `url` extension, so the results of a search show the URL of the
+/// page instead of the path of its file. The links of HTML pages to pages of
+/// the same host, resolved against the URLs of the pages after the redirects,
+/// are followed up to the depth of the limits. Pages that cannot be fetched
+/// are skipped printing an error message.
+///
+/// # Arguments
+///
+/// * `repo_dir`  - The path to the searchine repository.
+/// * `urls_path` - The path to the file of URLs, with a URL per line. Empty
+///   lines and lines that start with `#` are skipped.
+/// * `limits`    - The bounds of the crawl.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    urls_path: impl AsRef<Path>,
+    limits: CrawlLimits,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let urls_path = urls_path.as_ref();
+    let contents = std::fs::read_to_string(urls_path)
+        .context(format!("Failed to read URLs from: {}", urls_path.display()))?;
+    let urls = parse_urls(&contents)?;
+
+    let store_dir = repo_dir.join(WEB_DIRNAME);
+    std::fs::create_dir_all(&store_dir)?;
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .user_agent(FETCH_USER_AGENT)
+        .build();
+    let mut seen = urls.iter().cloned().collect::<HashSet<_>>();
+    let mut queue = urls
+        .into_iter()
+        .map(|url| (url, 0))
+        .collect::<VecDeque<_>>();
+    let mut last_requests = HashMap::new();
+    let mut n_fetched = 0;
+    let mut fetched = vec![];
+    while let Some((url, depth)) = queue.pop_front() {
+        if n_fetched >= limits.max_pages || shutdown::requested() {
+            break;
+        }
+        throttle(&mut last_requests, &url, limits.delay);
+        let page = match fetch(&agent, &url) {
+            Ok(page) => page,
+            Err(err) => {
+                eprintln!("ERROR: Skipping {url}: {err:#}");
+                continue;
+            }
+        };
+        n_fetched += 1;
+        if depth < limits.depth && page.extension == "html" {
+            let html = String::from_utf8_lossy(&page.body);
+            for link in page_links(&page.url, &html) {
+                if seen.insert(link.clone()) {
+                    queue.push_back((link, depth + 1));
+                }
+            }
+        }
+
+        let name = format!("page-{:016x}.{}", fnv1a(url.as_str()), page.extension);
+        let path = store_dir.join(name);
+        let is_new = !path.exists();
+        let url_path = path.with_extension("url");
+        std::fs::write(&url_path, format!("{}\n", page.url))
+            .context(format!("Failed to write URL to: {}", url_path.display()))?;
+        std::fs::write(&path, &page.body)
+            .context(format!("Failed to write page to: {}", path.display()))?;
+        if is_new {
+            fetched.push(path.canonicalize()?);
+        }
+    }
+    let n_new = fetched.len();
+    index_new_documents(repo_dir, fetched)?;
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x8C, 0x90]).unwrap_or_default();
+    println_bold!(
+        "{emoji} Fetched {} pages ({} new) from: {}",
+        n_fetched,
+        n_new,
+        urls_path.display()
+    );
+    Ok(())
+}
+
+/// Parses the URLs of a file of URLs, with a URL per line. Empty lines and
+/// lines that start with `#` are skipped.
+///
+/// # Errors
+///
+/// Returns an error if a line is not an `http` or `https` URL.
+fn parse_urls(contents: &str) -> anyhow::Result<Vec<Url>> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let url = Url::parse(line).context(format!("Invalid URL: {line}"))?;
+            if !is_web_url(&url) {
+                anyhow::bail!("Unsupported URL: {line}");
+            }
+            Ok(url)
+        })
+        .collect()
+}
+
+/// Checks if a URL points to a web page, that can be fetched.
+fn is_web_url(url: &Url) -> bool {
+    matches!(url.scheme(), "http" | "https")
+}
+
+/// Waits until the delay since the last request to the host of a URL has
+/// passed, and records the request.
+fn throttle(last_requests: &mut HashMap<String, Instant>, url: &Url, delay: Duration) {
+    let host = url.host_str().unwrap_or_default().to_string();
+    if let Some(last_request) = last_requests.get(&host) {
+        sleep(delay.saturating_sub(last_request.elapsed()));
+    }
+    last_requests.insert(host, Instant::now());
+}
+
+/// Fetches a page, and keeps it if it is HTML or text.
+///
+/// # Errors
+///
+/// Returns an error if the request fails, or if the server responds with an
+/// error status, or with a type of content that is not supported.
+fn fetch(agent: &ureq::Agent, url: &Url) -> anyhow::Result<Page> {
+    let response = match agent.request_url("GET", url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, _)) => anyhow::bail!("Server responded with {status}"),
+        Err(err) => return Err(err.into()),
+    };
+    let final_url = Url::parse(response.get_url()).unwrap_or_else(|_| url.clone());
+    let extension = match response.content_type() {
+        "text/html" | "application/xhtml+xml" => "html",
+        content_type if content_type.starts_with("text/") => "txt",
+        content_type => anyhow::bail!("Unsupported content type: {content_type}"),
+    };
+    let mut body = vec![];
+    response
+        .into_reader()
+        .take(FETCH_MAX_BYTES)
+        .read_to_end(&mut body)?;
+    Ok(Page {
+        url: final_url,
+        body,
+        extension,
+    })
+}
+
+/// Returns the links of an HTML page to other pages of the same host,
+/// resolved against the URL of the page, without their fragments.
+fn page_links(url: &Url, html: &str) -> Vec<Url> {
+    parse_hrefs(html)
+        .iter()
+        .filter_map(|target| url.join(target).ok())
+        .filter(|link| is_web_url(link) && link.host_str() == url.host_str())
+        .map(|mut link| {
+            link.set_fragment(None);
+            link
+        })
+        .filter(|link| link != url)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urls() {
+        let urls =
+            parse_urls("# Docs\nhttps://example.com/guide\n\n  http://example.org  \n").unwrap();
+        assert_eq!(urls.len(), 2);
+        assert_eq!(urls[1].as_str(), "http://example.org/");
+        assert!(parse_urls("ftp://example.com/file").is_err());
+        assert!(parse_urls("example.com").is_err());
+    }
+
+    #[test]
+    fn test_page_links() {
+        let url = Url::parse("https://example.com/docs/guide.html").unwrap();
+        let html = r##"<a href="intro.html#start">Intro</a> <a href="/faq">FAQ</a>
+<a href="#top">Top</a> <a href="https://other.com/">Other</a> <a href="mailto:a@example.com">Mail</a>"##;
+        let links = page_links(&url, html);
+        assert_eq!(
+            links.iter().map(Url::as_str).collect::<Vec<_>>(),
+            [
+                "https://example.com/docs/intro.html",
+                "https://example.com/faq"
+            ]
+        );
+    }
+}
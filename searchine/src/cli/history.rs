@@ -0,0 +1,164 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::cli::utils::{format_age, write_records};
+use crate::cli::OutputFormat;
+use crate::history::{self, HistoryEntry, SavedSearches};
+use crate::scheduler::unix_time;
+
+/// A query of the history, with its number, as it is written in the JSON
+/// output.
+#[derive(Debug, PartialEq, Serialize)]
+struct NumberedEntry<'a> {
+    /// The number of the query, counting back from the most recent, which
+    /// is 1.
+    number: usize,
+    #[serde(flatten)]
+    entry: &'a HistoryEntry,
+}
+
+/// A saved search, as it is written in the JSON output.
+#[derive(Debug, PartialEq, Serialize)]
+struct SavedSearch<'a> {
+    name: &'a str,
+    query: &'a str,
+}
+
+/// Lists the most recent queries of the history of a repository, from the
+/// most recent, numbered so that they can be run again with
+/// `searchine history --run <number>`.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `limit`    - The number of queries to list.
+/// * `output`   - The format the queries are written in.
+pub fn list(repo_dir: impl AsRef<Path>, limit: usize, output: OutputFormat) -> anyhow::Result<()> {
+    let entries = history::load(repo_dir)?;
+    let entries = numbered(&entries, limit);
+    match output {
+        OutputFormat::Text => display_entries(&entries)?,
+        _ => write_records(&entries, output)?,
+    }
+    Ok(())
+}
+
+/// Returns the query of the history of a repository with a number, counting
+/// back from the most recent, which is 1, if there is one.
+pub fn query(repo_dir: impl AsRef<Path>, number: usize) -> anyhow::Result<Option<String>> {
+    let entries = history::load(repo_dir)?;
+    let i = entries.len().checked_sub(number).filter(|_| number > 0);
+    Ok(i.map(|i| entries[i].query.clone()))
+}
+
+/// Saves a query by name, replacing the query that was saved by the same
+/// name, if any.
+pub fn save(repo_dir: impl AsRef<Path>, name: &str, query: &str) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let mut saved = SavedSearches::load(repo_dir)?;
+    let replaced = saved.queries.insert(name.to_string(), query.to_string());
+    saved.save(repo_dir)?;
+    match replaced {
+        Some(_) => println_bold!("Replaced saved search: {name}"),
+        None => println_bold!("Saved search: {name}"),
+    }
+    Ok(())
+}
+
+/// Removes a saved search by name.
+pub fn remove(repo_dir: impl AsRef<Path>, name: &str) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let mut saved = SavedSearches::load(repo_dir)?;
+    if saved.queries.remove(name).is_none() {
+        println_bold!("No search is saved as: {name}");
+        return Ok(());
+    }
+    saved.save(repo_dir)?;
+    println_bold!("Removed saved search: {name}");
+    Ok(())
+}
+
+/// Lists the saved searches of a repository, by name.
+pub fn list_saved(repo_dir: impl AsRef<Path>, output: OutputFormat) -> anyhow::Result<()> {
+    let saved = SavedSearches::load(repo_dir)?;
+    let searches = saved
+        .queries
+        .iter()
+        .map(|(name, query)| SavedSearch { name, query })
+        .collect::<Vec<_>>();
+    match output {
+        OutputFormat::Text => display_saved(&searches)?,
+        _ => write_records(&searches, output)?,
+    }
+    Ok(())
+}
+
+/// Numbers the `limit` most recent queries of a history, from the most
+/// recent, which is 1.
+fn numbered(entries: &[HistoryEntry], limit: usize) -> Vec<NumberedEntry<'_>> {
+    entries
+        .iter()
+        .rev()
+        .take(limit)
+        .enumerate()
+        .map(|(i, entry)| NumberedEntry {
+            number: i + 1,
+            entry,
+        })
+        .collect()
+}
+
+/// Writes the queries to stdout as a table, with their numbers, their ages
+/// and their numbers of results.
+fn display_entries(entries: &[NumberedEntry]) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    let now = unix_time();
+    for NumberedEntry { number, entry } in entries {
+        let results = match entry.hits {
+            1 => "result",
+            _ => "results",
+        };
+        writeln!(
+            tw,
+            "{number}\t{}\t{} {results}\t{}",
+            format_age(now.saturating_sub(entry.time)),
+            entry.hits,
+            entry.query
+        )?;
+    }
+    tw.flush()
+}
+
+/// Writes the saved searches to stdout as a table.
+fn display_saved(searches: &[SavedSearch]) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    for search in searches {
+        writeln!(tw, "{}\t{}", search.name, search.query)?;
+    }
+    tw.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbered() {
+        let entries = ["budget", "agenda", "minutes"].map(|query| HistoryEntry {
+            time: 0,
+            query: query.to_string(),
+            hits: 1,
+        });
+        let numbered = numbered(&entries, 2)
+            .into_iter()
+            .map(|numbered| (numbered.number, numbered.entry.query.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(numbered, [(1, "minutes"), (2, "agenda")]);
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use documents::read_bookmarks;
+
+use crate::cli::index::index_new_documents;
+use crate::cli::utils::fnv1a;
+use crate::config::BOOKMARKS_DIRNAME;
+
+/// Imports the bookmarks of a browser bookmarks export into the bookmarks
+/// store of the repository, and indexes them.
+///
+/// Each bookmark is stored as a separate document that contains its title
+/// and its URL, so web references can be found alongside local documents.
+/// The name of the document is derived from the URL, so bookmarks that have
+/// already been imported are skipped.
+///
+/// # Arguments
+///
+/// * `repo_dir`    - The path to the searchine repository.
+/// * `export_path` - The path to the bookmarks export.
+pub fn bookmarks(repo_dir: impl AsRef<Path>, export_path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let export_path = export_path.as_ref();
+    let bookmarks = read_bookmarks(export_path).context(format!(
+        "Failed to read bookmarks from: {}",
+        export_path.display()
+    ))?;
+
+    let store_dir = repo_dir.join(BOOKMARKS_DIRNAME);
+    std::fs::create_dir_all(&store_dir)?;
+    let mut imported = vec![];
+    for bookmark in &bookmarks {
+        let path = store_dir.join(format!("bookmark-{:016x}.txt", fnv1a(bookmark.url())));
+        if path.exists() {
+            continue;
+        }
+        std::fs::write(&path, format!("{}\n{}\n", bookmark.title(), bookmark.url()))
+            .context(format!("Failed to write bookmark to: {}", path.display()))?;
+        imported.push(path.canonicalize()?);
+    }
+    let n_imported = imported.len();
+    index_new_documents(repo_dir, imported)?;
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x94, 0x96]).unwrap_or_default();
+    println_bold!(
+        "{emoji} Imported {} bookmarks ({} already imported) from: {}",
+        n_imported,
+        bookmarks.len() - n_imported,
+        export_path.display()
+    );
+    Ok(())
+}
@@ -1,40 +1,194 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{Receiver, sync_channel};
 use std::thread::{JoinHandle, spawn};
 
 use anyhow::Context;
+use indicatif::ProgressBar;
+use serde::Deserialize;
 
-use documents::{Document, DocumentId};
+use documents::table::TableReader;
+use documents::{Document, DocumentId, Extraction};
 use index::collection::*;
+use index::compress::Compression;
 use index::doc::freq::DocumentFrequencyIndex;
+use index::fingerprint::Fingerprint;
+use index::inverted::builder::IndexBuilder;
 use index::inverted::freq::FrequencyIndex;
-use tokenize::{Token, Tokenizer};
+use index::positions::PositionMap;
+use index::postings::PostingsOrder;
+use index::score::TfWeighting;
+use index::store::DocumentStore;
+use tokenize::Token;
 
-use crate::config::{CHANNEL_BOUND, INDEX_FILENAME};
-use crate::fs::Directory;
+use crate::analyzer::AnalyzerConfig;
+use crate::audit::{self, AuditEvent};
+use crate::cli::utils::{
+    content_hash, fetch_modified_files, fetch_new_files, fetch_removed_files, index_format,
+    is_indexed, merge_small_segments, update_index, write_index,
+};
+use crate::cli::related::update_related;
+use crate::cli::semantic::update_vectors;
+use crate::cli::IndexFormat;
+use crate::config::{
+    CHANNEL_BOUND, COLLECTION_FILENAME, QUANTIZED_VECTORS_FILENAME, RELATED_FILENAME, RUNS_DIRNAME,
+    STAGING_STORE_DIRNAME, STORE_DIRNAME, VECTORS_FILENAME,
+};
+use crate::fs::{Directory, ExtractorCommands, RowFiles};
+use crate::importance::update_importance;
+use crate::path::corpus_dir;
+use crate::progress;
+use crate::settings::Settings;
+use crate::shutdown;
 
-type TokenizedDocument = (DocumentId, Vec<Token>);
+/// A tokenized document: its ID, its terms, and the terms of its other
+/// fields, its title and its case-sensitive field.
+type TokenizedDocument = (DocumentId, Vec<Token>, Vec<Token>);
 
-/// Part of a pipeline that loads documents. Each document in the collection is loaded
-/// and sent over a channel for tokenizing.
+/// The configuration of the extraction of the text of documents, read from
+/// the `[extraction]` section of the config of a repository.
+///
+/// ```toml
+/// [extraction]
+/// redact_secrets = true
+///
+/// [extraction.rows]
+/// "exports/*.csv" = ["name", "notes"]
+///
+/// [extraction.commands]
+/// "*.djvu" = "djvutxt {path}"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ExtractionConfig {
+    /// Whether likely secrets, like API keys and private keys, are redacted
+    /// from the text of documents before it is stored and indexed, so that
+    /// they cannot be found by searching. It is off by default.
+    pub redact_secrets: bool,
+    /// The globs of the CSV and TSV files whose rows are documents of their
+    /// own, relative to the root directories of the corpus, with the columns
+    /// of their rows that are indexed, or an empty list for all of them.
+    /// Other tables are documents as a whole.
+    pub rows: BTreeMap<String, Vec<String>>,
+    /// The globs of the files whose text is extracted by external commands,
+    /// relative to the root directories of the corpus, with the commands,
+    /// whose standard output is the text. The path to the file is passed in
+    /// place of `{path}`, or as the last argument.
+    pub commands: BTreeMap<String, String>,
+}
+
+impl ExtractionConfig {
+    /// Loads a document from file, with its external command if it has one,
+    /// or from the row of a table with the columns of the rows of the table,
+    /// and redacts its secrets if configured. The rows of a table are read
+    /// with a reader that keeps it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`index::Error::ExtractionFailed`] error if the text of
+    /// the document cannot be extracted from its file.
+    pub(crate) fn load(
+        &self,
+        doc_id: DocumentId,
+        path: impl AsRef<Path>,
+        rows: &RowFiles,
+        commands: &ExtractorCommands,
+        tables: &mut TableReader,
+    ) -> anyhow::Result<Document> {
+        let path = path.as_ref();
+        let document = match (rows.row_columns(path), commands.command(path)) {
+            (Some(columns), _) => Document::from_row(doc_id, path, columns, tables),
+            (None, Some(command)) => command
+                .extract(path)
+                .map(|text| Document::from_extraction(doc_id, path, Extraction::new(text))),
+            (None, None) => Document::from_file(doc_id, path),
+        };
+        let mut document = document.map_err(|err| index::Error::ExtractionFailed {
+            path: path.to_path_buf(),
+            source: err.into(),
+        })?;
+        if self.redact_secrets {
+            document.redact_secrets();
+        }
+        Ok(document)
+    }
+}
+
+/// The configuration of the index, read from the `[index]` section of the
+/// config of a repository.
+///
+/// The weighting of the term frequencies, the compression of the files and
+/// the order of the postings are kept in the index when it is built, so
+/// changing them takes effect once the repository is indexed again with
+/// `searchine index --full`.
+///
+/// ```toml
+/// [index]
+/// tf_weighting = "log"
+/// compression = "zstd"
+/// postings_order = "impact"
+/// memory_budget_mb = 2048
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// The way the number of times a term appears in a document is weighted
+    /// when documents are scored with TF-IDF: `"raw"`, `"log"` or
+    /// `"normalized"`. It is normalized by the length of the document by
+    /// default.
+    pub tf_weighting: TfWeighting,
+    /// The compression of the collection and the segments of the index:
+    /// `"none"` or `"zstd"`. Compressed segments are much smaller, but they
+    /// are loaded as a whole instead of being memory-mapped. They are not
+    /// compressed by default.
+    pub compression: Compression,
+    /// The order of the postings of the uncompressed binary segments of the
+    /// index: `"doc-id"` or `"impact"`. Postings that are ordered by impact
+    /// let the top results of a query be found from the first blocks of the
+    /// postings of its terms. They are ordered by document ID by default.
+    pub postings_order: PostingsOrder,
+    /// The memory in megabytes that the postings of the index can take while
+    /// the repository is indexed from scratch. Once they take more, they are
+    /// spilled to a sorted run on disk, and the runs are merged into the
+    /// index at the end. The index is built in memory by default.
+    pub memory_budget_mb: Option<usize>,
+}
+
+/// Part of a pipeline that loads documents. Each document in the collection
+/// is loaded, its text is kept in the document store, and it is sent over a
+/// channel for tokenizing.
 fn load_docs<I>(
     paths: I,
     collection: Collection,
+    store: DocumentStore,
+    extraction: ExtractionConfig,
+    rows: RowFiles,
+    commands: ExtractorCommands,
 ) -> (Receiver<Document>, JoinHandle<anyhow::Result<()>>)
 where
     I: IntoIterator<Item=PathBuf> + Send + 'static,
 {
     let (sender, receiver) = sync_channel(CHANNEL_BOUND);
     let handle = spawn(move || {
+        let mut tables = TableReader::new();
         for path in paths {
+            // The documents that are loaded are dropped if the process is
+            // asked to stop, so there is no point in loading the rest.
+            if shutdown::requested() {
+                break;
+            }
             // For each path, we map it to a document id with the collection index,
             // and load the document from file.
             let doc_id = collection
                 .get_document_id(&path)
                 .context(format!("Document {} is not in collection.", path.display()))?;
             // println!("Loading document: {}", path.display());
-            let document = Document::from_file(doc_id, &path)?;
+            let document = extraction.load(doc_id, &path, &rows, &commands, &mut tables)?;
+            store.write(doc_id, document.page_content())?;
+            store.write_links(doc_id, document.links())?;
+            store.write_fingerprint(doc_id, Fingerprint::of_text(document.page_content()))?;
+            store.write_content_hash(doc_id, &content_hash(document.page_content()))?;
 
             if sender.send(document).is_err() {
                 eprintln!("Failed to read from {}", path.display());
@@ -46,22 +200,24 @@ where
 }
 
 /// Part of a pipeline that tokenizes the contents of a document. Each received
-/// document is broken into tokens. The document ID and the stream of tokens are
-/// sent over a channel to a thread that indexes the documents.
+/// document is broken into tokens, and the map of their positions is kept in the
+/// document store. The document ID and the stream of tokens are sent over a
+/// channel to a thread that indexes the documents.
 fn tokenize_content(
     document_receiver: Receiver<Document>,
+    store: DocumentStore,
+    analyzer: AnalyzerConfig,
 ) -> (
     Receiver<TokenizedDocument>,
     JoinHandle<anyhow::Result<()>>,
 ) {
     let (sender, receiver) = sync_channel(CHANNEL_BOUND);
-    let mut tokenizer = Tokenizer::default();
+    let mut tokenizer = analyzer.tokenizer();
     let handle = spawn(move || {
         for document in document_receiver {
-            let tokens = (
-                document.doc_id(),
-                tokenizer.tokenize(document.page_content()),
-            );
+            let (spans, field_tokens) = analyzer.tokenize_document(&mut tokenizer, &document);
+            let tokens = store_positions(&store, &document, spans)?;
+            let tokens = (document.doc_id(), tokens, field_tokens);
             if sender.send(tokens).is_err() {
                 eprintln!("Failed to tokenize document {}", document.doc_id());
             }
@@ -71,21 +227,35 @@ fn tokenize_content(
     (receiver, handle)
 }
 
+/// Keeps the map of the positions of the terms of a document in the document
+/// store, and returns the terms.
+fn store_positions(
+    store: &DocumentStore,
+    document: &Document,
+    spans: Vec<(Token, Range<usize>)>,
+) -> index::error::Result<Vec<Token>> {
+    let text = document.page_content();
+    let positions = PositionMap::new(text, spans.iter().map(|(_, span)| span.clone()));
+    store.write_positions(document.doc_id(), &positions)?;
+    Ok(spans.into_iter().map(|(token, _)| token).collect())
+}
+
 /// Part of a pipeline that creates an index for each document. The received
 /// pair of document ID and token stream are turned into an index. The resulting
 /// document indices are sent over a channel and joint in the main thread that
 /// merges them into an overall index.
 fn index_documents(
-    tokens_receiver: Receiver<(u32, Vec<Token>)>,
+    tokens_receiver: Receiver<TokenizedDocument>,
 ) -> (
     Receiver<DocumentFrequencyIndex>,
     JoinHandle<anyhow::Result<()>>,
 ) {
     let (sender, receiver) = sync_channel(CHANNEL_BOUND);
     let handle = spawn(move || {
-        for (doc_id, tokens) in tokens_receiver {
+        for (doc_id, tokens, field_tokens) in tokens_receiver {
             let mut doc_index = DocumentFrequencyIndex::new(doc_id);
             doc_index.index_tokens(tokens);
+            doc_index.index_field_tokens(field_tokens);
 
             if sender.send(doc_index).is_err() {
                 eprintln!("Failed to send index {}", doc_id)
@@ -96,35 +266,449 @@ fn index_documents(
     (receiver, handle)
 }
 
-/// Indexes a directory of documents with a pipeline.
-pub fn invoke_par(repo_dir: impl AsRef<Path>, verbose: bool) -> anyhow::Result<()> {
-    // Get all paths
-    let repo_dir = repo_dir.as_ref();
-    let dir_path = repo_dir
-        .parent()
-        .context(format!("Failed to get parent for: {}", repo_dir.display()))?;
-    let dir = Directory::new(dir_path)?;
-    let dir = dir.iter_full_paths(verbose).collect::<BTreeSet<_>>();
-
-    // This is indexing collection from the scratch?
-    let collection = Collection::from_paths(dir.clone())?;
-
-    let (doc_rec, h1) = load_docs(dir, collection);
-    let (token_rec, h2) = tokenize_content(doc_rec);
+/// Runs the pipeline that loads, tokenizes and indexes the documents with
+/// the specified paths, and adds them to the index. The documents have to be
+/// in the collection already. The progress bar advances as each document is
+/// added, and it is cleared once they all are.
+pub fn index_paths<I>(
+    index: &mut IndexBuilder,
+    paths: I,
+    collection: Collection,
+    store: DocumentStore,
+    settings: &Settings,
+    progress: &ProgressBar,
+) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = PathBuf> + Send + 'static,
+{
+    let roots = collection.roots().to_vec();
+    let rows = RowFiles::new(roots.clone(), &settings.extraction.rows)?;
+    let commands = ExtractorCommands::new(roots, &settings.extraction.commands)?;
+    let extraction = settings.extraction.clone();
+    let (doc_rec, h1) = load_docs(paths, collection, store.clone(), extraction, rows, commands);
+    let (token_rec, h2) = tokenize_content(doc_rec, store, settings.analyzer.clone());
     let (doc_index_rec, h3) = index_documents(token_rec);
-    let mut index = FrequencyIndex::new();
     for doc_index in doc_index_rec {
-        index.index(doc_index);
+        index.index(doc_index)?;
+        progress.inc(1);
     }
+    progress.finish_and_clear();
 
     let _r1 = h1.join().unwrap()?;
     let _r2 = h2.join().unwrap()?;
     let _r3 = h3.join().unwrap()?;
+    Ok(())
+}
 
-    // Build index and store it to file.
-    index.into_file(repo_dir.join(INDEX_FILENAME))?;
+/// Indexes a directory of documents with a pipeline.
+///
+/// If the repository has already been indexed, the index is updated
+/// incrementally, unless `full` is `true`, in which case the collection,
+/// the document store and the index are rebuilt from scratch.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `verbose`  - `true` if the progress of indexing, and the files that are
+///   skipped, should be shown.
+/// * `full`     - `true` if the index should be rebuilt from scratch.
+/// * `format`   - The format in which the index is written.
+pub fn invoke_par(
+    repo_dir: impl AsRef<Path>,
+    verbose: bool,
+    full: bool,
+    format: IndexFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let dir_path = corpus_dir(repo_dir)?;
+    let (roots, dir) = list_documents(repo_dir, verbose)?;
+
+    let collection_path = repo_dir.join(COLLECTION_FILENAME);
+    if !full && collection_path.exists() && is_indexed(repo_dir) {
+        let changes = update(repo_dir, roots, dir, format, verbose)?;
+        if changes.is_empty() {
+            let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+            println_bold!("{emoji} No changes detected. Index is up to date.");
+        } else {
+            let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
+            println_bold!(
+                "{emoji} Updated index for: {} ({})",
+                dir_path.display(),
+                changes.summary()
+            );
+        }
+        return Ok(());
+    }
+
+    build(repo_dir, roots, dir, format, verbose)?;
     let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
     println_bold!("{emoji} Created index for: {}", dir_path.display());
 
     Ok(())
 }
+
+/// Lists the documents of the corpus of a repository, along with the root
+/// directories of the corpus.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `verbose`  - `true` if the progress of listing the files, and the files
+///   that are skipped, should be shown.
+pub fn list_documents(
+    repo_dir: &Path,
+    verbose: bool,
+) -> anyhow::Result<(Vec<PathBuf>, BTreeSet<PathBuf>)> {
+    let directory = Directory::for_repo(repo_dir)?;
+    let spinner = progress::files_spinner(verbose);
+    let dir = directory
+        .iter_full_paths(verbose)
+        .inspect(|_| spinner.inc(1))
+        .collect::<BTreeSet<_>>();
+    spinner.finish_and_clear();
+    Ok((directory.roots(), dir))
+}
+
+/// Builds the collection, the document store and the index of a repository
+/// from scratch, from the documents of its corpus. The documents that were
+/// already in the collection keep their tags.
+///
+/// A new document store is built next to the one in use, and the new files
+/// replace the old ones only once all the documents are indexed, so if the
+/// process is asked to stop before then, the new store is removed, and the
+/// repository is left as it was.
+pub fn build(
+    repo_dir: &Path,
+    roots: Vec<PathBuf>,
+    dir: BTreeSet<PathBuf>,
+    format: IndexFormat,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let mut collection = Collection::from_paths(dir.clone())?;
+    collection.set_roots(roots);
+    if let Ok(previous) = Collection::from_file(repo_dir.join(COLLECTION_FILENAME)) {
+        collection.keep_tags(&previous);
+    }
+    let staging_dir = repo_dir.join(STAGING_STORE_DIRNAME);
+    let store = DocumentStore::create(&staging_dir)?;
+    let settings = Settings::load(repo_dir)?;
+
+    let event = AuditEvent::Index {
+        added: dir.iter().map(PathBuf::as_path).collect(),
+        modified: vec![],
+        removed: vec![],
+    };
+    audit::record(repo_dir, &event)?;
+
+    let IndexConfig {
+        tf_weighting,
+        compression,
+        postings_order,
+        memory_budget_mb,
+    } = settings.index;
+    let mut builder = index_builder(repo_dir, memory_budget_mb);
+    let progress = progress::documents_bar(dir.len() as u64, verbose);
+    index_paths(
+        &mut builder,
+        dir,
+        collection.clone(),
+        store.clone(),
+        &settings,
+        &progress,
+    )?;
+    if let Err(err) = shutdown::check() {
+        std::fs::remove_dir_all(&staging_dir).context(format!(
+            "Failed to remove document store: {}",
+            staging_dir.display()
+        ))?;
+        return Err(err);
+    }
+
+    // Build index and store it to file.
+    write_index(
+        repo_dir,
+        builder,
+        format,
+        tf_weighting,
+        compression,
+        postings_order,
+    )?;
+    collection.set_compression(compression);
+    collection.into_file(repo_dir.join(COLLECTION_FILENAME))?;
+    store.replace(repo_dir.join(STORE_DIRNAME))?;
+    settings.analyzer.save(repo_dir)?;
+    // Document IDs are assigned again, so the semantic index is built again.
+    for filename in [VECTORS_FILENAME, QUANTIZED_VECTORS_FILENAME] {
+        let vectors_path = repo_dir.join(filename);
+        if vectors_path.exists() {
+            std::fs::remove_file(vectors_path)?;
+        }
+    }
+    update_vectors(repo_dir, &[], &[]);
+    update_related(repo_dir);
+    update_importance(repo_dir);
+    Ok(())
+}
+
+/// The changes of the documents in a directory since they were indexed.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub new: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl Changes {
+    /// Finds the changes of the documents in a directory, by comparing their
+    /// paths and last modified times against the ones in the collection.
+    pub fn from_dir(collection: &Collection, dir: &[PathBuf]) -> anyhow::Result<Self> {
+        Ok(Self {
+            new: fetch_new_files(collection, dir),
+            modified: fetch_modified_files(collection, dir)?,
+            removed: fetch_removed_files(collection, dir),
+        })
+    }
+
+    /// Returns true if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.new.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    /// Returns a short summary of the changes, for display.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} added, {} modified, {} removed",
+            self.new.len(),
+            self.modified.len(),
+            self.removed.len()
+        )
+    }
+}
+
+/// Updates the index of a repository incrementally, and returns the
+/// changes that were applied.
+///
+/// The last modified times of the documents in the directory are compared
+/// against the ones stored in the collection, and only the changes are
+/// applied to the index. The paths of the documents are written relative to
+/// the current root directories of the corpus.
+pub fn update(
+    repo_dir: &Path,
+    roots: Vec<PathBuf>,
+    dir: BTreeSet<PathBuf>,
+    format: IndexFormat,
+    verbose: bool,
+) -> anyhow::Result<Changes> {
+    let mut collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    collection.set_roots(roots);
+    let dir = dir.into_iter().collect::<Vec<_>>();
+    let changes = Changes::from_dir(&collection, &dir)?;
+    if changes.is_empty() {
+        // The semantic index may still be missing, or be of another model,
+        // and so may the co-occurrence sketch of an index that was built
+        // before it existed.
+        update_vectors(repo_dir, &[], &[]);
+        if !repo_dir.join(RELATED_FILENAME).exists() {
+            update_related(repo_dir);
+        }
+        return Ok(changes);
+    }
+
+    apply_changes(repo_dir, collection, &changes, format, verbose)?;
+    merge_small_segments(repo_dir, format)?;
+    Ok(changes)
+}
+
+/// Applies changes of the documents to the collection, the document store
+/// and the index of a repository.
+///
+/// Only new and modified documents are tokenized and indexed, into a new
+/// segment of the index, while modified and removed documents are removed
+/// from the segments they were in. Modified documents keep their document
+/// IDs. The segments are not merged, so that the caller can merge them now,
+/// or leave them to be merged in the background.
+///
+/// If the process is asked to stop while the documents are indexed, the
+/// index and the collection are left unchanged, so the same changes are
+/// found and applied the next time.
+///
+/// # Arguments
+///
+/// * `repo_dir`   - The path to the searchine repository.
+/// * `collection` - The collection of the repository, as it was indexed.
+/// * `changes`    - The changes of the documents since they were indexed.
+/// * `format`     - The format in which the new segment is written.
+/// * `verbose`    - `true` if the progress of indexing should be shown.
+pub fn apply_changes(
+    repo_dir: impl AsRef<Path>,
+    mut collection: Collection,
+    changes: &Changes,
+    format: IndexFormat,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let settings = Settings::load(repo_dir)?;
+    settings.analyzer.check(repo_dir)?;
+    let event = AuditEvent::Index {
+        added: changes.new.iter().map(PathBuf::as_path).collect(),
+        modified: changes.modified.iter().map(PathBuf::as_path).collect(),
+        removed: changes.removed.iter().map(PathBuf::as_path).collect(),
+    };
+    audit::record(repo_dir, &event)?;
+
+    let mut removed = vec![];
+    for path in &changes.new {
+        collection.insert(path.clone())?;
+    }
+    for path in &changes.modified {
+        let doc_id = collection
+            .get_document_id(path)
+            .context(format!("Document {} is not in collection.", path.display()))?;
+        removed.push(doc_id);
+        collection.update_modified(path)?;
+    }
+    let mut deleted = vec![];
+    for path in &changes.removed {
+        if let Some(entry) = collection.remove(path) {
+            removed.push(entry.document_id());
+            deleted.push(entry.document_id());
+        }
+    }
+
+    let paths = changes
+        .new
+        .iter()
+        .chain(&changes.modified)
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut builder = index_builder(repo_dir, settings.index.memory_budget_mb);
+    let progress = progress::documents_bar(paths.len() as u64, verbose);
+    index_paths(
+        &mut builder,
+        paths.clone(),
+        collection.clone(),
+        store.clone(),
+        &settings,
+        &progress,
+    )?;
+    shutdown::check()?;
+
+    update_index(repo_dir, builder.into_index()?, &removed, format)?;
+    let added = paths
+        .iter()
+        .filter_map(|path| collection.get_document_id(path))
+        .collect::<Vec<_>>();
+    collection.into_file(repo_dir.join(COLLECTION_FILENAME))?;
+    for doc_id in deleted {
+        store.remove(doc_id)?;
+    }
+    update_vectors(repo_dir, &added, &removed);
+    update_related(repo_dir);
+    update_importance(repo_dir);
+    Ok(())
+}
+
+/// Creates the builder of an index. With a memory budget, in megabytes, the
+/// postings are spilled to runs in the repository once it is exceeded.
+fn index_builder(repo_dir: &Path, memory_budget_mb: Option<usize>) -> IndexBuilder {
+    let builder = IndexBuilder::new();
+    match memory_budget_mb {
+        Some(budget_mb) => {
+            builder.with_memory_budget(budget_mb * 1024 * 1024, repo_dir.join(RUNS_DIRNAME))
+        }
+        None => builder,
+    }
+}
+
+/// Adds new documents to the collection and the inverted index of a
+/// repository, as a new segment, so they are searchable without re-indexing
+/// it.
+///
+/// If the collection has not been created yet, nothing is done, as the
+/// documents are picked up the next time the repository is indexed. The
+/// same goes for the inverted index.
+pub fn index_new_documents(
+    repo_dir: impl AsRef<Path>,
+    paths: impl IntoIterator<Item = PathBuf>,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let collection_path = repo_dir.join(COLLECTION_FILENAME);
+    if !collection_path.exists() {
+        return Ok(());
+    }
+    let mut collection = Collection::from_file(&collection_path)?;
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let settings = Settings::load(repo_dir)?;
+    let mut index = is_indexed(repo_dir).then(FrequencyIndex::new);
+    if index.is_some() {
+        settings.analyzer.check(repo_dir)?;
+    }
+
+    let mut added = vec![];
+    let mut added_paths = vec![];
+    for path in paths {
+        if collection.contains_path(&path) {
+            continue;
+        }
+        collection.insert(path.clone())?;
+        added_paths.push(path.clone());
+        if let Some(index) = index.as_mut() {
+            let doc_id = collection
+                .get_document_id(&path)
+                .context(format!("Document {} is not in collection.", path.display()))?;
+            index.index(index_document(doc_id, &path, &store, &settings)?);
+            added.push(doc_id);
+        }
+    }
+
+    let event = AuditEvent::Index {
+        added: added_paths.iter().map(PathBuf::as_path).collect(),
+        modified: vec![],
+        removed: vec![],
+    };
+    audit::record(repo_dir, &event)?;
+
+    let indexed = index.is_some();
+    if let Some(index) = index {
+        let format = index_format(repo_dir);
+        update_index(repo_dir, index, &[], format)?;
+        merge_small_segments(repo_dir, format)?;
+    }
+    collection.into_file(&collection_path)?;
+    if indexed {
+        update_vectors(repo_dir, &added, &[]);
+        update_related(repo_dir);
+        update_importance(repo_dir);
+    }
+    Ok(())
+}
+
+/// Loads, tokenizes and indexes a single document outside the pipeline,
+/// keeping its text and the map of its positions in the document store.
+///
+/// This is used when a single document has to be added to an existing
+/// index, where setting up the pipeline is not worth it.
+pub fn index_document(
+    doc_id: DocumentId,
+    path: impl AsRef<Path>,
+    store: &DocumentStore,
+    settings: &Settings,
+) -> anyhow::Result<DocumentFrequencyIndex> {
+    let (rows, commands) = (RowFiles::default(), ExtractorCommands::default());
+    let mut tables = TableReader::new();
+    let document = settings
+        .extraction
+        .load(doc_id, path, &rows, &commands, &mut tables)?;
+    store.write(doc_id, document.page_content())?;
+    store.write_links(doc_id, document.links())?;
+    store.write_fingerprint(doc_id, Fingerprint::of_text(document.page_content()))?;
+    store.write_content_hash(doc_id, &content_hash(document.page_content()))?;
+    let analyzer = &settings.analyzer;
+    let (spans, field_tokens) = analyzer.tokenize_document(&mut analyzer.tokenizer(), &document);
+    let tokens = store_positions(store, &document, spans)?;
+    let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+    doc_index.index_tokens(tokens);
+    doc_index.index_field_tokens(field_tokens);
+    Ok(doc_index)
+}
@@ -1,20 +1,164 @@
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::config::SEARCHINE_PATH;
+use crate::config::{CONFIG_FILENAME, SEARCHINE_PATH};
 
-/// Initializes a new searchine index repo.
+/// The config file that a new repository is created with. All the settings
+/// have their default values, so it only documents what can be configured.
+const DEFAULT_CONFIG: &str = r#"# The settings of the searchine repository. Settings that are missing take
+# their default values.
+
+# How the text of documents and queries is split into terms. Changing the
+# analyzer requires running `searchine index --full`.
+[analyzer]
+# The language of the stemmer, or "none" to keep the terms as they are.
+stemmer = "english"
+# The terms that are never indexed or searched for.
+stopwords = []
+# "auto" tokenizes source code as code and any other document as text, and
+# "text" or "code" tokenize every document the same way.
+tokenizer = "auto"
+# The extensions of the files that "auto" tokenizes as code, instead of the
+# ones of the source code that is supported out of the box.
+# code_extensions = ["rs", "py", "sql"]
+# "unicode" segments text into words by the word boundaries of Unicode, which
+# keeps words like "don't" whole, and "simple" splits it at every character
+# that is not alphanumeric.
+segmentation = "unicode"
+# Whether words joined by hyphens or underscores, like "state-of-the-art", are
+# indexed "both" as a whole and as their words, as a "whole", or "split".
+compounds = "both"
+# Whether the possessive 's of words is stripped.
+strip_possessives = false
+# Whether terms that are numbers, like "42" or "3.14", are dropped.
+drop_numbers = false
+# Whether identifiers are also indexed in their original case, for
+# `searchine search --case-sensitive`.
+cased_identifiers = false
+# Whether the terms of every document are also indexed in their original case,
+# so that `searchine search --case-sensitive` tells `FooBar` from `foobar` in
+# any text, and not only in source code.
+cased_terms = false
+
+# The files of the directory that are indexed, with globs like the ones of a
+# .gitignore file. If there are include globs, only the files that match one
+# of them are indexed.
+[filter]
+include = []
+exclude = []
+
+# How symbolic links are handled when the directory is walked: "follow" indexes
+# a file at every path it is linked at, "skip" ignores links, and
+# "index-target-once" indexes each linked file once, at its real path.
+[walk]
+symlinks = "index-target-once"
+# The size in bytes above which files are not indexed. Files of any size are
+# indexed if it is not set.
+# max_file_size = 10485760
+
+# How the TF-IDF scorer weights the number of times a term appears in a
+# document: "raw" counts, "log" scaled counts, or "normalized" counts divided
+# by the length of the document. Changing it requires running
+# `searchine index --full`.
+[index]
+tf_weighting = "normalized"
+# The compression of the collection and the segments of the index, "none"
+# or "zstd". Changing it requires running `searchine index --full`.
+compression = "none"
+# The order of the postings of the segments, "doc-id" or "impact". Postings
+# ordered by impact let searches stop early, but only in uncompressed binary
+# segments. Changing it requires running `searchine index --full`.
+postings_order = "doc-id"
+# The memory in megabytes that the postings can take while indexing from
+# scratch, after which they are spilled to disk and merged at the end.
+# memory_budget_mb = 2048
+
+[search]
+# The scorer of the documents, "tfidf" or "bm25".
+scorer = "tfidf"
+# The number of results, if `--top-n` is not given.
+top_n = 10
+# The weights of the matches in the titles of the documents, the names of
+# their files, and in their text.
+title_boost = 3.0
+body_boost = 1.0
+# The number of days after which the score of a document is halved, since it
+# was last modified. Scores do not decay if it is not set.
+# recency_half_life_days = 30
+# The weights of the documents whose paths match globs.
+path_boosts = {}
+# The weight of the importance of the documents, from the links of Markdown
+# and HTML documents to each other. Links are not weighted if it is 0.
+link_weight = 0.0
+# The weight of how close to each other the terms of a query are in the text
+# of the documents. The proximity of the terms is not weighted if it is 0.
+proximity_weight = 0.0
+# The rules that rewrite the paths of copies of documents into the paths of
+# the documents, so that only the best variant of a document is a result.
+canonical_paths = []
+
+# Ranking profiles, selected with `searchine search --profile <name>`, which
+# override the settings of the [search] section.
+# [profiles.recent-first]
+# recency_half_life_days = 14
+"#;
+
+/// Initializes a new searchine index repo, with a default config file.
 ///
 /// # Arguments
 ///
 /// * `dir_path` - The path to the directory where the index will be created.
-/// * `searchine_path` - The path to the searchine index directory relative to `dir_path`.
 pub fn invoke(dir_path: impl AsRef<Path>) -> io::Result<()> {
-    let dir_path = dir_path.as_ref();
-    let index_path = dir_path.join(SEARCHINE_PATH);
-    std::fs::create_dir_all(&index_path)?;
-    let full_index_path = std::fs::canonicalize(&index_path)?;
+    let full_index_path = create(dir_path)?;
     let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x82]).unwrap_or_default();
     println_bold!("{emoji} Index created at: {}", full_index_path.display());
     Ok(())
 }
+
+/// Initializes a new named index in a searchine index repo, with a default
+/// config file.
+///
+/// # Arguments
+///
+/// * `index_path` - The path to the directory of the named index.
+pub fn invoke_named(index_path: impl AsRef<Path>) -> io::Result<()> {
+    let full_index_path = create_index(index_path)?;
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x82]).unwrap_or_default();
+    println_bold!("{emoji} Index created at: {}", full_index_path.display());
+    Ok(())
+}
+
+/// Creates the directory of a searchine index repo in a directory, with a
+/// default config file, if it does not exist, and returns its full path.
+pub fn create(dir_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    create_index(dir_path.as_ref().join(SEARCHINE_PATH))
+}
+
+/// Creates the directory of an index, which is either a repo or a named
+/// index in it, with a default config file, if it does not exist, and
+/// returns its full path.
+fn create_index(index_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let index_path = index_path.as_ref();
+    std::fs::create_dir_all(index_path)?;
+    let config_path = index_path.join(CONFIG_FILENAME);
+    if !config_path.exists() {
+        std::fs::write(&config_path, DEFAULT_CONFIG)?;
+    }
+    std::fs::canonicalize(index_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::settings::Settings;
+
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        let settings: Settings = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(settings.analyzer, Default::default());
+        assert_eq!(settings.filter, Default::default());
+        assert_eq!(settings.walk, Default::default());
+        assert_eq!(settings.search, Default::default());
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use documents::DocumentId;
+use index::collection::Collection;
+
+use crate::cli::utils::load_manifest;
+use crate::config::{COLLECTION_FILENAME, MANIFEST_KEY_VAR};
+use crate::fs::roots::relative_to_root;
+use crate::fs::CorpusRoots;
+
+/// The HMAC-SHA256 that manifests are signed with.
+type HmacSha256 = Hmac<Sha256>;
+
+/// A record of the state of the corpus of a repository, so that the results
+/// of a search can be traced back to the exact documents they came from.
+///
+/// The manifest lists the documents of the collection with their IDs and
+/// the SHA-256 hashes of their files, and the generation of the index. Its
+/// digest is the SHA-256 hash of this content, so changes to the manifest
+/// are detected. If a key is given in `SEARCHINE_MANIFEST_KEY`, the content
+/// is also signed with HMAC-SHA256, so only holders of the key can create
+/// a manifest that verifies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorpusManifest {
+    /// The generation of the index when the manifest was created.
+    generation: u64,
+    /// The time the manifest was created, in seconds since the Unix epoch.
+    created: u64,
+    /// The documents of the corpus, ordered by path.
+    documents: Vec<ManifestEntry>,
+    /// The SHA-256 hash of the content of the manifest, in hex.
+    digest: String,
+    /// The HMAC-SHA256 of the content of the manifest, in hex, if it was
+    /// signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+/// A document of a corpus manifest.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The path of the document, relative to the root directory of the
+    /// corpus that it is in, so that a copy of the corpus elsewhere can be
+    /// verified.
+    path: String,
+    doc_id: DocumentId,
+    /// The SHA-256 hash of the file of the document, in hex.
+    sha256: String,
+}
+
+impl CorpusManifest {
+    /// Creates a manifest of documents, and signs it if a key is given.
+    pub fn new(
+        generation: u64,
+        created: u64,
+        mut documents: Vec<ManifestEntry>,
+        key: Option<&[u8]>,
+    ) -> anyhow::Result<Self> {
+        documents.sort();
+        let mut manifest = Self {
+            generation,
+            created,
+            documents,
+            digest: String::new(),
+            signature: None,
+        };
+        let content = manifest.content()?;
+        manifest.digest = hex(&Sha256::digest(&content));
+        manifest.signature = key.map(|key| hex(&mac(key, &content).finalize().into_bytes()));
+        Ok(manifest)
+    }
+
+    /// Checks the digest of the manifest, and its signature with a key, if
+    /// one is given. A manifest without a signature does not verify with a
+    /// key, as anyone can recompute its digest after changing it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest was modified, if a key is given and
+    /// the manifest is not signed, or if its signature does not match the
+    /// key.
+    fn check(&self, key: Option<&[u8]>) -> anyhow::Result<()> {
+        let content = self.content()?;
+        if hex(&Sha256::digest(&content)) != self.digest {
+            anyhow::bail!("Manifest was modified after it was created.");
+        }
+        match (&self.signature, key) {
+            (Some(signature), Some(key)) => {
+                let signature = unhex(signature).unwrap_or_default();
+                if mac(key, &content).verify_slice(&signature).is_err() {
+                    anyhow::bail!("Signature of the manifest does not match the key.");
+                }
+                println_green!("Signature of the manifest is valid.");
+            }
+            (Some(_), None) => {
+                println_bold!(
+                    "{} Set {MANIFEST_KEY_VAR} to the key to check it.",
+                    "Manifest is signed, but its signature was not checked."
+                );
+            }
+            (None, Some(_)) => {
+                anyhow::bail!("Manifest is not signed, but {MANIFEST_KEY_VAR} is set.");
+            }
+            (None, None) => {
+                println_bold!("Manifest is not signed.");
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the content of the manifest that is hashed and signed, which
+    /// is everything but the digest and the signature.
+    fn content(&self) -> anyhow::Result<Vec<u8>> {
+        let content = (self.generation, self.created, &self.documents);
+        Ok(serde_json::to_vec(&content)?)
+    }
+}
+
+/// Creates a manifest of the corpus of a repository, and writes it to stdout
+/// as JSON. The manifest is signed if `SEARCHINE_MANIFEST_KEY` is set.
+///
+/// The files are hashed as they are now, so documents whose files were
+/// modified since they were indexed, or no longer exist, are reported on
+/// stderr. Files that no longer exist are left out of the manifest.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+pub fn invoke(repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let generation = load_manifest(repo_dir)?.generation();
+    let (documents, problems) = corpus(repo_dir)?;
+    for problem in problems {
+        eprintln!("WARNING: {problem}");
+    }
+    let created = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let key = std::env::var(MANIFEST_KEY_VAR).ok();
+    let key = key.as_deref().map(str::as_bytes);
+    let manifest = CorpusManifest::new(generation, created, documents, key)?;
+    println!("{}", serde_json::to_string_pretty(&manifest)?);
+    Ok(())
+}
+
+/// Verifies a manifest against the current corpus of a repository.
+///
+/// The digest of the manifest is checked first, and its signature if
+/// `SEARCHINE_MANIFEST_KEY` is set, in which case a manifest that is not
+/// signed is rejected. Then the documents of the
+/// manifest are compared with the documents of the collection and their
+/// files, and every difference is listed.
+///
+/// # Errors
+///
+/// Returns an error if the manifest cannot be read, if it was modified, if
+/// it is not signed or its signature does not match while a key is set, or
+/// if the corpus does not match it.
+pub fn verify(repo_dir: impl AsRef<Path>, manifest_path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let manifest_path = manifest_path.as_ref();
+    let file = File::open(manifest_path).context(format!(
+        "Could not read manifest: {}",
+        manifest_path.display()
+    ))?;
+    let manifest: CorpusManifest = serde_json::from_reader(BufReader::new(file))
+        .context(format!("Invalid manifest: {}", manifest_path.display()))?;
+
+    let key = std::env::var(MANIFEST_KEY_VAR).ok();
+    manifest.check(key.as_deref().map(str::as_bytes))?;
+
+    let generation = load_manifest(repo_dir)?.generation();
+    if generation != manifest.generation {
+        println_bold!(
+            "Index is at generation {generation}, and the manifest at {}.",
+            manifest.generation
+        );
+    }
+    let (documents, _) = corpus(repo_dir)?;
+    let differences = differences(&manifest.documents, &documents);
+    if differences.is_empty() {
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+        println_bold!(
+            "{emoji} Corpus matches the manifest ({} documents).",
+            documents.len()
+        );
+        return Ok(());
+    }
+    for difference in &differences {
+        println_red!("  {difference}");
+    }
+    anyhow::bail!("Corpus does not match the manifest.")
+}
+
+/// Lists the documents of the collection of a repository, with the hashes
+/// of their files, and the problems with the documents whose files were
+/// modified since they were indexed, or cannot be read.
+fn corpus(repo_dir: &Path) -> anyhow::Result<(Vec<ManifestEntry>, Vec<String>)> {
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    Ok(corpus_documents(collection, &CorpusRoots::dirs(repo_dir)?))
+}
+
+/// Lists the documents of a collection, by their paths relative to the root
+/// directories of the corpus that they are in, with the hashes of their
+/// files, and the problems with the documents whose files were modified
+/// since they were indexed, or cannot be read.
+fn corpus_documents(
+    collection: Collection,
+    roots: &[PathBuf],
+) -> (Vec<ManifestEntry>, Vec<String>) {
+    let mut documents = vec![];
+    let mut problems = vec![];
+    for (path, entry) in collection {
+        let relative = relative_to_root(roots, &path);
+        let relative = relative.to_string_lossy().into_owned();
+        let sha256 = match hash_file(&path) {
+            Ok(sha256) => sha256,
+            Err(err) => {
+                problems.push(format!("{relative}: {err}"));
+                continue;
+            }
+        };
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified());
+        if modified.is_ok_and(|modified| modified != entry.modified()) {
+            problems.push(format!(
+                "{relative}: Modified since it was indexed. Run `searchine index` first."
+            ));
+        }
+        documents.push(ManifestEntry {
+            path: relative,
+            doc_id: entry.document_id(),
+            sha256,
+        });
+    }
+    documents.sort();
+    (documents, problems)
+}
+
+/// Lists the differences between the documents of a manifest and the
+/// documents of the corpus now, by path.
+fn differences(manifest: &[ManifestEntry], corpus: &[ManifestEntry]) -> Vec<String> {
+    let corpus = corpus
+        .iter()
+        .map(|document| (document.path.as_str(), document))
+        .collect::<BTreeMap<_, _>>();
+    let mut differences = vec![];
+    for recorded in manifest {
+        match corpus.get(recorded.path.as_str()) {
+            None => differences.push(format!("missing:     {}", recorded.path)),
+            Some(document) if document.sha256 != recorded.sha256 => {
+                differences.push(format!("changed:     {}", recorded.path))
+            }
+            Some(document) if document.doc_id != recorded.doc_id => differences.push(format!(
+                "renumbered:  {} (document {} instead of {})",
+                recorded.path, document.doc_id, recorded.doc_id
+            )),
+            Some(_) => {}
+        }
+    }
+    let recorded = manifest
+        .iter()
+        .map(|document| document.path.as_str())
+        .collect::<BTreeSet<_>>();
+    for path in corpus.keys() {
+        if !recorded.contains(path) {
+            differences.push(format!("added:       {path}"));
+        }
+    }
+    differences
+}
+
+/// Hashes the contents of a file with SHA-256.
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Starts the HMAC-SHA256 of a message with a key, as in RFC 2104.
+fn mac(key: &[u8], message: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length.");
+    mac.update(message);
+    mac
+}
+
+/// Writes bytes as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Reads bytes written as hex, or returns `None` if the text is not hex.
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // The second test case of RFC 4231.
+        let hmac = mac(b"Jefe", b"what do ya want for nothing?").finalize();
+        assert_eq!(
+            hex(&hmac.into_bytes()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_manifest_differences() {
+        let entry = |path: &str, doc_id, sha256: &str| ManifestEntry {
+            path: path.to_string(),
+            doc_id,
+            sha256: sha256.to_string(),
+        };
+        let manifest = CorpusManifest::new(
+            3,
+            0,
+            vec![entry("b.md", 1, "22"), entry("a.md", 0, "11")],
+            Some(b"key"),
+        )
+        .unwrap();
+        assert_eq!(manifest.documents[0].path, "a.md");
+        let unsigned = CorpusManifest::new(3, 0, manifest.documents.clone(), None).unwrap();
+        assert_eq!(unsigned.digest, manifest.digest);
+        assert!(unsigned.signature.is_none());
+
+        let corpus = vec![
+            entry("a.md", 0, "11"),
+            entry("b.md", 2, "22"),
+            entry("c.md", 3, "33"),
+        ];
+        let listed = differences(&manifest.documents, &corpus);
+        assert_eq!(listed.len(), 2);
+        assert!(listed[0].starts_with("renumbered:  b.md"));
+        assert_eq!(listed[1], "added:       c.md");
+
+        // The documents of an added root are listed relative to that root,
+        // like the documents of the directory of the repository.
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (repo_root, added_root) = (dir.join("repo"), dir.join("notes"));
+        fs::create_dir_all(&repo_root).unwrap();
+        fs::create_dir_all(&added_root).unwrap();
+        fs::write(repo_root.join("a.md"), "A").unwrap();
+        fs::write(added_root.join("d.md"), "D").unwrap();
+        let collection =
+            Collection::from_paths([repo_root.join("a.md"), added_root.join("d.md")]).unwrap();
+        let (corpus, problems) = corpus_documents(collection, &[repo_root, added_root]);
+        assert!(problems.is_empty());
+        let paths = corpus
+            .iter()
+            .map(|document| document.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["a.md", "d.md"]);
+        assert_eq!(
+            differences(&manifest.documents, &corpus),
+            [
+                "changed:     a.md",
+                "missing:     b.md",
+                "added:       d.md"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_manifest() {
+        let entry = ManifestEntry {
+            path: "a.md".to_string(),
+            doc_id: 0,
+            sha256: "11".to_string(),
+        };
+        let manifest = CorpusManifest::new(3, 0, vec![entry.clone()], Some(b"key")).unwrap();
+        assert!(manifest.check(Some(b"key")).is_ok());
+        assert!(manifest.check(None).is_ok());
+        assert!(manifest.check(Some(b"other")).is_err());
+
+        // A manifest that was changed, with its digest recomputed and its
+        // signature dropped, does not verify while a key is set.
+        let mut forged = manifest.clone();
+        forged.documents[0].sha256 = "22".to_string();
+        forged.digest = hex(&Sha256::digest(forged.content().unwrap()));
+        forged.signature = None;
+        assert!(forged.check(None).is_ok());
+        assert!(forged.check(Some(b"key")).is_err());
+
+        let mut tampered = manifest;
+        tampered.documents[0].doc_id = 1;
+        assert!(tampered.check(Some(b"key")).is_err());
+        assert_eq!(unhex("0aff"), Some(vec![0x0a, 0xff]));
+        assert_eq!(unhex("0g"), None);
+    }
+}
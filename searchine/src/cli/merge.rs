@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use crate::cli::utils::{index_format, load_manifest, merge_segments};
+use crate::cli::IndexFormat;
+
+/// Merges all the segments of the index of a repository into one, which
+/// makes queries faster after many incremental updates. The deleted
+/// documents of the segments are dropped.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `format`   - The format in which the merged segment is written. If it
+///   is not given, the format of the newest segment is kept.
+pub fn invoke(repo_dir: impl AsRef<Path>, format: Option<IndexFormat>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let manifest = load_manifest(repo_dir)?;
+    let segments = manifest.segments();
+    let format = format.unwrap_or_else(|| index_format(repo_dir));
+    let same_format = segments
+        .iter()
+        .all(|segment| IndexFormat::from_path(segment) == format);
+    if segments.len() <= 1 && same_format && manifest.n_deleted() == 0 {
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+        println_bold!("{emoji} Index has a single segment. Nothing to merge.");
+        return Ok(());
+    }
+
+    merge_segments(repo_dir, segments, format)?;
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
+    println_bold!("{emoji} Merged {} segments into one.", segments.len());
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use index::collection::Collection;
+use index::inverted::FORMAT_VERSION;
+
+use crate::cli::utils::upgrade_index;
+use crate::config::{COLLECTION_FILENAME, LEGACY_INDEX_FILENAMES};
+
+/// Upgrades the index of a repository that was written by an older version
+/// of searchine to the current version of the format, so that it can be
+/// searched and updated without indexing its documents again.
+///
+/// The segments in an older version of the format are written again in the
+/// current one, and the collection is written again with its version.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+///
+/// # Errors
+///
+/// Returns an error if the repository was indexed before the index was
+/// split into segments, or if its files are too old to be upgraded, so that
+/// it has to be indexed again.
+pub fn invoke(repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    if LEGACY_INDEX_FILENAMES
+        .iter()
+        .any(|name| repo_dir.join(name).exists())
+    {
+        anyhow::bail!(
+            "Repository was indexed before the index was split into segments, which \
+             cannot be upgraded. Run `searchine index --full` to build the index again."
+        );
+    }
+
+    let upgraded = upgrade_index(repo_dir)?;
+    let collection_path = repo_dir.join(COLLECTION_FILENAME);
+    if collection_path.exists() {
+        Collection::from_file(&collection_path)?.into_file(&collection_path)?;
+    }
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
+    match upgraded {
+        0 => println_bold!("{emoji} Index is in version {FORMAT_VERSION} of the format."),
+        n => println_bold!(
+            "{emoji} Upgraded {n} segments to version {FORMAT_VERSION} of the format."
+        ),
+    }
+    Ok(())
+}
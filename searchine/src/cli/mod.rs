@@ -1,9 +1,337 @@
+pub mod add;
+pub mod bench;
 pub mod collection;
+pub mod compact;
+pub mod doctor;
+pub mod dupes;
+pub mod explain;
+pub mod export;
+pub mod fetch;
+pub mod history;
+pub mod import;
 pub mod index;
 pub mod init;
+pub mod manifest;
+pub mod merge;
+pub mod migrate;
+pub mod note;
+pub mod plan;
+pub mod preview;
+pub mod prune;
+pub mod related;
+pub mod remove;
+pub mod retrieve;
 pub mod search;
+pub mod semantic;
+pub mod serve;
+pub mod similar;
+pub mod stats;
 pub mod status;
+pub mod suggest;
+pub mod tag;
+pub mod tui;
 pub mod utils;
+pub mod verify;
+pub mod watch;
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+
+use crate::cli::fetch::CrawlLimits;
+use crate::cli::search::{Listing, MetadataFilter};
+use crate::cli::utils::{is_indexed, parse_date, parse_query, parse_size};
+use crate::config::*;
+use crate::error::Error;
+use crate::fs::{CorpusRoots, PathFilter};
+use crate::lock::RepoLock;
+use crate::passage::{PassageConfig, PassageMode};
+use crate::path::{
+    canonicalize_dir_path, corpus_dir, find_index_path, find_repo_path, named_index_path,
+};
+use crate::settings::Settings;
+use crate::shutdown;
+use crate::snippet::{SnippetBoundary, SnippetConfig};
+
+/// The on-disk format of the segments of the inverted index.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum IndexFormat {
+    /// Compact binary format, which is fast to load.
+    #[default]
+    Binary,
+    /// JSON format, which is larger, but can be inspected for debugging.
+    Json,
+}
+
+impl IndexFormat {
+    /// Returns the extension of the segment files in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            IndexFormat::Binary => "bin",
+            IndexFormat::Json => "json",
+        }
+    }
+
+    /// Returns the format of a segment file, from its extension. The
+    /// extension of the compression of a compressed segment follows the one
+    /// of its format, like `bin.zst`.
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Self {
+        let mut path = path.as_ref().to_path_buf();
+        if path.extension().is_some_and(|extension| extension == "zst") {
+            path.set_extension("");
+        }
+        match path.extension() {
+            Some(extension) if extension == "json" => IndexFormat::Json,
+            _ => IndexFormat::Binary,
+        }
+    }
+}
+
+/// The format that a command writes its results to stdout in.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// A JSON array of records.
+    Json,
+    /// A JSON record per line, which can be piped into line-based tools.
+    Ndjson,
+}
+
+/// The format that the index of a repository is exported in.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    /// A JSON record per line: a header, the documents, and the terms with
+    /// their postings.
+    #[default]
+    Jsonl,
+}
+
+/// The order that the results of a search are listed in.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortOrder {
+    /// The most relevant documents first.
+    #[default]
+    Score,
+    /// The most recently modified documents first.
+    Mtime,
+    /// The documents in the order of their paths.
+    Path,
+}
+
+/// Command line overrides of the snippet policy in the config of the
+/// repository.
+#[derive(clap::Args)]
+pub struct SnippetArgs {
+    /// Do not show a snippet of each document below its result.
+    #[clap(long)]
+    pub no_snippets: bool,
+    /// The maximum length of each fragment of a snippet in bytes.
+    #[clap(long)]
+    pub snippet_length: Option<usize>,
+    /// The maximum number of fragments per snippet.
+    #[clap(long)]
+    pub fragments: Option<usize>,
+    /// The separator that joins the fragments of a snippet.
+    #[clap(long)]
+    pub fragment_separator: Option<String>,
+    /// The rule for the boundaries of the fragments.
+    #[clap(long, value_enum)]
+    pub snippet_boundary: Option<SnippetBoundary>,
+}
+
+impl SnippetArgs {
+    /// Overrides the snippet policy with the arguments that were given.
+    pub fn apply(self, mut config: SnippetConfig) -> SnippetConfig {
+        if let Some(length) = self.snippet_length {
+            config.length = length;
+        }
+        if let Some(fragments) = self.fragments {
+            config.fragments = fragments;
+        }
+        if let Some(separator) = self.fragment_separator {
+            config.separator = separator;
+        }
+        if let Some(boundary) = self.snippet_boundary {
+            config.boundary = boundary;
+        }
+        config
+    }
+}
+
+/// Command line filters of the documents of a search, by the metadata of
+/// their files.
+#[derive(clap::Args)]
+pub struct MetadataFilterArgs {
+    /// Only search the documents with these extensions, separated by commas.
+    #[clap(long, value_delimiter = ',')]
+    pub ext: Vec<String>,
+    /// Only search the documents modified on or after this date, as
+    /// YYYY-MM-DD. Calendar events and contacts are matched by their dates.
+    #[clap(long, value_parser = parse_date)]
+    pub modified_after: Option<SystemTime>,
+    /// Only search the documents modified before this date, as YYYY-MM-DD.
+    /// Calendar events and contacts are matched by their dates.
+    #[clap(long, value_parser = parse_date)]
+    pub modified_before: Option<SystemTime>,
+    /// Only search the documents of at least this size, in bytes, or with a
+    /// unit like 10K or 2M.
+    #[clap(long, value_parser = parse_size)]
+    pub min_size: Option<u64>,
+    /// Only search the documents of at most this size, in bytes, or with a
+    /// unit like 10K or 2M.
+    #[clap(long, value_parser = parse_size)]
+    pub max_size: Option<u64>,
+    /// Only search the documents with this tag. It can be given more than
+    /// once, to search the documents with all of the tags.
+    #[clap(long)]
+    pub tag: Vec<String>,
+    /// Only search the documents whose paths, relative to the root directory
+    /// they are in, start with this prefix, like `src/`, or match this glob,
+    /// like `src/**/*.rs`. It can be given more than once, to search the
+    /// documents that match any of them.
+    #[clap(long)]
+    pub path_prefix: Vec<String>,
+}
+
+impl MetadataFilterArgs {
+    /// Builds the metadata filter of the arguments that were given.
+    pub fn filter(self) -> MetadataFilter {
+        MetadataFilter {
+            extensions: self.ext,
+            modified_after: self.modified_after,
+            modified_before: self.modified_before,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            tags: self.tag,
+            path_prefixes: self.path_prefix,
+        }
+    }
+}
+
+/// Command line overrides of the passage policy in the config of the
+/// repository.
+#[derive(clap::Args)]
+pub struct PassageArgs {
+    /// Score passages of the documents instead of whole documents, and show
+    /// the best passages of each document below its result.
+    #[clap(short, long)]
+    pub passages: bool,
+    /// The unit of the passages.
+    #[clap(long, value_enum)]
+    pub passage_mode: Option<PassageMode>,
+    /// The number of terms in each passage window.
+    #[clap(long)]
+    pub passage_size: Option<usize>,
+    /// The number of terms between the starts of consecutive windows.
+    #[clap(long)]
+    pub passage_stride: Option<usize>,
+    /// The number of best passages that make up the score of a document.
+    #[clap(long)]
+    pub passages_per_document: Option<usize>,
+}
+
+impl PassageArgs {
+    /// Overrides the passage policy with the arguments that were given.
+    pub fn apply(self, mut config: PassageConfig) -> PassageConfig {
+        if let Some(mode) = self.passage_mode {
+            config.mode = mode;
+        }
+        if let Some(size) = self.passage_size {
+            config.size = size;
+        }
+        if let Some(stride) = self.passage_stride {
+            config.stride = stride;
+        }
+        if let Some(per_document) = self.passages_per_document {
+            config.per_document = per_document;
+        }
+        config
+    }
+}
+
+/// Command line filters of the files to index, which replace the filters
+/// that are persisted in the repository.
+#[derive(clap::Args)]
+pub struct FilterArgs {
+    /// Index only the files that match a glob, like `**/*.rs`. It can be
+    /// given more than once.
+    #[clap(long)]
+    pub include: Vec<String>,
+    /// Do not index the files that match a glob, like `**/tests/**`. It can
+    /// be given more than once.
+    #[clap(long)]
+    pub exclude: Vec<String>,
+    /// Remove the filters that are persisted in the repository.
+    #[clap(long)]
+    pub clear_filters: bool,
+}
+
+impl FilterArgs {
+    /// Persists the filters that were given in the repository. The include
+    /// globs that were given replace the persisted include globs, and the
+    /// exclude globs that were given replace the persisted exclude globs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the globs is invalid, or if the filters
+    /// cannot be read or written.
+    pub fn persist(self, repo_dir: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let repo_dir = repo_dir.as_ref();
+        if !self.clear_filters && self.include.is_empty() && self.exclude.is_empty() {
+            return Ok(());
+        }
+        let mut filter = if self.clear_filters {
+            PathFilter::default()
+        } else {
+            PathFilter::load(repo_dir)?
+        };
+        if !self.include.is_empty() {
+            filter.include = self.include;
+        }
+        if !self.exclude.is_empty() {
+            filter.exclude = self.exclude;
+        }
+        filter.matcher(repo_dir)?;
+        filter.save(repo_dir)
+    }
+}
+
+/// Command line root directories of the corpus, which are added to the ones
+/// that are persisted in the repository.
+#[derive(clap::Args)]
+pub struct RootArgs {
+    /// Add a root directory to the corpus, like `~/notes`, whose documents
+    /// are indexed along with the ones of the directory. It can be given
+    /// more than once.
+    #[clap(long = "root")]
+    pub roots: Vec<std::path::PathBuf>,
+}
+
+impl RootArgs {
+    /// Persists the root directories that were given in the repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the roots is not a directory, or overlaps
+    /// with the directory of the repository or another root, or if the roots
+    /// cannot be read or written.
+    pub fn persist(self, repo_dir: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let repo_dir = repo_dir.as_ref();
+        if self.roots.is_empty() {
+            return Ok(());
+        }
+        let dir_path = corpus_dir(repo_dir)?;
+        let mut roots = CorpusRoots::load(repo_dir)?;
+        for root in self.roots {
+            roots.add(dir_path, root)?;
+        }
+        roots.save(repo_dir)
+    }
+}
 
 #[derive(clap::Parser)]
 #[clap(
@@ -14,24 +342,148 @@ pub mod utils;
 pub struct SearchineCli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Use the named index of the repository, which is configured, indexed
+    /// and searched on its own, instead of the default one. Named indexes
+    /// are created with `searchine init --index <name>`.
+    #[clap(long, global = true)]
+    pub index: Option<String>,
 }
 
 #[derive(clap::Subcommand)]
 pub enum Commands {
     Init {
         dir_path: Option<String>,
+        #[command(flatten)]
+        roots: RootArgs,
     },
     IndexCollection {
         dir_path: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        roots: RootArgs,
+        /// Do not show the progress of listing the files, nor the files that
+        /// are skipped, for scripts.
+        #[clap(short, long)]
+        quiet: bool,
     },
     ListCollection {
         dir_path: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
     },
     Index {
         dir_path: Option<String>,
+        #[clap(long)]
+        full: bool,
+        #[clap(long, value_enum, default_value_t = IndexFormat::Binary)]
+        format: IndexFormat,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Do not show the progress of indexing, nor the files that are
+        /// skipped, for scripts.
+        #[clap(short, long)]
+        quiet: bool,
     },
     Status {
         dir_path: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Report the files that are skipped, because they are unsupported,
+        /// binary, or larger than the maximum file size.
+        #[clap(short, long)]
+        verbose: bool,
+        /// Write a line per change, with `A`, `M` or `D` and the path, for
+        /// scripts.
+        #[clap(long, conflicts_with = "output")]
+        porcelain: bool,
+    },
+    Plan {
+        dir_path: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Stats {
+        dir_path: Option<String>,
+        /// The number of the most frequent terms to list.
+        #[clap(short = 'k', long)]
+        top_k: Option<usize>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Bench {
+        dir_path: Option<String>,
+        /// The file of queries to measure the latency of, with a query per
+        /// line. Empty lines and lines that start with `#` are skipped.
+        #[clap(short, long)]
+        queries: Option<std::path::PathBuf>,
+        /// The number of times each query is searched for.
+        #[clap(short, long)]
+        runs: Option<usize>,
+        /// Measures only the queries, without indexing the corpus.
+        #[clap(long)]
+        skip_indexing: bool,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Dupes {
+        dir_path: Option<String>,
+        /// The maximum number of bits that the fingerprints of near-duplicates
+        /// differ in, out of 64. Above 3, some near-duplicates may be missed.
+        #[clap(long)]
+        max_distance: Option<u32>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Export {
+        dir_path: Option<String>,
+        #[clap(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+    },
+    Merge {
+        dir_path: Option<String>,
+        #[clap(long, value_enum)]
+        format: Option<IndexFormat>,
+    },
+    Compact {
+        dir_path: Option<String>,
+    },
+    Migrate {
+        dir_path: Option<String>,
+    },
+    Watch {
+        dir_path: Option<String>,
+    },
+    Verify {
+        dir_path: Option<String>,
+        #[clap(long)]
+        fix: bool,
+    },
+    Doctor {
+        dir_path: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Manifest {
+        dir_path: Option<String>,
+        /// Verify the corpus against this manifest, instead of creating one.
+        #[clap(long)]
+        verify: Option<String>,
+    },
+    Prune {
+        dir_path: Option<String>,
+        /// Prune the terms that are in fewer documents than this.
+        #[clap(long)]
+        df_below: Option<u32>,
+        /// Prune the terms that match this regular expression as a whole.
+        #[clap(long)]
+        terms_matching: Option<String>,
+        /// Prune the documents whose files were deleted from disk.
+        #[clap(long)]
+        deleted: bool,
+        /// Only report what would be pruned.
+        #[clap(long)]
+        dry_run: bool,
     },
     Search {
         query: String,
@@ -39,5 +491,1009 @@ pub enum Commands {
         dir_path: Option<String>,
         #[clap(short, long)]
         top_n: Option<u32>,
+        /// Skip this many of the most relevant documents, to page through
+        /// the results.
+        #[clap(long, conflicts_with = "page")]
+        offset: Option<u32>,
+        /// Show this page of the results, counting from 1, with `top_n`
+        /// results on each page.
+        #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+        page: Option<u32>,
+        /// Show all the documents that match, instead of the `top_n` most
+        /// relevant. With `--output ndjson`, each result is written as soon
+        /// as it is ready.
+        #[clap(long, conflicts_with_all = ["top_n", "page"])]
+        all: bool,
+        /// The order to list the matching documents in.
+        #[clap(long, value_enum, default_value_t = SortOrder::Score)]
+        sort: SortOrder,
+        /// Rank the documents with this ranking profile of the config.
+        #[clap(long)]
+        profile: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Write the results as JSON, the same as `--output json`.
+        #[clap(long)]
+        json: bool,
+        /// Rank the documents by semantic similarity to the query, with the
+        /// embedder in the config of the repository.
+        #[clap(long)]
+        semantic: bool,
+        /// Match the terms in their original case only, which requires
+        /// `cased_identifiers` or `cased_terms` in the analyzer config.
+        /// Without `cased_terms`, only the identifiers of source code match.
+        #[clap(long)]
+        case_sensitive: bool,
+        #[command(flatten)]
+        snippet: SnippetArgs,
+        #[command(flatten)]
+        passage: PassageArgs,
+        #[command(flatten)]
+        metadata: MetadataFilterArgs,
+        /// Count the matching documents by extension and by top-level
+        /// directory.
+        #[clap(long)]
+        facets: bool,
+        /// Search for the suggested query instead, if words of the query
+        /// match few or no documents and are close to words that match more.
+        #[clap(long)]
+        auto_correct: bool,
+        /// If the query matches no documents, relax it step by step, with
+        /// case-insensitive matching, matching in any field, and fuzzy
+        /// matching, until it matches some.
+        #[clap(long)]
+        relax: bool,
+        /// Expand the query with the terms that weigh the most in the
+        /// documents that it ranks the highest, with less weight than its own
+        /// terms, and rank the documents again.
+        #[clap(long)]
+        expand: bool,
+        /// Do not color the results, even if stdout is a terminal.
+        #[clap(long)]
+        no_color: bool,
+        /// Show only the most relevant of the documents whose texts are
+        /// identical or near-duplicates, with the number of its copies.
+        #[clap(long)]
+        collapse_duplicates: bool,
+        /// Also match the query, literally, in the lines of the files that
+        /// are new or modified since the last index run, and show them after
+        /// the results.
+        #[clap(long)]
+        include_unindexed: bool,
+    },
+    Suggest {
+        prefix: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        /// The number of terms to list.
+        #[clap(short = 'k', long)]
+        top_k: Option<usize>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Related {
+        term: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        /// The number of terms to list.
+        #[clap(short = 'k', long)]
+        top_k: Option<usize>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Explain {
+        query: String,
+        doc_path: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        /// Score the document with this ranking profile of the config.
+        #[clap(long)]
+        profile: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Similar {
+        doc_path: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        #[clap(short, long)]
+        top_n: Option<u32>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    Retrieve {
+        query: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        #[clap(short = 'k', long)]
+        top_k: Option<u32>,
+    },
+    Serve {
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        #[clap(short, long)]
+        address: Option<String>,
+    },
+    Tui {
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        /// Rank the documents with this ranking profile of the config.
+        #[clap(long)]
+        profile: Option<String>,
+    },
+    History {
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        /// The number of the most recent queries to list.
+        #[clap(short = 'n', long)]
+        limit: Option<usize>,
+        /// Run the query with this number again, counting back from the most
+        /// recent, which is 1.
+        #[clap(long, conflicts_with = "clear")]
+        run: Option<usize>,
+        /// Remove all the queries of the history.
+        #[clap(long)]
+        clear: bool,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// The options of `searchine search` that the query is run with
+        /// again, after `--`.
+        #[clap(last = true, requires = "run")]
+        args: Vec<String>,
+    },
+    Save {
+        name: String,
+        /// The query to save, which is required unless the saved search is
+        /// removed.
+        #[clap(required_unless_present = "remove")]
+        query: Option<String>,
+        /// Remove the saved search instead.
+        #[clap(long, conflicts_with = "query")]
+        remove: bool,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    Run {
+        /// The name of the saved search. The saved searches are listed if it
+        /// is not given.
+        name: Option<String>,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// The options of `searchine search` that the saved query is run
+        /// with, after `--`.
+        #[clap(last = true, requires = "name")]
+        args: Vec<String>,
+    },
+    AddNote {
+        text: Option<String>,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    Preview {
+        doc_path: String,
+        #[clap(short, long)]
+        query: Option<String>,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    Add {
+        doc_path: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    Remove {
+        doc_path: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    ImportBookmarks {
+        export_path: String,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    Fetch {
+        urls_path: String,
+        /// The number of links that are followed from the listed pages, to
+        /// pages of the same host.
+        #[clap(long, default_value_t = 0)]
+        depth: usize,
+        /// The largest number of pages that are fetched.
+        #[clap(long)]
+        max_pages: Option<usize>,
+        /// The milliseconds that are waited between two requests to the same
+        /// host.
+        #[clap(long)]
+        delay_millis: Option<u64>,
+        #[clap(short, long)]
+        dir_path: Option<String>,
     },
+    Tag {
+        #[command(subcommand)]
+        command: TagCommands,
+    },
+}
+
+/// The commands that change or list the tags of the documents.
+#[derive(clap::Subcommand)]
+pub enum TagCommands {
+    Add {
+        doc_path: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    Remove {
+        doc_path: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+    List {
+        doc_path: Option<String>,
+        #[clap(short, long)]
+        dir_path: Option<String>,
+    },
+}
+
+/// Runs the command of the arguments of the process.
+pub fn run() -> anyhow::Result<()> {
+    execute(SearchineCli::parse())
+}
+
+/// Runs a command.
+fn execute(args: SearchineCli) -> anyhow::Result<()> {
+    let index_name = args.index;
+
+    match args.command {
+        // Initializes a new searchine index repository if one does not already exist
+        // at the specified directory path. If it already exists, then nothing is done.
+        Commands::Init { dir_path, roots } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            let repo_path = find_repo_path(&dir_path, SEARCHINE_PATH);
+            if let Some(name) = index_name {
+                let repo_path = match repo_path {
+                    Some(repo_path) => repo_path,
+                    None => init::create(&dir_path)?,
+                };
+                let index_path = named_index_path(&repo_path, &name)?;
+                if index_path.exists() {
+                    println_bold!("Index {name} already exists at: {}", index_path.display());
+                    return Ok(());
+                }
+                init::invoke_named(&index_path)?;
+                roots.persist(index_path)?;
+                return Ok(());
+            }
+            if let Some(repo_path) = repo_path {
+                println_bold!("searchine repo already exists at: {}", repo_path.display());
+                return Ok(());
+            }
+            init::invoke(&dir_path)?;
+            roots.persist(dir_path.join(SEARCHINE_PATH))?;
+        }
+        // Indexes a corpus of documents at the specified directory path.
+        Commands::IndexCollection {
+            dir_path,
+            filter,
+            roots,
+            quiet,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                let _lock = RepoLock::acquire(&repo_path)?;
+                shutdown::install()?;
+                filter.persist(&repo_path)?;
+                roots.persist(&repo_path)?;
+                collection::index(repo_path, !quiet)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::ListCollection { dir_path, output } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !repo_path.join(COLLECTION_FILENAME).exists() {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                collection::list(repo_path, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::Index {
+            dir_path,
+            full,
+            format,
+            filter,
+            quiet,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                let _lock = RepoLock::acquire(&repo_path)?;
+                shutdown::install()?;
+                filter.persist(&repo_path)?;
+                if !repo_path.join(COLLECTION_FILENAME).exists() {
+                    let _ = collection::index(&repo_path, false);
+                }
+                index::invoke_par(repo_path, !quiet, full, format)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::Status {
+            dir_path,
+            output,
+            verbose,
+            porcelain,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                RepoLock::wait(&repo_path)?;
+                status::invoke(repo_path, verbose, output, porcelain)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Merges the segments of the index of the repository into one.
+        Commands::Merge { dir_path, format } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                let _lock = RepoLock::acquire(&repo_path)?;
+                merge::invoke(repo_path, format)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Drops the deleted documents from the segments of the index of the
+        // repository.
+        Commands::Compact { dir_path } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                let _lock = RepoLock::acquire(&repo_path)?;
+                compact::invoke(repo_path)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Upgrades the index of the repository to the current version of the
+        // format.
+        Commands::Migrate { dir_path } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                let _lock = RepoLock::acquire(&repo_path)?;
+                migrate::invoke(repo_path)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Checks the index of the repository for inconsistencies, and
+        // optionally repairs them.
+        Commands::Verify { dir_path, fix } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                // Only repairs change the repository, but checks should not
+                // see it while it is being changed either.
+                let _lock = if fix {
+                    Some(RepoLock::acquire(&repo_path)?)
+                } else {
+                    RepoLock::wait(&repo_path)?;
+                    None
+                };
+                verify::invoke(repo_path, fix)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Checks the health of the repository, and suggests how to fix it.
+        Commands::Doctor { dir_path, output } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                // The lock is not waited for, as a held lock is reported.
+                doctor::invoke(repo_path, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Estimates the resources that indexing the repository needs.
+        Commands::Plan { dir_path, output } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                RepoLock::wait(&repo_path)?;
+                plan::invoke(repo_path, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::Stats {
+            dir_path,
+            top_k,
+            output,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                let top_k = top_k.unwrap_or(STATS_TOP_K);
+                stats::invoke(repo_path, top_k, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Measures the indexing throughput and the query latencies of the
+        // repository.
+        Commands::Bench {
+            dir_path,
+            queries,
+            runs,
+            skip_indexing,
+            output,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let runs = runs.unwrap_or(BENCH_RUNS);
+                bench::invoke(repo_path, queries.as_deref(), runs, !skip_indexing, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Lists the clusters of near-duplicate documents of the repository.
+        Commands::Dupes {
+            dir_path,
+            max_distance,
+            output,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let max_distance = max_distance.unwrap_or(DUPES_MAX_DISTANCE);
+                dupes::invoke(repo_path, max_distance, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Exports the terms and the postings of the index of the repository.
+        Commands::Export { dir_path, format } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                export::invoke(repo_path, format)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Creates a manifest of the corpus of the repository, or verifies the
+        // corpus against one.
+        Commands::Manifest { dir_path, verify } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                match verify {
+                    Some(manifest_path) => manifest::verify(repo_path, manifest_path)?,
+                    None => manifest::invoke(repo_path)?,
+                }
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Removes useless terms from the index of the repository.
+        Commands::Prune {
+            dir_path,
+            df_below,
+            terms_matching,
+            deleted,
+            dry_run,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                let _lock = if dry_run {
+                    RepoLock::wait(&repo_path)?;
+                    None
+                } else {
+                    Some(RepoLock::acquire(&repo_path)?)
+                };
+                prune::invoke(repo_path, df_below, terms_matching, deleted, dry_run)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Watches the directory of the repository, and updates its index as
+        // the documents change.
+        Commands::Watch { dir_path } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                shutdown::install()?;
+                watch::invoke(repo_path)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::Search {
+            query: raw_query,
+            dir_path,
+            top_n,
+            offset,
+            page,
+            all,
+            sort,
+            profile,
+            output,
+            json,
+            semantic,
+            case_sensitive,
+            snippet,
+            passage,
+            metadata,
+            facets,
+            auto_correct,
+            relax,
+            expand,
+            no_color,
+            collapse_duplicates,
+            include_unindexed,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let settings = Settings::load(&repo_path)?;
+                let search_config = settings.search_config(profile.as_deref())?;
+                let top_n = match all {
+                    true => u32::MAX,
+                    false => top_n.unwrap_or(search_config.top_n),
+                };
+                let offset = match page {
+                    Some(page) => (page - 1).saturating_mul(top_n),
+                    None => offset.unwrap_or(0),
+                };
+                let output = if json { OutputFormat::Json } else { output };
+                let listing = Listing {
+                    range: offset..offset.saturating_add(top_n),
+                    sort,
+                    output,
+                    filter: metadata.filter(),
+                    facets,
+                    auto_correct,
+                    relax,
+                    expand,
+                    collapse_duplicates,
+                    include_unindexed,
+                    color: !no_color,
+                };
+                let show_snippets = !snippet.no_snippets;
+                let show_passages = passage.passages;
+                let snippet_config = snippet.apply(settings.snippet);
+                let snippet_config = show_snippets.then_some(&snippet_config);
+                let passage_config = passage.apply(settings.passage);
+                let passage_config = show_passages.then_some(&passage_config);
+                if case_sensitive && !settings.analyzer.is_cased() {
+                    let reason = format!(
+                        "{} {}",
+                        "Terms are not indexed in their original case.",
+                        "Set `cased_terms = true`, or `cased_identifiers = true` for source code only, in the [analyzer] section of .searchine/config.toml, and run `searchine index --full`."
+                    );
+                    return Err(Error::NotConfigured { reason }.into());
+                }
+                if show_passages && sort != SortOrder::Score {
+                    SearchineCli::command()
+                        .error(
+                            ErrorKind::ArgumentConflict,
+                            "Passages are only scored when the results are sorted by score. Remove `--passages`, or `--sort`.",
+                        )
+                        .exit();
+                }
+                let mut query = parse_query(&raw_query, settings.rewrite.build().as_deref())?;
+                query.case_sensitive = case_sensitive;
+                let semantic = semantic || query.semantic.is_some();
+                let embedder = match (semantic, settings.embedder) {
+                    (false, _) => None,
+                    (true, Some(config)) => Some(config.build()?),
+                    (true, None) => {
+                        let reason = format!(
+                            "{} {}",
+                            "No embedder is configured.",
+                            "Add an [embedder] section to .searchine/config.toml."
+                        );
+                        return Err(Error::NotConfigured { reason }.into());
+                    }
+                };
+                let n_hits = search::invoke(
+                    &repo_path,
+                    &query,
+                    &search_config,
+                    listing,
+                    snippet_config,
+                    passage_config,
+                    embedder.as_deref(),
+                )?;
+                // The query is kept in the history, but the search does not
+                // fail if it cannot be.
+                if let Err(err) = crate::history::record(&repo_path, &raw_query, n_hits) {
+                    eprintln!(
+                        "WARNING: Failed to record the query in the history because: {err:#}"
+                    );
+                }
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Retrieves the most relevant passages to a query, and writes them as
+        // JSON chunks for retrieval-augmented generation pipelines.
+        // Completes a prefix with the terms of the index, for shell
+        // integrations and editor plugins.
+        Commands::Suggest {
+            prefix,
+            dir_path,
+            top_k,
+            output,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let top_k = top_k.unwrap_or(SUGGEST_TOP_K);
+                suggest::invoke(repo_path, &prefix, top_k, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::Related {
+            term,
+            dir_path,
+            top_k,
+            output,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let top_k = top_k.unwrap_or(RELATED_TOP_K);
+                related::invoke(repo_path, &term, top_k, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Finds the documents that are the most similar to an indexed
+        // document, by searching for its most distinctive terms.
+        // Breaks down the score of a document against a query.
+        Commands::Explain {
+            query,
+            doc_path,
+            dir_path,
+            profile,
+            output,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                explain::invoke(repo_path, &query, doc_path, profile.as_deref(), output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::Similar {
+            doc_path,
+            dir_path,
+            top_n,
+            output,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let settings = Settings::load(&repo_path)?;
+                let top_n = top_n.unwrap_or(settings.search.top_n);
+                similar::invoke(repo_path, doc_path, top_n, output)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        Commands::Retrieve {
+            query,
+            dir_path,
+            top_k,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let top_k = top_k.unwrap_or(5);
+                let settings = Settings::load(&repo_path)?;
+                let query = parse_query(&query, settings.rewrite.build().as_deref())?;
+                retrieve::invoke(repo_path, &query.lexical, top_k, &settings.passage)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Serves the index over HTTP, loading it once for all queries.
+        Commands::Serve { dir_path, address } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let address = address.as_deref().unwrap_or(SERVE_ADDRESS);
+                shutdown::install()?;
+                serve::invoke(repo_path, address)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Browses the results of the queries that are typed, in the terminal.
+        Commands::Tui { dir_path, profile } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                let settings = Settings::load(&repo_path)?;
+                let search_config = settings.search_config(profile.as_deref())?;
+                tui::invoke(repo_path, search_config)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Lists the most recent queries of the history, runs one of them
+        // again, or clears the history.
+        Commands::History {
+            dir_path,
+            limit,
+            run,
+            clear,
+            output,
+            args,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if clear {
+                    crate::history::clear(&repo_path)?;
+                    println_bold!("Cleared the history of: {}", dir_path.display());
+                    return Ok(());
+                }
+                let Some(number) = run else {
+                    let limit = limit.unwrap_or(HISTORY_LIST_LEN);
+                    return history::list(repo_path, limit, output);
+                };
+                match history::query(&repo_path, number)? {
+                    Some(query) => {
+                        let args = search_args(&dir_path, index_name.as_deref(), &query, args);
+                        return execute(args);
+                    }
+                    None => println_bold!("No query with number {number} in the history."),
+                }
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Saves a query by name, to run it with `searchine run`, or removes a
+        // saved search.
+        Commands::Save {
+            name,
+            query,
+            remove,
+            dir_path,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                match query {
+                    Some(query) if !remove => history::save(repo_path, &name, &query)?,
+                    _ => history::remove(repo_path, &name)?,
+                }
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Runs a saved search, or lists the saved searches.
+        Commands::Run {
+            name,
+            dir_path,
+            output,
+            args,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                let Some(name) = name else {
+                    return history::list_saved(repo_path, output);
+                };
+                let saved = crate::history::SavedSearches::load(&repo_path)?;
+                match saved.queries.get(&name) {
+                    Some(query) => {
+                        let args = search_args(&dir_path, index_name.as_deref(), query, args);
+                        return execute(args);
+                    }
+                    None => println_bold!("No search is saved as: {name}"),
+                }
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Adds a note to the notes store of the repository, reading it from
+        // stdin if no text is given, and indexes it immediately.
+        Commands::AddNote { text, dir_path } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                note::add(repo_path, text)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Previews the stored text of an indexed document, highlighting the
+        // matches of an optional query.
+        Commands::Preview {
+            doc_path,
+            query,
+            dir_path,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !repo_path.join(COLLECTION_FILENAME).exists() {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                RepoLock::wait(&repo_path)?;
+                preview::invoke(repo_path, doc_path, query.as_deref())?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Indexes a single document, without walking the rest of the corpus.
+        Commands::Add { doc_path, dir_path } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                let _lock = RepoLock::acquire(&repo_path)?;
+                add::invoke(repo_path, doc_path)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Removes a document from the index, without indexing the repository
+        // again.
+        Commands::Remove { doc_path, dir_path } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                let _lock = RepoLock::acquire(&repo_path)?;
+                remove::invoke(repo_path, doc_path)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Imports the bookmarks of a browser bookmarks export as documents.
+        Commands::ImportBookmarks {
+            export_path,
+            dir_path,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                let _lock = RepoLock::acquire(&repo_path)?;
+                import::bookmarks(repo_path, export_path)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Fetches the web pages of a list of URLs as documents.
+        Commands::Fetch {
+            urls_path,
+            depth,
+            max_pages,
+            delay_millis,
+            dir_path,
+        } => {
+            let dir_path = canonicalize_dir_path(dir_path)?;
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                let _lock = RepoLock::acquire(&repo_path)?;
+                shutdown::install()?;
+                let limits = CrawlLimits {
+                    depth,
+                    max_pages: max_pages.unwrap_or(FETCH_MAX_PAGES),
+                    delay: Duration::from_millis(delay_millis.unwrap_or(FETCH_DELAY_MILLIS)),
+                };
+                fetch::invoke(repo_path, urls_path, limits)?;
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+        // Adds, removes or lists the tags of the documents of the collection.
+        Commands::Tag { command } => {
+            let dir_path = match &command {
+                TagCommands::Add { dir_path, .. }
+                | TagCommands::Remove { dir_path, .. }
+                | TagCommands::List { dir_path, .. } => canonicalize_dir_path(dir_path.clone())?,
+            };
+            if let Some(repo_path) = find_index_path(&dir_path, index_name.as_deref())? {
+                if !is_indexed(&repo_path) {
+                    return Err(Error::NotIndexed { path: dir_path }.into());
+                }
+                match command {
+                    TagCommands::Add { doc_path, tags, .. } => {
+                        let _lock = RepoLock::acquire(&repo_path)?;
+                        tag::add(repo_path, doc_path, &tags)?;
+                    }
+                    TagCommands::Remove { doc_path, tags, .. } => {
+                        let _lock = RepoLock::acquire(&repo_path)?;
+                        tag::remove(repo_path, doc_path, &tags)?;
+                    }
+                    TagCommands::List { doc_path, .. } => {
+                        RepoLock::wait(&repo_path)?;
+                        tag::list(repo_path, doc_path)?;
+                    }
+                }
+            } else {
+                return Err(Error::NotARepository { path: dir_path }.into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the arguments of `searchine search` for a query of the history or a
+/// saved search, in a directory, with the options that it is run with. Like
+/// the arguments of the process, the process exits with the usage if the
+/// options are not valid options of `searchine search`.
+fn search_args(
+    dir_path: &Path,
+    index_name: Option<&str>,
+    query: &str,
+    options: Vec<String>,
+) -> SearchineCli {
+    let mut args = vec!["searchine".to_string(), "search".to_string()];
+    args.extend(["--dir-path".to_string(), dir_path.display().to_string()]);
+    if let Some(name) = index_name {
+        args.extend(["--index".to_string(), name.to_string()]);
+    }
+    args.extend(options);
+    args.extend(["--".to_string(), query.to_string()]);
+    SearchineCli::try_parse_from(args).unwrap_or_else(|err| err.exit())
 }
@@ -0,0 +1,95 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+use crate::cli::index::index_new_documents;
+use crate::config::NOTES_DIRNAME;
+use crate::lock::RepoLock;
+
+/// Adds a note to the managed notes store of the repository and indexes it.
+///
+/// Each note is stored as a separate text file in the notes store. If the
+/// collection and the inverted index already exist, the note is added to
+/// both immediately, so it is searchable right away. Otherwise, it is picked
+/// up the next time the repository is indexed.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `text`     - The text of the note. If `None`, it is read from stdin.
+pub fn add(repo_dir: impl AsRef<Path>, text: Option<String>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let text = match text {
+        Some(text) => text,
+        None => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read note from stdin.")?;
+            text
+        }
+    };
+    if text.trim().is_empty() {
+        println_bold!("Note is empty. Nothing was added.");
+        return Ok(());
+    }
+
+    // The lock is acquired after the note is read, so that it is not held
+    // while the note is typed.
+    let _lock = RepoLock::acquire(repo_dir)?;
+
+    // Store the note in the notes store.
+    let notes_dir = repo_dir.join(NOTES_DIRNAME);
+    std::fs::create_dir_all(&notes_dir)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let (note_path, mut file) = create_note(&notes_dir, &format!("note-{timestamp}"))
+        .context(format!("Failed to create note in: {}", notes_dir.display()))?;
+    file.write_all(text.as_bytes())
+        .context(format!("Failed to write note to: {}", note_path.display()))?;
+    let note_path = note_path.canonicalize()?;
+
+    index_new_documents(repo_dir, [note_path.clone()])?;
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x9D]).unwrap_or_default();
+    println_bold!("{emoji} Added note: {}", note_path.display());
+    Ok(())
+}
+
+/// Creates the file of a new note in the notes store, named by its stem. If
+/// a note with the same name exists, like one added in the same millisecond,
+/// a counter is appended to the stem, so that no note is overwritten.
+fn create_note(notes_dir: &Path, stem: &str) -> io::Result<(PathBuf, File)> {
+    let mut counter = 0;
+    loop {
+        let name = match counter {
+            0 => format!("{stem}.txt"),
+            _ => format!("{stem}-{counter}.txt"),
+        };
+        let path = notes_dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_note() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (first, _) = create_note(dir, "note-1").unwrap();
+        let (second, _) = create_note(dir, "note-1").unwrap();
+        let (third, _) = create_note(dir, "note-1").unwrap();
+        assert_eq!(first, dir.join("note-1.txt"));
+        assert_eq!(second, dir.join("note-1-1.txt"));
+        assert_eq!(third, dir.join("note-1-2.txt"));
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use documents::DocumentType;
+use index::collection::Collection;
+
+use crate::analyzer::{AnalyzerConfig, Stemmer};
+use crate::cli::utils::{format_size, index_size, is_indexed, write_records};
+use crate::cli::OutputFormat;
+use crate::config::{CHANNEL_BOUND, COLLECTION_FILENAME};
+use crate::fs::Directory;
+use crate::settings::Settings;
+
+/// The average number of bytes of text per term.
+const BYTES_PER_TERM: f64 = 6.0;
+/// The number of terms of source code per term of text of the same length,
+/// as identifiers are split into their sub-terms.
+const CODE_TERMS_FACTOR: f64 = 1.3;
+/// The number of terms of source code per term of the text, when cased
+/// identifiers are indexed in their own field too.
+const CASED_TERMS_FACTOR: f64 = 1.5;
+/// The number of bytes of the segments of the index per term.
+const INDEX_BYTES_PER_TERM: f64 = 1.5;
+/// The number of bytes of the position maps of the document store per term.
+const POSITION_BYTES_PER_TERM: f64 = 4.0;
+/// The number of bytes of memory that the index takes per term, while it is
+/// built.
+const MEMORY_BYTES_PER_TERM: f64 = 12.0;
+/// The slowdown of the analysis of documents when their terms are stemmed.
+const STEMMING_SLOWDOWN: f64 = 1.3;
+
+/// The model of the cost of a type of documents, per byte of their files.
+struct Cost {
+    /// The number of bytes of text that is extracted per byte of a file.
+    text_ratio: f64,
+    /// The number of megabytes of files that are extracted and analyzed per
+    /// second.
+    mb_per_sec: f64,
+}
+
+/// Returns the name and the cost of a type of documents. The text of
+/// binary formats is a small part of their files, and it is slow to extract.
+fn cost(document_type: &DocumentType) -> (&'static str, Cost) {
+    let (name, text_ratio, mb_per_sec) = match document_type {
+        DocumentType::Text => ("text", 1.0, 20.0),
+        DocumentType::Code => ("code", 1.0, 15.0),
+        DocumentType::Xml => ("xml", 0.6, 10.0),
+        DocumentType::Calendar => ("calendar", 0.5, 10.0),
+        DocumentType::Contact => ("contact", 0.5, 10.0),
+        DocumentType::Email => ("email", 0.7, 10.0),
+        DocumentType::Pdf => ("pdf", 0.1, 1.0),
+        DocumentType::Docx => ("docx", 0.3, 4.0),
+        DocumentType::Odt => ("odt", 0.3, 4.0),
+    };
+    let cost = Cost {
+        text_ratio,
+        mb_per_sec,
+    };
+    (name, cost)
+}
+
+/// The documents of a type in a plan, as they are written in the JSON
+/// output.
+#[derive(Debug, PartialEq, Serialize)]
+struct TypePlan {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    documents: u64,
+    bytes: u64,
+}
+
+/// The estimates of the resources that a full build of the index of a
+/// repository needs, as they are written in the JSON output.
+#[derive(Debug, Serialize)]
+struct BuildPlan {
+    documents: u64,
+    /// The size of the files of the documents, in bytes.
+    bytes: u64,
+    types: Vec<TypePlan>,
+    /// The size of the text that is extracted from the documents.
+    text_bytes: u64,
+    /// The number of terms of the documents, in all their fields.
+    terms: u64,
+    /// The size of the segments of the index.
+    index_bytes: u64,
+    /// The size of the document store, with the text and the position maps
+    /// of the documents.
+    store_bytes: u64,
+    /// The memory that the index takes while it is built.
+    memory_bytes: u64,
+    /// The time that the build takes, in seconds, without embedding.
+    seconds: f64,
+    /// Whether the size of the index is calibrated with the current index.
+    calibrated: bool,
+    /// Whether the documents are embedded for semantic search too.
+    embedded: bool,
+}
+
+impl BuildPlan {
+    /// Estimates the resources that indexing files of paths and sizes needs,
+    /// with an analyzer. The files of types that are not supported are
+    /// skipped. The index is not calibrated.
+    fn estimate(files: &[(PathBuf, u64)], analyzer: &AnalyzerConfig) -> Self {
+        let mut types = BTreeMap::<&str, TypePlan>::new();
+        let (mut text_bytes, mut terms, mut seconds) = (0.0, 0.0, 0.0);
+        let mut documents = 0;
+        for (path, bytes) in files {
+            let Some(document_type) = DocumentType::from_path(path) else {
+                continue;
+            };
+            documents += 1;
+            let (kind, cost) = cost(&document_type);
+            let plan = types.entry(kind).or_insert(TypePlan {
+                kind,
+                documents: 0,
+                bytes: 0,
+            });
+            plan.documents += 1;
+            plan.bytes += bytes;
+
+            let text = *bytes as f64 * cost.text_ratio;
+            let terms_factor = match analyzer.is_code(path) {
+                true if analyzer.is_cased() => CODE_TERMS_FACTOR * CASED_TERMS_FACTOR,
+                true => CODE_TERMS_FACTOR,
+                false if analyzer.cased_terms => CASED_TERMS_FACTOR,
+                false => 1.0,
+            };
+            text_bytes += text;
+            terms += text / BYTES_PER_TERM * terms_factor;
+            seconds += *bytes as f64 / (cost.mb_per_sec * 1024.0 * 1024.0) * terms_factor;
+        }
+        if analyzer.stemmer != Stemmer::None {
+            seconds *= STEMMING_SLOWDOWN;
+        }
+        // The documents that are in the channels of the pipeline are held in
+        // memory along with the index.
+        let in_flight = match documents {
+            0 => 0.0,
+            _ => text_bytes / documents as f64 * (2 * CHANNEL_BOUND).min(documents) as f64,
+        };
+        Self {
+            documents: documents as u64,
+            bytes: types.values().map(|plan| plan.bytes).sum(),
+            types: types.into_values().collect(),
+            text_bytes: text_bytes as u64,
+            terms: terms as u64,
+            index_bytes: (terms * INDEX_BYTES_PER_TERM) as u64,
+            store_bytes: (text_bytes + terms * POSITION_BYTES_PER_TERM) as u64,
+            memory_bytes: (terms * MEMORY_BYTES_PER_TERM + in_flight) as u64,
+            seconds,
+            calibrated: false,
+            embedded: false,
+        }
+    }
+}
+
+/// Estimates the resources that a full build of the index of a repository
+/// needs, so that the filters and the analyzer can be tuned before a long
+/// build: the number and the size of the documents, by type, the size of
+/// their text and their number of terms, the size of the index and of the
+/// document store, the memory that the index takes while it is built, and
+/// the time that the build takes.
+///
+/// The files of the directory are listed with the filters of the
+/// repository, and only their sizes are read, so the plan is fast. The
+/// estimates come from a model of the cost of each type of documents, with
+/// the analyzer of the config. If the repository has been indexed with the
+/// same analyzer, the size of the index is calibrated with the size of the
+/// current index. The time of the build does not include embedding the
+/// documents, which depends on the embedding provider.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `output`   - The format the plan is written in.
+pub fn invoke(repo_dir: impl AsRef<Path>, output: OutputFormat) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let settings = Settings::load(repo_dir)?;
+    let files = Directory::for_repo(repo_dir)?
+        .iter_full_paths(false)
+        .filter_map(|path| {
+            let bytes = path.metadata().ok()?.len();
+            Some((path, bytes))
+        })
+        .collect::<Vec<_>>();
+    let mut plan = BuildPlan::estimate(&files, &settings.analyzer);
+    if let Some(ratio) = calibration(repo_dir, &settings.analyzer)? {
+        plan.index_bytes = (plan.index_bytes as f64 * ratio) as u64;
+        plan.calibrated = true;
+    }
+    plan.embedded = settings.embedder.is_some();
+    match output {
+        OutputFormat::Text => display_plan(&plan)?,
+        _ => write_records(&[plan], output)?,
+    }
+    Ok(())
+}
+
+/// Returns the ratio of the size of the current index of a repository to
+/// its estimate, from the sizes of the files that were indexed, or `None`
+/// if the repository has not been indexed, or it was indexed with another
+/// analyzer.
+fn calibration(repo_dir: &Path, analyzer: &AnalyzerConfig) -> anyhow::Result<Option<f64>> {
+    if !is_indexed(repo_dir) || analyzer.check(repo_dir).is_err() {
+        return Ok(None);
+    }
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let files = collection
+        .into_iter()
+        .filter_map(|(path, entry)| Some((path, entry.size()?)))
+        .collect::<Vec<_>>();
+    let estimate = BuildPlan::estimate(&files, analyzer).index_bytes;
+    match estimate {
+        0 => Ok(None),
+        _ => Ok(Some(index_size(repo_dir)? as f64 / estimate as f64)),
+    }
+}
+
+/// Writes the plan to stdout as a table, with the documents of each type.
+fn display_plan(plan: &BuildPlan) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    writeln!(
+        tw,
+        "Documents:\t{} ({})",
+        plan.documents,
+        format_size(plan.bytes)
+    )?;
+    for kind in &plan.types {
+        writeln!(
+            tw,
+            "  {}\t{} ({})",
+            kind.kind,
+            kind.documents,
+            format_size(kind.bytes)
+        )?;
+    }
+    writeln!(
+        tw,
+        "Text:\t~{} in ~{} terms",
+        format_size(plan.text_bytes),
+        plan.terms
+    )?;
+    let calibrated = match plan.calibrated {
+        true => " (calibrated with the current index)",
+        false => "",
+    };
+    writeln!(
+        tw,
+        "Index size:\t~{}{calibrated}",
+        format_size(plan.index_bytes)
+    )?;
+    writeln!(tw, "Document store:\t~{}", format_size(plan.store_bytes))?;
+    writeln!(tw, "Memory:\t~{}", format_size(plan.memory_bytes))?;
+    writeln!(tw, "Build time:\t~{}", format_duration(plan.seconds))?;
+    tw.flush()?;
+    if plan.embedded {
+        println!("The documents are embedded too, which the build time does not include.");
+    }
+    Ok(())
+}
+
+/// Formats a number of seconds in the largest unit that fits, like
+/// `12 minutes`, rounding up to a second.
+fn format_duration(seconds: f64) -> String {
+    const UNITS: [(&str, f64); 3] = [("hour", 3_600.0), ("minute", 60.0), ("second", 1.0)];
+    let seconds = seconds.ceil().max(1.0);
+    let (unit, length) = UNITS
+        .into_iter()
+        .find(|(_, length)| seconds >= *length)
+        .unwrap_or(UNITS[2]);
+    let n = (seconds / length).round();
+    let plural = if n == 1.0 { "" } else { "s" };
+    format!("{n} {unit}{plural}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let files = [
+            ("notes.txt", 6_000),
+            ("report.md", 6_000),
+            ("main.rs", 6_000),
+            ("paper.pdf", 60_000),
+            ("image.png", 1_000),
+        ]
+        .map(|(path, bytes)| (PathBuf::from(path), bytes));
+        let analyzer = AnalyzerConfig {
+            stemmer: Stemmer::None,
+            ..Default::default()
+        };
+        let plan = BuildPlan::estimate(&files, &analyzer);
+        assert_eq!(plan.documents, 4);
+        assert_eq!(plan.bytes, 78_000);
+        let types = plan
+            .types
+            .iter()
+            .map(|kind| (kind.kind, kind.documents))
+            .collect::<Vec<_>>();
+        assert_eq!(types, [("code", 1), ("pdf", 1), ("text", 2)]);
+        assert_eq!(plan.text_bytes, 24_000);
+        assert_eq!(plan.terms, 4_300);
+
+        // Cased identifiers add terms to source code, and stemming is slower.
+        let analyzer = AnalyzerConfig {
+            cased_identifiers: true,
+            ..Default::default()
+        };
+        let cased = BuildPlan::estimate(&files, &analyzer);
+        assert!(cased.terms > plan.terms);
+        assert!(cased.seconds > plan.seconds);
+        assert_eq!(BuildPlan::estimate(&[], &analyzer).memory_bytes, 0);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0.2), "1 second");
+        assert_eq!(format_duration(45.0), "45 seconds");
+        assert_eq!(format_duration(725.0), "12 minutes");
+        assert_eq!(format_duration(9_000.0), "3 hours");
+    }
+}
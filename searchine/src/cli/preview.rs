@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use documents::archive;
+use index::collection::Collection;
+use index::store::DocumentStore;
+
+use crate::config::{COLLECTION_FILENAME, STORE_DIRNAME};
+use crate::highlight::{find_matches, highlight};
+use crate::settings::Settings;
+
+/// Previews the stored text of an indexed document.
+///
+/// The text that was extracted from the document when it was indexed is
+/// printed, so the document does not have to be extracted again. If a query
+/// is given, the terms that match it are highlighted, and their byte offsets
+/// in the text are listed.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `doc_path` - The path to the indexed document.
+/// * `query`    - An optional free text query, whose matches are highlighted.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    doc_path: impl AsRef<Path>,
+    query: Option<&str>,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let doc_path = doc_path.as_ref();
+    let doc_path = archive::canonicalize(doc_path)
+        .context(format!("Failed to find document: {}", doc_path.display()))?;
+
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let doc_id = collection
+        .get_document_id(&doc_path)
+        .context(format!("Document {} is not indexed.", doc_path.display()))?;
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let text = store.read(doc_id).context(format!(
+        "Text of {} is not stored. Run `searchine index` to re-index the repository.",
+        doc_path.display()
+    ))?;
+
+    let analyzer = Settings::load(repo_dir)?.analyzer;
+    let matches = match query {
+        Some(query) => {
+            let terms = analyzer.tokenize_query(query, false);
+            find_matches(&text, &doc_path, &terms, &analyzer)
+        }
+        None => vec![],
+    };
+
+    println_bold!("{} (document ID: {})\n", doc_path.display(), doc_id);
+    println!("{}\n", highlight(&text, &matches));
+    if query.is_some() {
+        let offsets = matches
+            .iter()
+            .map(|span| format!("{}..{}", span.start, span.end))
+            .collect::<Vec<_>>();
+        println_bold!(
+            "{} matches at byte offsets: {}",
+            matches.len(),
+            offsets.join(", ")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use regex::Regex;
+
+use documents::archive::source_path;
+use index::collection::Collection;
+use tokenize::Token;
+
+use crate::cli::index::{apply_changes, Changes};
+use crate::cli::related::update_related;
+use crate::cli::utils::{
+    format_size, index_format, index_size, load_manifest, load_segment, merge_small_segments,
+    prune_index,
+};
+use crate::config::COLLECTION_FILENAME;
+
+/// Prunes the index of a repository: the documents whose files have been
+/// deleted, and useless terms, like hashes and other one-off junk, which
+/// take space but are never searched for.
+///
+/// # Arguments
+///
+/// * `repo_dir`       - The path to the searchine repository.
+/// * `df_below`       - The document frequency below which terms are pruned.
+/// * `terms_matching` - The regular expression of the terms to prune.
+/// * `deleted`        - Whether to prune the documents whose files have been
+///   deleted.
+/// * `dry_run`        - Whether to only report what would be pruned, without
+///   pruning it.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    df_below: Option<u32>,
+    terms_matching: Option<String>,
+    deleted: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    if df_below.is_none() && terms_matching.is_none() && !deleted {
+        anyhow::bail!(
+            "Nothing to prune. Use `--deleted` to select documents, or `--df-below` or \
+             `--terms-matching` to select terms."
+        );
+    }
+    if deleted {
+        prune_deleted(repo_dir, dry_run)?;
+    }
+    if df_below.is_some() || terms_matching.is_some() {
+        prune_terms(repo_dir, df_below, terms_matching, dry_run)?;
+    }
+    Ok(())
+}
+
+/// Removes the documents whose files have been deleted from the collection,
+/// the document store and the index of a repository, so they stop appearing
+/// in the results without indexing the repository again.
+fn prune_deleted(repo_dir: &Path, dry_run: bool) -> anyhow::Result<()> {
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let mut removed = (&collection)
+        .into_iter()
+        .filter(|(path, _)| !source_path(path).exists())
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<PathBuf>>();
+    removed.sort();
+    if removed.is_empty() {
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+        println_bold!("{emoji} No deleted documents to prune.");
+        return Ok(());
+    }
+
+    if dry_run {
+        for path in &removed {
+            println_red!("  deleted: {}", path.display());
+        }
+        println_bold!("Would prune {} deleted documents.", removed.len());
+        println_bold!("  (run without \"--dry-run\" to prune them)");
+        return Ok(());
+    }
+
+    let changes = Changes {
+        removed,
+        ..Changes::default()
+    };
+    let format = index_format(repo_dir);
+    apply_changes(repo_dir, collection, &changes, format, false)?;
+    merge_small_segments(repo_dir, format)?;
+    let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x82]).unwrap_or_default();
+    println_bold!(
+        "{emoji} Pruned {} deleted documents.",
+        changes.removed.len()
+    );
+    Ok(())
+}
+
+/// Removes useless terms from the dictionary and the postings of the index
+/// of a repository.
+///
+/// A term is pruned if it is in fewer documents than `df_below`, or if the
+/// whole term matches the `terms_matching` regular expression. The document
+/// frequencies are counted over all the segments of the index. Pruned terms
+/// come back when the documents that contain them are indexed again.
+fn prune_terms(
+    repo_dir: &Path,
+    df_below: Option<u32>,
+    terms_matching: Option<String>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let pattern = terms_matching
+        .map(|pattern| {
+            // The pattern is anchored, so that it matches whole terms.
+            Regex::new(&format!("^(?:{pattern})$"))
+                .context(format!("Invalid regular expression: {pattern}"))
+        })
+        .transpose()?;
+
+    let frequencies = document_frequencies(repo_dir)?;
+    let mut pruned = frequencies
+        .into_iter()
+        .filter(|(term, df)| {
+            df_below.is_some_and(|df_below| *df < df_below)
+                || pattern
+                    .as_ref()
+                    .is_some_and(|pattern| pattern.is_match(term))
+        })
+        .collect::<Vec<_>>();
+    pruned.sort();
+    if pruned.is_empty() {
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+        println_bold!("{emoji} No terms to prune.");
+        return Ok(());
+    }
+    let n_postings = pruned.iter().map(|(_, df)| *df as usize).sum::<usize>();
+
+    if dry_run {
+        let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x82]).unwrap_or_default();
+        for (term, df) in &pruned {
+            println_red!("  {emoji} {term} (in {df} documents)");
+        }
+        println_bold!(
+            "Would prune {} terms and {n_postings} postings.",
+            pruned.len()
+        );
+        println_bold!("  (run without \"--dry-run\" to prune them)");
+        return Ok(());
+    }
+
+    let size_before = index_size(repo_dir)?;
+    let terms = pruned
+        .into_iter()
+        .map(|(term, _)| term)
+        .collect::<HashSet<_>>();
+    let n_postings = prune_index(repo_dir, &terms)?;
+    update_related(repo_dir);
+    let size_after = index_size(repo_dir)?;
+
+    let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x82]).unwrap_or_default();
+    println_bold!(
+        "{emoji} Pruned {} terms and {n_postings} postings, saving {} ({} -> {}).",
+        terms.len(),
+        format_size(size_before.saturating_sub(size_after)),
+        format_size(size_before),
+        format_size(size_after),
+    );
+    Ok(())
+}
+
+/// Counts the number of documents that contain each term, over all the
+/// segments of the index.
+fn document_frequencies(repo_dir: &Path) -> anyhow::Result<HashMap<Token, u32>> {
+    let manifest = load_manifest(repo_dir)?;
+    let mut frequencies = HashMap::new();
+    for name in manifest.segments() {
+        let segment = load_segment(repo_dir, &manifest, name)?;
+        for (term, df) in segment.document_frequencies() {
+            *frequencies.entry(term.clone()).or_insert(0) += df;
+        }
+    }
+    Ok(frequencies)
+}
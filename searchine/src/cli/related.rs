@@ -0,0 +1,170 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use index::cooccur::CooccurrenceSketch;
+use index::inverted::Index;
+use tokenize::{CASED_PREFIX, TITLE_PREFIX};
+
+use crate::cli::utils::{load_manifest, open_segments, write_records};
+use crate::cli::OutputFormat;
+use crate::config::{RELATED_FILENAME, RELATED_MIN_DOCS, RELATED_SKETCH_SIZE};
+use crate::settings::Settings;
+
+/// A term that is related to another term, as it is written in the JSON
+/// output.
+#[derive(Debug, PartialEq, Serialize)]
+struct Related {
+    term: String,
+    /// The estimated number of documents that have both terms.
+    documents: u32,
+    /// The estimated Jaccard similarity of the documents of the terms.
+    similarity: f64,
+}
+
+/// Builds the co-occurrence sketch of the index of a repository again, from
+/// the postings of its terms. Errors are printed rather than returned, so
+/// the sketch cannot stop the index from being updated.
+///
+/// Only the terms of the text that are in at least `RELATED_MIN_DOCS`
+/// documents are sketched, as the terms of a single document co-occur with
+/// all its other terms, and they are most of the vocabulary.
+pub fn update_related(repo_dir: impl AsRef<Path>) {
+    let repo_dir = repo_dir.as_ref();
+    if let Err(err) = try_update_related(repo_dir) {
+        eprintln!("ERROR: Failed to update co-occurrence sketch because: {err:#}");
+    }
+}
+
+fn try_update_related(repo_dir: &Path) -> anyhow::Result<()> {
+    let manifest = load_manifest(repo_dir)?;
+    let index = open_segments(repo_dir, &manifest)?;
+    build_sketch(&index).into_file(repo_dir.join(RELATED_FILENAME))?;
+    Ok(())
+}
+
+/// Sketches the documents of the terms of the text of an index.
+fn build_sketch(index: &dyn Index) -> CooccurrenceSketch {
+    let mut sketch = CooccurrenceSketch::new(RELATED_SKETCH_SIZE);
+    for term in index.terms() {
+        if term.starts_with([TITLE_PREFIX, CASED_PREFIX])
+            || index.n_docs_containing(&term) < RELATED_MIN_DOCS
+        {
+            continue;
+        }
+        let doc_ids = index.doc_ids_containing(&term);
+        sketch.insert(term, &doc_ids);
+    }
+    sketch
+}
+
+/// Lists the terms that co-occur the most with a term in the documents of a
+/// repository, to discover the vocabulary that a query can be refined with.
+///
+/// The term is analyzed like the terms of a query, and the `top_k` terms
+/// whose documents are the most similar to its documents are listed, with
+/// the estimated number of documents that have both. The estimates come from
+/// the co-occurrence sketch that is built when the repository is indexed,
+/// so the postings are not read. The terms are the ones of the index, so
+/// they may be stemmed.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `term`     - The term to find the related terms of.
+/// * `top_k`    - The number of terms to list.
+/// * `output`   - The format the terms are written in.
+///
+/// # Errors
+///
+/// Returns an error if the term is not a single word, or if the sketch has
+/// not been built, or cannot be read.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    term: &str,
+    top_k: usize,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let settings = Settings::load(repo_dir)?;
+    settings.analyzer.check(repo_dir)?;
+    let tokens = settings.analyzer.tokenizer().tokenize(term);
+    let [token] = tokens.as_slice() else {
+        anyhow::bail!("`{term}` is not a single term.");
+    };
+    let sketch_path = repo_dir.join(RELATED_FILENAME);
+    if !sketch_path.exists() {
+        anyhow::bail!("Co-occurrence sketch has not been built. Run `searchine index` first.");
+    }
+    let sketch = CooccurrenceSketch::from_file(sketch_path)?;
+    if !sketch.contains(token) {
+        println_bold!(
+            "Term `{token}` is in fewer than {} documents, so it has no related terms.",
+            RELATED_MIN_DOCS
+        );
+        return Ok(());
+    }
+    let related = sketch
+        .related(token, top_k)
+        .into_iter()
+        .map(|related| Related {
+            term: related.term,
+            documents: related.n_docs,
+            similarity: related.similarity,
+        })
+        .collect::<Vec<_>>();
+    match output {
+        OutputFormat::Text => display_related(&related)?,
+        _ => write_records(&related, output)?,
+    }
+    Ok(())
+}
+
+/// Writes the related terms to stdout as a table.
+fn display_related(related: &[Related]) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    for related in related {
+        writeln!(
+            tw,
+            "{}\t{}\t{:.2}",
+            related.term, related.documents, related.similarity
+        )?;
+    }
+    tw.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_build_sketch() {
+        let mut index = FrequencyIndex::new();
+        let documents = [
+            (0, vec!["budget", "quarterly", "^budget"]),
+            (1, vec!["budget", "quarterly", "report"]),
+            (2, vec!["budget", "meeting"]),
+            (3, vec!["meeting"]),
+        ];
+        for (doc_id, tokens) in documents {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            index.index(doc_index);
+        }
+
+        let sketch = build_sketch(&index);
+        assert_eq!(sketch.len(), 3);
+        assert!(!sketch.contains("report"));
+        assert!(!sketch.contains("^budget"));
+        let related = sketch.related("budget", 10);
+        let terms = related.iter().map(|related| related.term.as_str());
+        assert_eq!(terms.collect::<Vec<_>>(), ["quarterly", "meeting"]);
+        assert_eq!(related[0].n_docs, 2);
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use documents::archive;
+use index::collection::Collection;
+
+use crate::cli::index::{apply_changes, Changes};
+use crate::cli::utils::{compact_index, index_format, merge_small_segments};
+use crate::config::COLLECTION_FILENAME;
+
+/// Removes a document from the collection, the document store and the index
+/// of a repository, so it stops appearing in the results at once, without
+/// indexing the repository again.
+///
+/// The document is marked as deleted in the segment that contains it, and
+/// then the segments with deleted documents are compacted, so that its
+/// postings do not stay on disk until the next merge. The file of the
+/// document is left as it is, so if it still exists and is not ignored, the
+/// next `searchine index` adds it again.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `doc_path` - The path to the document, whose file may have been deleted.
+///
+/// # Errors
+///
+/// Returns an error if the document is not in the collection.
+pub fn invoke(repo_dir: impl AsRef<Path>, doc_path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let doc_path = resolve(doc_path.as_ref())?;
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    if !collection.contains_path(&doc_path) {
+        anyhow::bail!("Document {} is not indexed.", doc_path.display());
+    }
+
+    let changes = Changes {
+        removed: vec![doc_path.clone()],
+        ..Changes::default()
+    };
+    let format = index_format(repo_dir);
+    apply_changes(repo_dir, collection, &changes, format, false)?;
+    compact_index(repo_dir)?;
+    merge_small_segments(repo_dir, format)?;
+    let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x82]).unwrap_or_default();
+    println_bold!("{emoji} Removed {} from the index.", doc_path.display());
+    Ok(())
+}
+
+/// Resolves the path to a document to the absolute path that it is kept by
+/// in the collection. The file of the document may have been deleted, so
+/// only its directory has to exist.
+fn resolve(doc_path: &Path) -> anyhow::Result<PathBuf> {
+    if let Ok(path) = archive::canonicalize(doc_path) {
+        return Ok(path);
+    }
+    let not_found = || format!("Failed to find document: {}", doc_path.display());
+    let name = doc_path.file_name().with_context(not_found)?;
+    let dir = match doc_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Ok(dir.canonicalize().with_context(not_found)?.join(name))
+}
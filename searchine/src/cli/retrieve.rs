@@ -0,0 +1,171 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use documents::DocumentId;
+use index::positions::PositionMap;
+use index::store::DocumentStore;
+
+use crate::cli::search::{score_documents, DocumentBoosts};
+use crate::config::{PASSAGE_CANDIDATES_FACTOR, STORE_DIRNAME};
+use crate::highlight::Offsets;
+use crate::passage::{non_overlapping, score_passages, PassageConfig};
+use crate::settings::Settings;
+use crate::snapshot::Snapshot;
+
+/// The version of the schema of the exported chunks. It is increased whenever
+/// a field is removed or its meaning changes, so that pipelines can detect
+/// output they do not understand.
+pub const CHUNK_SCHEMA_VERSION: u32 = 1;
+
+/// The chunks that were retrieved for a query.
+#[derive(Debug, Serialize)]
+pub struct ChunkExport {
+    pub schema_version: u32,
+    pub query: String,
+    pub chunks: Vec<Chunk>,
+}
+
+/// A passage of a document, with everything a retrieval-augmented generation
+/// pipeline needs to put it in a prompt and to cite its source.
+#[derive(Debug, Serialize)]
+pub struct Chunk {
+    pub rank: usize,
+    pub score: f64,
+    pub doc_id: DocumentId,
+    pub path: PathBuf,
+    pub text: String,
+    pub offsets: Offsets,
+    pub lines: Lines,
+}
+
+/// The range of lines of a chunk in its document, starting from 1, with the
+/// end inclusive.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Lines {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Lines {
+    /// Finds the lines of a text that a byte range spans, from the position
+    /// map of the text.
+    fn of(positions: &PositionMap, offsets: &Offsets) -> Self {
+        Self {
+            start: positions.line_of(offsets.byte_start),
+            end: positions.line_of(offsets.byte_end),
+        }
+    }
+}
+
+/// Retrieves the `top_k` most relevant passages to the free text `query`.
+///
+/// The best candidate documents are found by scoring whole documents, and
+/// then their passages are scored. Several passages of the same document can
+/// be retrieved, as long as they do not overlap. The query is analyzed, and
+/// the documents are scored, as configured in the config of the repository.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `query`    - The free text query.
+/// * `top_k`    - The number of passages to retrieve.
+/// * `config`   - The passage policy.
+pub fn retrieve(
+    repo_dir: impl AsRef<Path>,
+    query: &str,
+    top_k: u32,
+    config: &PassageConfig,
+) -> anyhow::Result<ChunkExport> {
+    let repo_dir = repo_dir.as_ref();
+    let settings = Settings::load(repo_dir)?;
+    let analyzer = &settings.analyzer;
+    analyzer.check(repo_dir)?;
+    let query_terms = analyzer.tokenize_query(query, false);
+
+    // The index and the collection are read from a snapshot, so that they
+    // stay consistent if the repository is updated during the retrieval.
+    let Snapshot {
+        index,
+        inv_collection,
+        ..
+    } = Snapshot::open(repo_dir)?;
+    let mut scores = score_documents(index.as_ref(), &query_terms, &settings.search, None);
+    if let Some(boosts) = DocumentBoosts::new(repo_dir, &settings.search)? {
+        boosts.apply(&mut scores, &inv_collection);
+    }
+    let candidates = scores.get_top_n(top_k.saturating_mul(PASSAGE_CANDIDATES_FACTOR));
+
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let mut chunks = vec![];
+    for (doc_id, _) in candidates {
+        let Some(path) = inv_collection.get_path(*doc_id) else {
+            continue;
+        };
+        let Ok(text) = store.read(*doc_id) else {
+            continue;
+        };
+        // Documents that were indexed before position maps were stored only
+        // have the lines of their text mapped.
+        let positions = store
+            .read_positions(*doc_id)
+            .unwrap_or_else(|_| PositionMap::new(&text, []));
+        let passages = score_passages(&text, path, &query_terms, index.as_ref(), analyzer, config);
+        for passage in non_overlapping(passages, top_k as usize) {
+            let lines = Lines::of(&positions, &passage.snippet.offsets);
+            chunks.push(Chunk {
+                rank: 0,
+                score: passage.score,
+                doc_id: *doc_id,
+                path: path.clone(),
+                text: passage.snippet.text,
+                offsets: passage.snippet.offsets,
+                lines,
+            });
+        }
+    }
+    chunks.sort_by(|a, b| b.score.total_cmp(&a.score));
+    chunks.truncate(top_k as usize);
+    for (rank, chunk) in chunks.iter_mut().enumerate() {
+        chunk.rank = rank;
+    }
+
+    Ok(ChunkExport {
+        schema_version: CHUNK_SCHEMA_VERSION,
+        query: query.to_string(),
+        chunks,
+    })
+}
+
+/// Retrieves the `top_k` most relevant passages to the free text `query`, and
+/// writes them to stdout as JSON.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    query: &str,
+    top_k: u32,
+    config: &PassageConfig,
+) -> anyhow::Result<()> {
+    let export = retrieve(repo_dir, query, top_k, config)?;
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer_pretty(&mut stdout, &export)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::highlight::offsets;
+
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let text = "First line.\nSecond line.\nThird line.\nFourth line.";
+        let spans = offsets(text, &[0..5, 19..30]);
+        let positions = PositionMap::new(text, []);
+        assert_eq!(Lines::of(&positions, &spans[1]), Lines { start: 2, end: 3 });
+        assert_eq!(Lines::of(&positions, &spans[0]), Lines { start: 1, end: 1 });
+    }
+}
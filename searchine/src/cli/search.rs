@@ -1,57 +1,2159 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{self, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use index::collection::InvertedCollection;
-use index::inverted::freq::FrequencyIndex;
+use anyhow::Context;
+use ignore::overrides::{Override, OverrideBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use documents::archive::source_path;
+use documents::table::TableReader;
+use documents::DocumentId;
+use embed::Embedder;
+use index::collection::{Collection, CollectionEntry, InvertedCollection};
+use index::fingerprint::Fingerprint;
 use index::inverted::Index;
 use index::score::*;
-use query::Query;
-use tokenize::Tokenizer;
+use index::store::DocumentStore;
+use query::{HybridQuery, Phrase, Query, SemanticClause};
+use tokenize::{Token, TITLE_PREFIX};
+
+use crate::analyzer::AnalyzerConfig;
+use crate::cache::QueryCache;
+use crate::cli::semantic;
+use crate::cli::utils::{fetch_modified_files, fetch_new_files, parse_date, write_records};
+use crate::cli::{OutputFormat, SortOrder};
+use crate::config::{
+    BM25_B, BM25_K, BODY_BOOST, COLLECTION_FILENAME, DEDUP_CANDIDATES_FACTOR, DUPES_MAX_DISTANCE,
+    EXPAND_DOCS, HYBRID_SEMANTIC_WEIGHT, PASSAGE_CANDIDATES_FACTOR, PROXIMITY_CANDIDATES_FACTOR,
+    SEARCH_TOP_N, SECONDS_PER_DAY, STORE_DIRNAME, TITLE_BOOST,
+};
+use crate::feedback::expansion_terms;
+use crate::fs::roots::relative_to_root;
+use crate::fs::{CorpusRoots, Directory, ExtractorCommands, RowFiles};
+use crate::grep::{LineMatch, LineMatcher};
+use crate::highlight::{find_matches, offsets, Marker, Offsets};
+use crate::importance::Importance;
+use crate::passage::{best_passages, score_passages, Passage, PassageConfig};
+use crate::pattern::{expand_patterns, split_patterns};
+use crate::phrase::phrase_matches;
+use crate::proximity::proximity;
+use crate::relax::{Relaxation, RelaxedQuery};
+use crate::settings::Settings;
+use crate::snapshot::Snapshot;
+use crate::snippet::{join_highlighted, snippets, Snippet, SnippetConfig};
+use crate::spelling::{self, Suggestion};
+use crate::synonyms::Thesaurus;
+
+/// The function that scores documents against the terms of a query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scorer {
+    /// The sum of the TF-IDF of the terms.
+    #[default]
+    TfIdf,
+    /// The sum of the BM25 of the terms, which saturates the frequency of a
+    /// term and normalizes it by the length of the document.
+    Bm25,
+}
+
+/// The configuration of searching, read from the `[search]` section of the
+/// config of a repository.
+///
+/// ```toml
+/// [search]
+/// scorer = "bm25"
+/// top_n = 20
+/// title_boost = 3.0
+/// body_boost = 1.0
+/// recency_half_life_days = 30
+/// path_boosts = { "docs/**" = 2.0, "archive/**" = 0.5 }
+/// link_weight = 0.5
+/// proximity_weight = 1.0
+/// canonical_paths = [
+///     { pattern = "^build/", replacement = "" },
+///     { pattern = "^dist/", replacement = "src/" },
+/// ]
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// The function that scores the documents. It is TF-IDF by default.
+    pub scorer: Scorer,
+    /// The number of results of a search, if it is not given.
+    pub top_n: u32,
+    /// The weight of the matches in the titles of the documents, the names
+    /// of their files, in their scores. It is 3 by default.
+    pub title_boost: f64,
+    /// The weight of the matches in the text of the documents in their
+    /// scores. It is 1 by default.
+    pub body_boost: f64,
+    /// The number of days since a document was last modified after which
+    /// its score is halved, so that recent documents rank higher. Scores do
+    /// not decay by default.
+    pub recency_half_life_days: Option<f64>,
+    /// The weights of the documents whose paths, relative to the directory,
+    /// match globs, which their scores are multiplied by. A document that
+    /// matches several globs is weighted by all of them.
+    pub path_boosts: BTreeMap<String, f64>,
+    /// The exponent of the importance of the documents, estimated from the
+    /// links between them, which their scores are multiplied by, so that
+    /// the documents that are linked to the most rank higher. The average
+    /// importance is 1, so with a weight of 0.5, a document that is four
+    /// times as important as the average scores twice as much. It is 0 by
+    /// default, which weights every document the same.
+    pub link_weight: f64,
+    /// The weight of the proximity of the terms of a query in the text of
+    /// the documents. The score of a document is multiplied by one plus the
+    /// weight times the proximity, which is 1 when the terms follow each
+    /// other, and close to 0 when they are scattered. It is 0 by default,
+    /// which does not weight the proximity.
+    pub proximity_weight: f64,
+    /// The rules that map the paths of copies of documents, like build
+    /// outputs, to the paths of the documents they are copies of, so that
+    /// only the best of them is a result. There are none by default.
+    pub canonical_paths: Vec<CanonicalPath>,
+}
+
+/// A rule that rewrites the paths of documents, relative to the directory,
+/// into canonical paths. The documents with the same canonical path are
+/// variants of the same document.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CanonicalPath {
+    /// The regular expression that the paths are matched against.
+    pub pattern: String,
+    /// The replacement of the matches, which can refer to the groups of the
+    /// pattern, like `$1`. It is empty by default.
+    #[serde(default)]
+    pub replacement: String,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            scorer: Scorer::default(),
+            top_n: SEARCH_TOP_N,
+            title_boost: TITLE_BOOST,
+            body_boost: BODY_BOOST,
+            recency_half_life_days: None,
+            path_boosts: BTreeMap::new(),
+            link_weight: 0.0,
+            proximity_weight: 0.0,
+            canonical_paths: vec![],
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Returns the boost of the field of a term, which its scores are
+    /// multiplied by. The case-sensitive field is scored like the text.
+    pub fn boost(&self, term: &str) -> f64 {
+        match term.starts_with(TITLE_PREFIX) {
+            true => self.title_boost,
+            false => self.body_boost,
+        }
+    }
+}
+
+/// A named ranking profile, read from a `[profiles.<name>]` section of the
+/// config of a repository, which is selected with `--profile`. The settings
+/// that a profile sets override the ones of the `[search]` section, so that
+/// the relevance of a search can be switched as a whole.
+///
+/// ```toml
+/// [profiles.code]
+/// scorer = "bm25"
+/// title_boost = 5.0
+/// path_boosts = { "src/**" = 2.0, "tests/**" = 0.5 }
+///
+/// [profiles.recent-first]
+/// recency_half_life_days = 14
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub scorer: Option<Scorer>,
+    pub top_n: Option<u32>,
+    pub title_boost: Option<f64>,
+    pub body_boost: Option<f64>,
+    pub recency_half_life_days: Option<f64>,
+    pub path_boosts: Option<BTreeMap<String, f64>>,
+    pub link_weight: Option<f64>,
+    pub proximity_weight: Option<f64>,
+    pub canonical_paths: Option<Vec<CanonicalPath>>,
+}
 
-use crate::config::{COLLECTION_FILENAME, INDEX_FILENAME};
+impl ProfileConfig {
+    /// Overrides the search config with the settings of the profile.
+    pub fn apply(&self, mut config: SearchConfig) -> SearchConfig {
+        if let Some(scorer) = self.scorer {
+            config.scorer = scorer;
+        }
+        if let Some(top_n) = self.top_n {
+            config.top_n = top_n;
+        }
+        if let Some(title_boost) = self.title_boost {
+            config.title_boost = title_boost;
+        }
+        if let Some(body_boost) = self.body_boost {
+            config.body_boost = body_boost;
+        }
+        if let Some(days) = self.recency_half_life_days {
+            config.recency_half_life_days = Some(days);
+        }
+        if let Some(path_boosts) = &self.path_boosts {
+            config.path_boosts = path_boosts.clone();
+        }
+        if let Some(link_weight) = self.link_weight {
+            config.link_weight = link_weight;
+        }
+        if let Some(proximity_weight) = self.proximity_weight {
+            config.proximity_weight = proximity_weight;
+        }
+        if let Some(canonical_paths) = &self.canonical_paths {
+            config.canonical_paths = canonical_paths.clone();
+        }
+        config
+    }
+}
+
+/// The weights of the documents that do not depend on the query, by how
+/// recently they were modified, by their paths and by the links to them,
+/// which their scores are multiplied by.
+pub struct DocumentBoosts {
+    /// The collection, with the modified times of the documents, and the
+    /// half-life of the scores in seconds, if the scores decay.
+    recency: Option<(Collection, f64)>,
+    /// The matchers of the globs of the path boosts, with their weights.
+    paths: Vec<(Override, f64)>,
+    /// The root directories of the corpus, that the globs of the path boosts
+    /// are relative to.
+    roots: Vec<PathBuf>,
+    /// The importance of the documents from the links between them, and the
+    /// weight of the link boosts, if documents are weighted by their links
+    /// and they link to each other.
+    links: Option<(Importance, f64)>,
+}
+
+impl DocumentBoosts {
+    /// Builds the weights of the documents of a repository from a search
+    /// config, or returns `None` if the config weights every document the
+    /// same.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the half-life is not positive, if the link
+    /// weight is negative, if a glob of the path boosts is invalid, or if
+    /// the collection or the importance of the documents cannot be read.
+    pub fn new(repo_dir: &Path, search: &SearchConfig) -> anyhow::Result<Option<Self>> {
+        if search.recency_half_life_days.is_none()
+            && search.path_boosts.is_empty()
+            && search.link_weight == 0.0
+        {
+            return Ok(None);
+        }
+        if search.link_weight < 0.0 {
+            anyhow::bail!(
+                "link_weight must not be negative, but it is {}.",
+                search.link_weight
+            )
+        }
+        let recency = match search.recency_half_life_days {
+            Some(days) if days <= 0.0 => {
+                anyhow::bail!("recency_half_life_days must be positive, but it is {days}.")
+            }
+            Some(days) => {
+                let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+                Some((collection, days * SECONDS_PER_DAY))
+            }
+            None => None,
+        };
+        let roots = CorpusRoots::dirs(repo_dir)?;
+        let paths = search
+            .path_boosts
+            .iter()
+            .map(|(glob, weight)| {
+                let mut builder = OverrideBuilder::new(&roots[0]);
+                builder
+                    .add(glob)
+                    .context(format!("Invalid path boost glob: {glob}"))?;
+                Ok((builder.build()?, *weight))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let links = match search.link_weight > 0.0 {
+            true => Importance::load(repo_dir)?.map(|importance| (importance, search.link_weight)),
+            false => None,
+        };
+        Ok(Some(Self {
+            recency,
+            paths,
+            roots,
+            links,
+        }))
+    }
+
+    /// Returns the weight of the document at a path. A document that is not
+    /// in the collection does not decay, and one that is not in the link
+    /// graph is of average importance.
+    pub fn boost(&self, path: &PathBuf) -> f64 {
+        self.factors(path).product()
+    }
+
+    /// Returns the weights of the document at a path by how recently it was
+    /// modified, by its path and by the links to it, which its weight is the
+    /// product of.
+    pub fn factors(&self, path: &PathBuf) -> BoostFactors {
+        let mut factors = BoostFactors::default();
+        if let Some((collection, half_life)) = &self.recency {
+            if let Some(modified) = collection.get_last_modified(path) {
+                let age = SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or_default();
+                factors.recency = 0.5_f64.powf(age.as_secs_f64() / half_life);
+            }
+        }
+        let relative = relative_to_root(&self.roots, path);
+        for (matcher, weight) in &self.paths {
+            if matcher.matched(relative, false).is_whitelist() {
+                factors.path *= weight;
+            }
+        }
+        if let Some((importance, weight)) = &self.links {
+            factors.links = importance.rank(path).powf(*weight);
+        }
+        factors
+    }
+
+    /// Multiplies the scores of documents by their weights.
+    pub fn apply(&self, scores: &mut DocumentsScores, inv_collection: &InvertedCollection) {
+        scores.scale(|doc_id| {
+            inv_collection
+                .get_path(doc_id)
+                .map_or(1.0, |path| self.boost(path))
+        });
+    }
+}
+
+/// The weights of a document that do not depend on the query. Each of them
+/// is 1 if the document is not weighted by it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BoostFactors {
+    /// The weight of the document by how recently it was modified.
+    pub recency: f64,
+    /// The product of the weights of the path boosts that match the path of
+    /// the document.
+    pub path: f64,
+    /// The weight of the document by its importance in the link graph.
+    pub links: f64,
+}
+
+impl Default for BoostFactors {
+    fn default() -> Self {
+        Self {
+            recency: 1.0,
+            path: 1.0,
+            links: 1.0,
+        }
+    }
+}
+
+impl BoostFactors {
+    /// Returns the weight of the document, the product of its weights.
+    pub fn product(&self) -> f64 {
+        self.recency * self.path * self.links
+    }
+}
+
+/// The rules of a search config that rewrite the paths of documents into
+/// canonical paths, to deduplicate the results that are variants of the same
+/// document.
+pub struct CanonicalPaths {
+    /// The root directories of the corpus, that the paths are rewritten
+    /// relative to.
+    roots: Vec<PathBuf>,
+    rules: Vec<(Regex, String)>,
+}
+
+impl CanonicalPaths {
+    /// Builds the rules of a search config for the documents of a
+    /// repository, or returns `None` if there are none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern of the rules is not a valid regular
+    /// expression.
+    pub fn new(repo_dir: &Path, search: &SearchConfig) -> anyhow::Result<Option<Self>> {
+        if search.canonical_paths.is_empty() {
+            return Ok(None);
+        }
+        let roots = CorpusRoots::dirs(repo_dir)?;
+        let rules = search
+            .canonical_paths
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern)
+                    .context(format!("Invalid canonical path pattern: {}", rule.pattern))?;
+                Ok((pattern, rule.replacement.clone()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Some(Self { roots, rules }))
+    }
+
+    /// Rewrites the path of a document, relative to the root directory it is
+    /// in, with the rules in order.
+    pub fn canonical(&self, path: &Path) -> String {
+        let path = relative_to_root(&self.roots, path);
+        let mut path = path.to_string_lossy().into_owned();
+        for (pattern, replacement) in &self.rules {
+            path = pattern
+                .replace_all(&path, replacement.as_str())
+                .into_owned();
+        }
+        path
+    }
+
+    /// Keeps the first of the hits with the same canonical path. The hits
+    /// are in the order of their rank, so the variant with the highest score
+    /// is kept.
+    fn dedup<'a>(&self, hits: Vec<Hit<'a>>) -> Vec<Hit<'a>> {
+        let mut seen = HashSet::new();
+        hits.into_iter()
+            .filter(|hit| seen.insert(self.canonical(hit.path)))
+            .collect()
+    }
+}
+
+/// The lexical part of a query, prepared to rank documents against.
+pub struct PreparedQuery {
+    /// The text of the query without its phrases and its patterns, expanded
+    /// with synonyms unless the query is case-sensitive.
+    pub lexical: String,
+    /// The terms of the text, and the terms of the index that the patterns
+    /// match.
+    pub terms: Query,
+    /// The phrases of the query, in double quotes.
+    pub phrases: Vec<Phrase>,
+}
 
-/// Search `top_n` most relevant documents to the free text `query`.
-pub fn invoke(repo_dir: impl AsRef<Path>, query: &str, top_n: u32) -> anyhow::Result<()> {
+impl PreparedQuery {
+    /// Finds the documents that match the phrases of the query, out of the
+    /// allowed documents, so that the others are never ranked. Returns
+    /// `None` if the query has no phrases, so all the allowed documents can
+    /// be ranked.
+    pub fn phrase_matches(
+        &self,
+        index: &dyn Index,
+        store: &DocumentStore,
+        inv_collection: &InvertedCollection,
+        analyzer: &AnalyzerConfig,
+        allowed: Option<&HashSet<DocumentId>>,
+    ) -> Option<HashSet<DocumentId>> {
+        if self.phrases.is_empty() {
+            return None;
+        }
+        phrase_matches(
+            index,
+            store,
+            inv_collection,
+            analyzer,
+            &self.phrases,
+            allowed,
+        )
+    }
+}
+
+/// Prepares the lexical part of a query to rank documents against, the same
+/// way for every command that searches. The phrases and the patterns are
+/// parsed out of the query, the rest of it is expanded with the synonyms of
+/// the thesaurus, unless the query is case-sensitive, and tokenized, and the
+/// patterns are expanded to the terms of the index that they match.
+///
+/// # Errors
+///
+/// Returns an error if a pattern is not a valid regular expression.
+pub fn prepare_query(
+    index: &dyn Index,
+    analyzer: &AnalyzerConfig,
+    thesaurus: &Thesaurus,
+    query: &str,
+    case_sensitive: bool,
+) -> anyhow::Result<PreparedQuery> {
+    let (lexical, phrases) = Phrase::parse_all(query);
+    let (lexical, patterns) = split_patterns(&lexical);
+    let lexical = match case_sensitive {
+        true => lexical,
+        false => thesaurus.expand(&lexical, analyzer),
+    };
+    let mut terms = analyzer.tokenize_query(&lexical, case_sensitive);
+    terms.extend(expand_patterns(index, &patterns, case_sensitive)?);
+    Ok(PreparedQuery {
+        lexical,
+        terms,
+        phrases,
+    })
+}
+
+/// The way documents are ranked against a query.
+pub struct Ranking<'a> {
+    /// The number of documents to rank.
+    pub top_n: u32,
+    /// The function that scores the documents, and the boosts of the fields.
+    pub search: &'a SearchConfig,
+    /// The weights of the documents by their paths and modified times, if
+    /// they are not all weighted the same.
+    pub boosts: Option<&'a DocumentBoosts>,
+    /// The analyzer that the documents were indexed with.
+    pub analyzer: &'a AnalyzerConfig,
+    /// The passage policy, if the best candidates are scored by their best
+    /// passages.
+    pub passage_config: Option<&'a PassageConfig>,
+    /// The rules of the canonical paths of the documents, if the variants of
+    /// the same document are deduplicated.
+    pub canonical: Option<&'a CanonicalPaths>,
+    /// The maximum distance of the fingerprints of the texts of documents
+    /// that are near-duplicates, if they are collapsed.
+    pub collapse: Option<u32>,
+}
+
+impl Ranking<'_> {
+    /// Returns the number of candidates to rank, so that `top_n` of them are
+    /// left once the variants of the same documents, and the near-duplicates,
+    /// are deduplicated.
+    fn n_candidates(&self, top_n: u32) -> u32 {
+        match self.canonical.is_some() || self.collapse.is_some() {
+            true => top_n.saturating_mul(DEDUP_CANDIDATES_FACTOR),
+            false => top_n,
+        }
+    }
+}
+
+/// The results of a search that are shown, the order they are listed in,
+/// and the format they are written in.
+pub struct Listing {
+    /// The positions of the results to show, counting from 0.
+    pub range: Range<u32>,
+    /// The order of the results.
+    pub sort: SortOrder,
+    /// The format of the results.
+    pub output: OutputFormat,
+    /// The metadata of the documents that can be in the results.
+    pub filter: MetadataFilter,
+    /// Whether to count the matching documents by extension and directory.
+    pub facets: bool,
+    /// Whether to search for the suggested query instead, if words of the
+    /// query are misspelled.
+    pub auto_correct: bool,
+    /// Whether to relax a lexical query that matches no documents, until it
+    /// matches some.
+    pub relax: bool,
+    /// Whether to expand a lexical query with the terms of the documents
+    /// that it ranks the highest, by pseudo-relevance feedback.
+    pub expand: bool,
+    /// Whether to show only the best of the documents whose texts are
+    /// identical or near-duplicates, along with the paths of the others.
+    pub collapse_duplicates: bool,
+    /// Whether to match the query in the files that are new or modified
+    /// since the repository was indexed too, without the index.
+    pub include_unindexed: bool,
+    /// Whether to color the results in the text output, if stdout is a
+    /// terminal.
+    pub color: bool,
+}
+
+/// The number of documents that match a query, by the extension of their
+/// files and by the top-level directory they are in, relative to the root
+/// directory of the corpus that they are in.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Facets {
+    /// The number of documents by extension, without the dot. Files without
+    /// an extension are counted under `(none)`.
+    extensions: BTreeMap<String, usize>,
+    /// The number of documents by top-level directory. Files that are right
+    /// in the indexed directory are counted under `.`.
+    directories: BTreeMap<String, usize>,
+}
+
+impl Facets {
+    /// Counts the documents with the given paths, by their extension and by
+    /// their top-level directory under the root of `roots` they are in.
+    pub fn count<'a>(paths: impl IntoIterator<Item = &'a Path>, roots: &[PathBuf]) -> Self {
+        let mut facets = Self::default();
+        for path in paths {
+            let extension = path
+                .extension()
+                .map_or("(none)".into(), |extension| extension.to_string_lossy());
+            *facets.extensions.entry(extension.into_owned()).or_default() += 1;
+
+            let relative = relative_to_root(roots, path);
+            let mut components = relative.components();
+            let directory = match (components.next(), components.next()) {
+                (Some(first), Some(_)) => first.as_os_str().to_string_lossy(),
+                _ => ".".into(),
+            };
+            *facets
+                .directories
+                .entry(directory.into_owned())
+                .or_default() += 1;
+        }
+        facets
+    }
+
+    /// Writes the counts to stdout as tables, with the largest counts first.
+    fn display(&self) -> io::Result<()> {
+        let stdout = io::stdout();
+        let stdout = stdout.lock();
+        let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+        for (title, counts) in [
+            ("Extension", &self.extensions),
+            ("Directory", &self.directories),
+        ] {
+            let mut counts = counts.iter().collect::<Vec<_>>();
+            counts.sort_by_key(|(_, count)| Reverse(**count));
+            writeln!(tw)?;
+            writeln!(tw, "\t{title}\tMatches")?;
+            for (value, count) in counts {
+                writeln!(tw, "\t{value}\t{count}")?;
+            }
+        }
+        tw.flush()
+    }
+}
+
+/// The results of a search in JSON, along with their facets and the matches
+/// in the files that are not indexed yet.
+#[derive(Serialize)]
+struct FacetedResults<'a, 'b> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    results: Option<&'b [SearchResult<'a>]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    facets: Option<&'b Facets>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unindexed: Option<&'b [LineMatch]>,
+}
+
+/// The metadata of the files of the documents that a search is restricted
+/// to, as it is kept in the collection. The documents that do not match are
+/// never ranked.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataFilter {
+    /// The extensions of the files, without the dot. Files with any
+    /// extension match if there are none.
+    pub extensions: Vec<String>,
+    /// The time the files were last modified at or after, or the date of
+    /// the documents that have one is on or after.
+    pub modified_after: Option<SystemTime>,
+    /// The time the files were last modified before, or the date of the
+    /// documents that have one is before.
+    pub modified_before: Option<SystemTime>,
+    /// The smallest size of the files, in bytes.
+    pub min_size: Option<u64>,
+    /// The largest size of the files, in bytes.
+    pub max_size: Option<u64>,
+    /// The tags that the documents have. Documents match only if they have
+    /// every one of them.
+    pub tags: Vec<String>,
+    /// The prefixes of the paths of the documents, like `src/`, or globs of
+    /// their paths, like `src/**/*.rs`, relative to the root directory of
+    /// the corpus that they are in. Documents match if their paths match any
+    /// of them, and every path matches if there are none.
+    pub path_prefixes: Vec<String>,
+}
+
+impl MetadataFilter {
+    /// Checks if the filter matches every document.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Checks if the entry of a document in the collection matches the
+    /// filter. The extensions are matched regardless of case, and the tags
+    /// exactly. The documents with a date, like calendar events and
+    /// contacts, are matched by the start of the day of their date, and the
+    /// others by the time their files were last modified. Entries that were
+    /// written before sizes and extensions were kept only match if neither
+    /// is filtered.
+    pub fn matches(&self, entry: &CollectionEntry) -> bool {
+        let extension = self.extensions.is_empty()
+            || entry.extension().is_some_and(|extension| {
+                self.extensions
+                    .iter()
+                    .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+            });
+        let modified = entry
+            .date()
+            .and_then(|date| parse_date(date).ok())
+            .unwrap_or_else(|| entry.modified());
+        let modified = self.modified_after.is_none_or(|after| modified >= after)
+            && self.modified_before.is_none_or(|before| modified < before);
+        let size = match entry.size() {
+            Some(size) => {
+                self.min_size.is_none_or(|min| size >= min)
+                    && self.max_size.is_none_or(|max| size <= max)
+            }
+            None => self.min_size.is_none() && self.max_size.is_none(),
+        };
+        let tags = self.tags.iter().all(|tag| entry.has_tag(tag));
+        extension && modified && size && tags
+    }
+
+    /// Finds the IDs of the documents of a repository that match the filter,
+    /// or returns `None` if every document matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collection or the roots of the repository
+    /// cannot be read, or if one of the globs of the path prefixes is invalid.
+    pub fn allowed(&self, repo_dir: &Path) -> anyhow::Result<Option<HashSet<DocumentId>>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let prefixes = PathPrefixes::new(&self.path_prefixes, CorpusRoots::dirs(repo_dir)?)?;
+        let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+        let doc_ids = (&collection)
+            .into_iter()
+            .filter(|(path, entry)| self.matches(entry) && prefixes.matches(path))
+            .map(|(_, entry)| entry.document_id())
+            .collect();
+        Ok(Some(doc_ids))
+    }
+}
+
+/// The matchers of the path prefixes of a metadata filter. Prefixes with
+/// glob characters are globs, and the others match the paths that are in
+/// them, component by component, so `src` matches `src/main.rs` but not
+/// `srcs/main.rs`.
+struct PathPrefixes {
+    prefixes: Vec<PathBuf>,
+    globs: Option<Override>,
+    roots: Vec<PathBuf>,
+}
+
+impl PathPrefixes {
+    /// Builds the matchers of the path prefixes, relative to the root
+    /// directories of a corpus.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the globs is invalid.
+    fn new(prefixes: &[String], roots: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let (globs, prefixes): (Vec<_>, Vec<_>) = prefixes
+            .iter()
+            .partition(|prefix| prefix.contains(['*', '?', '[', '{']));
+        let globs = match (globs.is_empty(), roots.first()) {
+            (false, Some(root)) => {
+                let mut builder = OverrideBuilder::new(root);
+                for glob in globs {
+                    builder
+                        .add(glob)
+                        .context(format!("Invalid path prefix glob: {glob}"))?;
+                }
+                Some(builder.build()?)
+            }
+            _ => None,
+        };
+        let prefixes = prefixes
+            .into_iter()
+            .map(|prefix| PathBuf::from(prefix.trim_start_matches("./")))
+            .collect();
+        Ok(Self {
+            prefixes,
+            globs,
+            roots,
+        })
+    }
+
+    /// Checks if the path of a document matches one of the prefixes, or if
+    /// there are none.
+    fn matches(&self, path: &Path) -> bool {
+        if self.prefixes.is_empty() && self.globs.is_none() {
+            return true;
+        }
+        let relative = relative_to_root(&self.roots, path);
+        self.prefixes
+            .iter()
+            .any(|prefix| relative.starts_with(prefix))
+            || self
+                .globs
+                .as_ref()
+                .is_some_and(|globs| globs.matched(relative, false).is_whitelist())
+    }
+}
+
+/// A search result, as it is written in the JSON output.
+///
+/// The offsets of the matches are given both in the coordinate space of the
+/// full document and in that of the snippets, so editors can place their own
+/// highlights without searching the text again.
+#[derive(Serialize)]
+pub struct SearchResult<'a> {
+    rank: usize,
+    doc_id: DocumentId,
+    path: &'a Path,
+    title: &'a str,
+    /// The URL that the document was fetched from, if it is a web page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    score: f64,
+    snippets: Vec<Snippet>,
+    matches: Vec<Offsets>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    passages: Vec<Passage>,
+    /// The paths of the duplicates of the document that were collapsed into
+    /// it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    copies: Vec<&'a Path>,
+}
+
+impl Hit<'_> {
+    /// Returns the ID of the document that was hit.
+    pub fn doc_id(&self) -> DocumentId {
+        self.doc_id
+    }
+
+    /// Returns the score of the document that was hit.
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    /// Returns the path of the document that was hit.
+    pub fn path(&self) -> &Path {
+        self.path
+    }
+
+    /// Returns the title of the document that was hit, or the name of its
+    /// file if it has none.
+    pub fn title(&self) -> &str {
+        self.title
+    }
+
+    /// Returns the URL that the document that was hit was fetched from, if
+    /// it is a web page.
+    pub fn url(&self) -> Option<&str> {
+        self.url
+    }
+}
+
+/// A document that was found, with the best passages of the document if
+/// passages were scored.
+pub struct Hit<'a> {
+    doc_id: DocumentId,
+    path: &'a PathBuf,
+    title: &'a str,
+    url: Option<&'a str>,
+    score: f64,
+    passages: Vec<Passage>,
+    /// The paths of the duplicates of the document that were collapsed into
+    /// it, in the order of their rank.
+    copies: Vec<&'a PathBuf>,
+}
+
+/// Search the most relevant documents to the parsed `query`, and show the
+/// ones at the positions in the range of the `listing`, counting from 0, so
+/// that the results can be paged through. The documents are ranked once, up
+/// to the end of the range, with ties broken by their IDs, and the range is
+/// sliced from the ranking, so a page shows the same documents as the same
+/// positions of a longer list.
+///
+/// If the listing is sorted by modified time or by path, all the documents
+/// that match the query are ranked, and sorted, before the range is sliced,
+/// with the documents that are ordered the same kept in the order of their
+/// rank. The modified times are those of the documents when they were
+/// indexed. A semantic query matches every document, so only the most
+/// similar documents, up to the end of the range, are sorted.
+///
+/// If the output format is JSON or NDJSON, the results are written to stdout
+/// as JSON records, along with the offsets of the matched terms. If a snippet
+/// policy is given, snippets of the documents are excerpted according to it,
+/// and they are shown with the results.
+///
+/// If a passage policy is given, the documents are split into passages, and
+/// the best passages of each document are scored instead of the whole
+/// document. The documents are first ranked as a whole, and the passages of
+/// the best candidates are scored, so book-length documents that match the
+/// query terms far apart rank below documents that match them together.
+///
+/// If the listing has a metadata filter, only the documents whose files
+/// match it, by their extensions, modified times and sizes when they were
+/// indexed, are ranked. If the query has phrases, in double quotes, only the
+/// documents whose texts match them are ranked, with their words next to
+/// each other, or within the slop of a phrase like `"budget meeting"~3`.
+/// The words of the query that are written as `/pattern/` match the terms of
+/// the index that the regular expression matches.
+///
+/// If an embedder is given, the documents are ranked by the similarity of
+/// their vectors in the semantic index to the vector of the query instead,
+/// and passages are not scored. If the query is a hybrid query, with a
+/// semantic clause, the documents that match its lexical terms are ranked
+/// by both their lexical score and their similarity to the clause.
+///
+/// If the query is case-sensitive, its lexical terms only match the
+/// identifiers of source code in their original case, which are indexed if
+/// the analyzer of the repository is configured to.
+///
+/// The query is analyzed as configured in the config of the repository, and
+/// the documents are scored with the search config, which is the `[search]`
+/// section of the config, with a ranking profile applied if one was
+/// selected. If the search config weights documents by how recently they
+/// were modified, or by their paths, their lexical scores are weighted, but
+/// the similarities of a semantic search are not.
+///
+/// If the listing relaxes queries, a lexical query that matches no documents
+/// is relaxed by the steps of [`Relaxation`], until a step matches some,
+/// and the step is shown with the results.
+///
+/// If the listing collapses duplicates, only the best of the documents whose
+/// texts are identical, by their stored hashes, or have fingerprints at most
+/// `DUPES_MAX_DISTANCE` bits apart, is shown, so copies of the same text,
+/// even with small edits, take a single result, which lists the others.
+///
+/// If the cache is enabled and persisted in the `[cache]` section of the
+/// config, the ranked documents of lexical queries without passages are kept
+/// in the repository, so a repeated query is not ranked again until the
+/// index changes.
+///
+/// If the listing includes the unindexed files, the lexical query is also
+/// matched, as a [`LineMatcher`], in the text of the files that are new or
+/// modified since the repository was indexed, and that match the metadata
+/// filter, so that they are found before the next index run. Their matching
+/// lines are shown after the results.
+///
+/// # Returns
+///
+/// The number of results that were shown.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    query: &HybridQuery,
+    search_config: &SearchConfig,
+    listing: Listing,
+    snippet_config: Option<&SnippetConfig>,
+    passage_config: Option<&PassageConfig>,
+    embedder: Option<&dyn Embedder>,
+) -> anyhow::Result<usize> {
     let repo_dir = repo_dir.as_ref();
+    // The index and the collection are read from a snapshot, so that they
+    // stay consistent if the repository is updated during the search.
+    let snapshot = Snapshot::open(repo_dir)?;
+    let index = snapshot.index.as_ref();
+    let inv_collection = &snapshot.inv_collection;
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let settings = Settings::load(repo_dir)?;
+    let analyzer = &settings.analyzer;
+    analyzer.check(repo_dir)?;
 
-    // Instantiate tokenizer.
-    let mut tokenizer = Tokenizer::default();
-    // Tokenize the query.
-    let query_tokens = tokenizer.tokenize(query);
-    let query = Query::from(query_tokens);
+    let hybrid = query;
+    let mut query = hybrid.lexical.as_str();
+    let suggestion = match hybrid.case_sensitive || query.is_empty() {
+        true => None,
+        false => spelling::suggest(index, analyzer, query),
+    };
+    if let Some(suggestion) = &suggestion {
+        display_suggestion(suggestion, listing.auto_correct, listing.output);
+        if listing.auto_correct {
+            query = &suggestion.query;
+        }
+    }
+    let thesaurus = Thesaurus::load(repo_dir, analyzer)?;
+    let prepared = prepare_query(index, analyzer, &thesaurus, query, hybrid.case_sensitive)?;
+    let top_n = listing.range.end;
+    let boosts = DocumentBoosts::new(repo_dir, search_config)?;
+    let canonical = CanonicalPaths::new(repo_dir, search_config)?;
+    let allowed = listing.filter.allowed(repo_dir)?;
+    let allowed = prepared
+        .phrase_matches(index, &store, inv_collection, analyzer, allowed.as_ref())
+        .or(allowed);
+    let allowed = allowed.as_ref();
+    let PreparedQuery {
+        lexical, mut terms, ..
+    } = prepared;
+    let ranking = Ranking {
+        top_n: match listing.sort {
+            SortOrder::Score => top_n,
+            SortOrder::Mtime | SortOrder::Path => u32::MAX,
+        },
+        search: search_config,
+        boosts: boosts.as_ref(),
+        analyzer,
+        passage_config,
+        canonical: canonical.as_ref(),
+        collapse: listing.collapse_duplicates.then_some(DUPES_MAX_DISTANCE),
+    };
+    // The lexical terms are expanded with the terms of the documents that
+    // they rank the highest, before the documents are ranked.
+    let is_lexical = embedder.is_none() || hybrid.semantic.is_some();
+    if listing.expand && is_lexical && !query.is_empty() {
+        let expansion = expand_query(index, inv_collection, &store, &terms, &ranking, allowed);
+        if !expansion.is_empty() {
+            let added = expansion.iter().map(|(term, _)| term.as_str());
+            let message = format!(
+                "Expanded the query with: {}",
+                added.collect::<Vec<_>>().join(" ")
+            );
+            display_notice(&message, listing.output);
+            terms.extend_weighted(expansion);
+        }
+    }
+    let n_ranked = ranking.n_candidates(ranking.top_n);
+    let n_semantic = ranking.n_candidates(top_n);
+    let mut hits = match (embedder, &hybrid.semantic) {
+        (Some(embedder), Some(clause)) if !query.is_empty() => {
+            let scores = lexical_scores(index, inv_collection, &terms, &ranking, allowed);
+            let ranked = rank_hybrid(repo_dir, embedder, &scores, clause, n_ranked)?;
+            ranked_hits(ranked, inv_collection)
+        }
+        (Some(embedder), Some(clause)) => {
+            let ranked = rank_semantic(repo_dir, embedder, &clause.text, n_semantic, allowed)?;
+            ranked_hits(ranked, inv_collection)
+        }
+        (Some(embedder), None) => {
+            let ranked = rank_semantic(repo_dir, embedder, query, n_semantic, allowed)?;
+            ranked_hits(ranked, inv_collection)
+        }
+        // Hits with passages or collapsed copies are not cached, as the
+        // cache only keeps the ranked documents, and neither are the hits
+        // of expanded queries, as the cache is keyed by the query.
+        (None, _) => match (passage_config.is_none()
+            && ranking.collapse.is_none()
+            && !listing.expand)
+            .then(|| QueryCache::load(repo_dir, &settings.cache, snapshot.generation))
+            .flatten()
+        {
+            Some(mut cache) => {
+                let key = cache_key(query, hybrid.case_sensitive, &ranking, &listing.filter);
+                match cache.get(&key) {
+                    Some(ranked) => ranked_hits(ranked, inv_collection),
+                    None => {
+                        let hits = rank(index, inv_collection, &store, &terms, &ranking, allowed)?;
+                        cache.insert(
+                            key,
+                            hits.iter().map(|hit| (hit.doc_id, hit.score)).collect(),
+                        );
+                        if let Err(err) = cache.save(repo_dir) {
+                            eprintln!("WARNING: Failed to cache the results because: {err:#}");
+                        }
+                        hits
+                    }
+                }
+            }
+            None => rank(index, inv_collection, &store, &terms, &ranking, allowed)?,
+        },
+    };
+    // The hits that were ranked by `rank` are deduplicated already.
+    if let (Some(canonical), Some(_)) = (&canonical, embedder) {
+        hits = canonical.dedup(hits);
+    }
+    if let (Some(max_distance), Some(_)) = (ranking.collapse, embedder) {
+        hits = collapse_duplicates(hits, &store, max_distance);
+    }
+    // A lexical query that matches no documents is relaxed step by step,
+    // until a step matches some.
+    if hits.is_empty() && listing.relax && embedder.is_none() && !query.is_empty() {
+        let relaxed = RelaxedQuery::new(analyzer, &lexical, hybrid.case_sensitive);
+        if let Some((step, relaxed, relaxed_hits)) =
+            relax_query(index, inv_collection, &store, relaxed, &ranking, allowed)?
+        {
+            let message = format!(
+                "No results for: {query}. Showing results with {}: {}",
+                step.description(),
+                relaxed.text
+            );
+            display_notice(&message, listing.output);
+            hits = relaxed_hits;
+            terms = relaxed.terms;
+        }
+    }
+    let facets = match listing.facets {
+        true => Some(count_facets(
+            repo_dir,
+            index,
+            inv_collection,
+            &hits,
+            &terms,
+            query.is_empty(),
+            allowed,
+        )?),
+        false => None,
+    };
+    // The files that changed since the repository was indexed are matched
+    // without the index, unless the whole query is semantic.
+    let matcher = match listing.include_unindexed && is_lexical {
+        true => LineMatcher::new(query, hybrid.case_sensitive)?,
+        false => None,
+    };
+    let unindexed = matcher
+        .map(|matcher| unindexed_matches(repo_dir, &settings, &matcher, &listing.filter))
+        .transpose()?;
+    sort_hits(repo_dir, &mut hits, listing.sort)?;
+    let first_rank = listing.range.start as usize;
+    let hits = hits
+        .into_iter()
+        .skip(first_rank)
+        .take(listing.range.len())
+        .collect::<Vec<_>>();
+    let n_hits = hits.len();
+
+    match listing.output {
+        OutputFormat::Text => {
+            let marker = Marker::for_stdout(listing.color);
+            display_results(
+                hits,
+                first_rank,
+                &store,
+                &terms,
+                analyzer,
+                snippet_config,
+                marker,
+            )?;
+            if let Some(facets) = &facets {
+                facets.display()?;
+            }
+            if let Some(unindexed) = &unindexed {
+                display_unindexed(unindexed, marker)?;
+            }
+        }
+        OutputFormat::Ndjson => {
+            stream_results(hits, first_rank, &store, &terms, analyzer, snippet_config)?;
+            if facets.is_some() || unindexed.is_some() {
+                let (facets, unindexed) = (facets.as_ref(), unindexed.as_deref());
+                write_faceted(&[], facets, unindexed, listing.output)?;
+            }
+        }
+        OutputFormat::Json => {
+            let results =
+                search_results(hits, first_rank, &store, &terms, analyzer, snippet_config);
+            match (facets.as_ref(), unindexed.as_deref()) {
+                (None, None) => write_records(&results, listing.output)?,
+                (facets, unindexed) => write_faceted(&results, facets, unindexed, listing.output)?,
+            }
+        }
+    }
+    Ok(n_hits)
+}
+
+/// Finds the terms that expand a lexical query, by pseudo-relevance
+/// feedback: the terms of the documents that the query ranks the highest,
+/// weighted, that are not in the query.
+fn expand_query(
+    index: &dyn Index,
+    inv_collection: &InvertedCollection,
+    store: &DocumentStore,
+    terms: &Query,
+    ranking: &Ranking,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> Vec<(Token, f64)> {
+    let feedback = score_documents(index, terms, ranking.search, allowed)
+        .get_top_n(EXPAND_DOCS)
+        .into_iter()
+        .map(|(doc_id, _)| *doc_id)
+        .collect::<Vec<_>>();
+    expansion_terms(
+        index,
+        store,
+        inv_collection,
+        ranking.analyzer,
+        terms,
+        &feedback,
+    )
+}
+
+/// Relaxes a lexical query that matches no documents by the steps of
+/// [`Relaxation`], until a step matches some.
+///
+/// # Returns
+///
+/// The step that matched some documents, with the relaxed query and its
+/// hits, or `None` if no step did.
+fn relax_query<'a>(
+    index: &dyn Index,
+    inv_collection: &'a InvertedCollection,
+    store: &DocumentStore,
+    mut relaxed: RelaxedQuery,
+    ranking: &Ranking,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> anyhow::Result<Option<(Relaxation, RelaxedQuery, Vec<Hit<'a>>)>> {
+    for step in Relaxation::STEPS {
+        let Some(next) = relaxed.relax(index, ranking.analyzer, step) else {
+            continue;
+        };
+        relaxed = next;
+        let hits = rank(
+            index,
+            inv_collection,
+            store,
+            &relaxed.terms,
+            ranking,
+            allowed,
+        )?;
+        if !hits.is_empty() {
+            return Ok(Some((step, relaxed, hits)));
+        }
+    }
+    Ok(None)
+}
+
+/// Counts the facets of a search. The facets count all the documents that
+/// match the terms of the query, or all the ranked documents of a semantic
+/// query, not just the page.
+///
+/// # Errors
+///
+/// Returns an error if the roots file of the repository cannot be read.
+fn count_facets(
+    repo_dir: &Path,
+    index: &dyn Index,
+    inv_collection: &InvertedCollection,
+    hits: &[Hit],
+    terms: &Query,
+    is_semantic: bool,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> anyhow::Result<Facets> {
+    let roots = CorpusRoots::dirs(repo_dir)?;
+    if is_semantic {
+        let paths = hits.iter().map(|hit| hit.path.as_path());
+        return Ok(Facets::count(paths, &roots));
+    }
+    let paths = matching_documents(index, terms, allowed)
+        .into_iter()
+        .filter_map(|doc_id| inv_collection.get_path(doc_id));
+    Ok(Facets::count(paths.map(PathBuf::as_path), &roots))
+}
+
+/// Writes the results of a search with their facets, and the matches in the
+/// files that are not indexed yet, to stdout. In JSON, they are written as
+/// an object with `results`, `facets` and `unindexed`, and in NDJSON the
+/// facets and the matches are written as a last record, without `results`.
+fn write_faceted(
+    results: &[SearchResult],
+    facets: Option<&Facets>,
+    unindexed: Option<&[LineMatch]>,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let faceted = FacetedResults {
+        results: Some(results),
+        facets,
+        unindexed,
+    };
+    match format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            serde_json::to_writer_pretty(&mut stdout, &faceted)?;
+            writeln!(stdout)?;
+        }
+        OutputFormat::Ndjson => {
+            write_records(results, format)?;
+            let faceted = FacetedResults {
+                results: None,
+                ..faceted
+            };
+            write_records(&[faceted], format)?;
+        }
+    }
+    Ok(())
+}
+
+/// Shows the matching lines of the files that are not indexed yet, below the
+/// results, as the path to each file and the number and text of each line.
+fn display_unindexed(matches: &[LineMatch], marker: Marker) -> io::Result<()> {
+    if matches.is_empty() {
+        return Ok(());
+    }
+    let n_files = matches
+        .iter()
+        .map(|line_match| &line_match.path)
+        .collect::<HashSet<_>>()
+        .len();
+    let files = match n_files {
+        1 => "1 file".to_string(),
+        n => format!("{n} files"),
+    };
+    println!();
+    println_bold!("Matches in {files} not indexed yet:");
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for line_match in matches {
+        let path = marker.path(&line_match.path);
+        let line = marker.dim(&line_match.line.to_string());
+        writeln!(stdout, "  {path}:{line}: {}", line_match.text)?;
+    }
+    stdout.flush()
+}
+
+/// Finds the lines that match a query in the text of the files of a
+/// repository that are new or modified since it was indexed, and whose
+/// metadata, as it is now, matches the filter. A modified file keeps the
+/// tags of its document. The files are read like they are when they are
+/// indexed, and the ones that cannot be read are skipped printing a warning.
+///
+/// # Errors
+///
+/// Returns an error if the collection, the directory or the config of the
+/// extraction of the repository cannot be read.
+fn unindexed_matches(
+    repo_dir: &Path,
+    settings: &Settings,
+    matcher: &LineMatcher,
+    filter: &MetadataFilter,
+) -> anyhow::Result<Vec<LineMatch>> {
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let dir = Directory::for_repo(repo_dir)?
+        .iter_full_paths(false)
+        .collect::<Vec<_>>();
+    let mut paths = fetch_new_files(&collection, &dir);
+    paths.extend(fetch_modified_files(&collection, &dir)?);
+    paths.sort();
+
+    let roots = CorpusRoots::dirs(repo_dir)?;
+    let prefixes = PathPrefixes::new(&filter.path_prefixes, roots.clone())?;
+    let rows = RowFiles::new(roots.clone(), &settings.extraction.rows)?;
+    let commands = ExtractorCommands::new(roots, &settings.extraction.commands)?;
+    let mut tables = TableReader::new();
+    let mut matches = vec![];
+    for path in paths {
+        if !prefixes.matches(&path) {
+            continue;
+        }
+        if !filter.is_empty() {
+            let metadata = source_path(&path)
+                .metadata()
+                .context(format!("Failed to get metadata for: {}.", path.display()))?;
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().into_owned());
+            let modified = metadata.modified()?;
+            let mut entry = CollectionEntry::new(0, modified, metadata.len(), extension);
+            if let Some(indexed) = collection.get(&path) {
+                entry.set_tags(indexed.tags().clone());
+            }
+            if !filter.matches(&entry) {
+                continue;
+            }
+        }
+        let document = match settings
+            .extraction
+            .load(0, &path, &rows, &commands, &mut tables)
+        {
+            Ok(document) => document,
+            Err(err) => {
+                eprintln!("WARNING: Skipping {}: {err:#}", path.display());
+                continue;
+            }
+        };
+        matches.extend(matcher.find(&path, document.page_content()));
+    }
+    Ok(matches)
+}
+
+/// Shows the suggested query of a search with misspelled words, or that the
+/// results are for the suggested query, if it is searched for instead.
+fn display_suggestion(suggestion: &Suggestion, auto_correct: bool, output: OutputFormat) {
+    let message = match auto_correct {
+        true => format!("Showing results for: {}", suggestion.query),
+        false => format!("Did you mean: {}?", suggestion.query),
+    };
+    display_notice(&message, output);
+}
+
+/// Shows a notice about the query of a search. It is written to stderr,
+/// unless the results are written as text, so it is never mixed with
+/// records.
+pub fn display_notice(message: &str, output: OutputFormat) {
+    match output {
+        OutputFormat::Text => {
+            println_bold!("{message}");
+        }
+        _ => eprintln!("{message}"),
+    }
+}
+
+/// Returns the key of the ranked documents of a lexical query in the cache,
+/// which includes everything that the ranking depends on, other than the
+/// index.
+pub fn cache_key(
+    query: &str,
+    case_sensitive: bool,
+    ranking: &Ranking,
+    filter: &MetadataFilter,
+) -> String {
+    format!(
+        "{query}\u{0}{case_sensitive}\u{0}{}\u{0}{:?}\u{0}{filter:?}\u{0}{:?}",
+        ranking.top_n, ranking.search, ranking.collapse
+    )
+}
+
+/// Sorts the ranked hits of a search in the given order. The sort is stable,
+/// so hits that are ordered the same are kept in the order of their rank.
+///
+/// # Errors
+///
+/// Returns an error if the hits are sorted by modified time, and the
+/// collection of the repository cannot be read.
+fn sort_hits(repo_dir: &Path, hits: &mut [Hit], sort: SortOrder) -> anyhow::Result<()> {
+    match sort {
+        SortOrder::Score => {}
+        SortOrder::Mtime => {
+            let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+            hits.sort_by_cached_key(|hit| {
+                Reverse(collection.get_last_modified(hit.path).unwrap_or(UNIX_EPOCH))
+            });
+        }
+        SortOrder::Path => hits.sort_by(|a, b| a.path.cmp(b.path)),
+    }
+    Ok(())
+}
+
+/// Ranks the most relevant documents to the tokenized `query`, from an index
+/// that is already open, the way that is given by `ranking`. If a passage
+/// policy is given, the best candidates are scored by their best passages.
+/// Otherwise, if the proximity of the terms is weighted, the best candidates
+/// are scored again by how close to each other the terms of the query are in
+/// their texts. If a set of allowed documents is given, the other documents
+/// are never ranked. The scores, or the scores of the best passages, are
+/// multiplied by the weights of the documents, if they are weighted. If the
+/// documents have canonical paths, only the variant of a document with the
+/// best score is ranked, and so is only the best of near-duplicates, if they
+/// are collapsed.
+///
+/// # Errors
+///
+/// Returns an error if the postings of a term of the query cannot be read
+/// from the index, rather than ranking the documents without it.
+pub fn rank<'a>(
+    index: &dyn Index,
+    inv_collection: &'a InvertedCollection,
+    store: &DocumentStore,
+    query: &Query,
+    ranking: &Ranking,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> anyhow::Result<Vec<Hit<'a>>> {
+    let top_n = ranking.top_n;
+    let weights_proximity = ranking.search.proximity_weight > 0.0;
+    let n_candidates = match (ranking.passage_config, weights_proximity) {
+        (Some(_), _) => top_n.saturating_mul(PASSAGE_CANDIDATES_FACTOR),
+        (None, true) => top_n.saturating_mul(PROXIMITY_CANDIDATES_FACTOR),
+        (None, false) => top_n,
+    };
+    // Without weights of the documents, the scores of the terms bound the
+    // scores of the documents, so only the best ones need to be scored.
+    let is_bounded = ranking.boosts.is_none()
+        && ranking.search.title_boost >= 0.0
+        && ranking.search.body_boost >= 0.0;
+    let k = ranking.n_candidates(n_candidates);
+    let ranked = match is_bounded {
+        true => score_top_documents(index, query, ranking.search, allowed, k as usize),
+        false => lexical_scores(index, inv_collection, query, ranking, allowed)
+            .get_top_n(k)
+            .into_iter()
+            .map(|(doc_id, score)| (*doc_id, *score))
+            .collect(),
+    };
+    let mut hits = ranked
+        .into_iter()
+        .filter_map(|(doc_id, score)| {
+            Some(Hit {
+                doc_id,
+                path: inv_collection.get_path(doc_id)?,
+                title: inv_collection.get_title(doc_id)?,
+                url: inv_collection.get_url(doc_id),
+                score,
+                passages: vec![],
+                copies: vec![],
+            })
+        })
+        .collect::<Vec<_>>();
+    if let Some(canonical) = ranking.canonical {
+        hits = canonical.dedup(hits);
+    }
+    if let Some(max_distance) = ranking.collapse {
+        hits = collapse_duplicates(hits, store, max_distance);
+    }
+    hits.truncate(n_candidates as usize);
+
+    if let Some(config) = ranking.passage_config {
+        hits = hits
+            .into_iter()
+            .filter_map(|hit| {
+                let text = store.read(hit.doc_id).ok()?;
+                let passages =
+                    score_passages(&text, hit.path, query, index, ranking.analyzer, config);
+                let (score, passages) = best_passages(passages, config);
+                let boost = ranking.boosts.map_or(1.0, |boosts| boosts.boost(hit.path));
+                Some(Hit {
+                    score: score * boost,
+                    passages,
+                    ..hit
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.doc_id.cmp(&b.doc_id)));
+        hits.truncate(top_n as usize);
+    } else if weights_proximity {
+        // Documents whose text is not in the store keep their scores.
+        for hit in &mut hits {
+            if let Ok(text) = store.read(hit.doc_id) {
+                let proximity = proximity(&text, hit.path, query, ranking.analyzer);
+                hit.score *= 1.0 + ranking.search.proximity_weight * proximity;
+            }
+        }
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.doc_id.cmp(&b.doc_id)));
+        hits.truncate(top_n as usize);
+    }
+    index.check()?;
+    Ok(hits)
+}
+
+/// Collapses the hits of documents whose texts are identical, by their
+/// stored hashes, or near-duplicates, with fingerprints that are at most
+/// `max_distance` bits apart, into the first of them, which keeps the paths
+/// of the others as its copies. The hits are in the order of their rank, so
+/// the duplicate with the best score is kept. The hits of documents without
+/// a hash or a fingerprint are compared by the one they have, if any.
+fn collapse_duplicates<'a>(
+    hits: Vec<Hit<'a>>,
+    store: &DocumentStore,
+    max_distance: u32,
+) -> Vec<Hit<'a>> {
+    let mut kept = Vec::<Hit>::new();
+    let mut keys = Vec::<(Option<String>, Option<Fingerprint>)>::new();
+    for hit in hits {
+        let hash = store.read_content_hash(hit.doc_id).ok().flatten();
+        let fingerprint = store.read_fingerprint(hit.doc_id).ok().flatten();
+        let original = keys.iter().position(|(other_hash, other_fingerprint)| {
+            let is_identical = hash.is_some() && hash == *other_hash;
+            let is_near = match (&fingerprint, other_fingerprint) {
+                (Some(fingerprint), Some(other)) => fingerprint.distance(other) <= max_distance,
+                _ => false,
+            };
+            is_identical || is_near
+        });
+        match original {
+            Some(i) => {
+                kept[i].copies.push(hit.path);
+                kept[i].copies.extend(hit.copies);
+            }
+            None => {
+                keys.push((hash, fingerprint));
+                kept.push(hit);
+            }
+        }
+    }
+    kept
+}
+
+/// Scores the documents against the tokenized `query`, the way that is
+/// given by `ranking`, and weights the scores by the weights of the
+/// documents. If a set of allowed documents is given, the other documents
+/// are never scored.
+fn lexical_scores(
+    index: &dyn Index,
+    inv_collection: &InvertedCollection,
+    query: &Query,
+    ranking: &Ranking,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> DocumentsScores {
+    let mut scores = score_documents(index, query, ranking.search, allowed);
+    if let Some(boosts) = ranking.boosts {
+        boosts.apply(&mut scores, inv_collection);
+    }
+    scores
+}
 
-    // Load inverted index from file.
-    let index_path = repo_dir.join(INDEX_FILENAME);
-    let index = FrequencyIndex::from_file(index_path)?;
+/// Ranks the `top_n` documents that are semantically the most similar to a
+/// free text query. If a set of allowed documents is given, only their
+/// similarities are calculated, so that there are `top_n` of them if they
+/// have vectors.
+fn rank_semantic(
+    repo_dir: &Path,
+    embedder: &dyn Embedder,
+    query: &str,
+    top_n: u32,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> anyhow::Result<Vec<(DocumentId, f64)>> {
+    let Some(allowed) = allowed else {
+        return semantic::search(repo_dir, embedder, query, top_n);
+    };
+    let doc_ids = allowed.iter().copied().collect::<Vec<_>>();
+    let mut ranked = semantic::similarities(repo_dir, embedder, query, &doc_ids)?;
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked.truncate(top_n as usize);
+    Ok(ranked)
+}
+
+/// Ranks the documents that match the lexical terms of a hybrid query by the
+/// weighted sum of their lexical score and their semantic similarity to the
+/// description of the query. The lexical scores are divided by the best
+/// lexical score, so both are on the same scale.
+fn rank_hybrid(
+    repo_dir: &Path,
+    embedder: &dyn Embedder,
+    scores: &DocumentsScores,
+    clause: &SemanticClause,
+    top_n: u32,
+) -> anyhow::Result<Vec<(DocumentId, f64)>> {
+    let lexical_scores = scores.get_top_n(u32::MAX);
+    let max_score = lexical_scores.first().map_or(0.0, |(_, score)| **score);
+    let doc_ids = lexical_scores
+        .iter()
+        .map(|(doc_id, _)| **doc_id)
+        .collect::<Vec<_>>();
+    let similarities = semantic::similarities(repo_dir, embedder, &clause.text, &doc_ids)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let weight = clause.weight.unwrap_or(HYBRID_SEMANTIC_WEIGHT);
+    let mut ranked = lexical_scores
+        .into_iter()
+        .map(|(doc_id, score)| {
+            let lexical = if max_score > 0.0 {
+                score / max_score
+            } else {
+                0.0
+            };
+            let similarity = similarities.get(doc_id).copied().unwrap_or(0.0);
+            (*doc_id, (1.0 - weight) * lexical + weight * similarity)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked.truncate(top_n as usize);
+    Ok(ranked)
+}
 
+/// Finds the documents that contain at least one of the terms of a query,
+/// and that are allowed, if a set of allowed documents is given.
+pub fn matching_documents(
+    index: &dyn Index,
+    query: &Query,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> HashSet<DocumentId> {
+    query
+        .iter()
+        .flat_map(|(term, _)| index.doc_ids_containing(term))
+        .filter(|doc_id| allowed.is_none_or(|allowed| allowed.contains(doc_id)))
+        .collect()
+}
+
+/// Looks up the paths of ranked documents. Documents that are not in the
+/// collection anymore are skipped.
+pub fn ranked_hits(
+    ranked: Vec<(DocumentId, f64)>,
+    inv_collection: &InvertedCollection,
+) -> Vec<Hit<'_>> {
+    ranked
+        .into_iter()
+        .filter_map(|(doc_id, score)| {
+            Some(Hit {
+                doc_id,
+                path: inv_collection.get_path(doc_id)?,
+                title: inv_collection.get_title(doc_id)?,
+                url: inv_collection.get_url(doc_id),
+                score,
+                passages: vec![],
+                copies: vec![],
+            })
+        })
+        .collect()
+}
+
+/// Scores the documents that contain the terms of a query with the scorer
+/// of a search config. The scores of the terms are multiplied by their
+/// weights in the query, and by the boosts of their fields, so that the
+/// scores of the fields are combined.
+///
+/// If a set of allowed documents is given, the other documents are never
+/// scored. For each term, the allowed documents are checked for the term if
+/// there are fewer of them than documents that contain the term, so that a
+/// small set of documents is scored without reading the whole postings.
+pub fn score_documents(
+    index: &dyn Index,
+    query: &Query,
+    search: &SearchConfig,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> DocumentsScores {
     // Iterate over all tokens in the query and score the documents they appear in.
     let mut scores = DocumentsScores::new();
-    for (term, count) in query {
-        for doc_id in index.doc_ids_containing(&term) {
-            let score = (count as f64) * index.calc_tf_idf(doc_id, &term);
+    for (term, _) in query.iter() {
+        let boost = search.boost(term);
+        let doc_ids = match allowed {
+            Some(allowed) if allowed.len() < index.n_docs_containing(term) as usize => allowed
+                .iter()
+                .copied()
+                .filter(|doc_id| index.term_frequency(*doc_id, term) > 0)
+                .collect(),
+            Some(allowed) => index
+                .doc_ids_containing(term)
+                .into_iter()
+                .filter(|doc_id| allowed.contains(doc_id))
+                .collect(),
+            None => index.doc_ids_containing(term),
+        };
+        for doc_id in doc_ids {
+            let score = match search.scorer {
+                Scorer::TfIdf => index.calc_tf_idf(doc_id, term),
+                Scorer::Bm25 => index.calc_bm25(doc_id, term, BM25_K, BM25_B),
+            };
+            let score = query.term_weight(term) * boost * score;
             scores.add_score(doc_id, score);
         }
     }
-    let top_n_results = scores.get_top_n(top_n);
+    scores
+}
 
-    let collection_path = repo_dir.join(COLLECTION_FILENAME);
-    let inv_collection = InvertedCollection::from_file(collection_path)?;
-    let top_n_results = top_n_results
+/// Finds the `k` documents with the highest scores against the tokenized
+/// `query`, with their scores, the same as [`score_documents`] scores them,
+/// ordered from the best one.
+///
+/// The postings of the terms are read in blocks, and the documents that
+/// cannot rank among the best, by the largest scores of the terms in their
+/// blocks, are skipped without being scored. If the postings of the index
+/// are ordered by impact, they are read from the blocks that score the most,
+/// and only until the rest cannot change the best documents. The boosts of
+/// the fields must not be negative. If a set of allowed documents is given,
+/// the other documents are never ranked.
+pub fn score_top_documents(
+    index: &dyn Index,
+    query: &Query,
+    search: &SearchConfig,
+    allowed: Option<&HashSet<DocumentId>>,
+    k: usize,
+) -> Vec<(DocumentId, f64)> {
+    let terms = query.iter().collect::<Vec<_>>();
+    let weights = terms
+        .iter()
+        .map(|(term, _)| query.term_weight(term) * search.boost(term))
+        .collect::<Vec<_>>();
+    let idfs = terms
         .iter()
-        .map(|(doc_id, score)| (inv_collection.get_path(**doc_id).unwrap(), *score))
+        .map(|(term, _)| index.calc_idf(term))
         .collect::<Vec<_>>();
+    let n_docs_containing = terms
+        .iter()
+        .map(|(term, _)| index.n_docs_containing(term))
+        .collect::<Vec<_>>();
+    let n_docs = index.n_docs();
+    let avg = index.avg_n_terms();
+    let tf_weighting = index.tf_weighting();
 
-    display_results(top_n_results)?;
-    Ok(())
+    let score = |term: usize, frequency: u32, n_terms: u32| {
+        let score = match search.scorer {
+            Scorer::TfIdf => calc_tf_idf(tf_weighting.calc(frequency, n_terms), idfs[term]),
+            Scorer::Bm25 => calc_bm25(
+                frequency,
+                n_docs,
+                n_docs_containing[term],
+                n_terms,
+                avg,
+                BM25_K,
+                BM25_B,
+            ),
+        };
+        weights[term] * score
+    };
+    let is_allowed = |doc_id: DocumentId| allowed.is_none_or(|allowed| allowed.contains(&doc_id));
+    let n_terms = |doc_id: DocumentId| index.n_terms(doc_id);
+
+    if terms.len() <= impact::MAX_IMPACT_TERMS {
+        let impact_postings = terms
+            .iter()
+            .map(|(term, _)| index.impact_postings(term))
+            .collect::<Option<Vec<_>>>();
+        if let Some(impact_postings) = impact_postings {
+            let frequency = |term: usize, doc_id| index.term_frequency(doc_id, terms[term].0);
+            match impact::top_k_impact(impact_postings, score, n_terms, frequency, is_allowed, k) {
+                Ok(ranked) => return ranked,
+                Err(err) => eprintln!("ERROR: Failed to read postings ordered by impact: {err}"),
+            }
+        }
+    }
+    let postings = terms
+        .iter()
+        .map(|(term, _)| index.term_postings(term))
+        .collect::<Vec<_>>();
+    topk::top_k(&postings, score, n_terms, is_allowed, k)
 }
 
-fn display_results(results: Vec<(&PathBuf, &f64)>) -> io::Result<()> {
+/// Writes the results to stdout as a table, with their ranks, titles, paths
+/// and scores. Fetched web pages are shown with their URLs instead of their
+/// paths. If a snippet policy is given, the snippet of each document is
+/// written below its result, with the fragments joined. The best passages of
+/// each document, if they were scored, are written below it with their
+/// scores. The results are numbered from `first_rank`.
+///
+/// The marker styles the table: with colors, the ranks and the directories
+/// of the paths are dimmed, the names of the files are bold, and the matches
+/// of the query in the snippets and the passages are highlighted.
+pub fn display_results(
+    results: Vec<Hit>,
+    first_rank: usize,
+    store: &DocumentStore,
+    query: &Query,
+    analyzer: &AnalyzerConfig,
+    snippet_config: Option<&SnippetConfig>,
+    marker: Marker,
+) -> io::Result<()> {
     let stdout = io::stdout();
     let stdout = stdout.lock();
-    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
-    writeln!(tw, "\tNo\tPath\tScore")?;
-    for (i, (path, score)) in results.iter().enumerate() {
-        writeln!(tw, "\t{i}\t{}\t{score}", path.display())?;
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2).ansi(true);
+    writeln!(tw, "\tNo\tDocument\tScore")?;
+    for (i, hit) in results.iter().enumerate() {
+        let rank = marker.dim(&(first_rank + i).to_string());
+        let title = hit.title.replace('\t', " ");
+        let path = match hit.url {
+            Some(url) => url.to_string(),
+            None => marker.path(hit.path),
+        };
+        let copies = match hit.copies.len() {
+            0 => String::new(),
+            1 => format!(" {}", marker.dim("+1 copy")),
+            n => format!(" {}", marker.dim(&format!("+{n} copies"))),
+        };
+        writeln!(
+            tw,
+            "\t{rank}\t{title} \u{2014} {path}{copies}\t{:.4}",
+            hit.score
+        )?;
+        for passage in &hit.passages {
+            let text = passage.snippet.highlight(marker).replace(['\n', '\t'], " ");
+            writeln!(tw, "\t\t{text}\t{:.4}", passage.score)?;
+        }
+        let Some(config) = snippet_config else {
+            continue;
+        };
+        if let Ok(text) = store.read(hit.doc_id) {
+            let matches = find_matches(&text, hit.path, query, analyzer);
+            let snippets = snippets(&text, &matches, config);
+            let snippet = join_highlighted(&snippets, text.len(), &config.separator, marker)
+                .replace(['\n', '\t'], " ");
+            writeln!(tw, "\t\t{snippet}")?;
+        }
     }
     tw.flush()
 }
+
+/// Converts the hits of a search into results, with the offsets of the
+/// matches and, if a snippet policy is given, the snippets of the documents.
+/// Documents whose text is not in the document store, because the
+/// repository was indexed before the store existed, have no snippets and
+/// matches. The results are ranked from `first_rank`.
+pub fn search_results<'a>(
+    hits: Vec<Hit<'a>>,
+    first_rank: usize,
+    store: &DocumentStore,
+    query: &Query,
+    analyzer: &AnalyzerConfig,
+    snippet_config: Option<&SnippetConfig>,
+) -> Vec<SearchResult<'a>> {
+    hits.into_iter()
+        .enumerate()
+        .map(|(i, hit)| search_result(hit, first_rank + i, store, query, analyzer, snippet_config))
+        .collect()
+}
+
+/// Writes the results of a search to stdout as NDJSON, each as soon as its
+/// snippets and matches are found, so that the results are never all held
+/// in memory, and a tool that reads them can start on the first before the
+/// last is written. If the reader stops reading, like `head` does, the rest
+/// of the results are not written.
+fn stream_results(
+    hits: Vec<Hit>,
+    first_rank: usize,
+    store: &DocumentStore,
+    query: &Query,
+    analyzer: &AnalyzerConfig,
+    snippet_config: Option<&SnippetConfig>,
+) -> anyhow::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (i, hit) in hits.into_iter().enumerate() {
+        let result = search_result(hit, first_rank + i, store, query, analyzer, snippet_config);
+        let written = serde_json::to_writer(&mut stdout, &result)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(stdout))
+            .and_then(|()| stdout.flush());
+        match written {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Converts a hit of a search into a result, with the offsets of its matches
+/// and, if a snippet policy is given, its snippets.
+fn search_result<'a>(
+    hit: Hit<'a>,
+    rank: usize,
+    store: &DocumentStore,
+    query: &Query,
+    analyzer: &AnalyzerConfig,
+    snippet_config: Option<&SnippetConfig>,
+) -> SearchResult<'a> {
+    let (snippets, matches) = match store.read(hit.doc_id) {
+        Ok(text) => {
+            let matches = find_matches(&text, hit.path, query, analyzer);
+            let snippets = snippet_config
+                .map(|config| snippets(&text, &matches, config))
+                .unwrap_or_default();
+            (snippets, offsets(&text, &matches))
+        }
+        Err(_) => (vec![], vec![]),
+    };
+    SearchResult {
+        rank,
+        doc_id: hit.doc_id,
+        path: hit.path,
+        title: hit.title,
+        url: hit.url,
+        score: hit.score,
+        snippets,
+        matches,
+        passages: hit.passages,
+        copies: hit.copies.into_iter().map(PathBuf::as_path).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::cli::utils::content_hash;
+
+    use super::*;
+
+    #[test]
+    fn test_metadata_filter() {
+        let day = Duration::from_secs(86_400);
+        let mut entry = CollectionEntry::new(0, UNIX_EPOCH + day, 2048, Some("MD".to_string()));
+        entry.add_tag("project:alpha");
+        assert!(MetadataFilter::default().matches(&entry));
+
+        let filter = MetadataFilter {
+            extensions: vec!["rs".to_string(), ".md".to_string()],
+            modified_after: Some(UNIX_EPOCH + day),
+            modified_before: Some(UNIX_EPOCH + 2 * day),
+            min_size: Some(1024),
+            max_size: Some(2048),
+            tags: vec!["project:alpha".to_string()],
+            path_prefixes: vec![],
+        };
+        assert!(filter.matches(&entry));
+
+        let filter = MetadataFilter {
+            extensions: vec!["rs".to_string()],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&entry));
+
+        let filter = MetadataFilter {
+            modified_before: Some(UNIX_EPOCH + day),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&entry));
+
+        let filter = MetadataFilter {
+            max_size: Some(1024),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&entry));
+
+        let filter = MetadataFilter {
+            tags: vec!["project:alpha".to_string(), "draft".to_string()],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&entry));
+
+        // The date of an event is matched instead of the time it was modified.
+        let entry = CollectionEntry::new(1, UNIX_EPOCH + day, 512, Some("ics".to_string()))
+            .with_date(Some("2024-03-15".to_string()));
+        let filter = MetadataFilter {
+            modified_after: Some(parse_date("2024-03-01").unwrap()),
+            modified_before: Some(parse_date("2024-04-01").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&entry));
+        let filter = MetadataFilter {
+            modified_after: Some(parse_date("2024-03-16").unwrap()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&entry));
+    }
+
+    #[test]
+    fn test_path_prefixes() {
+        let roots = vec![PathBuf::from("/corpus"), PathBuf::from("/notes")];
+        let prefixes = ["./src/".to_string(), "docs/**/*.md".to_string()];
+        let prefixes = PathPrefixes::new(&prefixes, roots.clone()).unwrap();
+        assert!(prefixes.matches(Path::new("/corpus/src/main.rs")));
+        assert!(prefixes.matches(Path::new("/notes/src/ideas.md")));
+        assert!(!prefixes.matches(Path::new("/corpus/srcs/main.rs")));
+        assert!(prefixes.matches(Path::new("/corpus/docs/guide/intro.md")));
+        assert!(!prefixes.matches(Path::new("/corpus/docs/guide/intro.txt")));
+        let prefixes = PathPrefixes::new(&[], roots).unwrap();
+        assert!(prefixes.matches(Path::new("/corpus/a.md")));
+    }
+
+    #[test]
+    fn test_canonical_paths() {
+        let search = SearchConfig {
+            canonical_paths: vec![
+                CanonicalPath {
+                    pattern: "^build/".to_string(),
+                    replacement: String::new(),
+                },
+                CanonicalPath {
+                    pattern: "^dist/".to_string(),
+                    replacement: "src/".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let repo_dir = Path::new("/notes/.searchine");
+        let canonical = CanonicalPaths::new(repo_dir, &search).unwrap().unwrap();
+        assert_eq!(
+            canonical.canonical(Path::new("/notes/build/main.js")),
+            "main.js"
+        );
+        assert_eq!(
+            canonical.canonical(Path::new("/notes/dist/main.js")),
+            "src/main.js"
+        );
+        assert_eq!(
+            canonical.canonical(Path::new("/notes/src/main.js")),
+            "src/main.js"
+        );
+
+        let paths = [
+            "/notes/dist/main.js",
+            "/notes/src/main.js",
+            "/notes/main.js",
+        ]
+        .map(PathBuf::from);
+        let hits = paths
+            .iter()
+            .enumerate()
+            .map(|(doc_id, path)| Hit {
+                doc_id: doc_id as DocumentId,
+                path,
+                title: "main",
+                url: None,
+                score: 1.0,
+                passages: vec![],
+                copies: vec![],
+            })
+            .collect();
+        let hits = canonical.dedup(hits);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].path, &paths[0]);
+        assert!(CanonicalPaths::new(repo_dir, &SearchConfig::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_facets() {
+        let roots = [PathBuf::from("/notes"), PathBuf::from("/work/docs")];
+        let paths = [
+            "/notes/report.md",
+            "/notes/projects/a/plan.md",
+            "/notes/projects/b/main.rs",
+            "/work/docs/shared/Makefile",
+        ];
+        let facets = Facets::count(paths.iter().map(Path::new), &roots);
+
+        let extensions = [("(none)", 1), ("md", 2), ("rs", 1)];
+        let extensions = extensions.map(|(extension, count)| (extension.to_string(), count));
+        assert_eq!(facets.extensions, BTreeMap::from(extensions));
+        let directories = [(".", 1), ("projects", 2), ("shared", 1)];
+        let directories = directories.map(|(directory, count)| (directory.to_string(), count));
+        assert_eq!(facets.directories, BTreeMap::from(directories));
+    }
+
+    #[test]
+    fn test_collapse_duplicates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let store = DocumentStore::create(dir).unwrap();
+        let texts = [
+            "The quarterly budget of the team.",
+            "A plan for the next release of the parser.",
+            "The quarterly budget of the team.",
+            "A plan for the next release of the parser!",
+            "",
+        ];
+        for (doc_id, text) in texts.iter().enumerate() {
+            let doc_id = doc_id as DocumentId;
+            let fingerprint = Fingerprint::of_text(text);
+            store
+                .write_content_hash(doc_id, &content_hash(text))
+                .unwrap();
+            store.write_fingerprint(doc_id, fingerprint).unwrap();
+        }
+        store.write_content_hash(5, &content_hash("")).unwrap();
+        let paths = (0..6)
+            .map(|doc_id| PathBuf::from(format!("/notes/{doc_id}.md")))
+            .collect::<Vec<_>>();
+        let hits = paths
+            .iter()
+            .enumerate()
+            .map(|(doc_id, path)| Hit {
+                doc_id: doc_id as DocumentId,
+                path,
+                title: "notes",
+                url: None,
+                score: 1.0,
+                passages: vec![],
+                copies: vec![],
+            })
+            .collect();
+
+        let hits = collapse_duplicates(hits, &store, 0);
+        let doc_ids = hits.iter().map(|hit| hit.doc_id).collect::<Vec<_>>();
+        assert_eq!(doc_ids, [0, 1, 4]);
+        assert_eq!(hits[0].copies, [&paths[2]]);
+        assert_eq!(hits[1].copies, [&paths[3]]);
+        assert_eq!(hits[2].copies, [&paths[5]]);
+    }
+
+    #[test]
+    fn test_search_result_matches() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let store = DocumentStore::create(dir).unwrap();
+        let text = "Καλημέρα. The budget of the team, and its Budget for next year.";
+        store.write(0, text).unwrap();
+        let analyzer = AnalyzerConfig::default();
+        let query = analyzer.tokenize_query("budget", false);
+        let path = PathBuf::from("/notes/budget.md");
+        let hit = Hit {
+            doc_id: 0,
+            path: &path,
+            title: "budget",
+            url: None,
+            score: 1.0,
+            passages: vec![],
+            copies: vec![],
+        };
+
+        // The records of `--output json` and `--output ndjson` are the same
+        // results, so their matches point into the stored text by bytes.
+        let result = search_result(hit, 1, &store, &query, &analyzer, None);
+        let record = serde_json::to_value(&result).unwrap();
+        let matches = record["matches"].as_array().unwrap();
+        let spans = matches
+            .iter()
+            .map(|span| {
+                let start = span["byte_start"].as_u64().unwrap() as usize;
+                let end = span["byte_end"].as_u64().unwrap() as usize;
+                (start, end)
+            })
+            .collect::<Vec<_>>();
+        let first = text.find("budget").unwrap();
+        let second = text.find("Budget").unwrap();
+        assert_eq!(spans, [(first, first + 6), (second, second + 6)]);
+        assert_eq!(matches[0]["char_start"], 14);
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use documents::DocumentId;
+use embed::Embedder;
+use index::collection::Collection;
+use index::store::DocumentStore;
+use index::vector::{Quantization, QuantizedIndex, VectorIndex, VectorReader};
+
+use crate::config::{
+    COLLECTION_FILENAME, EMBED_TEXT_CHARS, QUANTIZED_VECTORS_FILENAME, STORE_DIRNAME,
+    VECTORS_FILENAME, VECTOR_RESCORE_FACTOR,
+};
+use crate::settings::Settings;
+
+/// The storage policy of the semantic index.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct VectorConfig {
+    /// The method that the vectors are quantized with for searching. If it
+    /// is set, a quantized copy of the vectors is searched, and only the best
+    /// candidates are scored with their exact vectors.
+    pub quantization: Option<Quantization>,
+}
+
+/// Updates the semantic index of a repository, if an embedder is configured.
+///
+/// The vectors of removed documents are dropped, and the added documents are
+/// embedded from their stored text. If the semantic index does not exist, or
+/// it was built with another model, all the documents of the collection are
+/// embedded again. If quantization is configured, the quantized copy of the
+/// vectors is written again, and otherwise it is removed. Errors are printed
+/// rather than returned, so a failing embedding provider does not stop the
+/// lexical index from being updated.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `added`    - The IDs of the new and modified documents.
+/// * `removed`  - The IDs of the modified and removed documents.
+pub fn update_vectors(repo_dir: impl AsRef<Path>, added: &[DocumentId], removed: &[DocumentId]) {
+    let repo_dir = repo_dir.as_ref();
+    if let Err(err) = try_update_vectors(repo_dir, added, removed) {
+        eprintln!("ERROR: Failed to update semantic index because: {err:#}");
+    }
+}
+
+fn try_update_vectors(
+    repo_dir: &Path,
+    added: &[DocumentId],
+    removed: &[DocumentId],
+) -> anyhow::Result<()> {
+    let settings = Settings::load(repo_dir)?;
+    let Some(config) = settings.embedder else {
+        return Ok(());
+    };
+    let quantization = settings.vectors.quantization;
+    let embedder = config.build()?;
+    let vectors_path = repo_dir.join(VECTORS_FILENAME);
+    let quantized_path = repo_dir.join(QUANTIZED_VECTORS_FILENAME);
+    let existing = match vectors_path.exists() {
+        true => Some(VectorIndex::from_file(&vectors_path)?),
+        false => None,
+    };
+
+    let (mut vectors, added) = match existing {
+        Some(vectors) if vectors.model_id() == embedder.model_id() => {
+            let quantized = QuantizedIndex::from_file(&quantized_path).ok();
+            let is_quantized = quantized.map(|quantized| quantized.quantization());
+            if added.is_empty() && removed.is_empty() && is_quantized == quantization {
+                return Ok(());
+            }
+            (vectors, added.to_vec())
+        }
+        _ => {
+            let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+            let doc_ids = collection
+                .into_iter()
+                .map(|(_, entry)| entry.document_id())
+                .collect::<Vec<_>>();
+            (VectorIndex::new(embedder.model_id()), doc_ids)
+        }
+    };
+    for doc_id in removed {
+        vectors.remove(*doc_id);
+    }
+    embed_documents(&mut vectors, embedder.as_ref(), repo_dir, &added)?;
+
+    let quantized = quantization.map(|quantization| QuantizedIndex::build(&vectors, quantization));
+    vectors.into_file(vectors_path)?;
+    match quantized {
+        Some(quantized) => quantized.into_file(quantized_path)?,
+        None if quantized_path.exists() => fs::remove_file(quantized_path)?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// Embeds the stored text of documents, and inserts their vectors into the
+/// semantic index. The text is truncated, as embedding models only read the
+/// start of long texts anyway. Documents whose text is not stored are
+/// skipped.
+fn embed_documents(
+    vectors: &mut VectorIndex,
+    embedder: &dyn Embedder,
+    repo_dir: &Path,
+    doc_ids: &[DocumentId],
+) -> anyhow::Result<()> {
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let mut embedded_ids = vec![];
+    let mut texts = vec![];
+    for doc_id in doc_ids {
+        if let Ok(text) = store.read(*doc_id) {
+            let end = text
+                .char_indices()
+                .nth(EMBED_TEXT_CHARS)
+                .map_or(text.len(), |(i, _)| i);
+            embedded_ids.push(*doc_id);
+            texts.push(text[..end].to_string());
+        }
+    }
+
+    let texts = texts.iter().map(String::as_str).collect::<Vec<_>>();
+    let embeddings = embedder.embed(&texts)?;
+    for (doc_id, embedding) in embedded_ids.into_iter().zip(embeddings) {
+        vectors.insert(doc_id, embedding)?;
+    }
+    Ok(())
+}
+
+/// Finds the `top_n` documents that are semantically the most similar to a
+/// free text query.
+///
+/// If the vectors are quantized, the quantized vectors are searched, and the
+/// best candidates are scored again with their exact vectors.
+///
+/// # Errors
+///
+/// Returns an error if the semantic index does not exist, or if it was built
+/// with another model than the one of the embedder.
+pub fn search(
+    repo_dir: impl AsRef<Path>,
+    embedder: &dyn Embedder,
+    query: &str,
+    top_n: u32,
+) -> anyhow::Result<Vec<(DocumentId, f64)>> {
+    let repo_dir = repo_dir.as_ref();
+    let vectors_path = repo_dir.join(VECTORS_FILENAME);
+    if !vectors_path.exists() {
+        anyhow::bail!(
+            "Semantic index does not exist. Run `searchine index` to build it with the embedder."
+        );
+    }
+    let quantized_path = repo_dir.join(QUANTIZED_VECTORS_FILENAME);
+    let results = match quantized_path.exists() {
+        true => {
+            let quantized = QuantizedIndex::from_file(&quantized_path)?;
+            check_model(quantized.model_id(), embedder)?;
+            let query_vector = embedder.embed_one(query)?;
+            let mut exact = VectorReader::open(&vectors_path)?;
+            let n_candidates = top_n as usize * VECTOR_RESCORE_FACTOR;
+            quantized.search(&query_vector, top_n as usize, n_candidates, &mut exact)?
+        }
+        false => {
+            let vectors = VectorIndex::from_file(&vectors_path)?;
+            check_model(vectors.model_id(), embedder)?;
+            let query_vector = embedder.embed_one(query)?;
+            vectors.search(&query_vector, top_n as usize)
+        }
+    };
+    Ok(results
+        .into_iter()
+        .map(|(doc_id, similarity)| (doc_id, similarity as f64))
+        .collect())
+}
+
+/// Calculates the semantic similarities of documents to a free text query.
+/// Documents without a vector in the semantic index are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the semantic index does not exist, or if it was built
+/// with another model than the one of the embedder.
+pub fn similarities(
+    repo_dir: impl AsRef<Path>,
+    embedder: &dyn Embedder,
+    query: &str,
+    doc_ids: &[DocumentId],
+) -> anyhow::Result<Vec<(DocumentId, f64)>> {
+    let vectors_path = repo_dir.as_ref().join(VECTORS_FILENAME);
+    if !vectors_path.exists() {
+        anyhow::bail!(
+            "Semantic index does not exist. Run `searchine index` to build it with the embedder."
+        );
+    }
+    let vectors = VectorIndex::from_file(&vectors_path)?;
+    check_model(vectors.model_id(), embedder)?;
+    let query_vector = embedder.embed_one(query)?;
+    Ok(vectors
+        .similarities(&query_vector, doc_ids)
+        .into_iter()
+        .map(|(doc_id, similarity)| (doc_id, similarity as f64))
+        .collect())
+}
+
+/// Checks that the semantic index was built with the model of the embedder.
+fn check_model(model_id: &str, embedder: &dyn Embedder) -> anyhow::Result<()> {
+    if model_id != embedder.model_id() {
+        anyhow::bail!(
+            "Semantic index was built with {model_id}, but the embedder is {}. {}",
+            embedder.model_id(),
+            "Run `searchine index` to build it again."
+        );
+    }
+    Ok(())
+}
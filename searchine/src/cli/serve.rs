@@ -0,0 +1,649 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use documents::DocumentId;
+use index::collection::InvertedCollection;
+use index::inverted::Index;
+use index::store::DocumentStore;
+use query::{Query, QueryRewriter};
+use tokenize::Token;
+
+use crate::analyzer::AnalyzerConfig;
+use crate::audit::{AuditEvent, AuditLog};
+use crate::cache::QueryCache;
+use crate::cli::search::{
+    cache_key, matching_documents, prepare_query, rank, ranked_hits, search_results,
+    CanonicalPaths, DocumentBoosts, MetadataFilter, Ranking, SearchConfig,
+};
+use crate::cli::utils::parse_query;
+use crate::config::{CONFIG_POLL_MILLIS, SERVE_LAST_QUERIES, STORE_DIRNAME};
+use crate::highlight::{find_matches, offsets, Offsets};
+use crate::path::corpus_dir;
+use crate::reload::ConfigWatcher;
+use crate::settings::Settings;
+use crate::shutdown;
+use crate::snapshot::Snapshot;
+use crate::snippet::SnippetConfig;
+use crate::synonyms::Thesaurus;
+
+/// The configuration of the server, read from the `[serve]` section of the
+/// config of a repository.
+///
+/// ```toml
+/// [serve.keys]
+/// "key-of-team-a" = ["projects/a", "shared"]
+/// "key-of-admin" = ["."]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    /// The API keys of the clients, each with the path prefixes of the
+    /// documents that it can see. Relative prefixes are relative to the
+    /// indexed directory. If there are no keys, any client sees all the
+    /// documents.
+    pub keys: HashMap<String, Vec<PathBuf>>,
+}
+
+/// The files of a repository that are loaded once, when the server starts,
+/// and are shared by all requests, along with the settings that can be
+/// reloaded while it runs.
+struct ServerState {
+    index: Box<dyn Index>,
+    inv_collection: InvertedCollection,
+    store: DocumentStore,
+    analyzer: AnalyzerConfig,
+    search: SearchConfig,
+    boosts: Option<DocumentBoosts>,
+    canonical: Option<CanonicalPaths>,
+    snippet_config: SnippetConfig,
+    rewriter: Option<Box<dyn QueryRewriter>>,
+    /// The synonyms that expand the queries, as they were when the server
+    /// started.
+    thesaurus: Thesaurus,
+    /// The documents that each API key can see, if access is scoped.
+    scopes: Option<HashMap<String, HashSet<DocumentId>>>,
+    audit: Option<AuditLog>,
+    /// The last query of each client, by its address and API key.
+    last_queries: RefCell<HashMap<String, LastQuery>>,
+    /// The ranked documents of recent queries, if they are cached.
+    cache: Option<RefCell<QueryCache>>,
+}
+
+/// The last query of a client, so that a query that refines it, by adding
+/// terms to it, only looks up the documents of the terms it adds.
+struct LastQuery {
+    /// The terms of the query.
+    terms: HashSet<Token>,
+    /// The documents that contain at least one of the terms, and that the
+    /// client can see.
+    candidates: HashSet<DocumentId>,
+}
+
+impl LastQuery {
+    /// Checks if a query refines this one, that is, it has all of its terms.
+    /// The documents that match a refinement are the candidates of this
+    /// query, and the documents of the terms that the refinement adds.
+    fn is_refined_by(&self, query: &Query) -> bool {
+        !self.terms.is_empty() && self.terms.iter().all(|term| query.term_count(term) > 0)
+    }
+}
+
+/// The stored text of a document, as it is returned by the preview endpoint.
+#[derive(Serialize)]
+struct Preview<'a> {
+    doc_id: DocumentId,
+    path: &'a PathBuf,
+    text: String,
+    matches: Vec<Offsets>,
+}
+
+/// The body of an error response.
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// A response of the server, with its status code and JSON body.
+type JsonResponse = (u16, String);
+
+/// Serves the index of a repository over HTTP.
+///
+/// The index, the collection and the document store are loaded once, so
+/// each query only pays for scoring. The endpoints are:
+///
+/// * `GET /search?q=...&top=10` - The most relevant documents to a query,
+///   in the form of the JSON output of `searchine search`. The query is
+///   prepared like the one of `searchine search`, with its phrases, its
+///   patterns and its synonyms. The number of documents defaults to the one
+///   in the `[search]` section of the config.
+///   A query that has all the terms of the last query of the same client,
+///   like a query that is narrowed down term by term, reuses the documents
+///   that matched the last query, so only the postings of the terms it adds
+///   are read. If the cache is enabled in
+///   the `[cache]` section of the config, the ranked documents of recent
+///   queries are kept in memory, and repeated queries are not ranked again.
+/// * `GET /doc/{id}/preview?q=...` - The stored text of a document, with
+///   the offsets of the matches of an optional query.
+///
+/// Requests are handled one at a time, until the process is asked to stop,
+/// after which the request that is being handled is answered. The index is
+/// opened as a snapshot, and it is not reloaded, so the server keeps
+/// answering from the same generation of the index, and it must be
+/// restarted to see changes.
+///
+/// The config file is watched, and the changes of its `[search]`,
+/// `[snippet]`, `[rewrite]`, `[serve]`, `[audit]` and `[cache]` sections
+/// apply to the requests that come after them. The changes of sections that
+/// require indexing the repository again, like `[analyzer]`, are reported,
+/// and they do not apply until the server is restarted on the new index.
+///
+/// If API keys are configured, each request must have one, either in an
+/// `X-Api-Key` header or as an `Authorization: Bearer` token, and it only
+/// sees the documents under the path prefixes of its key.
+///
+/// If the audit log is enabled, every query, preview and denied request is
+/// written to it before its response is sent.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `address`  - The address to listen on, such as `127.0.0.1:7878`.
+pub fn invoke(repo_dir: impl AsRef<Path>, address: &str) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let dir_path = corpus_dir(repo_dir)?;
+    let mut settings = Settings::load(repo_dir)?;
+    settings.analyzer.check(repo_dir)?;
+    let Snapshot {
+        index,
+        inv_collection,
+        generation,
+    } = Snapshot::open(repo_dir)?;
+    let mut state = ServerState {
+        index,
+        scopes: scopes(&settings.serve, dir_path, &inv_collection),
+        audit: AuditLog::with_config(repo_dir, &settings.audit),
+        inv_collection,
+        store: DocumentStore::open(repo_dir.join(STORE_DIRNAME))?,
+        rewriter: settings.rewrite.build(),
+        thesaurus: Thesaurus::load(repo_dir, &settings.analyzer)?,
+        analyzer: settings.analyzer.clone(),
+        boosts: DocumentBoosts::new(repo_dir, &settings.search)?,
+        canonical: CanonicalPaths::new(repo_dir, &settings.search)?,
+        search: settings.search.clone(),
+        snippet_config: settings.snippet.clone(),
+        last_queries: RefCell::new(HashMap::new()),
+        cache: QueryCache::with_config(&settings.cache, generation).map(RefCell::new),
+    };
+    let config = ConfigWatcher::new(repo_dir)?;
+
+    let server = Server::http(address)
+        .map_err(|err| anyhow::anyhow!("Failed to listen on {address} because: {err}"))?;
+    println_bold!("Serving {} on http://{address}", repo_dir.display());
+    if let Some(scopes) = &state.scopes {
+        println_bold!("Access is scoped by {} API keys.", scopes.len());
+    }
+    // Requests are waited for with a timeout, so changes of the config are
+    // applied while the server is idle too.
+    let timeout = Duration::from_millis(CONFIG_POLL_MILLIS);
+    while !shutdown::requested() {
+        if let Some(reloaded) = config.reload(&settings) {
+            let search = &reloaded.search;
+            let rules = DocumentBoosts::new(repo_dir, search)
+                .and_then(|boosts| Ok((boosts, CanonicalPaths::new(repo_dir, search)?)));
+            match rules {
+                Ok((boosts, canonical)) => {
+                    state.boosts = boosts;
+                    state.canonical = canonical;
+                }
+                Err(err) => {
+                    eprintln!("ERROR: Failed to apply the [search] settings because: {err:#}")
+                }
+            }
+            state.search = reloaded.search.clone();
+            state.snippet_config = reloaded.snippet.clone();
+            state.rewriter = reloaded.rewrite.build();
+            state.scopes = scopes(&reloaded.serve, dir_path, &state.inv_collection);
+            state.audit = AuditLog::with_config(repo_dir, &reloaded.audit);
+            // The candidates of the last queries may not be visible to
+            // their clients anymore, and the results of cached queries may
+            // be ranked differently.
+            state.last_queries.borrow_mut().clear();
+            state.cache = QueryCache::with_config(&reloaded.cache, generation).map(RefCell::new);
+            settings = reloaded;
+        }
+        let request = server
+            .recv_timeout(timeout)
+            .context("Failed to receive request.")?;
+        if let Some(request) = request {
+            if let Err(err) = respond(&state, request) {
+                eprintln!("ERROR: Failed to respond to request because: {err}");
+            }
+        }
+    }
+    println_bold!("Stopped serving {}", repo_dir.display());
+    Ok(())
+}
+
+/// Finds the documents that each API key can see, from the path prefixes of
+/// the key. If no keys are configured, access is not scoped.
+fn scopes(
+    config: &ServeConfig,
+    dir_path: &Path,
+    inv_collection: &InvertedCollection,
+) -> Option<HashMap<String, HashSet<DocumentId>>> {
+    if config.keys.is_empty() {
+        return None;
+    }
+    let scopes = config
+        .keys
+        .iter()
+        .map(|(key, prefixes)| {
+            let prefixes = prefixes
+                .iter()
+                .map(|prefix| {
+                    let prefix = dir_path.join(prefix);
+                    prefix.canonicalize().unwrap_or(prefix)
+                })
+                .collect::<Vec<_>>();
+            let doc_ids = inv_collection
+                .iter()
+                .filter(|(_, path)| prefixes.iter().any(|prefix| path.starts_with(prefix)))
+                .map(|(doc_id, _)| doc_id)
+                .collect();
+            (key.clone(), doc_ids)
+        })
+        .collect();
+    Some(scopes)
+}
+
+/// Routes a request to its endpoint, and sends the response.
+fn respond(state: &ServerState, request: Request) -> anyhow::Result<()> {
+    let key = api_key(&request);
+    let actor = actor(&request, key);
+    let client = client(&request, key);
+    let (status, body) = match (request.method(), scope(state, key)) {
+        (Method::Get, Ok(allowed)) => route(state, request.url(), allowed, &actor, &client, key),
+        (Method::Get, Err(response)) => {
+            let event = AuditEvent::Denied { url: request.url() };
+            audited(state, &actor, &event, response)
+        }
+        _ => error(405, "Only GET requests are supported."),
+    };
+    let content_type = Header::from_bytes("Content-Type", "application/json")
+        .map_err(|_| anyhow::anyhow!("Invalid header."))?;
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    request
+        .respond(response)
+        .context("Failed to send response.")
+}
+
+/// Finds the API key of a request, in its `X-Api-Key` header, or as a token
+/// in its `Authorization` header.
+fn api_key(request: &Request) -> Option<&str> {
+    request.headers().iter().find_map(|header| {
+        let value = header.value.as_str();
+        if header.field.equiv("X-Api-Key") {
+            Some(value.trim())
+        } else if header.field.equiv("Authorization") {
+            value.strip_prefix("Bearer ").map(str::trim)
+        } else {
+            None
+        }
+    })
+}
+
+/// Describes the client of a request for the audit log, by its address and
+/// the last characters of its API key, so the key itself is never logged.
+fn actor(request: &Request, key: Option<&str>) -> String {
+    let address = request
+        .remote_addr()
+        .map_or("unknown".to_string(), |address| address.to_string());
+    match key {
+        Some(key) => {
+            let suffix = key
+                .char_indices()
+                .rev()
+                .nth(3)
+                .map_or("", |(i, _)| &key[i..]);
+            format!("{address} key ...{suffix}")
+        }
+        None => address,
+    }
+}
+
+/// Identifies the client of a request, by the IP address, without the port,
+/// which changes between connections, and the API key.
+fn client(request: &Request, key: Option<&str>) -> String {
+    let address = request
+        .remote_addr()
+        .map_or("unknown".to_string(), |address| address.ip().to_string());
+    format!("{address} {}", key.unwrap_or_default())
+}
+
+/// Finds the documents that the client with an API key can see. If access
+/// is scoped, the key must be known, and `None` means that all the documents
+/// can be seen.
+fn scope<'a>(
+    state: &'a ServerState,
+    key: Option<&str>,
+) -> Result<Option<&'a HashSet<DocumentId>>, JsonResponse> {
+    let Some(scopes) = &state.scopes else {
+        return Ok(None);
+    };
+    match key.and_then(|key| scopes.get(key)) {
+        Some(allowed) => Ok(Some(allowed)),
+        None => Err(error(401, "Missing or unknown API key.")),
+    }
+}
+
+/// Writes an event to the audit log, if it is enabled, before its response
+/// is sent. If the event cannot be written, an error is sent instead of the
+/// response, so that no access goes unaudited.
+fn audited(
+    state: &ServerState,
+    actor: &str,
+    event: &AuditEvent,
+    response: JsonResponse,
+) -> JsonResponse {
+    let Some(audit) = &state.audit else {
+        return response;
+    };
+    match audit.append(actor, event) {
+        Ok(()) => response,
+        Err(err) => error(500, &format!("Failed to write audit log because: {err:#}")),
+    }
+}
+
+/// Finds the endpoint of a URL, and handles the request, only with the
+/// allowed documents, if they are given.
+fn route(
+    state: &ServerState,
+    url: &str,
+    allowed: Option<&HashSet<DocumentId>>,
+    actor: &str,
+    client: &str,
+    key: Option<&str>,
+) -> JsonResponse {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = parse_params(query);
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+        ["search"] => search(state, &params, allowed, actor, client, key),
+        ["doc", doc_id, "preview"] => match doc_id.parse() {
+            Ok(doc_id) => {
+                let response = preview(state, doc_id, &params, allowed);
+                let found = response.0 == 200;
+                audited(
+                    state,
+                    actor,
+                    &AuditEvent::Preview { doc_id, found },
+                    response,
+                )
+            }
+            Err(_) => error(400, &format!("Invalid document ID: {doc_id}")),
+        },
+        _ => error(404, &format!("Not found: {path}")),
+    }
+}
+
+/// Handles `GET /search`.
+fn search(
+    state: &ServerState,
+    params: &HashMap<String, String>,
+    allowed: Option<&HashSet<DocumentId>>,
+    actor: &str,
+    client: &str,
+    key: Option<&str>,
+) -> JsonResponse {
+    let Some(raw_query) = params.get("q") else {
+        return error(400, "Missing query parameter `q`.");
+    };
+    let query = match parse_query(raw_query, state.rewriter.as_deref()) {
+        Ok(query) => query,
+        Err(err) => return error(500, &format!("Failed to rewrite query because: {err:#}")),
+    };
+    let top_n = match params.get("top").map(|top| top.parse::<u32>()) {
+        None => state.search.top_n,
+        Some(Ok(top_n)) => top_n,
+        Some(Err(_)) => return error(400, "Parameter `top` must be a number."),
+    };
+    let index = state.index.as_ref();
+    let prepared = match prepare_query(
+        index,
+        &state.analyzer,
+        &state.thesaurus,
+        &query.lexical,
+        query.case_sensitive,
+    ) {
+        Ok(prepared) => prepared,
+        Err(err) => return error(400, &format!("Invalid query: {err:#}")),
+    };
+    // The documents that do not match the phrases of the query are never
+    // ranked.
+    let phrase_allowed = prepared.phrase_matches(
+        index,
+        &state.store,
+        &state.inv_collection,
+        &state.analyzer,
+        allowed,
+    );
+    let has_phrases = phrase_allowed.is_some();
+    let allowed = phrase_allowed.as_ref().or(allowed);
+    let terms = prepared.terms;
+    let ranking = Ranking {
+        top_n,
+        search: &state.search,
+        boosts: state.boosts.as_ref(),
+        analyzer: &state.analyzer,
+        passage_config: None,
+        canonical: state.canonical.as_ref(),
+        collapse: None,
+    };
+    // Clients with different API keys can see different documents, so they
+    // only share the results of their queries if access is not scoped.
+    let scope = key.filter(|_| state.scopes.is_some()).unwrap_or_default();
+    let filter = MetadataFilter::default();
+    let text_key = cache_key(&query.lexical, query.case_sensitive, &ranking, &filter);
+    let cache_key = format!("{scope}\u{0}{text_key}");
+    let cached = state
+        .cache
+        .as_ref()
+        .and_then(|cache| cache.borrow_mut().get(&cache_key));
+    let mut last_queries = state.last_queries.borrow_mut();
+    let hits = match cached {
+        Some(ranked) => {
+            // The documents that matched the query are not known, so the
+            // next query of the client is not a refinement of it.
+            last_queries.remove(client);
+            ranked_hits(ranked, &state.inv_collection)
+        }
+        None => {
+            // The candidates of the last query are not restricted to the
+            // documents that match the phrases of this one.
+            let last = last_queries.remove(client).filter(|_| !has_phrases);
+            let candidates = candidates(index, last, &terms, allowed);
+            let hits = match rank(
+                index,
+                &state.inv_collection,
+                &state.store,
+                &terms,
+                &ranking,
+                Some(&candidates),
+            ) {
+                Ok(hits) => hits,
+                Err(err) => return error(500, &format!("Failed to search because: {err:#}")),
+            };
+            if last_queries.len() >= SERVE_LAST_QUERIES {
+                last_queries.clear();
+            }
+            // Neither are the candidates of a query with phrases kept for the
+            // next query, which may not have them.
+            if !has_phrases {
+                let last = LastQuery {
+                    terms: terms.iter().map(|(term, _)| term.clone()).collect(),
+                    candidates,
+                };
+                last_queries.insert(client.to_string(), last);
+            }
+            if let Some(cache) = &state.cache {
+                let ranked = hits.iter().map(|hit| (hit.doc_id(), hit.score())).collect();
+                cache.borrow_mut().insert(cache_key, ranked);
+            }
+            hits
+        }
+    };
+    drop(last_queries);
+    let event = AuditEvent::Search {
+        query: raw_query,
+        results: hits.iter().map(|hit| hit.doc_id()).collect(),
+    };
+    let results = search_results(
+        hits,
+        0,
+        &state.store,
+        &terms,
+        &state.analyzer,
+        Some(&state.snippet_config),
+    );
+    audited(state, actor, &event, json(200, &results))
+}
+
+/// Finds the documents that contain at least one of the terms of a query,
+/// and that are allowed. If the query refines the last query of the client,
+/// the documents that matched the last query are kept, and only the terms
+/// that the query adds are looked up, so the documents are the same as if
+/// there was no last query.
+fn candidates(
+    index: &dyn Index,
+    last: Option<LastQuery>,
+    query: &Query,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> HashSet<DocumentId> {
+    match last {
+        Some(last) if last.is_refined_by(query) => {
+            let LastQuery {
+                terms,
+                mut candidates,
+            } = last;
+            let added = query
+                .iter()
+                .filter(|(term, _)| !terms.contains(*term))
+                .flat_map(|(term, _)| index.doc_ids_containing(term))
+                .filter(|doc_id| allowed.is_none_or(|allowed| allowed.contains(doc_id)));
+            candidates.extend(added);
+            candidates
+        }
+        _ => matching_documents(index, query, allowed),
+    }
+}
+
+/// Handles `GET /doc/{id}/preview`.
+fn preview(
+    state: &ServerState,
+    doc_id: DocumentId,
+    params: &HashMap<String, String>,
+    allowed: Option<&HashSet<DocumentId>>,
+) -> JsonResponse {
+    // Documents that cannot be seen are not found, so their IDs are not
+    // revealed either.
+    let is_allowed = allowed.is_none_or(|allowed| allowed.contains(&doc_id));
+    let Some(path) = state.inv_collection.get_path(doc_id).filter(|_| is_allowed) else {
+        return error(404, &format!("Document {doc_id} is not indexed."));
+    };
+    let Ok(text) = state.store.read(doc_id) else {
+        return error(404, &format!("Text of document {doc_id} is not stored."));
+    };
+    let matches = match params.get("q") {
+        Some(query) => {
+            let terms = state.analyzer.tokenize_query(query, false);
+            offsets(&text, &find_matches(&text, path, &terms, &state.analyzer))
+        }
+        None => vec![],
+    };
+    json(
+        200,
+        &Preview {
+            doc_id,
+            path,
+            text,
+            matches,
+        },
+    )
+}
+
+/// Parses the parameters of a URL query string. If a parameter is repeated,
+/// its last value is kept.
+fn parse_params(query: &str) -> HashMap<String, String> {
+    form_urlencoded::parse(query.as_bytes())
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect()
+}
+
+/// Creates a response with a value serialized as its JSON body.
+fn json(status: u16, value: &impl Serialize) -> JsonResponse {
+    match serde_json::to_string(value) {
+        Ok(body) => (status, body),
+        Err(err) => error(500, &format!("Failed to serialize response because: {err}")),
+    }
+}
+
+/// Creates an error response with a message.
+fn error(status: u16, message: &str) -> JsonResponse {
+    let body = ErrorBody {
+        error: message.to_string(),
+    };
+    (status, serde_json::to_string(&body).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let mut index = FrequencyIndex::new();
+        let documents = [
+            vec!["budget"],
+            vec!["report"],
+            vec!["budget", "report"],
+            vec!["holiday"],
+        ];
+        for (doc_id, tokens) in documents.into_iter().enumerate() {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id as DocumentId);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            index.index(doc_index);
+        }
+        let query = |terms: &[&str]| Query::from(terms.iter().map(|term| term.to_string()));
+        let last_query = |terms: &[&str], candidates| LastQuery {
+            terms: terms.iter().map(|term| term.to_string()).collect(),
+            candidates,
+        };
+
+        let budget = candidates(&index, None, &query(&["budget"]), None);
+        assert_eq!(budget, HashSet::from([0, 2]));
+        let refined = query(&["budget", "report"]);
+        let last = last_query(&["budget"], budget);
+        let without_last = candidates(&index, None, &refined, None);
+        assert_eq!(candidates(&index, Some(last), &refined, None), without_last);
+        assert_eq!(without_last, HashSet::from([0, 1, 2]));
+
+        let allowed = HashSet::from([0, 2]);
+        let last = last_query(&["budget"], HashSet::from([0, 2]));
+        let refined = candidates(&index, Some(last), &refined, Some(&allowed));
+        assert_eq!(refined, allowed);
+        let last = last_query(&["budget"], HashSet::from([0, 2]));
+        let other = candidates(&index, Some(last), &query(&["holiday"]), None);
+        assert_eq!(other, HashSet::from([3]));
+    }
+}
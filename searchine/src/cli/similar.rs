@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use anyhow::Context;
+
+use documents::archive;
+use documents::DocumentId;
+use index::inverted::Index;
+use index::store::DocumentStore;
+use query::Query;
+use tokenize::Token;
+
+use crate::cli::search::{
+    display_notice, display_results, rank, search_results, CanonicalPaths, DocumentBoosts,
+    Ranking,
+};
+use crate::cli::utils::write_records;
+use crate::cli::OutputFormat;
+use crate::config::{SIMILAR_TERMS, STORE_DIRNAME};
+use crate::highlight::Marker;
+use crate::settings::Settings;
+use crate::snapshot::Snapshot;
+
+/// Finds the documents that are the most similar to an indexed document,
+/// like the notes that are related to a note.
+///
+/// The terms of the stored text of the document are weighted by their
+/// TF-IDF in the document, and the `SIMILAR_TERMS` terms with the highest
+/// weights, which are in other documents too, are searched for as a query.
+/// The document itself is left out of the results. The documents are ranked
+/// with the `[search]` section of the config, like the results of a search.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `doc_path` - The path to the indexed document.
+/// * `top_n`    - The number of similar documents to list.
+/// * `output`   - The format the documents are written in.
+///
+/// # Errors
+///
+/// Returns an error if the document is not indexed, or if its text is not
+/// in the document store.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    doc_path: impl AsRef<Path>,
+    top_n: u32,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let doc_path = doc_path.as_ref();
+    let doc_path = archive::canonicalize(doc_path)
+        .context(format!("Failed to find document: {}", doc_path.display()))?;
+    let snapshot = Snapshot::open(repo_dir)?;
+    let index = snapshot.index.as_ref();
+    let inv_collection = &snapshot.inv_collection;
+    let doc_id = inv_collection
+        .iter()
+        .find_map(|(doc_id, path)| (*path == doc_path).then_some(doc_id))
+        .context(format!("Document {} is not indexed.", doc_path.display()))?;
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let text = store.read(doc_id).context(format!(
+        "Text of {} is not stored. Run `searchine index` to re-index the repository.",
+        doc_path.display()
+    ))?;
+    let settings = Settings::load(repo_dir)?;
+    let analyzer = &settings.analyzer;
+    analyzer.check(repo_dir)?;
+
+    let tokens = analyzer.tokenizer().tokenize(text);
+    let terms = top_terms(index, doc_id, tokens, SIMILAR_TERMS);
+    let message = format!(
+        "Documents similar to {}, by: {}",
+        doc_path.display(),
+        terms.join(" ")
+    );
+    display_notice(&message, output);
+    let terms = Query::from(terms);
+
+    let search = settings.search_config(None)?;
+    let boosts = DocumentBoosts::new(repo_dir, &search)?;
+    let canonical = CanonicalPaths::new(repo_dir, &search)?;
+    let ranking = Ranking {
+        top_n: top_n.saturating_add(1),
+        search: &search,
+        boosts: boosts.as_ref(),
+        analyzer,
+        passage_config: None,
+        canonical: canonical.as_ref(),
+        collapse: None,
+    };
+    let mut hits = rank(index, inv_collection, &store, &terms, &ranking, None)?;
+    hits.retain(|hit| hit.doc_id() != doc_id);
+    hits.truncate(top_n as usize);
+
+    match output {
+        OutputFormat::Text => {
+            let marker = Marker::for_stdout(true);
+            display_results(hits, 0, &store, &terms, analyzer, None, marker)?;
+        }
+        _ => {
+            let results = search_results(hits, 0, &store, &terms, analyzer, None);
+            write_records(&results, output)?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the `top_k` terms of a document with the highest TF-IDF in it,
+/// from the terms of its text, ordered by their weights, and then by the
+/// terms. The terms that are in no other document are left out, as they
+/// cannot match another document.
+fn top_terms(
+    index: &dyn Index,
+    doc_id: DocumentId,
+    tokens: impl IntoIterator<Item = Token>,
+    top_k: usize,
+) -> Vec<Token> {
+    let mut terms = tokens
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|term| index.n_docs_containing(term) > 1)
+        .map(|term| (index.calc_tf_idf(doc_id, &term), term))
+        .filter(|(weight, _)| *weight > 0.0)
+        .collect::<Vec<_>>();
+    terms.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    terms.truncate(top_k);
+    terms.into_iter().map(|(_, term)| term).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_top_terms() {
+        let mut index = FrequencyIndex::new();
+        let documents = [
+            (0, vec!["budget", "budget", "meeting", "the", "unique"]),
+            (1, vec!["budget", "the"]),
+            (2, vec!["meeting", "the"]),
+            (3, vec!["the", "travel"]),
+        ];
+        for (doc_id, tokens) in documents {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            index.index(doc_index);
+        }
+
+        let tokens = ["budget", "budget", "meeting", "the", "unique"].map(String::from);
+        let terms = top_terms(&index, 0, tokens.clone(), 10);
+        assert_eq!(terms, ["budget", "meeting"]);
+        assert_eq!(top_terms(&index, 0, tokens, 1), ["budget"]);
+    }
+}
@@ -0,0 +1,250 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use serde::Serialize;
+
+use index::inverted::Index;
+use tokenize::{CASED_PREFIX, TITLE_PREFIX};
+
+use crate::cli::utils::{format_age, format_size, index_size, load_manifest, write_records};
+use crate::cli::OutputFormat;
+use crate::config::COLLECTION_FILENAME;
+use crate::scheduler::unix_time;
+use crate::snapshot::Snapshot;
+
+/// The statistics of the index of a repository, as they are written in the
+/// JSON output.
+#[derive(Debug, Serialize)]
+struct IndexStats {
+    documents: u32,
+    /// The number of distinct terms in all the fields.
+    vocabulary: usize,
+    /// The number of distinct terms of the titles.
+    title_terms: usize,
+    /// The number of distinct terms of the case-sensitive field.
+    cased_terms: usize,
+    /// The number of postings, that is, of the pairs of a term and a
+    /// document that contains it.
+    postings: u64,
+    /// The average number of terms of the text of a document.
+    avg_document_length: f64,
+    /// The number of terms of the text of the shortest document.
+    min_document_length: u32,
+    /// The median number of terms of the text of a document.
+    median_document_length: u32,
+    /// The number of terms of the text of the longest document.
+    max_document_length: u32,
+    segments: usize,
+    /// The number of documents that are deleted, but that are still in the
+    /// segment files, until they are compacted.
+    deleted_documents: usize,
+    /// The size of the segment files of the index, in bytes.
+    index_bytes: u64,
+    /// The time the documents were last indexed, in seconds since the Unix
+    /// epoch.
+    last_indexed: u64,
+    /// The terms of the text that are in the most documents.
+    top_terms: Vec<TermCount>,
+}
+
+/// A term of the index, with the number of documents that contain it.
+#[derive(Debug, PartialEq, Serialize)]
+struct TermCount {
+    term: String,
+    documents: u32,
+}
+
+impl IndexStats {
+    /// Counts the documents, the terms and the postings of an index, finds
+    /// the lengths of the documents, which the index stores, and the `top_k`
+    /// terms of the text that are in the most documents. The statistics of
+    /// the files of the index are left empty.
+    fn of(index: &dyn Index, top_k: usize) -> Self {
+        let documents = index.n_docs();
+        let mut lengths = index
+            .doc_lengths()
+            .into_iter()
+            .map(|(_, n_terms)| n_terms)
+            .collect::<Vec<_>>();
+        lengths.sort_unstable();
+        let mut stats = Self {
+            documents,
+            vocabulary: 0,
+            title_terms: 0,
+            cased_terms: 0,
+            postings: 0,
+            avg_document_length: index.avg_n_terms(),
+            min_document_length: lengths.first().copied().unwrap_or(0),
+            median_document_length: lengths.get(lengths.len() / 2).copied().unwrap_or(0),
+            max_document_length: lengths.last().copied().unwrap_or(0),
+            segments: 0,
+            deleted_documents: 0,
+            index_bytes: 0,
+            last_indexed: 0,
+            top_terms: vec![],
+        };
+        for term in index.terms() {
+            let n_docs = index.n_docs_containing(&term);
+            stats.vocabulary += 1;
+            stats.postings += u64::from(n_docs);
+            if term.starts_with(TITLE_PREFIX) {
+                stats.title_terms += 1;
+            } else if term.starts_with(CASED_PREFIX) {
+                stats.cased_terms += 1;
+            } else {
+                stats.top_terms.push(TermCount {
+                    term,
+                    documents: n_docs,
+                });
+            }
+        }
+        stats.top_terms.sort_by(|a, b| {
+            Reverse(a.documents)
+                .cmp(&Reverse(b.documents))
+                .then_with(|| a.term.cmp(&b.term))
+        });
+        stats.top_terms.truncate(top_k);
+        stats
+    }
+}
+
+/// Reports the statistics of the index of a repository, to tune the
+/// analyzer and to diagnose an index that grows too large: the number of
+/// documents, the size of the vocabulary, by field, the number of postings,
+/// the average, shortest, median and longest lengths of the documents, which
+/// scorers like BM25 normalize by, the size of the index on disk, the
+/// time the documents were last indexed, and the `top_k` terms of the text
+/// that are in the most documents, which are candidates for stopwords.
+///
+/// The index is read from a snapshot, so the statistics are of a single
+/// generation of the index. Every term of the vocabulary is looked up, but
+/// the postings are not read.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `top_k`    - The number of the most frequent terms to list.
+/// * `output`   - The format the statistics are written in.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    top_k: usize,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let snapshot = Snapshot::open(repo_dir)?;
+    let mut stats = IndexStats::of(snapshot.index.as_ref(), top_k);
+    let manifest = load_manifest(repo_dir)?;
+    stats.segments = manifest.segments().len();
+    stats.deleted_documents = manifest.n_deleted();
+    stats.index_bytes = index_size(repo_dir)?;
+    // The collection is written every time documents are indexed, but not
+    // when the segments of the index are merged.
+    stats.last_indexed = fs::metadata(repo_dir.join(COLLECTION_FILENAME))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs());
+    match output {
+        OutputFormat::Text => display_stats(&stats)?,
+        _ => write_records(&[stats], output)?,
+    }
+    Ok(())
+}
+
+/// Writes the statistics to stdout as a table, followed by the terms that
+/// are in the most documents.
+fn display_stats(stats: &IndexStats) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    writeln!(tw, "Documents:\t{}", stats.documents)?;
+    writeln!(
+        tw,
+        "Vocabulary:\t{} terms ({} in titles, {} case-sensitive)",
+        stats.vocabulary, stats.title_terms, stats.cased_terms
+    )?;
+    writeln!(tw, "Postings:\t{}", stats.postings)?;
+    writeln!(
+        tw,
+        "Average document length:\t{:.1} terms",
+        stats.avg_document_length
+    )?;
+    writeln!(
+        tw,
+        "Document lengths:\t{} to {} terms, {} median",
+        stats.min_document_length, stats.max_document_length, stats.median_document_length
+    )?;
+    writeln!(
+        tw,
+        "Index size:\t{} in {} segments",
+        format_size(stats.index_bytes),
+        stats.segments
+    )?;
+    if stats.deleted_documents > 0 {
+        writeln!(
+            tw,
+            "Deleted documents:\t{} (run `searchine compact` to reclaim their space)",
+            stats.deleted_documents
+        )?;
+    }
+    writeln!(
+        tw,
+        "Last indexed:\t{}",
+        format_age(unix_time().saturating_sub(stats.last_indexed))
+    )?;
+    if !stats.top_terms.is_empty() {
+        writeln!(tw, "\nMost frequent terms:\tDocuments")?;
+        for term in &stats.top_terms {
+            writeln!(tw, "  {}\t{}", term.term, term.documents)?;
+        }
+    }
+    tw.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_index_stats() {
+        let mut index = FrequencyIndex::new();
+        let documents = [
+            (
+                0,
+                vec!["budget", "report", "budget"],
+                vec!["^notes", "=Budget"],
+            ),
+            (1, vec!["budget", "meeting", "agenda"], vec!["^notes"]),
+        ];
+        for (doc_id, tokens, field_tokens) in documents {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            doc_index.index_field_tokens(field_tokens.into_iter().map(String::from));
+            index.index(doc_index);
+        }
+
+        let stats = IndexStats::of(&index, 2);
+        assert_eq!(stats.documents, 2);
+        assert_eq!(stats.vocabulary, 6);
+        assert_eq!(stats.title_terms, 1);
+        assert_eq!(stats.cased_terms, 1);
+        assert_eq!(stats.postings, 8);
+        assert_eq!(stats.avg_document_length, 3.0);
+        assert_eq!(stats.min_document_length, 3);
+        assert_eq!(stats.max_document_length, 3);
+        let top_terms = stats
+            .top_terms
+            .iter()
+            .map(|term| (term.term.as_str(), term.documents));
+        assert_eq!(
+            top_terms.collect::<Vec<_>>(),
+            [("budget", 2), ("agenda", 1)]
+        );
+    }
+}
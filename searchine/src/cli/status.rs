@@ -1,15 +1,46 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
+use serde::Serialize;
 
 use index::collection::Collection;
 
-use crate::cli::utils::{fetch_modified_files, fetch_new_files, fetch_removed_files};
+use crate::cli::utils::{
+    fetch_modified_files, fetch_new_files, fetch_removed_files, format_size, write_records,
+};
+use crate::cli::OutputFormat;
 use crate::config::COLLECTION_FILENAME;
 use crate::fs::Directory;
+use crate::scheduler::{unix_time, MergeStatus};
 
-/// Checks for new files, removed files, and modified files.
-pub fn invoke(repo_dir: impl AsRef<Path>, verbose: bool) -> anyhow::Result<()> {
+/// A change of a document, as it is written in the JSON output. The change is
+/// one of `added`, `removed` and `modified`.
+#[derive(Serialize)]
+struct ChangeRecord<'a> {
+    change: &'static str,
+    path: &'a Path,
+}
+
+/// Checks for new files, removed files, and modified files, by comparing the
+/// files of the directory and their last modified times against the ones in
+/// the collection.
+///
+/// In the text output, the changes are shown in three sections, with their
+/// counts, followed by the merges that run in the background, while the
+/// repository is watched. In the porcelain output, each change is written on
+/// a line, as `A`, `M` or `D` and the path, which is stable for scripts. In
+/// the JSON output formats, each change is written as a record. No changes
+/// are an empty output in both.
+///
+/// If `verbose` is true, the files of the directory that are skipped, as
+/// they are unsupported, binary, or larger than the maximum file size, are
+/// reported on stderr, so they never mix with the output.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    verbose: bool,
+    output: OutputFormat,
+    porcelain: bool,
+) -> anyhow::Result<()> {
     let repo_path = repo_dir.as_ref();
     let index_path = repo_path.join(COLLECTION_FILENAME);
 
@@ -18,63 +49,139 @@ pub fn invoke(repo_dir: impl AsRef<Path>, verbose: bool) -> anyhow::Result<()> {
         index_path.display()
     ))?;
 
-    let dir_path = repo_path.parent().context(format!(
-        "Could not get parent directory of the repo {}",
-        repo_path.display()
-    ))?;
-    let dir = Directory::new(dir_path)?;
+    let dir = Directory::for_repo(repo_path)?;
     let dir = dir.iter_full_paths(verbose).collect::<Vec<_>>();
 
     // Get the paths that are in the directory but not in the index.
-    // Add them to the corpus index
-    let new_paths = fetch_new_files(&corpus_index, &dir);
+    let mut new_paths = fetch_new_files(&corpus_index, &dir);
+    new_paths.sort();
 
     // Get the paths that are in the index but not in the directory.
-    // Remove them from the corpus index
-    let removed_paths = fetch_removed_files(&corpus_index, &dir);
+    let mut removed_paths = fetch_removed_files(&corpus_index, &dir);
+    removed_paths.sort();
 
     // Get the paths that are both in the directory and in the index,
     // but they have different modified times.
-    let modified_paths = fetch_modified_files(&corpus_index, &dir)?;
+    let mut modified_paths = fetch_modified_files(&corpus_index, &dir)?;
+    modified_paths.sort();
+
+    if porcelain {
+        let changes = [
+            ("A", &new_paths),
+            ("M", &modified_paths),
+            ("D", &removed_paths),
+        ];
+        for (change, paths) in changes {
+            for path in paths {
+                println!("{change} {}", path.display());
+            }
+        }
+        return Ok(());
+    }
+
+    if output != OutputFormat::Text {
+        let changes = [
+            ("added", &new_paths),
+            ("removed", &removed_paths),
+            ("modified", &modified_paths),
+        ];
+        let records = changes
+            .into_iter()
+            .flat_map(|(change, paths)| paths.iter().map(move |path| ChangeRecord { change, path }))
+            .collect::<Vec<_>>();
+        return write_records(&records, output);
+    }
 
     // Display updates
     if new_paths.is_empty() && removed_paths.is_empty() && modified_paths.is_empty() {
         let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
         println_bold!("{emoji} No changes detected. Index is up to date.");
     } else {
-        display_removed_files(&removed_paths);
         display_new_files(&new_paths);
         display_modified_files(&modified_paths);
+        display_removed_files(&removed_paths);
+        println_bold!(
+            "{} new, {} modified, {} deleted",
+            new_paths.len(),
+            modified_paths.len(),
+            removed_paths.len()
+        );
+        println!();
+    }
+    if let Some(status) = MergeStatus::load(repo_path) {
+        display_merges(&status);
     }
     Ok(())
 }
 
-fn display_removed_files(removed_files: &Vec<PathBuf>) {
-    for removed_file in removed_files {
-        let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x96]).unwrap_or_default();
-        println_bold!("Documents removed from the index:");
-        println_bold!("  (use \"searchine re-index\" to update the index)");
-        println_red!("  {emoji} removed: {}", removed_file.display());
+fn display_new_files(new_files: &[PathBuf]) {
+    if new_files.is_empty() {
+        return;
     }
-    println!();
-}
-
-fn display_new_files(new_files: &Vec<PathBuf>) {
+    let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x94]).unwrap_or_default();
+    println_bold!("New documents, not indexed yet ({}):", new_files.len());
+    println_bold!("  (use \"searchine index\" to add them to the index)");
     for new_file in new_files {
-        let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x94]).unwrap_or_default();
-        println_bold!("Documents to be added to the index:");
-        println_bold!("  (use \"searchine re-index\" to update the index)");
-        println_green!("  {emoji} added: {}", new_file.display());
+        println_green!("  {emoji} new: {}", new_file.display());
     }
     println!();
 }
 
-fn display_modified_files(modified_files: &Vec<PathBuf>) {
+fn display_modified_files(modified_files: &[PathBuf]) {
+    if modified_files.is_empty() {
+        return;
+    }
+    let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x8F]).unwrap_or_default();
+    println_bold!(
+        "Documents modified since they were indexed ({}):",
+        modified_files.len()
+    );
+    println_bold!("  (use \"searchine index\" to update them in the index)");
     for modified_file in modified_files {
-        let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x8F]).unwrap_or_default();
-        println_bold!("Documents to be updated in the index:");
-        println_bold!("  (use \"searchine re-index\" to update the index)");
         println_red!("  {emoji} modified: {}", modified_file.display());
     }
     println!();
 }
+
+fn display_removed_files(removed_files: &[PathBuf]) {
+    if removed_files.is_empty() {
+        return;
+    }
+    let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x96]).unwrap_or_default();
+    println_bold!(
+        "Indexed documents that no longer exist ({}):",
+        removed_files.len()
+    );
+    println_bold!(
+        "  (use \"searchine index\" or \"searchine prune --deleted\" to drop them from the index)"
+    );
+    for removed_file in removed_files {
+        println_red!("  {emoji} deleted: {}", removed_file.display());
+    }
+    println!();
+}
+
+fn display_merges(status: &MergeStatus) {
+    println_bold!("Segments merged in the background:");
+    println_bold!("  (by \"searchine watch\", PID {})", status.pid);
+    for merge in &status.running {
+        let emoji = String::from_utf8(vec![0xE2, 0x9A, 0x99]).unwrap_or_default();
+        println!(
+            "  {emoji} merging {} segments ({}) for {}s",
+            merge.segments.len(),
+            format_size(merge.bytes),
+            unix_time().saturating_sub(merge.started)
+        );
+    }
+    println!(
+        "  {} running, {} completed, {} abandoned, {} failed",
+        status.running.len(),
+        status.completed,
+        status.abandoned,
+        status.failed
+    );
+    if let Some(err) = &status.last_error {
+        println_red!("  last error: {err}");
+    }
+    println!();
+}
@@ -0,0 +1,115 @@
+use std::cmp::Reverse;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use index::inverted::Index;
+use tokenize::{CASED_PREFIX, TITLE_PREFIX};
+
+use crate::cli::utils::write_records;
+use crate::cli::OutputFormat;
+use crate::snapshot::Snapshot;
+
+/// A completion of a prefix, as it is written in the JSON output.
+#[derive(Debug, PartialEq, Serialize)]
+struct Completion {
+    term: String,
+    /// The number of documents that contain the term.
+    documents: u32,
+}
+
+/// Completes a prefix with the terms of the index that start with it, for
+/// shell integrations and editor plugins that complete queries.
+///
+/// The prefix is lowercased, like the terms of the text, and the `top_k`
+/// terms that are in the most documents are listed, with the number of
+/// documents that contain them. The terms are the ones of the index, so
+/// they may be stemmed. The terms of the titles and of the case-sensitive
+/// field are not completed.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `prefix`   - The prefix of the terms.
+/// * `top_k`    - The number of terms to list.
+/// * `output`   - The format the terms are written in.
+pub fn invoke(
+    repo_dir: impl AsRef<Path>,
+    prefix: &str,
+    top_k: usize,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let snapshot = Snapshot::open(repo_dir)?;
+    let completions = complete(snapshot.index.as_ref(), prefix, top_k);
+    match output {
+        OutputFormat::Text => display_completions(&completions)?,
+        _ => write_records(&completions, output)?,
+    }
+    Ok(())
+}
+
+/// Finds the `top_k` terms of an index that start with a prefix, ordered by
+/// the number of documents that contain them, and then by the terms.
+fn complete(index: &dyn Index, prefix: &str, top_k: usize) -> Vec<Completion> {
+    let prefix = prefix.trim().to_lowercase();
+    let mut completions = index
+        .terms_with_prefix(&prefix)
+        .into_iter()
+        .filter(|term| !term.starts_with([TITLE_PREFIX, CASED_PREFIX]))
+        .map(|term| Completion {
+            documents: index.n_docs_containing(&term),
+            term,
+        })
+        .collect::<Vec<_>>();
+    completions.sort_by(|a, b| {
+        Reverse(a.documents)
+            .cmp(&Reverse(b.documents))
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    completions.truncate(top_k);
+    completions
+}
+
+/// Writes the completions to stdout as a table.
+fn display_completions(completions: &[Completion]) -> io::Result<()> {
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut tw = tabwriter::TabWriter::new(stdout).padding(2);
+    for completion in completions {
+        writeln!(tw, "{}\t{}", completion.term, completion.documents)?;
+    }
+    tw.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_complete() {
+        let mut index = FrequencyIndex::new();
+        let documents = [
+            (0, vec!["search", "searchine", "^search"]),
+            (1, vec!["search", "seal"]),
+            (2, vec!["seal", "index"]),
+        ];
+        for (doc_id, tokens) in documents {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.into_iter().map(String::from));
+            index.index(doc_index);
+        }
+
+        let terms = complete(&index, "Se", 10)
+            .into_iter()
+            .map(|completion| (completion.term, completion.documents))
+            .collect::<Vec<_>>();
+        let expected = [("seal", 2), ("search", 2), ("searchine", 1)];
+        assert_eq!(terms, expected.map(|(term, n)| (term.to_string(), n)));
+        assert_eq!(complete(&index, "sea", 1)[0].term, "seal");
+        assert!(complete(&index, "x", 10).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tabwriter::TabWriter;
+
+use documents::archive;
+use index::collection::Collection;
+
+use crate::cache::QueryCache;
+use crate::config::COLLECTION_FILENAME;
+
+/// Adds tags to a document of the collection of a repository, so that
+/// searches can be restricted to the documents with a tag with `--tag`.
+///
+/// The tags are kept in the collection, so they last until the document is
+/// removed from the corpus, whether it is modified or the repository is
+/// indexed again.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `doc_path` - The path to the document.
+/// * `tags`     - The tags to add, like `project:alpha`.
+///
+/// # Errors
+///
+/// Returns an error if a tag is empty or has whitespace, if the document is
+/// not in the collection, or if the collection cannot be read or written.
+pub fn add(
+    repo_dir: impl AsRef<Path>,
+    doc_path: impl AsRef<Path>,
+    tags: &[String],
+) -> anyhow::Result<()> {
+    if let Some(tag) = tags.iter().find(|tag| !is_valid(tag)) {
+        anyhow::bail!("Invalid tag {tag:?}: tags cannot be empty or have whitespace.");
+    }
+    let repo_dir = repo_dir.as_ref();
+    let doc_path = resolve(doc_path.as_ref())?;
+    let mut collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let entry = collection
+        .get_mut(&doc_path)
+        .with_context(|| format!("Document {} is not indexed.", doc_path.display()))?;
+    let added = tags
+        .iter()
+        .filter(|tag| entry.add_tag(tag.as_str()))
+        .count();
+    save(repo_dir, collection)?;
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x8F, 0xB7]).unwrap_or_default();
+    println_bold!("{emoji} Added {added} tags to {}.", doc_path.display());
+    Ok(())
+}
+
+/// Removes tags from a document of the collection of a repository. Tags
+/// that the document does not have are skipped.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `doc_path` - The path to the document.
+/// * `tags`     - The tags to remove.
+///
+/// # Errors
+///
+/// Returns an error if the document is not in the collection, or if the
+/// collection cannot be read or written.
+pub fn remove(
+    repo_dir: impl AsRef<Path>,
+    doc_path: impl AsRef<Path>,
+    tags: &[String],
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let doc_path = resolve(doc_path.as_ref())?;
+    let mut collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let entry = collection
+        .get_mut(&doc_path)
+        .with_context(|| format!("Document {} is not indexed.", doc_path.display()))?;
+    let removed = tags.iter().filter(|tag| entry.remove_tag(tag)).count();
+    save(repo_dir, collection)?;
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x8F, 0xB7]).unwrap_or_default();
+    println_bold!("{emoji} Removed {removed} tags from {}.", doc_path.display());
+    Ok(())
+}
+
+/// Lists the tags of a document of the collection of a repository, or, if
+/// no document is given, every tag of the collection with the number of
+/// documents that have it.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `doc_path` - The path to the document, if any.
+///
+/// # Errors
+///
+/// Returns an error if the document is not in the collection, or if the
+/// collection cannot be read.
+pub fn list(repo_dir: impl AsRef<Path>, doc_path: Option<impl AsRef<Path>>) -> anyhow::Result<()> {
+    let collection = Collection::from_file(repo_dir.as_ref().join(COLLECTION_FILENAME))?;
+    if let Some(doc_path) = doc_path {
+        let doc_path = resolve(doc_path.as_ref())?;
+        let entry = collection
+            .get(&doc_path)
+            .with_context(|| format!("Document {} is not indexed.", doc_path.display()))?;
+        for tag in entry.tags() {
+            println!("{tag}");
+        }
+        return Ok(());
+    }
+
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for (_, entry) in &collection {
+        for tag in entry.tags() {
+            *counts.entry(tag.as_str()).or_default() += 1;
+        }
+    }
+    let mut tab_writer = TabWriter::new(io::stdout());
+    _ = writeln!(tab_writer, "Tag\tDocuments");
+    for (tag, count) in counts {
+        _ = writeln!(tab_writer, "{tag}\t{count}");
+    }
+    tab_writer.flush()?;
+    Ok(())
+}
+
+/// Checks if a tag can be given to a document. Tags are matched exactly, so
+/// they cannot be empty, and they cannot have whitespace.
+fn is_valid(tag: &str) -> bool {
+    !tag.is_empty() && !tag.contains(char::is_whitespace)
+}
+
+/// Resolves the path to a document to the absolute path that it is kept by
+/// in the collection.
+fn resolve(doc_path: &Path) -> anyhow::Result<PathBuf> {
+    archive::canonicalize(doc_path)
+        .with_context(|| format!("Failed to find document: {}", doc_path.display()))
+}
+
+/// Writes the collection of a repository with the changed tags, and removes
+/// the cached results of queries, which may have been filtered by them.
+fn save(repo_dir: &Path, collection: Collection) -> anyhow::Result<()> {
+    collection.into_file(repo_dir.join(COLLECTION_FILENAME))?;
+    QueryCache::clear(repo_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("project:alpha"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("project alpha"));
+    }
+}
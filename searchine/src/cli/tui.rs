@@ -0,0 +1,458 @@
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use documents::archive::source_path;
+use index::collection::InvertedCollection;
+use index::inverted::Index;
+use index::store::DocumentStore;
+use query::{Query, QueryRewriter};
+
+use crate::cli::search::{
+    prepare_query, rank, CanonicalPaths, DocumentBoosts, Ranking, SearchConfig,
+};
+use crate::cli::utils::parse_query;
+use crate::config::{STORE_DIRNAME, TUI_EDITOR, TUI_PAGE_LEN, TUI_PREVIEW_FRAGMENTS, TUI_TOP_N};
+use crate::engine::SearchHit;
+use crate::fs::roots::relative_to_root;
+use crate::fs::CorpusRoots;
+use crate::highlight::find_matches;
+use crate::settings::Settings;
+use crate::snapshot::Snapshot;
+use crate::snippet::{snippets, SnippetConfig};
+use crate::synonyms::Thesaurus;
+
+/// The files of a repository that are loaded once, when the browser starts,
+/// and are shared by all the queries.
+struct Session {
+    index: Box<dyn Index>,
+    inv_collection: InvertedCollection,
+    store: DocumentStore,
+    settings: Settings,
+    search: SearchConfig,
+    boosts: Option<DocumentBoosts>,
+    canonical: Option<CanonicalPaths>,
+    rewriter: Option<Box<dyn QueryRewriter>>,
+    thesaurus: Thesaurus,
+    /// The root directories of the corpus, that the paths of the results
+    /// are shown relative to.
+    roots: Vec<PathBuf>,
+}
+
+impl Session {
+    /// Ranks the documents against a query, like `searchine search` does
+    /// for its lexical terms, its phrases and its patterns.
+    ///
+    /// # Returns
+    ///
+    /// The terms of the query, which the previews are highlighted with, and
+    /// the ranked documents.
+    fn search(&self, raw_query: &str) -> anyhow::Result<(Query, Vec<SearchHit>)> {
+        let analyzer = &self.settings.analyzer;
+        let index = self.index.as_ref();
+        let query = parse_query(raw_query, self.rewriter.as_deref())?;
+        let prepared = prepare_query(
+            index,
+            analyzer,
+            &self.thesaurus,
+            &query.lexical,
+            query.case_sensitive,
+        )?;
+        let ranking = Ranking {
+            top_n: TUI_TOP_N,
+            search: &self.search,
+            boosts: self.boosts.as_ref(),
+            analyzer,
+            passage_config: None,
+            canonical: self.canonical.as_ref(),
+            collapse: None,
+        };
+        let inv_collection = &self.inv_collection;
+        let allowed = prepared.phrase_matches(index, &self.store, inv_collection, analyzer, None);
+        let terms = prepared.terms;
+        let hits = rank(
+            index,
+            inv_collection,
+            &self.store,
+            &terms,
+            &ranking,
+            allowed.as_ref(),
+        )?
+        .iter()
+        .map(|hit| SearchHit {
+            doc_id: hit.doc_id(),
+            path: hit.path().to_path_buf(),
+            title: hit.title().to_string(),
+            url: hit.url().map(str::to_string),
+            score: hit.score(),
+        })
+        .collect();
+        Ok((terms, hits))
+    }
+
+    /// Returns the path of a document relative to the root directory of the
+    /// corpus that it is in.
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        relative_to_root(&self.roots, path)
+    }
+
+    /// Returns where a result is shown to be: the URL of a fetched web page,
+    /// or the path of the document relative to its root directory.
+    fn location(&self, hit: &SearchHit) -> String {
+        match &hit.url {
+            Some(url) => url.clone(),
+            None => self.relative(&hit.path).display().to_string(),
+        }
+    }
+}
+
+/// What the browser does after a key is pressed.
+enum Action {
+    None,
+    Search,
+    Open,
+    Quit,
+}
+
+/// The state of the browser: the query that is typed, its ranked results,
+/// and the one that is selected.
+struct Browser {
+    session: Session,
+    query: String,
+    /// The terms of the query that the results were ranked for.
+    terms: Query,
+    hits: Vec<SearchHit>,
+    list: ListState,
+    /// The message of the status line, like the error of a query, instead
+    /// of the help of the keys.
+    status: Option<String>,
+}
+
+impl Browser {
+    /// Updates the query, or the selection, with a pressed key.
+    fn handle(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if ctrl => return Action::Quit,
+            KeyCode::Enter => return Action::Open,
+            KeyCode::Up => self.list.select_previous(),
+            KeyCode::Char('p') if ctrl => self.list.select_previous(),
+            KeyCode::Down => self.list.select_next(),
+            KeyCode::Char('n') if ctrl => self.list.select_next(),
+            KeyCode::PageUp => self.list.scroll_up_by(TUI_PAGE_LEN),
+            KeyCode::PageDown => self.list.scroll_down_by(TUI_PAGE_LEN),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                return Action::Search;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                return Action::Search;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                return Action::Search;
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    /// Ranks the documents against the query, and selects the best one. If
+    /// the query is invalid, the results of the last query are kept, and the
+    /// error is shown in the status line.
+    fn search(&mut self) {
+        if self.query.trim().is_empty() {
+            self.hits.clear();
+            self.list.select(None);
+            self.status = None;
+            return;
+        }
+        match self.session.search(&self.query) {
+            Ok((terms, hits)) => {
+                self.terms = terms;
+                self.hits = hits;
+                self.list.select((!self.hits.is_empty()).then_some(0));
+                self.status = None;
+            }
+            Err(err) => self.status = Some(format!("{err:#}")),
+        }
+    }
+
+    /// Returns the result that is selected, if any.
+    fn selected(&self) -> Option<&SearchHit> {
+        self.list.selected().and_then(|i| self.hits.get(i))
+    }
+
+    /// Draws the query box, the results, the preview of the selected result
+    /// and the status line.
+    fn draw(&mut self, frame: &mut Frame) {
+        let [query_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [results_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(main_area);
+
+        let query = Paragraph::new(self.query.as_str()).block(Block::bordered().title(" Query "));
+        frame.render_widget(query, query_area);
+        let cursor_x = query_area.x + 1 + self.query.chars().count() as u16;
+        frame.set_cursor_position(Position::new(cursor_x, query_area.y + 1));
+
+        let items = self
+            .hits
+            .iter()
+            .map(|hit| {
+                let path = self.session.location(hit);
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{:.4}  ", hit.score)).dim(),
+                    Span::raw(hit.title.replace(['\n', '\t'], " ")),
+                    Span::raw(format!(" \u{2014} {path}")).dim(),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let results = List::new(items)
+            .block(Block::bordered().title(format!(" Results ({}) ", self.hits.len())))
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(results, results_area, &mut self.list);
+
+        let preview = self
+            .selected()
+            .map(|hit| self.preview(hit))
+            .unwrap_or_default();
+        let preview = Paragraph::new(preview)
+            .block(Block::bordered().title(" Preview "))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(preview, preview_area);
+
+        let help = "\u{2191}/\u{2193} select  Enter open in $EDITOR  Ctrl-U clear  Esc quit";
+        let status = match &self.status {
+            Some(message) => Line::from(message.as_str()).red(),
+            None => Line::from(help).dim(),
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+
+    /// Returns the preview of a result: its title, its path or URL, and the
+    /// snippets of its stored text, with the matches of the query
+    /// highlighted.
+    fn preview(&self, hit: &SearchHit) -> Text<'static> {
+        let session = &self.session;
+        let mut lines = vec![
+            Line::from(hit.title.clone()).bold(),
+            Line::from(session.location(hit)).dim(),
+            Line::default(),
+        ];
+        let Ok(text) = session.store.read(hit.doc_id) else {
+            lines.push(Line::from("Text is not stored. Run `searchine index --full`.").dim());
+            return Text::from(lines);
+        };
+        let analyzer = &session.settings.analyzer;
+        let matches = find_matches(&text, &hit.path, &self.terms, analyzer);
+        let config = &session.settings.snippet;
+        let config = SnippetConfig {
+            fragments: config.fragments.max(TUI_PREVIEW_FRAGMENTS),
+            ..config.clone()
+        };
+        for snippet in snippets(&text, &matches, &config) {
+            let matches = snippet
+                .matches
+                .iter()
+                .map(|offsets| offsets.byte_start..offsets.byte_end)
+                .collect::<Vec<_>>();
+            lines.push(highlighted_line(&snippet.text, &matches));
+            lines.push(Line::default());
+        }
+        Text::from(lines)
+    }
+}
+
+/// Opens a browser of the results of a repository in the terminal, which
+/// searches the query as it is typed.
+///
+/// The screen has a box for the query, the ranked results, and a preview of
+/// the selected result, with the snippets of its text and the matches of the
+/// query highlighted. The query has the syntax of `searchine search`, but
+/// only its lexical terms, its phrases and its patterns are searched for,
+/// ranked with the search config, with a ranking profile applied if one was
+/// selected. The index is read from a snapshot of it when the browser opens,
+/// so the documents that are indexed afterwards are found once it is opened
+/// again.
+///
+/// The up and down arrows, or `Ctrl-P` and `Ctrl-N`, select a result, and
+/// `Enter` opens its file in the editor of `$VISUAL` or `$EDITOR`, or in
+/// `TUI_EDITOR` if neither is set, and returns to the browser once the editor
+/// exits. `Esc` or `Ctrl-C` closes the browser.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `search`   - The search config, with the ranking profile applied.
+pub fn invoke(repo_dir: impl AsRef<Path>, search: SearchConfig) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let settings = Settings::load(repo_dir)?;
+    settings.analyzer.check(repo_dir)?;
+    let Snapshot {
+        index,
+        inv_collection,
+        ..
+    } = Snapshot::open(repo_dir)?;
+    let session = Session {
+        index,
+        inv_collection,
+        store: DocumentStore::open(repo_dir.join(STORE_DIRNAME))?,
+        boosts: DocumentBoosts::new(repo_dir, &search)?,
+        canonical: CanonicalPaths::new(repo_dir, &search)?,
+        rewriter: settings.rewrite.build(),
+        thesaurus: Thesaurus::load(repo_dir, &settings.analyzer)?,
+        roots: CorpusRoots::dirs(repo_dir)?,
+        settings,
+        search,
+    };
+    let mut browser = Browser {
+        session,
+        query: String::new(),
+        terms: Query::from(Vec::new()),
+        hits: vec![],
+        list: ListState::default(),
+        status: None,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut browser);
+    ratatui::restore();
+    result
+}
+
+/// Draws the browser and handles the pressed keys, until it is closed.
+fn run(terminal: &mut DefaultTerminal, browser: &mut Browser) -> anyhow::Result<()> {
+    loop {
+        terminal.draw(|frame| browser.draw(frame))?;
+        // The keys that were pressed while the results were ranked are all
+        // handled before the query is searched again, so a query that is
+        // typed quickly is not searched once for every key.
+        let mut changed = false;
+        loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    match browser.handle(key) {
+                        Action::Quit => return Ok(()),
+                        Action::Search => changed = true,
+                        Action::Open => {
+                            if let Some(hit) = browser.selected() {
+                                let path = source_path(&hit.path);
+                                browser.status = open_in_editor(terminal, &path)?;
+                            }
+                        }
+                        Action::None => {}
+                    }
+                }
+            }
+            if !event::poll(std::time::Duration::ZERO)? {
+                break;
+            }
+        }
+        if changed {
+            browser.search();
+        }
+    }
+}
+
+/// Opens a file in the editor, leaving the screen of the browser until the
+/// editor exits.
+///
+/// # Returns
+///
+/// The message of the status line if the editor cannot be run, or if it
+/// fails.
+///
+/// # Errors
+///
+/// Returns an error if the screen of the browser cannot be restored.
+fn open_in_editor(terminal: &mut DefaultTerminal, path: &Path) -> io::Result<Option<String>> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_default();
+    let (program, args) = editor_command(&editor);
+    ratatui::restore();
+    let status = Command::new(program).args(args).arg(path).status();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    Ok(editor_error(program, status))
+}
+
+/// Splits the command of an editor, like `code --wait`, into its program and
+/// its arguments. An empty command is `TUI_EDITOR`.
+fn editor_command(editor: &str) -> (&str, Vec<&str>) {
+    let mut words = editor.split_whitespace();
+    match words.next() {
+        Some(program) => (program, words.collect()),
+        None => (TUI_EDITOR, vec![]),
+    }
+}
+
+/// Returns the message of an editor that could not be run, or that failed.
+fn editor_error(program: &str, status: io::Result<ExitStatus>) -> Option<String> {
+    match status {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(format!("{program} exited with {status}.")),
+        Err(err) => Some(format!("Failed to run {program} because: {err}")),
+    }
+}
+
+/// Returns a text as a line, with its matches, given as sorted byte ranges,
+/// highlighted. Line breaks and tabs are replaced with spaces.
+fn highlighted_line(text: &str, matches: &[Range<usize>]) -> Line<'static> {
+    let span = |text: &str| text.replace(['\n', '\r', '\t'], " ");
+    let mut spans = vec![];
+    let mut pos = 0;
+    for range in matches {
+        spans.push(Span::raw(span(&text[pos..range.start])));
+        spans.push(Span::raw(span(&text[range.clone()])).red().bold());
+        pos = range.end;
+    }
+    spans.push(Span::raw(span(&text[pos..])));
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Modifier;
+
+    use super::*;
+
+    #[test]
+    fn test_highlighted_line() {
+        let line = highlighted_line("The budget\nmeeting", &[4..10, 11..18]);
+        let texts = line.spans.iter().map(|span| span.content.as_ref());
+        assert_eq!(
+            texts.collect::<Vec<_>>(),
+            ["The ", "budget", " ", "meeting", ""]
+        );
+        assert!(line.spans[1].style.add_modifier.contains(Modifier::BOLD));
+        assert!(line.spans[2].style.add_modifier.is_empty());
+    }
+
+    #[test]
+    fn test_editor_command() {
+        assert_eq!(editor_command("code --wait"), ("code", vec!["--wait"]));
+        assert_eq!(editor_command(" "), (TUI_EDITOR, vec![]));
+        assert!(editor_error("vi", Err(io::ErrorKind::NotFound.into())).is_some());
+    }
+}
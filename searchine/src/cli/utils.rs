@@ -1,12 +1,731 @@
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::Context;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
+use documents::archive::source_path;
+use documents::DocumentId;
 use index::collection::Collection;
+use index::compress::Compression;
+use index::inverted::builder::IndexBuilder;
+use index::inverted::freq::FrequencyIndex;
+use index::inverted::mapped::MappedIndex;
+use index::inverted::segmented::{Manifest, SegmentedIndex};
+use index::inverted::Index;
+use index::postings::PostingsOrder;
+use index::score::TfWeighting;
+use query::{HybridQuery, QueryRewriter};
+use tokenize::Token;
+
+use crate::cli::{IndexFormat, OutputFormat};
+use crate::config::{
+    COMPACT_DELETED_RATIO, LEGACY_INDEX_FILENAMES, MANIFEST_FILENAME, SEGMENTS_DIRNAME,
+    SEGMENTS_MERGE_THRESHOLD,
+};
+
+/// Hashes a string with the 64-bit FNV-1a hash, which is stable across
+/// platforms and releases, unlike the hasher of the standard library.
+pub fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Hashes the text of a document with SHA-256, in hex, so that documents
+/// with identical texts are found by their stored hashes.
+pub fn content_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text))
+}
+
+/// Returns the path to the directory of the segments of the index of a
+/// repository.
+pub fn segments_dir(repo_dir: impl AsRef<Path>) -> PathBuf {
+    repo_dir.as_ref().join(SEGMENTS_DIRNAME)
+}
+
+/// Checks if a repository has been indexed, that is, if the manifest of the
+/// segments of its index exists.
+pub fn is_indexed(repo_dir: impl AsRef<Path>) -> bool {
+    segments_dir(repo_dir).join(MANIFEST_FILENAME).exists()
+}
+
+/// Loads the manifest of the segments of the index of a repository.
+///
+/// # Errors
+///
+/// Returns an error if the repository has not been indexed, or if the
+/// manifest cannot be read.
+pub fn load_manifest(repo_dir: impl AsRef<Path>) -> anyhow::Result<Manifest> {
+    let repo_dir = repo_dir.as_ref();
+    if !is_indexed(repo_dir) {
+        anyhow::bail!(
+            "Index does not exist at: {}. Run `searchine index` to index the repository.",
+            repo_dir.display()
+        );
+    }
+    Ok(Manifest::from_file(
+        segments_dir(repo_dir).join(MANIFEST_FILENAME),
+    )?)
+}
+
+/// Returns the format of the newest segment of the index of a repository, so
+/// that updates are written in the format the repository was indexed with.
+/// If the repository has not been indexed, the default format is returned.
+pub fn index_format(repo_dir: impl AsRef<Path>) -> IndexFormat {
+    load_manifest(repo_dir)
+        .ok()
+        .and_then(|manifest| manifest.segments().last().map(IndexFormat::from_path))
+        .unwrap_or_default()
+}
+
+/// Opens the segments that are listed in a manifest of the index of a
+/// repository for querying, skipping their deleted documents.
+///
+/// Binary segments are memory-mapped, so only the postings lists that the
+/// queries need are read, while JSON segments are loaded as a whole.
+///
+/// # Errors
+///
+/// Returns an error if a segment cannot be read.
+pub fn open_segments(
+    repo_dir: impl AsRef<Path>,
+    manifest: &Manifest,
+) -> anyhow::Result<SegmentedIndex> {
+    let repo_dir = repo_dir.as_ref();
+    let segments = manifest
+        .segments()
+        .iter()
+        .map(|name| open_segment(segments_dir(repo_dir).join(name)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let deleted = manifest
+        .segments()
+        .iter()
+        .map(|name| manifest.deleted(name).cloned().unwrap_or_default())
+        .collect();
+    Ok(SegmentedIndex::new(segments)
+        .with_deleted(deleted)
+        .with_tf_weighting(manifest.tf_weighting()))
+}
+
+/// Loads a segment that is listed in a manifest of the index of a
+/// repository as a whole, without its deleted documents, so that it can be
+/// changed and written again.
+///
+/// # Errors
+///
+/// Returns an error if the segment cannot be read.
+pub fn load_segment(
+    repo_dir: impl AsRef<Path>,
+    manifest: &Manifest,
+    name: &str,
+) -> anyhow::Result<FrequencyIndex> {
+    let mut segment = FrequencyIndex::from_file(segments_dir(repo_dir).join(name))?;
+    for doc_id in manifest.deleted(name).into_iter().flatten() {
+        segment.remove(*doc_id);
+    }
+    Ok(segment)
+}
+
+/// Opens a segment for querying, in the format of its file. Compressed
+/// segments cannot be memory-mapped, so they are loaded as a whole.
+fn open_segment(path: impl AsRef<Path>) -> anyhow::Result<Box<dyn Index>> {
+    let path = path.as_ref();
+    let compressed = path.extension().and_then(|extension| extension.to_str())
+        == Compression::Zstd.extension();
+    Ok(match IndexFormat::from_path(path) {
+        IndexFormat::Binary if !compressed => Box::new(MappedIndex::open(path)?),
+        _ => Box::new(FrequencyIndex::from_file(path)?),
+    })
+}
+
+/// Returns the extension of the files of segments in a format and a
+/// compression, like `bin` or `bin.zst`.
+pub fn segment_extension(format: IndexFormat, compression: Compression) -> String {
+    match compression.extension() {
+        Some(extension) => format!("{}.{extension}", format.extension()),
+        None => format.extension().to_string(),
+    }
+}
+
+/// Writes an index as a new segment, in the compression of the manifest,
+/// and in its order of the postings if the segment is uncompressed binary,
+/// naming it with the manifest. The manifest is not written.
+fn write_segment(
+    segments_dir: &Path,
+    manifest: &mut Manifest,
+    index: FrequencyIndex,
+    format: IndexFormat,
+) -> anyhow::Result<String> {
+    let compression = manifest.compression();
+    let name = manifest.new_segment_name(&segment_extension(format, compression));
+    let path = segments_dir.join(&name);
+    match (format, compression) {
+        (IndexFormat::Binary, Compression::None) => {
+            index.into_ordered_file(path, manifest.postings_order())?
+        }
+        (IndexFormat::Json, Compression::None) => index.into_json_file(path)?,
+        (IndexFormat::Binary, _) => index.into_compressed_file(path, compression)?,
+        (IndexFormat::Json, _) => index.into_compressed_json_file(path, compression)?,
+    }
+    Ok(name)
+}
+
+/// Writes the index of a builder as a new segment, like [`write_segment`],
+/// merging the runs that the builder spilled into it. Binary segments are
+/// merged from the runs one term at a time, while JSON segments are merged
+/// in memory.
+fn write_built_segment(
+    segments_dir: &Path,
+    manifest: &mut Manifest,
+    builder: IndexBuilder,
+    format: IndexFormat,
+) -> anyhow::Result<String> {
+    let compression = manifest.compression();
+    match (format, compression) {
+        (IndexFormat::Binary, Compression::None) => {
+            let name = manifest.new_segment_name(&segment_extension(format, compression));
+            builder.into_file(segments_dir.join(&name), manifest.postings_order())?;
+            Ok(name)
+        }
+        (IndexFormat::Binary, _) => {
+            let name = manifest.new_segment_name(&segment_extension(format, compression));
+            builder.into_compressed_file(segments_dir.join(&name), compression)?;
+            Ok(name)
+        }
+        (IndexFormat::Json, _) => {
+            write_segment(segments_dir, manifest, builder.into_index()?, format)
+        }
+    }
+}
+
+/// Removes the files of segments that are not in the manifest anymore.
+fn remove_segments(segments_dir: &Path, names: &[String]) -> anyhow::Result<()> {
+    for name in names {
+        let path = segments_dir.join(name);
+        std::fs::remove_file(&path)
+            .context(format!("Failed to remove segment: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Drops segments from the index of a repository, such as segments whose
+/// files are corrupted, and removes their files if they exist. The
+/// documents of the dropped segments are not searchable until they are
+/// indexed again.
+pub fn drop_segments(repo_dir: impl AsRef<Path>, names: &[String]) -> anyhow::Result<()> {
+    let segments_dir = segments_dir(&repo_dir);
+    let mut manifest = load_manifest(&repo_dir)?;
+    manifest.replace(names, None);
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+    let existing = names
+        .iter()
+        .filter(|name| segments_dir.join(name).exists())
+        .cloned()
+        .collect::<Vec<_>>();
+    remove_segments(&segments_dir, &existing)
+}
+
+/// Writes the index of a repository, from its builder, as its only segment,
+/// replacing any
+/// existing segments, and records the weighting of its term frequencies, the
+/// compression of its segments and the order of their postings in its
+/// manifest. The index file of
+/// repositories that were indexed before the index was split into segments
+/// is removed.
+pub fn write_index(
+    repo_dir: impl AsRef<Path>,
+    builder: IndexBuilder,
+    format: IndexFormat,
+    tf_weighting: TfWeighting,
+    compression: Compression,
+    postings_order: PostingsOrder,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    // The generation continues from the replaced index, so that readers that
+    // opened it can tell that it has changed.
+    let generation = load_manifest(repo_dir).map_or(0, |manifest| manifest.generation());
+    if segments_dir.exists() {
+        std::fs::remove_dir_all(&segments_dir).context(format!(
+            "Failed to remove segments at: {}",
+            segments_dir.display()
+        ))?;
+    }
+    std::fs::create_dir_all(&segments_dir).context(format!(
+        "Failed to create segments directory at: {}",
+        segments_dir.display()
+    ))?;
+
+    let mut manifest = Manifest::starting_at(generation);
+    manifest.set_tf_weighting(tf_weighting);
+    manifest.set_compression(compression);
+    manifest.set_postings_order(postings_order);
+    let name = write_built_segment(&segments_dir, &mut manifest, builder, format)?;
+    manifest.replace(&[], Some(name));
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+
+    for filename in LEGACY_INDEX_FILENAMES {
+        let legacy_path = repo_dir.join(filename);
+        if legacy_path.exists() {
+            std::fs::remove_file(legacy_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Updates the index of a repository, without rewriting its segments.
+///
+/// The removed documents are marked as deleted from the segments that
+/// contain them, and the new documents are written as a new segment. The
+/// segments whose documents are all deleted are dropped, and the others are
+/// left to be compacted, with [`compact_index`], or merged. The segments are
+/// not merged, so that the caller can merge them now, with
+/// [`merge_small_segments`], or in the background.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `index`    - The index of the new documents.
+/// * `removed`  - The IDs of the documents that are removed from the index.
+/// * `format`   - The format in which the new segment is written.
+pub fn update_index(
+    repo_dir: impl AsRef<Path>,
+    index: FrequencyIndex,
+    removed: &[DocumentId],
+    format: IndexFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    let mut manifest = load_manifest(repo_dir)?;
+
+    let mut obsolete = vec![];
+    for name in manifest.segments().to_vec() {
+        let segment = open_segment(segments_dir.join(&name))?;
+        let deleted = manifest.deleted(&name).cloned().unwrap_or_default();
+        let doc_ids = removed
+            .iter()
+            .copied()
+            .filter(|doc_id| segment.contains(*doc_id) && !deleted.contains(doc_id))
+            .collect::<Vec<_>>();
+        if doc_ids.is_empty() {
+            continue;
+        }
+        if (deleted.len() + doc_ids.len()) as u32 >= segment.n_docs() {
+            manifest.replace(std::slice::from_ref(&name), None);
+            obsolete.push(name);
+        } else {
+            manifest.delete(&name, doc_ids);
+        }
+    }
+    if index.n_docs() > 0 {
+        let name = write_segment(&segments_dir, &mut manifest, index, format)?;
+        manifest.replace(&[], Some(name));
+    }
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+    remove_segments(&segments_dir, &obsolete)
+}
+
+/// Compacts the segments of the index of a repository that have deleted
+/// documents, by writing each of them again without the postings of its
+/// deleted documents, which reclaims their space. The other segments are
+/// not rewritten.
+///
+/// # Returns
+///
+/// The number of deleted documents that were dropped from the segments.
+///
+/// # Errors
+///
+/// Returns an error if a segment cannot be read or written.
+pub fn compact_index(repo_dir: impl AsRef<Path>) -> anyhow::Result<usize> {
+    let repo_dir = repo_dir.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    let mut manifest = load_manifest(repo_dir)?;
+    let n_deleted = manifest.n_deleted();
+
+    let mut obsolete = vec![];
+    for name in manifest.segments().to_vec() {
+        if manifest.deleted(&name).is_none() {
+            continue;
+        }
+        let segment = load_segment(repo_dir, &manifest, &name)?;
+        let compacted = match segment.n_docs() {
+            0 => None,
+            _ => Some(write_segment(
+                &segments_dir,
+                &mut manifest,
+                segment,
+                IndexFormat::from_path(&name),
+            )?),
+        };
+        manifest.replace(std::slice::from_ref(&name), compacted);
+        obsolete.push(name);
+    }
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+    remove_segments(&segments_dir, &obsolete)?;
+    Ok(n_deleted)
+}
+
+/// Picks the segment of the index of a repository, out of some segments,
+/// with the largest share of deleted documents, if it is at least the ratio
+/// above which segments are compacted in the background.
+pub fn pick_deleted_segment(
+    repo_dir: impl AsRef<Path>,
+    manifest: &Manifest,
+    segments: &[String],
+) -> Option<String> {
+    let segments_dir = segments_dir(repo_dir);
+    segments
+        .iter()
+        .filter_map(|name| {
+            let n_deleted = manifest.deleted(name)?.len();
+            let n_docs = open_segment(segments_dir.join(name)).ok()?.n_docs();
+            Some((name, n_deleted as f64 / n_docs.max(1) as f64))
+        })
+        .filter(|(_, ratio)| *ratio >= COMPACT_DELETED_RATIO)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(name, _)| name.clone())
+}
+
+/// Upgrades the segments of the index of a repository that were written in
+/// an older version of the format, writing each of them again as a new
+/// segment in the current one, and records the current version in the
+/// manifest. The segments in the current version are not rewritten.
+///
+/// # Returns
+///
+/// The number of segments that were upgraded.
+///
+/// # Errors
+///
+/// Returns an error if a segment cannot be read, or if it was written in a
+/// version of the format that is too old to be upgraded, in which case the
+/// index has to be built again.
+pub fn upgrade_index(repo_dir: impl AsRef<Path>) -> anyhow::Result<usize> {
+    let repo_dir = repo_dir.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    let mut manifest = load_manifest(repo_dir)?;
+
+    let mut upgraded = vec![];
+    for name in manifest.segments().to_vec() {
+        let path = segments_dir.join(&name);
+        match open_segment(&path) {
+            Ok(_) => continue,
+            Err(err) if is_outdated(&err) => {}
+            Err(err) => return Err(err),
+        }
+        let mut segment = FrequencyIndex::upgrade_file(&path).map_err(|err| match err {
+            index::Error::VersionMismatch { found, .. } => anyhow::anyhow!(
+                "Segment {name} was written in version {found} of the index format, which \
+                 cannot be upgraded. Run `searchine index --full` to build the index again."
+            ),
+            err => err.into(),
+        })?;
+        for doc_id in manifest.deleted(&name).into_iter().flatten() {
+            segment.remove(*doc_id);
+        }
+        let format = IndexFormat::from_path(&path);
+        let rewritten = write_segment(&segments_dir, &mut manifest, segment, format)?;
+        manifest.replace(std::slice::from_ref(&name), Some(rewritten));
+        upgraded.push(name);
+    }
+    if upgraded.is_empty() && !manifest.is_outdated() {
+        return Ok(0);
+    }
+    manifest.set_upgraded();
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+    remove_segments(&segments_dir, &upgraded)?;
+    Ok(upgraded.len())
+}
+
+/// Checks if an error is that a file of the index was written in an older
+/// version of the format.
+fn is_outdated(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<index::Error>(),
+        Some(index::Error::VersionMismatch { expected, found, .. }) if found < expected
+    )
+}
+
+/// Removes terms from the segments of the index of a repository. The
+/// segments that contain any of the terms are written again without them.
+///
+/// # Returns
+///
+/// The number of postings that were removed.
+pub fn prune_index(repo_dir: impl AsRef<Path>, terms: &HashSet<Token>) -> anyhow::Result<usize> {
+    let repo_dir = repo_dir.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    let mut manifest = load_manifest(repo_dir)?;
+
+    let mut n_postings = 0;
+    let mut obsolete = vec![];
+    for name in manifest.segments().to_vec() {
+        let mut segment = load_segment(repo_dir, &manifest, &name)?;
+        let n_removed = segment.remove_terms(terms);
+        if n_removed == 0 {
+            continue;
+        }
+        n_postings += n_removed;
+        let rewritten = write_segment(
+            &segments_dir,
+            &mut manifest,
+            segment,
+            IndexFormat::from_path(&name),
+        )?;
+        manifest.replace(std::slice::from_ref(&name), Some(rewritten));
+        obsolete.push(name);
+    }
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+    remove_segments(&segments_dir, &obsolete)?;
+    Ok(n_postings)
+}
+
+/// Merges segments of the index of a repository into a single segment,
+/// without their deleted documents.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `names`    - The file names of the segments to merge.
+/// * `format`   - The format in which the merged segment is written.
+pub fn merge_segments(
+    repo_dir: impl AsRef<Path>,
+    names: &[String],
+    format: IndexFormat,
+) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    let mut manifest = load_manifest(repo_dir)?;
+
+    let mut merged = FrequencyIndex::new();
+    for name in names {
+        merged.merge(load_segment(repo_dir, &manifest, name)?);
+    }
+    let name = write_segment(&segments_dir, &mut manifest, merged, format)?;
+    manifest.replace(names, Some(name));
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+    remove_segments(&segments_dir, names)
+}
+
+/// Merges the smallest segments of the index of a repository, if it has more
+/// segments than the threshold. The smallest half of the segments are merged,
+/// so that segments of similar sizes are merged, and large segments are not
+/// rewritten with every update.
+pub fn merge_small_segments(repo_dir: impl AsRef<Path>, format: IndexFormat) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let manifest = load_manifest(repo_dir)?;
+    match pick_small_segments(repo_dir, manifest.segments().to_vec()) {
+        Some(segments) => merge_segments(repo_dir, &segments, format),
+        None => Ok(()),
+    }
+}
+
+/// Picks the smallest half of some segments of the index of a repository to
+/// merge, if there are more of them than the threshold.
+pub fn pick_small_segments(
+    repo_dir: impl AsRef<Path>,
+    mut segments: Vec<String>,
+) -> Option<Vec<String>> {
+    if segments.len() <= SEGMENTS_MERGE_THRESHOLD {
+        return None;
+    }
+    let segments_dir = segments_dir(repo_dir);
+    segments.sort_by_key(|name| segment_size(segments_dir.join(name)));
+    segments.truncate(segments.len() / 2 + 1);
+    Some(segments)
+}
+
+/// Replaces segments of the index of a repository with a segment that was
+/// merged from them elsewhere, such as in the background, by moving its file
+/// into the segments directory. The repository must be locked, and the
+/// merged segment must be written in the compression of the manifest.
+///
+/// If any of the segments has been replaced since the merge started, like a
+/// segment whose documents were all removed, or any of their documents has
+/// been deleted since then, the merged segment is out of date, so its file
+/// is removed instead.
+///
+/// # Arguments
+///
+/// * `repo_dir`    - The path to the searchine repository.
+/// * `names`       - The file names of the merged segments.
+/// * `deleted`     - The IDs of the deleted documents of each of the merged
+///   segments, which were left out of the merged segment.
+/// * `merged_path` - The path to the merged segment.
+/// * `format`      - The format the merged segment is written in.
+///
+/// # Returns
+///
+/// Whether the segments were replaced.
+pub fn replace_segments(
+    repo_dir: impl AsRef<Path>,
+    names: &[String],
+    deleted: &[Option<BTreeSet<DocumentId>>],
+    merged_path: impl AsRef<Path>,
+    format: IndexFormat,
+) -> anyhow::Result<bool> {
+    let repo_dir = repo_dir.as_ref();
+    let merged_path = merged_path.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    let mut manifest = load_manifest(repo_dir)?;
+    let is_current = names.iter().zip(deleted).all(|(name, deleted)| {
+        manifest.segments().contains(name) && manifest.deleted(name) == deleted.as_ref()
+    });
+    if !is_current {
+        std::fs::remove_file(merged_path).context(format!(
+            "Failed to remove segment: {}",
+            merged_path.display()
+        ))?;
+        return Ok(false);
+    }
+    let name = manifest.new_segment_name(&segment_extension(format, manifest.compression()));
+    std::fs::rename(merged_path, segments_dir.join(&name))
+        .context(format!("Failed to move segment: {}", merged_path.display()))?;
+    manifest.replace(names, Some(name));
+    manifest.into_file(segments_dir.join(MANIFEST_FILENAME))?;
+    remove_segments(&segments_dir, names)?;
+    Ok(true)
+}
+
+/// Returns the size of the file of a segment in bytes, or zero if it cannot
+/// be read.
+pub fn segment_size(path: impl AsRef<Path>) -> u64 {
+    path.as_ref()
+        .metadata()
+        .map_or(0, |metadata| metadata.len())
+}
+
+/// Formats a size in bytes with a binary unit, like `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Formats a number of seconds as the time that has passed, in the largest
+/// unit that fits, like `3 hours ago`.
+pub fn format_age(seconds: u64) -> String {
+    const UNITS: [(&str, u64); 4] = [
+        ("day", 86_400),
+        ("hour", 3_600),
+        ("minute", 60),
+        ("second", 1),
+    ];
+    for (unit, length) in UNITS {
+        let n = seconds / length;
+        if n > 0 {
+            let plural = if n == 1 { "" } else { "s" };
+            return format!("{n} {unit}{plural} ago");
+        }
+    }
+    "just now".to_string()
+}
+
+/// Parses a size in bytes, with an optional binary unit, like `512`, `10K`,
+/// `2MiB` or `1G`.
+///
+/// # Errors
+///
+/// Returns an error if the size is not a number with a known unit, or if it
+/// is too large.
+pub fn parse_size(size: &str) -> anyhow::Result<u64> {
+    let invalid = || anyhow::anyhow!("Invalid size: {size}. Expected a size like 512, 10K or 2M.");
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+/// Returns the total size of the segment files of the index of a repository,
+/// in bytes.
+///
+/// # Errors
+///
+/// Returns an error if the manifest or a segment of the index cannot be read.
+pub fn index_size(repo_dir: impl AsRef<Path>) -> anyhow::Result<u64> {
+    let repo_dir = repo_dir.as_ref();
+    let segments_dir = segments_dir(repo_dir);
+    let mut size = 0;
+    for name in load_manifest(repo_dir)?.segments() {
+        let path = segments_dir.join(name);
+        size += path
+            .metadata()
+            .context(format!("Failed to read segment: {}", path.display()))?
+            .len();
+    }
+    Ok(size)
+}
+
+/// Parses a date as `YYYY-MM-DD`, into the time at the start of the day in
+/// UTC.
+///
+/// # Errors
+///
+/// Returns an error if the date is not in the format, or it does not exist.
+pub fn parse_date(date: &str) -> anyhow::Result<SystemTime> {
+    let invalid = || anyhow::anyhow!("Invalid date: {date}. Expected a date like 2024-01-31.");
+    let parts = date
+        .split('-')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if is_leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return Err(invalid()),
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return Err(invalid());
+    }
+
+    // The days since the Unix epoch, counted in years that start in March,
+    // so that the leap day is the last day of a year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = Duration::from_secs(days.unsigned_abs() * 86_400);
+    let time = match days >= 0 {
+        true => UNIX_EPOCH.checked_add(seconds),
+        false => UNIX_EPOCH.checked_sub(seconds),
+    };
+    time.ok_or_else(invalid)
+}
 
 /// Fetches the paths of the files that have been removed from the directory,
 /// compared to the corpus index.
 pub fn fetch_removed_files(corpus_index: &Collection, dir: &[PathBuf]) -> Vec<PathBuf> {
+    let dir = dir.iter().collect::<HashSet<_>>();
     let mut removed_paths = vec![];
     for (index_path, _) in corpus_index {
         if !dir.contains(index_path) {
@@ -37,7 +756,7 @@ pub fn fetch_modified_files(
     let mut modified_paths = vec![];
     for path in dir {
         if corpus_index.contains_path(path) {
-            let metadata = path
+            let metadata = source_path(path)
                 .metadata()
                 .context(format!("Failed to get metadata for: {}.", path.display()))?;
             let current_modified = metadata.modified().context(format!(
@@ -55,3 +774,77 @@ pub fn fetch_modified_files(
     }
     Ok(modified_paths)
 }
+
+/// Writes records to stdout, as a JSON array, or as a JSON record per line.
+/// Records are not written in the text format, as each command formats its
+/// own text.
+pub fn write_records<T: Serialize>(records: &[T], format: OutputFormat) -> anyhow::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, records)?;
+            writeln!(stdout)?;
+        }
+        OutputFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut stdout, record)?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses a query, and rewrites it with the query rewriter of the
+/// repository, if one is configured.
+pub fn parse_query(
+    query: &str,
+    rewriter: Option<&dyn QueryRewriter>,
+) -> anyhow::Result<HybridQuery> {
+    let query = HybridQuery::parse(query);
+    match rewriter {
+        Some(rewriter) => rewriter.rewrite(query),
+        None => Ok(query),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("10K").unwrap(), 10 * 1024);
+        assert_eq!(parse_size("2MiB").unwrap(), 2 * 1024 * 1024);
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("K").is_err());
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(0), "just now");
+        assert_eq!(format_age(1), "1 second ago");
+        assert_eq!(format_age(125), "2 minutes ago");
+        assert_eq!(format_age(2 * 86_400 + 5), "2 days ago");
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let seconds = |date| {
+            parse_date(date)
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        assert_eq!(seconds("1970-01-01"), 0);
+        assert_eq!(seconds("2024-01-01"), 1_704_067_200);
+        assert_eq!(seconds("2024-02-29"), 1_709_164_800);
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("2024-01").is_err());
+    }
+}
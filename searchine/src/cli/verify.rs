@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use documents::archive::source_path;
+use documents::DocumentId;
+use index::collection::Collection;
+use index::inverted::freq::FrequencyIndex;
+use index::store::DocumentStore;
+
+use crate::audit::{self, AuditEvent};
+use crate::cli::related::update_related;
+use crate::cli::utils::{
+    drop_segments, index_format, load_manifest, load_segment, merge_small_segments, update_index,
+};
+use crate::config::{COLLECTION_FILENAME, STORE_DIRNAME};
+
+/// The problems that were found in the index of a repository.
+#[derive(Default)]
+pub struct Report {
+    /// The segments whose files are missing, truncated or corrupted, with
+    /// the error that reading them returned.
+    pub corrupted: Vec<(String, anyhow::Error)>,
+    /// The documents that are in the postings of the index, but not in the
+    /// collection.
+    pub orphans: BTreeSet<DocumentId>,
+    /// The documents of the collection whose files no longer exist.
+    pub missing: Vec<(PathBuf, DocumentId)>,
+    /// The documents of the collection that are in no segment of the index.
+    pub unindexed: Vec<(PathBuf, DocumentId)>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.corrupted.is_empty()
+            && self.orphans.is_empty()
+            && self.missing.is_empty()
+            && self.unindexed.is_empty()
+    }
+}
+
+/// Checks the integrity of the index of a repository, by cross-checking the
+/// collection against the segments of the index and the filesystem.
+///
+/// It reports the segments that cannot be read, the documents that are in
+/// the postings but not in the collection, the documents of the collection
+/// whose files no longer exist, and the documents of the collection that
+/// are in no segment.
+///
+/// With `fix`, corrupted segments are dropped, orphan and missing documents
+/// are removed from the index, and documents that are missing or not indexed
+/// are removed from the collection, so that `searchine index` indexes again
+/// whatever still exists.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+/// * `fix`      - Whether to repair the problems that are found.
+pub fn invoke(repo_dir: impl AsRef<Path>, fix: bool) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let collection_path = repo_dir.join(COLLECTION_FILENAME);
+    let mut collection = Collection::from_file(&collection_path).context(format!(
+        "Could not read collection file: {}",
+        collection_path.display()
+    ))?;
+
+    let report = check(repo_dir, &collection)?;
+    display_report(&report);
+    if report.is_empty() {
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x8C]).unwrap_or_default();
+        println_bold!("{emoji} No problems found. Index is consistent.");
+        return Ok(());
+    }
+    if !fix {
+        println_bold!("  (use \"searchine verify --fix\" to repair the index)");
+        return Ok(());
+    }
+
+    let event = AuditEvent::Index {
+        added: vec![],
+        modified: vec![],
+        removed: report
+            .missing
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .collect(),
+    };
+    audit::record(repo_dir, &event)?;
+
+    let format = index_format(repo_dir);
+    let names = report
+        .corrupted
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    if !names.is_empty() {
+        drop_segments(repo_dir, &names)?;
+    }
+
+    let removed = report
+        .orphans
+        .iter()
+        .chain(report.missing.iter().map(|(_, doc_id)| doc_id))
+        .copied()
+        .collect::<Vec<_>>();
+    if !removed.is_empty() {
+        update_index(repo_dir, FrequencyIndex::new(), &removed, format)?;
+        merge_small_segments(repo_dir, format)?;
+    }
+
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    for doc_id in &removed {
+        store.remove(*doc_id)?;
+    }
+    for (path, _) in &report.missing {
+        collection.remove(path);
+    }
+    // Documents that are not indexed, including those of the dropped
+    // segments, are removed from the collection, so they are indexed again.
+    let indexed = indexed_doc_ids(repo_dir)?;
+    let unindexed = (&collection)
+        .into_iter()
+        .filter(|(_, entry)| !indexed.contains(&entry.document_id()))
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    for path in &unindexed {
+        collection.remove(path);
+    }
+    collection.into_file(&collection_path)?;
+    update_related(repo_dir);
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x94, 0xA7]).unwrap_or_default();
+    println_bold!(
+        "{emoji} Repaired the index. Run `searchine index` to index the remaining documents."
+    );
+    Ok(())
+}
+
+/// Cross-checks the collection against the segments and the filesystem.
+///
+/// # Errors
+///
+/// Returns an error if the manifest of the index cannot be read.
+pub fn check(repo_dir: &Path, collection: &Collection) -> anyhow::Result<Report> {
+    let mut report = Report::default();
+    let mut indexed = BTreeSet::new();
+    let manifest = load_manifest(repo_dir)?;
+    for name in manifest.segments() {
+        match load_segment(repo_dir, &manifest, name) {
+            Ok(segment) => indexed.extend(segment.doc_ids()),
+            Err(err) => report.corrupted.push((name.clone(), err)),
+        }
+    }
+
+    let mut known = BTreeSet::new();
+    for (path, entry) in collection {
+        let doc_id = entry.document_id();
+        known.insert(doc_id);
+        if !source_path(path).exists() {
+            report.missing.push((path.clone(), doc_id));
+        } else if !indexed.contains(&doc_id) {
+            report.unindexed.push((path.clone(), doc_id));
+        }
+    }
+    report.orphans = indexed.difference(&known).copied().collect();
+    report.missing.sort();
+    report.unindexed.sort();
+    Ok(report)
+}
+
+/// Returns the IDs of the documents in the readable segments of the index.
+fn indexed_doc_ids(repo_dir: &Path) -> anyhow::Result<BTreeSet<DocumentId>> {
+    let manifest = load_manifest(repo_dir)?;
+    let mut doc_ids = BTreeSet::new();
+    for name in manifest.segments() {
+        doc_ids.extend(load_segment(repo_dir, &manifest, name)?.doc_ids());
+    }
+    Ok(doc_ids)
+}
+
+fn display_report(report: &Report) {
+    let emoji = String::from_utf8(vec![0xE2, 0x9C, 0x96]).unwrap_or_default();
+    for (name, err) in &report.corrupted {
+        println_red!("  {emoji} corrupted segment: {name} ({err:#})");
+    }
+    for doc_id in &report.orphans {
+        println_red!("  {emoji} not in collection: document {doc_id}");
+    }
+    for (path, doc_id) in &report.missing {
+        println_red!(
+            "  {emoji} file missing: {} (document {doc_id})",
+            path.display()
+        );
+    }
+    for (path, doc_id) in &report.unindexed {
+        println_red!(
+            "  {emoji} not indexed: {} (document {doc_id})",
+            path.display()
+        );
+    }
+}
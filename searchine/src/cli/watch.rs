@@ -0,0 +1,219 @@
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+use anyhow::Context;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use documents::archive::source_path;
+use index::collection::Collection;
+
+use crate::cli::index::{apply_changes, Changes};
+use crate::cli::utils::{fetch_modified_files, fetch_new_files, index_format};
+use crate::config::{COLLECTION_FILENAME, CONFIG_POLL_MILLIS, WATCH_DEBOUNCE_MILLIS};
+use crate::fs::Directory;
+use crate::lock::RepoLock;
+use crate::reload::ConfigWatcher;
+use crate::scheduler::MergeScheduler;
+use crate::settings::Settings;
+use crate::shutdown;
+
+/// Watches the directory of a repository, and updates its index as the
+/// documents change, so it is always fresh without re-indexing manually.
+///
+/// The events of the filesystem are collected until no event has arrived
+/// for a short while, so that a burst of events, like the ones caused by
+/// saving a file, triggers a single update. The changed paths are then
+/// checked against the collection, and the changes are applied to the
+/// index. The directory, and the other root directories of the corpus, are
+/// watched until the process is interrupted.
+///
+/// The lock of the repository is held while each update is applied, so the
+/// repository can be indexed by other commands while it is watched. The
+/// segments of the index are not merged while the lock is held, but by a
+/// scheduler in the background, so merges never hold up updates or queries.
+///
+/// The config file is watched too, and the changes of its `[merge]` section
+/// apply to the merges from then on, while the other sections are read again
+/// with every update.
+///
+/// When the process is asked to stop, an update that is being applied is
+/// dropped before it is committed, so it is applied by the next run of
+/// `searchine index` or `searchine watch`, and the merges in the background
+/// are stopped.
+///
+/// # Arguments
+///
+/// * `repo_dir` - The path to the searchine repository.
+pub fn invoke(repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let repo_dir = repo_dir.as_ref();
+    let dir = Directory::for_repo(repo_dir)?;
+
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    for root in dir.roots() {
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .context(format!("Failed to watch: {}", root.display()))?;
+    }
+
+    let mut settings = Settings::load(repo_dir)?;
+    let scheduler = MergeScheduler::new(repo_dir, settings.merge)?;
+    scheduler.schedule()?;
+    let config = ConfigWatcher::new(repo_dir)?;
+
+    let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x91, 0x80]).unwrap_or_default();
+    println_bold!("{emoji} Watching for changes in: {}", dir.path().display());
+
+    let debounce = Duration::from_millis(WATCH_DEBOUNCE_MILLIS);
+    let poll = Duration::from_millis(CONFIG_POLL_MILLIS);
+    let mut paths = BTreeSet::new();
+    while !shutdown::requested() {
+        // Events are waited for with a timeout, so a request to stop is
+        // noticed while there are no changes.
+        let event = match receiver.recv_timeout(poll) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        collect_paths(event, &mut paths);
+        while let Ok(event) = receiver.recv_timeout(debounce) {
+            collect_paths(event, &mut paths);
+        }
+        if let Some(reloaded) = config.reload(&settings) {
+            scheduler.reconfigure(reloaded.merge)?;
+            settings = reloaded;
+        }
+
+        // Other commands may change the repository while it is watched, so
+        // the lock is only held while the changes are applied. Creating the
+        // lock file is an event too, so it is not created without changes.
+        RepoLock::wait(repo_dir)?;
+        let changed_paths = std::mem::take(&mut paths);
+        let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+        if find_changes(&collection, &dir, changed_paths.clone())?.is_empty() {
+            continue;
+        }
+        // A merge in the background may be replacing its segments.
+        let _lock = RepoLock::acquire_waiting(repo_dir)?;
+        let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+        let changes = find_changes(&collection, &dir, changed_paths)?;
+        let format = index_format(repo_dir);
+        match apply_changes(repo_dir, collection, &changes, format, false) {
+            Ok(()) => {}
+            Err(_) if shutdown::requested() => break,
+            Err(err) => return Err(err),
+        }
+        let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
+        println_bold!("{emoji} Updated index ({})", changes.summary());
+        scheduler.schedule()?;
+    }
+    scheduler.stop();
+    println_bold!("Stopped watching: {}", dir.path().display());
+    Ok(())
+}
+
+/// Collects the paths of a filesystem event. Events that only access the
+/// files are skipped, and errors are printed.
+fn collect_paths(event: notify::Result<Event>, paths: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+        Ok(event) => paths.extend(event.paths),
+        Err(err) => eprintln!("ERROR: Failed to watch for changes because: {}", err),
+    }
+}
+
+/// Finds the changes of the documents with the specified paths. Paths that
+/// still exist are checked against the collection for new and modified
+/// documents, and so are the files in directories that still exist, while
+/// the documents in the collection at paths, or in directories, that do not
+/// exist anymore are removed, so a moved directory is removed from its old
+/// path and added at its new one. The members of an archive are checked when
+/// the archive changes, and are removed when it is deleted, or when they are
+/// not in it anymore.
+fn find_changes(
+    collection: &Collection,
+    dir: &Directory,
+    paths: BTreeSet<PathBuf>,
+) -> anyhow::Result<Changes> {
+    let (existing, missing): (Vec<_>, Vec<_>) = paths.into_iter().partition(|path| path.exists());
+    let (dirs, files): (Vec<_>, Vec<_>) = existing
+        .into_iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .partition(|path| path.is_dir());
+    let mut existing = files
+        .into_iter()
+        .filter(|path| dir.contains(path))
+        .collect::<HashSet<_>>();
+    if !dirs.is_empty() {
+        existing.extend(
+            dir.iter_file_paths(false)
+                .filter(|path| dirs.iter().any(|dir_path| path.starts_with(dir_path))),
+        );
+    }
+    let documents = existing
+        .iter()
+        .flat_map(|path| dir.expand(path))
+        .collect::<Vec<_>>();
+    let listed = documents.iter().collect::<HashSet<_>>();
+    let removed = collection
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| {
+            let source = source_path(path);
+            missing.iter().any(|missing| source.starts_with(missing))
+                || (existing.contains(&source) && !listed.contains(path))
+        })
+        .cloned()
+        .collect();
+    Ok(Changes {
+        new: fetch_new_files(collection, &documents),
+        modified: fetch_modified_files(collection, &documents)?,
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Searchine;
+
+    use super::*;
+
+    #[test]
+    fn test_find_changes_moved_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("notes/budget.txt"), "The budget of the project.").unwrap();
+        std::fs::write(dir.join("notes/meeting.txt"), "The meeting on the budget.").unwrap();
+        std::fs::write(dir.join("plan.txt"), "The plan of the project.").unwrap();
+        let searchine = Searchine::init(&dir).unwrap();
+        searchine.index().unwrap();
+        let repo_dir = searchine.repo_dir();
+        let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME)).unwrap();
+
+        std::fs::rename(dir.join("notes"), dir.join("minutes")).unwrap();
+        let paths = BTreeSet::from([dir.join("notes"), dir.join("minutes")]);
+        let corpus = Directory::for_repo(repo_dir).unwrap();
+        let changes = find_changes(&collection, &corpus, paths).unwrap();
+        let sorted = |paths: &[PathBuf]| {
+            let mut paths = paths.to_vec();
+            paths.sort();
+            paths
+        };
+        assert_eq!(
+            sorted(&changes.removed),
+            [dir.join("notes/budget.txt"), dir.join("notes/meeting.txt")]
+        );
+        assert_eq!(
+            sorted(&changes.new),
+            [
+                dir.join("minutes/budget.txt"),
+                dir.join("minutes/meeting.txt")
+            ]
+        );
+        assert!(changes.modified.is_empty());
+    }
+}
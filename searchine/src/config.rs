@@ -1,5 +1,91 @@
 pub const SEARCHINE_PATH: &str = ".searchine";
 pub const COLLECTION_FILENAME: &str = "collection.json";
-pub const INDEX_FILENAME: &str = "index.json";
+pub const SEGMENTS_DIRNAME: &str = "segments";
+pub const RUNS_DIRNAME: &str = "runs";
+pub const INDEXES_DIRNAME: &str = "indexes";
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+pub const MANIFEST_KEY_VAR: &str = "SEARCHINE_MANIFEST_KEY";
+pub const LEGACY_INDEX_FILENAMES: [&str; 2] = ["index.bin", "index.json"];
+pub const CONFIG_FILENAME: &str = "config.toml";
+pub const ANALYZER_FILENAME: &str = "analyzer.json";
+pub const FILTERS_FILENAME: &str = "filters.json";
+pub const ROOTS_FILENAME: &str = "roots.json";
+pub const SYNONYMS_FILENAME: &str = "synonyms.txt";
+pub const GITIGNORE_FILENAME: &str = ".gitignore";
+pub const SEARCHINE_IGNORE_FILENAME: &str = ".searchineignore";
+pub const VECTORS_FILENAME: &str = "vectors.bin";
+pub const QUANTIZED_VECTORS_FILENAME: &str = "vectors.quantized.bin";
+pub const RELATED_FILENAME: &str = "related.bin";
+pub const IMPORTANCE_FILENAME: &str = "importance.json";
+pub const STORE_DIRNAME: &str = "store";
+pub const STAGING_STORE_DIRNAME: &str = "store.new";
+pub const LOCK_FILENAME: &str = "lock";
+pub const AUDIT_FILENAME: &str = "audit.log";
+pub const HISTORY_FILENAME: &str = "history";
+pub const SAVED_SEARCHES_FILENAME: &str = "saved.json";
+pub const MERGE_STATUS_FILENAME: &str = "merges.json";
+pub const CACHE_DIRNAME: &str = "cache";
+pub const QUERY_CACHE_FILENAME: &str = "queries.json";
+pub const NOTES_DIRNAME: &str = "notes";
+pub const BOOKMARKS_DIRNAME: &str = "bookmarks";
+pub const WEB_DIRNAME: &str = "web";
+pub const MANAGED_DIRNAMES: [&str; 3] = [NOTES_DIRNAME, BOOKMARKS_DIRNAME, WEB_DIRNAME];
+pub const BINARY_SNIFF_BYTES: usize = 8 * 1024;
 
+pub const SEGMENTS_MERGE_THRESHOLD: usize = 8;
+pub const COMPACT_DELETED_RATIO: f64 = 0.2;
+pub const MERGE_CONCURRENCY: usize = 1;
+pub const MERGE_IO_MB_PER_SEC: u32 = 32;
+pub const THROTTLE_CHUNK_BYTES: usize = 64 * 1024;
 pub const CHANNEL_BOUND: usize = 1024;
+pub const WATCH_DEBOUNCE_MILLIS: u64 = 500;
+pub const CONFIG_POLL_MILLIS: u64 = 500;
+pub const PASSAGE_CANDIDATES_FACTOR: u32 = 10;
+pub const PROXIMITY_CANDIDATES_FACTOR: u32 = 5;
+pub const EMBED_TEXT_CHARS: usize = 2000;
+pub const VECTOR_RESCORE_FACTOR: usize = 10;
+pub const HYBRID_SEMANTIC_WEIGHT: f64 = 0.5;
+pub const EXPAND_DOCS: u32 = 10;
+pub const EXPAND_TERMS: usize = 10;
+pub const EXPAND_WEIGHT: f64 = 0.3;
+pub const BM25_K: f64 = 1.2;
+pub const BM25_B: f64 = 0.75;
+pub const SERVE_ADDRESS: &str = "127.0.0.1:7878";
+pub const SERVE_LAST_QUERIES: usize = 1024;
+pub const TUI_TOP_N: u32 = 100;
+pub const TUI_PREVIEW_FRAGMENTS: usize = 5;
+pub const TUI_EDITOR: &str = "vi";
+pub const TUI_PAGE_LEN: u16 = 10;
+pub const HISTORY_MAX_ENTRIES: usize = 1000;
+pub const HISTORY_LIST_LEN: usize = 20;
+pub const FETCH_MAX_PAGES: usize = 100;
+pub const FETCH_DELAY_MILLIS: u64 = 1000;
+pub const FETCH_TIMEOUT_SECS: u64 = 30;
+pub const FETCH_MAX_BYTES: u64 = 10 * 1024 * 1024;
+pub const FETCH_USER_AGENT: &str = concat!("searchine/", env!("CARGO_PKG_VERSION"));
+pub const SEARCH_TOP_N: u32 = 10;
+pub const QUERY_CACHE_CAPACITY: usize = 256;
+pub const SUGGEST_TOP_K: usize = 10;
+pub const RELATED_TOP_K: usize = 10;
+pub const SIMILAR_TERMS: usize = 20;
+pub const PATTERN_MAX_TERMS: usize = 1024;
+pub const STATS_TOP_K: usize = 20;
+pub const BENCH_RUNS: usize = 10;
+pub const RELATED_SKETCH_SIZE: usize = 64;
+pub const RELATED_MIN_DOCS: u32 = 2;
+pub const PAGERANK_DAMPING: f64 = 0.85;
+pub const PAGERANK_ITERATIONS: usize = 50;
+pub const DEDUP_CANDIDATES_FACTOR: u32 = 4;
+pub const DUPES_MAX_DISTANCE: u32 = 3;
+pub const UNINDEXED_MAX_LINES: usize = 3;
+pub const UNINDEXED_LINE_CHARS: usize = 160;
+pub const SPELLING_MAX_DOCS: u32 = 1;
+pub const SPELLING_MAX_DISTANCE: usize = 2;
+pub const TITLE_FIELD: &str = "title:";
+pub const BODY_FIELD: &str = "body:";
+pub const TITLE_BOOST: f64 = 3.0;
+pub const BODY_BOOST: f64 = 1.0;
+pub const SECONDS_PER_DAY: f64 = 86_400.0;
+pub const LOCK_WAIT_MILLIS: u64 = 10_000;
+pub const LOCK_POLL_MILLIS: u64 = 100;
+pub const SNAPSHOT_ATTEMPTS: u32 = 5;
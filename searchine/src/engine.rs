@@ -0,0 +1,260 @@
+use std::path::{Path, PathBuf};
+
+use documents::DocumentId;
+use index::store::DocumentStore;
+
+use crate::cli::index::{build, list_documents, update, Changes};
+use crate::cli::init;
+use crate::cli::search::{prepare_query, rank, CanonicalPaths, DocumentBoosts, Ranking};
+use crate::cli::utils::{index_format, is_indexed, parse_query};
+use crate::config::{COLLECTION_FILENAME, SEARCHINE_PATH, STORE_DIRNAME};
+use crate::error::{Error, Result};
+use crate::lock::RepoLock;
+use crate::path::{find_index_path, find_repo_path};
+use crate::settings::Settings;
+use crate::snapshot::Snapshot;
+use crate::synonyms::Thesaurus;
+
+/// A searchine repository, with the index of the documents of a directory.
+///
+/// The repository is the `.searchine` directory that `searchine init`
+/// creates, so it can be indexed and searched both with the command line
+/// tool and with the library. It is configured by its config file, like it
+/// is for the tool, and the operations that change it hold its lock, so
+/// they never run at the same time as a command that changes it too.
+///
+/// Unlike the commands of the tool, the operations do not show their
+/// progress, nor write their results to stdout, so they can be used from
+/// terminal user interfaces.
+#[derive(Debug, Clone)]
+pub struct Searchine {
+    repo_dir: PathBuf,
+}
+
+/// A document that matched a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// The ID of the document in the index.
+    pub doc_id: DocumentId,
+    /// The path of the file of the document.
+    pub path: PathBuf,
+    /// The title of the document, or the name of its file if it has none.
+    pub title: String,
+    /// The URL that the document was fetched from, if it is a web page.
+    pub url: Option<String>,
+    /// The score of the document against the query.
+    pub score: f64,
+}
+
+impl Searchine {
+    /// Creates a repository in a directory, with a default config file, or
+    /// opens the repository that the directory is already in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory of the repository cannot be
+    /// created.
+    pub fn init(dir_path: impl AsRef<Path>) -> Result<Self> {
+        let dir_path = dir_path.as_ref();
+        if let Ok(searchine) = Self::open(dir_path) {
+            return Ok(searchine);
+        }
+        let repo_dir = init::create(dir_path).map_err(|err| Error::Other(err.into()))?;
+        Ok(Self { repo_dir })
+    }
+
+    /// Opens the repository that a directory is in, which is the closest
+    /// `.searchine` directory in the directory or in one of its ancestors.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NotARepository`] error if the directory is not in
+    /// a repository.
+    pub fn open(dir_path: impl AsRef<Path>) -> Result<Self> {
+        let dir_path = dir_path.as_ref();
+        match find_repo_path(dir_path, SEARCHINE_PATH) {
+            Some(repo_dir) => Ok(Self { repo_dir }),
+            None => Err(Error::NotARepository {
+                path: dir_path.to_path_buf(),
+            }),
+        }
+    }
+
+    /// Opens a named index of the repository that a directory is in, which
+    /// is configured, indexed and searched on its own, like with `--index`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NotARepository`] error if the directory is not in
+    /// a repository, or an [`Error::Other`] error if the repository has no
+    /// index with the name.
+    pub fn open_index(dir_path: impl AsRef<Path>, name: &str) -> Result<Self> {
+        let dir_path = dir_path.as_ref();
+        match find_index_path(dir_path, Some(name)).map_err(Error::Other)? {
+            Some(repo_dir) => Ok(Self { repo_dir }),
+            None => Err(Error::NotARepository {
+                path: dir_path.to_path_buf(),
+            }),
+        }
+    }
+
+    /// Returns the path to the `.searchine` directory of the repository, or
+    /// to the directory of its named index.
+    pub fn repo_dir(&self) -> &Path {
+        &self.repo_dir
+    }
+
+    /// Checks if the repository has been indexed.
+    pub fn is_indexed(&self) -> bool {
+        self.repo_dir.join(COLLECTION_FILENAME).exists() && is_indexed(&self.repo_dir)
+    }
+
+    /// Indexes the documents of the repository from scratch, like
+    /// `searchine index --full`, in the format that it was indexed in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Index`] error if the repository is locked by
+    /// another process, or if a file of the index cannot be written.
+    pub fn index(&self) -> Result<()> {
+        let _lock = RepoLock::acquire(&self.repo_dir)?;
+        let (roots, dir) = list_documents(&self.repo_dir, false)?;
+        build(&self.repo_dir, roots, dir, index_format(&self.repo_dir), false)?;
+        Ok(())
+    }
+
+    /// Updates the index of the repository with the documents that were
+    /// added, modified or removed since it was indexed, like
+    /// `searchine index`, and returns the changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NotIndexed`] error if the repository has not been
+    /// indexed yet, or an [`Error::Index`] error if it is locked by another
+    /// process, or if a file of the index cannot be read or written.
+    pub fn update(&self) -> Result<Changes> {
+        if !self.is_indexed() {
+            return Err(self.not_indexed());
+        }
+        let _lock = RepoLock::acquire(&self.repo_dir)?;
+        let (roots, dir) = list_documents(&self.repo_dir, false)?;
+        let changes = update(&self.repo_dir, roots, dir, index_format(&self.repo_dir), false)?;
+        Ok(changes)
+    }
+
+    /// Searches the `top_n` most relevant documents to a query, ranked the
+    /// way that the `[search]` section of the config of the repository
+    /// configures. The query has the syntax of `searchine search`, but only
+    /// its lexical terms, its phrases and its patterns are searched for. The
+    /// terms are expanded with the synonyms of the repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NotIndexed`] error if the repository has not been
+    /// indexed yet, or an [`Error::Index`] error if a file of the index
+    /// cannot be read.
+    pub fn search(&self, query: &str, top_n: u32) -> Result<Vec<SearchHit>> {
+        if !self.is_indexed() {
+            return Err(self.not_indexed());
+        }
+        let repo_dir = self.repo_dir.as_path();
+        let snapshot = Snapshot::open(repo_dir)?;
+        let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+        let settings = Settings::load(repo_dir)?;
+        let analyzer = &settings.analyzer;
+        analyzer.check(repo_dir)?;
+        let search = settings.search_config(None)?;
+        let query = parse_query(query, settings.rewrite.build().as_deref())?;
+        let index = snapshot.index.as_ref();
+        let thesaurus = Thesaurus::load(repo_dir, analyzer)?;
+        let prepared = prepare_query(
+            index,
+            analyzer,
+            &thesaurus,
+            &query.lexical,
+            query.case_sensitive,
+        )?;
+        let boosts = DocumentBoosts::new(repo_dir, &search)?;
+        let canonical = CanonicalPaths::new(repo_dir, &search)?;
+        let ranking = Ranking {
+            top_n,
+            search: &search,
+            boosts: boosts.as_ref(),
+            analyzer,
+            passage_config: None,
+            canonical: canonical.as_ref(),
+            collapse: None,
+        };
+        let inv_collection = &snapshot.inv_collection;
+        let allowed = prepared.phrase_matches(index, &store, inv_collection, analyzer, None);
+        let hits = rank(
+            index,
+            inv_collection,
+            &store,
+            &prepared.terms,
+            &ranking,
+            allowed.as_ref(),
+        )?;
+        let hits = hits
+            .iter()
+            .map(|hit| SearchHit {
+                doc_id: hit.doc_id(),
+                path: hit.path().to_path_buf(),
+                title: hit.title().to_string(),
+                url: hit.url().map(str::to_string),
+                score: hit.score(),
+            })
+            .collect();
+        Ok(hits)
+    }
+
+    /// Returns the error of a repository that has not been indexed yet.
+    fn not_indexed(&self) -> Error {
+        Error::NotIndexed {
+            path: self.repo_dir.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_and_search() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("budget.txt"), "The budget of the project.").unwrap();
+        assert!(matches!(Searchine::open(dir), Err(Error::NotARepository { .. })));
+
+        let searchine = Searchine::init(dir).unwrap();
+        assert!(matches!(searchine.search("budget", 10), Err(Error::NotIndexed { .. })));
+        searchine.index().unwrap();
+        let hits = searchine.search("budget", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].path.ends_with("budget.txt"));
+
+        std::fs::write(dir.join("meeting.txt"), "The meeting on the budget.").unwrap();
+        let changes = Searchine::open(dir).unwrap().update().unwrap();
+        assert_eq!(changes.new.len(), 1);
+        assert_eq!(searchine.search("budget", 10).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_search_fetched_page() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let page = "<html><head><title>Budget</title></head><body>The budget.</body></html>";
+        std::fs::write(dir.join("page-0123.html"), page).unwrap();
+        std::fs::write(dir.join("page-0123.url"), "https://example.com/budget\n").unwrap();
+        std::fs::write(dir.join("budget.txt"), "The budget of the project.").unwrap();
+
+        let searchine = Searchine::init(dir).unwrap();
+        searchine.index().unwrap();
+        let mut hits = searchine.search("budget", 10).unwrap();
+        hits.sort_by_key(|hit| hit.url.is_none());
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].url.as_deref(), Some("https://example.com/budget"));
+        assert_eq!(hits[1].url, None);
+    }
+}
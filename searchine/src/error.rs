@@ -0,0 +1,155 @@
+//! The errors of a [`Searchine`](crate::Searchine) repository, which
+//! applications can match on to react to them, like by indexing a repository
+//! that has not been indexed yet.
+//!
+//! The `searchine` tool exits with a distinct code for each kind of error,
+//! given by [`Error::exit_code`], so that scripts can react to them too.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A specialized `Result` type for the operations of a repository.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error of a repository.
+#[derive(Debug)]
+pub enum Error {
+    /// The directory is not in a searchine repository.
+    NotARepository { path: PathBuf },
+    /// The repository has not been indexed yet.
+    NotIndexed { path: PathBuf },
+    /// The config file of the repository cannot be read or parsed.
+    InvalidConfig { path: PathBuf, reason: String },
+    /// The config of the repository does not enable what was requested, like
+    /// a semantic search without an embedder.
+    NotConfigured { reason: String },
+    /// The command was asked to stop, by a signal, before it committed its
+    /// changes.
+    Interrupted,
+    /// A file of the index could not be read or written, or the repository
+    /// is locked by another process.
+    Index(index::Error),
+    /// Any other error, like an invalid config, with the context that it
+    /// occurred in.
+    Other(anyhow::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotARepository { path } => write!(
+                f,
+                "Index repository does not exist at: {}. Run `searchine init` to create it.",
+                path.display()
+            ),
+            Self::NotIndexed { path } => write!(
+                f,
+                "Index repository has not been indexed: {}. Run `searchine index` to index it.",
+                path.display()
+            ),
+            Self::InvalidConfig { path, reason } => {
+                write!(f, "Invalid config file: {}. {reason}", path.display())
+            }
+            Self::NotConfigured { reason } => write!(f, "{reason}"),
+            Self::Interrupted => write!(
+                f,
+                "Interrupted before the changes were committed. The index is unchanged."
+            ),
+            Self::Index(err) => write!(f, "{err}"),
+            Self::Other(err) => write!(f, "{err:#}"),
+        }
+    }
+}
+
+impl Error {
+    /// Returns the code that the `searchine` tool exits with for the error:
+    ///
+    /// * 1 - Any other error, like a file that cannot be read or written.
+    /// * 3 - The directory is not in a searchine repository.
+    /// * 4 - The repository has not been indexed yet.
+    /// * 5 - The repository is locked by another process.
+    /// * 6 - A file of the index is corrupt, or not a file of the index.
+    /// * 7 - A file of the index was written in an unsupported version of
+    ///   the format.
+    /// * 8 - The config file is invalid.
+    /// * 9 - The config does not enable what was requested.
+    /// * 130 - The command was interrupted before it committed its changes.
+    ///
+    /// Invalid arguments exit with 2.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::NotARepository { .. } => 3,
+            Self::NotIndexed { .. } => 4,
+            Self::Index(index::Error::Locked { .. }) => 5,
+            Self::Index(index::Error::Corrupt { .. } | index::Error::NotAnIndex { .. }) => 6,
+            Self::Index(index::Error::VersionMismatch { .. }) => 7,
+            Self::InvalidConfig { .. } => 8,
+            Self::NotConfigured { .. } => 9,
+            Self::Interrupted => 130,
+            Self::Index(_) | Self::Other(_) => 1,
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Index(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<index::Error> for Error {
+    fn from(err: index::Error) -> Self {
+        Self::Index(err)
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    /// Converts an error of the commands into an error of the repository,
+    /// keeping the errors of the repository and of the index apart, so that
+    /// they can be matched.
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<Self>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        match err.downcast::<index::Error>() {
+            Ok(err) => Self::Index(err),
+            Err(err) => Self::Other(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let locked = index::Error::Locked {
+            path: PathBuf::from("lock"),
+            pid: Some(1),
+        };
+        let err = Error::from(anyhow::Error::from(locked).context("Failed to index."));
+        assert!(matches!(err, Error::Index(index::Error::Locked { pid: Some(1), .. })));
+        assert_eq!(err.exit_code(), 5);
+
+        let err = Error::from(anyhow::anyhow!("Invalid config."));
+        assert!(matches!(err, Error::Other(_)));
+        assert_eq!(err.exit_code(), 1);
+
+        let not_indexed = Error::NotIndexed {
+            path: PathBuf::from("notes"),
+        };
+        let err = Error::from(anyhow::Error::from(not_indexed).context("Failed to search."));
+        assert!(matches!(err, Error::NotIndexed { .. }));
+        assert_eq!(err.exit_code(), 4);
+
+        let err = Error::from(anyhow::Error::from(Error::Interrupted).context("Failed to index."));
+        assert!(matches!(err, Error::Interrupted));
+        assert_eq!(err.exit_code(), 130);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use documents::DocumentId;
+use index::collection::InvertedCollection;
+use index::inverted::Index;
+use index::store::DocumentStore;
+use query::Query;
+use tokenize::{Token, CASED_PREFIX, TITLE_PREFIX};
+
+use crate::analyzer::AnalyzerConfig;
+use crate::config::{EXPAND_TERMS, EXPAND_WEIGHT};
+
+/// Finds the terms to expand a query with by pseudo-relevance feedback,
+/// from the documents that the query ranks the highest, which are assumed
+/// to be relevant.
+///
+/// The texts of the documents are tokenized the way they were indexed, and
+/// each of their terms is weighted by the average of its TF-IDF in them, as
+/// the centroid of their vectors in the Rocchio algorithm. The
+/// `EXPAND_TERMS` terms with the highest weights that are not in the query
+/// are returned, from the highest, with their weights scaled so that the
+/// highest is `EXPAND_WEIGHT`, so they weigh less than the terms of the
+/// query. The terms only match the text of the documents. Documents whose
+/// text is not in the store are skipped.
+///
+/// # Arguments
+///
+/// * `index`          - The index that the documents were ranked with.
+/// * `store`          - The store of the texts of the documents.
+/// * `inv_collection` - The collection of the documents.
+/// * `analyzer`       - The analyzer that the documents were indexed with.
+/// * `query`          - The terms of the query.
+/// * `doc_ids`        - The documents that the query ranks the highest.
+pub fn expansion_terms(
+    index: &dyn Index,
+    store: &DocumentStore,
+    inv_collection: &InvertedCollection,
+    analyzer: &AnalyzerConfig,
+    query: &Query,
+    doc_ids: &[DocumentId],
+) -> Vec<(Token, f64)> {
+    let mut tokenizer = analyzer.tokenizer();
+    let documents = doc_ids
+        .iter()
+        .filter_map(|&doc_id| {
+            let path = inv_collection.get_path(doc_id)?;
+            let text = store.read(doc_id).ok()?;
+            let spans = analyzer.document_spans(&mut tokenizer, &text, path, false);
+            Some(spans.into_iter().map(|(token, _)| token).collect())
+        })
+        .collect::<Vec<_>>();
+    weigh_terms(index, query, &documents)
+}
+
+/// Weighs the terms of tokenized documents by the sum of their TF-IDF in
+/// them, and keeps the `EXPAND_TERMS` highest that are not in the query,
+/// scaled so that the highest is `EXPAND_WEIGHT`. The scaling cancels out
+/// the number of documents that the sum would be divided by for the
+/// average.
+fn weigh_terms(index: &dyn Index, query: &Query, documents: &[Vec<Token>]) -> Vec<(Token, f64)> {
+    let mut weights = HashMap::<&Token, f64>::new();
+    for tokens in documents {
+        let mut frequencies = HashMap::<&Token, u32>::new();
+        for token in tokens {
+            *frequencies.entry(token).or_default() += 1;
+        }
+        for (token, frequency) in frequencies {
+            let tf = frequency as f64 / tokens.len() as f64;
+            *weights.entry(token).or_default() += tf * index.calc_idf(token);
+        }
+    }
+    let mut weights = weights
+        .into_iter()
+        .filter(|(token, weight)| {
+            *weight > 0.0
+                && query.term_count(token) == 0
+                && !token.starts_with([TITLE_PREFIX, CASED_PREFIX])
+                && index.n_docs_containing(token) > 0
+        })
+        .collect::<Vec<_>>();
+    weights.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    weights.truncate(EXPAND_TERMS);
+    let max_weight = weights.first().map_or(1.0, |(_, weight)| *weight);
+    weights
+        .into_iter()
+        .map(|(token, weight)| (token.clone(), weight / max_weight * EXPAND_WEIGHT))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_weigh_terms() {
+        let documents = [
+            vec!["budget", "forecast", "revenue", "forecast"],
+            vec!["budget", "forecast", "meeting"],
+            vec!["holiday", "photos"],
+            vec!["holiday", "budget"],
+        ]
+        .map(|tokens| tokens.into_iter().map(String::from).collect::<Vec<_>>());
+        let mut index = FrequencyIndex::new();
+        for (doc_id, tokens) in documents.iter().enumerate() {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id as DocumentId);
+            doc_index.index_tokens(tokens.iter().cloned());
+            index.index(doc_index);
+        }
+
+        let query = Query::from(["budget".to_string()]);
+        let terms = weigh_terms(&index, &query, &documents[..2]);
+        assert_eq!(terms[0], ("forecast".to_string(), EXPAND_WEIGHT));
+        assert!(terms[1..].iter().all(|(_, weight)| *weight < EXPAND_WEIGHT));
+        assert!(terms.iter().all(|(term, _)| term != "budget"));
+        assert!(terms.iter().all(|(term, _)| term != "holiday"));
+        assert!(weigh_terms(&index, &query, &[]).is_empty());
+    }
+}
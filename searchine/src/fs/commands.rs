@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use ignore::overrides::{Override, OverrideBuilder};
+
+use documents::archive::split_member_path;
+
+use crate::fs::roots::relative_to_root;
+
+/// The placeholder in the arguments of an extractor command that is replaced
+/// by the path to the file.
+const PATH_PLACEHOLDER: &str = "{path}";
+
+/// The files whose text is extracted by external commands, matched by the
+/// globs of the `commands` of the `[extraction]` section of the config,
+/// relative to the root directories of the corpus, with the commands that
+/// write their text to their standard output.
+///
+/// ```toml
+/// [extraction.commands]
+/// "*.djvu" = "djvutxt {path}"
+/// "scans/**/*.tiff" = "tesseract {path} -"
+/// ```
+///
+/// The files that are matched are walked even if their format is not
+/// supported, and their commands take precedence over the built-in
+/// extraction. Files in archives are not extracted by commands.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractorCommands {
+    /// The matchers of the globs, with the commands of the files they match.
+    globs: Vec<(Override, ExtractorCommand)>,
+    /// The root directories of the corpus, that the globs are relative to.
+    roots: Vec<PathBuf>,
+}
+
+impl ExtractorCommands {
+    /// Builds the matchers of the globs of the files whose text is extracted
+    /// by commands. Without roots, no file matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the globs is invalid, or if one of the
+    /// commands is empty.
+    pub fn new(roots: Vec<PathBuf>, commands: &BTreeMap<String, String>) -> anyhow::Result<Self> {
+        let Some(root) = roots.first() else {
+            return Ok(Self::default());
+        };
+        let globs = commands
+            .iter()
+            .map(|(glob, command)| {
+                let mut builder = OverrideBuilder::new(root);
+                builder
+                    .add(glob)
+                    .context(format!("Invalid extractor glob: {glob}"))?;
+                let command = ExtractorCommand::parse(command)
+                    .context(format!("Empty extractor command for glob: {glob}"))?;
+                Ok((builder.build()?, command))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self { globs, roots })
+    }
+
+    /// Returns the command that extracts the text of a file, or `None` if
+    /// the file is not matched by any glob.
+    pub fn command(&self, path: &Path) -> Option<&ExtractorCommand> {
+        if self.globs.is_empty() || split_member_path(path).is_some() {
+            return None;
+        }
+        let relative = relative_to_root(&self.roots, path);
+        self.globs
+            .iter()
+            .find(|(matcher, _)| matcher.matched(relative, false).is_whitelist())
+            .map(|(_, command)| command)
+    }
+}
+
+/// An external command that extracts the text of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractorCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ExtractorCommand {
+    /// Parses a command from its program and its arguments, separated by
+    /// whitespace. Returns `None` if the command is empty.
+    fn parse(command: &str) -> Option<Self> {
+        let mut words = command.split_whitespace().map(String::from);
+        let program = words.next()?;
+        Some(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+
+    /// Returns the arguments of the command for a file, with the path to the
+    /// file in place of `{path}`, or after them if none has it.
+    fn args(&self, path: &Path) -> Vec<String> {
+        let path = path.to_string_lossy();
+        let mut args = self
+            .args
+            .iter()
+            .map(|arg| arg.replace(PATH_PLACEHOLDER, &path))
+            .collect::<Vec<_>>();
+        if !self.args.iter().any(|arg| arg.contains(PATH_PLACEHOLDER)) {
+            args.push(path.into_owned());
+        }
+        args
+    }
+
+    /// Runs the command for a file, and returns its standard output as the
+    /// text of the file. Bytes that are not valid UTF-8 are replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command cannot be run, or if it fails.
+    pub fn extract(&self, path: &Path) -> anyhow::Result<String> {
+        let output = Command::new(&self.program)
+            .args(self.args(path))
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .context(format!("Failed to run extractor command: {}", self.program))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Extractor command {} failed with {}.",
+                self.program,
+                output.status
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let globs = BTreeMap::from([("*.djvu".to_string(), "djvutxt {path}".to_string())]);
+        let commands = ExtractorCommands::new(vec![PathBuf::from("/corpus")], &globs).unwrap();
+        let command = commands.command(Path::new("/corpus/books/a.djvu")).unwrap();
+        assert_eq!(command.args(Path::new("a.djvu")), ["a.djvu"]);
+        assert!(commands.command(Path::new("/corpus/books/a.pdf")).is_none());
+        assert!(commands
+            .command(Path::new("/corpus/books.zip!/a.djvu"))
+            .is_none());
+
+        let command = ExtractorCommand::parse("tesseract --psm 3").unwrap();
+        assert_eq!(command.args(Path::new("a.tiff")), ["--psm", "3", "a.tiff"]);
+        assert!(ExtractorCommand::parse(" ").is_none());
+        let empty = BTreeMap::from([("*.djvu".to_string(), String::new())]);
+        assert!(ExtractorCommands::new(vec![PathBuf::from("/corpus")], &empty).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract() {
+        let command = ExtractorCommand::parse("echo extracted from {path}").unwrap();
+        let text = command.extract(Path::new("a.djvu")).unwrap();
+        assert_eq!(text, "extracted from a.djvu\n");
+        assert!(ExtractorCommand::parse("false")
+            .unwrap()
+            .extract(Path::new("a"))
+            .is_err());
+    }
+}
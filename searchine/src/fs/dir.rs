@@ -1,11 +1,53 @@
-use std::io;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use walkdir::{DirEntry, WalkDir};
+use ignore::gitignore::{Gitignore, Glob};
+use ignore::overrides::Override;
+use ignore::{Match, WalkBuilder};
+use serde::Deserialize;
 
-use documents::DocumentType;
+use documents::{archive, extract, table, DocumentType};
 
-use crate::path::get_relative_path;
+use crate::config::{
+    BINARY_SNIFF_BYTES, GITIGNORE_FILENAME, MANAGED_DIRNAMES, SEARCHINE_IGNORE_FILENAME,
+};
+use crate::fs::{CorpusRoots, ExtractorCommands, PathFilter, RowFiles};
+use crate::path::{corpus_dir, get_relative_path};
+use crate::settings::Settings;
+
+/// The way the symbolic links in a directory are handled when it is walked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Links to files and directories are followed, and each file is a
+    /// document at every path it is reached by, so a file that is linked to
+    /// twice is indexed twice.
+    Follow,
+    /// Links are not followed, and links to files are not indexed.
+    Skip,
+    /// Links are followed, but each file is a document once, at its real
+    /// path, however many paths it is reached by.
+    #[default]
+    IndexTargetOnce,
+}
+
+/// The configuration of how the directories of a repository are walked.
+///
+/// ```toml
+/// [walk]
+/// symlinks = "skip"
+/// max_file_size = 10485760
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WalkConfig {
+    pub symlinks: SymlinkPolicy,
+    /// The size in bytes above which files are skipped, or `None` if files
+    /// of any size are indexed.
+    pub max_file_size: Option<u64>,
+}
 
 /// A struct representing a directory in the repository.
 ///
@@ -15,69 +57,253 @@ use crate::path::get_relative_path;
 ///
 /// * Hidden files and directories.
 /// * Files that are currently not supported for parsing.
-/// * TODO: Add more filters.
+/// * Text files whose content is binary, and files larger than the maximum
+///   file size of the repository (see [`WalkConfig`]).
+/// * Files that match a `.gitignore` or a `.searchineignore` file, or the
+///   global ignore file of git, like build artifacts and dependencies.
+/// * Files that are filtered out by the include and exclude globs of the
+///   repository (see [`PathFilter`]).
+///
+/// The messages of mailboxes, and with the `archives` feature, the files of
+/// zip and tar archives, are walked into, and they are documents at paths
+/// like `docs.zip!/guide/ch1.txt`, with the same rules for hidden,
+/// unsupported and large files. The members are not checked for binary
+/// content. The CSV and TSV files whose rows are documents (see
+/// [`RowFiles`]) are replaced by their rows, at paths like `sales.csv!/12`.
+///
+/// A `.searchineignore` file has the syntax of a `.gitignore` file, and
+/// takes precedence over it.
+///
+/// Symbolic links are handled by the [`SymlinkPolicy`] of the repository.
+/// When links are followed, a link to a directory that contains it is
+/// detected, and skipped printing an error message, so cycles of links do
+/// not make the walk endless.
+///
+/// The root directories that were added to the corpus of the repository (see
+/// [`CorpusRoots`]) are walked along with the directory, with the same
+/// filters.
 ///
 /// Additionally, it provides methods for presenting the paths to
 /// the files as relative to the current working directory.
 #[derive(Debug)]
 pub struct Directory {
     path: PathBuf,
-    #[allow(dead_code)]
     repo: PathBuf,
     #[allow(dead_code)]
     cwd: PathBuf,
+    global_ignore: Gitignore,
+    /// The root directories of the corpus, the directory first, with the
+    /// matchers of the filters for their files.
+    roots: Vec<(PathBuf, Override)>,
+    walk: WalkConfig,
+    rows: RowFiles,
+    commands: ExtractorCommands,
 }
 
 impl Directory {
-    /// Creates a new `Directory` struct by specifying the path to
-    /// the directory. The current working directory is retrieved
+    /// Creates a new `Directory` struct for the corpus of a repository, by
+    /// specifying the path to the repository, or to a named index of it,
+    /// whose filters, roots and config apply. The directory is the one that
+    /// the repository is in. The current working directory is retrieved
     /// from the environment and stored in the struct.
     ///
     /// # Arguments
     ///
-    /// * `path` - The path to the directory.
+    /// * `repo_dir` - The path to the repository or to the named index.
     ///
     /// # Errors
     ///
-    /// Returns an `Err` if the current working directory value is invalid.
-    /// Possible cases:
+    /// Returns an `Err` if the current working directory value is invalid,
+    /// or if the filters of the repository are invalid. Possible cases:
     ///
     /// * Current directory does not exist.
     /// * There are insufficient permissions to access the current directory.
+    /// * The config, the filters or the roots file cannot be read, or one of
+    ///   the globs of the filters is invalid.
     ///
-    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
-        let path = path.as_ref().to_path_buf().canonicalize()?;
-        let repo = path.join(".searchine").canonicalize()?;
+    /// Root directories that do not exist anymore are skipped printing an
+    /// error message, so their documents are removed from the index.
+    pub fn for_repo(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let repo = repo_dir.as_ref().canonicalize()?;
+        let path = corpus_dir(&repo)?.to_path_buf();
         let cwd = std::env::current_dir()?;
-        Ok(Self { path, repo, cwd })
+        let (global_ignore, _) = Gitignore::global();
+        let filter = PathFilter::for_repo(&repo)?;
+        let settings = Settings::load(&repo)?;
+        let walk = settings.walk;
+        let mut roots = vec![(path.clone(), filter.matcher(&path)?)];
+        for root in CorpusRoots::load(&repo)?.roots {
+            if !root.is_dir() {
+                eprintln!("ERROR: Skipping missing root directory: {}", root.display());
+                continue;
+            }
+            let matcher = filter.matcher(&root)?;
+            roots.push((root, matcher));
+        }
+        let root_dirs: Vec<_> = roots.iter().map(|(root, _)| root.clone()).collect();
+        let rows = RowFiles::new(root_dirs.clone(), &settings.extraction.rows)?;
+        let commands = ExtractorCommands::new(root_dirs, &settings.extraction.commands)?;
+        Ok(Self {
+            path,
+            repo,
+            cwd,
+            global_ignore,
+            roots,
+            walk,
+            rows,
+            commands,
+        })
     }
 
-    /// Returns an iterator of the paths to the files in the directory,
-    /// relative to the current working directory.
+    /// Returns an iterator of the paths to the files in the directory, and
+    /// in the other root directories of the corpus, relative to the current
+    /// working directory.
     ///
-    /// Hidden directories and files (starting with a dot `.`) are ignored,
-    /// and entries that cause errors are skipped printing an error message.
+    /// Hidden directories and files (starting with a dot `.`), and the ones
+    /// that match an ignore file or are filtered out, are ignored, and entries
+    /// that cause errors are skipped printing an error message.
     pub fn iter_relative_paths(&self, verbose: bool) -> impl Iterator<Item = PathBuf> {
-        WalkDir::new(&self.path)
+        let (roots, config) = (self.roots.clone(), self.walk);
+        let commands = self.commands.clone();
+        roots.into_iter().flat_map(move |(root, filter)| {
+            walk(&root, verbose, config, Some(filter), commands.clone())
+        })
+    }
+
+    /// Returns an iterator of the paths to the documents in the stores
+    /// managed by the repository, like the notes and imported bookmarks.
+    /// Stores that have not been created yet are skipped. Ignore files and
+    /// filters do not apply to the stores, as their documents are added on
+    /// purpose.
+    pub fn iter_managed_paths(&self, verbose: bool) -> impl Iterator<Item = PathBuf> {
+        let config = self.walk;
+        let store_paths = MANAGED_DIRNAMES
+            .iter()
+            .map(|name| self.repo.join(name))
+            .filter(|path| path.exists())
+            .collect::<Vec<_>>();
+        store_paths
             .into_iter()
-            .filter_entry(|entry| !is_hidden(entry))
-            .filter_map(|entry| match entry {
-                Ok(entry) => Some(entry.path().to_path_buf()),
-                Err(err) => {
-                    eprintln!("ERROR: Skipping entry because: {}", err);
-                    None
-                }
-            })
-            .filter(move |path| !is_ignored(path, verbose))
+            .flat_map(move |path| walk(&path, verbose, config, None, ExtractorCommands::default()))
     }
 
-    /// Returns an iterator of the full paths to the files in the directory.
+    /// Returns an iterator of the full paths to the files in the directory,
+    /// including the documents in the managed stores of the repository.
     ///
     /// Hidden directories and files (starting with a dot `.`) are ignored,
     /// and entries that cause errors are skipped printing an error message.
+    /// Unless the links are followed as they are, the paths are resolved to
+    /// the real paths of the files, and each file is returned once. Archives
+    /// are replaced by the paths to their members, and the tables whose rows
+    /// are documents by the paths to their rows.
     pub fn iter_full_paths(&self, verbose: bool) -> impl Iterator<Item = PathBuf> {
+        let config = self.walk;
+        let rows = self.rows.clone();
+        self.iter_file_paths(verbose)
+            .flat_map(move |path| expand_archive(path, verbose, &config))
+            .flat_map(move |path| expand_rows(path, &rows))
+    }
+
+    /// Returns an iterator of the full paths to the files in the directory,
+    /// like [`Directory::iter_full_paths`], without replacing the archives
+    /// and the tables by the paths to their documents.
+    pub fn iter_file_paths(&self, verbose: bool) -> impl Iterator<Item = PathBuf> {
+        let symlinks = self.walk.symlinks;
+        let mut seen = HashSet::new();
         self.iter_relative_paths(verbose)
-            .filter_map(|path| path.canonicalize().ok())
+            .chain(self.iter_managed_paths(verbose))
+            .filter_map(move |path| match symlinks {
+                // The roots are canonical, so the paths are already absolute.
+                SymlinkPolicy::Follow => Some(path),
+                _ => path.canonicalize().ok(),
+            })
+            .filter(move |path| seen.insert(path.clone()))
+    }
+
+    /// Returns the paths to the documents of a file of the directory, which
+    /// are the paths to the members of an archive, or to the rows of a table
+    /// whose rows are documents, and the file itself otherwise.
+    pub fn expand(&self, path: impl AsRef<Path>) -> Vec<PathBuf> {
+        expand_archive(path.as_ref().to_path_buf(), false, &self.walk)
+            .into_iter()
+            .flat_map(|path| expand_rows(path, &self.rows))
+            .collect()
+    }
+
+    /// Checks if a file is one of the documents of the directory, i.e. if it
+    /// is in the directory, in one of the other root directories of the
+    /// corpus, or in one of the managed stores of the repository,
+    /// it is not hidden, it does not match an ignore file, it is not filtered
+    /// out, it is not a link that is skipped, and it is a supported file type.
+    /// A member of an archive is one of the documents if the archive is, and
+    /// the member is not hidden and is a supported file type, and so is the
+    /// row of a table whose rows are documents.
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if let Some((archive, member)) = archive::split_member_path(path) {
+            if table::is_table(&archive) {
+                return self.rows.columns(&archive).is_some() && self.contains(archive);
+            }
+            return !is_ignored_member(&member, None, false, &self.walk) && self.contains(archive);
+        }
+        if self.walk.symlinks == SymlinkPolicy::Skip && path.is_symlink() {
+            return false;
+        }
+        let store = MANAGED_DIRNAMES
+            .iter()
+            .map(|name| self.repo.join(name))
+            .find(|store_path| path.starts_with(store_path));
+        let root = self.roots.iter().find(|(root, _)| path.starts_with(root));
+        let base = match (&store, root) {
+            (Some(store), _) => store.as_path(),
+            (None, Some((root, _))) => root.as_path(),
+            (None, None) => return false,
+        };
+        let Ok(relative_path) = path.strip_prefix(base) else {
+            return false;
+        };
+        let is_hidden = relative_path
+            .components()
+            .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+        let is_excluded = match (&store, root) {
+            (None, Some((root, filter))) => {
+                self.is_ignored_by_files(path, root) || filter.matched(path, false).is_ignore()
+            }
+            _ => false,
+        };
+        !is_hidden && !is_excluded && !is_ignored(path, false, &self.walk, &self.commands)
+    }
+
+    /// Checks if a file in a root directory matches an ignore file, the same
+    /// way as the root is walked. The ignore files of deeper directories take
+    /// precedence, and the global ignore file of git comes last.
+    fn is_ignored_by_files(&self, path: &Path, root: &Path) -> bool {
+        for dir in path.ancestors().skip(1) {
+            for filename in [SEARCHINE_IGNORE_FILENAME, GITIGNORE_FILENAME] {
+                let ignore_path = dir.join(filename);
+                if !ignore_path.is_file() {
+                    continue;
+                }
+                let (gitignore, _) = Gitignore::new(&ignore_path);
+                match matched(&gitignore, path, dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+        }
+        matched(&self.global_ignore, path, root).is_ignore()
+    }
+
+    /// Returns the path to the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the paths to the root directories of the corpus that exist,
+    /// the directory first.
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.roots.iter().map(|(root, _)| root.clone()).collect()
     }
 
     /// Returns an iterator of the paths to the files in the directory,
@@ -92,43 +318,268 @@ impl Directory {
     }
 }
 
-/// Checks if a directory entry is hidden, i.e. if its name starts with
-/// a dot (`.`).
-fn is_hidden(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s.starts_with("."))
-        .unwrap_or(false)
+/// Walks the directory at the specified path, skipping hidden and ignored
+/// entries, and entries that cause errors, and handling symbolic links by the
+/// policy of a config. If there is a `filter`, the
+/// entries that it filters out, and the entries that match a `.gitignore` or
+/// a `.searchineignore` file, in the directory or in its parents, or the
+/// global ignore file of git, are skipped too. The files that are extracted
+/// by external commands are walked whatever their format.
+fn walk(
+    path: &Path,
+    verbose: bool,
+    config: WalkConfig,
+    filter: Option<Override>,
+    commands: ExtractorCommands,
+) -> impl Iterator<Item = PathBuf> {
+    let symlinks = config.symlinks;
+    let ignore_files = filter.is_some();
+    let mut builder = WalkBuilder::new(path);
+    builder
+        .standard_filters(false)
+        .follow_links(symlinks != SymlinkPolicy::Skip)
+        .hidden(true)
+        .parents(ignore_files)
+        .git_ignore(ignore_files)
+        .git_global(ignore_files)
+        .require_git(false);
+    if let Some(filter) = filter {
+        builder
+            .add_custom_ignore_filename(SEARCHINE_IGNORE_FILENAME)
+            .overrides(filter);
+    }
+    builder
+        .build()
+        .filter_map(move |entry| match entry {
+            Ok(entry) if symlinks == SymlinkPolicy::Skip && entry.path_is_symlink() => None,
+            Ok(entry) => Some(entry.path().to_path_buf()),
+            Err(err) => {
+                eprintln!("ERROR: Skipping entry because: {}", err);
+                None
+            }
+        })
+        .filter(move |path| !is_ignored(path, verbose, &config, &commands))
+}
+
+/// Matches a file against the rules of an ignore file whose rules are
+/// relative to a directory. The parent directories of the file, up to that
+/// directory, are matched first, as a file in an ignored directory is ignored
+/// even if it is re-included.
+fn matched<'a>(gitignore: &'a Gitignore, path: &Path, root: &Path) -> Match<&'a Glob> {
+    let mut dirs = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root) && *dir != root)
+        .collect::<Vec<_>>();
+    dirs.reverse();
+    for dir in dirs {
+        let dir_match = gitignore.matched(dir, true);
+        if dir_match.is_ignore() {
+            return dir_match;
+        }
+    }
+    gitignore.matched(path, false)
 }
 
 /// Checks if a directory entry is a supported file type.
 ///
-/// A file type is considered supported if its extension matches one of the
-/// supported document types.
+/// A file type is considered supported if one of the registered extractors
+/// sniffs it from its path and its first bytes, or if it is an archive whose
+/// members can be read.
 fn is_supported_file_type(path: impl AsRef<Path>) -> bool {
-    let extension = path.as_ref().extension();
-    if let Some(extension) = extension {
-        DocumentType::from_extension(extension).is_some()
-    } else {
-        false
+    if archive::is_supported(&path) {
+        return true;
     }
+    let head = extract::read_head(&path);
+    extract::find_extractor(path, &head).is_some()
+}
+
+/// Checks if a file that is read as text is binary, from its first bytes: a
+/// file with a null byte, or bytes that are not valid UTF-8, is binary. A
+/// file that cannot be read is not checked, so its error is reported when it
+/// is indexed.
+fn is_binary(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut buffer = Vec::with_capacity(BINARY_SNIFF_BYTES);
+    if file
+        .take(BINARY_SNIFF_BYTES as u64)
+        .read_to_end(&mut buffer)
+        .is_err()
+    {
+        return false;
+    }
+    if buffer.contains(&0) {
+        return true;
+    }
+    // A character may be cut off at the end of the buffer, which does not
+    // make the file binary.
+    std::str::from_utf8(&buffer).is_err_and(|err| err.error_len().is_some())
 }
 
 /// Checks if a directory entry is ignored. A directory entry is ignored if
-/// it is a directory, or an unsupported file type.
-fn is_ignored(path: impl AsRef<Path>, verbose: bool) -> bool {
+/// it is a directory, an unsupported file type, a file larger than the
+/// maximum file size of a config, or a text file whose content is binary.
+/// The files that are extracted by external commands are supported, and are
+/// not checked for binary content.
+fn is_ignored(
+    path: impl AsRef<Path>,
+    verbose: bool,
+    config: &WalkConfig,
+    commands: &ExtractorCommands,
+) -> bool {
     let path = path.as_ref();
-    if path.is_file() {
-        if is_supported_file_type(path) {
-            false
-        } else {
+    if !path.is_file() {
+        return true;
+    }
+    let has_command = commands.command(path).is_some();
+    if !has_command && !is_supported_file_type(path) {
+        if verbose {
+            eprintln!("WARNING: Ignoring unsupported file: {}", path.display());
+        }
+        return true;
+    }
+    // The maximum file size applies to the members of an archive instead.
+    if let Some(max_file_size) = config.max_file_size.filter(|_| !archive::is_archive(path)) {
+        let size = path.metadata().map_or(0, |metadata| metadata.len());
+        if size > max_file_size {
             if verbose {
-                eprintln!("WARNING: Ignoring unsupported file: {}", path.display());
+                eprintln!(
+                    "WARNING: Skipping file larger than {max_file_size} bytes: {}",
+                    path.display()
+                );
+            }
+            return true;
+        }
+    }
+    let is_text = !has_command
+        && matches!(
+            DocumentType::from_path(path),
+            Some(DocumentType::Text | DocumentType::Code)
+        );
+    if is_text && is_binary(path) {
+        if verbose {
+            eprintln!("WARNING: Skipping binary file: {}", path.display());
+        }
+        return true;
+    }
+    false
+}
+
+/// Replaces the path to an archive with the paths to its members that are
+/// not ignored. An archive that cannot be read is skipped printing an error
+/// message. Other paths are returned as they are.
+fn expand_archive(path: PathBuf, verbose: bool, config: &WalkConfig) -> Vec<PathBuf> {
+    if !archive::is_archive(&path) {
+        return vec![path];
+    }
+    match archive::list_members(&path) {
+        Ok(members) => members
+            .into_iter()
+            .filter_map(|(member_path, size)| {
+                let (_, member) = archive::split_member_path(&member_path)?;
+                let ignored = is_ignored_member(&member, Some(size), verbose, config);
+                (!ignored).then_some(member_path)
+            })
+            .collect(),
+        Err(err) => {
+            eprintln!("ERROR: Skipping archive {} because: {err}", path.display());
+            vec![]
+        }
+    }
+}
+
+/// Replaces the path to a table whose rows are documents with the paths to
+/// its rows. A table that cannot be read is skipped printing an error
+/// message. Other paths are returned as they are.
+fn expand_rows(path: PathBuf, rows: &RowFiles) -> Vec<PathBuf> {
+    if rows.columns(&path).is_none() {
+        return vec![path];
+    }
+    table::list_rows(&path).unwrap_or_else(|err| {
+        eprintln!("ERROR: Skipping table {} because: {err}", path.display());
+        vec![]
+    })
+}
+
+/// Checks if a member of an archive is ignored, from its path in the archive
+/// and its size, if it is known. A member is ignored if it is hidden, if it
+/// is an unsupported file type, which includes archives in the archive, or
+/// if it is larger than the maximum file size of a config.
+fn is_ignored_member(member: &Path, size: Option<u64>, verbose: bool, config: &WalkConfig) -> bool {
+    let is_hidden = member
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+    // Members are sniffed from their paths only, so that the archive is not
+    // read for each of them.
+    let is_supported = extract::find_extractor(member, &[]).is_some();
+    if is_hidden || !is_supported {
+        return true;
+    }
+    match (size, config.max_file_size) {
+        (Some(size), Some(max_file_size)) if size > max_file_size => {
+            if verbose {
+                eprintln!(
+                    "WARNING: Skipping archive member larger than {max_file_size} bytes: {}",
+                    member.display()
+                );
             }
             true
         }
-    } else {
-        true
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let text = dir.join("notes.txt");
+        std::fs::write(&text, "Notes on the budget, in UTF-8: caf\u{e9}.").unwrap();
+        let binary = dir.join("data.txt");
+        std::fs::write(&binary, [0x7f, b'E', b'L', b'F', 0x00, 0x01]).unwrap();
+        let latin1 = dir.join("latin1.md");
+        std::fs::write(&latin1, b"caf\xe9 au lait").unwrap();
+        let scan = dir.join("scan.djvu");
+        std::fs::write(&scan, [0x41, 0x54, 0x26, 0x54, 0x00]).unwrap();
+
+        let config = WalkConfig::default();
+        let commands = ExtractorCommands::default();
+        assert!(!is_ignored(&text, false, &config, &commands));
+        assert!(is_ignored(&binary, false, &config, &commands));
+        assert!(is_ignored(&latin1, false, &config, &commands));
+        assert!(is_ignored(dir, false, &config, &commands));
+        assert!(is_ignored(&scan, false, &config, &commands));
+        let globs = BTreeMap::from([("*.djvu".to_string(), "djvutxt".to_string())]);
+        let commands = ExtractorCommands::new(vec![dir.to_path_buf()], &globs).unwrap();
+        assert!(!is_ignored(&scan, false, &config, &commands));
+
+        let config = WalkConfig {
+            max_file_size: Some(10),
+            ..Default::default()
+        };
+        assert!(is_ignored(&text, false, &config, &commands));
+    }
+
+    #[test]
+    fn test_is_ignored_member() {
+        let config = WalkConfig {
+            max_file_size: Some(10),
+            ..Default::default()
+        };
+        let ignored =
+            |member: &str, size| is_ignored_member(Path::new(member), size, false, &config);
+        assert!(!ignored("guide/ch1.txt", Some(5)));
+        assert!(!ignored("guide/ch1.txt", None));
+        assert!(ignored("guide/ch1.txt", Some(20)));
+        assert!(ignored(".git/notes.md", Some(5)));
+        assert!(ignored("nested.zip", Some(5)));
     }
 }
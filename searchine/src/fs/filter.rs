@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use ignore::overrides::{Override, OverrideBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::config::FILTERS_FILENAME;
+use crate::settings::Settings;
+
+/// The glob filters that select the files of a directory to index. They are
+/// persisted in the repository, so that every command that walks the
+/// directory, like an incremental `searchine index` or `searchine status`,
+/// applies the same filters.
+///
+/// The globs have the syntax of a `.gitignore` file, and they are matched
+/// against the paths relative to the directory. If there are include globs,
+/// only the files that match one of them are indexed. The files that match an
+/// exclude glob are never indexed, even if they match an include glob.
+///
+/// Filters can also be set in the `[filter]` section of the config of a
+/// repository, and they apply along with the persisted ones.
+///
+/// ```toml
+/// [filter]
+/// include = ["**/*.rs", "**/*.md"]
+/// exclude = ["target/**"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl PathFilter {
+    /// Loads the filters of a repository. A repository without a filters file
+    /// has no filters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the filters file exists, but it cannot be read or
+    /// parsed.
+    pub fn load(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = repo_dir.as_ref().join(FILTERS_FILENAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .context(format!("Failed to read filters file: {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .context(format!("Failed to parse filters file: {}", path.display()))
+    }
+
+    /// Loads all the filters of a repository: the ones in its config, and the
+    /// persisted ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config file or the filters file cannot be read
+    /// or parsed.
+    pub fn for_repo(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let repo_dir = repo_dir.as_ref();
+        let mut filter = Settings::load(repo_dir)?.filter;
+        let persisted = Self::load(repo_dir)?;
+        filter.include.extend(persisted.include);
+        filter.exclude.extend(persisted.exclude);
+        Ok(filter)
+    }
+
+    /// Writes the filters to a repository. The filters file of a repository
+    /// without filters is removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the filters file cannot be written or removed.
+    pub fn save(&self, repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = repo_dir.as_ref().join(FILTERS_FILENAME);
+        if self.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .context(format!("Failed to remove filters file: {}", path.display()))?;
+            }
+            return Ok(());
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?).context(format!(
+            "Failed to write filters file: {}",
+            tmp_path.display()
+        ))?;
+        fs::rename(&tmp_path, &path)
+            .context(format!("Failed to write filters file: {}", path.display()))
+    }
+
+    /// Checks if there are no filters.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Builds the matcher of the filters for the files of a directory. A file
+    /// that is filtered out is matched as ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the globs is invalid.
+    pub fn matcher(&self, dir_path: impl AsRef<Path>) -> anyhow::Result<Override> {
+        let mut builder = OverrideBuilder::new(dir_path);
+        for glob in &self.include {
+            builder
+                .add(glob)
+                .context(format!("Invalid include glob: {glob}"))?;
+        }
+        // The globs that are added last take precedence, so the exclude globs
+        // win over the include globs.
+        for glob in &self.exclude {
+            builder
+                .add(&format!("!{glob}"))
+                .context(format!("Invalid exclude glob: {glob}"))?;
+        }
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matcher() {
+        let filter = PathFilter {
+            include: vec!["**/*.rs".to_string()],
+            exclude: vec!["**/tests/**".to_string()],
+        };
+        let matcher = filter.matcher("/dir").unwrap();
+        assert!(!matcher.matched("/dir/src/main.rs", false).is_ignore());
+        assert!(matcher.matched("/dir/Readme.md", false).is_ignore());
+        assert!(matcher.matched("/dir/src/tests/it.rs", false).is_ignore());
+
+        let matcher = PathFilter::default().matcher("/dir").unwrap();
+        assert!(!matcher.matched("/dir/Readme.md", false).is_ignore());
+    }
+}
@@ -1,3 +1,11 @@
+pub mod commands;
 pub mod dir;
+pub mod filter;
+pub mod roots;
+pub mod rows;
 
+pub use commands::ExtractorCommands;
 pub use dir::Directory;
+pub use filter::PathFilter;
+pub use roots::CorpusRoots;
+pub use rows::RowFiles;
@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ROOTS_FILENAME;
+use crate::path::corpus_dir;
+
+/// The root directories of the corpus of a repository, besides the directory
+/// that the repository is in. They are persisted in the repository, so that
+/// every command that walks the corpus, like an incremental `searchine index`
+/// or `searchine status`, walks all of them.
+///
+/// The paths of the documents are kept relative to the root they are in, and
+/// the filters and the ignore files of the repository apply to every root,
+/// with their globs relative to each root.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorpusRoots {
+    pub roots: Vec<PathBuf>,
+}
+
+impl CorpusRoots {
+    /// Loads the root directories of a repository. A repository without a
+    /// roots file has none besides its own directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the roots file exists, but it cannot be read or
+    /// parsed.
+    pub fn load(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = repo_dir.as_ref().join(ROOTS_FILENAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .context(format!("Failed to read roots file: {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .context(format!("Failed to parse roots file: {}", path.display()))
+    }
+
+    /// Returns all the root directories of the corpus of a repository: the
+    /// directory that the repository is in first, and then the ones that
+    /// were added to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the roots file cannot be read or parsed.
+    pub fn dirs(repo_dir: impl AsRef<Path>) -> anyhow::Result<Vec<PathBuf>> {
+        let repo_dir = repo_dir.as_ref();
+        let dir_path = corpus_dir(repo_dir)?;
+        let mut dirs = vec![dir_path.to_path_buf()];
+        dirs.extend(Self::load(repo_dir)?.roots);
+        Ok(dirs)
+    }
+
+    /// Writes the root directories to a repository. The roots file of a
+    /// repository without added roots is removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the roots file cannot be written or removed.
+    pub fn save(&self, repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = repo_dir.as_ref().join(ROOTS_FILENAME);
+        if self.roots.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .context(format!("Failed to remove roots file: {}", path.display()))?;
+            }
+            return Ok(());
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?).context(format!(
+            "Failed to write roots file: {}",
+            tmp_path.display()
+        ))?;
+        fs::rename(&tmp_path, &path)
+            .context(format!("Failed to write roots file: {}", path.display()))
+    }
+
+    /// Adds a root directory to the corpus of the repository in `dir_path`.
+    /// A root that was already added is not added again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the root is not a directory, or if it is in the
+    /// directory of the repository or in another root, or contains one of
+    /// them, as its documents would be indexed twice.
+    pub fn add(&mut self, dir_path: &Path, root: impl AsRef<Path>) -> anyhow::Result<()> {
+        let root = root.as_ref();
+        let root = root
+            .canonicalize()
+            .context(format!("Root directory does not exist: {}", root.display()))?;
+        if !root.is_dir() {
+            anyhow::bail!("Root is not a directory: {}", root.display());
+        }
+        if self.roots.contains(&root) {
+            return Ok(());
+        }
+        let overlapping = std::iter::once(dir_path)
+            .chain(self.roots.iter().map(PathBuf::as_path))
+            .find(|dir| root.starts_with(dir) || dir.starts_with(&root));
+        if let Some(dir) = overlapping {
+            anyhow::bail!(
+                "Root directory {} overlaps with {}.",
+                root.display(),
+                dir.display()
+            );
+        }
+        self.roots.push(root);
+        Ok(())
+    }
+}
+
+/// Returns the path of a document relative to the root directory it is in,
+/// or the path itself if it is in none of the roots.
+pub fn relative_to_root<'a>(roots: &[PathBuf], path: &'a Path) -> &'a Path {
+    roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        let dir_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .canonicalize()
+            .unwrap();
+        let workspace = dir_path.parent().unwrap();
+        let mut roots = CorpusRoots::default();
+        roots.add(&dir_path, workspace.join("index")).unwrap();
+        roots
+            .add(&dir_path, workspace.join("index/../index"))
+            .unwrap();
+        assert_eq!(roots.roots, [workspace.join("index")]);
+
+        // Roots that overlap with the directory or another root are rejected.
+        assert!(roots.add(&dir_path, dir_path.join("src")).is_err());
+        assert!(roots.add(&dir_path, workspace).is_err());
+        assert!(roots.add(&dir_path, workspace.join("index/src")).is_err());
+        assert!(roots.add(&dir_path, workspace.join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_relative_to_root() {
+        let roots = [PathBuf::from("/project"), PathBuf::from("/notes")];
+        let relative = |path| relative_to_root(&roots, Path::new(path));
+        assert_eq!(relative("/project/src/main.rs"), Path::new("src/main.rs"));
+        assert_eq!(relative("/notes/todo.md"), Path::new("todo.md"));
+        assert_eq!(relative("/other/todo.md"), Path::new("/other/todo.md"));
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use ignore::overrides::{Override, OverrideBuilder};
+
+use documents::archive::split_member_path;
+use documents::table::is_table;
+
+use crate::fs::roots::relative_to_root;
+
+/// The CSV and TSV files whose rows are documents of their own, matched by
+/// the globs of the `rows` of the `[extraction]` section of the config,
+/// relative to the root directories of the corpus, with the columns of their
+/// rows that are indexed.
+///
+/// ```toml
+/// [extraction.rows]
+/// "exports/*.csv" = ["name", "notes"]
+/// "logs/**/*.tsv" = []
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RowFiles {
+    /// The matchers of the globs, with the columns of the files they match.
+    globs: Vec<(Override, Vec<String>)>,
+    /// The root directories of the corpus, that the globs are relative to.
+    roots: Vec<PathBuf>,
+}
+
+impl RowFiles {
+    /// Builds the matchers of the globs of the files whose rows are
+    /// documents. Without roots, no file matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the globs is invalid.
+    pub fn new(roots: Vec<PathBuf>, rows: &BTreeMap<String, Vec<String>>) -> anyhow::Result<Self> {
+        let Some(root) = roots.first() else {
+            return Ok(Self::default());
+        };
+        let globs = rows
+            .iter()
+            .map(|(glob, columns)| {
+                let mut builder = OverrideBuilder::new(root);
+                builder
+                    .add(glob)
+                    .context(format!("Invalid rows glob: {glob}"))?;
+                Ok((builder.build()?, columns.clone()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self { globs, roots })
+    }
+
+    /// Returns the columns of the rows of a table whose rows are documents,
+    /// which are all of them if the list is empty, or `None` if the file is
+    /// a document as a whole. Tables in archives are documents as a whole.
+    pub fn columns(&self, path: &Path) -> Option<&[String]> {
+        if !is_table(path) || split_member_path(path).is_some() {
+            return None;
+        }
+        let relative = relative_to_root(&self.roots, path);
+        self.globs
+            .iter()
+            .find(|(matcher, _)| matcher.matched(relative, false).is_whitelist())
+            .map(|(_, columns)| columns.as_slice())
+    }
+
+    /// Returns the columns of a row that are indexed, from the path to the
+    /// row, like `sales.csv!/12`, or `None` if the path is not to a row. All
+    /// the columns of a table that is not matched anymore are indexed.
+    pub fn row_columns(&self, path: &Path) -> Option<&[String]> {
+        let (table, _) = split_member_path(path).filter(|(table, _)| is_table(table))?;
+        Some(self.columns(&table).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns() {
+        let globs = BTreeMap::from([("exports/*.csv".to_string(), vec!["notes".to_string()])]);
+        let rows = RowFiles::new(vec![PathBuf::from("/corpus")], &globs).unwrap();
+        let notes = ["notes".to_string()];
+        assert_eq!(
+            rows.columns(Path::new("/corpus/exports/sales.csv")),
+            Some(&notes[..])
+        );
+        assert_eq!(rows.columns(Path::new("/corpus/sales.csv")), None);
+        assert_eq!(rows.columns(Path::new("/corpus/exports/notes.md")), None);
+        assert_eq!(
+            rows.row_columns(Path::new("/corpus/exports/sales.csv!/3")),
+            Some(&notes[..])
+        );
+        assert_eq!(
+            rows.row_columns(Path::new("/corpus/sales.csv!/3")),
+            Some(&[][..])
+        );
+        assert_eq!(
+            rows.row_columns(Path::new("/corpus/exports/sales.csv")),
+            None
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use query::Phrase;
+
+use crate::config::{BODY_FIELD, TITLE_FIELD, UNINDEXED_LINE_CHARS, UNINDEXED_MAX_LINES};
+use crate::pattern::split_patterns;
+
+/// A line of the text of a file that matches a query.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LineMatch {
+    /// The path to the file.
+    pub path: PathBuf,
+    /// The number of the line in the text of the file, counting from 1.
+    pub line: usize,
+    /// The line, without its surrounding whitespace. A longer line than
+    /// `UNINDEXED_LINE_CHARS` characters is cut to them, around its first
+    /// match.
+    pub text: String,
+}
+
+/// A matcher of the words, phrases and patterns of a query in the text of
+/// files, without an index.
+///
+/// The words are matched literally, as they are written, so they are not
+/// stemmed nor expanded with their synonyms, and `title:` and `body:` are
+/// dropped from them. The phrases without a slop are matched with their
+/// words next to each other, and the patterns, written as `/pattern/`, as
+/// regular expressions between word boundaries. Unless the query is
+/// case-sensitive, everything is matched regardless of case.
+#[derive(Debug)]
+pub struct LineMatcher {
+    regexes: Vec<Regex>,
+}
+
+impl LineMatcher {
+    /// Builds the matcher of a lexical query, or returns `None` if it has
+    /// nothing to match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern is not a valid regular expression.
+    pub fn new(query: &str, case_sensitive: bool) -> anyhow::Result<Option<Self>> {
+        let (lexical, phrases) = Phrase::parse_all(query);
+        let (lexical, patterns) = split_patterns(&lexical);
+        let words = lexical
+            .split_whitespace()
+            .map(|word| {
+                word.strip_prefix(TITLE_FIELD)
+                    .or_else(|| word.strip_prefix(BODY_FIELD))
+                    .unwrap_or(word)
+            })
+            .filter(|word| !word.is_empty())
+            .map(regex::escape);
+        let phrases = phrases
+            .iter()
+            .filter(|phrase| phrase.slop == 0 && phrase.text.contains(char::is_whitespace))
+            .map(|phrase| {
+                let words = phrase.text.split_whitespace().map(regex::escape);
+                words.collect::<Vec<_>>().join(r"\s+")
+            });
+        let mut regexes = words
+            .chain(phrases)
+            .map(|regex| build_regex(&regex, case_sensitive))
+            .collect::<Result<Vec<_>, _>>()?;
+        for pattern in patterns {
+            let regex = build_regex(&format!(r"\b(?:{pattern})\b"), case_sensitive)
+                .context(format!("Invalid pattern: /{pattern}/"))?;
+            regexes.push(regex);
+        }
+        Ok((!regexes.is_empty()).then_some(Self { regexes }))
+    }
+
+    /// Finds the lines of the text of a file that match any part of the
+    /// query, if the text matches every part of it, up to
+    /// `UNINDEXED_MAX_LINES` lines.
+    pub fn find(&self, path: &Path, text: &str) -> Vec<LineMatch> {
+        if !self.regexes.iter().all(|regex| regex.is_match(text)) {
+            return vec![];
+        }
+        text.lines()
+            .map(str::trim)
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let first_match = self
+                    .regexes
+                    .iter()
+                    .filter_map(|regex| regex.find(line))
+                    .map(|found| found.start())
+                    .min()?;
+                Some(LineMatch {
+                    path: path.to_path_buf(),
+                    line: i + 1,
+                    text: excerpt(line, first_match),
+                })
+            })
+            .take(UNINDEXED_MAX_LINES)
+            .collect()
+    }
+}
+
+/// Cuts a line that is longer than `UNINDEXED_LINE_CHARS` characters to
+/// them, starting a quarter of them before the byte offset of its first
+/// match, so that the match is shown.
+fn excerpt(line: &str, first_match: usize) -> String {
+    if line.chars().count() <= UNINDEXED_LINE_CHARS {
+        return line.to_string();
+    }
+    let before = line[..first_match].chars().count();
+    line.chars()
+        .skip(before.saturating_sub(UNINDEXED_LINE_CHARS / 4))
+        .take(UNINDEXED_LINE_CHARS)
+        .collect()
+}
+
+/// Compiles a regular expression, regardless of case unless it is
+/// case-sensitive.
+fn build_regex(regex: &str, case_sensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(regex)
+        .case_insensitive(!case_sensitive)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_matcher() {
+        let path = Path::new("notes.md");
+        let text = "# Budget\n\nThe budget meeting is on Monday.\nAgenda: v1.2.3 release\n";
+        let matcher = LineMatcher::new("title:budget", false).unwrap().unwrap();
+        let lines = matcher
+            .find(path, text)
+            .into_iter()
+            .map(|m| (m.line, m.text))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                (1, "# Budget".to_string()),
+                (3, "The budget meeting is on Monday.".to_string())
+            ]
+        );
+
+        let matcher = LineMatcher::new(r#""budget meeting" /v1\.2\.\d+/"#, false).unwrap();
+        let lines = matcher.unwrap().find(path, text);
+        assert_eq!(lines.iter().map(|m| m.line).collect::<Vec<_>>(), [1, 3, 4]);
+        let matcher = LineMatcher::new("budget friday", false).unwrap().unwrap();
+        assert!(matcher.find(path, text).is_empty());
+        let matcher = LineMatcher::new("Budget", true).unwrap().unwrap();
+        assert_eq!(matcher.find(path, text).len(), 1);
+        let long = format!("{}budget", "word ".repeat(100));
+        let matcher = LineMatcher::new("budget", false).unwrap().unwrap();
+        let lines = matcher.find(path, &long);
+        assert!(lines[0].text.ends_with("budget"));
+        assert!(lines[0].text.len() <= UNINDEXED_LINE_CHARS);
+        assert!(LineMatcher::new("", false).unwrap().is_none());
+        assert!(LineMatcher::new("/(budget/", false).is_err());
+    }
+}
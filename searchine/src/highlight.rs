@@ -0,0 +1,212 @@
+use std::io::{self, IsTerminal};
+use std::ops::Range;
+use std::path::Path;
+
+use serde::Serialize;
+
+use query::Query;
+
+use crate::analyzer::AnalyzerConfig;
+
+/// The offsets of a match in a text, both in bytes and in characters, so
+/// that clients can place highlights whichever way they index strings.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Offsets {
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+/// Finds the terms of a document's text that match the tokens of a query.
+///
+/// The text is tokenized the same way the document was tokenized when it
+/// was indexed, so every term that contributed to the score of the document
+/// is found. Overlapping matches, like an identifier and one of its sub-terms
+/// in source code, are merged.
+///
+/// # Arguments
+///
+/// * `text`     - The text of the document.
+/// * `doc_path` - The path to the document, used to determine its type.
+/// * `query`    - The tokenized query. The terms of a case-sensitive query
+///   only match the identifiers of source code in their original case.
+/// * `analyzer` - The analyzer that the document was indexed with.
+///
+/// # Returns
+///
+/// The sorted byte ranges of the matches in the text.
+pub fn find_matches(
+    text: &str,
+    doc_path: impl AsRef<Path>,
+    query: &Query,
+    analyzer: &AnalyzerConfig,
+) -> Vec<Range<usize>> {
+    let mut tokenizer = analyzer.tokenizer();
+    let matches = analyzer
+        .document_spans(&mut tokenizer, text, doc_path, query.is_case_sensitive())
+        .into_iter()
+        .filter(|(token, _)| query.term_count(token) > 0)
+        .map(|(_, span)| span)
+        .collect::<Vec<_>>();
+    merge_matches(matches)
+}
+
+/// Sorts the byte ranges of matches, and merges the ones that overlap.
+pub fn merge_matches(mut matches: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    matches.sort_by_key(|span| span.start);
+    let mut merged: Vec<Range<usize>> = vec![];
+    for span in matches {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// The way matches are marked when a text is highlighted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Marker {
+    /// Bold red, with ANSI escape codes for the terminal.
+    Ansi,
+    /// Wrapped in `<mark>` tags, for clients that render the text.
+    Tags,
+    /// Not marked, for output that is not a terminal.
+    Plain,
+}
+
+impl Marker {
+    /// Returns the marker for text written to stdout: ANSI escape codes if
+    /// `color` is true and stdout is a terminal, and no marks otherwise, so
+    /// piped output stays clean. The `NO_COLOR` environment variable turns
+    /// colors off too.
+    pub fn for_stdout(color: bool) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        match color && !no_color && io::stdout().is_terminal() {
+            true => Marker::Ansi,
+            false => Marker::Plain,
+        }
+    }
+
+    /// Dims a text that is of less interest, like the rank of a result, in
+    /// the terminal.
+    pub fn dim(&self, text: &str) -> String {
+        match self {
+            Marker::Ansi => format!("\x1b[2m{text}\x1b[0m"),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Styles a path for the terminal, with its directory dimmed and the
+    /// name of its file bold, so the names stand out in a list of paths.
+    pub fn path(&self, path: &Path) -> String {
+        let (Marker::Ansi, Some(name)) = (self, path.file_name()) else {
+            return path.display().to_string();
+        };
+        let name = name.to_string_lossy();
+        match path.parent().map(Path::as_os_str) {
+            Some(dir) if !dir.is_empty() => {
+                let separator = std::path::MAIN_SEPARATOR;
+                let dir = dir.to_string_lossy();
+                format!("\x1b[2m{dir}{separator}\x1b[0m\x1b[1m{name}\x1b[0m")
+            }
+            _ => format!("\x1b[1m{name}\x1b[0m"),
+        }
+    }
+
+    /// Marks a match.
+    fn mark(&self, matched: &str) -> String {
+        match self {
+            Marker::Ansi => format!("\x1b[31m\x1b[1m{matched}\x1b[0m"),
+            Marker::Tags => format!("<mark>{matched}</mark>"),
+            Marker::Plain => matched.to_string(),
+        }
+    }
+}
+
+/// Highlights the matches in a text, making them bold red in the terminal.
+///
+/// # Arguments
+///
+/// * `text`    - The text to highlight.
+/// * `matches` - The sorted, non-overlapping byte ranges of the matches.
+pub fn highlight(text: &str, matches: &[Range<usize>]) -> String {
+    highlight_with(text, matches, Marker::Ansi)
+}
+
+/// Highlights the matches in a text with a marker.
+///
+/// # Arguments
+///
+/// * `text`    - The text to highlight.
+/// * `matches` - The sorted, non-overlapping byte ranges of the matches.
+/// * `marker`  - The way the matches are marked.
+pub fn highlight_with(text: &str, matches: &[Range<usize>], marker: Marker) -> String {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut pos = 0;
+    for span in matches {
+        highlighted.push_str(&text[pos..span.start]);
+        highlighted.push_str(&marker.mark(&text[span.clone()]));
+        pos = span.end;
+    }
+    highlighted.push_str(&text[pos..]);
+    highlighted
+}
+
+/// Converts the byte ranges of matches in a text to their byte and character
+/// offsets.
+///
+/// # Arguments
+///
+/// * `text`    - The text that contains the matches.
+/// * `matches` - The sorted, non-overlapping byte ranges of the matches.
+pub fn offsets(text: &str, matches: &[Range<usize>]) -> Vec<Offsets> {
+    let mut pos = 0;
+    let mut n_chars = 0;
+    let mut offsets = Vec::with_capacity(matches.len());
+    for span in matches {
+        n_chars += text[pos..span.start].chars().count();
+        let char_start = n_chars;
+        n_chars += text[span.clone()].chars().count();
+        pos = span.end;
+        offsets.push(Offsets {
+            byte_start: span.start,
+            byte_end: span.end,
+            char_start,
+            char_end: n_chars,
+        });
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets() {
+        let text = "Καλημέρα search world";
+        let offsets = offsets(text, &[17..23, 24..29]);
+        assert_eq!(offsets[0].char_start, 9);
+        assert_eq!(offsets[0].char_end, 15);
+        assert_eq!(offsets[1].byte_start, 24);
+        assert_eq!(offsets[1].char_start, 16);
+        assert_eq!(offsets[1].char_end, 21);
+    }
+
+    #[test]
+    fn test_path() {
+        let path = Path::new("notes/report.md");
+        assert_eq!(
+            Marker::Ansi.path(path),
+            "\x1b[2mnotes/\x1b[0m\x1b[1mreport.md\x1b[0m"
+        );
+        assert_eq!(
+            Marker::Ansi.path(Path::new("report.md")),
+            "\x1b[1mreport.md\x1b[0m"
+        );
+        assert_eq!(Marker::Plain.path(path), "notes/report.md");
+        assert_eq!(Marker::Plain.dim("1"), "1");
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{HISTORY_FILENAME, HISTORY_MAX_ENTRIES, SAVED_SEARCHES_FILENAME};
+use crate::scheduler::unix_time;
+
+/// A query that was searched, as it is kept in the history of a repository.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The time of the search, in seconds since the Unix epoch.
+    pub time: u64,
+    /// The query, as it was written.
+    pub query: String,
+    /// The number of results that were shown.
+    pub hits: usize,
+}
+
+/// Appends a query that was searched to the history of a repository.
+///
+/// Each query is a line of JSON. Once the history has more than
+/// `HISTORY_MAX_ENTRIES` queries, it is rewritten with only the most recent
+/// of them.
+///
+/// # Errors
+///
+/// Returns an error if the history cannot be written.
+pub fn record(repo_dir: impl AsRef<Path>, query: &str, hits: usize) -> anyhow::Result<()> {
+    let path = repo_dir.as_ref().join(HISTORY_FILENAME);
+    let entry = HistoryEntry {
+        time: unix_time(),
+        query: query.to_string(),
+        hits,
+    };
+    let mut line = serde_json::to_vec(&entry)?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context(format!("Failed to open history: {}", path.display()))?;
+    file.write_all(&line)
+        .context(format!("Failed to write history: {}", path.display()))?;
+
+    let entries = load(&repo_dir)?;
+    if entries.len() > HISTORY_MAX_ENTRIES {
+        write(&repo_dir, &entries[entries.len() - HISTORY_MAX_ENTRIES..])?;
+    }
+    Ok(())
+}
+
+/// Reads the history of a repository, from the oldest query to the most
+/// recent. The history is empty if no queries were searched, and lines that
+/// cannot be parsed are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the history exists, but cannot be read.
+pub fn load(repo_dir: impl AsRef<Path>) -> anyhow::Result<Vec<HistoryEntry>> {
+    let path = repo_dir.as_ref().join(HISTORY_FILENAME);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents =
+        fs::read_to_string(&path).context(format!("Failed to read history: {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Removes the history of a repository, if it exists.
+///
+/// # Errors
+///
+/// Returns an error if the history cannot be removed.
+pub fn clear(repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = repo_dir.as_ref().join(HISTORY_FILENAME);
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).context(format!("Failed to remove history: {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the history of a repository with the entries, writing them to a
+/// temporary file first.
+fn write(repo_dir: impl AsRef<Path>, entries: &[HistoryEntry]) -> anyhow::Result<()> {
+    let path = repo_dir.as_ref().join(HISTORY_FILENAME);
+    let tmp_path = path.with_extension("tmp");
+    let mut contents = vec![];
+    for entry in entries {
+        serde_json::to_writer(&mut contents, entry)?;
+        contents.push(b'\n');
+    }
+    fs::write(&tmp_path, contents)
+        .context(format!("Failed to write history: {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, &path).context(format!("Failed to write history: {}", path.display()))
+}
+
+/// The searches of a repository that were saved by name, to run them again
+/// with `searchine run <name>`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedSearches {
+    /// The queries of the searches, by their names.
+    pub queries: BTreeMap<String, String>,
+}
+
+impl SavedSearches {
+    /// Reads the saved searches of a repository, which are none if no search
+    /// was saved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the saved searches exist, but cannot be read or
+    /// parsed.
+    pub fn load(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = repo_dir.as_ref().join(SAVED_SEARCHES_FILENAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .context(format!("Failed to read saved searches: {}", path.display()))?;
+        serde_json::from_str(&contents).context(format!(
+            "Failed to parse saved searches: {}",
+            path.display()
+        ))
+    }
+
+    /// Writes the saved searches to a repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the saved searches cannot be written.
+    pub fn save(&self, repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = repo_dir.as_ref().join(SAVED_SEARCHES_FILENAME);
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(&path, contents).context(format!(
+            "Failed to write saved searches: {}",
+            path.display()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        assert!(load(dir).unwrap().is_empty());
+        for i in 0..HISTORY_MAX_ENTRIES + 2 {
+            record(dir, &format!("query {i}"), i).unwrap();
+        }
+        let entries = load(dir).unwrap();
+        assert_eq!(entries.len(), HISTORY_MAX_ENTRIES);
+        assert_eq!(entries[0].query, "query 2");
+        assert_eq!(entries.last().unwrap().hits, HISTORY_MAX_ENTRIES + 1);
+
+        let mut saved = SavedSearches::load(dir).unwrap();
+        saved
+            .queries
+            .insert("budget".into(), r#""budget meeting""#.into());
+        saved.save(dir).unwrap();
+        assert_eq!(SavedSearches::load(dir).unwrap(), saved);
+        clear(dir).unwrap();
+        assert!(load(dir).unwrap().is_empty());
+    }
+}
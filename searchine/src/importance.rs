@@ -0,0 +1,236 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+
+use documents::{DocumentId, Link};
+use index::collection::Collection;
+use index::graph::LinkGraph;
+use index::store::DocumentStore;
+
+use crate::config::{
+    COLLECTION_FILENAME, IMPORTANCE_FILENAME, PAGERANK_DAMPING, PAGERANK_ITERATIONS, STORE_DIRNAME,
+};
+use crate::fs::CorpusRoots;
+
+/// The importance of the documents of a repository, estimated from the links
+/// between them, which does not depend on queries.
+#[derive(Debug, Default)]
+pub struct Importance {
+    /// The PageRank of each document, by its path, with an average of 1.
+    ranks: HashMap<PathBuf, f64>,
+}
+
+impl Importance {
+    /// Loads the importance of the documents of a repository, or returns
+    /// `None` if none of its documents link to each other.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file of the importance cannot be read.
+    pub fn load(repo_dir: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        let path = repo_dir.as_ref().join(IMPORTANCE_FILENAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(&path).context(format!(
+            "Failed to read importance file: {}",
+            path.display()
+        ))?;
+        let ranks = serde_json::from_reader(BufReader::new(file)).context(format!(
+            "Failed to read importance file: {}",
+            path.display()
+        ))?;
+        Ok(Some(Self { ranks }))
+    }
+
+    /// Returns the importance of the document at a path. A document that
+    /// was indexed after the importance was computed is of average
+    /// importance.
+    pub fn rank(&self, path: &Path) -> f64 {
+        self.ranks.get(path).copied().unwrap_or(1.0)
+    }
+}
+
+/// Computes the importance of the documents of a repository again, from the
+/// links of the documents that are kept in the document store. Errors are
+/// printed rather than returned, so the importance cannot stop the index
+/// from being updated.
+///
+/// If none of the documents link to each other, the file of the importance
+/// is removed, as every document would be of the same importance.
+pub fn update_importance(repo_dir: impl AsRef<Path>) {
+    let repo_dir = repo_dir.as_ref();
+    if let Err(err) = try_update_importance(repo_dir) {
+        eprintln!("ERROR: Failed to update importance of documents because: {err:#}");
+    }
+}
+
+fn try_update_importance(repo_dir: &Path) -> anyhow::Result<()> {
+    let roots = CorpusRoots::dirs(repo_dir)?;
+    let collection = Collection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+    let store = DocumentStore::open(repo_dir.join(STORE_DIRNAME))?;
+    let mut documents = vec![];
+    for (path, entry) in &collection {
+        let links = store.read_links(entry.document_id())?;
+        documents.push((path.clone(), entry.document_id(), links));
+    }
+    let graph = build_graph(&roots, &documents);
+    let path = repo_dir.join(IMPORTANCE_FILENAME);
+    if graph.n_links() == 0 {
+        if path.exists() {
+            fs::remove_file(&path).context(format!(
+                "Failed to remove importance file: {}",
+                path.display()
+            ))?;
+        }
+        return Ok(());
+    }
+
+    let paths = documents
+        .iter()
+        .map(|(path, doc_id, _)| (*doc_id, path))
+        .collect::<HashMap<_, _>>();
+    let ranks = graph
+        .pagerank(PAGERANK_DAMPING, PAGERANK_ITERATIONS)
+        .into_iter()
+        .filter_map(|(doc_id, rank)| Some((paths.get(&doc_id)?, rank)))
+        .collect::<HashMap<_, _>>();
+    let tmp_path = path.with_extension("json.tmp");
+    let file = File::create(&tmp_path).context(format!(
+        "Failed to write importance file: {}",
+        tmp_path.display()
+    ))?;
+    serde_json::to_writer(BufWriter::new(file), &ranks)?;
+    fs::rename(&tmp_path, &path).context(format!(
+        "Failed to write importance file: {}",
+        path.display()
+    ))
+}
+
+/// Builds the graph of the links between documents, from their paths, their
+/// IDs and their links. Links to files that are not documents of the
+/// collection are dropped, and absolute links are resolved against the root
+/// directory of `roots` that the linking document is in.
+fn build_graph(roots: &[PathBuf], documents: &[(PathBuf, DocumentId, Vec<Link>)]) -> LinkGraph {
+    let doc_ids = documents
+        .iter()
+        .map(|(path, doc_id, _)| (path.as_path(), *doc_id))
+        .collect::<HashMap<_, _>>();
+    // Wiki links point to documents by their names, and the shortest path
+    // wins when several documents have the same name.
+    let mut by_name = documents.iter().collect::<Vec<_>>();
+    by_name.sort_by_key(|(path, _, _)| Reverse((path.components().count(), path.clone())));
+    let by_name = by_name
+        .into_iter()
+        .filter_map(|(path, doc_id, _)| {
+            let name = path.file_stem()?.to_string_lossy().to_lowercase();
+            Some((name, *doc_id))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut graph = LinkGraph::new();
+    for (path, doc_id, links) in documents {
+        graph.add_document(*doc_id);
+        let root = roots
+            .iter()
+            .find(|root| path.starts_with(root))
+            .or(roots.first())
+            .map_or(Path::new("/"), PathBuf::as_path);
+        let base = path.parent().unwrap_or(root);
+        for link in links {
+            let target = match link {
+                Link::Name(name) => by_name.get(&name.to_lowercase()).copied(),
+                Link::Path(target) => {
+                    let target = match target.strip_prefix('/') {
+                        Some(target) => root.join(target),
+                        None => base.join(target),
+                    };
+                    candidates(&normalize(&target))
+                        .iter()
+                        .find_map(|candidate| doc_ids.get(candidate.as_path()).copied())
+                }
+            };
+            if let Some(target) = target {
+                graph.add_link(*doc_id, target);
+            }
+        }
+    }
+    graph
+}
+
+/// Returns the paths of the documents that a link to a path may point to:
+/// the path itself, the Markdown or HTML file of a path without an
+/// extension, and the index page of a directory.
+fn candidates(path: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![path.to_path_buf()];
+    if path.extension().is_none() {
+        for extension in ["md", "html"] {
+            candidates.push(path.with_extension(extension));
+        }
+    }
+    for index in ["index.md", "index.html", "README.md"] {
+        candidates.push(path.join(index));
+    }
+    candidates
+}
+
+/// Resolves the `.` and `..` components of a path without reading the file
+/// system, as the file that a link points to may not exist.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_graph() {
+        let roots = [PathBuf::from("/wiki")];
+        let documents = [
+            (
+                "/wiki/index.md",
+                0,
+                vec![Link::Path("guides/setup.md".to_string())],
+            ),
+            (
+                "/wiki/guides/setup.md",
+                1,
+                vec![
+                    Link::Path("../index.md".to_string()),
+                    Link::Name("FAQ".to_string()),
+                    Link::Path("missing.md".to_string()),
+                ],
+            ),
+            (
+                "/wiki/faq.md",
+                2,
+                vec![Link::Path("/guides/setup".to_string())],
+            ),
+            ("/wiki/archive/faq.md", 3, vec![]),
+        ]
+        .map(|(path, doc_id, links)| (PathBuf::from(path), doc_id, links));
+
+        let graph = build_graph(&roots, &documents);
+        assert_eq!(graph.n_docs(), 4);
+        assert_eq!(graph.n_links(), 4);
+        let ranks = graph.pagerank(PAGERANK_DAMPING, PAGERANK_ITERATIONS);
+        // The copy of the FAQ in the archive is linked to by none.
+        assert!(ranks[&2] > ranks[&3]);
+        assert!(ranks[&1] > ranks[&3]);
+    }
+}
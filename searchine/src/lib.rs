@@ -0,0 +1,55 @@
+//! A simple local search engine, which indexes the documents of a directory
+//! with an inverted index, and searches them.
+//!
+//! The `searchine` command line tool is built on this library, and
+//! applications can embed the same search through a [`Searchine`]
+//! repository, which is compatible with the one of the tool:
+//!
+//! ```no_run
+//! use searchine::Searchine;
+//!
+//! let searchine = Searchine::init("notes")?;
+//! searchine.index()?;
+//! for hit in searchine.search("budget meeting", 10)? {
+//!     println!("{:.3} {}", hit.score, hit.path.display());
+//! }
+//! # Ok::<(), searchine::Error>(())
+//! ```
+
+#[macro_use]
+mod fmt;
+mod analyzer;
+mod audit;
+mod cache;
+mod cli;
+mod config;
+mod engine;
+mod error;
+mod feedback;
+mod fs;
+mod grep;
+mod highlight;
+mod history;
+mod importance;
+mod lock;
+mod passage;
+mod path;
+mod pattern;
+mod phrase;
+mod progress;
+mod proximity;
+mod relax;
+mod reload;
+mod scheduler;
+mod settings;
+mod shutdown;
+mod snapshot;
+mod snippet;
+mod spelling;
+mod synonyms;
+
+pub use cli::index::Changes;
+pub use cli::run;
+pub use documents::extract;
+pub use engine::{SearchHit, Searchine};
+pub use error::{Error, Result};
@@ -0,0 +1,203 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use fs2::FileExt;
+
+use crate::config::{LOCK_FILENAME, LOCK_POLL_MILLIS, LOCK_WAIT_MILLIS};
+
+/// The lock of a repository, which is held by a command while it changes the
+/// files of the repository, so that two commands never change them at the
+/// same time. The lock is released when it is dropped.
+///
+/// The lock is an advisory lock of the operating system on a file in the
+/// repository, which is held for as long as the file is open. A process that
+/// is killed before it releases the lock has its file closed, and its lock
+/// released, by the operating system, so a lock is never stale. The file
+/// has the ID of the process that holds the lock, which is only read to tell
+/// which process holds it. The file itself is never removed, so that every
+/// process locks the same file.
+pub struct RepoLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl RepoLock {
+    /// Acquires the lock of a repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`index::Error::Locked`] error if another process holds
+    /// the lock, or an error if the lock file cannot be opened.
+    pub fn acquire(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = repo_dir.as_ref().join(LOCK_FILENAME);
+        let mut file = open_lock_file(&path)?;
+        if let Err(err) = file.try_lock_exclusive() {
+            if err.raw_os_error() != fs2::lock_contended_error().raw_os_error() {
+                return Err(err).context(format!("Failed to lock: {}", path.display()));
+            }
+            // A lock without an ID is held by a process that is about to
+            // write its ID, or that is only checking for a holder, so it is
+            // tried once more.
+            let is_locked = lock_pid(&mut file).is_none() && {
+                sleep(Duration::from_millis(LOCK_POLL_MILLIS));
+                file.try_lock_exclusive().is_ok()
+            };
+            if !is_locked {
+                let pid = lock_pid(&mut file);
+                return Err(index::Error::Locked { path, pid }.into());
+            }
+        }
+        file.set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| write!(file, "{}", std::process::id()))
+            .and_then(|()| file.flush())
+            .context(format!("Failed to write lock file: {}", path.display()))?;
+        Ok(Self { path, file })
+    }
+
+    /// Acquires the lock of a repository, waiting for a while if it is held,
+    /// like by a short update of another command, or of another thread of
+    /// this process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock is still held after a while, or if the
+    /// lock file cannot be opened.
+    pub fn acquire_waiting(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let repo_dir = repo_dir.as_ref();
+        let start = Instant::now();
+        loop {
+            match Self::acquire(repo_dir) {
+                Ok(lock) => return Ok(lock),
+                Err(err) if start.elapsed() >= Duration::from_millis(LOCK_WAIT_MILLIS) => {
+                    return Err(err)
+                }
+                Err(_) => sleep(Duration::from_millis(LOCK_POLL_MILLIS)),
+            }
+        }
+    }
+
+    /// Waits until no process holds the lock of a repository, so that the
+    /// files of the repository are read after they are changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock is still held after a while.
+    pub fn wait(repo_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = repo_dir.as_ref().join(LOCK_FILENAME);
+        let start = Instant::now();
+        while let Holder::Running(pid) = holder(&path) {
+            if start.elapsed() >= Duration::from_millis(LOCK_WAIT_MILLIS) {
+                anyhow::bail!(
+                    "Repository is being updated by another searchine process{}. \
+                     Try again once it finishes.",
+                    pid.map(|pid| format!(" (PID {pid})")).unwrap_or_default()
+                );
+            }
+            sleep(Duration::from_millis(LOCK_POLL_MILLIS));
+        }
+        Ok(())
+    }
+
+    /// Finds the holder of the lock of a repository, without waiting for it.
+    pub fn holder(repo_dir: impl AsRef<Path>) -> Holder {
+        holder(&repo_dir.as_ref().join(LOCK_FILENAME))
+    }
+
+    /// Checks if another process holds the lock of a repository, without
+    /// waiting for it.
+    pub fn is_held(repo_dir: impl AsRef<Path>) -> bool {
+        let path = repo_dir.as_ref().join(LOCK_FILENAME);
+        matches!(holder(&path), Holder::Running(pid) if pid != Some(std::process::id()))
+    }
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        // The ID is cleared before the lock is released, so that it is never
+        // read as the ID of the holder of a later lock. The lock itself is
+        // released when the file is closed.
+        if let Err(err) = self.file.set_len(0) {
+            eprintln!(
+                "ERROR: Failed to clear lock file: {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// The holder of a lock.
+pub enum Holder {
+    /// The lock is held by a running process, with its ID if it could be
+    /// read.
+    Running(Option<u32>),
+    /// The lock is not held.
+    None,
+}
+
+/// Finds the holder of a lock from its file, by trying to share the lock,
+/// which only fails while a process holds it.
+fn holder(path: &Path) -> Holder {
+    let Ok(mut file) = File::open(path) else {
+        return Holder::None;
+    };
+    match file.try_lock_shared() {
+        Ok(()) => Holder::None,
+        Err(_) => Holder::Running(lock_pid(&mut file)),
+    }
+}
+
+/// Opens a lock file, creating it if it does not exist, without changing
+/// the ID of the process in it.
+fn open_lock_file(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .context(format!("Failed to open lock file: {}", path.display()))
+}
+
+/// Reads the ID of the process in a lock file, if it has one. On platforms
+/// where a locked file cannot be read, the ID is never found.
+fn lock_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_repo_lock() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join(LOCK_FILENAME);
+        // A lock file that is left behind, but not locked, is not held.
+        fs::write(&path, "12345").unwrap();
+        assert!(matches!(RepoLock::holder(dir), Holder::None));
+
+        let lock = RepoLock::acquire(dir).unwrap();
+        let pid = std::process::id();
+        assert!(matches!(RepoLock::holder(dir), Holder::Running(Some(id)) if id == pid));
+        assert!(!RepoLock::is_held(dir));
+        let err = RepoLock::acquire(dir).err().unwrap();
+        let err = err.downcast::<index::Error>().unwrap();
+        assert!(matches!(err, index::Error::Locked { pid: Some(id), .. } if id == pid));
+
+        drop(lock);
+        assert!(matches!(RepoLock::holder(dir), Holder::None));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        drop(RepoLock::acquire(dir).unwrap());
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use index::inverted::Index;
+use index::score::calc_bm25;
+use query::Query;
+use tokenize::Token;
+
+use crate::analyzer::AnalyzerConfig;
+use crate::config::{BM25_B, BM25_K};
+use crate::highlight::merge_matches;
+use crate::snippet::Snippet;
+
+/// The unit that a document is split into, when passages are scored instead
+/// of whole documents.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PassageMode {
+    /// Overlapping windows of a fixed number of terms.
+    #[default]
+    Window,
+    /// Sections that start at Markdown headings, or paragraphs if the
+    /// document has no headings.
+    Section,
+}
+
+/// The policy for splitting documents into passages and aggregating their
+/// scores.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PassageConfig {
+    /// The unit of the passages.
+    pub mode: PassageMode,
+    /// The number of terms in each window.
+    pub size: usize,
+    /// The number of terms between the starts of consecutive windows.
+    pub stride: usize,
+    /// The number of best passages of a document whose scores are summed
+    /// into the score of the document.
+    pub per_document: usize,
+}
+
+impl Default for PassageConfig {
+    fn default() -> Self {
+        Self {
+            mode: PassageMode::default(),
+            size: 100,
+            stride: 50,
+            per_document: 1,
+        }
+    }
+}
+
+/// A scored passage of a document.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Passage {
+    pub score: f64,
+    #[serde(flatten)]
+    pub snippet: Snippet,
+}
+
+/// Splits a document's text into passages and scores them against a query.
+///
+/// Each passage is scored with BM25, taking the term frequencies and the
+/// length from the passage, and the inverse document frequencies from the
+/// index, so that a rare term is still worth more than a common one. The
+/// passages that match no query term are dropped.
+///
+/// # Arguments
+///
+/// * `text`     - The text of the document.
+/// * `doc_path` - The path to the document, used to determine its type.
+/// * `query`    - The query.
+/// * `index`    - The index of the collection.
+/// * `analyzer` - The analyzer that the document was indexed with.
+/// * `config`   - The passage policy.
+///
+/// # Returns
+///
+/// The passages sorted by descending score.
+pub fn score_passages(
+    text: &str,
+    doc_path: impl AsRef<Path>,
+    query: &Query,
+    index: &dyn Index,
+    analyzer: &AnalyzerConfig,
+    config: &PassageConfig,
+) -> Vec<Passage> {
+    let mut tokenizer = analyzer.tokenizer();
+    let spans = analyzer.document_spans(&mut tokenizer, text, doc_path, query.is_case_sensitive());
+    let units = match config.mode {
+        PassageMode::Window => windows(&spans, config.size, config.stride),
+        PassageMode::Section => sections(text, &spans),
+    };
+    if units.is_empty() {
+        return vec![];
+    }
+    let avg_len = units.iter().map(|unit| unit.len()).sum::<usize>() as f64 / units.len() as f64;
+
+    let mut passages = units
+        .into_iter()
+        .filter_map(|unit| {
+            let spans = &spans[unit];
+            let score = score_passage(spans, query, index, avg_len);
+            if score <= 0.0 {
+                return None;
+            }
+            let window = spans.first()?.1.start..spans.last()?.1.end;
+            let matches = spans
+                .iter()
+                .filter(|(token, _)| query.term_count(token) > 0)
+                .map(|(_, span)| span.clone())
+                .collect::<Vec<_>>();
+            let snippet = Snippet::from_window(text, &merge_matches(matches), window);
+            Some(Passage { score, snippet })
+        })
+        .collect::<Vec<_>>();
+    passages.sort_by(|a, b| b.score.total_cmp(&a.score));
+    passages
+}
+
+/// Aggregates the scores of the best passages of a document into the score
+/// of the document, keeping only those passages.
+pub fn best_passages(passages: Vec<Passage>, config: &PassageConfig) -> (f64, Vec<Passage>) {
+    let best = non_overlapping(passages, config.per_document.max(1));
+    let score = best.iter().map(|passage| passage.score).sum();
+    (score, best)
+}
+
+/// Selects up to `limit` passages in order, skipping the passages that
+/// overlap a passage selected before them. Overlapping windows share their
+/// matches, so only the better of them is kept.
+pub fn non_overlapping(passages: Vec<Passage>, limit: usize) -> Vec<Passage> {
+    let mut selected: Vec<Passage> = vec![];
+    for passage in passages {
+        if selected.len() == limit {
+            break;
+        }
+        let offsets = &passage.snippet.offsets;
+        let overlaps = selected.iter().any(|other| {
+            let other = &other.snippet.offsets;
+            offsets.byte_start < other.byte_end && other.byte_start < offsets.byte_end
+        });
+        if !overlaps {
+            selected.push(passage);
+        }
+    }
+    selected
+}
+
+/// Scores the terms of a passage with BM25.
+fn score_passage(
+    spans: &[(Token, Range<usize>)],
+    query: &Query,
+    index: &dyn Index,
+    avg_len: f64,
+) -> f64 {
+    let mut term_freqs = HashMap::new();
+    for (token, _) in spans {
+        if query.term_count(token) > 0 {
+            *term_freqs.entry(token).or_insert(0) += 1;
+        }
+    }
+    term_freqs
+        .into_iter()
+        .map(|(term, freq)| {
+            let n_docs_containing = index.n_docs_containing(term);
+            let bm25 = calc_bm25(
+                freq,
+                index.n_docs(),
+                n_docs_containing,
+                spans.len() as u32,
+                avg_len,
+                BM25_K,
+                BM25_B,
+            );
+            query.term_weight(term) * bm25
+        })
+        .sum()
+}
+
+/// Splits the terms of a text into overlapping windows, returning the ranges
+/// of the terms of each window. The last window ends at the last term.
+fn windows<T>(spans: &[T], size: usize, stride: usize) -> Vec<Range<usize>> {
+    let size = size.max(1);
+    let stride = stride.clamp(1, size);
+    let mut windows = vec![];
+    let mut start = 0;
+    while start < spans.len() {
+        let end = (start + size).min(spans.len());
+        windows.push(start..end);
+        if end == spans.len() {
+            break;
+        }
+        start += stride;
+    }
+    windows
+}
+
+/// Splits the terms of a text into sections, returning the ranges of the
+/// terms of each section. Sections start at Markdown headings, or at
+/// paragraphs if the text has no headings.
+fn sections(text: &str, spans: &[(Token, Range<usize>)]) -> Vec<Range<usize>> {
+    let mut line_starts = vec![];
+    let mut pos = 0;
+    for line in text.split_inclusive('\n') {
+        line_starts.push((pos, line.trim()));
+        pos += line.len();
+    }
+    let mut starts = line_starts
+        .iter()
+        .filter(|(_, line)| line.starts_with('#'))
+        .map(|(start, _)| *start)
+        .collect::<Vec<_>>();
+    if starts.is_empty() {
+        starts = line_starts
+            .windows(2)
+            .filter(|pair| pair[0].1.is_empty() && !pair[1].1.is_empty())
+            .map(|pair| pair[1].0)
+            .collect();
+    }
+
+    let mut sections = vec![];
+    let mut section_start = 0;
+    for start in starts {
+        let section_end = spans.partition_point(|(_, span)| span.start < start);
+        if section_end > section_start {
+            sections.push(section_start..section_end);
+        }
+        section_start = section_end;
+    }
+    if section_start < spans.len() {
+        sections.push(section_start..spans.len());
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenize::Tokenizer;
+
+    use super::*;
+
+    #[test]
+    fn test_windows() {
+        let spans = vec![(); 10];
+        assert_eq!(windows(&spans, 4, 2), vec![0..4, 2..6, 4..8, 6..10]);
+        assert_eq!(windows(&spans, 4, 4), vec![0..4, 4..8, 8..10]);
+        assert_eq!(windows(&spans, 20, 5), vec![0..10]);
+        assert!(windows::<()>(&[], 4, 2).is_empty());
+    }
+
+    #[test]
+    fn test_sections() {
+        let text = "# Intro\nSome words here.\n\n# Budget\nThe quarterly budget.\n";
+        let mut tokenizer = Tokenizer::default();
+        let spans = tokenizer.tokenize_spans(text);
+        let sections = sections(text, &spans);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(spans[sections[1].start].1, 28..34);
+    }
+
+    #[test]
+    fn test_paragraph_sections() {
+        let text = "First paragraph.\nStill first.\n\nSecond paragraph.";
+        let mut tokenizer = Tokenizer::default();
+        let spans = tokenizer.tokenize_spans(text);
+        let sections = sections(text, &spans);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(&text[spans[sections[1].start].1.clone()], "Second");
+    }
+}
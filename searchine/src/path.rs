@@ -1,7 +1,9 @@
-use std::path::{Path, PathBuf, StripPrefixError};
+use std::path::{Component, Path, PathBuf, StripPrefixError};
 
 use anyhow::Context;
 
+use crate::config::{INDEXES_DIRNAME, SEARCHINE_PATH};
+
 /// Checks if a directory is contained in a directory with specified name.
 /// If it is, returns the path to the repo. Otherwise, returns `None`.
 ///
@@ -11,9 +13,9 @@ use anyhow::Context;
 /// * `repo_path` - The path to the `.searchine` directory.
 ///
 /// # Examples
-/// ```no_run
+/// ```ignore
 /// use std::path::Path;
-/// use fingertips::path::find_repo_path;
+/// use searchine::path::find_repo_path;
 ///
 /// let target_dir = "target_dir";
 /// let dir_path = Path::new("/Users/some_user/target_dir/some_dir/");
@@ -36,6 +38,78 @@ pub fn find_repo_path(path: impl AsRef<Path>, repo_dir_name: impl AsRef<Path>) -
     None
 }
 
+/// Finds the directory of an index of the repository that a directory is
+/// in: the repository itself for the default index, or the directory of the
+/// named index in it. Returns `None` if the directory is not in a repository.
+///
+/// # Errors
+///
+/// Returns an error if the name is not a valid name of an index, or if the
+/// repository has no index with the name.
+pub fn find_index_path(
+    path: impl AsRef<Path>,
+    name: Option<&str>,
+) -> anyhow::Result<Option<PathBuf>> {
+    let Some(repo_path) = find_repo_path(path, SEARCHINE_PATH) else {
+        return Ok(None);
+    };
+    let Some(name) = name else {
+        return Ok(Some(repo_path));
+    };
+    let index_path = named_index_path(&repo_path, name)?;
+    if !index_path.exists() {
+        anyhow::bail!(
+            "Index {name} does not exist at: {}. Run `searchine init --index {name}` to create it.",
+            repo_path.display()
+        );
+    }
+    Ok(Some(index_path))
+}
+
+/// Returns the path to the directory of a named index of a repository. The
+/// name has to be a plain name, like `notes`, which is not hidden.
+///
+/// # Errors
+///
+/// Returns an error if the name is empty, hidden, or has separators.
+pub fn named_index_path(repo_path: impl AsRef<Path>, name: &str) -> anyhow::Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    let is_plain = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !is_plain || name.starts_with('.') || name.contains(['/', '\\']) {
+        anyhow::bail!("Invalid index name: {name}");
+    }
+    Ok(repo_path.as_ref().join(INDEXES_DIRNAME).join(name))
+}
+
+/// Returns the directory of the corpus of a repository, which is the
+/// directory that its `.searchine` directory is in, for the named indexes
+/// in it too.
+///
+/// # Errors
+///
+/// Returns an error if the repository is at the root of the file system.
+pub fn corpus_dir(repo_dir: &Path) -> anyhow::Result<&Path> {
+    let is_named = repo_dir.parent().is_some_and(|indexes| {
+        indexes
+            .file_name()
+            .is_some_and(|name| name == INDEXES_DIRNAME)
+            && indexes
+                .parent()
+                .and_then(Path::file_name)
+                .is_some_and(|name| name == SEARCHINE_PATH)
+    });
+    let repo_path = match is_named {
+        true => repo_dir.ancestors().nth(2).unwrap_or(repo_dir),
+        false => repo_dir,
+    };
+    repo_path
+        .parent()
+        .context(format!("Failed to get parent for: {}", repo_dir.display()))
+}
+
 /// Formats the directory path that is optionally specified.
 /// If the path is specified, it is canonicalized and returned.
 /// If the path is not specified, the current directory is
@@ -59,9 +133,9 @@ pub fn canonicalize_dir_path(dir_path: Option<String>) -> anyhow::Result<PathBuf
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// use std::path::{Path, PathBuf};
-/// use fingertips::path::get_relative_path;
+/// use searchine::path::get_relative_path;
 ///
 /// let base_path = Path::new("/Users/some_user/target_dir/");
 /// let path = Path::new("/Users/some_user/target_dir/some_dir/");
@@ -94,4 +168,16 @@ mod tests {
         let expected_path = PathBuf::from("some_dir/");
         assert_eq!(get_relative_path(path, base_path), Ok(expected_path));
     }
+
+    #[test]
+    fn test_named_index_path() {
+        let repo_path = Path::new("/notes/.searchine");
+        let index_path = named_index_path(repo_path, "code").unwrap();
+        assert_eq!(index_path, Path::new("/notes/.searchine/indexes/code"));
+        assert_eq!(corpus_dir(&index_path).unwrap(), Path::new("/notes"));
+        assert_eq!(corpus_dir(repo_path).unwrap(), Path::new("/notes"));
+        for name in ["", ".", "..", ".hidden", "a/b", "/abs"] {
+            assert!(named_index_path(repo_path, name).is_err());
+        }
+    }
 }
@@ -0,0 +1,110 @@
+use anyhow::Context;
+use regex::Regex;
+
+use index::inverted::Index;
+use tokenize::{Token, CASED_PREFIX, TITLE_PREFIX};
+
+use crate::config::PATTERN_MAX_TERMS;
+
+/// The character that a pattern of a query starts and ends with.
+const PATTERN_DELIMITER: char = '/';
+
+/// Splits the patterns out of the lexical terms of a query, which are the
+/// words written as `/pattern/`, like `/v1\.2\.\d+/`.
+///
+/// # Returns
+///
+/// The terms without the patterns, and the patterns without their slashes,
+/// in the order they were written.
+pub fn split_patterns(lexical: &str) -> (String, Vec<&str>) {
+    let (patterns, words): (Vec<_>, Vec<_>) = lexical
+        .split_whitespace()
+        .partition(|word| word.len() > 2 && word.starts_with('/') && word.ends_with('/'));
+    let patterns = patterns
+        .into_iter()
+        .map(|pattern| &pattern[1..pattern.len() - 1])
+        .collect();
+    (words.join(" "), patterns)
+}
+
+/// Finds the terms of the index that match patterns, by scanning its
+/// vocabulary with each pattern, compiled as a regular expression that has
+/// to match the whole term.
+///
+/// The terms are matched as they were indexed, in lowercase and stemmed, so
+/// a term matches both the text and the title of the documents, like the
+/// words of a query. The terms of a case-sensitive query are matched against
+/// the identifiers of source code in their original case instead.
+///
+/// # Errors
+///
+/// Returns an error if a pattern is not a valid regular expression, or if
+/// the patterns match more than `PATTERN_MAX_TERMS` terms.
+pub fn expand_patterns(
+    index: &dyn Index,
+    patterns: &[&str],
+    case_sensitive: bool,
+) -> anyhow::Result<Vec<Token>> {
+    if patterns.is_empty() {
+        return Ok(vec![]);
+    }
+    let regexes = patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(&format!("^(?:{pattern})$")).context(format!(
+                "Invalid pattern: {PATTERN_DELIMITER}{pattern}{PATTERN_DELIMITER}"
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut terms = index
+        .terms()
+        .into_iter()
+        .filter(|term| {
+            let word = match case_sensitive {
+                true => term.strip_prefix(CASED_PREFIX),
+                false if term.starts_with(CASED_PREFIX) => None,
+                false => Some(term.strip_prefix(TITLE_PREFIX).unwrap_or(term)),
+            };
+            word.is_some_and(|word| regexes.iter().any(|regex| regex.is_match(word)))
+        })
+        .collect::<Vec<_>>();
+    if terms.len() > PATTERN_MAX_TERMS {
+        anyhow::bail!(
+            "The patterns match {} terms, more than {PATTERN_MAX_TERMS}. Use a narrower pattern.",
+            terms.len()
+        );
+    }
+    terms.sort();
+    Ok(terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_split_patterns() {
+        let (terms, patterns) = split_patterns(r"release /v1\.2\.\d+/ notes / //");
+        assert_eq!(terms, "release notes / //");
+        assert_eq!(patterns, [r"v1\.2\.\d+"]);
+    }
+
+    #[test]
+    fn test_expand_patterns() {
+        let mut index = FrequencyIndex::new();
+        let mut doc_index = DocumentFrequencyIndex::new(0);
+        let tokens = ["v1.2.0", "v1.2.13", "v1.3.0", "^v1.2.0", "=V1.2.0", "release"];
+        doc_index.index_tokens(tokens.map(String::from));
+        index.index(doc_index);
+
+        let terms = expand_patterns(&index, &[r"v1\.2\.\d+"], false).unwrap();
+        assert_eq!(terms, ["^v1.2.0", "v1.2.0", "v1.2.13"]);
+        let terms = expand_patterns(&index, &[r"V1\.2\.\d+"], true).unwrap();
+        assert_eq!(terms, ["=V1.2.0"]);
+        assert!(expand_patterns(&index, &["rel"], false).unwrap().is_empty());
+        assert!(expand_patterns(&index, &["(release"], false).is_err());
+    }
+}
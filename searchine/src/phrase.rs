@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+
+use documents::DocumentId;
+use index::collection::InvertedCollection;
+use index::inverted::Index;
+use index::store::DocumentStore;
+use query::Phrase;
+use tokenize::Token;
+
+use crate::analyzer::AnalyzerConfig;
+
+/// Finds the documents whose text matches every phrase of a query, or
+/// returns `None` if every document matches.
+///
+/// The candidates of a phrase are the intersection of the postings of its
+/// terms, so only the documents that contain all of them are read. The
+/// stored text of each candidate is then tokenized the way it was indexed,
+/// and the positions of its terms are checked for the phrase. Documents
+/// whose text is not in the document store cannot be checked, so they never
+/// match. Phrases without any terms, like phrases of stopwords, match every
+/// document.
+///
+/// # Arguments
+///
+/// * `index`          - The index of the collection.
+/// * `store`          - The store of the texts of the documents.
+/// * `inv_collection` - The paths of the documents, by their IDs.
+/// * `analyzer`       - The analyzer that the documents were indexed with.
+/// * `phrases`        - The phrases of the query.
+/// * `allowed`        - The documents that can match, if not all of them.
+pub fn phrase_matches(
+    index: &dyn Index,
+    store: &DocumentStore,
+    inv_collection: &InvertedCollection,
+    analyzer: &AnalyzerConfig,
+    phrases: &[Phrase],
+    allowed: Option<&HashSet<DocumentId>>,
+) -> Option<HashSet<DocumentId>> {
+    let mut tokenizer = analyzer.tokenizer();
+    let phrases = phrases
+        .iter()
+        .map(|phrase| (tokenizer.tokenize(&phrase.text), phrase.slop))
+        .filter(|(terms, _)| !terms.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut candidates = allowed.cloned();
+    for (terms, _) in &phrases {
+        for term in terms {
+            let doc_ids = index.doc_ids_containing(term);
+            candidates = Some(match candidates {
+                Some(candidates) => doc_ids
+                    .into_iter()
+                    .filter(|doc_id| candidates.contains(doc_id))
+                    .collect(),
+                None => doc_ids.into_iter().collect(),
+            });
+        }
+    }
+    let candidates = candidates?;
+    if phrases.is_empty() {
+        return Some(candidates);
+    }
+
+    let doc_ids = candidates
+        .into_iter()
+        .filter(|doc_id| {
+            let (Some(path), Ok(text)) = (inv_collection.get_path(*doc_id), store.read(*doc_id))
+            else {
+                return false;
+            };
+            let spans = analyzer.document_spans(&mut tokenizer, &text, path, false);
+            let text_terms = spans.iter().map(|(term, _)| term).collect::<Vec<_>>();
+            phrases
+                .iter()
+                .all(|(terms, slop)| contains_phrase(&text_terms, terms, *slop))
+        })
+        .collect();
+    Some(doc_ids)
+}
+
+/// Checks if the terms of a text contain a phrase.
+///
+/// Without slop, the terms of the phrase have to follow each other, in
+/// order. With slop, they can be in any order, within a window of the length
+/// of the phrase plus the slop.
+fn contains_phrase(text_terms: &[&Token], phrase: &[Token], slop: u32) -> bool {
+    if slop == 0 {
+        return text_terms
+            .windows(phrase.len())
+            .any(|window| window.iter().zip(phrase).all(|(a, b)| *a == b));
+    }
+    let mut needed = HashMap::<&Token, usize>::new();
+    for term in phrase {
+        *needed.entry(term).or_default() += 1;
+    }
+    let len = phrase.len() + slop as usize;
+    (0..text_terms.len())
+        .filter(|start| needed.contains_key(text_terms[*start]))
+        .any(|start| {
+            let window = &text_terms[start..(start + len).min(text_terms.len())];
+            needed.iter().all(|(term, count)| {
+                window.iter().filter(|other| **other == *term).count() >= *count
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_phrase() {
+        let text = ["budget", "of", "the", "quarterly", "meeting"].map(String::from);
+        let text_terms = text.iter().collect::<Vec<_>>();
+        let phrase = |words: &[&str]| words.iter().map(|word| word.to_string()).collect::<Vec<_>>();
+
+        assert!(contains_phrase(&text_terms, &phrase(&["the", "quarterly"]), 0));
+        assert!(!contains_phrase(&text_terms, &phrase(&["quarterly", "the"]), 0));
+        assert!(!contains_phrase(&text_terms, &phrase(&["budget", "meeting"]), 0));
+        assert!(!contains_phrase(&text_terms, &phrase(&["budget", "meeting"]), 2));
+        assert!(contains_phrase(&text_terms, &phrase(&["budget", "meeting"]), 3));
+        assert!(contains_phrase(&text_terms, &phrase(&["meeting", "budget"]), 3));
+        assert!(!contains_phrase(&text_terms, &phrase(&["budget", "budget"]), 3));
+        assert!(!contains_phrase(&text_terms, &phrase(&["travel", "budget"]), 5));
+    }
+}
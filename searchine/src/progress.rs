@@ -0,0 +1,39 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// The template of the progress bar of indexing documents, with the number
+/// of documents that are indexed, the throughput, and the time left.
+const BAR_TEMPLATE: &str =
+    "{spinner} [{elapsed_precise}] [{bar:40}] {pos}/{len} documents ({per_sec}, ETA {eta})";
+/// The template of the spinner of listing the files of a directory, whose
+/// number is not known until they are all listed.
+const SPINNER_TEMPLATE: &str = "{spinner} [{elapsed_precise}] {pos} files found ({per_sec})";
+
+/// Creates the progress bar of indexing a number of documents, or a hidden
+/// one if `visible` is false, for scripts.
+///
+/// The bar is drawn on stderr, and only if it is a terminal, so it never
+/// mixes with the output of a command, or with a log it is piped to.
+pub fn documents_bar(len: u64, visible: bool) -> ProgressBar {
+    if !visible {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len);
+    if let Ok(style) = ProgressStyle::with_template(BAR_TEMPLATE) {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    bar
+}
+
+/// Creates the spinner of listing the files of a directory, or a hidden one
+/// if `visible` is false, for scripts. It is drawn like the progress bar of
+/// indexing documents.
+pub fn files_spinner(visible: bool) -> ProgressBar {
+    if !visible {
+        return ProgressBar::hidden();
+    }
+    let spinner = ProgressBar::new_spinner();
+    if let Ok(style) = ProgressStyle::with_template(SPINNER_TEMPLATE) {
+        spinner.set_style(style);
+    }
+    spinner
+}
@@ -0,0 +1,122 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use query::Query;
+use tokenize::Token;
+
+use crate::analyzer::AnalyzerConfig;
+
+/// Scores how close to each other the terms of a query are in the text of a
+/// document, from 0, when they are scattered, to 1, when they follow each
+/// other.
+///
+/// The text is tokenized the way it was indexed, so the position of each
+/// term is its index in the terms of the text, and the shortest window of
+/// positions that contains every distinct term of the query that is in the
+/// text is found. The score is the number of those terms divided by the
+/// length of the window. The terms of the title do not count, and a
+/// document with fewer than two of the terms of the query scores 0, as
+/// there are no terms to be close to each other.
+///
+/// # Arguments
+///
+/// * `text`     - The text of the document.
+/// * `doc_path` - The path to the document, used to determine its type.
+/// * `query`    - The query.
+/// * `analyzer` - The analyzer that the document was indexed with.
+pub fn proximity(
+    text: &str,
+    doc_path: impl AsRef<Path>,
+    query: &Query,
+    analyzer: &AnalyzerConfig,
+) -> f64 {
+    let mut tokenizer = analyzer.tokenizer();
+    let spans = analyzer.document_spans(&mut tokenizer, text, doc_path, query.is_case_sensitive());
+    let positions = spans
+        .iter()
+        .enumerate()
+        .filter(|(_, (token, _))| query.term_count(token) > 0)
+        .map(|(position, (token, _))| (position, token))
+        .collect::<Vec<_>>();
+    match shortest_window(&positions) {
+        Some((n_terms, len)) => n_terms as f64 / len as f64,
+        None => 0.0,
+    }
+}
+
+/// Finds the shortest window of positions that contains every distinct term
+/// of some terms, given in the order of their positions.
+///
+/// # Returns
+///
+/// The number of distinct terms and the length of the window, or `None` if
+/// there are fewer than two distinct terms.
+fn shortest_window(positions: &[(usize, &Token)]) -> Option<(usize, usize)> {
+    let n_terms = positions
+        .iter()
+        .map(|(_, term)| *term)
+        .collect::<HashSet<_>>()
+        .len();
+    if n_terms < 2 {
+        return None;
+    }
+    let mut counts = HashMap::<&Token, usize>::new();
+    let mut shortest = usize::MAX;
+    let mut start = 0;
+    for &(end_position, term) in positions {
+        *counts.entry(term).or_default() += 1;
+        while counts.len() == n_terms {
+            let (start_position, start_term) = positions[start];
+            shortest = shortest.min(end_position - start_position + 1);
+            let count = counts.entry(start_term).or_default();
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(start_term);
+            }
+            start += 1;
+        }
+    }
+    Some((n_terms, shortest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortest_window() {
+        let (budget, meeting, travel) = (
+            "budget".to_string(),
+            "meeting".to_string(),
+            "travel".to_string(),
+        );
+        let positions = [
+            (0, &budget),
+            (10, &meeting),
+            (12, &budget),
+            (30, &travel),
+            (31, &meeting),
+            (32, &budget),
+        ];
+        assert_eq!(shortest_window(&positions), Some((3, 3)));
+        assert_eq!(shortest_window(&positions[..3]), Some((2, 3)));
+        assert_eq!(shortest_window(&[(4, &budget), (9, &budget)]), None);
+        assert_eq!(shortest_window(&[]), None);
+    }
+
+    #[test]
+    fn test_proximity() {
+        let analyzer = AnalyzerConfig::default();
+        let query = analyzer.tokenize_query("budget meeting", false);
+        let close = proximity("The budget meeting is on Monday.", "notes.txt", &query, &analyzer);
+        assert_eq!(close, 1.0);
+        let far = proximity(
+            "The budget is due. We talked about it at length, and the meeting is on Monday.",
+            "notes.txt",
+            &query,
+            &analyzer,
+        );
+        assert!(far > 0.0 && far < close);
+        assert_eq!(proximity("The budget.", "notes.txt", &query, &analyzer), 0.0);
+    }
+}
@@ -0,0 +1,166 @@
+use std::iter;
+
+use index::inverted::Index;
+use query::Query;
+use tokenize::{Token, TITLE_PREFIX};
+
+use crate::analyzer::AnalyzerConfig;
+use crate::config::{BODY_FIELD, TITLE_FIELD};
+use crate::spelling::fuzzy_terms;
+
+/// A step of the relaxation of a query that matches no documents. Each step
+/// loosens the query of the steps before it.
+///
+/// A query matches the documents that have any of its terms, so dropping a
+/// term never matches more documents. The phrases of a query are kept as
+/// they are, and the steps loosen how each term matches instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relaxation {
+    /// The terms of a case-sensitive query match any terms in any case.
+    CaseInsensitive,
+    /// The words that are written as `title:word` or `body:word` match both
+    /// the title and the text.
+    AnyField,
+    /// The words also match the terms of the index that are an edit or two
+    /// away from them.
+    Fuzzy,
+}
+
+impl Relaxation {
+    /// The steps of the relaxation, in the order they are tried.
+    pub const STEPS: [Self; 3] = [Self::CaseInsensitive, Self::AnyField, Self::Fuzzy];
+
+    /// Describes the step, for display.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::CaseInsensitive => "case-insensitive matching",
+            Self::AnyField => "matching in any field",
+            Self::Fuzzy => "fuzzy matching",
+        }
+    }
+}
+
+/// A query that is relaxed, with the terms that it matches.
+#[derive(Debug)]
+pub struct RelaxedQuery {
+    /// The query as it is shown. The words that are matched fuzzily are
+    /// followed by the terms they match, separated by `|`.
+    pub text: String,
+    pub case_sensitive: bool,
+    pub terms: Query,
+}
+
+impl RelaxedQuery {
+    /// Creates a query that is not relaxed yet.
+    pub fn new(analyzer: &AnalyzerConfig, text: &str, case_sensitive: bool) -> Self {
+        Self {
+            text: text.to_string(),
+            case_sensitive,
+            terms: analyzer.tokenize_query(text, case_sensitive),
+        }
+    }
+
+    /// Relaxes the query by one more step, or returns `None` if the step does
+    /// not change the query. The vocabulary of the index is only read for
+    /// fuzzy matching.
+    pub fn relax(
+        &self,
+        index: &dyn Index,
+        analyzer: &AnalyzerConfig,
+        step: Relaxation,
+    ) -> Option<Self> {
+        match step {
+            Relaxation::CaseInsensitive => self
+                .case_sensitive
+                .then(|| Self::new(analyzer, &self.text, false)),
+            Relaxation::AnyField => {
+                let text = self
+                    .text
+                    .split_whitespace()
+                    .map(|word| {
+                        [TITLE_FIELD, BODY_FIELD]
+                            .into_iter()
+                            .find_map(|field| word.strip_prefix(field))
+                            .unwrap_or(word)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                (text != self.text).then(|| Self::new(analyzer, &text, self.case_sensitive))
+            }
+            Relaxation::Fuzzy if self.case_sensitive => None,
+            Relaxation::Fuzzy => self.fuzzy(index, analyzer),
+        }
+    }
+
+    /// Adds the terms of the index that are close to the words of the query
+    /// to its terms, both in the text and in the title.
+    fn fuzzy(&self, index: &dyn Index, analyzer: &AnalyzerConfig) -> Option<Self> {
+        let vocabulary = index.terms();
+        let mut tokenizer = analyzer.tokenizer();
+        let mut tokens = self
+            .terms
+            .iter()
+            .flat_map(|(term, count)| iter::repeat_n(term.clone(), *count as usize))
+            .collect::<Vec<Token>>();
+        let mut words = vec![];
+        let mut is_relaxed = false;
+        for word in self.text.split_whitespace() {
+            let mut fuzzy = tokenizer.tokenize(word);
+            fuzzy.extend(tokenizer.tokenize_title(word));
+            let fuzzy = fuzzy
+                .iter()
+                .flat_map(|token| fuzzy_terms(&vocabulary, token))
+                .collect::<Vec<_>>();
+            let shown = fuzzy
+                .iter()
+                .filter(|term| !term.starts_with(TITLE_PREFIX))
+                .map(String::as_str);
+            words.push(iter::once(word).chain(shown).collect::<Vec<_>>().join("|"));
+            is_relaxed |= !fuzzy.is_empty();
+            tokens.extend(fuzzy);
+        }
+        is_relaxed.then(|| Self {
+            text: words.join(" "),
+            case_sensitive: false,
+            terms: Query::from(tokens),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use crate::analyzer::Stemmer;
+
+    use super::*;
+
+    #[test]
+    fn test_relax() {
+        let mut index = FrequencyIndex::new();
+        let mut doc_index = DocumentFrequencyIndex::new(0);
+        doc_index.index_tokens(["budget", "report"].map(String::from));
+        index.index(doc_index);
+        let analyzer = AnalyzerConfig {
+            stemmer: Stemmer::None,
+            ..Default::default()
+        };
+
+        let query = RelaxedQuery::new(&analyzer, "title:budjet", false);
+        assert!(query
+            .relax(&index, &analyzer, Relaxation::CaseInsensitive)
+            .is_none());
+        let query = query
+            .relax(&index, &analyzer, Relaxation::AnyField)
+            .unwrap();
+        assert_eq!(query.text, "budjet");
+        let query = query.relax(&index, &analyzer, Relaxation::Fuzzy).unwrap();
+        assert_eq!(query.text, "budjet|budget");
+        assert_eq!(query.terms.term_count(&"budget".to_string()), 1);
+        assert_eq!(query.terms.term_count(&"budjet".to_string()), 1);
+
+        let query = RelaxedQuery::new(&analyzer, "xylophone", false);
+        assert!(query.relax(&index, &analyzer, Relaxation::Fuzzy).is_none());
+    }
+}
@@ -0,0 +1,102 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use anyhow::Context;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::config::CONFIG_FILENAME;
+use crate::settings::Settings;
+
+/// A watcher of the config file of a repository, so that long-running
+/// commands, like `serve` and `watch`, apply the changes of the config
+/// without being restarted.
+///
+/// The directory of the repository is watched, rather than the config file,
+/// because editors often save a file by replacing it with a new one.
+pub struct ConfigWatcher {
+    repo_dir: PathBuf,
+    receiver: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Starts watching the config file of a repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory of the repository cannot be watched.
+    pub fn new(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let repo_dir = repo_dir.as_ref().to_path_buf();
+        let (sender, receiver) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher
+            .watch(&repo_dir, RecursiveMode::NonRecursive)
+            .context(format!("Failed to watch: {}", repo_dir.display()))?;
+        Ok(Self {
+            repo_dir,
+            receiver,
+            _watcher: watcher,
+        })
+    }
+
+    /// Checks if the config file has changed since it was last checked,
+    /// without waiting.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(event) => {
+                    changed |= event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == Some(OsStr::new(CONFIG_FILENAME)));
+                }
+                Err(err) => eprintln!("ERROR: Failed to watch the config because: {}", err),
+            }
+        }
+        changed
+    }
+
+    /// Loads the settings of the repository again, if its config file has
+    /// changed, and reports which sections have changed.
+    ///
+    /// The changes of sections that only take effect once the repository is
+    /// indexed again, like the analyzer, are reported as such, and it is up
+    /// to the command to keep using the settings that it has for them. If
+    /// the config file cannot be loaded, like while it is being edited, the
+    /// error is reported, and `None` is returned, so the current settings
+    /// are kept.
+    pub fn reload(&self, current: &Settings) -> Option<Settings> {
+        if !self.changed() {
+            return None;
+        }
+        let settings = match Settings::load(&self.repo_dir) {
+            Ok(settings) => settings,
+            Err(err) => {
+                eprintln!("ERROR: Failed to reload the config, so it is unchanged: {err:#}");
+                return None;
+            }
+        };
+        let changes = current.changes(&settings);
+        if changes.is_empty() {
+            return None;
+        }
+        if !changes.live.is_empty() {
+            let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x94, 0x84]).unwrap_or_default();
+            println_bold!(
+                "{emoji} Reloaded the config. Applied the changes of: [{}]",
+                changes.live.join("], [")
+            );
+        }
+        if !changes.reindex.is_empty() {
+            println_red!(
+                "The changes of [{}] require indexing the repository again. \
+                 Run `searchine index --full` to apply them.",
+                changes.reindex.join("], [")
+            );
+        }
+        Some(settings)
+    }
+}
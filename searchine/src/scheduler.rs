@@ -0,0 +1,524 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, sleep};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use index::compress::Compression;
+use index::inverted::freq::FrequencyIndex;
+
+use crate::cli::utils::{
+    index_format, load_manifest, pick_deleted_segment, pick_small_segments, replace_segments,
+    segment_size, segments_dir,
+};
+use crate::cli::IndexFormat;
+use crate::config::{
+    LOCK_POLL_MILLIS, MERGE_CONCURRENCY, MERGE_IO_MB_PER_SEC, MERGE_STATUS_FILENAME,
+    THROTTLE_CHUNK_BYTES,
+};
+use crate::lock::RepoLock;
+use crate::shutdown;
+
+/// The extension of the files of segments that are being merged in the
+/// background.
+pub const MERGING_EXTENSION: &str = "merging";
+
+/// The configuration of the merges of segments in the background, while a
+/// repository is watched, read from the `[merge]` section of the config of
+/// a repository.
+///
+/// ```toml
+/// [merge]
+/// concurrency = 2
+/// io_mb_per_sec = 64
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MergeConfig {
+    /// The number of merges that may run at the same time. It is 1 by
+    /// default.
+    pub concurrency: usize,
+    /// The limit of the rate at which the merges read and write segments, in
+    /// MiB per second, shared by all the merges, or 0 for no limit. It is 32
+    /// by default.
+    pub io_mb_per_sec: u32,
+}
+
+impl Default for MergeConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: MERGE_CONCURRENCY,
+            io_mb_per_sec: MERGE_IO_MB_PER_SEC,
+        }
+    }
+}
+
+/// A merge that is running in the background.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunningMerge {
+    /// The file names of the segments that are merged.
+    pub segments: Vec<String>,
+    /// The total size of the segments, in bytes.
+    pub bytes: u64,
+    /// The time the merge started, in seconds since the Unix epoch.
+    pub started: u64,
+}
+
+/// The status of the merges in the background of a watched repository. It
+/// is kept in the repository, so that `searchine status` can show it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergeStatus {
+    /// The ID of the process that runs the merges.
+    pub pid: u32,
+    /// The merges that are running.
+    pub running: Vec<RunningMerge>,
+    /// The number of merges that have completed.
+    pub completed: u64,
+    /// The number of merges that were dropped, because their segments were
+    /// replaced while they were merged.
+    pub abandoned: u64,
+    /// The number of merges that have failed.
+    pub failed: u64,
+    /// The error of the last merge that failed.
+    pub last_error: Option<String>,
+}
+
+impl MergeStatus {
+    /// Loads the status of the merges of a repository, if the process that
+    /// runs them is still running.
+    pub fn load(repo_dir: impl AsRef<Path>) -> Option<Self> {
+        let path = repo_dir.as_ref().join(MERGE_STATUS_FILENAME);
+        let contents = fs::read_to_string(path).ok()?;
+        let status: Self = serde_json::from_str(&contents).ok()?;
+        is_running(status.pid).then_some(status)
+    }
+
+    /// Writes the status of the merges to the repository.
+    fn save(&self, repo_dir: &Path) -> anyhow::Result<()> {
+        let path = repo_dir.join(MERGE_STATUS_FILENAME);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?).context(format!(
+            "Failed to write merge status: {}",
+            tmp_path.display()
+        ))?;
+        fs::rename(&tmp_path, &path)
+            .context(format!("Failed to write merge status: {}", path.display()))
+    }
+}
+
+/// Checks if a process is running. On Unix, the process is signalled with
+/// the null signal, which only checks that it exists, so a process of
+/// another user, which cannot be signalled, is running too. On other
+/// platforms, the process is assumed to be running.
+fn is_running(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        let pid = match libc::pid_t::try_from(pid) {
+            Ok(pid) if pid > 0 => pid,
+            _ => return false,
+        };
+        // SAFETY: `kill` with the null signal sends no signal, and only
+        // checks that the process exists and can be signalled.
+        let result = unsafe { libc::kill(pid, 0) };
+        result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        true
+    }
+}
+
+/// A limit of the rate of reads and writes, shared by the merges, so that
+/// they never saturate the disk.
+///
+/// Each read or write takes its time at the rate of the limit after the time
+/// that was taken before it, and waits until then. A throttle that has been
+/// idle does not save up time for a burst.
+pub struct Throttle {
+    bytes_per_sec: AtomicU64,
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    /// Creates a throttle with a limit in bytes per second, or without a
+    /// limit if it is 0.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: AtomicU64::new(bytes_per_sec),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Changes the limit of the throttle, in bytes per second, or removes
+    /// it if it is 0.
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        self.bytes_per_sec.store(bytes_per_sec, Ordering::Relaxed);
+    }
+
+    /// Takes the time of a number of bytes, and waits until it is over.
+    pub fn consume(&self, bytes: usize) {
+        let bytes_per_sec = self.bytes_per_sec.load(Ordering::Relaxed);
+        if bytes_per_sec == 0 || bytes == 0 {
+            return;
+        }
+        let duration = Duration::from_secs_f64(bytes as f64 / bytes_per_sec as f64);
+        let until = {
+            let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+            *next = (*next).max(Instant::now()) + duration;
+            *next
+        };
+        sleep(until.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// A reader or a writer whose bytes go through a throttle, in chunks that
+/// are small enough to keep the rate steady. Its reads and writes fail once
+/// the process is asked to stop, so merges do not hold up the shutdown.
+struct Throttled<'a, T> {
+    inner: T,
+    throttle: &'a Throttle,
+}
+
+impl<T: Read> Read for Throttled<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if shutdown::requested() {
+            return Err(io::Error::other("Interrupted."));
+        }
+        let len = buf.len().min(THROTTLE_CHUNK_BYTES);
+        let n = self.inner.read(&mut buf[..len])?;
+        self.throttle.consume(n);
+        Ok(n)
+    }
+}
+
+impl<T: Write> Write for Throttled<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if shutdown::requested() {
+            return Err(io::Error::other("Interrupted."));
+        }
+        let len = buf.len().min(THROTTLE_CHUNK_BYTES);
+        let n = self.inner.write(&buf[..len])?;
+        self.throttle.consume(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The scheduler of the merges of the segments of a repository in the
+/// background, while it is watched.
+///
+/// After every update of the index, the smallest segments that are not
+/// already being merged are picked, the same way as they are after updates
+/// in the foreground, and they are merged in a thread, as long as there are
+/// fewer merges running than the concurrency. The segments are read and the
+/// merged segment is written without holding the lock of the repository, so
+/// that merges never block queries or updates. The lock is only held to
+/// replace the segments with the merged one in the manifest.
+///
+/// Once there are no segments to merge, a segment with a large share of
+/// deleted documents is merged alone, which compacts it, so the space of
+/// the documents that are removed while the repository is watched is
+/// reclaimed without `searchine compact`.
+#[derive(Clone)]
+pub struct MergeScheduler {
+    repo_dir: PathBuf,
+    concurrency: Arc<AtomicUsize>,
+    throttle: Arc<Throttle>,
+    status: Arc<Mutex<MergeStatus>>,
+}
+
+impl MergeScheduler {
+    /// Creates the scheduler of the merges of a repository. The files of
+    /// merges that were interrupted, when a previous scheduler was stopped,
+    /// are removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files of interrupted merges cannot be removed,
+    /// or if the merge status cannot be written.
+    pub fn new(repo_dir: impl AsRef<Path>, config: MergeConfig) -> anyhow::Result<Self> {
+        let repo_dir = repo_dir.as_ref().to_path_buf();
+        remove_interrupted_merges(&segments_dir(&repo_dir))?;
+        let status = MergeStatus {
+            pid: std::process::id(),
+            ..MergeStatus::default()
+        };
+        status.save(&repo_dir)?;
+        Ok(Self {
+            repo_dir,
+            concurrency: Arc::new(AtomicUsize::new(config.concurrency.max(1))),
+            throttle: Arc::new(Throttle::new(bytes_per_sec(config))),
+            status: Arc::new(Mutex::new(status)),
+        })
+    }
+
+    /// Starts merges of the segments of the repository, while there are
+    /// enough segments to merge, and fewer merges running than the
+    /// concurrency.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest cannot be read, or if the merge
+    /// status cannot be written.
+    pub fn schedule(&self) -> anyhow::Result<()> {
+        let mut status = self.status.lock().unwrap_or_else(PoisonError::into_inner);
+        let segments_dir = segments_dir(&self.repo_dir);
+        while !shutdown::requested()
+            && status.running.len() < self.concurrency.load(Ordering::Relaxed)
+        {
+            let manifest = load_manifest(&self.repo_dir)?;
+            let idle = manifest
+                .segments()
+                .iter()
+                .filter(|name| {
+                    !status
+                        .running
+                        .iter()
+                        .any(|merge| merge.segments.contains(name))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            // A segment with many deleted documents is compacted by merging
+            // it alone, once no segments are left to merge.
+            let Some(segments) = pick_small_segments(&self.repo_dir, idle.clone()).or_else(|| {
+                pick_deleted_segment(&self.repo_dir, &manifest, &idle).map(|name| vec![name])
+            }) else {
+                break;
+            };
+            let bytes = segments
+                .iter()
+                .map(|name| segment_size(segments_dir.join(name)))
+                .sum();
+            status.running.push(RunningMerge {
+                segments: segments.clone(),
+                bytes,
+                started: unix_time(),
+            });
+            let scheduler = self.clone();
+            thread::spawn(move || scheduler.run(segments));
+        }
+        status.save(&self.repo_dir)
+    }
+
+    /// Changes the concurrency and the limit of the rate of the merges. The
+    /// merges that are running keep going, and their reads and writes are
+    /// limited by the new rate from now on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if more merges can run, but they cannot be scheduled.
+    pub fn reconfigure(&self, config: MergeConfig) -> anyhow::Result<()> {
+        self.concurrency
+            .store(config.concurrency.max(1), Ordering::Relaxed);
+        self.throttle.set_rate(bytes_per_sec(config));
+        self.schedule()
+    }
+
+    /// Waits for the merges that are running to stop, once the process has
+    /// been asked to stop, and removes the merge status from the repository.
+    /// The merges that are replacing their segments finish, and the others
+    /// are dropped.
+    pub fn stop(&self) {
+        loop {
+            let status = self.status.lock().unwrap_or_else(PoisonError::into_inner);
+            if status.running.is_empty() {
+                break;
+            }
+            drop(status);
+            sleep(Duration::from_millis(LOCK_POLL_MILLIS));
+        }
+        let path = self.repo_dir.join(MERGE_STATUS_FILENAME);
+        if let Err(err) = fs::remove_file(&path) {
+            eprintln!(
+                "ERROR: Failed to remove merge status {}: {err}",
+                path.display()
+            );
+        }
+    }
+
+    /// Runs a merge, and records how it went in the status. If the merge
+    /// succeeded, more merges are scheduled, as the merged segment may have
+    /// to be merged in turn.
+    fn run(&self, segments: Vec<String>) {
+        let result = merge_in_background(&self.repo_dir, &segments, &self.throttle);
+        let mut status = self.status.lock().unwrap_or_else(PoisonError::into_inner);
+        status.running.retain(|merge| merge.segments != segments);
+        match result {
+            Ok(true) => {
+                status.completed += 1;
+                let emoji = String::from_utf8(vec![0xF0, 0x9F, 0x93, 0x8B]).unwrap_or_default();
+                match segments.len() {
+                    1 => println_bold!("{emoji} Compacted a segment in the background."),
+                    n => println_bold!("{emoji} Merged {n} segments in the background."),
+                }
+            }
+            Ok(false) => status.abandoned += 1,
+            Err(_) if shutdown::requested() => status.abandoned += 1,
+            Err(err) => {
+                eprintln!("ERROR: Failed to merge segments because: {err:#}");
+                status.failed += 1;
+                status.last_error = Some(format!("{err:#}"));
+                if let Err(err) = status.save(&self.repo_dir) {
+                    eprintln!("ERROR: {err:#}");
+                }
+                return;
+            }
+        }
+        drop(status);
+        if let Err(err) = self.schedule() {
+            eprintln!("ERROR: Failed to schedule merges because: {err:#}");
+        }
+    }
+}
+
+/// Merges segments of the index of a repository into a file next to them,
+/// at the rate of a throttle, without their deleted documents, and then
+/// replaces them with it.
+///
+/// # Returns
+///
+/// Whether the segments were replaced. They are not if any of them was
+/// replaced while they were merged, or if documents were deleted from it,
+/// like by an update that removed them.
+fn merge_in_background(
+    repo_dir: &Path,
+    segments: &[String],
+    throttle: &Throttle,
+) -> anyhow::Result<bool> {
+    let segments_dir = segments_dir(repo_dir);
+    let manifest = load_manifest(repo_dir)?;
+    let deleted = segments
+        .iter()
+        .map(|name| manifest.deleted(name).cloned())
+        .collect::<Vec<_>>();
+    let mut merged = FrequencyIndex::new();
+    for (name, deleted) in segments.iter().zip(&deleted) {
+        let path = segments_dir.join(name);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => {
+                return Err(err).context(format!("Failed to open segment: {}", path.display()))
+            }
+        };
+        let mut bytes = vec![];
+        Throttled {
+            inner: file,
+            throttle,
+        }
+        .read_to_end(&mut bytes)
+        .context(format!("Failed to read segment: {}", path.display()))?;
+        let mut segment = FrequencyIndex::from_bytes(bytes)?;
+        for doc_id in deleted.iter().flatten() {
+            segment.remove(*doc_id);
+        }
+        merged.merge(segment);
+    }
+
+    let format = index_format(repo_dir);
+    let compression = manifest.compression();
+    let merged_path = segments_dir.join(format!("{}.{MERGING_EXTENSION}", segments[0]));
+    let file = File::create(&merged_path).context(format!(
+        "Failed to create segment: {}",
+        merged_path.display()
+    ))?;
+    let mut writer = BufWriter::new(Throttled {
+        inner: file,
+        throttle,
+    });
+    let written = match (format, compression) {
+        (IndexFormat::Binary, Compression::None) => {
+            merged.write_ordered_to(writer, manifest.postings_order())
+        }
+        (IndexFormat::Json, Compression::None) => merged.write_json_to(writer),
+        // Compressed segments are compressed as a whole, before they are
+        // written.
+        (format, compression) => {
+            let mut bytes = vec![];
+            match format {
+                IndexFormat::Binary => merged.write_to(&mut bytes),
+                IndexFormat::Json => merged.write_json_to(&mut bytes),
+            }
+            .and_then(|()| compression.compress(bytes))
+            .and_then(|bytes| writer.write_all(&bytes))
+            .and_then(|()| writer.flush())
+        }
+    };
+    if let Err(err) = written {
+        let _ = fs::remove_file(&merged_path);
+        return Err(err.into());
+    }
+
+    let _lock = RepoLock::acquire_waiting(repo_dir)?;
+    replace_segments(repo_dir, segments, &deleted, &merged_path, format)
+}
+
+/// Returns the limit of the rate of the merges of a config, in bytes per
+/// second.
+fn bytes_per_sec(config: MergeConfig) -> u64 {
+    config.io_mb_per_sec as u64 * 1024 * 1024
+}
+
+/// Removes the files of merges that were interrupted from the segments
+/// directory of a repository.
+fn remove_interrupted_merges(segments_dir: &Path) -> anyhow::Result<()> {
+    if !segments_dir.exists() {
+        return Ok(());
+    }
+    let entries = fs::read_dir(segments_dir).context(format!(
+        "Failed to read segments directory: {}",
+        segments_dir.display()
+    ))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == MERGING_EXTENSION)
+        {
+            fs::remove_file(&path)
+                .context(format!("Failed to remove segment: {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle() {
+        let throttle = Throttle::new(1000);
+        let start = Instant::now();
+        let mut writer = Throttled {
+            inner: vec![],
+            throttle: &throttle,
+        };
+        writer.write_all(&[0; 100]).unwrap();
+        writer.write_all(&[0; 100]).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(writer.inner.len(), 200);
+
+        let start = Instant::now();
+        Throttle::new(0).consume(1_000_000);
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
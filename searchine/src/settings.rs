@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use embed::EmbedderConfig;
+use query::RewriteConfig;
+
+use crate::analyzer::AnalyzerConfig;
+use crate::audit::AuditConfig;
+use crate::cache::CacheConfig;
+use crate::cli::index::{ExtractionConfig, IndexConfig};
+use crate::cli::search::{ProfileConfig, SearchConfig};
+use crate::cli::semantic::VectorConfig;
+use crate::cli::serve::ServeConfig;
+use crate::config::CONFIG_FILENAME;
+use crate::error::Error;
+use crate::fs::dir::WalkConfig;
+use crate::fs::PathFilter;
+use crate::passage::PassageConfig;
+use crate::scheduler::MergeConfig;
+use crate::snippet::SnippetConfig;
+
+/// The settings of a repository, read from the `config.toml` file in the
+/// repository, which `searchine init` creates. Settings that are missing
+/// from the file, or the whole file, take their default values.
+///
+/// ```toml
+/// [search]
+/// scorer = "bm25"
+/// top_n = 20
+///
+/// [profiles.recent-first]
+/// recency_half_life_days = 14
+///
+/// [snippet]
+/// length = 200
+/// fragments = 2
+/// separator = " ... "
+/// boundary = "sentence"
+///
+/// [passage]
+/// mode = "window"
+/// size = 100
+/// stride = 50
+/// per_document = 1
+///
+/// [embedder]
+/// provider = "http"
+/// url = "http://localhost:11434/v1/embeddings"
+/// model = "nomic-embed-text"
+///
+/// [vectors]
+/// quantization = "scalar"
+///
+/// [rewrite]
+/// command = ["python3", "/path/to/rewrite.py"]
+///
+/// [extraction]
+/// redact_secrets = true
+///
+/// [extraction.rows]
+/// "exports/*.csv" = ["name", "notes"]
+///
+/// [extraction.commands]
+/// "*.djvu" = "djvutxt {path}"
+///
+/// [index]
+/// tf_weighting = "log"
+/// compression = "zstd"
+/// postings_order = "impact"
+/// memory_budget_mb = 2048
+///
+/// [analyzer]
+/// stemmer = "english"
+/// stopwords = ["the", "a", "of"]
+/// tokenizer = "auto"
+/// code_extensions = ["rs", "py", "sql"]
+/// segmentation = "unicode"
+/// compounds = "both"
+/// strip_possessives = false
+/// drop_numbers = false
+/// cased_identifiers = true
+/// cased_terms = false
+///
+/// [filter]
+/// include = ["**/*.rs", "**/*.md"]
+/// exclude = ["target/**"]
+///
+/// [walk]
+/// symlinks = "follow"
+///
+/// [serve.keys]
+/// "key-of-team-a" = ["projects/a", "shared"]
+///
+/// [audit]
+/// enabled = true
+///
+/// [cache]
+/// enabled = true
+/// persist = true
+///
+/// [merge]
+/// concurrency = 2
+/// io_mb_per_sec = 64
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub search: SearchConfig,
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    pub snippet: SnippetConfig,
+    pub passage: PassageConfig,
+    pub embedder: Option<EmbedderConfig>,
+    pub vectors: VectorConfig,
+    pub rewrite: RewriteConfig,
+    pub extraction: ExtractionConfig,
+    pub index: IndexConfig,
+    pub analyzer: AnalyzerConfig,
+    pub filter: PathFilter,
+    pub walk: WalkConfig,
+    pub serve: ServeConfig,
+    pub audit: AuditConfig,
+    pub cache: CacheConfig,
+    pub merge: MergeConfig,
+}
+
+impl Settings {
+    /// Loads the settings of a repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::InvalidConfig`] error if the config file exists,
+    /// but it cannot be read or parsed.
+    pub fn load(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = repo_dir.as_ref().join(CONFIG_FILENAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let invalid = |reason: String| Error::InvalidConfig {
+            path: path.clone(),
+            reason,
+        };
+        let contents = std::fs::read_to_string(&path).map_err(|err| invalid(format!("{err}.")))?;
+        Ok(toml::from_str(&contents)
+            .map_err(|err| invalid(err.to_string().trim_end().to_string()))?)
+    }
+
+    /// Returns the search config, with a ranking profile applied if one is
+    /// given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NotConfigured`] error if the profile is not in the
+    /// config.
+    pub fn search_config(&self, profile: Option<&str>) -> anyhow::Result<SearchConfig> {
+        let Some(name) = profile else {
+            return Ok(self.search.clone());
+        };
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.apply(self.search.clone())),
+            None => Err(Error::NotConfigured {
+                reason: format!(
+                    "Ranking profile \"{name}\" does not exist. Add a [profiles.{name}] section \
+                     to .searchine/config.toml."
+                ),
+            }
+            .into()),
+        }
+    }
+
+    /// Finds the sections of the config that are different in other
+    /// settings, like the settings of a config file that has been edited.
+    pub fn changes(&self, other: &Self) -> SettingsChanges {
+        let live = [
+            ("search", self.search != other.search),
+            ("profiles", self.profiles != other.profiles),
+            ("snippet", self.snippet != other.snippet),
+            ("passage", self.passage != other.passage),
+            ("rewrite", self.rewrite != other.rewrite),
+            ("serve", self.serve != other.serve),
+            ("audit", self.audit != other.audit),
+            ("cache", self.cache != other.cache),
+            ("merge", self.merge != other.merge),
+        ];
+        let reindex = [
+            ("analyzer", self.analyzer != other.analyzer),
+            ("extraction", self.extraction != other.extraction),
+            ("index", self.index != other.index),
+            ("filter", self.filter != other.filter),
+            ("walk", self.walk != other.walk),
+            ("embedder", self.embedder != other.embedder),
+            ("vectors", self.vectors != other.vectors),
+        ];
+        let changed = |sections: &[(&'static str, bool)]| {
+            sections
+                .iter()
+                .filter(|(_, changed)| *changed)
+                .map(|(section, _)| *section)
+                .collect()
+        };
+        SettingsChanges {
+            live: changed(&live),
+            reindex: changed(&reindex),
+        }
+    }
+}
+
+/// The sections of the config that are different between two settings.
+#[derive(Debug, Default, PartialEq)]
+pub struct SettingsChanges {
+    /// The sections whose changes take effect without indexing the
+    /// repository again, like the number of results of a search.
+    pub live: Vec<&'static str>,
+    /// The sections whose changes only take effect for all the documents once
+    /// the repository is indexed again, like the analyzer.
+    pub reindex: Vec<&'static str>,
+}
+
+impl SettingsChanges {
+    /// Checks if no section has changed.
+    pub fn is_empty(&self) -> bool {
+        self.live.is_empty() && self.reindex.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::Stemmer;
+    use crate::cli::search::Scorer;
+
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let settings = Settings::default();
+        assert!(settings.changes(&settings.clone()).is_empty());
+
+        let mut edited = settings.clone();
+        edited.search.top_n = 20;
+        edited.merge.concurrency = 2;
+        edited.analyzer.stemmer = Stemmer::None;
+        let changes = settings.changes(&edited);
+        assert_eq!(changes.live, ["search", "merge"]);
+        assert_eq!(changes.reindex, ["analyzer"]);
+    }
+
+    #[test]
+    fn test_search_config() {
+        let mut settings = Settings::default();
+        settings.search.scorer = Scorer::Bm25;
+        let profile = ProfileConfig {
+            title_boost: Some(5.0),
+            recency_half_life_days: Some(14.0),
+            link_weight: Some(0.5),
+            ..Default::default()
+        };
+        settings.profiles.insert("recent".to_string(), profile);
+
+        assert_eq!(settings.search_config(None).unwrap(), settings.search);
+        let search = settings.search_config(Some("recent")).unwrap();
+        assert_eq!(search.scorer, Scorer::Bm25);
+        assert_eq!(search.title_boost, 5.0);
+        assert_eq!(search.recency_half_life_days, Some(14.0));
+        assert_eq!(search.link_weight, 0.5);
+        assert_eq!(search.top_n, settings.search.top_n);
+        assert!(settings.search_config(Some("code")).is_err());
+    }
+}
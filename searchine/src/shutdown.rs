@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Context;
+
+use crate::error::Error;
+
+/// Whether the process has been asked to stop, by a signal.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Handles the signals that stop the process, like `SIGINT` from Ctrl-C and
+/// `SIGTERM`, in the commands that change the index or run until they are
+/// stopped.
+///
+/// The first signal only requests a shutdown, so that the command finishes
+/// or rolls back the change that it is making, stops its threads, and
+/// returns, which releases the lock of the repository. A second signal exits
+/// at once, and the operating system releases the lock.
+///
+/// # Errors
+///
+/// Returns an error if the handler cannot be installed.
+pub fn install() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            eprintln!("Interrupted again. Exiting without cleaning up.");
+            std::process::exit(130);
+        }
+        eprintln!("Interrupted. Stopping cleanly, interrupt again to exit now.");
+    })
+    .context("Failed to install the signal handler.")
+}
+
+/// Checks if the process has been asked to stop.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Returns an error if the process has been asked to stop, so that a change
+/// of the index stops before it is committed.
+///
+/// # Errors
+///
+/// Returns [`Error::Interrupted`] if the process has been asked to stop.
+pub fn check() -> anyhow::Result<()> {
+    if requested() {
+        return Err(Error::Interrupted.into());
+    }
+    Ok(())
+}
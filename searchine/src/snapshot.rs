@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use index::collection::InvertedCollection;
+use index::inverted::Index;
+
+use crate::cli::utils::{load_manifest, open_segments};
+use crate::config::{COLLECTION_FILENAME, SNAPSHOT_ATTEMPTS};
+use crate::lock::RepoLock;
+
+/// The index and the collection of a repository, as they were at one
+/// generation of the index.
+///
+/// A query that runs for a while reads its index from a snapshot, so that a
+/// command that changes the repository at the same time cannot swap the
+/// files of the index out from under it. The segments of the snapshot are
+/// memory-mapped or loaded when it is opened, and a segment that is replaced
+/// afterwards is only unlinked, so the snapshot keeps reading the segment
+/// that it opened. The collection is loaded when the snapshot is opened too.
+///
+/// The stored texts of the documents are read when they are needed, so a
+/// document that is modified during the query is shown as it is now.
+pub struct Snapshot {
+    pub index: Box<dyn Index>,
+    pub inv_collection: InvertedCollection,
+    /// The generation of the index that the snapshot is of.
+    pub generation: u64,
+}
+
+impl Snapshot {
+    /// Opens a snapshot of the latest generation of the index of a
+    /// repository.
+    ///
+    /// The generation of the index is read before and after the files are
+    /// opened. If it has changed in between, or another command is changing
+    /// the repository, the files may be of different generations, so they are
+    /// opened again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the repository has not been indexed, if a file
+    /// cannot be read, or if the index keeps changing while it is opened.
+    pub fn open(repo_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let repo_dir = repo_dir.as_ref();
+        let mut attempt = 1;
+        loop {
+            RepoLock::wait(repo_dir)?;
+            match Self::try_open(repo_dir) {
+                Ok(Some(snapshot)) => return Ok(snapshot),
+                // A segment that was listed in the manifest may have been
+                // removed by the time it was opened.
+                Ok(None) | Err(_) if attempt < SNAPSHOT_ATTEMPTS => attempt += 1,
+                Ok(None) => anyhow::bail!(
+                    "Index kept changing while it was being opened. Try again once it is updated."
+                ),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Opens the files of the latest generation of the index, or returns
+    /// `None` if the index changed while they were opened.
+    fn try_open(repo_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let manifest = load_manifest(repo_dir)?;
+        let index = open_segments(repo_dir, &manifest)?;
+        let inv_collection = InvertedCollection::from_file(repo_dir.join(COLLECTION_FILENAME))?;
+        let changed = load_manifest(repo_dir)?.generation() != manifest.generation();
+        if changed || RepoLock::is_held(repo_dir) {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            index: Box::new(index),
+            inv_collection,
+            generation: manifest.generation(),
+        }))
+    }
+}
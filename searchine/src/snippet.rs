@@ -0,0 +1,313 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::highlight::{highlight_with, offsets, Marker, Offsets};
+
+/// The rule that decides where the fragments of a snippet start and end.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetBoundary {
+    /// Fragments are windows of fixed length, cut at word boundaries.
+    #[default]
+    Window,
+    /// Fragments start and end at sentence boundaries where possible, which
+    /// suits prose better.
+    Sentence,
+}
+
+/// The policy for excerpting snippets from the documents.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SnippetConfig {
+    /// The maximum length of each fragment in bytes.
+    pub length: usize,
+    /// The maximum number of fragments per document.
+    pub fragments: usize,
+    /// The separator that joins the fragments when they are displayed.
+    pub separator: String,
+    /// The rule for the boundaries of the fragments.
+    pub boundary: SnippetBoundary,
+}
+
+impl Default for SnippetConfig {
+    fn default() -> Self {
+        Self {
+            length: 160,
+            fragments: 1,
+            separator: " ... ".to_string(),
+            boundary: SnippetBoundary::default(),
+        }
+    }
+}
+
+/// A fragment of a document's text around its matches.
+///
+/// The snippet keeps its own offsets in the text of the document, and the
+/// offsets of the matches it contains relative to its own text, so clients
+/// can highlight either the snippet or the full document. The text is also
+/// kept with its matches wrapped in `<mark>` tags, for clients that render
+/// it as it is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snippet {
+    pub text: String,
+    pub highlighted: String,
+    pub offsets: Offsets,
+    pub matches: Vec<Offsets>,
+}
+
+impl Snippet {
+    /// Creates a snippet from a window of a text, keeping the matches that
+    /// fall in the window.
+    pub fn from_window(text: &str, matches: &[Range<usize>], window: Range<usize>) -> Self {
+        let snippet_matches = matches
+            .iter()
+            .filter(|span| span.start >= window.start && span.end <= window.end)
+            .map(|span| span.start - window.start..span.end - window.start)
+            .collect::<Vec<_>>();
+        let snippet_text = &text[window.clone()];
+
+        Self {
+            text: snippet_text.to_string(),
+            highlighted: highlight_with(snippet_text, &snippet_matches, Marker::Tags),
+            offsets: offsets(text, &[window]).remove(0),
+            matches: offsets(snippet_text, &snippet_matches),
+        }
+    }
+
+    /// Returns the text of the snippet with its matches marked.
+    pub fn highlight(&self, marker: Marker) -> String {
+        let matches = self
+            .matches
+            .iter()
+            .map(|offsets| offsets.byte_start..offsets.byte_end)
+            .collect::<Vec<_>>();
+        highlight_with(&self.text, &matches, marker)
+    }
+}
+
+/// Excerpts the snippet of a text, made of its best fragments.
+///
+/// A candidate fragment is built around each match, and it is scored by the
+/// number of distinct terms it matches, and then by its number of matches,
+/// so passages where the query terms appear together are preferred over the
+/// first match. The best candidates that do not overlap are selected, and
+/// they are returned in the order they appear in the text. If there are no
+/// matches, a single fragment is taken from the start of the text.
+///
+/// # Arguments
+///
+/// * `text`    - The text of the document.
+/// * `matches` - The sorted, non-overlapping byte ranges of the matches.
+/// * `config`  - The snippet policy.
+pub fn snippets(text: &str, matches: &[Range<usize>], config: &SnippetConfig) -> Vec<Snippet> {
+    if config.fragments == 0 {
+        return vec![];
+    }
+    if matches.is_empty() {
+        let window = window(text, &(0..0), config);
+        return vec![Snippet::from_window(text, matches, window)];
+    }
+
+    let mut candidates = matches
+        .iter()
+        .map(|span| {
+            let window = window(text, span, config);
+            let score = score_window(text, matches, &window);
+            (window, score)
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(_, score)| Reverse(*score));
+
+    let mut windows: Vec<Range<usize>> = vec![];
+    for (window, _) in candidates {
+        if windows.len() == config.fragments {
+            break;
+        }
+        let overlaps = windows
+            .iter()
+            .any(|other| window.start < other.end && other.start < window.end);
+        if !overlaps {
+            windows.push(window);
+        }
+    }
+    windows.sort_by_key(|window| window.start);
+    windows
+        .into_iter()
+        .map(|window| Snippet::from_window(text, matches, window))
+        .collect()
+}
+
+/// Scores a candidate fragment by the number of distinct terms it matches,
+/// ignoring case, and its number of matches.
+fn score_window(text: &str, matches: &[Range<usize>], window: &Range<usize>) -> (usize, usize) {
+    let window_matches = matches
+        .iter()
+        .filter(|span| span.start >= window.start && span.end <= window.end)
+        .map(|span| text[span.clone()].to_lowercase())
+        .collect::<Vec<_>>();
+    let n_distinct = window_matches.iter().collect::<HashSet<_>>().len();
+    (n_distinct, window_matches.len())
+}
+
+/// Joins the fragments of a snippet with the separator, highlighting their
+/// matches. The separator is also added before the first fragment and after
+/// the last one, if they do not reach the start and the end of the text.
+///
+/// # Arguments
+///
+/// * `snippets`  - The fragments of the snippet.
+/// * `text_len`  - The length of the text of the document in bytes.
+/// * `separator` - The separator of the fragments.
+pub fn join_highlighted(
+    snippets: &[Snippet],
+    text_len: usize,
+    separator: &str,
+    marker: Marker,
+) -> String {
+    let mut joined = String::new();
+    if snippets.first().is_some_and(|s| s.offsets.byte_start > 0) {
+        joined.push_str(separator.trim_start());
+    }
+    let fragments = snippets
+        .iter()
+        .map(|snippet| snippet.highlight(marker))
+        .collect::<Vec<_>>();
+    joined.push_str(&fragments.join(separator));
+    if snippets
+        .last()
+        .is_some_and(|s| s.offsets.byte_end < text_len)
+    {
+        joined.push_str(separator.trim_end());
+    }
+    joined
+}
+
+/// Finds the byte range of the window of a fragment around a match.
+///
+/// The match is placed in the first third of the window, so there is some
+/// context before it.
+fn window(text: &str, span: &Range<usize>, config: &SnippetConfig) -> Range<usize> {
+    let length = config.length;
+    let mut end = (span.start.saturating_sub(length / 3) + length).min(text.len());
+    let mut start = end.saturating_sub(length);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    // Cut partial sentences or words at the start of the window, without
+    // cutting into the match.
+    if start > 0 && start < span.start {
+        let before = &text[start..span.start];
+        let sentence_start = match config.boundary {
+            SnippetBoundary::Sentence => before.rfind(is_sentence_end).map(|i| i + 1),
+            SnippetBoundary::Window => None,
+        };
+        if let Some(i) = sentence_start.or_else(|| before.find(char::is_whitespace)) {
+            start += i;
+        }
+    }
+    if config.boundary == SnippetBoundary::Sentence {
+        end = (start + length).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+    }
+
+    // Cut partial sentences or words at the end of the window.
+    let match_end = span.end.clamp(start, end);
+    if end < text.len() {
+        let after = &text[match_end..end];
+        let sentence_end = match config.boundary {
+            SnippetBoundary::Sentence => after.rfind(is_sentence_end).map(|i| i + 1),
+            SnippetBoundary::Window => None,
+        };
+        if let Some(i) = sentence_end.or_else(|| after.rfind(char::is_whitespace)) {
+            end = match_end + i;
+        }
+    }
+
+    let window = &text[start..end];
+    let start = start + (window.len() - window.trim_start().len());
+    let end = end - (window.len() - window.trim_end().len());
+    start..end.max(start)
+}
+
+/// Checks if a character ends a sentence.
+fn is_sentence_end(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(length: usize, fragments: usize, boundary: SnippetBoundary) -> SnippetConfig {
+        SnippetConfig {
+            length,
+            fragments,
+            boundary,
+            ..SnippetConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_snippet() {
+        let text = "The quick brown fox jumps over the lazy dog near the river bank.";
+        let config = config(24, 1, SnippetBoundary::Window);
+        let snippets = snippets(text, &[35..39, 53..58], &config);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].text, "the lazy dog near");
+        assert_eq!(snippets[0].offsets.byte_start, 31);
+        assert_eq!(snippets[0].matches.len(), 1);
+        assert_eq!(snippets[0].matches[0].byte_start, 4);
+        assert_eq!(snippets[0].matches[0].char_end, 8);
+    }
+
+    #[test]
+    fn test_snippet_without_matches() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let config = config(20, 1, SnippetBoundary::Window);
+        let snippets = snippets(text, &[], &config);
+        assert_eq!(snippets[0].text, "The quick brown fox");
+        assert_eq!(snippets[0].offsets.byte_start, 0);
+        assert!(snippets[0].matches.is_empty());
+    }
+
+    #[test]
+    fn test_multiple_fragments() {
+        let text = "The quick brown fox jumps over the lazy dog near the river bank.";
+        let config = config(24, 2, SnippetBoundary::Window);
+        let snippets = snippets(text, &[4..9, 53..58], &config);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].text, "The quick brown fox");
+        assert_eq!(snippets[1].text, "near the river bank.");
+        let joined = join_highlighted(&snippets, text.len(), " ... ", Marker::Plain);
+        assert_eq!(joined.matches("...").count(), 1);
+        assert_eq!(snippets[0].highlighted, "The <mark>quick</mark> brown fox");
+    }
+
+    #[test]
+    fn test_best_passage() {
+        let text = "A budget was mentioned. Many unrelated words follow here. \
+                    The quarterly budget was reviewed.";
+        let config = config(40, 1, SnippetBoundary::Sentence);
+        let snippets = snippets(text, &[2..8, 62..71, 72..78], &config);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].text, "The quarterly budget was reviewed.");
+        assert_eq!(snippets[0].matches.len(), 2);
+    }
+
+    #[test]
+    fn test_sentence_boundary() {
+        let text = "Intro text here. The budget was reviewed today. It went well, mostly.";
+        let config = config(40, 1, SnippetBoundary::Sentence);
+        let snippets = snippets(text, &[21..27, 32..40], &config);
+        assert_eq!(snippets[0].text, "The budget was reviewed today.");
+    }
+}
@@ -0,0 +1,201 @@
+use std::cmp::Reverse;
+
+use index::inverted::Index;
+use tokenize::{Token, CASED_PREFIX, TITLE_PREFIX};
+
+use crate::analyzer::AnalyzerConfig;
+use crate::config::{BODY_FIELD, SPELLING_MAX_DISTANCE, SPELLING_MAX_DOCS, TITLE_FIELD};
+
+/// A correction of a word of a query, by the term of the index that is
+/// closest to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Correction {
+    /// The word of the query, as it was written.
+    pub word: String,
+    /// The term of the index, as it was indexed, so it may be stemmed.
+    pub term: Token,
+}
+
+/// A suggestion for a query with misspelled words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    /// The corrections of the misspelled words, in the order of the query.
+    pub corrections: Vec<Correction>,
+    /// The query with the misspelled words replaced by their corrections.
+    pub query: String,
+}
+
+/// Suggests corrections for the words of a query that match at most
+/// `SPELLING_MAX_DOCS` documents, in their text or their title.
+///
+/// The correction of a word is the term of the index with the smallest edit
+/// distance to the term of the word, that matches more documents than it.
+/// Terms that are equally close are ranked by the number of documents that
+/// they match. Words of up to 4 characters are only corrected by terms at an
+/// edit distance of 1, and longer ones by terms at `SPELLING_MAX_DISTANCE`.
+/// The vocabulary of the index is only read if a word needs a correction.
+///
+/// Returns `None` if no word of the query needs a correction, or no term is
+/// close enough to the words that need one.
+pub fn suggest(index: &dyn Index, analyzer: &AnalyzerConfig, query: &str) -> Option<Suggestion> {
+    let mut tokenizer = analyzer.tokenizer();
+    let mut vocabulary = None;
+    let mut corrections = vec![];
+    let mut words = vec![];
+    for word in query.split_whitespace() {
+        let (field, text) = [TITLE_FIELD, BODY_FIELD]
+            .into_iter()
+            .find_map(|field| Some((field, word.strip_prefix(field)?)))
+            .unwrap_or(("", word));
+        let title_matches = tokenizer
+            .tokenize_title(text)
+            .iter()
+            .map(|token| index.n_docs_containing(token))
+            .max()
+            .unwrap_or(0);
+        let term = match tokenizer.tokenize(text).as_slice() {
+            [token] if index.n_docs_containing(token) + title_matches <= SPELLING_MAX_DOCS => {
+                let vocabulary = vocabulary.get_or_insert_with(|| index.terms());
+                closest_term(index, vocabulary, token)
+            }
+            _ => None,
+        };
+        match term {
+            Some(term) => {
+                words.push(format!("{field}{term}"));
+                let word = word.to_string();
+                corrections.push(Correction { word, term });
+            }
+            None => words.push(word.to_string()),
+        }
+    }
+    let query = words.join(" ");
+    (!corrections.is_empty()).then_some(Suggestion { corrections, query })
+}
+
+/// Finds the term of the vocabulary that is the closest to a token, and
+/// matches more documents than it.
+fn closest_term(index: &dyn Index, vocabulary: &[Token], token: &Token) -> Option<Token> {
+    let len = token.chars().count();
+    let max_distance = max_distance(len);
+    let n_docs = index.n_docs_containing(token);
+    vocabulary
+        .iter()
+        .filter(|term| !term.starts_with([TITLE_PREFIX, CASED_PREFIX]))
+        .filter(|term| term.chars().count().abs_diff(len) <= max_distance)
+        .filter_map(|term| {
+            let distance = edit_distance(token, term);
+            (0 < distance && distance <= max_distance).then_some((term, distance))
+        })
+        .map(|(term, distance)| (term, distance, index.n_docs_containing(term)))
+        .filter(|(_, _, term_docs)| *term_docs > n_docs)
+        .min_by_key(|(term, distance, term_docs)| (*distance, Reverse(*term_docs), *term))
+        .map(|(term, _, _)| term.clone())
+}
+
+/// Finds the terms of the vocabulary that are close to a token, other than
+/// the token itself, for fuzzy matching. Terms of the title only match the
+/// terms of the title, and the terms of the text the terms of the text. The
+/// terms of the case-sensitive field are never matched.
+pub fn fuzzy_terms(vocabulary: &[Token], token: &Token) -> Vec<Token> {
+    let (prefix, word) = match token.strip_prefix(TITLE_PREFIX) {
+        Some(word) => (Some(TITLE_PREFIX), word),
+        None => (None, token.as_str()),
+    };
+    let len = word.chars().count();
+    let max_distance = max_distance(len);
+    vocabulary
+        .iter()
+        .filter(|term| !term.starts_with(CASED_PREFIX))
+        .filter_map(|term| match prefix {
+            Some(prefix) => Some((term, term.strip_prefix(prefix)?)),
+            None => (!term.starts_with(TITLE_PREFIX)).then_some((term, term.as_str())),
+        })
+        .filter(|(_, other)| other.chars().count().abs_diff(len) <= max_distance)
+        .filter(|(_, other)| (1..=max_distance).contains(&edit_distance(word, other)))
+        .map(|(term, _)| term.clone())
+        .collect()
+}
+
+/// Returns the largest edit distance of a correction of a word with `len`
+/// characters. Words of up to 4 characters are only corrected by one edit.
+fn max_distance(len: usize) -> usize {
+    if len <= 4 {
+        1
+    } else {
+        SPELLING_MAX_DISTANCE
+    }
+}
+
+/// Calculates the Levenshtein distance between two strings, that is, the
+/// number of characters that have to be inserted, deleted or substituted to
+/// turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use index::doc::freq::DocumentFrequencyIndex;
+    use index::inverted::freq::FrequencyIndex;
+
+    use crate::analyzer::Stemmer;
+
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("budget", "budget"), 0);
+        assert_eq!(edit_distance("budjet", "budget"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("καλό", "καλά"), 1);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let mut index = FrequencyIndex::new();
+        for (doc_id, tokens) in [(0, ["budget", "report"]), (1, ["budget", "meeting"])] {
+            let mut doc_index = DocumentFrequencyIndex::new(doc_id);
+            doc_index.index_tokens(tokens.map(String::from));
+            index.index(doc_index);
+        }
+        let analyzer = AnalyzerConfig {
+            stemmer: Stemmer::None,
+            ..Default::default()
+        };
+
+        let suggestion = suggest(&index, &analyzer, "budjet title:reprt").unwrap();
+        assert_eq!(suggestion.query, "budget title:report");
+        assert_eq!(suggestion.corrections[0].word, "budjet");
+        assert_eq!(suggestion.corrections[1].term, "report");
+        assert_eq!(suggest(&index, &analyzer, "budget"), None);
+        assert_eq!(suggest(&index, &analyzer, "xylophone"), None);
+    }
+
+    #[test]
+    fn test_fuzzy_terms() {
+        let vocabulary = [
+            "budget", "budgets", "bucket", "^budget", "=Budget", "report",
+        ];
+        let vocabulary = vocabulary.map(String::from);
+        let fuzzy = fuzzy_terms(&vocabulary, &"budjet".to_string());
+        assert_eq!(fuzzy, ["budget", "budgets", "bucket"]);
+        assert_eq!(
+            fuzzy_terms(&vocabulary, &"^budjet".to_string()),
+            ["^budget"]
+        );
+        assert!(fuzzy_terms(&vocabulary, &"report".to_string()).is_empty());
+    }
+}
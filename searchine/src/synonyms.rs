@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+
+use tokenize::Token;
+
+use crate::analyzer::AnalyzerConfig;
+use crate::config::{BODY_FIELD, SYNONYMS_FILENAME, TITLE_FIELD};
+
+/// The character that comments of the synonyms file start with.
+const COMMENT_PREFIX: char = '#';
+
+/// The groups of synonyms of a repository, read from its synonyms file,
+/// which expand the words of queries, so that documents that use another
+/// word for the same thing match them too.
+///
+/// Each line of the synonyms file is a group of words or phrases that mean
+/// the same thing, separated by commas, like `car, automobile, vehicle`.
+/// Empty lines and lines that start with `#` are skipped. Only the queries
+/// are expanded, so the synonyms can be changed without indexing again.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Thesaurus {
+    /// The synonyms of each term of the groups, by the term as it is
+    /// analyzed, so that they match every form of the word.
+    synonyms: HashMap<Token, Vec<String>>,
+}
+
+impl Thesaurus {
+    /// Loads the synonyms of a repository. A repository without a synonyms
+    /// file has no synonyms.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the synonyms file exists, but it cannot be read.
+    pub fn load(repo_dir: impl AsRef<Path>, analyzer: &AnalyzerConfig) -> anyhow::Result<Self> {
+        let path = repo_dir.as_ref().join(SYNONYMS_FILENAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .context(format!("Failed to read synonyms file: {}", path.display()))?;
+        Ok(Self::parse(&contents, analyzer))
+    }
+
+    /// Parses the groups of synonyms of the contents of a synonyms file.
+    ///
+    /// Only the entries that are a single term once they are analyzed can be
+    /// expanded, but every entry of a group, phrases too, is a synonym of the
+    /// others.
+    pub fn parse(contents: &str, analyzer: &AnalyzerConfig) -> Self {
+        let mut tokenizer = analyzer.tokenizer();
+        let mut synonyms = HashMap::<Token, Vec<String>>::new();
+        let groups = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(COMMENT_PREFIX));
+        for group in groups {
+            let entries = group
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .collect::<Vec<_>>();
+            for entry in &entries {
+                let Ok([term]) = <[Token; 1]>::try_from(tokenizer.tokenize(entry)) else {
+                    continue;
+                };
+                let others = synonyms.entry(term).or_default();
+                for other in &entries {
+                    if other != entry && !others.iter().any(|synonym| synonym == other) {
+                        others.push(other.to_string());
+                    }
+                }
+            }
+        }
+        Self { synonyms }
+    }
+
+    /// Checks if there are no synonyms.
+    pub fn is_empty(&self) -> bool {
+        self.synonyms.is_empty()
+    }
+
+    /// Expands the words of a query with their synonyms, which are added
+    /// right after them, in the field of the word, if it has one. The words
+    /// of a synonym that is a phrase are added as separate words.
+    pub fn expand(&self, query: &str, analyzer: &AnalyzerConfig) -> String {
+        if self.is_empty() {
+            return query.to_string();
+        }
+        let mut tokenizer = analyzer.tokenizer();
+        let mut words = vec![];
+        for word in query.split_whitespace() {
+            words.push(word.to_string());
+            let (field, text) = [TITLE_FIELD, BODY_FIELD]
+                .into_iter()
+                .find_map(|field| Some((field, word.strip_prefix(field)?)))
+                .unwrap_or(("", word));
+            let synonyms = match tokenizer.tokenize(text).as_slice() {
+                [term] => self.synonyms.get(term),
+                _ => None,
+            };
+            for synonym in synonyms.into_iter().flatten() {
+                words.extend(synonym.split_whitespace().map(|word| format!("{field}{word}")));
+            }
+        }
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let analyzer = AnalyzerConfig::default();
+        let contents = "# Vehicles\n\ncar, automobile, motor vehicle\nbudget, budget,";
+        let thesaurus = Thesaurus::parse(contents, &analyzer);
+
+        assert_eq!(
+            thesaurus.expand("cars budget", &analyzer),
+            "cars automobile motor vehicle budget"
+        );
+        assert_eq!(
+            thesaurus.expand("title:automobiles", &analyzer),
+            "title:automobiles title:car title:motor title:vehicle"
+        );
+        assert_eq!(thesaurus.expand("vehicle", &analyzer), "vehicle");
+        assert!(Thesaurus::default().is_empty());
+    }
+}
@@ -1,4 +1,6 @@
-pub use tokenizer::Tokenizer;
+pub use pre::{Compounds, Segmentation};
+pub use rust_stemmers::Algorithm;
+pub use tokenizer::{Tokenizer, CASED_PREFIX, TITLE_PREFIX};
 
 mod encode;
 mod pre;
@@ -1,13 +1,59 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The way text is segmented into words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Segmentation {
+    /// The words are found by the word boundaries of Unicode (UAX #29), so
+    /// words with apostrophes, like `don't`, and numbers, like `3.14`, are
+    /// kept whole, and ideographs are separate words. Compounds of words
+    /// joined by hyphens or underscores are kept as [`Compounds`] sets.
+    #[default]
+    Unicode,
+    /// The words are the runs of alphanumeric characters, split at every
+    /// other character.
+    Simple,
+}
+
+/// The way compounds of words that are joined by hyphens or underscores,
+/// like `state-of-the-art`, are separated, when text is segmented by the
+/// word boundaries of Unicode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compounds {
+    /// The compound is kept whole, followed by its words, so both the
+    /// compound and its words match it.
+    #[default]
+    Both,
+    /// The compound is kept whole, so only the compound matches it.
+    Whole,
+    /// The compound is split into its words.
+    Split,
+}
+
 /// A pre-tokenizer.
 ///
 /// This struct is used specifically to separate text into lowercase
 /// alphanumeric terms.
-pub(crate) struct PreTokenizer;
+pub(crate) struct PreTokenizer {
+    pub(crate) segmentation: Segmentation,
+    pub(crate) compounds: Compounds,
+    /// Whether the possessive `'s` of words is stripped, when text is
+    /// segmented by the word boundaries of Unicode.
+    pub(crate) strip_possessives: bool,
+}
 
 impl PreTokenizer {
-    /// Creates a new pre-tokenizer
-    pub(crate) fn new() -> Self {
-        Self
+    /// Creates a new pre-tokenizer, with the segmentation of text into words.
+    pub(crate) fn new(segmentation: Segmentation) -> Self {
+        Self {
+            segmentation,
+            compounds: Compounds::default(),
+            strip_possessives: false,
+        }
     }
 
     /// Separates the input text into a vector of lowercase alphanumeric terms.
@@ -21,12 +67,179 @@ impl PreTokenizer {
     /// A `Vec<String>` containing the lowercase alphanumeric terms extracted
     /// from the input text.
     pub(crate) fn separate_text(&self, text: impl AsRef<str>) -> Vec<String> {
+        self.separate_text_spans(text)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    /// Separates the input text into a vector of lowercase alphanumeric terms,
+    /// along with the byte range of each term in the input text.
+    pub(crate) fn separate_text_spans(&self, text: impl AsRef<str>) -> Vec<(String, Range<usize>)> {
         let text = text.as_ref();
-        text.split(|c: char| !c.is_alphanumeric())
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_lowercase())
+        match self.segmentation {
+            Segmentation::Unicode => self.separate_words(text),
+            Segmentation::Simple => text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|s| !s.is_empty())
+                .map(|s| (s.to_lowercase(), span(text, s)))
+                .collect(),
+        }
+    }
+
+    /// Separates text into lowercase words by the word boundaries of Unicode,
+    /// along with the byte range of each word in the text.
+    ///
+    /// Words that are joined by hyphens, like `state-of-the-art`, or by
+    /// underscores, are kept as a whole compound, or split into their words,
+    /// or both, with the compound followed by its words. Typographic
+    /// apostrophes are replaced with ASCII ones, so `don’t` and `don't` are
+    /// the same word.
+    fn separate_words(&self, text: &str) -> Vec<(String, Range<usize>)> {
+        let segments = text.split_word_bounds().collect::<Vec<_>>();
+        let mut terms = vec![];
+        let mut i = 0;
+        while i < segments.len() {
+            if !is_word(segments[i]) {
+                i += 1;
+                continue;
+            }
+            let first = i;
+            while i + 2 < segments.len() && is_hyphen(segments[i + 1]) && is_word(segments[i + 2]) {
+                i += 2;
+            }
+            let parts = segments[first..=i]
+                .iter()
+                .step_by(2)
+                .map(|word| match self.strip_possessives {
+                    true => strip_possessive(word),
+                    false => word,
+                })
+                .flat_map(|word| word.split('_'))
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>();
+            if parts.len() > 1 && self.compounds != Compounds::Split {
+                let start = span(text, parts[0]).start;
+                let end = span(text, parts[parts.len() - 1]).end;
+                terms.push((normalize_word(&text[start..end]), start..end));
+            }
+            if parts.len() == 1 || self.compounds != Compounds::Whole {
+                terms.extend(
+                    parts
+                        .into_iter()
+                        .map(|s| (normalize_word(s), span(text, s))),
+                );
+            }
+            i += 1;
+        }
+        terms
+    }
+
+    /// Separates source code into a vector of lowercase identifier terms.
+    ///
+    /// Identifiers are made of alphanumeric characters and underscores. Each
+    /// identifier is split into sub-terms at underscores (`snake_case`) and
+    /// at case changes (`camelCase`, `PascalCase`), while the identifier is
+    /// also kept as a whole, so both `getUserById` and `user` match it.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - An input source code that can be referenced as a string slice.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<String>` containing, for each identifier, the whole identifier
+    /// followed by its sub-terms, all in lowercase. Identifiers that consist
+    /// of a single term appear only once.
+    pub(crate) fn separate_code(&self, text: impl AsRef<str>) -> Vec<String> {
+        self.separate_code_spans(text)
+            .into_iter()
+            .map(|(term, _)| term)
             .collect()
     }
+
+    /// Separates source code into a vector of lowercase identifier terms,
+    /// along with the byte range of each term in the input source code.
+    pub(crate) fn separate_code_spans(&self, text: impl AsRef<str>) -> Vec<(String, Range<usize>)> {
+        let text = text.as_ref();
+        let mut terms = vec![];
+        let identifiers = text
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|s| !s.is_empty());
+        for identifier in identifiers {
+            let parts = identifier
+                .split('_')
+                .filter(|s| !s.is_empty())
+                .flat_map(split_camel_case)
+                .collect::<Vec<_>>();
+            if parts.len() > 1 {
+                let identifier = identifier.trim_matches('_');
+                terms.push((identifier.to_lowercase(), span(text, identifier)));
+            }
+            terms.extend(parts.into_iter().map(|s| (s.to_lowercase(), span(text, s))));
+        }
+        terms
+    }
+}
+
+/// Checks if a segment of text between word boundaries is a word, rather
+/// than whitespace or punctuation.
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+/// Checks if a segment of text between word boundaries is a hyphen.
+fn is_hyphen(segment: &str) -> bool {
+    matches!(segment, "-" | "\u{2010}")
+}
+
+/// Strips the possessive `'s` from the end of a word, with an ASCII or a
+/// typographic apostrophe.
+fn strip_possessive(word: &str) -> &str {
+    ["'s", "'S", "\u{2019}s", "\u{2019}S"]
+        .iter()
+        .find_map(|suffix| word.strip_suffix(suffix))
+        .filter(|word| !word.is_empty())
+        .unwrap_or(word)
+}
+
+/// Turns a word to lowercase, with its typographic apostrophes replaced
+/// with ASCII ones.
+fn normalize_word(word: &str) -> String {
+    word.replace('\u{2019}', "'").to_lowercase()
+}
+
+/// Returns the byte range of a slice in the text it was sliced from.
+fn span(text: &str, slice: &str) -> Range<usize> {
+    let start = slice.as_ptr() as usize - text.as_ptr() as usize;
+    start..start + slice.len()
+}
+
+/// Splits a word at its case changes.
+///
+/// A word is split before an uppercase character that follows a lowercase
+/// character or a digit (`getUser` -> `get`, `User`), and before the last
+/// uppercase character of an acronym that is followed by a lowercase one
+/// (`HTTPServer` -> `HTTP`, `Server`).
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let chars = word.char_indices().collect::<Vec<_>>();
+    let mut parts = vec![];
+    let mut start = 0;
+    for i in 1..chars.len() {
+        let (idx, cur) = chars[i];
+        let prev = chars[i - 1].1;
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let is_boundary = cur.is_uppercase()
+            && (prev.is_lowercase()
+                || prev.is_numeric()
+                || (prev.is_uppercase() && next.is_some_and(|c| c.is_lowercase())));
+        if is_boundary {
+            parts.push(&word[start..idx]);
+            start = idx;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
 }
 
 #[cfg(test)]
@@ -35,9 +248,102 @@ mod tests {
 
     #[test]
     fn test_pre_tokenizer() {
-        let splitter = PreTokenizer::new();
+        let splitter = PreTokenizer::new(Segmentation::Simple);
         let text = "I want it, i got it.";
         let words = splitter.separate_text(text);
         assert_eq!(words, vec!["i", "want", "it", "i", "got", "it"]);
     }
+
+    #[test]
+    fn test_pre_tokenizer_spans() {
+        let splitter = PreTokenizer::new(Segmentation::default());
+        let text = "Café, au lait!";
+        let spans = splitter.separate_text_spans(text);
+        assert_eq!(
+            spans,
+            vec![
+                ("café".to_string(), 0..5),
+                ("au".to_string(), 7..9),
+                ("lait".to_string(), 10..14),
+            ]
+        );
+        let spans = splitter.separate_code_spans("let user_id");
+        assert_eq!(spans[1], ("user_id".to_string(), 4..11));
+        assert_eq!(spans[3], ("id".to_string(), 9..11));
+    }
+
+    #[test]
+    fn test_unicode_segmentation() {
+        let splitter = PreTokenizer::new(Segmentation::Unicode);
+        let words = splitter.separate_text("Don’t use a well-known user_id, pi is 3.14.");
+        assert_eq!(
+            words,
+            vec![
+                "don't",
+                "use",
+                "a",
+                "well-known",
+                "well",
+                "known",
+                "user_id",
+                "user",
+                "id",
+                "pi",
+                "is",
+                "3.14",
+            ]
+        );
+        let words = splitter.separate_text("Привет, мир! 東京タワー");
+        assert_eq!(words, vec!["привет", "мир", "東", "京", "タワー"]);
+
+        let spans = splitter.separate_text_spans("a state-of-the-art -- b");
+        assert_eq!(spans[1], ("state-of-the-art".to_string(), 2..18));
+        assert_eq!(spans[5], ("art".to_string(), 15..18));
+        assert_eq!(spans[6], ("b".to_string(), 22..23));
+    }
+
+    #[test]
+    fn test_compounds_and_possessives() {
+        let text = "The state-of-the-art parser’s output";
+        let mut splitter = PreTokenizer::new(Segmentation::Unicode);
+        splitter.compounds = Compounds::Whole;
+        let words = splitter.separate_text(text);
+        assert_eq!(words, vec!["the", "state-of-the-art", "parser's", "output"]);
+
+        splitter.compounds = Compounds::Split;
+        splitter.strip_possessives = true;
+        let spans = splitter.separate_text_spans(text);
+        let words = spans.iter().map(|(word, _)| word).collect::<Vec<_>>();
+        assert_eq!(
+            words,
+            vec!["the", "state", "of", "the", "art", "parser", "output"]
+        );
+        assert_eq!(spans[5].1, 21..27);
+    }
+
+    #[test]
+    fn test_code_pre_tokenizer() {
+        let splitter = PreTokenizer::new(Segmentation::default());
+        let code = "fn parse_config_file() { getUserById(HTTPServer::new); }";
+        let words = splitter.separate_code(code);
+        assert_eq!(
+            words,
+            vec![
+                "fn",
+                "parse_config_file",
+                "parse",
+                "config",
+                "file",
+                "getuserbyid",
+                "get",
+                "user",
+                "by",
+                "id",
+                "httpserver",
+                "http",
+                "server",
+                "new",
+            ]
+        );
+    }
 }
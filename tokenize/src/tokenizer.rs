@@ -1,26 +1,83 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
 use rust_stemmers::{Algorithm, Stemmer};
 
-use crate::pre::PreTokenizer;
+use crate::pre::{Compounds, PreTokenizer, Segmentation};
 use crate::Token;
 
-/// A tokenizer that processes input text into tokens, and stems them.
+/// The prefix of the terms of the case-sensitive field, where the identifiers
+/// of source code keep their original case. The terms of the case-folded
+/// field are alphanumeric, so the terms of the two fields never collide in
+/// the same index.
+pub const CASED_PREFIX: char = '=';
+
+/// The prefix of the terms of the title field, where the names of the
+/// documents are indexed apart from their text, so that matches in the name
+/// of a document can be told apart and weighted differently.
+pub const TITLE_PREFIX: char = '^';
+
+/// A tokenizer that processes input text into tokens, drops the stopwords,
+/// and stems them.
 pub struct Tokenizer {
     pre_tokenizer: PreTokenizer,
-    stemmer: Stemmer,
+    stemmer: Option<Stemmer>,
+    stopwords: HashSet<Token>,
+    /// Whether the terms that are numbers, like `42` or `3.14`, are dropped.
+    drop_numbers: bool,
 }
 
 impl Default for Tokenizer {
-    /// Creates a new builder with a default pre-tokenizer and stemmer,
-    /// and no encoder.
+    /// Creates a new builder with a default pre-tokenizer and English
+    /// stemmer, no stopwords, and no encoder.
     fn default() -> Self {
-        Self {
-            pre_tokenizer: PreTokenizer::new(),
-            stemmer: Stemmer::create(Algorithm::English),
-        }
+        Self::new(Some(Algorithm::English), HashSet::new())
     }
 }
 
 impl Tokenizer {
+    /// Creates a new tokenizer with the stemmer of a language, or no stemmer,
+    /// and a set of stopwords. The stopwords are lowercase, and they are
+    /// dropped before the terms are stemmed.
+    pub fn new(algorithm: Option<Algorithm>, stopwords: HashSet<Token>) -> Self {
+        Self {
+            pre_tokenizer: PreTokenizer::new(Segmentation::default()),
+            stemmer: algorithm.map(Stemmer::create),
+            stopwords,
+            drop_numbers: false,
+        }
+    }
+
+    /// Sets the segmentation of text into words, which is by the word
+    /// boundaries of Unicode by default. Source code is always separated
+    /// into its identifiers.
+    pub fn with_segmentation(mut self, segmentation: Segmentation) -> Self {
+        self.pre_tokenizer = PreTokenizer::new(segmentation);
+        self
+    }
+
+    /// Sets the way compounds of words that are joined by hyphens or
+    /// underscores are separated, when text is segmented by the word
+    /// boundaries of Unicode. They are kept whole and split by default.
+    pub fn with_compounds(mut self, compounds: Compounds) -> Self {
+        self.pre_tokenizer.compounds = compounds;
+        self
+    }
+
+    /// Sets whether the possessive `'s` of words is stripped, when text is
+    /// segmented by the word boundaries of Unicode, so `parser's` matches
+    /// `parser` without a stemmer.
+    pub fn with_stripped_possessives(mut self, strip_possessives: bool) -> Self {
+        self.pre_tokenizer.strip_possessives = strip_possessives;
+        self
+    }
+
+    /// Sets whether the terms that are numbers are dropped, like stopwords.
+    pub fn with_dropped_numbers(mut self, drop_numbers: bool) -> Self {
+        self.drop_numbers = drop_numbers;
+        self
+    }
+
     /// Tokenizes the input text.
     ///
     /// The input text is separated into lowercase alphanumeric terms,
@@ -29,12 +86,145 @@ impl Tokenizer {
         let tokens = self.pre_tokenizer.separate_text(text);
         tokens
             .iter()
-            .map(|token| {
-                let stem = self.stemmer.stem(token).to_string();
-                stem
-            })
+            .filter(|token| !self.is_dropped(token))
+            .map(|token| self.stem(token))
+            .collect::<Vec<_>>()
+    }
+
+    /// Tokenizes the input source code.
+    ///
+    /// The input is separated into lowercase identifiers, which are split
+    /// into their `snake_case` and `camelCase` sub-terms while also kept as
+    /// a whole. All the terms are stemmed, and returned as a vector of strings.
+    pub fn tokenize_code(&mut self, text: impl AsRef<str>) -> Vec<Token> {
+        let tokens = self.pre_tokenizer.separate_code(text);
+        tokens
+            .iter()
+            .filter(|token| !self.is_dropped(token))
+            .map(|token| self.stem(token))
             .collect::<Vec<_>>()
     }
+
+    /// Tokenizes the input text, like [`Tokenizer::tokenize`], and returns
+    /// each token along with the byte range of the term it was derived from.
+    /// This is used to locate the tokens in the original text.
+    pub fn tokenize_spans(&mut self, text: impl AsRef<str>) -> Vec<(Token, Range<usize>)> {
+        let spans = self.pre_tokenizer.separate_text_spans(text);
+        self.stem_spans(spans)
+    }
+
+    /// Tokenizes the input source code, like [`Tokenizer::tokenize_code`], and
+    /// returns each token along with the byte range of the term it was
+    /// derived from.
+    pub fn tokenize_code_spans(&mut self, text: impl AsRef<str>) -> Vec<(Token, Range<usize>)> {
+        let spans = self.pre_tokenizer.separate_code_spans(text);
+        self.stem_spans(spans)
+    }
+
+    /// Tokenizes the input text into the terms of the case-sensitive field.
+    ///
+    /// The text is separated into alphanumeric terms like in
+    /// [`Tokenizer::tokenize`], but the terms keep their original case, they
+    /// are not stemmed, and they are prefixed with [`CASED_PREFIX`].
+    pub fn tokenize_cased(&mut self, text: impl AsRef<str>) -> Vec<Token> {
+        self.tokenize_cased_spans(text)
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    /// Tokenizes the input text into the terms of the case-sensitive field,
+    /// like [`Tokenizer::tokenize_cased`], and returns each token along with
+    /// the byte range of the term it was derived from.
+    pub fn tokenize_cased_spans(&mut self, text: impl AsRef<str>) -> Vec<(Token, Range<usize>)> {
+        let text = text.as_ref();
+        let spans = self.pre_tokenizer.separate_text_spans(text);
+        self.case_spans(text, spans)
+    }
+
+    /// Tokenizes the input source code into the terms of the case-sensitive
+    /// field.
+    ///
+    /// The identifiers are split into their sub-terms like in
+    /// [`Tokenizer::tokenize_code`], but all the terms keep their original
+    /// case, they are not stemmed, and they are prefixed with [`CASED_PREFIX`].
+    pub fn tokenize_code_cased(&mut self, text: impl AsRef<str>) -> Vec<Token> {
+        self.tokenize_code_cased_spans(text)
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    /// Tokenizes the input source code into the terms of the case-sensitive
+    /// field, like [`Tokenizer::tokenize_code_cased`], and returns each token
+    /// along with the byte range of the term it was derived from.
+    pub fn tokenize_code_cased_spans(
+        &mut self,
+        text: impl AsRef<str>,
+    ) -> Vec<(Token, Range<usize>)> {
+        let text = text.as_ref();
+        let spans = self.pre_tokenizer.separate_code_spans(text);
+        self.case_spans(text, spans)
+    }
+
+    /// Tokenizes the title of a document, like the name of its file, into the
+    /// terms of the title field.
+    ///
+    /// The title is split like source code, so that names in `snake_case`
+    /// and `camelCase` are split into their words, and the terms are
+    /// prefixed with [`TITLE_PREFIX`].
+    pub fn tokenize_title(&mut self, title: impl AsRef<str>) -> Vec<Token> {
+        self.tokenize_code(title)
+            .into_iter()
+            .map(|token| format!("{TITLE_PREFIX}{token}"))
+            .collect()
+    }
+
+    /// Replaces the lowercase terms of a vector of terms and their byte
+    /// ranges with the terms of the case-sensitive field, as they are written
+    /// in the text, dropping the stopwords.
+    fn case_spans(
+        &self,
+        text: &str,
+        spans: Vec<(String, Range<usize>)>,
+    ) -> Vec<(Token, Range<usize>)> {
+        spans
+            .into_iter()
+            .filter(|(term, _)| !self.is_dropped(term))
+            .map(|(_, span)| (format!("{CASED_PREFIX}{}", &text[span.clone()]), span))
+            .collect()
+    }
+
+    /// Stems the terms of a vector of terms and their byte ranges.
+    fn stem_spans(&self, spans: Vec<(String, Range<usize>)>) -> Vec<(Token, Range<usize>)> {
+        spans
+            .into_iter()
+            .filter(|(term, _)| !self.is_dropped(term))
+            .map(|(term, span)| (self.stem(&term), span))
+            .collect()
+    }
+
+    /// Checks if a lowercase term is dropped, as a stopword or as a number.
+    fn is_dropped(&self, term: &str) -> bool {
+        self.stopwords.contains(term) || (self.drop_numbers && is_number(term))
+    }
+
+    /// Stems a term, or keeps it as it is if there is no stemmer.
+    fn stem(&self, term: &str) -> Token {
+        match &self.stemmer {
+            Some(stemmer) => stemmer.stem(term).to_string(),
+            None => term.to_string(),
+        }
+    }
+}
+
+/// Checks if a term is a number, made of digits and the separators of their
+/// groups and decimals.
+fn is_number(term: &str) -> bool {
+    term.chars().any(char::is_numeric)
+        && term
+            .chars()
+            .all(|c| c.is_numeric() || matches!(c, '.' | ','))
 }
 
 #[cfg(test)]
@@ -50,6 +240,70 @@ mod tests {
         assert_eq!(stemmer.stem("Intensely"), "Intens");
     }
 
+    #[test]
+    fn test_cased_tokenization() {
+        let mut tokenizer = Tokenizer::default();
+        let tokens = tokenizer.tokenize_code_cased("let server = HTTPServer::new();");
+        assert_eq!(
+            tokens,
+            ["=let", "=server", "=HTTPServer", "=HTTP", "=Server", "=new"].map(String::from)
+        );
+
+        let tokens = tokenizer.tokenize_cased("The FooBar of foobar.");
+        assert_eq!(tokens, ["=The", "=FooBar", "=of", "=foobar"].map(String::from));
+    }
+
+    #[test]
+    fn test_title_tokenization() {
+        let mut tokenizer = Tokenizer::new(None, HashSet::new());
+        let tokens = tokenizer.tokenize_title("quarterly_report");
+        assert_eq!(
+            tokens,
+            ["^quarterly_report", "^quarterly", "^report"].map(String::from)
+        );
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let stopwords = HashSet::from(["the".to_string(), "of".to_string()]);
+        let mut tokenizer = Tokenizer::new(None, stopwords);
+        let tokens = tokenizer.tokenize("The Lord of the Rings");
+        assert_eq!(tokens, ["lord", "rings"].map(String::from));
+    }
+
+    #[test]
+    fn test_segmentation() {
+        let text = "The user's well-known café";
+        let mut tokenizer = Tokenizer::default();
+        let tokens = tokenizer.tokenize(text);
+        assert_eq!(
+            tokens,
+            ["the", "user", "well-known", "well", "known", "café"].map(String::from)
+        );
+
+        let mut tokenizer = Tokenizer::default().with_segmentation(Segmentation::Simple);
+        let tokens = tokenizer.tokenize(text);
+        assert_eq!(
+            tokens,
+            ["the", "user", "s", "well", "known", "café"].map(String::from)
+        );
+    }
+
+    #[test]
+    fn test_token_filters() {
+        let text = "Released 3.14 in 2024, the parser's v2 build";
+        let mut tokenizer = Tokenizer::new(None, HashSet::new())
+            .with_stripped_possessives(true)
+            .with_dropped_numbers(true);
+        let tokens = tokenizer.tokenize(text);
+        assert_eq!(
+            tokens,
+            ["released", "in", "the", "parser", "v2", "build"].map(String::from)
+        );
+        let tokens = tokenizer.tokenize_code("let x = 42;");
+        assert_eq!(tokens, ["let", "x"].map(String::from));
+    }
+
     #[test]
     fn test_tokenization() {
         let text = "I want it. I got it.".to_string();